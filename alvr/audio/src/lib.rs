@@ -12,7 +12,8 @@ use alvr_common::{
     ConnectionError, ToAny,
 };
 use alvr_session::{
-    AudioBufferingConfig, CustomAudioDeviceConfig, LinuxAudioBackend, MicrophoneDevicesConfig,
+    AudioBufferingConfig, CustomAudioDeviceConfig, GameAudioMode, LinuxAudioBackend,
+    MicrophoneDevicesConfig,
 };
 use alvr_sockets::{StreamReceiver, StreamSender};
 use cpal::{
@@ -20,6 +21,7 @@ use cpal::{
     BufferSize, Device, Host, Sample, SampleFormat, StreamConfig,
 };
 use rodio::{OutputStream, Source};
+use serde::{Deserialize, Serialize};
 use std::{
     collections::{HashMap, VecDeque},
     sync::Arc,
@@ -194,6 +196,15 @@ impl AudioDevice {
 
         Ok(config.sample_rate().0)
     }
+
+    pub fn input_channels_count(&self) -> Result<u16> {
+        let config = self
+            .inner
+            .default_input_config()
+            .or_else(|_| self.inner.default_output_config())?;
+
+        Ok(config.channels())
+    }
 }
 
 pub fn is_same_device(device1: &AudioDevice, device2: &AudioDevice) -> bool {
@@ -210,6 +221,7 @@ pub enum AudioRecordState {
     Err(Option<anyhow::Error>),
 }
 
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
 pub enum AudioChannel {
     FrontLeft,
     FrontRight,
@@ -270,6 +282,46 @@ fn downmix_channels(channels: &[AudioChannel], data: &[u8], out_channels: u16) -
     }
 }
 
+// Speaker positions of the interleaved channels, in the WAVE order
+pub fn channel_layout(channels_count: u16) -> Result<Vec<AudioChannel>> {
+    let layout = match channels_count {
+        1 => vec![AudioChannel::Center],
+        2 => vec![AudioChannel::FrontLeft, AudioChannel::FrontRight],
+        3 => vec![
+            AudioChannel::FrontLeft,
+            AudioChannel::FrontRight,
+            AudioChannel::LowFrequency,
+        ],
+        4 => vec![
+            AudioChannel::FrontLeft,
+            AudioChannel::FrontRight,
+            AudioChannel::BackLeft,
+            AudioChannel::BackRight,
+        ],
+        6 => vec![
+            AudioChannel::FrontLeft,
+            AudioChannel::FrontRight,
+            AudioChannel::Center,
+            AudioChannel::LowFrequency,
+            AudioChannel::SurroundLeft, // Sometimes actually BackLeft, has same level so it's okay
+            AudioChannel::SurroundRight, // Sometimes actually BackRight, has same level so it's okay
+        ],
+        8 => vec![
+            AudioChannel::FrontLeft,
+            AudioChannel::FrontRight,
+            AudioChannel::Center,
+            AudioChannel::LowFrequency,
+            AudioChannel::BackLeft,
+            AudioChannel::BackRight,
+            AudioChannel::SurroundLeft,
+            AudioChannel::SurroundRight,
+        ],
+        _ => bail!("Audio layouts with {channels_count} channels are not supported"),
+    };
+
+    Ok(layout)
+}
+
 fn downmix_audio(data: Vec<u8>, in_channels: u16, out_channels: u16) -> Vec<u8> {
     if in_channels == out_channels {
         data
@@ -278,37 +330,8 @@ fn downmix_audio(data: Vec<u8>, in_channels: u16, out_channels: u16) -> Vec<u8>
             .flat_map(|c| vec![c[0], c[1], c[0], c[1]])
             .collect()
     } else {
-        let channels = match in_channels {
-            2 => vec![AudioChannel::FrontLeft, AudioChannel::FrontRight],
-            3 => vec![
-                AudioChannel::FrontLeft,
-                AudioChannel::FrontRight,
-                AudioChannel::LowFrequency,
-            ],
-            4 => vec![
-                AudioChannel::FrontLeft,
-                AudioChannel::FrontRight,
-                AudioChannel::BackLeft,
-                AudioChannel::BackRight,
-            ],
-            6 => vec![
-                AudioChannel::FrontRight,
-                AudioChannel::Center,
-                AudioChannel::LowFrequency,
-                AudioChannel::SurroundLeft, // Sometimes actually BackLeft, has same level so it's okay
-                AudioChannel::SurroundRight, // Sometimes actually BackRight, has same level so it's okay
-            ],
-            8 => vec![
-                AudioChannel::FrontLeft,
-                AudioChannel::FrontRight,
-                AudioChannel::Center,
-                AudioChannel::LowFrequency,
-                AudioChannel::BackLeft,
-                AudioChannel::BackRight,
-                AudioChannel::SurroundLeft,
-                AudioChannel::SurroundRight,
-            ],
-            _ => unreachable!("Invalid input channel count"),
+        let Ok(channels) = channel_layout(in_channels) else {
+            unreachable!("Invalid input channel count")
         };
 
         data.chunks_exact(in_channels as usize * 2)
@@ -317,13 +340,77 @@ fn downmix_audio(data: Vec<u8>, in_channels: u16, out_channels: u16) -> Vec<u8>
     }
 }
 
+// Copies each channel to the speaker with the same position. Channels without a matching speaker
+// are dropped
+fn remap_audio(data: Vec<u8>, in_channels: u16, out_channels: u16) -> Result<Vec<u8>> {
+    let in_layout = channel_layout(in_channels)?;
+    let out_indices = channel_layout(out_channels)?
+        .iter()
+        .map(|channel| in_layout.iter().position(|c| c == channel))
+        .collect::<Vec<_>>();
+
+    Ok(data
+        .chunks_exact(in_channels as usize * 2)
+        .flat_map(|frame| {
+            out_indices.iter().flat_map(|index| match index {
+                Some(i) => [frame[i * 2], frame[i * 2 + 1]],
+                None => [0, 0],
+            })
+        })
+        .collect())
+}
+
+// Sent with each audio packet. The layout of the game audio can change during the stream, this
+// way the receiver always interprets the samples as they were recorded
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub struct AudioPacketHeader {
+    pub channels_count: u16,
+}
+
+// Pre-spatialized audio is always mixed down to stereo, otherwise the channels of the device are
+// streamed unchanged
+pub fn game_audio_channels_count(mode: GameAudioMode, device_channels_count: u16) -> Result<u16> {
+    match mode {
+        GameAudioMode::PreSpatializedStereo => Ok(2),
+        GameAudioMode::MultichannelPassthrough => {
+            channel_layout(device_channels_count)?;
+
+            Ok(device_channels_count)
+        }
+    }
+}
+
+// Converts the samples of a packet to the playback layout. Multichannel audio is mixed down if the
+// playback device is mono or stereo
+pub fn decode_samples(
+    header: &AudioPacketHeader,
+    data: &[u8],
+    channels_count: u16,
+) -> Result<Vec<f32>> {
+    let in_channels = header.channels_count;
+    channel_layout(in_channels)?;
+
+    let data = if in_channels == channels_count || channels_count <= 2 {
+        downmix_audio(data.to_vec(), in_channels, channels_count)
+    } else {
+        remap_audio(data.to_vec(), in_channels, channels_count)?
+    };
+
+    Ok(data
+        .chunks_exact(2)
+        .map(|c| i16::from_ne_bytes([c[0], c[1]]).to_sample::<f32>())
+        .collect())
+}
+
 #[allow(unused_variables)]
 pub fn record_audio_blocking(
     is_running: Arc<dyn Fn() -> bool + Send + Sync>,
-    mut sender: StreamSender<()>,
+    mut sender: StreamSender<AudioPacketHeader>,
     device: &AudioDevice,
     channels_count: u16,
     mute: bool,
+    // Receives the samples sent to the client, mixed down to stereo
+    on_samples: impl Fn(&[u8]) + Send + 'static,
 ) -> Result<()> {
     let config = device
         .inner
@@ -375,7 +462,15 @@ pub fn record_audio_blocking(
                 let data = downmix_audio(data, config.channels(), channels_count);
 
                 if is_running() {
-                    let mut buffer = sender.get_buffer(&()).unwrap();
+                    if channels_count == 2 {
+                        on_samples(&data);
+                    } else {
+                        on_samples(&downmix_audio(data.clone(), channels_count, 2));
+                    }
+
+                    let mut buffer = sender
+                        .get_buffer(&AudioPacketHeader { channels_count })
+                        .unwrap();
                     buffer.get_range_mut(0, data.len()).copy_from_slice(&data);
                     sender.send(buffer).ok();
                 } else {
@@ -451,7 +546,7 @@ pub fn get_next_frame_batch(
 // continuity will not be affected.
 pub fn receive_samples_loop(
     is_running: impl Fn() -> bool,
-    receiver: &mut StreamReceiver<()>,
+    receiver: &mut StreamReceiver<AudioPacketHeader>,
     sample_buffer: Arc<Mutex<VecDeque<f32>>>,
     channels_count: usize,
     batch_frames_count: usize,
@@ -464,12 +559,9 @@ pub fn receive_samples_loop(
             Err(ConnectionError::TryAgain(_)) => continue,
             Err(ConnectionError::Other(e)) => return Err(e),
         };
-        let (_, packet) = data.get()?;
+        let (header, packet) = data.get()?;
 
-        let new_samples = packet
-            .chunks_exact(2)
-            .map(|c| i16::from_ne_bytes([c[0], c[1]]).to_sample::<f32>())
-            .collect::<Vec<_>>();
+        let new_samples = decode_samples(&header, packet, channels_count as _)?;
 
         let mut sample_buffer_ref = sample_buffer.lock();
 
@@ -605,7 +697,7 @@ pub fn play_audio_loop(
     channels_count: u16,
     sample_rate: u32,
     config: AudioBufferingConfig,
-    receiver: &mut StreamReceiver<()>,
+    receiver: &mut StreamReceiver<AudioPacketHeader>,
 ) -> Result<()> {
     // Size of a chunk of frames. It corresponds to the duration if a fade-in/out in frames.
     let batch_frames_count = sample_rate as usize * config.batch_ms as usize / 1000;
@@ -639,3 +731,66 @@ pub fn play_audio_loop(
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SURROUND_LEVELS: [f32; 6] = [0.1, 0.2, 0.3, 0.4, 0.5, 0.6];
+
+    fn surround_frames(frames_count: usize) -> Vec<u8> {
+        (0..frames_count)
+            .flat_map(|_| SURROUND_LEVELS)
+            .flat_map(|level| level.to_sample::<i16>().to_ne_bytes())
+            .collect()
+    }
+
+    // Same processing as the game audio recording, for a 5.1 device
+    fn record(mode: GameAudioMode, data: Vec<u8>) -> (AudioPacketHeader, Vec<u8>) {
+        let channels_count = game_audio_channels_count(mode, 6).unwrap();
+
+        (
+            AudioPacketHeader { channels_count },
+            downmix_audio(data, 6, channels_count),
+        )
+    }
+
+    #[test]
+    fn test_received_channel_layout() {
+        let (header, packet) = record(GameAudioMode::PreSpatializedStereo, surround_frames(4));
+        assert_eq!(header.channels_count, 2);
+        assert_eq!(
+            channel_layout(header.channels_count).unwrap(),
+            [AudioChannel::FrontLeft, AudioChannel::FrontRight]
+        );
+        let stereo = decode_samples(&header, &packet, 2).unwrap();
+        assert_eq!(stereo.len(), 4 * 2);
+
+        // A surround playback device receives the mix in the front speakers
+        let surround = decode_samples(&header, &packet, 6).unwrap();
+        assert_eq!(surround.len(), 4 * 6);
+        assert_eq!(surround[..6], [stereo[0], stereo[1], 0.0, 0.0, 0.0, 0.0]);
+
+        let (header, packet) = record(GameAudioMode::MultichannelPassthrough, surround_frames(4));
+        assert_eq!(header.channels_count, 6);
+        let surround = decode_samples(&header, &packet, 6).unwrap();
+        for (sample, level) in surround.iter().zip(SURROUND_LEVELS.iter().cycle()) {
+            assert!((sample - level).abs() < 1e-3);
+        }
+
+        // A stereo headset mixes down the channels as the PC would have done
+        assert_eq!(decode_samples(&header, &packet, 2).unwrap(), stereo);
+    }
+
+    #[test]
+    fn test_unsupported_layout_is_rejected() {
+        assert!(game_audio_channels_count(GameAudioMode::MultichannelPassthrough, 5).is_err());
+        assert_eq!(
+            game_audio_channels_count(GameAudioMode::PreSpatializedStereo, 5).unwrap(),
+            2
+        );
+
+        let header = AudioPacketHeader { channels_count: 7 };
+        assert!(decode_samples(&header, &[0; 28], 2).is_err());
+    }
+}
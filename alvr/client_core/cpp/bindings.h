@@ -15,6 +15,9 @@ struct FfiStreamConfig {
     unsigned int viewHeight;
     const unsigned int *swapchainTextures[2];
     unsigned int swapchainLength;
+    // Larger than the view if the encode resolution is capped, the stream is upscaled when drawn
+    unsigned int swapchainWidth;
+    unsigned int swapchainHeight;
     unsigned int enableFoveation;
     float foveationCenterSizeX;
     float foveationCenterSizeY;
@@ -25,6 +28,48 @@ struct FfiStreamConfig {
     unsigned int enableSrgbCorrection;
     unsigned int fixLimitedRange;
     float encodingGamma;
+    unsigned int enableMotionSmoothing;
+    unsigned int enableTestPattern;
+    unsigned int testPattern;
+    unsigned int enableVignetteCorrection;
+    float vignetteCorrectionLeftStrength;
+    float vignetteCorrectionRightStrength;
+    float vignetteCorrectionFalloffExponent;
+    // Optical center of each lens, in UV coordinates of its eye image
+    float vignetteCorrectionLeftLensCenter[2];
+    float vignetteCorrectionRightLensCenter[2];
+    unsigned int enableColorLut;
+    unsigned int colorLutSize;
+    const float *colorLutData; // RGB, red changes fastest
+    float colorLutDomainMin[3];
+    float colorLutDomainMax[3];
+    float colorLutIntensity;
+    unsigned int enableComfortVignette;
+    unsigned int enableDithering;
+    unsigned int ditherNoiseSize;
+    const unsigned char *ditherNoiseData; // thresholds as 8 bit levels, row major
+    float ditherStrength;
+    unsigned int enableVirtualScreen;
+    float virtualScreenDistance;
+    float virtualScreenWidth;
+    float virtualScreenHeight;
+    float virtualScreenCurvature; // 0 for flat screens
+};
+
+struct FfiReticle {
+    unsigned int shape; // 0: crosshair, 1: dot
+    float centers[2][2]; // tangent space, per eye
+    unsigned int visible[2];
+    float radius;
+    float halfThickness;
+    float color[4];
+};
+
+struct FfiLatencyStamp {
+    unsigned int origin[2]; // top left pixel
+    unsigned int cellSize;
+    unsigned int imageHeight;
+    unsigned int value[2]; // low, high
 };
 
 // gltf_model.h
@@ -46,4 +91,9 @@ extern "C" void streamStartNative(FfiStreamConfig config);
 extern "C" void updateLobbyHudTexture(const unsigned char *data);
 extern "C" void renderLobbyNative(const FfiViewInput eyeInputs[2]);
 extern "C" void renderStreamNative(void *streamHardwareBuffer,
-                                   const unsigned int swapchainIndices[2]);
+                                   const FfiViewInput eyeInputs[2],
+                                   unsigned int testPatternFrameIndex,
+                                   float interpolationFactor,
+                                   float comfortVignetteRadius);
+extern "C" void renderReticleNative(const FfiViewInput eyeInputs[2], FfiReticle reticle);
+extern "C" void renderLatencyStampNative(const FfiViewInput eyeInputs[2], FfiLatencyStamp stamp);
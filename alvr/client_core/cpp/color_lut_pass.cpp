@@ -0,0 +1,88 @@
+#include "color_lut_pass.h"
+#include "utils.h"
+#include <memory>
+
+using namespace std;
+using namespace gl_render_utils;
+
+namespace {
+// Tested on the Rust side, in color_lut.rs. LUTs are authored for sRGB encoded colors, while the
+// intermediate textures are sampled as linear. The LUT is sampled at the texel centers, so that the
+// first and last entries map to the ends of the domain.
+const string COLOR_LUT_FRAGMENT_SHADER = R"glsl(#version 300 es
+        precision mediump float;
+        precision mediump sampler3D;
+
+        uniform sampler2D tex0;
+        uniform sampler3D tex1;
+        layout(std140) uniform ColorLutBlock {
+            vec3 domainMin;
+            float intensity;
+            vec3 domainMax;
+            float lutSize;
+        };
+        in vec2 uv;
+        out vec4 color;
+
+        vec3 linearToSrgb(vec3 c) {
+            return mix(c * 12.92, 1.055 * pow(c, vec3(1.0 / 2.4)) - 0.055, step(0.0031308, c));
+        }
+
+        vec3 srgbToLinear(vec3 c) {
+            return mix(c / 12.92, pow((c + 0.055) / 1.055, vec3(2.4)), step(0.04045, c));
+        }
+
+        void main() {
+            color = texture(tex0, uv);
+
+            vec3 srgb = linearToSrgb(color.rgb);
+            vec3 lutUV = clamp((srgb - domainMin) / (domainMax - domainMin), 0.0, 1.0);
+            lutUV = (lutUV * (lutSize - 1.0) + 0.5) / lutSize;
+            vec3 graded = mix(srgb, texture(tex1, lutUV).rgb, intensity);
+
+            color.rgb = srgbToLinear(clamp(graded, 0.0, 1.0));
+        }
+    )glsl";
+
+struct ColorLutBlock {
+    float domainMin[3];
+    float intensity;
+    float domainMax[3];
+    float lutSize;
+};
+} // namespace
+
+ColorLutPass::ColorLutPass(Texture *inputSurface) : mInputSurface(inputSurface) {}
+
+void ColorLutPass::Initialize(uint32_t width,
+                              uint32_t height,
+                              ColorLutData data,
+                              GLint outputFormat) {
+    mData = data;
+    // The data is not referenced after the upload
+    mData.data = nullptr;
+
+    mLutTexture = make_unique<Texture>(data.size, GL_RGB16F, GL_RGB, GL_FLOAT, data.data);
+
+    mOutputTexture.reset(new Texture(false, 0, false, width * 2, height, outputFormat));
+    mOutputTextureState = make_unique<RenderState>(mOutputTexture.get());
+
+    mPipeline =
+        make_unique<RenderPipeline>(vector<const Texture *>{mInputSurface, mLutTexture.get()},
+                                    QUAD_2D_VERTEX_SHADER,
+                                    COLOR_LUT_FRAGMENT_SHADER,
+                                    sizeof(ColorLutBlock));
+}
+
+void ColorLutPass::Render() const {
+    ColorLutBlock block = {};
+    for (int i = 0; i < 3; i++) {
+        block.domainMin[i] = mData.domainMin[i];
+        block.domainMax[i] = mData.domainMax[i];
+    }
+    block.intensity = mData.intensity;
+    block.lutSize = (float)mData.size;
+
+    mOutputTextureState->ClearDepth();
+    mPipeline->Render(*mOutputTextureState, &block);
+}
@@ -0,0 +1,37 @@
+#pragma once
+
+#include "gl_render_utils/render_pipeline.h"
+#include <cstdint>
+#include <memory>
+
+struct ColorLutData {
+    uint32_t size;
+    const float *data; // RGB, red changes fastest
+    float domainMin[3];
+    float domainMax[3];
+    float intensity;
+};
+
+// Applies a 3D color lookup table (loaded from a .cube file on the Rust side) for color grading.
+// The LUT is uploaded once to a 3D texture and sampled with trilinear filtering.
+class ColorLutPass {
+  public:
+    ColorLutPass(gl_render_utils::Texture *inputSurface);
+
+    void Initialize(uint32_t width,
+                    uint32_t height,
+                    ColorLutData data,
+                    GLint outputFormat = GL_SRGB8_ALPHA8);
+
+    void Render() const;
+
+    gl_render_utils::Texture *GetOutputTexture() { return mOutputTexture.get(); }
+
+  private:
+    gl_render_utils::Texture *mInputSurface;
+    std::unique_ptr<gl_render_utils::Texture> mLutTexture;
+    std::unique_ptr<gl_render_utils::Texture> mOutputTexture;
+    std::unique_ptr<gl_render_utils::RenderState> mOutputTextureState;
+    std::unique_ptr<gl_render_utils::RenderPipeline> mPipeline;
+    ColorLutData mData;
+};
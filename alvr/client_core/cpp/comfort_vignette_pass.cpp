@@ -0,0 +1,58 @@
+#include "comfort_vignette_pass.h"
+#include "utils.h"
+#include <memory>
+
+using namespace std;
+using namespace gl_render_utils;
+
+namespace {
+// The radius is relative to the half diagonal of each eye image, as in ComfortVignette on the Rust
+// side. The edge is feathered to avoid a hard border in the peripheral vision.
+const string COMFORT_VIGNETTE_FRAGMENT_SHADER = R"glsl(#version 300 es
+        precision mediump float;
+
+        uniform sampler2D tex0;
+        layout(std140) uniform ComfortVignetteBlock {
+            float radius;
+        };
+        in vec2 uv;
+        out vec4 color;
+
+        const float INV_SQRT_2 = 0.70710678;
+        const float FEATHER = 0.15;
+
+        void main() {
+            color = texture(tex0, uv);
+
+            vec2 eyeUV = vec2(fract(uv.x * 2.0), uv.y);
+            float distance = length(eyeUV * 2.0 - 1.0) * INV_SQRT_2;
+
+            color.rgb *= 1.0 - smoothstep(radius, radius + FEATHER, distance);
+        }
+    )glsl";
+
+struct ComfortVignetteBlock {
+    float radius;
+    float padding[3];
+};
+} // namespace
+
+ComfortVignettePass::ComfortVignettePass(Texture *inputSurface) : mInputSurface(inputSurface) {}
+
+void ComfortVignettePass::Initialize(uint32_t width, uint32_t height, GLint outputFormat) {
+    mOutputTexture.reset(new Texture(false, 0, false, width * 2, height, outputFormat));
+    mOutputTextureState = make_unique<RenderState>(mOutputTexture.get());
+
+    mPipeline = make_unique<RenderPipeline>(vector<const Texture *>{mInputSurface},
+                                            QUAD_2D_VERTEX_SHADER,
+                                            COMFORT_VIGNETTE_FRAGMENT_SHADER,
+                                            sizeof(ComfortVignetteBlock));
+}
+
+void ComfortVignettePass::Render(float radius) const {
+    ComfortVignetteBlock block = {};
+    block.radius = radius;
+
+    mOutputTextureState->ClearDepth();
+    mPipeline->Render(*mOutputTextureState, &block);
+}
@@ -0,0 +1,25 @@
+#pragma once
+
+#include "gl_render_utils/render_pipeline.h"
+#include <cstdint>
+#include <memory>
+
+// Darkens the periphery of each eye image during fast head motion. The radius of the unobstructed
+// area is computed on the Rust side and updated every frame, so the pass is rendered also when the
+// stream frame is repeated.
+class ComfortVignettePass {
+  public:
+    ComfortVignettePass(gl_render_utils::Texture *inputSurface);
+
+    void Initialize(uint32_t width, uint32_t height, GLint outputFormat = GL_SRGB8_ALPHA8);
+
+    void Render(float radius) const;
+
+    gl_render_utils::Texture *GetOutputTexture() { return mOutputTexture.get(); }
+
+  private:
+    gl_render_utils::Texture *mInputSurface;
+    std::unique_ptr<gl_render_utils::Texture> mOutputTexture;
+    std::unique_ptr<gl_render_utils::RenderState> mOutputTextureState;
+    std::unique_ptr<gl_render_utils::RenderPipeline> mPipeline;
+};
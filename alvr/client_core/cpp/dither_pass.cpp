@@ -0,0 +1,90 @@
+#include "dither_pass.h"
+#include "utils.h"
+#include <memory>
+
+using namespace std;
+using namespace gl_render_utils;
+
+namespace {
+// The noise is added to the sRGB encoded color, so it is one 8 bit step of the output everywhere,
+// and the output texture encodes and rounds it. The pattern is tiled over the output pixels, texel
+// centers map to (0, 1). Tested on the Rust side, in dithering.rs
+const string DITHER_FRAGMENT_SHADER = R"glsl(#version 300 es
+        precision highp float;
+
+        uniform sampler2D tex0;
+        uniform sampler2D tex1;
+        layout(std140) uniform DitherBlock {
+            float strength;
+            int noiseSize;
+        };
+        in vec2 uv;
+        out vec4 color;
+
+        vec3 linearToSrgb(vec3 c) {
+            return mix(c * 12.92, 1.055 * pow(c, vec3(1.0 / 2.4)) - 0.055, step(0.0031308, c));
+        }
+
+        vec3 srgbToLinear(vec3 c) {
+            return mix(c / 12.92, pow((c + 0.055) / 1.055, vec3(2.4)), step(0.04045, c));
+        }
+
+        void main() {
+            color = texture(tex0, uv);
+
+            ivec2 noiseCoord = ivec2(gl_FragCoord.xy) % noiseSize;
+            float threshold = (texelFetch(tex1, noiseCoord, 0).r * 255.0 + 0.5) / 256.0;
+
+            vec3 srgb = linearToSrgb(clamp(color.rgb, 0.0, 1.0));
+            srgb += (threshold - 0.5) * strength / 255.0;
+
+            color.rgb = srgbToLinear(clamp(srgb, 0.0, 1.0));
+        }
+    )glsl";
+
+struct DitherBlock {
+    float strength;
+    int32_t noiseSize;
+    float padding[2];
+};
+} // namespace
+
+DitherPass::DitherPass(Texture *inputSurface) : mInputSurface(inputSurface) {}
+
+void DitherPass::Initialize(uint32_t width, uint32_t height, DitherData data) {
+    mData = data;
+    // The data is not referenced after the upload
+    mData.noiseData = nullptr;
+
+    // Rows of a single channel texture are not 4 byte aligned
+    GL(glPixelStorei(GL_UNPACK_ALIGNMENT, 1));
+    mNoiseTexture = make_unique<Texture>(
+        false,
+        0,
+        false,
+        data.noiseSize,
+        data.noiseSize,
+        GL_R8,
+        GL_RED,
+        vector<uint8_t>(data.noiseData, data.noiseData + data.noiseSize * data.noiseSize));
+    GL(glPixelStorei(GL_UNPACK_ALIGNMENT, 4));
+
+    // The quantization to 8 bit happens when rendering to this texture
+    mOutputTexture.reset(new Texture(false, 0, false, width * 2, height));
+    mOutputTextureState = make_unique<RenderState>(mOutputTexture.get());
+
+    mPipeline =
+        make_unique<RenderPipeline>(vector<const Texture *>{mInputSurface, mNoiseTexture.get()},
+                                    QUAD_2D_VERTEX_SHADER,
+                                    DITHER_FRAGMENT_SHADER,
+                                    sizeof(DitherBlock));
+}
+
+void DitherPass::Render() const {
+    DitherBlock block = {};
+    block.strength = mData.strength;
+    block.noiseSize = (int32_t)mData.noiseSize;
+
+    mOutputTextureState->ClearDepth();
+    mPipeline->Render(*mOutputTextureState, &block);
+}
@@ -0,0 +1,34 @@
+#pragma once
+
+#include "gl_render_utils/render_pipeline.h"
+#include <cstdint>
+#include <memory>
+
+struct DitherData {
+    uint32_t noiseSize;
+    const uint8_t *noiseData; // thresholds as 8 bit levels, row major
+    float strength;
+};
+
+// Adds the noise of a tiled threshold pattern (generated on the Rust side) before the image of both
+// eyes is quantized to 8 bit, to break up the banding of gradients. The previous passes must render
+// to a higher precision format, or there is no banding left to break up. The output must have the
+// resolution of the swapchain, one noise texel per displayed pixel.
+class DitherPass {
+  public:
+    DitherPass(gl_render_utils::Texture *inputSurface);
+
+    void Initialize(uint32_t width, uint32_t height, DitherData data);
+
+    void Render() const;
+
+    gl_render_utils::Texture *GetOutputTexture() { return mOutputTexture.get(); }
+
+  private:
+    gl_render_utils::Texture *mInputSurface;
+    std::unique_ptr<gl_render_utils::Texture> mNoiseTexture;
+    std::unique_ptr<gl_render_utils::Texture> mOutputTexture;
+    std::unique_ptr<gl_render_utils::RenderState> mOutputTextureState;
+    std::unique_ptr<gl_render_utils::RenderPipeline> mPipeline;
+    DitherData mData;
+};
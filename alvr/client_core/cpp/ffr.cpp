@@ -148,7 +148,7 @@ FoveationVars CalculateFoveationVars(FFRData data) {
 
 FFR::FFR(Texture *inputSurface) : mInputSurface(inputSurface) {}
 
-void FFR::Initialize(FoveationVars fv) {
+void FFR::Initialize(FoveationVars fv, GLint outputFormat) {
     using glm::vec2;
 
     // Precalculate a bunch of constants that will be used in fragment shader
@@ -193,7 +193,8 @@ void FFR::Initialize(FoveationVars fv) {
                                             cright.x, cright.y
                                             );
 
-    mExpandedTexture.reset(new Texture(false, 0, false, fv.targetEyeWidth * 2, fv.targetEyeHeight));
+    mExpandedTexture.reset(new Texture(
+        false, 0, false, fv.targetEyeWidth * 2, fv.targetEyeHeight, outputFormat));
     mExpandedTextureState = make_unique<RenderState>(mExpandedTexture.get());
 
     auto decompressAxisAlignedShaderStr =
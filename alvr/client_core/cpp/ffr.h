@@ -39,7 +39,7 @@ class FFR {
 public:
     FFR(gl_render_utils::Texture *inputSurface);
 
-    void Initialize(FoveationVars fv);
+    void Initialize(FoveationVars fv, GLint outputFormat = GL_SRGB8_ALPHA8);
 
     void Render() const;
 
@@ -47,6 +47,25 @@ Texture::Texture(bool external,
     LOGV("New texture Created id#%d", mGLTexture);
 }
 
+Texture::Texture(
+    uint32_t size, GLint internalFormat, GLenum format, GLenum type, const void *content) {
+    mOES = false;
+    mWidth = size;
+    mHeight = size;
+    mTarget = GL_TEXTURE_3D;
+    mExternal = false;
+
+    GL(glGenTextures(1, &mGLTexture));
+    GL(glBindTexture(mTarget, mGLTexture));
+    GL(glTexImage3D(mTarget, 0, internalFormat, size, size, size, 0, format, type, content));
+    GL(glTexParameteri(mTarget, GL_TEXTURE_WRAP_S, GL_CLAMP_TO_EDGE));
+    GL(glTexParameteri(mTarget, GL_TEXTURE_WRAP_T, GL_CLAMP_TO_EDGE));
+    GL(glTexParameteri(mTarget, GL_TEXTURE_WRAP_R, GL_CLAMP_TO_EDGE));
+    GL(glTexParameteri(mTarget, GL_TEXTURE_MAG_FILTER, GL_LINEAR));
+    GL(glTexParameteri(mTarget, GL_TEXTURE_MIN_FILTER, GL_LINEAR));
+    LOGV("New 3D texture Created id#%d", mGLTexture);
+}
+
 Texture::~Texture() {
     if (!mExternal) {
         GL(glDeleteTextures(1, &mGLTexture));
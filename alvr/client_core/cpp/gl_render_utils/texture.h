@@ -20,6 +20,9 @@ class Texture {
             GLenum format = GL_RGBA,
             std::vector<uint8_t> content = {});
 
+    // Cubic 3D texture, used for color lookup tables
+    Texture(uint32_t size, GLint internalFormat, GLenum format, GLenum type, const void *content);
+
     uint32_t GetWidth() const { return mWidth; }
 
     uint32_t GetHeight() const { return mHeight; }
@@ -1,8 +1,17 @@
 #include "bindings.h"
+#include "color_lut_pass.h"
+#include "comfort_vignette_pass.h"
+#include "dither_pass.h"
 #include "ffr.h"
 #include "gltf_model.h"
+#include "latency_stamp_pass.h"
+#include "motion_smoothing_pass.h"
+#include "reticle_pass.h"
 #include "srgb_correction_pass.h"
+#include "test_pattern_pass.h"
 #include "utils.h"
+#include "vignette_correction_pass.h"
+#include "virtual_screen_pass.h"
 #include <EGL/egl.h>
 #include <EGL/eglext.h>
 #include <glm/gtc/quaternion.hpp>
@@ -56,6 +65,15 @@ typedef struct {
     GltfModel *lobbyScene;
     std::unique_ptr<FFR> ffr;
     std::unique_ptr<SrgbCorrectionPass> srgbCorrectionPass;
+    std::unique_ptr<MotionSmoothingPass> motionSmoothingPass;
+    std::unique_ptr<TestPatternPass> testPatternPass;
+    std::unique_ptr<VignetteCorrectionPass> vignetteCorrectionPass;
+    std::unique_ptr<ColorLutPass> colorLutPass;
+    std::unique_ptr<ComfortVignettePass> comfortVignettePass;
+    std::unique_ptr<DitherPass> ditherPass;
+    std::unique_ptr<VirtualScreenPass> virtualScreenPass;
+    std::unique_ptr<ReticlePass> reticlePass;
+    std::unique_ptr<LatencyStampPass> latencyStampPass;
     bool enableFFE;
     GLuint streamRenderTexture;
 } ovrRenderer;
@@ -354,12 +372,31 @@ void ovrRenderer_Create(ovrRenderer *renderer,
                         Texture *streamTexture,
                         int hudTexture,
                         std::vector<GLuint> textures[2],
+                        int swapchainWidth,
+                        int swapchainHeight,
                         FFRData ffrData,
                         bool isLobby,
                         bool enableSrgbCorrection,
                         bool fixLimitedRange,
-                        float encodingGamma) {
+                        float encodingGamma,
+                        bool enableMotionSmoothing,
+                        bool enableTestPattern,
+                        TestPattern testPattern,
+                        bool enableVignetteCorrection,
+                        VignetteCorrectionData vignetteCorrectionData,
+                        bool enableColorLut,
+                        ColorLutData colorLutData,
+                        bool enableComfortVignette,
+                        bool enableDithering,
+                        DitherData ditherData,
+                        bool enableVirtualScreen,
+                        VirtualScreenData virtualScreenData) {
     if (!isLobby) {
+        Texture *outputTexture;
+
+        // With dithering, the image is quantized to 8 bit only once, by the dither pass
+        GLint intermediateFormat = enableDithering ? GL_RGBA16F : GL_SRGB8_ALPHA8;
+
         renderer->srgbCorrectionPass = std::make_unique<SrgbCorrectionPass>(streamTexture);
         renderer->enableFFE = ffrData.enabled;
         if (renderer->enableFFE) {
@@ -368,15 +405,71 @@ void ovrRenderer_Create(ovrRenderer *renderer,
                                                      fv.optimizedEyeHeight,
                                                      !enableSrgbCorrection,
                                                      fixLimitedRange,
-                                                     encodingGamma);
+                                                     encodingGamma,
+                                                     intermediateFormat);
             renderer->ffr = std::make_unique<FFR>(renderer->srgbCorrectionPass->GetOutputTexture());
-            renderer->ffr->Initialize(fv);
-            renderer->streamRenderTexture = renderer->ffr->GetOutputTexture()->GetGLTexture();
+            renderer->ffr->Initialize(fv, intermediateFormat);
+            outputTexture = renderer->ffr->GetOutputTexture();
         } else {
-            renderer->srgbCorrectionPass->Initialize(
-                width, height, !enableSrgbCorrection, fixLimitedRange, encodingGamma);
-            renderer->streamRenderTexture =
-                renderer->srgbCorrectionPass->GetOutputTexture()->GetGLTexture();
+            renderer->srgbCorrectionPass->Initialize(width,
+                                                     height,
+                                                     !enableSrgbCorrection,
+                                                     fixLimitedRange,
+                                                     encodingGamma,
+                                                     intermediateFormat);
+            outputTexture = renderer->srgbCorrectionPass->GetOutputTexture();
+        }
+
+        // Interpolates the decoded stream, the full resolution corrections are applied after
+        if (enableMotionSmoothing) {
+            renderer->motionSmoothingPass = std::make_unique<MotionSmoothingPass>(outputTexture);
+            renderer->motionSmoothingPass->Initialize(width, height, intermediateFormat);
+            outputTexture = renderer->motionSmoothingPass->GetOutputTexture();
+        }
+
+        // The test pattern replaces the decoded stream
+        if (enableTestPattern) {
+            renderer->testPatternPass = std::make_unique<TestPatternPass>();
+            renderer->testPatternPass->Initialize(width, height, testPattern, intermediateFormat);
+            outputTexture = renderer->testPatternPass->GetOutputTexture();
+        }
+
+        // Applied after color correction and on the full resolution image
+        if (enableVignetteCorrection) {
+            renderer->vignetteCorrectionPass =
+                std::make_unique<VignetteCorrectionPass>(outputTexture);
+            renderer->vignetteCorrectionPass->Initialize(
+                width, height, vignetteCorrectionData, intermediateFormat);
+            outputTexture = renderer->vignetteCorrectionPass->GetOutputTexture();
+        }
+
+        // Final color transform
+        if (enableColorLut) {
+            renderer->colorLutPass = std::make_unique<ColorLutPass>(outputTexture);
+            renderer->colorLutPass->Initialize(width, height, colorLutData, intermediateFormat);
+            outputTexture = renderer->colorLutPass->GetOutputTexture();
+        }
+
+        // Applied last since it must track the head motion also on repeated frames
+        if (enableComfortVignette) {
+            renderer->comfortVignettePass = std::make_unique<ComfortVignettePass>(outputTexture);
+            renderer->comfortVignettePass->Initialize(width, height, intermediateFormat);
+            outputTexture = renderer->comfortVignettePass->GetOutputTexture();
+        }
+
+        // Must be the last pass, it quantizes the image. It upscales the frames to the swapchain
+        // resolution, so that the final copy does not interpolate the noise
+        if (enableDithering) {
+            renderer->ditherPass = std::make_unique<DitherPass>(outputTexture);
+            renderer->ditherPass->Initialize(swapchainWidth, swapchainHeight, ditherData);
+            outputTexture = renderer->ditherPass->GetOutputTexture();
+        }
+
+        renderer->streamRenderTexture = outputTexture->GetGLTexture();
+
+        if (enableVirtualScreen) {
+            renderer->virtualScreenPass = std::make_unique<VirtualScreenPass>(outputTexture);
+            renderer->virtualScreenPass->Initialize(virtualScreenData);
         }
     }
 
@@ -386,8 +479,14 @@ void ovrRenderer_Create(ovrRenderer *renderer,
 
         for (int i = 0; i < textures[eye].size(); i++) {
             auto glRenderTarget = textures[eye][i];
-            frameBuffer->renderTargets.push_back(std::make_unique<gl_render_utils::Texture>(
-                true, glRenderTarget, false, width, height, GL_RGBA16F, GL_RGBA));
+            frameBuffer->renderTargets.push_back(
+                std::make_unique<gl_render_utils::Texture>(true,
+                                                           glRenderTarget,
+                                                           false,
+                                                           swapchainWidth,
+                                                           swapchainHeight,
+                                                           GL_RGBA16F,
+                                                           GL_RGBA));
             frameBuffer->renderStates.push_back(std::make_unique<gl_render_utils::RenderState>(
                 frameBuffer->renderTargets[i].get()));
         }
@@ -506,7 +605,12 @@ void ovrRenderer_RenderFrame(ovrRenderer *renderer, const FfiViewInput input[2],
                           (int)frameBuffer->renderTargets[0]->GetWidth(),
                           (int)frameBuffer->renderTargets[0]->GetHeight()};
 
-        renderEye(eye, mvpMatrix[eye], &viewport, renderer, isLobby);
+        if (!isLobby && renderer->virtualScreenPass) {
+            renderer->virtualScreenPass->Render(
+                *frameBuffer->renderStates[input[eye].swapchainIndex], input[eye]);
+        } else {
+            renderEye(eye, mvpMatrix[eye], &viewport, renderer, isLobby);
+        }
 
         // Discard the depth buffer, so the tiler won't need to write it back out to memory.
         const GLenum depthAttachment[1] = {GL_DEPTH_ATTACHMENT};
@@ -562,11 +666,25 @@ void prepareLobbyRoom(int viewWidth,
                        nullptr,
                        g_ctx.hudTexture->GetGLTexture(),
                        g_ctx.lobbySwapchainTextures,
+                       viewWidth,
+                       viewHeight,
                        {false},
                        true,
                        enable_srgb_correction,
                        false,
-                       1.0);
+                       1.0,
+                       false,
+                       false,
+                       TEST_PATTERN_COLOR_BARS,
+                       false,
+                       {},
+                       false,
+                       {},
+                       false,
+                       false,
+                       {},
+                       false,
+                       {});
 }
 
 void destroyLobby() {
@@ -607,6 +725,8 @@ void streamStartNative(FfiStreamConfig config) {
                        g_ctx.streamTexture.get(),
                        g_ctx.hudTexture->GetGLTexture(),
                        g_ctx.streamSwapchainTextures,
+                       config.swapchainWidth,
+                       config.swapchainHeight,
                        {(bool)config.enableFoveation,
                         config.viewWidth,
                         config.viewHeight,
@@ -619,7 +739,36 @@ void streamStartNative(FfiStreamConfig config) {
                        false,
                        config.enableSrgbCorrection,
                        config.fixLimitedRange,
-                       config.encodingGamma);
+                       config.encodingGamma,
+                       config.enableMotionSmoothing,
+                       config.enableTestPattern,
+                       (TestPattern)config.testPattern,
+                       config.enableVignetteCorrection,
+                       {config.vignetteCorrectionLeftStrength,
+                        config.vignetteCorrectionRightStrength,
+                        config.vignetteCorrectionFalloffExponent,
+                        {config.vignetteCorrectionLeftLensCenter[0],
+                         config.vignetteCorrectionLeftLensCenter[1]},
+                        {config.vignetteCorrectionRightLensCenter[0],
+                         config.vignetteCorrectionRightLensCenter[1]}},
+                       config.enableColorLut,
+                       {config.colorLutSize,
+                        config.colorLutData,
+                        {config.colorLutDomainMin[0],
+                         config.colorLutDomainMin[1],
+                         config.colorLutDomainMin[2]},
+                        {config.colorLutDomainMax[0],
+                         config.colorLutDomainMax[1],
+                         config.colorLutDomainMax[2]},
+                        config.colorLutIntensity},
+                       config.enableComfortVignette,
+                       config.enableDithering,
+                       {config.ditherNoiseSize, config.ditherNoiseData, config.ditherStrength},
+                       config.enableVirtualScreen,
+                       {config.virtualScreenDistance,
+                        config.virtualScreenWidth,
+                        config.virtualScreenHeight,
+                        config.virtualScreenCurvature});
 }
 
 void updateLobbyHudTexture(const unsigned char *data) {
@@ -653,10 +802,22 @@ void renderLobbyNative(const FfiViewInput eyeInputs[2]) {
     ovrRenderer_RenderFrame(g_ctx.lobbyRenderer.get(), eyeInputs, true);
 }
 
-void renderStreamNative(void *streamHardwareBuffer, const unsigned int swapchainIndices[2]) {
+void renderStreamNative(void *streamHardwareBuffer,
+                        const FfiViewInput eyeInputs[2],
+                        unsigned int testPatternFrameIndex,
+                        float interpolationFactor,
+                        float comfortVignetteRadius) {
     auto renderer = g_ctx.streamRenderer.get();
 
-    if (streamHardwareBuffer != 0) {
+    if (renderer->testPatternPass) {
+        renderer->testPatternPass->Render(testPatternFrameIndex);
+        if (renderer->vignetteCorrectionPass) {
+            renderer->vignetteCorrectionPass->Render();
+        }
+        if (renderer->colorLutPass) {
+            renderer->colorLutPass->Render();
+        }
+    } else if (streamHardwareBuffer != 0) {
         GL(EGLClientBuffer clientBuffer =
                eglGetNativeClientBufferANDROID((const AHardwareBuffer *)streamHardwareBuffer));
         GL(EGLImageKHR image = eglCreateImageKHR(
@@ -669,12 +830,71 @@ void renderStreamNative(void *streamHardwareBuffer, const unsigned int swapchain
         if (renderer->enableFFE) {
             renderer->ffr->Render();
         }
+        if (renderer->motionSmoothingPass) {
+            renderer->motionSmoothingPass->PushFrame();
+            renderer->motionSmoothingPass->Render(interpolationFactor);
+        }
+        if (renderer->vignetteCorrectionPass) {
+            renderer->vignetteCorrectionPass->Render();
+        }
+        if (renderer->colorLutPass) {
+            renderer->colorLutPass->Render();
+        }
 
         GL(eglDestroyImageKHR(g_ctx.eglDisplay, image));
+    } else if (renderer->motionSmoothingPass) {
+        // The last frame could have been presented interpolated
+        renderer->motionSmoothingPass->Render(interpolationFactor);
+        if (renderer->vignetteCorrectionPass) {
+            renderer->vignetteCorrectionPass->Render();
+        }
+        if (renderer->colorLutPass) {
+            renderer->colorLutPass->Render();
+        }
+    }
+
+    if (renderer->comfortVignettePass) {
+        renderer->comfortVignettePass->Render(comfortVignetteRadius);
+    }
+
+    if (renderer->ditherPass) {
+        renderer->ditherPass->Render();
     }
 
-    FfiViewInput eyeInputs[2] = {};
-    eyeInputs[0].swapchainIndex = swapchainIndices[0];
-    eyeInputs[1].swapchainIndex = swapchainIndices[1];
     ovrRenderer_RenderFrame(renderer, eyeInputs, false);
 }
+
+void renderReticleNative(const FfiViewInput eyeInputs[2], FfiReticle reticle) {
+    auto renderer = g_ctx.streamRenderer.get();
+
+    if (!renderer->reticlePass) {
+        renderer->reticlePass = std::make_unique<ReticlePass>();
+    }
+
+    for (int eye = 0; eye < 2; eye++) {
+        if (reticle.visible[eye]) {
+            renderer->reticlePass->Render(
+                *renderer->FrameBuffer[eye].renderStates[eyeInputs[eye].swapchainIndex],
+                eyeInputs[eye],
+                reticle,
+                eye);
+        }
+    }
+
+    GL(glBindFramebuffer(GL_DRAW_FRAMEBUFFER, 0));
+}
+
+void renderLatencyStampNative(const FfiViewInput eyeInputs[2], FfiLatencyStamp stamp) {
+    auto renderer = g_ctx.streamRenderer.get();
+
+    if (!renderer->latencyStampPass) {
+        renderer->latencyStampPass = std::make_unique<LatencyStampPass>();
+    }
+
+    for (int eye = 0; eye < 2; eye++) {
+        renderer->latencyStampPass->Render(
+            *renderer->FrameBuffer[eye].renderStates[eyeInputs[eye].swapchainIndex], stamp);
+    }
+
+    GL(glBindFramebuffer(GL_DRAW_FRAMEBUFFER, 0));
+}
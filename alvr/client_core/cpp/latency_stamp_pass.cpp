@@ -0,0 +1,81 @@
+#include "latency_stamp_pass.h"
+#include "utils.h"
+
+using namespace std;
+using namespace gl_render_utils;
+
+namespace {
+// Grid of cells of whole pixels, a ring of white cells around 8x6 data cells. The bits are read
+// from the most significant, row by row starting from the top left. The value is split in two
+// words since uint64 is not available in GLSL ES.
+const string LATENCY_STAMP_FRAGMENT_SHADER = R"glsl(#version 300 es
+        precision highp float;
+        precision highp int;
+
+        layout(std140) uniform LatencyStampBlock {
+            uvec2 origin; // top left pixel
+            uint cellSize;
+            uint imageHeight;
+            uvec2 value; // low, high
+        };
+        out vec4 color;
+
+        const uint DATA_COLUMNS = 8u;
+        const uint DATA_ROWS = 6u;
+        const uint DATA_BITS = DATA_COLUMNS * DATA_ROWS;
+
+        void main() {
+            // gl_FragCoord has the origin at the bottom left
+            ivec2 pixel = ivec2(int(gl_FragCoord.x), int(imageHeight) - 1 - int(gl_FragCoord.y));
+            ivec2 offset = pixel - ivec2(origin);
+            if (offset.x < 0 || offset.y < 0) {
+                discard;
+            }
+            uvec2 cell = uvec2(offset) / cellSize;
+            if (cell.x >= DATA_COLUMNS + 2u || cell.y >= DATA_ROWS + 2u) {
+                discard;
+            }
+
+            bool bit;
+            if (cell.x == 0u || cell.y == 0u || cell.x == DATA_COLUMNS + 1u ||
+                cell.y == DATA_ROWS + 1u) {
+                bit = true;
+            } else {
+                uint shift = DATA_BITS - 1u - ((cell.y - 1u) * DATA_COLUMNS + cell.x - 1u);
+                uint word = shift < 32u ? value.x : value.y;
+                bit = ((word >> (shift % 32u)) & 1u) == 1u;
+            }
+
+            color = bit ? vec4(1.0) : vec4(0.0, 0.0, 0.0, 1.0);
+        }
+    )glsl";
+
+struct LatencyStampBlock {
+    uint32_t origin[2];
+    uint32_t cellSize;
+    uint32_t imageHeight;
+    uint32_t value[2];
+    uint32_t padding[2];
+};
+} // namespace
+
+LatencyStampPass::LatencyStampPass() {
+    mPipeline = make_unique<RenderPipeline>(vector<const Texture *>{},
+                                            QUAD_2D_VERTEX_SHADER,
+                                            LATENCY_STAMP_FRAGMENT_SHADER,
+                                            sizeof(LatencyStampBlock));
+}
+
+void LatencyStampPass::Render(const RenderState &renderState, const FfiLatencyStamp &stamp) const {
+    LatencyStampBlock block = {};
+    block.origin[0] = stamp.origin[0];
+    block.origin[1] = stamp.origin[1];
+    block.cellSize = stamp.cellSize;
+    block.imageHeight = stamp.imageHeight;
+    block.value[0] = stamp.value[0];
+    block.value[1] = stamp.value[1];
+
+    // The eye image is already in the swapchain, only the depth is cleared
+    renderState.ClearDepth();
+    mPipeline->Render(renderState, &block);
+}
@@ -0,0 +1,18 @@
+#pragma once
+
+#include "bindings.h"
+#include "gl_render_utils/render_pipeline.h"
+#include <memory>
+
+// Draws the latency stamp on top of the eye images, directly into the swapchain. The position of
+// the stamp and the value are computed on the Rust side by LatencyStamp.
+class LatencyStampPass {
+  public:
+    LatencyStampPass();
+
+    void Render(const gl_render_utils::RenderState &renderState,
+                const FfiLatencyStamp &stamp) const;
+
+  private:
+    std::unique_ptr<gl_render_utils::RenderPipeline> mPipeline;
+};
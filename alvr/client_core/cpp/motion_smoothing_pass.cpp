@@ -0,0 +1,183 @@
+#include "motion_smoothing_pass.h"
+#include "utils.h"
+#include <memory>
+
+using namespace std;
+using namespace gl_render_utils;
+
+namespace {
+// Must be kept in sync with the shader header.
+const uint32_t MOTION_BLOCK_SIZE = 16;
+
+const string MOTION_SMOOTHING_SHADER_HEADER = R"glsl(#version 300 es
+        precision highp float;
+        precision highp int;
+
+        const int MOTION_BLOCK_SIZE = 16;
+        const int MOTION_SEARCH_RANGE = 8;
+        const int MOTION_SEARCH_STEP = 2;
+        const int MOTION_BLOCK_SAMPLES = 8;
+
+        uniform sampler2D tex0; // previous frame
+        uniform sampler2D tex1; // last frame
+
+        vec4 fetch(sampler2D tex, ivec2 position) {
+            return texelFetch(tex, clamp(position, ivec2(0), textureSize(tex, 0) - 1), 0);
+        }
+    )glsl";
+
+const string COPY_FRAGMENT_SHADER = R"glsl(#version 300 es
+        precision mediump float;
+        uniform sampler2D tex0;
+        in vec2 uv;
+        out vec4 color;
+
+        void main() {
+            color = texture(tex0, uv);
+        }
+    )glsl";
+
+// Tested on the Rust side, in motion_smoothing.rs. The shortest displacement is preferred when
+// multiple ones match equally. The displacement is stored normalized in the red and green channels.
+const string MOTION_ESTIMATION_FRAGMENT_SHADER = R"glsl(
+        out vec4 color;
+
+        const vec3 LUMA_WEIGHTS = vec3(0.299, 0.587, 0.114);
+        const int SAMPLE_SPACING = MOTION_BLOCK_SIZE / MOTION_BLOCK_SAMPLES;
+
+        float blockCost(ivec2 origin, ivec2 displacement) {
+            float cost = 0.0;
+            for (int sy = 0; sy < MOTION_BLOCK_SAMPLES; sy++) {
+                for (int sx = 0; sx < MOTION_BLOCK_SAMPLES; sx++) {
+                    ivec2 position =
+                        origin + ivec2(sx, sy) * SAMPLE_SPACING + ivec2(SAMPLE_SPACING / 2);
+                    cost += abs(dot(fetch(tex0, position).rgb, LUMA_WEIGHTS) -
+                                dot(fetch(tex1, position + displacement).rgb, LUMA_WEIGHTS));
+                }
+            }
+            return cost;
+        }
+
+        void main() {
+            ivec2 origin = ivec2(gl_FragCoord.xy) * MOTION_BLOCK_SIZE;
+
+            float bestCost = blockCost(origin, ivec2(0));
+            ivec2 bestDisplacement = ivec2(0);
+            for (int dy = -MOTION_SEARCH_RANGE; dy <= MOTION_SEARCH_RANGE; dy += MOTION_SEARCH_STEP) {
+                for (int dx = -MOTION_SEARCH_RANGE; dx <= MOTION_SEARCH_RANGE;
+                     dx += MOTION_SEARCH_STEP) {
+                    ivec2 displacement = ivec2(dx, dy);
+                    float cost = blockCost(origin, displacement);
+                    if (cost < bestCost ||
+                        (cost == bestCost && dot(vec2(displacement), vec2(displacement)) <
+                                                 dot(vec2(bestDisplacement), vec2(bestDisplacement)))) {
+                        bestCost = cost;
+                        bestDisplacement = displacement;
+                    }
+                }
+            }
+
+            color = vec4(vec2(bestDisplacement) / float(2 * MOTION_SEARCH_RANGE) + 0.5, 0.0, 1.0);
+        }
+    )glsl";
+
+// Each pixel is fetched along its block motion vector from both frames.
+const string INTERPOLATION_FRAGMENT_SHADER = R"glsl(
+        uniform sampler2D tex2; // motion vectors
+        layout(std140) uniform InterpolationBlock {
+            float interpolationFactor;
+        };
+        out vec4 color;
+
+        void main() {
+            ivec2 position = ivec2(gl_FragCoord.xy);
+
+            if (interpolationFactor >= 1.0) {
+                color = fetch(tex1, position);
+                return;
+            }
+
+            vec2 motion = (texelFetch(tex2, position / MOTION_BLOCK_SIZE, 0).rg - 0.5) *
+                          float(2 * MOTION_SEARCH_RANGE);
+            // Undo the quantization of the motion texture
+            motion = round(motion / float(MOTION_SEARCH_STEP)) * float(MOTION_SEARCH_STEP);
+
+            vec2 previousPosition = vec2(position) - motion * interpolationFactor;
+            vec2 lastPosition = vec2(position) + motion * (1.0 - interpolationFactor);
+
+            color = mix(fetch(tex0, ivec2(round(previousPosition))),
+                        fetch(tex1, ivec2(round(lastPosition))),
+                        interpolationFactor);
+        }
+    )glsl";
+
+struct InterpolationBlock {
+    float interpolationFactor;
+    float padding[3];
+};
+} // namespace
+
+MotionSmoothingPass::MotionSmoothingPass(Texture *inputSurface) : mInputSurface(inputSurface) {}
+
+void MotionSmoothingPass::Initialize(uint32_t width, uint32_t height, GLint outputFormat) {
+    for (int slot = 0; slot < 2; slot++) {
+        mFrames[slot].reset(new Texture(false, 0, false, width * 2, height, outputFormat));
+        mFrameStates[slot] = make_unique<RenderState>(mFrames[slot].get());
+    }
+    mCopyPipeline = make_unique<RenderPipeline>(
+        vector<const Texture *>{mInputSurface}, QUAD_2D_VERTEX_SHADER, COPY_FRAGMENT_SHADER);
+
+    // Linear format, the motion is not a color
+    mMotionTexture.reset(new Texture(false,
+                                     0,
+                                     false,
+                                     (width * 2 + MOTION_BLOCK_SIZE - 1) / MOTION_BLOCK_SIZE,
+                                     (height + MOTION_BLOCK_SIZE - 1) / MOTION_BLOCK_SIZE,
+                                     GL_RGBA8));
+    mMotionTextureState = make_unique<RenderState>(mMotionTexture.get());
+
+    mOutputTexture.reset(new Texture(false, 0, false, width * 2, height, outputFormat));
+    mOutputTextureState = make_unique<RenderState>(mOutputTexture.get());
+
+    for (int lastSlot = 0; lastSlot < 2; lastSlot++) {
+        auto previous = mFrames[1 - lastSlot].get();
+        auto last = mFrames[lastSlot].get();
+
+        mEstimationPipelines[lastSlot] =
+            make_unique<RenderPipeline>(vector<const Texture *>{previous, last},
+                                        QUAD_2D_VERTEX_SHADER,
+                                        MOTION_SMOOTHING_SHADER_HEADER +
+                                            MOTION_ESTIMATION_FRAGMENT_SHADER);
+        mInterpolationPipelines[lastSlot] = make_unique<RenderPipeline>(
+            vector<const Texture *>{previous, last, mMotionTexture.get()},
+            QUAD_2D_VERTEX_SHADER,
+            MOTION_SMOOTHING_SHADER_HEADER + INTERPOLATION_FRAGMENT_SHADER,
+            sizeof(InterpolationBlock));
+    }
+}
+
+void MotionSmoothingPass::PushFrame() {
+    mLastFrameSlot = 1 - mLastFrameSlot;
+    mFramesCount = min(mFramesCount + 1, 2);
+
+    mFrameStates[mLastFrameSlot]->ClearDepth();
+    mCopyPipeline->Render(*mFrameStates[mLastFrameSlot]);
+
+    if (mFramesCount == 2) {
+        mMotionTextureState->ClearDepth();
+        mEstimationPipelines[mLastFrameSlot]->Render(*mMotionTextureState);
+    }
+}
+
+void MotionSmoothingPass::Render(float interpolationFactor) const {
+    if (mFramesCount == 0) {
+        return;
+    }
+
+    InterpolationBlock block = {};
+    // Without motion vectors fall back to duplicating the last frame
+    block.interpolationFactor = mFramesCount == 2 ? interpolationFactor : 1.0f;
+
+    mOutputTextureState->ClearDepth();
+    mInterpolationPipelines[mLastFrameSlot]->Render(*mOutputTextureState, &block);
+}
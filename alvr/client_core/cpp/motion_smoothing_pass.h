@@ -0,0 +1,43 @@
+#pragma once
+
+#include "gl_render_utils/render_pipeline.h"
+#include <cstdint>
+#include <memory>
+
+// Synthesizes intermediate frames between the last two stream frames. The motion of each block is
+// estimated by block matching, since the decoder does not expose its motion vectors. When no motion
+// is available (the first frame after the stream start) the last frame is duplicated.
+class MotionSmoothingPass {
+  public:
+    MotionSmoothingPass(gl_render_utils::Texture *inputSurface);
+
+    void Initialize(uint32_t width, uint32_t height, GLint outputFormat = GL_SRGB8_ALPHA8);
+
+    // Stores the current content of the input texture as the last frame and estimates the motion
+    // from the previous one
+    void PushFrame();
+
+    // Renders the frame at interpolationFactor between the previous (0) and the last (1) frame
+    void Render(float interpolationFactor) const;
+
+    gl_render_utils::Texture *GetOutputTexture() { return mOutputTexture.get(); }
+
+  private:
+    gl_render_utils::Texture *mInputSurface;
+
+    std::unique_ptr<gl_render_utils::Texture> mFrames[2];
+    std::unique_ptr<gl_render_utils::RenderState> mFrameStates[2];
+    std::unique_ptr<gl_render_utils::RenderPipeline> mCopyPipeline;
+
+    std::unique_ptr<gl_render_utils::Texture> mMotionTexture;
+    std::unique_ptr<gl_render_utils::RenderState> mMotionTextureState;
+    // Indexed by the slot of the last frame
+    std::unique_ptr<gl_render_utils::RenderPipeline> mEstimationPipelines[2];
+    std::unique_ptr<gl_render_utils::RenderPipeline> mInterpolationPipelines[2];
+
+    std::unique_ptr<gl_render_utils::Texture> mOutputTexture;
+    std::unique_ptr<gl_render_utils::RenderState> mOutputTextureState;
+
+    int mLastFrameSlot = 0;
+    int mFramesCount = 0;
+};
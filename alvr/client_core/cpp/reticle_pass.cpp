@@ -0,0 +1,80 @@
+#include "reticle_pass.h"
+#include "utils.h"
+#include <cmath>
+#include <cstring>
+
+using namespace std;
+using namespace gl_render_utils;
+
+namespace {
+// Works in the tangent space of the view angles, so the reticle keeps its angular size across the
+// eye image. Pixels outside the shape are discarded and the rest is blended with the opacity.
+const string RETICLE_FRAGMENT_SHADER = R"glsl(#version 300 es
+        precision highp float;
+
+        layout(std140) uniform ReticleBlock {
+            vec4 tangents; // left, down, right, up
+            vec4 reticleColor;
+            vec2 center;
+            float radius;
+            float halfThickness;
+            uint shape;
+        };
+        in vec2 uv;
+        out vec4 color;
+
+        void main() {
+            vec2 offset = abs(mix(tangents.xy, tangents.zw, uv) - center);
+
+            bool inside;
+            if (shape == 0u) {
+                inside = (offset.x <= halfThickness && offset.y <= radius) ||
+                         (offset.y <= halfThickness && offset.x <= radius);
+            } else {
+                inside = length(offset) <= radius;
+            }
+
+            if (!inside) {
+                discard;
+            }
+            color = reticleColor;
+        }
+    )glsl";
+
+struct ReticleBlock {
+    float tangents[4];
+    float reticleColor[4];
+    float center[2];
+    float radius;
+    float halfThickness;
+    uint32_t shape;
+    uint32_t padding[3];
+};
+} // namespace
+
+ReticlePass::ReticlePass() {
+    mPipeline = make_unique<RenderPipeline>(vector<const Texture *>{},
+                                            QUAD_2D_VERTEX_SHADER,
+                                            RETICLE_FRAGMENT_SHADER,
+                                            sizeof(ReticleBlock));
+}
+
+void ReticlePass::Render(const RenderState &renderState,
+                         const FfiViewInput &eyeInput,
+                         const FfiReticle &reticle,
+                         int eye) const {
+    ReticleBlock block = {};
+    block.tangents[0] = tan(eyeInput.fovLeft);
+    block.tangents[1] = tan(eyeInput.fovDown);
+    block.tangents[2] = tan(eyeInput.fovRight);
+    block.tangents[3] = tan(eyeInput.fovUp);
+    memcpy(block.reticleColor, reticle.color, sizeof(block.reticleColor));
+    memcpy(block.center, reticle.centers[eye], sizeof(block.center));
+    block.radius = reticle.radius;
+    block.halfThickness = reticle.halfThickness;
+    block.shape = reticle.shape;
+
+    // The eye image is already in the swapchain, only the depth is cleared
+    renderState.ClearDepth();
+    mPipeline->Render(renderState, &block);
+}
@@ -0,0 +1,20 @@
+#pragma once
+
+#include "bindings.h"
+#include "gl_render_utils/render_pipeline.h"
+#include <memory>
+
+// Draws the reticle on top of the eye images, directly into the swapchain. The per-eye centers
+// are computed on the Rust side by ReticleParams.
+class ReticlePass {
+  public:
+    ReticlePass();
+
+    void Render(const gl_render_utils::RenderState &renderState,
+                const FfiViewInput &eyeInput,
+                const FfiReticle &reticle,
+                int eye) const;
+
+  private:
+    std::unique_ptr<gl_render_utils::RenderPipeline> mPipeline;
+};
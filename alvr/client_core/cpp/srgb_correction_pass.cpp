@@ -53,8 +53,13 @@ const string CORRECTION_FRAGMENT_SHADER = R"glsl(
 
 SrgbCorrectionPass::SrgbCorrectionPass(Texture *inputSurface) : mInputSurface(inputSurface) {}
 
-void SrgbCorrectionPass::Initialize(uint32_t width, uint32_t height, bool passthrough, bool fixLimitedRange, float encodingGamma) {
-    mOutputTexture.reset(new Texture(false, 0, false, width * 2, height));
+void SrgbCorrectionPass::Initialize(uint32_t width,
+                                    uint32_t height,
+                                    bool passthrough,
+                                    bool fixLimitedRange,
+                                    float encodingGamma,
+                                    GLint outputFormat) {
+    mOutputTexture.reset(new Texture(false, 0, false, width * 2, height, outputFormat));
     mOutputTextureState = make_unique<RenderState>(mOutputTexture.get());
 
     string defines = passthrough ? "" : "#define SRGB_CORRECTION";
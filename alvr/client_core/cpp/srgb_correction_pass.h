@@ -9,7 +9,12 @@ class SrgbCorrectionPass {
   public:
     SrgbCorrectionPass(gl_render_utils::Texture *inputSurface);
 
-    void Initialize(uint32_t width, uint32_t height, bool passthrough, bool fixLimitedRange, float encodingGamma);
+    void Initialize(uint32_t width,
+                    uint32_t height,
+                    bool passthrough,
+                    bool fixLimitedRange,
+                    float encodingGamma,
+                    GLint outputFormat = GL_SRGB8_ALPHA8);
 
     void Render() const;
 
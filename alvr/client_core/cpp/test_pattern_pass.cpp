@@ -0,0 +1,83 @@
+#include "test_pattern_pass.h"
+#include "utils.h"
+#include <memory>
+
+using namespace std;
+using namespace gl_render_utils;
+
+namespace {
+const string TEST_PATTERN_FRAGMENT_SHADER_HEADER = R"glsl(#version 300 es
+        precision highp float;
+        precision highp int;)glsl";
+
+// Tested on the Rust side, in test_pattern.rs
+const string TEST_PATTERN_FRAGMENT_SHADER = R"glsl(
+        layout(std140) uniform FrameBlock {
+            uint frameIndex;
+        };
+        in vec2 uv;
+        out vec4 color;
+
+        const uint COLOR_BARS_COUNT = 8u;
+        const uint SCROLL_BAR_PERIOD_FRAMES = 120u;
+        const float SCROLL_BAR_WIDTH = 1.0 / 16.0;
+        const uint CHECKERBOARD_CELLS = 8u;
+        const uint FRAME_COUNTER_BITS = 16u;
+
+        uint cell(float coord, uint count) {
+            return min(uint(coord * float(count)), count - 1u);
+        }
+
+        void main() {
+            // Each eye shows the same pattern
+            vec2 eyeUV = vec2(fract(uv.x * 2.0), uv.y);
+
+#if PATTERN == 0
+            uint bar = cell(eyeUV.x, COLOR_BARS_COUNT);
+            color = vec4(float((bar & 2u) == 0u),
+                         float((bar & 4u) == 0u),
+                         float((bar & 1u) == 0u),
+                         1.0);
+#elif PATTERN == 1
+            float position =
+                float(frameIndex % SCROLL_BAR_PERIOD_FRAMES) / float(SCROLL_BAR_PERIOD_FRAMES);
+            float onBar = float(eyeUV.x >= position && eyeUV.x < position + SCROLL_BAR_WIDTH);
+            color = vec4(vec3(onBar), 1.0);
+#elif PATTERN == 2
+            uint cellSum = cell(eyeUV.x, CHECKERBOARD_CELLS) + cell(eyeUV.y, CHECKERBOARD_CELLS);
+            color = vec4(vec3(float(cellSum % 2u == 0u)), 1.0);
+#else
+            uint bit = FRAME_COUNTER_BITS - 1u - cell(eyeUV.x, FRAME_COUNTER_BITS);
+            color = vec4(vec3(float(((frameIndex >> bit) & 1u) == 1u)), 1.0);
+#endif
+        }
+    )glsl";
+
+struct FrameBlock {
+    uint32_t frameIndex;
+    uint32_t padding[3];
+};
+} // namespace
+
+void TestPatternPass::Initialize(uint32_t width,
+                                 uint32_t height,
+                                 TestPattern pattern,
+                                 GLint outputFormat) {
+    mOutputTexture.reset(new Texture(false, 0, false, width * 2, height, outputFormat));
+    mOutputTextureState = make_unique<RenderState>(mOutputTexture.get());
+
+    string defines = "#define PATTERN " + to_string(pattern);
+
+    auto fragmentShader =
+        TEST_PATTERN_FRAGMENT_SHADER_HEADER + "\n" + defines + "\n" + TEST_PATTERN_FRAGMENT_SHADER;
+    mPipeline = make_unique<RenderPipeline>(
+        vector<const Texture *>{}, QUAD_2D_VERTEX_SHADER, fragmentShader, sizeof(FrameBlock));
+}
+
+void TestPatternPass::Render(uint32_t frameIndex) const {
+    FrameBlock block = {};
+    block.frameIndex = frameIndex;
+
+    mOutputTextureState->ClearDepth();
+    mPipeline->Render(*mOutputTextureState, &block);
+}
@@ -0,0 +1,31 @@
+#pragma once
+
+#include "gl_render_utils/render_pipeline.h"
+#include <cstdint>
+#include <memory>
+
+// Values must match the TestPattern setting
+enum TestPattern {
+    TEST_PATTERN_COLOR_BARS = 0,
+    TEST_PATTERN_SCROLL_BAR = 1,
+    TEST_PATTERN_CHECKERBOARD = 2,
+    TEST_PATTERN_FRAME_COUNTER = 3,
+};
+
+// Generates a side by side test pattern, used in place of the decoded stream for diagnostics.
+class TestPatternPass {
+  public:
+    void Initialize(uint32_t width,
+                    uint32_t height,
+                    TestPattern pattern,
+                    GLint outputFormat = GL_SRGB8_ALPHA8);
+
+    void Render(uint32_t frameIndex) const;
+
+    gl_render_utils::Texture *GetOutputTexture() { return mOutputTexture.get(); }
+
+  private:
+    std::unique_ptr<gl_render_utils::Texture> mOutputTexture;
+    std::unique_ptr<gl_render_utils::RenderState> mOutputTextureState;
+    std::unique_ptr<gl_render_utils::RenderPipeline> mPipeline;
+};
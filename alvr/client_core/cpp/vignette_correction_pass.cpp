@@ -0,0 +1,62 @@
+#include "vignette_correction_pass.h"
+#include "utils.h"
+#include <memory>
+
+using namespace std;
+using namespace gl_render_utils;
+
+namespace {
+const string VIGNETTE_CORRECTION_FRAGMENT_SHADER_HEADER = R"glsl(#version 300 es
+        precision mediump float;)glsl";
+
+// Tested on the Rust side, in vignette_correction.rs
+const string VIGNETTE_CORRECTION_FRAGMENT_SHADER = R"glsl(
+        uniform sampler2D tex0;
+        in vec2 uv;
+        out vec4 color;
+
+        void main() {
+            color = texture(tex0, uv);
+
+            bool isRightEye = uv.x >= 0.5;
+            vec2 eyeUV = vec2(fract(uv.x * 2.0), uv.y);
+
+            // Normalized so that the farthest corner is at distance 1
+            vec2 lensCenter = isRightEye ? RIGHT_LENS_CENTER : LEFT_LENS_CENTER;
+            float radius = length(eyeUV - lensCenter) / length(max(lensCenter, 1.0 - lensCenter));
+
+            float strength = isRightEye ? RIGHT_STRENGTH : LEFT_STRENGTH;
+            float gain = 1.0 + strength * pow(radius, FALLOFF_EXPONENT);
+            color.rgb = min(color.rgb * gain, vec3(1.0));
+        }
+    )glsl";
+} // namespace
+
+VignetteCorrectionPass::VignetteCorrectionPass(Texture *inputSurface)
+    : mInputSurface(inputSurface) {}
+
+void VignetteCorrectionPass::Initialize(uint32_t width,
+                                        uint32_t height,
+                                        VignetteCorrectionData data,
+                                        GLint outputFormat) {
+    mOutputTexture.reset(new Texture(false, 0, false, width * 2, height, outputFormat));
+    mOutputTextureState = make_unique<RenderState>(mOutputTexture.get());
+
+    string defines = "#define LEFT_STRENGTH (" + to_string(data.leftStrength) + ")";
+    defines += "\n#define RIGHT_STRENGTH (" + to_string(data.rightStrength) + ")";
+    defines += "\n#define FALLOFF_EXPONENT (" + to_string(data.falloffExponent) + ")";
+    defines += "\n#define LEFT_LENS_CENTER vec2(" + to_string(data.leftLensCenter[0]) + ", " +
+               to_string(data.leftLensCenter[1]) + ")";
+    defines += "\n#define RIGHT_LENS_CENTER vec2(" + to_string(data.rightLensCenter[0]) + ", " +
+               to_string(data.rightLensCenter[1]) + ")";
+
+    auto fragmentShader = VIGNETTE_CORRECTION_FRAGMENT_SHADER_HEADER + "\n" + defines + "\n" +
+                          VIGNETTE_CORRECTION_FRAGMENT_SHADER;
+    mPipeline = make_unique<RenderPipeline>(
+        vector<const Texture *>{mInputSurface}, QUAD_2D_VERTEX_SHADER, fragmentShader);
+}
+
+void VignetteCorrectionPass::Render() const {
+    mOutputTextureState->ClearDepth();
+    mPipeline->Render(*mOutputTextureState);
+}
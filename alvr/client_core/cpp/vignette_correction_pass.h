@@ -0,0 +1,35 @@
+#pragma once
+
+#include "gl_render_utils/render_pipeline.h"
+#include <cstdint>
+#include <memory>
+
+struct VignetteCorrectionData {
+    float leftStrength;
+    float rightStrength;
+    float falloffExponent;
+    float leftLensCenter[2];
+    float rightLensCenter[2];
+};
+
+// Brightens the edges of each eye image to compensate for lens vignetting. The input is the side by
+// side stream texture, each half is corrected independently, around the center of its lens.
+class VignetteCorrectionPass {
+  public:
+    VignetteCorrectionPass(gl_render_utils::Texture *inputSurface);
+
+    void Initialize(uint32_t width,
+                    uint32_t height,
+                    VignetteCorrectionData data,
+                    GLint outputFormat = GL_SRGB8_ALPHA8);
+
+    void Render() const;
+
+    gl_render_utils::Texture *GetOutputTexture() { return mOutputTexture.get(); }
+
+  private:
+    gl_render_utils::Texture *mInputSurface;
+    std::unique_ptr<gl_render_utils::Texture> mOutputTexture;
+    std::unique_ptr<gl_render_utils::RenderState> mOutputTextureState;
+    std::unique_ptr<gl_render_utils::RenderPipeline> mPipeline;
+};
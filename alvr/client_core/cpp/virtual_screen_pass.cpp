@@ -0,0 +1,104 @@
+#include "virtual_screen_pass.h"
+#include "utils.h"
+#include <cmath>
+#include <cstring>
+#include <glm/gtc/quaternion.hpp>
+#include <glm/gtc/type_ptr.hpp>
+
+using namespace std;
+using namespace gl_render_utils;
+
+namespace {
+const string VIRTUAL_SCREEN_FRAGMENT_SHADER_HEADER = R"glsl(#version 300 es
+        precision highp float;)glsl";
+
+// Cast a ray from the eye for each pixel and find the intersection with the screen, either a plane
+// or a vertical cylinder. Tested on the Rust side, in virtual_screen.rs
+const string VIRTUAL_SCREEN_FRAGMENT_SHADER = R"glsl(
+        layout(std140) uniform ViewBlock {
+            mat4 eyeRotation;
+            vec4 eyePosition;
+            vec4 tangents; // left, down, right, up
+        };
+        uniform sampler2D tex0;
+        in vec2 uv;
+        out vec4 color;
+
+        void main() {
+            vec2 tangent = mix(tangents.xy, tangents.zw, uv);
+            vec3 direction = mat3(eyeRotation) * vec3(tangent, -1.0);
+            vec3 origin = eyePosition.xyz;
+
+            float t;
+            vec2 screenUV;
+#ifdef RADIUS
+            float centerZ = RADIUS - DISTANCE;
+            vec2 originXZ = vec2(origin.x, origin.z - centerZ);
+            float a = dot(direction.xz, direction.xz);
+            float b = 2.0 * dot(originXZ, direction.xz);
+            float c = dot(originXZ, originXZ) - RADIUS * RADIUS;
+            float discriminant = b * b - 4.0 * a * c;
+
+            t = (-b + sqrt(max(discriminant, 0.0))) / (2.0 * a);
+            vec3 point = origin + t * direction;
+            screenUV = vec2(atan(point.x, centerZ - point.z) * RADIUS / WIDTH + 0.5,
+                            0.5 - point.y / HEIGHT);
+            if (discriminant < 0.0) {
+                t = -1.0;
+            }
+#else
+            t = (-DISTANCE - origin.z) / direction.z;
+            vec3 point = origin + t * direction;
+            screenUV = vec2(point.x / WIDTH + 0.5, 0.5 - point.y / HEIGHT);
+#endif
+
+            if (t > 0.0 && all(greaterThanEqual(screenUV, vec2(0.0))) &&
+                all(lessThanEqual(screenUV, vec2(1.0)))) {
+                // The source is the left half of the stream texture
+                color = vec4(texture(tex0, vec2(screenUV.x * 0.5, screenUV.y)).rgb, 1.0);
+            } else {
+                color = vec4(0.0, 0.0, 0.0, 1.0);
+            }
+        }
+    )glsl";
+
+struct ViewBlock {
+    float eyeRotation[16];
+    float eyePosition[4];
+    float tangents[4];
+};
+} // namespace
+
+VirtualScreenPass::VirtualScreenPass(Texture *inputSurface) : mInputSurface(inputSurface) {}
+
+void VirtualScreenPass::Initialize(VirtualScreenData data) {
+    string defines = "#define DISTANCE (" + to_string(data.distance) + ")";
+    defines += "\n#define WIDTH (" + to_string(data.width) + ")";
+    defines += "\n#define HEIGHT (" + to_string(data.height) + ")";
+    if (data.curvature > 0.0) {
+        defines += "\n#define RADIUS (" + to_string(data.distance / data.curvature) + ")";
+    }
+
+    auto fragmentShader = VIRTUAL_SCREEN_FRAGMENT_SHADER_HEADER + "\n" + defines + "\n" +
+                          VIRTUAL_SCREEN_FRAGMENT_SHADER;
+    mPipeline = make_unique<RenderPipeline>(vector<const Texture *>{mInputSurface},
+                                            QUAD_2D_VERTEX_SHADER,
+                                            fragmentShader,
+                                            sizeof(ViewBlock));
+}
+
+void VirtualScreenPass::Render(const RenderState &renderState, const FfiViewInput &eyeInput) const {
+    auto o = eyeInput.orientation;
+    auto rotation = glm::mat4_cast(glm::quat(o[3], o[0], o[1], o[2]));
+
+    ViewBlock block = {};
+    memcpy(block.eyeRotation, glm::value_ptr(rotation), sizeof(block.eyeRotation));
+    memcpy(block.eyePosition, eyeInput.position, sizeof(eyeInput.position));
+    block.tangents[0] = tan(eyeInput.fovLeft);
+    block.tangents[1] = tan(eyeInput.fovDown);
+    block.tangents[2] = tan(eyeInput.fovRight);
+    block.tangents[3] = tan(eyeInput.fovUp);
+
+    renderState.ClearDepth();
+    mPipeline->Render(renderState, &block);
+}
@@ -0,0 +1,28 @@
+#pragma once
+
+#include "bindings.h"
+#include "gl_render_utils/render_pipeline.h"
+#include <memory>
+
+struct VirtualScreenData {
+    float distance;
+    float width;
+    float height;
+    float curvature; // 0 for flat screens
+};
+
+// Renders the left view of the input texture on a head locked virtual screen, for each eye.
+class VirtualScreenPass {
+  public:
+    VirtualScreenPass(gl_render_utils::Texture *inputSurface);
+
+    void Initialize(VirtualScreenData data);
+
+    // eyeInput pose must be relative to the head
+    void Render(const gl_render_utils::RenderState &renderState,
+                const FfiViewInput &eyeInput) const;
+
+  private:
+    gl_render_utils::Texture *mInputSurface;
+    std::unique_ptr<gl_render_utils::RenderPipeline> mPipeline;
+};
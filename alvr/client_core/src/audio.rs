@@ -1,4 +1,4 @@
-use alvr_audio::{AudioDevice, AudioRecordState};
+use alvr_audio::{AudioDevice, AudioPacketHeader, AudioRecordState};
 use alvr_common::{
     anyhow::{bail, Result},
     parking_lot::Mutex,
@@ -15,7 +15,7 @@ use std::{collections::VecDeque, mem, sync::Arc, thread, time::Duration};
 
 struct RecorderCallback {
     is_running: Arc<dyn Fn() -> bool + Send + Sync>,
-    sender: StreamSender<()>,
+    sender: StreamSender<AudioPacketHeader>,
     state: Arc<Mutex<AudioRecordState>>,
 }
 
@@ -34,7 +34,10 @@ impl AudioInputCallback for RecorderCallback {
         }
 
         if (self.is_running)() {
-            let mut buffer = self.sender.get_buffer(&()).unwrap();
+            let mut buffer = self
+                .sender
+                .get_buffer(&AudioPacketHeader { channels_count: 1 })
+                .unwrap();
             buffer
                 .get_range_mut(0, sample_buffer.len())
                 .copy_from_slice(&sample_buffer);
@@ -60,7 +63,7 @@ impl AudioInputCallback for RecorderCallback {
 #[allow(unused_variables)]
 pub fn record_audio_blocking(
     is_running: Arc<dyn Fn() -> bool + Send + Sync>,
-    sender: StreamSender<()>,
+    sender: StreamSender<AudioPacketHeader>,
     device: &AudioDevice,
     channels_count: u16,
     mute: bool,
@@ -161,7 +164,7 @@ pub fn play_audio_loop(
     channels_count: u16,
     sample_rate: u32,
     config: AudioBufferingConfig,
-    receiver: &mut StreamReceiver<()>,
+    receiver: &mut StreamReceiver<AudioPacketHeader>,
 ) -> Result<()> {
     // the client sends invalid sample rates sometimes, and we crash if we try and use one
     // (batch_frames_count ends up zero and the audio callback gets confused)
@@ -1,5 +1,8 @@
 use crate::{
-    graphics::{GraphicsContext, LobbyRenderer, RenderViewInput, StreamRenderer},
+    frame_user_data::FrameUserDataQueue,
+    graphics::{
+        self, GraphicsContext, LobbyRenderer, RenderViewInput, StreamPassesConfig, StreamRenderer,
+    },
     storage, ClientCapabilities, ClientCoreContext, ClientCoreEvent,
 };
 use alvr_common::{
@@ -8,7 +11,7 @@ use alvr_common::{
     info,
     once_cell::sync::Lazy,
     parking_lot::Mutex,
-    warn, DeviceMotion, Fov, OptLazy, Pose,
+    warn, DeviceMotion, Fov, OptLazy, Pose, RngSource,
 };
 use alvr_packets::{ButtonEntry, ButtonValue, FaceData, ViewParams};
 use alvr_session::{CodecType, FoveatedEncodingConfig};
@@ -25,9 +28,12 @@ use std::{
 static CLIENT_CORE_CONTEXT: OptLazy<ClientCoreContext> = alvr_common::lazy_mut_none();
 static HUD_MESSAGE: Lazy<Mutex<String>> = Lazy::new(|| Mutex::new("".into()));
 static SETTINGS: Lazy<Mutex<String>> = Lazy::new(|| Mutex::new("".into()));
+static RNG_SEED: Lazy<Mutex<Option<u64>>> = Lazy::new(|| Mutex::new(None));
 #[allow(clippy::type_complexity)]
 static NAL_QUEUE: Lazy<Mutex<VecDeque<(u64, [ViewParams; 2], Vec<u8>)>>> =
     Lazy::new(|| Mutex::new(VecDeque::new()));
+static FRAME_USER_DATA: Lazy<Mutex<FrameUserDataQueue>> =
+    Lazy::new(|| Mutex::new(FrameUserDataQueue::new()));
 
 // Core interface:
 
@@ -42,6 +48,8 @@ pub struct AlvrClientCapabilities {
     encoder_high_profile: bool,
     encoder_10_bits: bool,
     encoder_av1: bool,
+    // The swapchain supports a 10 bit format, see alvr_supports_10_bit_swapchain()
+    display_10_bits: bool,
 }
 
 #[repr(u8)]
@@ -72,6 +80,13 @@ pub enum AlvrEvent {
         codec: AlvrCodec,
     },
     FrameReady,
+    EyeCalibration {
+        left: AlvrPose,
+        right: AlvrPose,
+    },
+    StreamPaused {
+        paused: bool,
+    },
 }
 
 #[repr(C)]
@@ -241,6 +256,18 @@ pub unsafe extern "C" fn alvr_initialize_android_context(
     ndk_context::initialize_android_context(java_vm, context);
 }
 
+/// Whether the swapchain formats, as OpenGL internal formats, contain a 10 bit format. Use the
+/// result for the display_10_bits capability.
+#[no_mangle]
+pub unsafe extern "C" fn alvr_supports_10_bit_swapchain(
+    formats: *const u32,
+    formats_count: u64,
+) -> bool {
+    let formats = (!formats.is_null()).then(|| slice::from_raw_parts(formats, formats_count as _));
+
+    graphics::supports_10_bit_swapchain(formats)
+}
+
 /// On android, alvr_initialize_android_context() must be called first, then alvr_initialize().
 #[no_mangle]
 pub unsafe extern "C" fn alvr_initialize(capabilities: AlvrClientCapabilities) {
@@ -263,6 +290,8 @@ pub unsafe extern "C" fn alvr_initialize(capabilities: AlvrClientCapabilities) {
         encoder_high_profile: capabilities.encoder_high_profile,
         encoder_10_bits: capabilities.encoder_10_bits,
         encoder_av1: capabilities.encoder_av1,
+        runtime_foveation: false,
+        display_10_bits: capabilities.display_10_bits,
     };
     *CLIENT_CORE_CONTEXT.lock() = Some(ClientCoreContext::new(capabilities));
 }
@@ -305,6 +334,7 @@ pub extern "C" fn alvr_poll_event(out_event: *mut AlvrEvent) -> bool {
                     negotiated_config,
                 } => {
                     *SETTINGS.lock() = serde_json::to_string(&settings).unwrap();
+                    *RNG_SEED.lock() = settings.extra.rng_seed.as_option().copied();
 
                     AlvrEvent::StreamingStarted {
                         view_width: negotiated_config.view_resolution.x,
@@ -342,13 +372,20 @@ pub extern "C" fn alvr_poll_event(out_event: *mut AlvrEvent) -> bool {
                     timestamp,
                     view_params,
                     nal,
+                    user_data,
                 } => {
+                    FRAME_USER_DATA.lock().report_frame(timestamp, user_data);
                     NAL_QUEUE
                         .lock()
                         .push_back((timestamp.as_nanos() as _, view_params, nal));
 
                     AlvrEvent::FrameReady
                 }
+                ClientCoreEvent::EyeCalibration([left, right]) => AlvrEvent::EyeCalibration {
+                    left: to_capi_pose(left),
+                    right: to_capi_pose(right),
+                },
+                ClientCoreEvent::StreamPaused(paused) => AlvrEvent::StreamPaused { paused },
             };
 
             unsafe { *out_event = event };
@@ -439,6 +476,20 @@ pub extern "C" fn alvr_send_playspace(width: f32, height: f32) {
     }
 }
 
+#[no_mangle]
+pub extern "C" fn alvr_set_stream_paused(paused: bool) {
+    if let Some(context) = &*CLIENT_CORE_CONTEXT.lock() {
+        context.set_stream_paused(paused);
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn alvr_send_headset_presence(present: bool) {
+    if let Some(context) = &*CLIENT_CORE_CONTEXT.lock() {
+        context.send_headset_presence(present);
+    }
+}
+
 #[no_mangle]
 pub extern "C" fn alvr_send_active_interaction_profile(device_id: u64, profile_id: u64) {
     if let Some(context) = &*CLIENT_CORE_CONTEXT.lock() {
@@ -657,6 +708,10 @@ pub unsafe extern "C" fn alvr_get_frame(
             };
             *out_buffer = decoded_frame.buffer_ptr as _;
 
+            FRAME_USER_DATA
+                .lock()
+                .report_frame(decoded_frame.timestamp, decoded_frame.user_data);
+
             decoded_frame.timestamp.as_nanos() as _
         } else {
             -1
@@ -666,6 +721,20 @@ pub unsafe extern "C" fn alvr_get_frame(
     }
 }
 
+/// Returns the size of the user data attached by the server to the frame, 0 if there is none.
+/// Call after the frame is returned by alvr_get_frame() or alvr_poll_nal(). out_buffer can be null.
+#[no_mangle]
+pub unsafe extern "C" fn alvr_get_frame_user_data(timestamp_ns: u64, out_buffer: *mut u8) -> u64 {
+    let user_data = FRAME_USER_DATA
+        .lock()
+        .frame_user_data(Duration::from_nanos(timestamp_ns));
+    if !out_buffer.is_null() {
+        ptr::copy_nonoverlapping(user_data.as_ptr(), out_buffer, user_data.len());
+    }
+
+    user_data.len() as u64
+}
+
 // OpenGL-related interface
 
 thread_local! {
@@ -777,11 +846,15 @@ pub unsafe extern "C" fn alvr_start_stream_opengl(config: AlvrStreamConfig) {
     STREAM_RENDERER.set(Some(StreamRenderer::new(
         GRAPHICS_CONTEXT.with_borrow(|c| c.as_ref().unwrap().clone()),
         view_resolution,
+        view_resolution,
         swapchain_textures,
         foveated_encoding,
         true,
         false, // TODO: limited range fix config
         1.0,   // TODO: encoding gamma config
+        false,
+        StreamPassesConfig::default(),
+        RngSource::from_env_or(*RNG_SEED.lock()),
     )));
 }
 
@@ -812,11 +885,23 @@ pub unsafe extern "C" fn alvr_render_stream_opengl(
     hardware_buffer: *mut c_void,
     swapchain_indices: *const u32,
 ) {
-    STREAM_RENDERER.with_borrow(|renderer| {
+    STREAM_RENDERER.with_borrow_mut(|renderer| {
         if let Some(renderer) = renderer {
+            // Note: view poses are used only by the virtual screen, which is not supported here
             renderer.render(
                 hardware_buffer,
-                [*swapchain_indices, *swapchain_indices.offset(1)],
+                [
+                    RenderViewInput {
+                        pose: Pose::default(),
+                        fov: Fov::default(),
+                        swapchain_index: *swapchain_indices,
+                    },
+                    RenderViewInput {
+                        pose: Pose::default(),
+                        fov: Fov::default(),
+                        swapchain_index: *swapchain_indices.offset(1),
+                    },
+                ],
             );
         }
     });
@@ -2,11 +2,16 @@
 
 use crate::{
     decoder::{self, DecoderConfig, DecoderSink, DecoderSource},
+    foveation::FoveationSync,
+    frame_references::{FrameAction, FrameReferenceTracker},
+    frame_user_data::FrameUserDataQueue,
+    graphics,
     logging_backend::{LogMirrorData, LOG_CHANNEL_SENDER},
     platform,
     sockets::AnnouncerSocket,
     statistics::StatisticsManager,
     storage::Config,
+    video_slices::SliceAssembler,
     ClientCapabilities, ClientCoreEvent,
 };
 use alvr_audio::AudioDevice;
@@ -19,9 +24,9 @@ use alvr_common::{
 use alvr_packets::{
     ClientConnectionResult, ClientControlPacket, ClientStatistics, Haptics, ServerControlPacket,
     StreamConfigPacket, Tracking, VideoPacketHeader, VideoStreamingCapabilities, ViewParams, AUDIO,
-    HAPTICS, STATISTICS, TRACKING, VIDEO,
+    HAPTICS, MAX_FRAME_USER_DATA_SIZE, STATISTICS, TRACKING, VIDEO,
 };
-use alvr_session::settings_schema::Switch;
+use alvr_session::{settings_schema::Switch, VideoLossRecovery};
 use alvr_sockets::{
     ControlSocketSender, PeerType, ProtoControlSocket, StreamSender, StreamSocketBuilder,
     KEEPALIVE_INTERVAL, KEEPALIVE_TIMEOUT,
@@ -48,6 +53,7 @@ const STREAM_STARTING_MESSAGE: &str = "The stream will begin soon\nPlease wait..
 const SERVER_RESTART_MESSAGE: &str = "The streamer is restarting\nPlease wait...";
 const SERVER_DISCONNECTED_MESSAGE: &str = "The streamer has disconnected.";
 const CONNECTION_TIMEOUT_MESSAGE: &str = "Connection timeout.";
+const STREAM_PAUSED_MESSAGE: &str = "The stream is paused.";
 
 const SOCKET_INIT_RETRY_INTERVAL: Duration = Duration::from_millis(500);
 const CONNECTION_RETRY_INTERVAL: Duration = Duration::from_secs(1);
@@ -69,6 +75,8 @@ pub struct ConnectionContext {
     // todo: the server is supposed to receive and send view configs for each frame
     pub view_params_queue: RwLock<VecDeque<(Duration, [ViewParams; 2])>>,
     pub last_good_view_params: RwLock<[ViewParams; 2]>,
+    pub foveation_sync: Mutex<Option<FoveationSync>>,
+    pub frame_user_data: Mutex<FrameUserDataQueue>,
 }
 
 fn set_hud_message(event_queue: &Mutex<VecDeque<ClientCoreEvent>>, message: &str) {
@@ -172,6 +180,13 @@ fn connection_pipeline(
                     encoder_high_profile: capabilities.encoder_high_profile,
                     encoder_10_bits: capabilities.encoder_10_bits,
                     encoder_av1: capabilities.encoder_av1,
+                    supports_runtime_foveation: capabilities.runtime_foveation,
+                    max_bit_depth: if capabilities.encoder_10_bits && capabilities.display_10_bits {
+                        10
+                    } else {
+                        8
+                    },
+                    supports_separate_audio_socket: true,
                 })
                 .to_con()?,
             ),
@@ -188,6 +203,13 @@ fn connection_pipeline(
         negotiated_config: negotiated_config.clone(),
     };
 
+    *ctx.foveation_sync.lock() = Some(FoveationSync::new(
+        negotiated_config
+            .enable_foveated_encoding
+            .then(|| settings.video.foveated_encoding.as_option().cloned())
+            .flatten(),
+    ));
+
     *ctx.statistics_manager.lock() = Some(StatisticsManager::new(
         settings.connection.statistics_history_size,
         Duration::from_secs_f32(1.0 / negotiated_config.refresh_rate_hint),
@@ -233,6 +255,21 @@ fn connection_pipeline(
         settings.connection.client_recv_buffer_bytes,
     )
     .to_con()?;
+    let audio_socket_builder = if let Some(port) = negotiated_config.audio_port {
+        Some(
+            StreamSocketBuilder::listen_for_server(
+                Duration::from_secs(1),
+                port,
+                settings.connection.stream_protocol,
+                settings.connection.dscp,
+                settings.connection.client_send_buffer_bytes,
+                settings.connection.client_recv_buffer_bytes,
+            )
+            .to_con()?,
+        )
+    } else {
+        None
+    };
 
     if let Err(e) = control_sender.send(&ClientControlPacket::StreamReady) {
         info!("Server disconnected. Cause: {e:?}");
@@ -243,25 +280,50 @@ fn connection_pipeline(
     let mut stream_socket = stream_socket_builder.accept_from_server(
         server_ip,
         settings.connection.stream_port,
-        settings.connection.packet_size as _,
+        negotiated_config.packet_size,
         HANDSHAKE_ACTION_TIMEOUT,
     )?;
+    // The server connects the audio socket after the stream socket
+    let mut audio_socket =
+        if let (Some(builder), Some(port)) = (audio_socket_builder, negotiated_config.audio_port) {
+            Some(builder.accept_from_server(
+                server_ip,
+                port,
+                negotiated_config.packet_size,
+                HANDSHAKE_ACTION_TIMEOUT,
+            )?)
+        } else {
+            None
+        };
 
     info!("Connected to server");
 
-    let mut video_receiver =
-        stream_socket.subscribe_to_stream::<VideoPacketHeader>(VIDEO, MAX_UNREAD_PACKETS);
-    let mut game_audio_receiver = stream_socket.subscribe_to_stream(AUDIO, MAX_UNREAD_PACKETS);
+    let mut video_receiver = if let VideoLossRecovery::Nack { max_age_ms, .. } =
+        settings.connection.video_loss_recovery
+    {
+        stream_socket.subscribe_to_stream_with_nacks::<VideoPacketHeader>(
+            VIDEO,
+            MAX_UNREAD_PACKETS,
+            Duration::from_millis(max_age_ms),
+        )
+    } else {
+        stream_socket.subscribe_to_stream::<VideoPacketHeader>(VIDEO, MAX_UNREAD_PACKETS)
+    };
+    let mut game_audio_receiver = audio_socket
+        .as_mut()
+        .unwrap_or(&mut stream_socket)
+        .subscribe_to_stream(AUDIO, MAX_UNREAD_PACKETS);
     let tracking_sender = stream_socket.request_stream(TRACKING);
     let mut haptics_receiver =
         stream_socket.subscribe_to_stream::<Haptics>(HAPTICS, MAX_UNREAD_PACKETS);
     let statistics_sender = stream_socket.request_stream(STATISTICS);
 
+    let mut frame_references = FrameReferenceTracker::new(negotiated_config.reference_structure);
+    let mut slice_assembler = SliceAssembler::default();
     let video_receive_thread = thread::spawn({
         let ctx = Arc::clone(&ctx);
         let event_queue = Arc::clone(&event_queue);
         move || {
-            let mut stream_corrupted = false;
             while is_streaming(&ctx) {
                 let data = match video_receiver.recv(STREAMING_RECV_TIMEOUT) {
                     Ok(data) => data,
@@ -272,52 +334,117 @@ fn connection_pipeline(
                     return;
                 };
 
-                if let Some(stats) = &mut *ctx.statistics_manager.lock() {
-                    stats.report_video_packet_received(header.timestamp);
-                }
-
-                if header.is_idr {
-                    stream_corrupted = false;
-                } else if data.had_packet_loss() {
-                    stream_corrupted = true;
-                    if let Some(sender) = &mut *ctx.control_sender.lock() {
-                        sender.send(&ClientControlPacket::RequestIdr).ok();
+                // The frame references decide if an IDR is needed, the loss is only counted for
+                // the congestion controller
+                if data.had_packet_loss() {
+                    if let Some(stats) = &mut *ctx.statistics_manager.lock() {
+                        stats.report_video_packet_loss();
                     }
                     warn!("Network dropped video packet");
                 }
 
-                if !stream_corrupted || !settings.connection.avoid_video_glitching {
-                    if capabilities.external_decoder {
-                        let mut view_params = *ctx.last_good_view_params.read();
-                        for (timestamp, views) in &*ctx.view_params_queue.read() {
-                            if *timestamp == header.timestamp {
-                                view_params = *views;
-                                break;
-                            }
-                        }
-                        event_queue.lock().push_back(ClientCoreEvent::FrameReady {
-                            timestamp: header.timestamp,
-                            view_params,
-                            nal: nal.to_vec(),
-                        });
-                    } else if !ctx
-                        .decoder_sink
+                for frame in slice_assembler.push_slice(header, nal) {
+                    if let Some(stats) = &mut *ctx.statistics_manager.lock() {
+                        stats.report_video_packet_received(frame.header.timestamp);
+                    }
+
+                    // The frame cannot be rendered without knowing its foveated encoding. The next
+                    // frame of the same epoch must be an IDR, since this one is not decoded
+                    let foveation_known = ctx
+                        .foveation_sync
                         .lock()
                         .as_mut()
-                        .map(|sink| sink.push_nal(header.timestamp, nal))
-                        .unwrap_or(false)
-                    {
-                        stream_corrupted = true;
+                        .map(|sync| {
+                            sync.report_frame(frame.header.timestamp, frame.header.foveation_epoch)
+                        })
+                        .unwrap_or(true);
+                    if !foveation_known {
+                        frame_references.report_undecodable();
                         if let Some(sender) = &mut *ctx.control_sender.lock() {
                             sender.send(&ClientControlPacket::RequestIdr).ok();
                         }
-                        warn!("Dropped video packet. Reason: Decoder saturation")
+                        warn!("Dropped video packet. Reason: Unknown foveated encoding");
+                        continue;
                     }
-                } else {
-                    if let Some(sender) = &mut *ctx.control_sender.lock() {
-                        sender.send(&ClientControlPacket::RequestIdr).ok();
+
+                    let user_data = if frame.header.user_data.len() <= MAX_FRAME_USER_DATA_SIZE {
+                        frame.header.user_data.clone()
+                    } else {
+                        warn!("Ignoring frame user data. Reason: Too big");
+                        vec![]
+                    };
+                    if !capabilities.external_decoder {
+                        ctx.frame_user_data
+                            .lock()
+                            .report_frame(frame.header.timestamp, user_data.clone());
+                    }
+
+                    // The lost region propagates to the next frames through the references
+                    if frame.missing_slices > 0 {
+                        if let Some(sender) = &mut *ctx.control_sender.lock() {
+                            sender.send(&ClientControlPacket::RequestIdr).ok();
+                        }
+
+                        if settings.connection.avoid_video_glitching {
+                            frame_references.report_undecodable();
+                            warn!("Dropped video packet. Reason: Lost slices");
+                            continue;
+                        }
+
+                        warn!(
+                            "Decoding partial frame. Lost {} of {} slices",
+                            frame.missing_slices, frame.header.slices_count
+                        );
+                    }
+
+                    // Lost frames invalidate only the frames that depend on them
+                    let frame_action = frame_references
+                        .report_frame(frame.header.frame_index, frame.header.is_idr);
+                    if frame_action == FrameAction::WaitForIdr {
+                        if let Some(sender) = &mut *ctx.control_sender.lock() {
+                            sender.send(&ClientControlPacket::RequestIdr).ok();
+                        }
+                    }
+
+                    if frame_action == FrameAction::Decode
+                        || !settings.connection.avoid_video_glitching
+                    {
+                        if capabilities.external_decoder {
+                            let mut view_params = *ctx.last_good_view_params.read();
+                            for (timestamp, views) in &*ctx.view_params_queue.read() {
+                                if *timestamp == frame.header.timestamp {
+                                    view_params = *views;
+                                    break;
+                                }
+                            }
+                            event_queue.lock().push_back(ClientCoreEvent::FrameReady {
+                                timestamp: frame.header.timestamp,
+                                view_params,
+                                nal: frame.nal,
+                                user_data,
+                            });
+                        } else if let Some(request_idr) = match &mut *ctx.decoder_sink.lock() {
+                            Some(sink) => (!sink.push_nal(
+                                frame.header.timestamp,
+                                frame.header.is_idr,
+                                &frame.nal,
+                            ))
+                            .then(|| sink.should_request_idr()),
+                            None => Some(true),
+                        } {
+                            frame_references.report_undecodable();
+                            if request_idr {
+                                if let Some(sender) = &mut *ctx.control_sender.lock() {
+                                    sender.send(&ClientControlPacket::RequestIdr).ok();
+                                }
+                            }
+                            warn!("Dropped video packet. Reason: Decoder saturation")
+                        }
+                    } else if frame_action == FrameAction::Skip {
+                        warn!("Dropped video packet. Reason: Lost reference frame")
+                    } else {
+                        warn!("Dropped video packet. Reason: Waiting for IDR frame")
                     }
-                    warn!("Dropped video packet. Reason: Waiting for IDR frame")
                 }
             }
         }
@@ -332,7 +459,7 @@ fn connection_pipeline(
                     alvr_common::show_err(audio::play_audio_loop(
                         || is_streaming(&ctx),
                         &device,
-                        2,
+                        negotiated_config.game_audio_channels_count,
                         negotiated_config.game_audio_sample_rate,
                         config.buffering.clone(),
                         &mut game_audio_receiver,
@@ -347,7 +474,10 @@ fn connection_pipeline(
     let microphone_thread = if matches!(settings.audio.microphone, Switch::Enabled(_)) {
         let device = AudioDevice::new_input(None).to_con()?;
 
-        let microphone_sender = stream_socket.request_stream(AUDIO);
+        let microphone_sender = audio_socket
+            .as_mut()
+            .unwrap_or(&mut stream_socket)
+            .request_stream(AUDIO);
 
         thread::spawn({
             let ctx = Arc::clone(&ctx);
@@ -459,6 +589,7 @@ fn connection_pipeline(
         let disconnect_notif = Arc::clone(&disconnect_notif);
         move || {
             let mut disconnection_deadline = Instant::now() + KEEPALIVE_TIMEOUT;
+            let mut last_decoder_config_buffer = None;
             while is_streaming(&ctx) {
                 let maybe_packet = control_receiver.recv(STREAMING_RECV_TIMEOUT);
 
@@ -471,7 +602,13 @@ fn connection_pipeline(
                                     codec: config.codec,
                                     config_nal: config.config_buffer,
                                 });
-                        } else if ctx.decoder_sink.lock().is_none() {
+                        } else if ctx.decoder_sink.lock().is_none()
+                            || last_decoder_config_buffer.as_ref() != Some(&config.config_buffer)
+                        {
+                            // The encoder is restarted with a different resolution when the
+                            // foveated encoding changes
+                            last_decoder_config_buffer = Some(config.config_buffer.clone());
+
                             let config = DecoderConfig {
                                 codec: config.codec,
                                 force_software_decoder: settings.video.force_software_decoder,
@@ -479,6 +616,13 @@ fn connection_pipeline(
                                 buffering_history_weight: settings.video.buffering_history_weight,
                                 options: settings.video.mediacodec_extra_options.clone(),
                                 config_buffer: config.config_buffer,
+                                parallel_sessions: settings.video.parallel_decoder_sessions
+                                    as usize,
+                                max_queue_frames: settings
+                                    .video
+                                    .max_decode_queue_frames
+                                    .as_option()
+                                    .map(|frames| *frames as usize),
                             };
 
                             let (sink, source) = decoder::create_decoder(config, {
@@ -503,6 +647,26 @@ fn connection_pipeline(
                         set_hud_message(&event_queue, SERVER_RESTART_MESSAGE);
                         disconnect_notif.notify_one();
                     }
+                    Ok(ServerControlPacket::FoveatedEncoding { epoch, config }) => {
+                        if let Some(sync) = &mut *ctx.foveation_sync.lock() {
+                            sync.report_config(epoch, config);
+                        }
+                    }
+                    Ok(ServerControlPacket::StreamPaused(paused)) => {
+                        if paused {
+                            set_hud_message(&event_queue, STREAM_PAUSED_MESSAGE);
+                        }
+                        event_queue
+                            .lock()
+                            .push_back(ClientCoreEvent::StreamPaused(paused));
+                    }
+                    Ok(ServerControlPacket::EyeCalibration(config)) => {
+                        event_queue
+                            .lock()
+                            .push_back(ClientCoreEvent::EyeCalibration(
+                                graphics::eye_calibration_poses(config.as_ref()),
+                            ));
+                    }
                     Ok(_) => (),
                     Err(ConnectionError::TryAgain(_)) => {
                         if Instant::now() > disconnection_deadline {
@@ -544,6 +708,29 @@ fn connection_pipeline(
         }
     });
 
+    let audio_receive_thread = if let Some(mut audio_socket) = audio_socket {
+        thread::spawn({
+            let ctx = Arc::clone(&ctx);
+            let event_queue = Arc::clone(&event_queue);
+            let disconnect_notif = Arc::clone(&disconnect_notif);
+            move || {
+                while is_streaming(&ctx) {
+                    match audio_socket.recv() {
+                        Ok(()) => (),
+                        Err(ConnectionError::TryAgain(_)) => continue,
+                        Err(e) => {
+                            info!("Client disconnected. Cause: {e}");
+                            set_hud_message(&event_queue, SERVER_DISCONNECTED_MESSAGE);
+                            disconnect_notif.notify_one();
+                        }
+                    }
+                }
+            }
+        })
+    } else {
+        thread::spawn(|| ())
+    };
+
     *ctx.control_sender.lock() = Some(control_sender);
     *ctx.tracking_sender.lock() = Some(tracking_sender);
     *ctx.statistics_sender.lock() = Some(statistics_sender);
@@ -560,6 +747,7 @@ fn connection_pipeline(
     // Make sure IPD and FoV are resent after reconnection
     // todo: send this data as part of the connection handshake
     ctx.view_params_queue.write().clear();
+    *ctx.frame_user_data.lock() = FrameUserDataQueue::new();
 
     // Unlock CONNECTION_STATE and block thread
     wait_rwlock(&disconnect_notif, &mut connection_state_lock);
@@ -577,6 +765,7 @@ fn connection_pipeline(
 
     *ctx.decoder_sink.lock() = None;
     *ctx.decoder_source.lock() = None;
+    *ctx.foveation_sync.lock() = None;
 
     // Remove lock to allow threads to properly exit:
     drop(connection_state_lock);
@@ -588,6 +777,7 @@ fn connection_pipeline(
     control_send_thread.join().ok();
     control_receive_thread.join().ok();
     stream_receive_thread.join().ok();
+    audio_receive_thread.join().ok();
 
     Ok(())
 }
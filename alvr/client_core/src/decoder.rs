@@ -1,6 +1,17 @@
-use alvr_common::anyhow::Result;
+use alvr_common::{anyhow::Result, parking_lot::Mutex};
 use alvr_session::{CodecType, MediacodecDataType};
-use std::time::Duration;
+use std::{
+    collections::VecDeque,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+// Bounds the frames waiting to be output, in case a decoder drops frames silently at the end of a
+// GOP
+const MAX_PENDING_FRAMES: usize = 32;
+
+// The server needs some time to encode and send the requested IDR
+const IDR_REQUEST_INTERVAL: Duration = Duration::from_millis(100);
 
 #[derive(Clone, Default)]
 pub struct DecoderConfig {
@@ -10,29 +21,213 @@ pub struct DecoderConfig {
     pub buffering_history_weight: f32,
     pub options: Vec<(String, MediacodecDataType)>,
     pub config_buffer: Vec<u8>,
+    pub parallel_sessions: usize,
+    // Unbounded if None
+    pub max_queue_frames: Option<usize>,
+}
+
+// Decoded frames of a decoder session, in decode order
+pub trait DecodedFrameQueue {
+    fn front_timestamp(&mut self) -> Option<Duration>;
+    fn discard_front(&mut self);
+}
+
+// Frames of a GOP (from an IDR to the next one) don't depend on the frames of other GOPs, so GOPs
+// are assigned in round robin to the decoder sessions and decoded in parallel. The submission order
+// is recorded to output the frames in the same order.
+pub struct GopScheduler {
+    sessions_count: usize,
+    current_session: Option<usize>,
+    pending_frames: VecDeque<(Duration, usize)>,
+}
+
+impl GopScheduler {
+    pub fn new(sessions_count: usize) -> Self {
+        Self {
+            sessions_count: sessions_count.max(1),
+            // A single session receives all frames, without waiting for the first IDR
+            current_session: (sessions_count <= 1).then_some(0),
+            pending_frames: VecDeque::new(),
+        }
+    }
+
+    // Returns the session to decode the frame with, or None if the frame needs a previous IDR
+    pub fn assign(&mut self, timestamp: Duration, is_idr: bool) -> Option<usize> {
+        if is_idr {
+            self.current_session = Some(
+                self.current_session
+                    .map(|session| (session + 1) % self.sessions_count)
+                    .unwrap_or(0),
+            );
+        }
+        let session = self.current_session?;
+
+        self.pending_frames.push_back((timestamp, session));
+        if self.pending_frames.len() > MAX_PENDING_FRAMES {
+            self.pending_frames.pop_front();
+        }
+
+        Some(session)
+    }
+
+    // The frame could not be submitted to the decoder
+    pub fn cancel(&mut self, timestamp: Duration) {
+        self.pending_frames.retain(|(t, _)| *t != timestamp);
+    }
+
+    // Returns the session to dequeue the next frame from, if the frame has been decoded
+    pub fn next_session(&mut self, sessions: &mut [impl DecodedFrameQueue]) -> Option<usize> {
+        while let Some(&(timestamp, session)) = self.pending_frames.front() {
+            match sessions[session].front_timestamp() {
+                Some(t) if t == timestamp => {
+                    self.pending_frames.pop_front();
+
+                    return Some(session);
+                }
+                // The frame has been dropped by the decoder
+                Some(t) if t > timestamp => {
+                    self.pending_frames.pop_front();
+                }
+                // The frame has already been skipped
+                Some(_) => sessions[session].discard_front(),
+                None => return None,
+            }
+        }
+
+        None
+    }
+
+    // Number of sessions with frames being decoded
+    pub fn parallelism(&self) -> usize {
+        let mut sessions = self
+            .pending_frames
+            .iter()
+            .map(|(_, session)| *session)
+            .collect::<Vec<_>>();
+        sessions.sort_unstable();
+        sessions.dedup();
+
+        sessions.len()
+    }
+}
+
+// Bounds the frames submitted to the decoder and not decoded yet, so the latency stays bounded when
+// the decoder cannot keep up. Once a frame is dropped the following frames depend on it, so all
+// frames are dropped until the next IDR.
+pub struct DecodeQueue {
+    max_frames: usize,
+    queued_frames: VecDeque<Duration>,
+    waiting_for_idr: bool,
+    last_idr_request: Option<Instant>,
+    dropped_frames: usize,
+}
+
+impl DecodeQueue {
+    pub fn new(max_frames: Option<usize>) -> Self {
+        Self {
+            max_frames: max_frames.map(|frames| frames.max(1)).unwrap_or(usize::MAX),
+            queued_frames: VecDeque::new(),
+            waiting_for_idr: false,
+            last_idr_request: None,
+            dropped_frames: 0,
+        }
+    }
+
+    // Returns false if the frame must be dropped
+    pub fn admit(&mut self, timestamp: Duration, is_idr: bool) -> bool {
+        if self.queued_frames.len() >= self.max_frames || (self.waiting_for_idr && !is_idr) {
+            self.waiting_for_idr = true;
+            self.dropped_frames += 1;
+
+            return false;
+        }
+        self.waiting_for_idr = false;
+        self.last_idr_request = None;
+
+        self.queued_frames.push_back(timestamp);
+
+        true
+    }
+
+    // Call when a frame is dropped. While waiting for an IDR, it is requested again only after
+    // IDR_REQUEST_INTERVAL
+    pub fn should_request_idr(&mut self, now: Instant) -> bool {
+        if self.waiting_for_idr
+            && self
+                .last_idr_request
+                .is_some_and(|last| now < last + IDR_REQUEST_INTERVAL)
+        {
+            return false;
+        }
+        self.last_idr_request = Some(now);
+
+        true
+    }
+
+    // The frame could not be submitted to the decoder
+    pub fn cancel(&mut self, timestamp: Duration) {
+        self.queued_frames.retain(|t| *t != timestamp);
+    }
+
+    pub fn report_frame_decoded(&mut self, timestamp: Duration) {
+        // Frames dropped silently by the decoder are removed too. With parallel sessions this
+        // underestimates the depth, since GOPs are not decoded in order
+        self.queued_frames.retain(|t| *t > timestamp);
+    }
+
+    pub fn depth(&self) -> usize {
+        self.queued_frames.len()
+    }
+
+    pub fn dropped_frames(&self) -> usize {
+        self.dropped_frames
+    }
 }
 
 pub struct DecoderSink {
     #[cfg(target_os = "android")]
-    inner: crate::platform::VideoDecoderSink,
+    inner: Vec<crate::platform::VideoDecoderSink>,
+    scheduler: Arc<Mutex<GopScheduler>>,
+    queue: Arc<Mutex<DecodeQueue>>,
 }
 
 impl DecoderSink {
     // returns true if frame has been successfully enqueued
     #[allow(unused_variables)]
-    pub fn push_nal(&mut self, timestamp: Duration, nal: &[u8]) -> bool {
-        #[cfg(target_os = "android")]
-        {
-            alvr_common::show_err(self.inner.push_frame_nal(timestamp, nal)).unwrap_or(false)
+    pub fn push_nal(&mut self, timestamp: Duration, is_idr: bool, nal: &[u8]) -> bool {
+        if !self.queue.lock().admit(timestamp, is_idr) {
+            return false;
         }
+        let Some(session) = self.scheduler.lock().assign(timestamp, is_idr) else {
+            self.queue.lock().cancel(timestamp);
+            return false;
+        };
+
+        #[cfg(target_os = "android")]
+        let enqueued = alvr_common::show_err(self.inner[session].push_frame_nal(timestamp, nal))
+            .unwrap_or(false);
         #[cfg(not(target_os = "android"))]
-        false
+        let enqueued = false;
+
+        if !enqueued {
+            self.scheduler.lock().cancel(timestamp);
+            self.queue.lock().cancel(timestamp);
+        }
+
+        enqueued
+    }
+
+    // Call after push_nal() failed
+    pub fn should_request_idr(&mut self) -> bool {
+        self.queue.lock().should_request_idr(Instant::now())
     }
 }
 
 pub struct DecoderSource {
     #[cfg(target_os = "android")]
-    inner: crate::platform::VideoDecoderSource,
+    inner: Vec<crate::platform::VideoDecoderSource>,
+    scheduler: Arc<Mutex<GopScheduler>>,
+    queue: Arc<Mutex<DecodeQueue>>,
 }
 
 impl DecoderSource {
@@ -40,30 +235,269 @@ impl DecoderSource {
     pub fn get_frame(&mut self) -> Result<Option<(Duration, *mut std::ffi::c_void)>> {
         #[cfg(target_os = "android")]
         {
-            self.inner.dequeue_frame()
+            if self.inner.len() == 1 {
+                return self.inner[0].dequeue_frame();
+            }
+
+            let maybe_session = self.scheduler.lock().next_session(&mut self.inner);
+            if let Some(session) = maybe_session {
+                self.inner[session].dequeue_front()
+            } else {
+                // Errors are reported only when dequeuing frames
+                for source in &mut self.inner {
+                    source.check_error()?;
+                }
+
+                Ok(None)
+            }
         }
         #[cfg(not(target_os = "android"))]
         alvr_common::anyhow::bail!("Not implemented");
     }
+
+    // Number of decoder sessions currently decoding frames
+    pub fn parallelism(&self) -> usize {
+        self.scheduler.lock().parallelism()
+    }
+
+    // Frames dropped because the decode queue was full, since the decoder creation
+    pub fn dropped_frames(&self) -> usize {
+        self.queue.lock().dropped_frames()
+    }
 }
 
 // report_frame_decoded: (target_timestamp: Duration) -> ()
 #[allow(unused_variables)]
 pub fn create_decoder(
     config: DecoderConfig,
-    report_frame_decoded: impl Fn(Duration) + Send + 'static,
+    report_frame_decoded: impl Fn(Duration) + Send + Sync + 'static,
 ) -> (DecoderSink, DecoderSource) {
+    let scheduler = Arc::new(Mutex::new(GopScheduler::new(config.parallel_sessions)));
+    let queue = Arc::new(Mutex::new(DecodeQueue::new(config.max_queue_frames)));
+
     #[cfg(target_os = "android")]
     {
-        let (sink, source) = crate::platform::video_decoder_split(
-            config.clone(),
-            config.config_buffer,
-            report_frame_decoded,
-        )
-        .unwrap();
+        let report_frame_decoded = Arc::new({
+            let queue = Arc::clone(&queue);
+            move |timestamp| {
+                queue.lock().report_frame_decoded(timestamp);
+                report_frame_decoded(timestamp)
+            }
+        });
+
+        let mut sinks = vec![];
+        let mut sources = vec![];
+        for _ in 0..config.parallel_sessions.max(1) {
+            let (sink, source) = crate::platform::video_decoder_split(
+                config.clone(),
+                config.config_buffer.clone(),
+                {
+                    let report_frame_decoded = Arc::clone(&report_frame_decoded);
+                    move |timestamp| report_frame_decoded(timestamp)
+                },
+            )
+            .unwrap();
+
+            sinks.push(sink);
+            sources.push(source);
+        }
 
-        (DecoderSink { inner: sink }, DecoderSource { inner: source })
+        (
+            DecoderSink {
+                inner: sinks,
+                scheduler: Arc::clone(&scheduler),
+                queue: Arc::clone(&queue),
+            },
+            DecoderSource {
+                inner: sources,
+                scheduler,
+                queue,
+            },
+        )
     }
     #[cfg(not(target_os = "android"))]
-    (DecoderSink {}, DecoderSource {})
+    (
+        DecoderSink {
+            scheduler: Arc::clone(&scheduler),
+            queue: Arc::clone(&queue),
+        },
+        DecoderSource { scheduler, queue },
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Decodes a frame as the sum of the payloads since the IDR, like a decoder would depend on the
+    // reference frames
+    #[derive(Default)]
+    struct MockSession {
+        state: u32,
+        input: VecDeque<(Duration, bool, u32)>,
+        output: VecDeque<(Duration, u32)>,
+    }
+
+    impl MockSession {
+        fn decode_one(&mut self) {
+            if let Some((timestamp, is_idr, payload)) = self.input.pop_front() {
+                self.state = if is_idr {
+                    payload
+                } else {
+                    self.state + payload
+                };
+                self.output.push_back((timestamp, self.state));
+            }
+        }
+    }
+
+    impl DecodedFrameQueue for MockSession {
+        fn front_timestamp(&mut self) -> Option<Duration> {
+            self.output.front().map(|(timestamp, _)| *timestamp)
+        }
+
+        fn discard_front(&mut self) {
+            self.output.pop_front();
+        }
+    }
+
+    fn decode_stream(sessions_count: usize) -> (Vec<(Duration, u32)>, usize) {
+        let mut scheduler = GopScheduler::new(sessions_count);
+        let mut sessions = (0..sessions_count)
+            .map(|_| MockSession::default())
+            .collect::<Vec<_>>();
+
+        let mut output = vec![];
+        let mut max_parallelism = 0;
+        for i in 0..60_u32 {
+            let timestamp = Duration::from_millis(i as u64);
+            // GOPs of varying length
+            let is_idr = i % 7 == 0 || i % 11 == 0;
+            if let Some(session) = scheduler.assign(timestamp, is_idr) {
+                sessions[session].input.push_back((timestamp, is_idr, i));
+            }
+
+            // Sessions decode at different speeds, the first one being the slowest
+            for (index, session) in sessions.iter_mut().enumerate() {
+                if i % (sessions_count - index) as u32 == 0 {
+                    session.decode_one();
+                }
+            }
+            max_parallelism = max_parallelism.max(scheduler.parallelism());
+
+            while let Some(session) = scheduler.next_session(&mut sessions) {
+                output.push(sessions[session].output.pop_front().unwrap());
+            }
+        }
+
+        // Drain
+        for _ in 0..60 {
+            for session in &mut sessions {
+                session.decode_one();
+            }
+            while let Some(session) = scheduler.next_session(&mut sessions) {
+                output.push(sessions[session].output.pop_front().unwrap());
+            }
+        }
+
+        (output, max_parallelism)
+    }
+
+    #[test]
+    fn test_parallel_decode_matches_single_session() {
+        let (single_output, single_parallelism) = decode_stream(1);
+        let (parallel_output, parallel_parallelism) = decode_stream(3);
+
+        assert_eq!(single_output.len(), 60);
+        assert_eq!(parallel_output, single_output);
+        assert_eq!(single_parallelism, 1);
+        assert!(parallel_parallelism > 1);
+    }
+
+    #[test]
+    fn test_dropped_frames_do_not_stall_output() {
+        let mut scheduler = GopScheduler::new(2);
+        let mut sessions = [MockSession::default(), MockSession::default()];
+
+        for i in 0..3 {
+            let timestamp = Duration::from_millis(i);
+            let session = scheduler.assign(timestamp, i == 0).unwrap();
+            sessions[session].input.push_back((timestamp, i == 0, 1));
+        }
+        // Frames cannot be decoded before the first IDR
+        assert!(GopScheduler::new(2).assign(Duration::ZERO, false).is_none());
+
+        // The decoder drops the second frame
+        sessions[0].decode_one();
+        sessions[0].input.pop_front();
+        sessions[0].decode_one();
+
+        let session = scheduler.next_session(&mut sessions).unwrap();
+        assert_eq!(
+            sessions[session].output.pop_front().unwrap().0,
+            Duration::ZERO
+        );
+        let session = scheduler.next_session(&mut sessions).unwrap();
+        assert_eq!(
+            sessions[session].output.pop_front().unwrap().0,
+            Duration::from_millis(2)
+        );
+    }
+
+    #[test]
+    fn test_slow_decoder_keeps_queue_bounded() {
+        const MAX_QUEUE_FRAMES: usize = 3;
+
+        let mut queue = DecodeQueue::new(Some(MAX_QUEUE_FRAMES));
+        let mut session = MockSession::default();
+
+        let start = Instant::now();
+        let mut next_idr = None;
+        let mut idr_requests = 0;
+        let mut last_idr_request = None;
+        let mut last_submitted = None;
+        let mut decoded_frames = 0;
+        for i in 0..120_u32 {
+            let timestamp = Duration::from_millis(i as u64 * 11);
+            let now = start + timestamp;
+            // The server sends an IDR three frames after the request, otherwise only at the start
+            // of the stream
+            let is_idr = i == 0 || next_idr == Some(i);
+            if is_idr {
+                next_idr = None;
+            }
+
+            if queue.admit(timestamp, is_idr) {
+                // After a drop, decoding resumes only from an IDR
+                assert!(is_idr || last_submitted == Some(i - 1));
+                last_submitted = Some(i);
+                last_idr_request = None;
+
+                session.input.push_back((timestamp, is_idr, 1));
+            } else if queue.should_request_idr(now) {
+                // The IDRs dropped by the full queue are not requested again at every frame
+                if let Some(last) = last_idr_request {
+                    assert!(now - last >= IDR_REQUEST_INTERVAL);
+                }
+                last_idr_request = Some(now);
+
+                next_idr.get_or_insert(i + 3);
+                idr_requests += 1;
+            }
+            assert!(queue.depth() <= MAX_QUEUE_FRAMES);
+
+            // The decoder takes two frame intervals for each frame
+            if i % 2 == 0 {
+                session.decode_one();
+                if let Some((timestamp, _)) = session.output.pop_front() {
+                    queue.report_frame_decoded(timestamp);
+                    decoded_frames += 1;
+                }
+            }
+        }
+
+        assert!(idr_requests > 0);
+        assert!(queue.dropped_frames() > idr_requests);
+        assert!(decoded_frames >= 55);
+    }
 }
@@ -0,0 +1,108 @@
+use alvr_session::FoveatedEncodingConfig;
+use std::{
+    collections::{HashMap, VecDeque},
+    time::Duration,
+};
+
+const MAX_TRACKED_FRAMES: usize = 1024;
+
+// Associates each video frame with the foveated encoding it was encoded with. Frames are tagged by
+// the server with an epoch, and each epoch config is received on the control socket, which is not
+// synchronized with the video stream. Epoch 0 corresponds to the negotiated config.
+pub struct FoveationSync {
+    configs: HashMap<u32, Option<FoveatedEncodingConfig>>,
+    frame_epochs: VecDeque<(Duration, u32)>,
+}
+
+impl FoveationSync {
+    pub fn new(negotiated_config: Option<FoveatedEncodingConfig>) -> Self {
+        Self {
+            configs: HashMap::from([(0, negotiated_config)]),
+            frame_epochs: VecDeque::new(),
+        }
+    }
+
+    pub fn report_config(&mut self, epoch: u32, config: Option<FoveatedEncodingConfig>) {
+        self.configs.insert(epoch, config);
+    }
+
+    // Returns false if the config of the epoch is not known yet. In this case the frame cannot be
+    // rendered and should be dropped
+    pub fn report_frame(&mut self, timestamp: Duration, epoch: u32) -> bool {
+        if !self.configs.contains_key(&epoch) {
+            return false;
+        }
+
+        self.frame_epochs.push_back((timestamp, epoch));
+        while self.frame_epochs.len() > MAX_TRACKED_FRAMES {
+            self.frame_epochs.pop_front();
+        }
+
+        true
+    }
+
+    // Config the frame has been encoded with. None also for untracked frames
+    pub fn frame_config(&self, timestamp: Duration) -> Option<FoveatedEncodingConfig> {
+        self.frame_epochs
+            .iter()
+            .rev()
+            .find(|(t, _)| *t == timestamp)
+            .and_then(|(_, epoch)| self.configs.get(epoch).cloned().flatten())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(center_size_x: f32) -> FoveatedEncodingConfig {
+        FoveatedEncodingConfig {
+            force_enable: false,
+            center_size_x,
+            center_size_y: 0.35,
+            center_shift_x: 0.4,
+            center_shift_y: 0.1,
+            edge_ratio_x: 4.0,
+            edge_ratio_y: 5.0,
+        }
+    }
+
+    #[test]
+    fn test_frames_use_the_config_they_were_encoded_with() {
+        let mut sync = FoveationSync::new(Some(config(0.45)));
+
+        // Frames encoded on the server side, as (timestamp, epoch, shrink config)
+        let mut frames = vec![];
+        for i in 0..10 {
+            let (epoch, config) = match i {
+                0..=3 => (0, Some(config(0.45))),
+                4..=6 => (1, None),
+                _ => (2, Some(config(0.3))),
+            };
+            frames.push((Duration::from_millis(i), epoch, config));
+        }
+
+        let mut rendered = 0;
+        for (timestamp, epoch, shrink_config) in &frames {
+            // The control packet of epoch 1 arrives late, together with the frame 5
+            if *timestamp == Duration::from_millis(5) {
+                sync.report_config(1, None);
+            }
+            if *timestamp == Duration::from_millis(7) {
+                sync.report_config(2, Some(config(0.3)));
+            }
+
+            if !sync.report_frame(*timestamp, *epoch) {
+                assert_eq!(*epoch, 1);
+                continue;
+            }
+
+            // The expand config used to render must match the shrink config used to encode
+            assert!(sync.frame_config(*timestamp) == *shrink_config);
+            rendered += 1;
+        }
+        assert_eq!(rendered, 9);
+
+        assert!(sync.frame_config(Duration::from_secs(1)).is_none());
+    }
+}
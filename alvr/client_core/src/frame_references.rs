@@ -0,0 +1,257 @@
+use alvr_session::ReferenceStructure;
+
+#[derive(PartialEq, Eq, Debug)]
+pub enum FrameAction {
+    Decode,
+    // A reference of the frame was lost, but the references of the following frames are intact
+    Skip,
+    // The base layer was lost, no frame can be decoded until the next IDR
+    WaitForIdr,
+}
+
+fn temporal_layers_count(reference_structure: ReferenceStructure) -> u8 {
+    match reference_structure {
+        ReferenceStructure::POnly => 1,
+        ReferenceStructure::HierarchicalP { temporal_layers } => temporal_layers.clamp(1, 8),
+    }
+}
+
+// Layer of the frame at index since the IDR. The layers follow a dyadic pattern with a period of
+// 2^(temporal_layers - 1) frames: with 3 layers the pattern is 0, 2, 1, 2, 0, ...
+pub fn temporal_layer(temporal_layers: u8, frame_index: u32) -> u8 {
+    let period_position = frame_index % (1 << (temporal_layers - 1));
+    if period_position == 0 {
+        0
+    } else {
+        temporal_layers - 1 - period_position.trailing_zeros() as u8
+    }
+}
+
+// Tracks the references between the received frames, to decode only the frames with all the
+// references intact. Each frame references the most recent frame of a lower layer, and the base
+// layer references the previous base layer frame, so the frames of the top layer are never
+// referenced. With P-only every frame is in the base layer.
+// Frames are lost when they never arrive, because of the network or because the retransmission
+// timed out, or when they cannot be decoded because of a lost reference.
+pub struct FrameReferenceTracker {
+    temporal_layers: u8,
+    // None while waiting for an IDR
+    next_index: Option<u32>,
+    // Index of the most recent frame of each layer and if it was decoded
+    last_frames: Vec<Option<(u32, bool)>>,
+}
+
+impl FrameReferenceTracker {
+    pub fn new(reference_structure: ReferenceStructure) -> Self {
+        let temporal_layers = temporal_layers_count(reference_structure);
+
+        Self {
+            temporal_layers,
+            next_index: None,
+            last_frames: vec![None; temporal_layers as usize],
+        }
+    }
+
+    fn record_frame(&mut self, frame_index: u32, decoded: bool) {
+        let layer = temporal_layer(self.temporal_layers, frame_index) as usize;
+        self.last_frames[layer] = Some((frame_index, decoded));
+    }
+
+    fn reference_decoded(&self, frame_index: u32) -> bool {
+        let layer = temporal_layer(self.temporal_layers, frame_index) as usize;
+
+        self.last_frames[..usize::max(layer, 1)]
+            .iter()
+            .flatten()
+            .max_by_key(|(index, _)| *index)
+            .map(|(_, decoded)| *decoded)
+            .unwrap_or(false)
+    }
+
+    pub fn report_frame(&mut self, frame_index: u32, is_idr: bool) -> FrameAction {
+        if is_idr {
+            self.last_frames.fill(None);
+            self.record_frame(frame_index, true);
+            self.next_index = Some(frame_index.wrapping_add(1));
+
+            return FrameAction::Decode;
+        }
+
+        let Some(expected_index) = self.next_index else {
+            return FrameAction::WaitForIdr;
+        };
+        // The IDR that restarted the count was lost
+        if frame_index < expected_index {
+            self.report_undecodable();
+
+            return FrameAction::WaitForIdr;
+        }
+
+        // Only the last period of the missing frames affects the references
+        let period = 1 << (self.temporal_layers - 1);
+        for missing_index in
+            u32::max(expected_index, frame_index.saturating_sub(period))..frame_index
+        {
+            self.record_frame(missing_index, false);
+        }
+
+        let decodable = self.reference_decoded(frame_index);
+        self.record_frame(frame_index, decodable);
+        self.next_index = Some(frame_index.wrapping_add(1));
+
+        if decodable {
+            FrameAction::Decode
+        } else if matches!(self.last_frames[0], Some((_, false))) {
+            FrameAction::WaitForIdr
+        } else {
+            FrameAction::Skip
+        }
+    }
+
+    // The last reported frame was not decoded for other reasons, for example decoder saturation.
+    // Since the decoder state is not known anymore, the next frames need an IDR
+    pub fn report_undecodable(&mut self) {
+        self.next_index = None;
+        self.last_frames.fill(None);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Each encoded frame is the difference from its reference. The decoder adds it to the decoded
+    // reference, so a frame is reconstructed correctly only if its whole reference chain is intact
+    fn encode(temporal_layers: u8, frames: &[i64]) -> Vec<i64> {
+        let mut references: Vec<Option<usize>> = vec![None; temporal_layers as usize];
+
+        frames
+            .iter()
+            .enumerate()
+            .map(|(index, frame)| {
+                let layer = temporal_layer(temporal_layers, index as u32) as usize;
+                let reference = references[..usize::max(layer, 1)].iter().flatten().max();
+                let residual = frame - reference.map(|r| frames[*r]).unwrap_or(0);
+                references[layer] = Some(index);
+
+                residual
+            })
+            .collect()
+    }
+
+    // Returns the frames shown to the user, None for the dropped ones
+    fn decode(
+        reference_structure: ReferenceStructure,
+        encoded: &[i64],
+        lost: &[usize],
+    ) -> (Vec<Option<i64>>, usize) {
+        let temporal_layers = temporal_layers_count(reference_structure);
+        let mut tracker = FrameReferenceTracker::new(reference_structure);
+        let mut references: Vec<Option<i64>> = vec![None; temporal_layers as usize];
+        let mut idr_requests = 0;
+
+        let decoded = encoded
+            .iter()
+            .enumerate()
+            .map(|(index, residual)| {
+                if lost.contains(&index) {
+                    return None;
+                }
+
+                let layer = temporal_layer(temporal_layers, index as u32) as usize;
+                // The most recent frame of a lower layer
+                let reference = references[..usize::max(layer, 1)]
+                    .iter()
+                    .flatten()
+                    .last()
+                    .copied()
+                    .unwrap_or(0);
+
+                match tracker.report_frame(index as u32, index == 0) {
+                    FrameAction::Decode => {
+                        let frame = reference + residual;
+                        references[layer] = Some(frame);
+                        references[layer + 1..].fill(None);
+
+                        Some(frame)
+                    }
+                    FrameAction::Skip => None,
+                    FrameAction::WaitForIdr => {
+                        idr_requests += 1;
+                        None
+                    }
+                }
+            })
+            .collect();
+
+        (decoded, idr_requests)
+    }
+
+    #[test]
+    fn test_temporal_layers() {
+        let layers = |temporal_layers| {
+            (0..9)
+                .map(|index| temporal_layer(temporal_layers, index))
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(layers(1), [0; 9]);
+        assert_eq!(layers(2), [0, 1, 0, 1, 0, 1, 0, 1, 0]);
+        assert_eq!(layers(3), [0, 2, 1, 2, 0, 2, 1, 2, 0]);
+        assert_eq!(layers(4), [0, 3, 2, 3, 1, 3, 2, 3, 0]);
+    }
+
+    #[test]
+    fn test_decoded_frames_are_reconstructed() {
+        let frames = (0..16).map(|i| 100 + i * i).collect::<Vec<_>>();
+
+        for reference_structure in [
+            ReferenceStructure::POnly,
+            ReferenceStructure::HierarchicalP { temporal_layers: 2 },
+            ReferenceStructure::HierarchicalP { temporal_layers: 3 },
+        ] {
+            let encoded = encode(temporal_layers_count(reference_structure), &frames);
+
+            let (decoded, idr_requests) = decode(reference_structure, &encoded, &[]);
+            assert_eq!(
+                decoded,
+                frames.iter().copied().map(Some).collect::<Vec<_>>()
+            );
+            assert_eq!(idr_requests, 0);
+
+            // Every decoded frame is correct, even after a loss
+            for lost in 1..frames.len() {
+                let (decoded, _) = decode(reference_structure, &encoded, &[lost]);
+                for (index, frame) in decoded.iter().enumerate() {
+                    if let Some(frame) = frame {
+                        assert_eq!(*frame, frames[index]);
+                    }
+                }
+            }
+        }
+
+        let encoded = encode(3, &frames);
+        let hierarchical = ReferenceStructure::HierarchicalP { temporal_layers: 3 };
+
+        // Frame 5 is in the top layer: only that frame is missing, with no IDR request
+        let (decoded, idr_requests) = decode(hierarchical, &encoded, &[5]);
+        assert_eq!(decoded.iter().filter(|f| f.is_none()).count(), 1);
+        assert_eq!(idr_requests, 0);
+
+        // Frame 6 is referenced by frame 7, frame 8 is in the base layer and references frame 4
+        let (decoded, idr_requests) = decode(hierarchical, &encoded, &[6]);
+        assert_eq!(decoded[5], Some(frames[5]));
+        assert_eq!(decoded[7], None);
+        assert_eq!(decoded[8], Some(frames[8]));
+        assert_eq!(idr_requests, 0);
+
+        // The loss of a base layer frame breaks the stream until the next IDR
+        let (decoded, idr_requests) = decode(hierarchical, &encoded, &[4]);
+        assert!(decoded[4..].iter().all(Option::is_none));
+        assert_eq!(idr_requests, 11);
+
+        // With P-only every loss breaks the stream
+        let (decoded, _) = decode(ReferenceStructure::POnly, &encode(1, &frames), &[5]);
+        assert!(decoded[5..].iter().all(Option::is_none));
+    }
+}
@@ -0,0 +1,67 @@
+use std::{collections::VecDeque, time::Duration};
+
+const MAX_TRACKED_FRAMES: usize = 1024;
+
+// User data attached by the server to the video frames. Frames can be dropped anywhere along the
+// pipeline, so the data is looked up by timestamp once the frame is decoded.
+#[derive(Default)]
+pub struct FrameUserDataQueue {
+    frames: VecDeque<(Duration, Vec<u8>)>,
+}
+
+impl FrameUserDataQueue {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn report_frame(&mut self, timestamp: Duration, user_data: Vec<u8>) {
+        // Most frames have no user data, don't waste time searching for them
+        if user_data.is_empty() {
+            return;
+        }
+
+        self.frames.push_back((timestamp, user_data));
+        while self.frames.len() > MAX_TRACKED_FRAMES {
+            self.frames.pop_front();
+        }
+    }
+
+    // Empty for frames without user data or untracked
+    pub fn frame_user_data(&self, timestamp: Duration) -> Vec<u8> {
+        self.frames
+            .iter()
+            .rev()
+            .find(|(t, _)| *t == timestamp)
+            .map(|(_, user_data)| user_data.clone())
+            .unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_user_data_follows_its_frame() {
+        let mut queue = FrameUserDataQueue::new();
+
+        for i in 0..10_u8 {
+            // Some frames don't carry any user data
+            let user_data = if i % 4 == 3 { vec![] } else { vec![i, i * 2] };
+            queue.report_frame(Duration::from_millis(i as _), user_data);
+        }
+
+        // Frames 4 and 5 are dropped by the decoder
+        for i in [0_u8, 1, 2, 3, 6, 7, 8, 9] {
+            let user_data = queue.frame_user_data(Duration::from_millis(i as _));
+
+            if i % 4 == 3 {
+                assert!(user_data.is_empty());
+            } else {
+                assert_eq!(user_data, [i, i * 2]);
+            }
+        }
+
+        assert!(queue.frame_user_data(Duration::from_secs(1)).is_empty());
+    }
+}
@@ -0,0 +1,224 @@
+use alvr_common::{
+    anyhow::{anyhow, bail, Context, Result},
+    glam::Vec3,
+};
+use std::{fs, path::Path};
+
+// Sizes above this are not guaranteed to be supported for 3D textures
+const MAX_LUT_SIZE: usize = 256;
+
+// 3D color lookup table in the Adobe .cube format. Entries are RGB triplets with the red index
+// changing fastest, which is also the layout of a 3D texture.
+pub struct ColorLut {
+    pub size: usize,
+    pub domain_min: Vec3,
+    pub domain_max: Vec3,
+    pub data: Vec<f32>,
+}
+
+impl ColorLut {
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let text = fs::read_to_string(path)
+            .map_err(|e| anyhow!("Failed to read LUT file {}: {e}", path.display()))?;
+
+        Self::parse(&text)
+    }
+
+    pub fn parse(text: &str) -> Result<Self> {
+        fn parse_triplet<'a>(values: impl Iterator<Item = &'a str>) -> Result<Vec3> {
+            let values = values
+                .map(|v| v.parse::<f32>())
+                .collect::<Result<Vec<_>, _>>()
+                .context("Invalid LUT value")?;
+            if values.len() != 3 {
+                bail!("Expected 3 LUT values, found {}", values.len());
+            }
+
+            Ok(Vec3::from_slice(&values))
+        }
+
+        let mut size = None;
+        let mut domain_min = Vec3::ZERO;
+        let mut domain_max = Vec3::ONE;
+        let mut data = vec![];
+        for line in text.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let mut tokens = line.split_whitespace();
+            match tokens.next() {
+                Some("TITLE") => (),
+                Some("LUT_1D_SIZE") => bail!("1D LUTs are not supported"),
+                Some("LUT_3D_SIZE") => {
+                    size = Some(
+                        tokens
+                            .next()
+                            .and_then(|s| s.parse::<usize>().ok())
+                            .context("Invalid LUT size")?,
+                    )
+                }
+                Some("DOMAIN_MIN") => domain_min = parse_triplet(tokens)?,
+                Some("DOMAIN_MAX") => domain_max = parse_triplet(tokens)?,
+                _ => data.extend(parse_triplet(line.split_whitespace())?.to_array()),
+            }
+        }
+
+        let Some(size) = size else {
+            bail!("Missing LUT_3D_SIZE");
+        };
+        if !(2..=MAX_LUT_SIZE).contains(&size) {
+            bail!("LUT size must be between 2 and {MAX_LUT_SIZE}, found {size}");
+        }
+        if data.len() != size.pow(3) * 3 {
+            bail!(
+                "Expected {} LUT entries, found {}",
+                size.pow(3),
+                data.len() / 3
+            );
+        }
+        if domain_min.cmpge(domain_max).any() {
+            bail!("Invalid LUT domain");
+        }
+
+        Ok(Self {
+            size,
+            domain_min,
+            domain_max,
+            data,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graphics::gl_test::{self, TestGl, UniformBlock};
+
+    const COLOR_LUT_PASS_CPP: &str = include_str!("../../cpp/color_lut_pass.cpp");
+
+    const EYE_SIZE: usize = 2;
+
+    fn cube_file(size: usize, map: impl Fn(Vec3) -> Vec3) -> String {
+        let mut text = format!("TITLE \"test\"\n# comment\nLUT_3D_SIZE {size}\n\n");
+        for b in 0..size {
+            for g in 0..size {
+                for r in 0..size {
+                    let color = Vec3::new(r as f32, g as f32, b as f32) / (size - 1) as f32;
+                    let out = map(color);
+                    text += &format!("{} {} {}\n", out.x, out.y, out.z);
+                }
+            }
+        }
+
+        text
+    }
+
+    // Side by side image, same layout as the stream texture. sRGB encoded, like the LUTs
+    fn test_image() -> Vec<Vec3> {
+        (0..EYE_SIZE * 2 * EYE_SIZE)
+            .map(|i| Vec3::new(0.1 * i as f32, 0.3, 1.0 - 0.1 * i as f32))
+            .collect()
+    }
+
+    fn srgb_to_linear(color: Vec3) -> Vec3 {
+        Vec3::from(color.to_array().map(|c| {
+            if c <= 0.04045 {
+                c / 12.92
+            } else {
+                ((c + 0.055) / 1.055).powf(2.4)
+            }
+        }))
+    }
+
+    fn linear_to_srgb(color: Vec3) -> Vec3 {
+        Vec3::from(color.to_array().map(|c| {
+            if c <= 0.0031308 {
+                c * 12.92
+            } else {
+                1.055 * c.powf(1.0 / 2.4) - 0.055
+            }
+        }))
+    }
+
+    // Grades the sRGB encoded image with the shader, through the linear stream texture
+    fn render_graded(gl: &TestGl, lut: &ColorLut, intensity: f32, image: &[Vec3]) -> Vec<Vec3> {
+        let input = gl.texture_rgba16f(
+            (EYE_SIZE * 2) as _,
+            EYE_SIZE as _,
+            &image
+                .iter()
+                .map(|color| srgb_to_linear(*color).extend(1.0).to_array())
+                .collect::<Vec<_>>(),
+        );
+        // Same texture as ColorLutPass
+        let lut_texture = gl.texture_3d(
+            lut.size as _,
+            glow::RGB16F,
+            glow::RGB,
+            glow::FLOAT,
+            &lut.data
+                .iter()
+                .flat_map(|value| value.to_ne_bytes())
+                .collect::<Vec<_>>(),
+        );
+
+        let output = gl.output((EYE_SIZE * 2) as _, EYE_SIZE as _, glow::RGBA16F);
+        gl.render(
+            &gl_test::quad_vertex_shader(),
+            &gl_test::glsl(COLOR_LUT_PASS_CPP, "COLOR_LUT_FRAGMENT_SHADER"),
+            &[input, lut_texture],
+            UniformBlock::default()
+                .vec3(lut.domain_min.to_array())
+                .float(intensity)
+                .vec3(lut.domain_max.to_array())
+                .float(lut.size as _),
+            &output,
+        );
+
+        gl.read_rgba_f32(&output)
+            .into_iter()
+            .map(|pixel| linear_to_srgb(Vec3::from_slice(&pixel[..3])))
+            .collect()
+    }
+
+    #[test]
+    #[ignore = "needs a GLES 3 driver"]
+    fn test_color_lut_golden_image() {
+        let gl = TestGl::new();
+
+        let identity = ColorLut::parse(&cube_file(17, |c| c)).unwrap();
+        let output = render_graded(&gl, &identity, 1.0, &test_image());
+        for (pixel, color) in output.into_iter().zip(test_image()) {
+            assert!(pixel.abs_diff_eq(color, 1e-3), "{pixel} != {color}");
+        }
+
+        // Rotates the channels and lifts the shadows, half blended with the original colors
+        let shift =
+            ColorLut::parse(&cube_file(5, |c| Vec3::new(c.z, c.x, c.y) * 0.8 + 0.1)).unwrap();
+        let output = render_graded(&gl, &shift, 0.5, &test_image());
+
+        #[rustfmt::skip]
+        let golden = [
+            [0.45, 0.20, 0.67], [0.46, 0.24, 0.62], [0.47, 0.28, 0.57], [0.48, 0.32, 0.52],
+            [0.49, 0.36, 0.47], [0.50, 0.40, 0.42], [0.51, 0.44, 0.37], [0.52, 0.48, 0.32],
+        ];
+        for (pixel, golden) in output.into_iter().zip(golden) {
+            assert!(
+                pixel.abs_diff_eq(Vec3::from(golden), 1e-3),
+                "{pixel} != {golden:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_invalid_lut_is_rejected() {
+        assert!(ColorLut::parse("LUT_3D_SIZE 2\n0 0 0\n").is_err());
+        assert!(ColorLut::parse("LUT_3D_SIZE 300\n").is_err());
+        assert!(ColorLut::parse("LUT_1D_SIZE 4\n").is_err());
+        assert!(ColorLut::parse(&cube_file(2, |c| c).replace("LUT_3D_SIZE 2", "")).is_err());
+        assert!(ColorLut::parse(&format!("DOMAIN_MIN 1 1 1\n{}", cube_file(2, |c| c))).is_err());
+    }
+}
@@ -0,0 +1,181 @@
+use alvr_common::Pose;
+use alvr_session::ComfortVignetteConfig;
+use std::time::Duration;
+
+// Velocities measured over longer intervals (e.g. after a stall) are not meaningful
+const MAX_MEASUREMENT_INTERVAL: Duration = Duration::from_millis(100);
+
+fn ramp(value: f32, threshold: f32, full: f32) -> f32 {
+    if full <= threshold {
+        return if value >= threshold { 1.0 } else { 0.0 };
+    }
+
+    ((value - threshold) / (full - threshold)).clamp(0.0, 1.0)
+}
+
+// Narrows the view when the head moves fast. The intensity follows the measured velocity with an
+// exponential smoothing, so the vignette never pops in or out. The radius is relative to the half
+// diagonal of each eye image: 1 leaves the view unobstructed.
+// Note: the radius is consumed by the comfort vignette shader.
+pub struct ComfortVignette {
+    config: ComfortVignetteConfig,
+    last_pose: Option<(Pose, Duration)>,
+    target_intensity: f32,
+    intensity: f32,
+    last_update: Option<Duration>,
+}
+
+impl ComfortVignette {
+    pub fn new(config: ComfortVignetteConfig) -> Self {
+        Self {
+            config,
+            last_pose: None,
+            target_intensity: 0.0,
+            intensity: 0.0,
+            last_update: None,
+        }
+    }
+
+    // Called with the head pose of each new frame
+    pub fn report_head_pose(&mut self, pose: Pose, time: Duration) {
+        if let Some((last_pose, last_time)) = self.last_pose {
+            let interval = time.saturating_sub(last_time);
+
+            if interval.is_zero() {
+                return;
+            } else if interval <= MAX_MEASUREMENT_INTERVAL {
+                // Computed with atan2, acos is not precise for the small rotations between frames
+                let delta = last_pose.orientation.inverse() * pose.orientation;
+                let angle = 2.0 * f32::atan2(delta.xyz().length(), delta.w.abs());
+
+                let angular_velocity = angle / interval.as_secs_f32();
+                let linear_velocity =
+                    (pose.position - last_pose.position).length() / interval.as_secs_f32();
+
+                self.target_intensity = f32::max(
+                    ramp(
+                        angular_velocity.to_degrees(),
+                        self.config.angular_threshold_deg_per_s,
+                        self.config.angular_full_deg_per_s,
+                    ),
+                    ramp(
+                        linear_velocity,
+                        self.config.linear_threshold_m_per_s,
+                        self.config.linear_full_m_per_s,
+                    ),
+                );
+            }
+        }
+
+        self.last_pose = Some((pose, time));
+    }
+
+    // Radius of the unobstructed area for the frame displayed at time
+    pub fn radius(&mut self, time: Duration) -> f32 {
+        let elapsed = self
+            .last_update
+            .map(|last| time.saturating_sub(last))
+            .unwrap_or_default();
+        self.last_update = Some(time);
+
+        let alpha = 1.0 - (-elapsed.as_secs_f32() / self.config.transition_time_s.max(0.01)).exp();
+        self.intensity += (self.target_intensity - self.intensity) * alpha;
+
+        1.0 - self.config.strength.clamp(0.0, 1.0) * self.intensity
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alvr_common::glam::{Quat, Vec3};
+
+    const FRAME_INTERVAL: Duration = Duration::from_micros(11_111);
+
+    fn config() -> ComfortVignetteConfig {
+        ComfortVignetteConfig {
+            strength: 0.5,
+            angular_threshold_deg_per_s: 60.0,
+            angular_full_deg_per_s: 180.0,
+            linear_threshold_m_per_s: 1.0,
+            linear_full_m_per_s: 3.0,
+            transition_time_s: 0.2,
+        }
+    }
+
+    // Moves the head with constant velocities for the duration. Returns the radius of each frame
+    fn drive(
+        vignette: &mut ComfortVignette,
+        time: &mut Duration,
+        pose: &mut Pose,
+        angular_velocity_deg_per_s: f32,
+        linear_velocity_m_per_s: f32,
+        duration: Duration,
+    ) -> Vec<f32> {
+        let dt = FRAME_INTERVAL.as_secs_f32();
+        let mut radii = vec![];
+        for _ in 0..(duration.as_secs_f32() / dt) as usize {
+            *time += FRAME_INTERVAL;
+            pose.orientation *=
+                Quat::from_rotation_y((angular_velocity_deg_per_s * dt).to_radians());
+            pose.position.x += linear_velocity_m_per_s * dt;
+
+            vignette.report_head_pose(*pose, *time);
+            radii.push(vignette.radius(*time));
+        }
+
+        radii
+    }
+
+    #[test]
+    fn test_radius_follows_velocity() {
+        let mut vignette = ComfortVignette::new(config());
+        let mut time = Duration::ZERO;
+        let mut pose = Pose {
+            orientation: Quat::IDENTITY,
+            position: Vec3::new(0.0, 1.6, 0.0),
+        };
+        let duration = Duration::from_secs(2);
+
+        // Below the thresholds the view is unobstructed
+        let radii = drive(&mut vignette, &mut time, &mut pose, 50.0, 0.5, duration);
+        assert!(radii.iter().all(|r| (*r - 1.0).abs() < 1e-4));
+
+        // Halfway through the angular ramp
+        let radii = drive(&mut vignette, &mut time, &mut pose, 120.0, 0.0, duration);
+        assert!((radii.last().unwrap() - 0.75).abs() < 1e-3);
+
+        // Above full intensity the radius is limited by the strength
+        let radii = drive(&mut vignette, &mut time, &mut pose, 0.0, 4.0, duration);
+        assert!((radii.last().unwrap() - 0.5).abs() < 1e-3);
+
+        // The vignette fades out when the motion stops
+        let radii = drive(&mut vignette, &mut time, &mut pose, 0.0, 0.0, duration);
+        assert!((radii.last().unwrap() - 1.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn test_radius_changes_smoothly() {
+        let mut vignette = ComfortVignette::new(config());
+        let mut time = Duration::ZERO;
+        let mut pose = Pose::default();
+
+        // A sudden fast turn
+        let radii = drive(
+            &mut vignette,
+            &mut time,
+            &mut pose,
+            360.0,
+            0.0,
+            Duration::from_millis(500),
+        );
+
+        // The vignette takes about the transition time to appear
+        assert!(radii[0] > 0.95);
+        let max_step = config().strength * FRAME_INTERVAL.as_secs_f32() / 0.2;
+        for pair in radii.windows(2) {
+            assert!(pair[1] <= pair[0]);
+            assert!(pair[0] - pair[1] <= max_step + 1e-4);
+        }
+    }
+}
@@ -0,0 +1,279 @@
+use alvr_common::Rng;
+use alvr_session::DitheringMode;
+
+const BAYER_SIZE: usize = 8;
+const BLUE_NOISE_SIZE: usize = 64;
+// Standard deviation of the energy filter of the void and cluster method, in pixels
+const BLUE_NOISE_SIGMA: f32 = 1.9;
+
+// Square tile of dithering thresholds, repeated over the image of both eyes. Thresholds are stored
+// as 8 bit levels, in row major order, which is also the layout of the noise texture.
+pub struct DitherPattern {
+    pub size: usize,
+    pub data: Vec<u8>,
+}
+
+impl DitherPattern {
+    // The blue noise depends on the random generator, the ordered pattern is always the same
+    pub fn new(mode: DitheringMode, rng: &mut Rng) -> Self {
+        match mode {
+            DitheringMode::Ordered => Self::bayer(),
+            DitheringMode::BlueNoise => Self::blue_noise(rng),
+        }
+    }
+
+    fn from_ranks(size: usize, ranks: impl Iterator<Item = usize>) -> Self {
+        let count = size * size;
+        let data = ranks.map(|rank| (rank * 256 / count) as u8).collect();
+
+        Self { size, data }
+    }
+
+    fn bayer() -> Self {
+        let rank = |x: usize, y: usize| {
+            (0..BAYER_SIZE.trailing_zeros()).fold(0, |rank, bit| {
+                let bx = (x >> bit) & 1;
+                let by = (y >> bit) & 1;
+                rank | (((bx ^ by) << 1 | by) << (2 * (BAYER_SIZE.trailing_zeros() - 1 - bit)))
+            })
+        };
+
+        Self::from_ranks(
+            BAYER_SIZE,
+            (0..BAYER_SIZE * BAYER_SIZE).map(|i| rank(i % BAYER_SIZE, i / BAYER_SIZE)),
+        )
+    }
+
+    // Void and cluster method, starting from an empty pattern: each pixel is ranked in the order it
+    // fills the largest void left by the previous ones. The energy filter wraps around, so the tile
+    // is seamless
+    fn blue_noise(rng: &mut Rng) -> Self {
+        let size = BLUE_NOISE_SIZE;
+        let count = size * size;
+
+        let wrapped_offset = |offset: usize| {
+            let offset = offset as f32;
+            if offset > size as f32 / 2.0 {
+                offset - size as f32
+            } else {
+                offset
+            }
+        };
+        let kernel = (0..count)
+            .map(|i| {
+                let dx = wrapped_offset(i % size);
+                let dy = wrapped_offset(i / size);
+                (-(dx * dx + dy * dy) / (2.0 * BLUE_NOISE_SIGMA * BLUE_NOISE_SIGMA)).exp()
+            })
+            .collect::<Vec<_>>();
+
+        // The initial jitter breaks the ties, so the seed selects the pattern
+        let mut energy = (0..count)
+            .map(|_| rng.next_f32() * 1e-3)
+            .collect::<Vec<_>>();
+        let mut ranks = vec![0; count];
+        for rank in 0..count {
+            let (index, _) = energy
+                .iter()
+                .enumerate()
+                .min_by(|(_, a), (_, b)| a.total_cmp(b))
+                .unwrap();
+            ranks[index] = rank;
+
+            let (x0, y0) = (index % size, index / size);
+            for (i, energy) in energy.iter_mut().enumerate() {
+                let dx = (i % size + size - x0) % size;
+                let dy = (i / size + size - y0) % size;
+                *energy += kernel[dy * size + dx];
+            }
+            energy[index] = f32::INFINITY;
+        }
+
+        Self::from_ranks(size, ranks.into_iter())
+    }
+
+    // Threshold in (0, 1) for a pixel of the output image
+    pub fn threshold(&self, x: usize, y: usize) -> f32 {
+        let level = self.data[(y % self.size) * self.size + x % self.size];
+
+        (level as f32 + 0.5) / 256.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graphics::gl_test::{self, TestGl, UniformBlock};
+    use alvr_common::RngSource;
+
+    const DITHER_PASS_CPP: &str = include_str!("../../cpp/dither_pass.cpp");
+
+    const WIDTH: usize = 256;
+    const HEIGHT: usize = 64;
+    const BLOCK_SIZE: usize = 8;
+
+    // Shallow horizontal gradient spanning 5 levels, where banding is most visible. sRGB encoded
+    fn gradient(x: f32) -> f32 {
+        0.4 + 5.0 / 255.0 * x / WIDTH as f32
+    }
+
+    fn srgb_to_linear(value: f32) -> f32 {
+        if value < 0.04045 {
+            value / 12.92
+        } else {
+            ((value + 0.055) / 1.055).powf(2.4)
+        }
+    }
+
+    // Renders the gradient with the dithering shader to an 8 bit sRGB texture, like the swapchain.
+    // The input is smaller than the output by the given scale. Returns the red levels of the rows,
+    // bottom row first like gl_FragCoord
+    fn render(
+        gl: &TestGl,
+        pattern: &DitherPattern,
+        strength: f32,
+        input_scale: usize,
+    ) -> Vec<Vec<u8>> {
+        let (input_width, input_height) = (WIDTH / input_scale, HEIGHT / input_scale);
+        let pixels = (0..input_width * input_height)
+            .map(|i| {
+                // Center of the input texel, in output pixels
+                let x = ((i % input_width) as f32 + 0.5) * input_scale as f32 - 0.5;
+                let value = srgb_to_linear(gradient(x));
+
+                [value, value, value, 1.0]
+            })
+            .collect::<Vec<_>>();
+        let input = gl.texture_rgba16f(input_width as _, input_height as _, &pixels);
+
+        let noise = gl.texture(
+            pattern.size as _,
+            pattern.size as _,
+            glow::R8,
+            glow::RED,
+            glow::UNSIGNED_BYTE,
+            Some(&pattern.data),
+        );
+
+        let output = gl.output(WIDTH as _, HEIGHT as _, glow::SRGB8_ALPHA8);
+        gl.render(
+            &gl_test::quad_vertex_shader(),
+            &gl_test::glsl(DITHER_PASS_CPP, "DITHER_FRAGMENT_SHADER"),
+            &[input, noise],
+            UniformBlock::default()
+                .float(strength)
+                .int(pattern.size as _),
+            &output,
+        );
+
+        gl.read_rgba8(&output)
+            .chunks(WIDTH)
+            .map(|row| row.iter().map(|pixel| pixel[0]).collect())
+            .collect()
+    }
+
+    // Errors of the image seen from a distance, as averages over blocks of pixels, in 8 bit steps.
+    // Without dithering, the error jumps between -0.5 and 0.5 at the edges of the bands
+    fn perceived_errors(image: &[Vec<u8>]) -> Vec<f32> {
+        let mut errors = vec![];
+        for by in (0..HEIGHT).step_by(BLOCK_SIZE) {
+            for bx in (0..WIDTH).step_by(BLOCK_SIZE) {
+                let mut error = 0.0;
+                for row in image.iter().skip(by).take(BLOCK_SIZE) {
+                    for (x, pixel) in row.iter().enumerate().skip(bx).take(BLOCK_SIZE) {
+                        error += *pixel as f32 - gradient(x as f32) * 255.0;
+                    }
+                }
+                errors.push((error / (BLOCK_SIZE * BLOCK_SIZE) as f32).abs());
+            }
+        }
+
+        errors
+    }
+
+    fn mean(values: &[f32]) -> f32 {
+        values.iter().sum::<f32>() / values.len() as f32
+    }
+
+    fn max(values: &[f32]) -> f32 {
+        values.iter().copied().fold(0.0, f32::max)
+    }
+
+    #[test]
+    #[ignore = "needs a GLES 3 driver"]
+    fn test_dithering_shader_reduces_banding_of_shallow_gradient() {
+        let gl = TestGl::new();
+
+        let source = RngSource::new(1234);
+        let ordered = DitherPattern::new(DitheringMode::Ordered, &mut source.stream("dithering"));
+        let blue_noise =
+            DitherPattern::new(DitheringMode::BlueNoise, &mut source.stream("dithering"));
+
+        // Without noise the shader only quantizes
+        let plain = render(&gl, &ordered, 0.0, 1);
+        assert_eq!(
+            plain[0].iter().step_by(32).copied().collect::<Vec<_>>(),
+            [102, 103, 103, 104, 105, 105, 106, 106]
+        );
+        let errors = perceived_errors(&plain);
+        assert!(mean(&errors) > 0.15);
+        assert!(max(&errors) > 0.4);
+
+        // Golden rows of the ordered dithering, which mixes the two closest levels
+        let image = render(&gl, &ordered, 1.0, 1);
+        assert_eq!(
+            image[1][..16],
+            [102, 102, 102, 102, 102, 102, 103, 102, 102, 102, 103, 102, 103, 102, 103, 102]
+        );
+        assert_eq!(
+            image[0][128..144],
+            [104, 105, 104, 105, 104, 105, 104, 105, 104, 105, 104, 105, 104, 105, 104, 105]
+        );
+
+        for image in [image, render(&gl, &blue_noise, 1.0, 1)] {
+            let errors = perceived_errors(&image);
+            assert!(mean(&errors) < 0.04);
+            assert!(max(&errors) < 0.1);
+        }
+
+        // When the pass upscales, the noise still changes at every output pixel
+        let upscaled = render(&gl, &ordered, 1.0, 2);
+        assert_eq!(
+            upscaled[0][128..144],
+            [104, 105, 104, 105, 104, 105, 104, 105, 104, 105, 104, 105, 104, 105, 104, 105]
+        );
+        assert!(mean(&perceived_errors(&upscaled)) < 0.04);
+    }
+
+    #[test]
+    fn test_blue_noise_is_uniform_and_seeded() {
+        let pattern = |seed| {
+            DitherPattern::new(
+                DitheringMode::BlueNoise,
+                &mut RngSource::new(seed).stream("dithering"),
+            )
+            .data
+        };
+
+        let data = pattern(1);
+        assert_eq!(data, pattern(1));
+        assert_ne!(data, pattern(2));
+
+        // Every level is used the same number of times
+        let mut histogram = [0; 256];
+        for level in &data {
+            histogram[*level as usize] += 1;
+        }
+        assert!(histogram.iter().all(|count| *count == 16));
+
+        // Neighboring pixels are never both dark or both bright
+        let size = BLUE_NOISE_SIZE;
+        for y in 0..size {
+            for x in 0..size {
+                let level = data[y * size + x] as i32;
+                let right = data[y * size + (x + 1) % size] as i32;
+                assert!(level + right > 8 && level + right < 502);
+            }
+        }
+    }
+}
@@ -0,0 +1,128 @@
+use alvr_common::{
+    glam::{EulerRot, Quat, Vec3},
+    Pose,
+};
+use alvr_session::{EyeCalibration, EyeCalibrationConfig};
+
+// Bigger corrections produce unusable output, and are most likely a misconfiguration
+const MAX_ROTATION_DEG: f32 = 2.0;
+const MAX_OFFSET_MM: f32 = 5.0;
+
+fn calibration_pose(calibration: &EyeCalibration) -> Pose {
+    let angle = |deg: f32| deg.clamp(-MAX_ROTATION_DEG, MAX_ROTATION_DEG).to_radians();
+    let offset = |mm: f32| mm.clamp(-MAX_OFFSET_MM, MAX_OFFSET_MM) / 1000.0;
+
+    Pose {
+        orientation: Quat::from_euler(
+            EulerRot::YXZ,
+            angle(calibration.yaw_deg),
+            angle(calibration.pitch_deg),
+            angle(calibration.roll_deg),
+        ),
+        position: Vec3::new(
+            offset(calibration.offset_x_mm),
+            offset(calibration.offset_y_mm),
+            offset(calibration.offset_z_mm),
+        ),
+    }
+}
+
+// Clamped calibration transforms, relative to each eye. Identity if disabled
+pub fn eye_calibration_poses(config: Option<&EyeCalibrationConfig>) -> [Pose; 2] {
+    config
+        .map(|config| {
+            [
+                calibration_pose(&config.left_eye),
+                calibration_pose(&config.right_eye),
+            ]
+        })
+        .unwrap_or_default()
+}
+
+// The view poses are reported to the runtime together with the stream images. Moving one of them
+// makes the runtime reproject that eye image accordingly
+pub fn apply_eye_calibration(view_poses: [Pose; 2], calibration_poses: [Pose; 2]) -> [Pose; 2] {
+    [
+        view_poses[0] * calibration_poses[0],
+        view_poses[1] * calibration_poses[1],
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alvr_common::glam::Vec2;
+
+    const IPD: f32 = 0.064;
+
+    fn no_calibration() -> EyeCalibration {
+        EyeCalibration {
+            yaw_deg: 0.0,
+            pitch_deg: 0.0,
+            roll_deg: 0.0,
+            offset_x_mm: 0.0,
+            offset_y_mm: 0.0,
+            offset_z_mm: 0.0,
+        }
+    }
+
+    fn view_poses() -> [Pose; 2] {
+        [-IPD / 2.0, IPD / 2.0].map(|x| Pose {
+            orientation: Quat::IDENTITY,
+            position: Vec3::new(x, 1.6, 0.0),
+        })
+    }
+
+    // Point in the image plane of a view (tangent space)
+    fn project(view_pose: Pose, point: Vec3) -> Vec2 {
+        let local = view_pose.orientation.inverse() * (point - view_pose.position);
+        Vec2::new(local.x, local.y) / -local.z
+    }
+
+    #[test]
+    fn test_rotation_shifts_only_calibrated_eye() {
+        let config = EyeCalibrationConfig {
+            left_eye: EyeCalibration {
+                yaw_deg: 1.0,
+                ..no_calibration()
+            },
+            right_eye: no_calibration(),
+        };
+
+        let point = Vec3::new(0.0, 1.6, -2.0);
+        let uncalibrated = view_poses().map(|pose| project(pose, point));
+        let calibrated = apply_eye_calibration(view_poses(), eye_calibration_poses(Some(&config)))
+            .map(|pose| project(pose, point));
+
+        // Turning the left view to the left moves the content to the right in its image
+        let expected_shift =
+            ((IPD / 2.0 / 2.0).atan() + 1_f32.to_radians()).tan() - IPD / 2.0 / 2.0;
+        assert!((calibrated[0].x - uncalibrated[0].x - expected_shift).abs() < 1e-5);
+        assert!((calibrated[0].y - uncalibrated[0].y).abs() < 1e-5);
+
+        assert!((calibrated[1] - uncalibrated[1]).length() < 1e-6);
+    }
+
+    #[test]
+    fn test_calibration_is_clamped() {
+        let config = EyeCalibrationConfig {
+            left_eye: EyeCalibration {
+                pitch_deg: 30.0,
+                offset_z_mm: -100.0,
+                ..no_calibration()
+            },
+            right_eye: no_calibration(),
+        };
+
+        let [left, right] = eye_calibration_poses(Some(&config));
+        assert!(left
+            .orientation
+            .abs_diff_eq(Quat::from_rotation_x(MAX_ROTATION_DEG.to_radians()), 1e-6));
+        assert!((left.position.z + MAX_OFFSET_MM / 1000.0).abs() < 1e-6);
+        assert_eq!(right.orientation, Quat::IDENTITY);
+        assert_eq!(right.position, Vec3::ZERO);
+
+        let [left, _] = eye_calibration_poses(None);
+        assert_eq!(left.orientation, Quat::IDENTITY);
+    }
+}
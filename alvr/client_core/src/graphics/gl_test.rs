@@ -0,0 +1,448 @@
+// Runs the shaders of the C++ passes offscreen, with the pipeline of gl_render_utils: a quad without
+// vertex buffers, samplers named tex0, tex1... and at most one uniform block. The shader tests are
+// ignored by default, run them with `cargo test -p alvr_client_core -- --ignored`. On headless
+// machines Mesa provides a software driver with EGL_PLATFORM=surfaceless.
+
+use glow::HasContext;
+use khronos_egl::{self as egl, EGL1_4};
+
+pub const RENDER_PIPELINE_H: &str = include_str!("../../cpp/gl_render_utils/render_pipeline.h");
+
+// GLSL of a raw string literal of the C++ sources, declared like `NAME = R"glsl(...)glsl"`
+pub fn glsl(cpp_source: &str, name: &str) -> String {
+    let is_identifier = |c: char| c.is_ascii_alphanumeric() || c == '_';
+
+    let (declaration, _) = cpp_source
+        .match_indices(name)
+        .find(|(index, _)| {
+            !cpp_source[..*index].ends_with(is_identifier)
+                && !cpp_source[index + name.len()..].starts_with(is_identifier)
+        })
+        .unwrap_or_else(|| panic!("{name} not found"));
+
+    let start = declaration + cpp_source[declaration..].find("R\"glsl(").unwrap() + 7;
+    let end = start + cpp_source[start..].find(")glsl\"").unwrap();
+
+    cpp_source[start..end].to_owned()
+}
+
+pub fn quad_vertex_shader() -> String {
+    glsl(RENDER_PIPELINE_H, "QUAD_2D_VERTEX_SHADER")
+}
+
+// Fields are aligned with the std140 layout
+#[derive(Default)]
+pub struct UniformBlock(Vec<u8>);
+
+impl UniformBlock {
+    fn push(mut self, alignment: usize, values: &[[u8; 4]]) -> Self {
+        self.0.resize(self.0.len().next_multiple_of(alignment), 0);
+        self.0.extend(values.iter().flatten());
+
+        self
+    }
+
+    pub fn float(self, value: f32) -> Self {
+        self.push(4, &[value.to_ne_bytes()])
+    }
+
+    pub fn int(self, value: i32) -> Self {
+        self.push(4, &[value.to_ne_bytes()])
+    }
+
+    pub fn uint(self, value: u32) -> Self {
+        self.push(4, &[value.to_ne_bytes()])
+    }
+
+    pub fn uvec2(self, value: [u32; 2]) -> Self {
+        self.push(8, &value.map(u32::to_ne_bytes))
+    }
+
+    pub fn vec3(self, value: [f32; 3]) -> Self {
+        self.push(16, &value.map(f32::to_ne_bytes))
+    }
+
+    pub fn vec4(self, value: [f32; 4]) -> Self {
+        self.push(16, &value.map(f32::to_ne_bytes))
+    }
+
+    // Column major
+    pub fn mat4(self, columns: [[f32; 4]; 4]) -> Self {
+        columns
+            .into_iter()
+            .fold(self, |block, column| block.vec4(column))
+    }
+
+    fn into_bytes(mut self) -> Vec<u8> {
+        self.0.resize(self.0.len().next_multiple_of(16), 0);
+
+        self.0
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct Input {
+    pub target: u32,
+    pub texture: glow::Texture,
+}
+
+pub struct Output {
+    pub width: u32,
+    pub height: u32,
+    framebuffer: glow::Framebuffer,
+}
+
+pub struct TestGl {
+    instance: egl::DynamicInstance<EGL1_4>,
+    display: egl::Display,
+    surface: egl::Surface,
+    context: egl::Context,
+    pub gl: glow::Context,
+}
+
+impl TestGl {
+    pub fn new() -> Self {
+        Self::create().expect("No GLES 3 driver. On headless machines set EGL_PLATFORM=surfaceless")
+    }
+
+    fn create() -> Option<Self> {
+        let instance = unsafe { egl::DynamicInstance::<EGL1_4>::load_required().ok()? };
+        let display = unsafe { instance.get_display(egl::DEFAULT_DISPLAY)? };
+        instance.initialize(display).ok()?;
+
+        const CONFIG_ATTRIBS: [i32; 11] = [
+            egl::RED_SIZE,
+            8,
+            egl::GREEN_SIZE,
+            8,
+            egl::BLUE_SIZE,
+            8,
+            egl::SURFACE_TYPE,
+            egl::PBUFFER_BIT,
+            egl::RENDERABLE_TYPE,
+            egl::OPENGL_ES3_BIT,
+            egl::NONE,
+        ];
+        let config = instance
+            .choose_first_config(display, &CONFIG_ATTRIBS)
+            .ok()??;
+
+        instance.bind_api(egl::OPENGL_ES_API).ok()?;
+
+        const CONTEXT_ATTRIBS: [i32; 3] = [egl::CONTEXT_CLIENT_VERSION, 3, egl::NONE];
+        let context = instance
+            .create_context(display, config, None, &CONTEXT_ATTRIBS)
+            .ok()?;
+
+        const PBUFFER_ATTRIBS: [i32; 5] = [egl::WIDTH, 16, egl::HEIGHT, 16, egl::NONE];
+        let surface = instance
+            .create_pbuffer_surface(display, config, &PBUFFER_ATTRIBS)
+            .ok()?;
+
+        instance
+            .make_current(display, Some(surface), Some(surface), Some(context))
+            .ok()?;
+
+        let gl = unsafe {
+            glow::Context::from_loader_function(|name| {
+                instance
+                    .get_proc_address(name)
+                    .map_or(std::ptr::null(), |function| function as *const _)
+            })
+        };
+
+        Some(Self {
+            instance,
+            display,
+            surface,
+            context,
+            gl,
+        })
+    }
+
+    // Like gl_render_utils::Texture: clamped to the edges, with linear filtering
+    pub fn texture(
+        &self,
+        width: u32,
+        height: u32,
+        internal_format: u32,
+        format: u32,
+        ty: u32,
+        data: Option<&[u8]>,
+    ) -> Input {
+        unsafe {
+            let texture = self.gl.create_texture().unwrap();
+            self.gl.bind_texture(glow::TEXTURE_2D, Some(texture));
+            self.gl.pixel_store_i32(glow::UNPACK_ALIGNMENT, 1);
+            self.gl.tex_image_2d(
+                glow::TEXTURE_2D,
+                0,
+                internal_format as _,
+                width as _,
+                height as _,
+                0,
+                format,
+                ty,
+                data,
+            );
+            self.set_sampling(glow::TEXTURE_2D);
+
+            Input {
+                target: glow::TEXTURE_2D,
+                texture,
+            }
+        }
+    }
+
+    // Linear RGBA pixels in a 16 bit float texture, bottom row first
+    pub fn texture_rgba16f(&self, width: u32, height: u32, pixels: &[[f32; 4]]) -> Input {
+        self.float_texture(width, height, glow::RGBA16F, pixels)
+    }
+
+    // Like texture_rgba16f(), for values that need the full precision
+    pub fn texture_rgba32f(&self, width: u32, height: u32, pixels: &[[f32; 4]]) -> Input {
+        self.float_texture(width, height, glow::RGBA32F, pixels)
+    }
+
+    fn float_texture(
+        &self,
+        width: u32,
+        height: u32,
+        internal_format: u32,
+        pixels: &[[f32; 4]],
+    ) -> Input {
+        let bytes = pixels
+            .iter()
+            .flatten()
+            .flat_map(|value| value.to_ne_bytes())
+            .collect::<Vec<_>>();
+
+        self.texture(
+            width,
+            height,
+            internal_format,
+            glow::RGBA,
+            glow::FLOAT,
+            Some(&bytes),
+        )
+    }
+
+    pub fn texture_3d(
+        &self,
+        size: u32,
+        internal_format: u32,
+        format: u32,
+        ty: u32,
+        data: &[u8],
+    ) -> Input {
+        unsafe {
+            let texture = self.gl.create_texture().unwrap();
+            self.gl.bind_texture(glow::TEXTURE_3D, Some(texture));
+            self.gl.pixel_store_i32(glow::UNPACK_ALIGNMENT, 1);
+            self.gl.tex_image_3d(
+                glow::TEXTURE_3D,
+                0,
+                internal_format as _,
+                size as _,
+                size as _,
+                size as _,
+                0,
+                format,
+                ty,
+                Some(data),
+            );
+            self.set_sampling(glow::TEXTURE_3D);
+
+            Input {
+                target: glow::TEXTURE_3D,
+                texture,
+            }
+        }
+    }
+
+    unsafe fn set_sampling(&self, target: u32) {
+        for wrap in [
+            glow::TEXTURE_WRAP_S,
+            glow::TEXTURE_WRAP_T,
+            glow::TEXTURE_WRAP_R,
+        ] {
+            self.gl
+                .tex_parameter_i32(target, wrap, glow::CLAMP_TO_EDGE as _);
+        }
+        self.gl
+            .tex_parameter_i32(target, glow::TEXTURE_MAG_FILTER, glow::LINEAR as _);
+        self.gl
+            .tex_parameter_i32(target, glow::TEXTURE_MIN_FILTER, glow::LINEAR as _);
+    }
+
+    // Render target cleared to transparent black, like a newly allocated texture
+    pub fn output(&self, width: u32, height: u32, internal_format: u32) -> Output {
+        unsafe {
+            let texture = self.gl.create_texture().unwrap();
+            self.gl.bind_texture(glow::TEXTURE_2D, Some(texture));
+            self.gl.tex_storage_2d(
+                glow::TEXTURE_2D,
+                1,
+                internal_format,
+                width as _,
+                height as _,
+            );
+
+            let framebuffer = self.gl.create_framebuffer().unwrap();
+            self.gl
+                .bind_framebuffer(glow::FRAMEBUFFER, Some(framebuffer));
+            self.gl.framebuffer_texture_2d(
+                glow::FRAMEBUFFER,
+                glow::COLOR_ATTACHMENT0,
+                glow::TEXTURE_2D,
+                Some(texture),
+                0,
+            );
+            assert_eq!(
+                self.gl.check_framebuffer_status(glow::FRAMEBUFFER),
+                glow::FRAMEBUFFER_COMPLETE
+            );
+
+            self.gl.clear_color(0.0, 0.0, 0.0, 0.0);
+            self.gl.clear(glow::COLOR_BUFFER_BIT);
+
+            Output {
+                width,
+                height,
+                framebuffer,
+            }
+        }
+    }
+
+    // Draws the quad like RenderPipeline::Render(), blending with the previous content of the
+    // output
+    pub fn render(
+        &self,
+        vertex_shader: &str,
+        fragment_shader: &str,
+        inputs: &[Input],
+        block: UniformBlock,
+        output: &Output,
+    ) {
+        unsafe {
+            let program = self.gl.create_program().unwrap();
+            for (ty, source) in [
+                (glow::VERTEX_SHADER, vertex_shader),
+                (glow::FRAGMENT_SHADER, fragment_shader),
+            ] {
+                let shader = self.gl.create_shader(ty).unwrap();
+                self.gl.shader_source(shader, source);
+                self.gl.compile_shader(shader);
+                assert!(
+                    self.gl.get_shader_compile_status(shader),
+                    "{}",
+                    self.gl.get_shader_info_log(shader)
+                );
+                self.gl.attach_shader(program, shader);
+            }
+            self.gl.link_program(program);
+            assert!(
+                self.gl.get_program_link_status(program),
+                "{}",
+                self.gl.get_program_info_log(program)
+            );
+            self.gl.use_program(Some(program));
+
+            for (index, input) in inputs.iter().enumerate() {
+                self.gl.active_texture(glow::TEXTURE0 + index as u32);
+                self.gl.bind_texture(input.target, Some(input.texture));
+                let location = self
+                    .gl
+                    .get_uniform_location(program, &format!("tex{index}"));
+                self.gl.uniform_1_i32(location.as_ref(), index as _);
+            }
+
+            if !block.0.is_empty() {
+                let block = block.into_bytes();
+                self.gl.uniform_block_binding(program, 0, 0);
+                let buffer = self.gl.create_buffer().unwrap();
+                self.gl.bind_buffer(glow::UNIFORM_BUFFER, Some(buffer));
+                self.gl
+                    .buffer_data_u8_slice(glow::UNIFORM_BUFFER, &block, glow::DYNAMIC_DRAW);
+                self.gl
+                    .bind_buffer_base(glow::UNIFORM_BUFFER, 0, Some(buffer));
+            }
+
+            self.gl
+                .bind_framebuffer(glow::FRAMEBUFFER, Some(output.framebuffer));
+            self.gl
+                .viewport(0, 0, output.width as _, output.height as _);
+            self.gl.enable(glow::BLEND);
+            self.gl
+                .blend_func(glow::SRC_ALPHA, glow::ONE_MINUS_SRC_ALPHA);
+
+            let vertex_array = self.gl.create_vertex_array().unwrap();
+            self.gl.bind_vertex_array(Some(vertex_array));
+            self.gl.draw_arrays(glow::TRIANGLE_STRIP, 0, 4);
+            assert_eq!(self.gl.get_error(), glow::NO_ERROR);
+        }
+    }
+
+    // Stored 8 bit values, sRGB encoded for sRGB outputs. Bottom row first
+    pub fn read_rgba8(&self, output: &Output) -> Vec<[u8; 4]> {
+        let mut bytes = vec![0; (output.width * output.height * 4) as usize];
+        unsafe {
+            self.gl
+                .bind_framebuffer(glow::FRAMEBUFFER, Some(output.framebuffer));
+            self.gl.read_pixels(
+                0,
+                0,
+                output.width as _,
+                output.height as _,
+                glow::RGBA,
+                glow::UNSIGNED_BYTE,
+                glow::PixelPackData::Slice(&mut bytes),
+            );
+        }
+
+        bytes
+            .chunks_exact(4)
+            .map(|pixel| pixel.try_into().unwrap())
+            .collect()
+    }
+
+    // For float outputs. Bottom row first
+    pub fn read_rgba_f32(&self, output: &Output) -> Vec<[f32; 4]> {
+        let mut bytes = vec![0; (output.width * output.height * 16) as usize];
+        unsafe {
+            self.gl
+                .bind_framebuffer(glow::FRAMEBUFFER, Some(output.framebuffer));
+            self.gl.read_pixels(
+                0,
+                0,
+                output.width as _,
+                output.height as _,
+                glow::RGBA,
+                glow::FLOAT,
+                glow::PixelPackData::Slice(&mut bytes),
+            );
+        }
+
+        bytes
+            .chunks_exact(16)
+            .map(|pixel| {
+                [0, 1, 2, 3].map(|c| f32::from_ne_bytes(pixel[c * 4..][..4].try_into().unwrap()))
+            })
+            .collect()
+    }
+}
+
+impl Drop for TestGl {
+    // The display is shared with the other tests, it's not terminated
+    fn drop(&mut self) {
+        self.instance
+            .make_current(self.display, None, None, None)
+            .ok();
+        self.instance
+            .destroy_surface(self.display, self.surface)
+            .ok();
+        self.instance
+            .destroy_context(self.display, self.context)
+            .ok();
+    }
+}
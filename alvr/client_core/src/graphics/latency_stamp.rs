@@ -0,0 +1,153 @@
+use alvr_common::glam::UVec2;
+use alvr_session::{LatencyStampConfig, StampCorner};
+use std::time::Duration;
+
+// The data cells are surrounded by a ring of white cells, which marks the position and the cell
+// size of the stamp
+pub const DATA_COLUMNS: u32 = 8;
+pub const DATA_ROWS: u32 = 6;
+pub const DATA_BITS: u32 = DATA_COLUMNS * DATA_ROWS;
+const GRID_SIZE: UVec2 = UVec2::new(DATA_COLUMNS + 2, DATA_ROWS + 2);
+
+// Value shown by the stamp: the frame timestamp in microseconds, truncated to the data bits. It
+// wraps every 8.9 years
+pub fn stamp_value(timestamp: Duration) -> u64 {
+    timestamp.as_micros() as u64 & ((1 << DATA_BITS) - 1)
+}
+
+// Binary stamp drawn in a corner of each eye image, to measure the latency with an external
+// camera. Each cell is a square of pixels, white for 1, black for 0. The bits are written from the
+// most significant, row by row starting from the top left data cell.
+pub struct LatencyStamp {
+    // Top left pixel of the stamp (Y down)
+    pub origin: UVec2,
+    pub cell_size: u32,
+}
+
+impl LatencyStamp {
+    pub fn new(config: &LatencyStampConfig, view_resolution: UVec2) -> Self {
+        // The stamp must fit in the image
+        let cell_size = config
+            .cell_size_px
+            .clamp(1, u32::max((view_resolution / GRID_SIZE).min_element(), 1));
+        let size = GRID_SIZE * cell_size;
+        let margin = view_resolution.as_vec2() * config.margin.clamp(0.0, 1.0);
+        let free_space = view_resolution.saturating_sub(size);
+        let margin = UVec2::new(margin.x as u32, margin.y as u32).min(free_space);
+
+        let origin = match config.corner {
+            StampCorner::TopLeft => margin,
+            StampCorner::TopRight => UVec2::new(free_space.x - margin.x, margin.y),
+            StampCorner::BottomLeft => UVec2::new(margin.x, free_space.y - margin.y),
+            StampCorner::BottomRight => free_space - margin,
+        };
+
+        Self { origin, cell_size }
+    }
+
+    // Reads the value from the center of each cell of an image containing the stamp. Returns None
+    // if the white ring is not found, for example if the image shows the blending of two frames
+    pub fn read(&self, image: impl Fn(UVec2) -> bool) -> Option<u64> {
+        let cell_center =
+            |x, y| self.origin + UVec2::new(x, y) * self.cell_size + self.cell_size / 2;
+
+        let ring_found = (0..GRID_SIZE.x)
+            .all(|x| image(cell_center(x, 0)) && image(cell_center(x, GRID_SIZE.y - 1)))
+            && (0..GRID_SIZE.y)
+                .all(|y| image(cell_center(0, y)) && image(cell_center(GRID_SIZE.x - 1, y)));
+        if !ring_found {
+            return None;
+        }
+
+        let value = (0..DATA_BITS).fold(0, |value, index| {
+            let bit = image(cell_center(
+                index % DATA_COLUMNS + 1,
+                index / DATA_COLUMNS + 1,
+            ));
+            (value << 1) | bit as u64
+        });
+
+        Some(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graphics::gl_test::{self, TestGl, UniformBlock};
+
+    const LATENCY_STAMP_PASS_CPP: &str = include_str!("../../cpp/latency_stamp_pass.cpp");
+
+    const VIEW_RESOLUTION: UVec2 = UVec2::new(1832, 1920);
+
+    fn config(corner: StampCorner) -> LatencyStampConfig {
+        LatencyStampConfig {
+            corner,
+            margin: 0.2,
+            cell_size_px: 6,
+        }
+    }
+
+    // Draws the stamp with the shader over a black eye image, with the block of
+    // draw_latency_stamp(). Returns the white pixels from the top row
+    fn render(gl: &TestGl, stamp: &LatencyStamp, value: u64) -> Vec<bool> {
+        let output = gl.output(VIEW_RESOLUTION.x, VIEW_RESOLUTION.y, glow::RGBA8);
+        gl.render(
+            &gl_test::quad_vertex_shader(),
+            &gl_test::glsl(LATENCY_STAMP_PASS_CPP, "LATENCY_STAMP_FRAGMENT_SHADER"),
+            &[],
+            UniformBlock::default()
+                .uvec2(stamp.origin.to_array())
+                .uint(stamp.cell_size)
+                .uint(VIEW_RESOLUTION.y)
+                .uvec2([value as u32, (value >> 32) as u32]),
+            &output,
+        );
+
+        let pixels = gl.read_rgba8(&output);
+        pixels
+            .chunks_exact(VIEW_RESOLUTION.x as usize)
+            .rev()
+            .flatten()
+            .map(|pixel| pixel[0] > 127)
+            .collect()
+    }
+
+    #[test]
+    #[ignore = "needs a GLES 3 driver"]
+    fn test_rendered_stamp_reads_back_the_timestamp() {
+        let gl = TestGl::new();
+
+        let timestamp = Duration::from_secs(3 * 3600) + Duration::from_nanos(123_456_789);
+        let value = stamp_value(timestamp);
+        assert_eq!(value, 10_800_123_456);
+
+        for corner in [
+            StampCorner::TopLeft,
+            StampCorner::TopRight,
+            StampCorner::BottomLeft,
+            StampCorner::BottomRight,
+        ] {
+            let stamp = LatencyStamp::new(&config(corner), VIEW_RESOLUTION);
+            assert_eq!(stamp.cell_size, 6);
+
+            let image = render(&gl, &stamp, value);
+            let read_pixel = |pixel: UVec2| image[(pixel.y * VIEW_RESOLUTION.x + pixel.x) as usize];
+            assert_eq!(stamp.read(read_pixel), Some(value));
+
+            // The stamp is inside the image, away from the corner
+            let end = stamp.origin + GRID_SIZE * stamp.cell_size;
+            assert!(end.x <= VIEW_RESOLUTION.x && end.y <= VIEW_RESOLUTION.y);
+            assert!(stamp.origin.x >= 366 || VIEW_RESOLUTION.x - end.x >= 366);
+        }
+
+        // Nothing is read where there is no stamp
+        let stamp = LatencyStamp::new(&config(StampCorner::TopLeft), VIEW_RESOLUTION);
+        let other_corner = LatencyStamp::new(&config(StampCorner::BottomRight), VIEW_RESOLUTION);
+        let image = render(&gl, &stamp, value);
+        assert_eq!(
+            other_corner.read(|pixel| image[(pixel.y * VIEW_RESOLUTION.x + pixel.x) as usize]),
+            None
+        );
+    }
+}
@@ -0,0 +1,57 @@
+use alvr_common::{
+    anyhow::{bail, Result},
+    glam::Vec2,
+};
+use alvr_session::LensCenterOffsetConfig;
+
+pub const CENTERED_LENSES: [Vec2; 2] = [Vec2::new(0.5, 0.5); 2];
+
+// Optical center of each lens in the UV coordinates of its eye image, left eye first. The centers
+// must be inside of the image, the vignette correction grows from them.
+pub fn lens_centers(config: Option<&LensCenterOffsetConfig>) -> Result<[Vec2; 2]> {
+    let Some(config) = config else {
+        return Ok(CENTERED_LENSES);
+    };
+
+    let offsets = [
+        Vec2::new(config.left_x, config.left_y),
+        Vec2::new(config.right_x, config.right_y),
+    ];
+    for (eye, offset) in ["left", "right"].into_iter().zip(offsets) {
+        if !offset.is_finite() || offset.abs().max_element() >= 0.5 {
+            bail!("The {eye} lens center offset {offset} is outside of the eye image");
+        }
+    }
+
+    Ok(offsets.map(|offset| Vec2::splat(0.5) + offset))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(left: Vec2, right: Vec2) -> LensCenterOffsetConfig {
+        LensCenterOffsetConfig {
+            left_x: left.x,
+            left_y: left.y,
+            right_x: right.x,
+            right_y: right.y,
+        }
+    }
+
+    #[test]
+    fn test_lens_centers_are_validated() {
+        assert_eq!(lens_centers(None).unwrap(), CENTERED_LENSES);
+
+        // Lenses closer to the nose than the image centers
+        let centers =
+            lens_centers(Some(&config(Vec2::new(0.1, -0.05), Vec2::new(-0.1, -0.05)))).unwrap();
+        assert!((centers[0] - Vec2::new(0.6, 0.45)).length() < 1e-6);
+        assert!((centers[1] - Vec2::new(0.4, 0.45)).length() < 1e-6);
+
+        for offset in [0.5, -0.7, f32::NAN, f32::INFINITY] {
+            assert!(lens_centers(Some(&config(Vec2::new(offset, 0.0), Vec2::ZERO))).is_err());
+            assert!(lens_centers(Some(&config(Vec2::ZERO, Vec2::new(0.0, offset)))).is_err());
+        }
+    }
+}
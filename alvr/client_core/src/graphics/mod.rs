@@ -1,14 +1,38 @@
+mod color_lut;
+mod comfort_vignette;
+mod dithering;
+mod eye_calibration;
+#[cfg(test)]
+mod gl_test;
+mod latency_stamp;
+mod lens_center;
 mod lobby;
+mod motion_smoothing;
 mod opengl;
+mod reticle;
 mod stream;
-
+mod test_pattern;
+mod vignette_correction;
+mod virtual_screen;
+
+pub use color_lut::*;
+pub use comfort_vignette::*;
+pub use dithering::*;
+pub use eye_calibration::*;
+pub use latency_stamp::*;
+pub use lens_center::*;
 pub use lobby::*;
-pub use opengl::choose_swapchain_format;
+pub use motion_smoothing::*;
+pub use opengl::{choose_swapchain_format, is_8_bit_swapchain_format, supports_10_bit_swapchain};
+pub use reticle::*;
 pub use stream::*;
+pub use test_pattern::*;
+pub use virtual_screen::*;
 
 use alvr_common::{Fov, Pose};
 use khronos_egl::{self as egl, EGL1_4};
 
+#[derive(Clone, Copy)]
 pub struct RenderViewInput {
     pub pose: Pose,
     pub fov: Fov,
@@ -0,0 +1,175 @@
+// Decides which frame to present at each display refresh. When the stream framerate is lower than
+// the refresh rate, a new frame is first presented interpolated halfway from the previous one and
+// then as is on the following refresh.
+pub struct MotionSmoothingScheduler {
+    refreshes_since_new_frame: u32,
+}
+
+impl MotionSmoothingScheduler {
+    pub fn new() -> Self {
+        Self {
+            refreshes_since_new_frame: 0,
+        }
+    }
+
+    // Returns the interpolation factor between the previous (0) and the last (1) frame
+    pub fn interpolation_factor(&mut self, new_frame: bool) -> f32 {
+        if new_frame {
+            let repeated = self.refreshes_since_new_frame > 0;
+            self.refreshes_since_new_frame = 0;
+
+            if repeated {
+                0.5
+            } else {
+                1.0
+            }
+        } else {
+            self.refreshes_since_new_frame += 1;
+
+            1.0
+        }
+    }
+}
+
+impl Default for MotionSmoothingScheduler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graphics::gl_test::{self, Input, TestGl, UniformBlock};
+
+    const MOTION_SMOOTHING_PASS_CPP: &str = include_str!("../../cpp/motion_smoothing_pass.cpp");
+
+    const MOTION_BLOCK_SIZE: u32 = 16;
+    const MOTION_SEARCH_RANGE: f32 = 8.0;
+    const WIDTH: u32 = 64;
+    const HEIGHT: u32 = 32;
+    const SQUARE_SIZE: u32 = 8;
+
+    const WHITE: [u8; 4] = [255; 4];
+    const BLACK: [u8; 4] = [0, 0, 0, 255];
+
+    fn square_frame(square_x: u32) -> Vec<[u8; 4]> {
+        (0..WIDTH * HEIGHT)
+            .map(|i| {
+                let (x, y) = (i % WIDTH, i / WIDTH);
+                let inside = (square_x..square_x + SQUARE_SIZE).contains(&x)
+                    && (20..20 + SQUARE_SIZE).contains(&y);
+
+                if inside {
+                    WHITE
+                } else {
+                    BLACK
+                }
+            })
+            .collect()
+    }
+
+    fn upload(gl: &TestGl, width: u32, height: u32, pixels: &[[u8; 4]]) -> Input {
+        gl.texture(
+            width,
+            height,
+            glow::RGBA8,
+            glow::RGBA,
+            glow::UNSIGNED_BYTE,
+            Some(&pixels.concat()),
+        )
+    }
+
+    fn shader(name: &str) -> String {
+        gl_test::glsl(MOTION_SMOOTHING_PASS_CPP, "MOTION_SMOOTHING_SHADER_HEADER")
+            + &gl_test::glsl(MOTION_SMOOTHING_PASS_CPP, name)
+    }
+
+    // Horizontal span of the white pixels of a row
+    fn white_span(pixels: &[[u8; 4]], y: u32) -> (u32, u32) {
+        let row = &pixels[(y * WIDTH) as usize..((y + 1) * WIDTH) as usize];
+        let first = row.iter().position(|p| *p == WHITE).unwrap() as u32;
+        let last = row.iter().rposition(|p| *p == WHITE).unwrap() as u32;
+
+        (first, last)
+    }
+
+    #[test]
+    #[ignore = "needs a GLES 3 driver"]
+    fn test_midpoint_frame_is_between_frames() {
+        let gl = TestGl::new();
+
+        let previous_pixels = square_frame(16);
+        let current_pixels = square_frame(24);
+        let previous = upload(&gl, WIDTH, HEIGHT, &previous_pixels);
+        let current = upload(&gl, WIDTH, HEIGHT, &current_pixels);
+
+        // The motion texture has the format of the one of the pass
+        let (blocks_x, blocks_y) = (WIDTH / MOTION_BLOCK_SIZE, HEIGHT / MOTION_BLOCK_SIZE);
+        let motion_output = gl.output(blocks_x, blocks_y, glow::RGBA8);
+        gl.render(
+            &gl_test::quad_vertex_shader(),
+            &shader("MOTION_ESTIMATION_FRAGMENT_SHADER"),
+            &[previous, current],
+            UniformBlock::default(),
+            &motion_output,
+        );
+        let motion_pixels = gl.read_rgba8(&motion_output);
+
+        let motion_vector = |block: u32| {
+            let [x, y, ..] = motion_pixels[block as usize];
+            [x, y].map(|value| ((value as f32 / 255.0 - 0.5) * 2.0 * MOTION_SEARCH_RANGE).round())
+        };
+        assert_eq!(motion_vector(blocks_x + 1), [8.0, 0.0]);
+        assert_eq!(motion_vector(0), [0.0, 0.0]);
+
+        let motion = upload(&gl, blocks_x, blocks_y, &motion_pixels);
+        let interpolate = |interpolation_factor| {
+            let output = gl.output(WIDTH, HEIGHT, glow::RGBA8);
+            gl.render(
+                &gl_test::quad_vertex_shader(),
+                &shader("INTERPOLATION_FRAGMENT_SHADER"),
+                &[previous, current, motion],
+                UniformBlock::default().float(interpolation_factor),
+                &output,
+            );
+
+            gl.read_rgba8(&output)
+        };
+
+        let midpoint = interpolate(0.5);
+
+        // The square moved halfway and it is not ghosted
+        assert_eq!(white_span(&midpoint, 24), (20, 27));
+        assert!(midpoint.iter().all(|p| *p == WHITE || *p == BLACK));
+        assert_eq!(
+            midpoint.iter().filter(|p| **p == WHITE).count(),
+            (SQUARE_SIZE * SQUARE_SIZE) as usize
+        );
+
+        let (previous_start, _) = white_span(&previous_pixels, 24);
+        let (current_start, _) = white_span(&current_pixels, 24);
+        let (midpoint_start, _) = white_span(&midpoint, 24);
+        assert!(previous_start < midpoint_start && midpoint_start < current_start);
+
+        // The ends of the interval are the original frames. Without motion vectors the pass
+        // renders at the end, which duplicates the last frame
+        assert_eq!(interpolate(1.0), current_pixels);
+        assert_eq!(interpolate(0.0), previous_pixels);
+    }
+    #[test]
+    fn test_interpolation_only_below_refresh_rate() {
+        let mut scheduler = MotionSmoothingScheduler::new();
+
+        // Stream at the refresh rate
+        for _ in 0..3 {
+            assert_eq!(scheduler.interpolation_factor(true), 1.0);
+        }
+
+        // Stream at half the refresh rate
+        for _ in 0..3 {
+            assert_eq!(scheduler.interpolation_factor(false), 1.0);
+            assert_eq!(scheduler.interpolation_factor(true), 0.5);
+        }
+    }
+}
@@ -3,7 +3,7 @@
 #[cfg(target_os = "android")]
 include!(concat!(env!("OUT_DIR"), "/bindings.rs"));
 
-pub fn choose_swapchain_format(formats: Option<&[u32]>, enable_hdr: bool) -> u32 {
+pub fn choose_swapchain_format(formats: Option<&[u32]>, enable_hdr: bool, bit_depth: u8) -> u32 {
     // Priority-sorted list of swapchain formats we'll accept--
     let mut app_supported_swapchain_formats = vec![
         glow::SRGB8_ALPHA8,
@@ -14,6 +14,11 @@ pub fn choose_swapchain_format(formats: Option<&[u32]>, enable_hdr: bool) -> u32
         glow::BGR,
     ];
 
+    // 10 bit streams would be quantized by 8 bit swapchains, reintroducing the banding
+    if bit_depth == 10 {
+        app_supported_swapchain_formats.insert(0, glow::RGB10_A2);
+    }
+
     // float16 is required for HDR output. However, float16 swapchains
     // have a high perf cost, so only use these if HDR is enabled.
     if enable_hdr {
@@ -32,3 +37,18 @@ pub fn choose_swapchain_format(formats: Option<&[u32]>, enable_hdr: bool) -> u32
     // If we can't enumerate, default to a required format (SRGBA8)
     glow::SRGB8_ALPHA8
 }
+
+// The compositor reads these formats with 8 bits per channel, which reintroduces the banding of
+// gradients
+pub fn is_8_bit_swapchain_format(format: u32) -> bool {
+    matches!(
+        format,
+        glow::SRGB8_ALPHA8 | glow::SRGB8 | glow::RGBA8 | glow::BGRA | glow::RGB8 | glow::BGR
+    )
+}
+
+pub fn supports_10_bit_swapchain(formats: Option<&[u32]>) -> bool {
+    formats
+        .map(|formats| formats.contains(&glow::RGB10_A2))
+        .unwrap_or(false)
+}
@@ -0,0 +1,172 @@
+use alvr_common::{
+    glam::{Quat, Vec2, Vec3},
+    Pose,
+};
+use alvr_session::{ReticleConfig, ReticlePosition, ReticleShape};
+
+pub struct ReticleParams {
+    pub shape: ReticleShape,
+    pub position: ReticlePosition,
+    // Radius and line half width, as tangents of the view angle
+    pub radius: f32,
+    pub half_thickness: f32,
+    // RGBA, not premultiplied
+    pub color: [f32; 4],
+}
+
+impl ReticleParams {
+    pub fn new(config: &ReticleConfig) -> Self {
+        Self {
+            shape: config.shape,
+            position: config.position,
+            radius: (config.size_deg.to_radians() / 2.0).tan(),
+            half_thickness: (config.thickness_deg.to_radians() / 2.0).tan(),
+            color: [
+                config.red,
+                config.green,
+                config.blue,
+                config.opacity.clamp(0.0, 1.0),
+            ],
+        }
+    }
+
+    // Center of the reticle in the view of each eye, as tangents of the view angles (X right, Y
+    // up), the same space used by the eye FOV. None if the reticle is behind the eye.
+    // A world position is seen from each eye position, which gives the parallax. A screen position
+    // is a direction relative to the head (the average of the eye orientations), infinitely far.
+    // Note: the centers are consumed by the reticle shader.
+    pub fn view_tangents(&self, eye_poses: [Pose; 2]) -> [Option<Vec2>; 2] {
+        let head_orientation = eye_poses[0]
+            .orientation
+            .slerp(eye_poses[1].orientation, 0.5);
+
+        eye_poses.map(|pose| {
+            let direction = match self.position {
+                ReticlePosition::Screen {
+                    horizontal_deg,
+                    vertical_deg,
+                } => {
+                    head_orientation
+                        * Quat::from_rotation_y(-horizontal_deg.to_radians())
+                        * Quat::from_rotation_x(vertical_deg.to_radians())
+                        * Vec3::NEG_Z
+                }
+                ReticlePosition::World { x, y, z } => Vec3::new(x, y, z) - pose.position,
+            };
+            let local = pose.orientation.inverse() * direction;
+
+            (local.z < 0.0).then(|| Vec2::new(local.x, local.y) / -local.z)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alvr_common::{glam::UVec2, Fov};
+
+    const VIEW_RESOLUTION: UVec2 = UVec2::new(1000, 800);
+
+    fn config(position: ReticlePosition) -> ReticleConfig {
+        ReticleConfig {
+            shape: ReticleShape::Crosshair,
+            position,
+            size_deg: 2.0,
+            thickness_deg: 0.1,
+            red: 0.0,
+            green: 1.0,
+            blue: 0.0,
+            opacity: 0.8,
+        }
+    }
+
+    fn eye_pose(x: f32) -> Pose {
+        Pose {
+            orientation: Quat::IDENTITY,
+            position: Vec3::new(x, 1.6, 0.0),
+        }
+    }
+
+    // Asymmetric FOVs, with more field on the outer side of each eye
+    fn eye_fovs() -> [Fov; 2] {
+        let fov = |left: f32, right: f32| Fov {
+            left: -left.atan(),
+            right: right.atan(),
+            up: 1.0_f32.atan(),
+            down: -1.0_f32.atan(),
+        };
+
+        [fov(1.25, 0.75), fov(0.75, 1.25)]
+    }
+
+    // Pixel of the eye image (origin at the top left) where the shader draws the center
+    fn pixel(tangent: Vec2, fov: Fov) -> UVec2 {
+        let uv = Vec2::new(
+            (tangent.x - fov.left.tan()) / (fov.right.tan() - fov.left.tan()),
+            (fov.up.tan() - tangent.y) / (fov.up.tan() - fov.down.tan()),
+        );
+
+        (uv * VIEW_RESOLUTION.as_vec2()).round().as_uvec2()
+    }
+
+    #[test]
+    fn test_world_reticle_per_eye_pixel() {
+        let reticle = ReticleParams::new(&config(ReticlePosition::World {
+            x: 0.0,
+            y: 1.7,
+            z: -1.0,
+        }));
+        let poses = [eye_pose(-0.032), eye_pose(0.032)];
+        let fovs = eye_fovs();
+
+        let [left, right] = reticle.view_tangents(poses);
+        let left = left.unwrap();
+        let right = right.unwrap();
+
+        // Seen to the right by the left eye and to the left by the right eye
+        assert!(left.abs_diff_eq(Vec2::new(0.032, 0.1), 1e-5));
+        assert!(right.abs_diff_eq(Vec2::new(-0.032, 0.1), 1e-5));
+
+        // Tangent ranges are 2 wide and 2 tall
+        assert_eq!(pixel(left, fovs[0]), UVec2::new(641, 360));
+        assert_eq!(pixel(right, fovs[1]), UVec2::new(359, 360));
+
+        // Behind the user
+        let behind = ReticleParams::new(&config(ReticlePosition::World {
+            x: 0.0,
+            y: 1.6,
+            z: 1.0,
+        }));
+        assert_eq!(behind.view_tangents(poses), [None, None]);
+    }
+
+    #[test]
+    fn test_screen_reticle_has_no_parallax() {
+        let reticle = ReticleParams::new(&config(ReticlePosition::Screen {
+            horizontal_deg: 0.0,
+            vertical_deg: 0.0,
+        }));
+        let fovs = eye_fovs();
+
+        // The same direction in both eyes, also when the head is turned and moved
+        let turn = Quat::from_rotation_y(0.5);
+        let poses = [-0.032, 0.032].map(|x| Pose {
+            orientation: turn,
+            position: turn * eye_pose(x).position + Vec3::X,
+        });
+        let [left, right] = reticle.view_tangents(poses);
+        assert!(left.unwrap().abs_diff_eq(Vec2::ZERO, 1e-5));
+        assert!(right.unwrap().abs_diff_eq(Vec2::ZERO, 1e-5));
+
+        // Following the FOV asymmetry of each eye
+        assert_eq!(pixel(left.unwrap(), fovs[0]), UVec2::new(625, 400));
+        assert_eq!(pixel(right.unwrap(), fovs[1]), UVec2::new(375, 400));
+
+        let offset = ReticleParams::new(&config(ReticlePosition::Screen {
+            horizontal_deg: 45.0,
+            vertical_deg: 0.0,
+        }));
+        let [left, _] = offset.view_tangents([eye_pose(-0.032), eye_pose(0.032)]);
+        assert!(left.unwrap().abs_diff_eq(Vec2::new(1.0, 0.0), 1e-5));
+    }
+}
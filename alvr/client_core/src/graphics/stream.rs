@@ -1,26 +1,116 @@
-use super::GraphicsContext;
-use alvr_common::glam::UVec2;
-use alvr_session::FoveatedEncodingConfig;
-use std::rc::Rc;
+use super::{
+    lens_centers, ColorLut, ComfortVignette, DitherPattern, GraphicsContext, LatencyStamp,
+    MotionSmoothingScheduler, RenderViewInput, ReticleParams, TestPatternSource, VirtualScreen,
+    CENTERED_LENSES,
+};
+use alvr_common::{glam::UVec2, Pose, RngSource};
+use alvr_session::{
+    ColorLutConfig, ComfortVignetteConfig, DitheringConfig, FoveatedEncodingConfig,
+    MonoVirtualScreenConfig, TestPattern, VignetteCorrectionConfig,
+};
+use std::{rc::Rc, time::Instant};
+
+// Optional passes of the stream renderer, each one is skipped when None
+#[derive(Clone, Default)]
+pub struct StreamPassesConfig {
+    pub test_pattern: Option<TestPattern>,
+    pub vignette_correction: Option<VignetteCorrectionConfig>,
+    pub color_lut: Option<ColorLutConfig>,
+    pub comfort_vignette: Option<ComfortVignetteConfig>,
+    pub dithering: Option<DitheringConfig>,
+    pub mono_virtual_screen: Option<MonoVirtualScreenConfig>,
+}
 
 pub struct StreamRenderer {
     _context: Rc<GraphicsContext>,
+    virtual_screen: Option<VirtualScreen>,
+    test_pattern_source: Option<TestPatternSource>,
+    motion_smoothing_scheduler: Option<MotionSmoothingScheduler>,
+    comfort_vignette: Option<ComfortVignette>,
+    start_time: Instant,
+    #[cfg(target_os = "android")]
+    config: super::opengl::FfiStreamConfig,
+    // Referenced by config
+    #[cfg(target_os = "android")]
+    _swapchain_textures: [Vec<u32>; 2],
+    #[cfg(target_os = "android")]
+    _color_lut: Option<ColorLut>,
+    #[cfg(target_os = "android")]
+    _dither_pattern: Option<DitherPattern>,
+}
+
+#[cfg(target_os = "android")]
+fn set_ffi_foveated_encoding(
+    config: &mut super::opengl::FfiStreamConfig,
+    foveated_encoding: Option<&FoveatedEncodingConfig>,
+) {
+    config.enableFoveation = foveated_encoding.is_some().into();
+    config.foveationCenterSizeX = foveated_encoding
+        .map(|f| f.center_size_x)
+        .unwrap_or_default();
+    config.foveationCenterSizeY = foveated_encoding
+        .map(|f| f.center_size_y)
+        .unwrap_or_default();
+    config.foveationCenterShiftX = foveated_encoding
+        .map(|f| f.center_shift_x)
+        .unwrap_or_default();
+    config.foveationCenterShiftY = foveated_encoding
+        .map(|f| f.center_shift_y)
+        .unwrap_or_default();
+    config.foveationEdgeRatioX = foveated_encoding
+        .map(|f| f.edge_ratio_x)
+        .unwrap_or_default();
+    config.foveationEdgeRatioY = foveated_encoding
+        .map(|f| f.edge_ratio_y)
+        .unwrap_or_default();
 }
 
 impl StreamRenderer {
     #[allow(unused_variables)]
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         context: Rc<GraphicsContext>,
         view_resolution: UVec2,
+        swapchain_resolution: UVec2,
         swapchain_textures: [Vec<u32>; 2],
         foveated_encoding: Option<FoveatedEncodingConfig>,
         enable_srgb_correction: bool,
         fix_limited_range: bool,
         encoding_gamma: f32,
+        enable_motion_smoothing: bool,
+        passes: StreamPassesConfig,
+        rng_source: RngSource,
     ) -> Self {
+        let StreamPassesConfig {
+            test_pattern,
+            vignette_correction,
+            color_lut,
+            comfort_vignette,
+            dithering,
+            mono_virtual_screen,
+        } = passes;
+
+        // The correction stays centered on the images if the offsets are invalid
+        let lens_centers = vignette_correction
+            .as_ref()
+            .and_then(|c| alvr_common::show_err(lens_centers(c.lens_center_offset.as_option())))
+            .unwrap_or(CENTERED_LENSES);
+
+        // The stream is rendered without color grading if the LUT cannot be loaded
+        let color_lut_intensity = color_lut.as_ref().map(|c| c.intensity).unwrap_or_default();
+        let color_lut = color_lut.and_then(|c| alvr_common::show_err(ColorLut::load(c.file_path)));
+
+        let dither_strength = dithering.as_ref().map(|c| c.strength).unwrap_or_default();
+        let dither_pattern =
+            dithering.map(|c| DitherPattern::new(c.mode, &mut rng_source.stream("dithering")));
+
+        let virtual_screen = mono_virtual_screen.map(|config| {
+            VirtualScreen::new(&config, view_resolution.x as f32 / view_resolution.y as f32)
+        });
+
         #[cfg(target_os = "android")]
         unsafe {
-            let config = super::opengl::FfiStreamConfig {
+            let mut config = super::opengl::FfiStreamConfig {
                 viewWidth: view_resolution.x,
                 viewHeight: view_resolution.y,
                 swapchainTextures: [
@@ -28,47 +118,238 @@ impl StreamRenderer {
                     swapchain_textures[1].as_ptr(),
                 ],
                 swapchainLength: swapchain_textures[0].len() as _,
-                enableFoveation: foveated_encoding.is_some().into(),
-                foveationCenterSizeX: foveated_encoding
+                swapchainWidth: swapchain_resolution.x,
+                swapchainHeight: swapchain_resolution.y,
+                enableSrgbCorrection: enable_srgb_correction as u32,
+                fixLimitedRange: fix_limited_range as u32,
+                encodingGamma: encoding_gamma,
+                enableMotionSmoothing: enable_motion_smoothing.into(),
+                enableTestPattern: test_pattern.is_some().into(),
+                testPattern: test_pattern.map(|p| p as u32).unwrap_or_default(),
+                enableVignetteCorrection: vignette_correction.is_some().into(),
+                vignetteCorrectionLeftStrength: vignette_correction
+                    .as_ref()
+                    .map(|c| c.left_strength)
+                    .unwrap_or_default(),
+                vignetteCorrectionRightStrength: vignette_correction
                     .as_ref()
-                    .map(|f| f.center_size_x)
+                    .map(|c| c.right_strength)
                     .unwrap_or_default(),
-                foveationCenterSizeY: foveated_encoding
+                vignetteCorrectionFalloffExponent: vignette_correction
                     .as_ref()
-                    .map(|f| f.center_size_y)
+                    .map(|c| c.falloff_exponent)
                     .unwrap_or_default(),
-                foveationCenterShiftX: foveated_encoding
+                vignetteCorrectionLeftLensCenter: lens_centers[0].to_array(),
+                vignetteCorrectionRightLensCenter: lens_centers[1].to_array(),
+                enableColorLut: color_lut.is_some().into(),
+                colorLutSize: color_lut.as_ref().map(|l| l.size as _).unwrap_or_default(),
+                colorLutData: color_lut
+                    .as_ref()
+                    .map(|l| l.data.as_ptr())
+                    .unwrap_or(std::ptr::null()),
+                colorLutDomainMin: color_lut
                     .as_ref()
-                    .map(|f| f.center_shift_x)
+                    .map(|l| l.domain_min.to_array())
                     .unwrap_or_default(),
-                foveationCenterShiftY: foveated_encoding
+                colorLutDomainMax: color_lut
                     .as_ref()
-                    .map(|f| f.center_shift_y)
+                    .map(|l| l.domain_max.to_array())
                     .unwrap_or_default(),
-                foveationEdgeRatioX: foveated_encoding
+                colorLutIntensity: color_lut_intensity,
+                enableComfortVignette: comfort_vignette.is_some().into(),
+                enableDithering: dither_pattern.is_some().into(),
+                ditherNoiseSize: dither_pattern
                     .as_ref()
-                    .map(|f| f.edge_ratio_x)
+                    .map(|p| p.size as _)
                     .unwrap_or_default(),
-                foveationEdgeRatioY: foveated_encoding
+                ditherNoiseData: dither_pattern
                     .as_ref()
-                    .map(|f| f.edge_ratio_y)
+                    .map(|p| p.data.as_ptr())
+                    .unwrap_or(std::ptr::null()),
+                ditherStrength: dither_strength,
+                enableVirtualScreen: virtual_screen.is_some().into(),
+                virtualScreenDistance: virtual_screen.map(|s| s.distance).unwrap_or_default(),
+                virtualScreenWidth: virtual_screen.map(|s| s.width).unwrap_or_default(),
+                virtualScreenHeight: virtual_screen.map(|s| s.height).unwrap_or_default(),
+                virtualScreenCurvature: virtual_screen
+                    .filter(|s| s.is_curved())
+                    .map(|s| s.curvature)
                     .unwrap_or_default(),
-                enableSrgbCorrection: enable_srgb_correction as u32,
-                fixLimitedRange: fix_limited_range as u32,
-                encodingGamma: encoding_gamma,
+                ..Default::default()
             };
+            set_ffi_foveated_encoding(&mut config, foveated_encoding.as_ref());
 
             super::opengl::streamStartNative(config);
         }
 
-        Self { _context: context }
+        Self {
+            _context: context,
+            #[cfg(target_os = "android")]
+            config,
+            #[cfg(target_os = "android")]
+            _swapchain_textures: swapchain_textures,
+            #[cfg(target_os = "android")]
+            _color_lut: color_lut,
+            #[cfg(target_os = "android")]
+            _dither_pattern: dither_pattern,
+            virtual_screen,
+            test_pattern_source: test_pattern.map(|_| TestPatternSource::new()),
+            motion_smoothing_scheduler: enable_motion_smoothing.then(MotionSmoothingScheduler::new),
+            comfort_vignette: comfort_vignette.map(ComfortVignette::new),
+            start_time: Instant::now(),
+        }
     }
 
+    // The stream textures depend on the foveated encoding, so the native renderer is restarted
     #[allow(unused_variables)]
-    pub fn render(&self, hardware_buffer: *mut std::ffi::c_void, swapchain_indices: [u32; 2]) {
+    pub fn set_foveated_encoding(&mut self, foveated_encoding: Option<FoveatedEncodingConfig>) {
         #[cfg(target_os = "android")]
         unsafe {
-            super::opengl::renderStreamNative(hardware_buffer, swapchain_indices.as_ptr());
+            set_ffi_foveated_encoding(&mut self.config, foveated_encoding.as_ref());
+
+            super::opengl::destroyStream();
+            super::opengl::streamStartNative(self.config);
+        }
+
+        // The previous frame is lost
+        if let Some(scheduler) = &mut self.motion_smoothing_scheduler {
+            *scheduler = MotionSmoothingScheduler::new();
+        }
+    }
+
+    #[allow(unused_variables)]
+    pub fn render(
+        &mut self,
+        hardware_buffer: *mut std::ffi::c_void,
+        view_inputs: [RenderViewInput; 2],
+    ) {
+        let poses = if self.virtual_screen.is_some() {
+            // The virtual screen is head locked
+            super::eye_poses_in_head_space([view_inputs[0].pose, view_inputs[1].pose])
+        } else {
+            [view_inputs[0].pose, view_inputs[1].pose]
+        };
+
+        let test_pattern_frame_index = self
+            .test_pattern_source
+            .as_mut()
+            .map(|source| source.next_frame())
+            .unwrap_or_default();
+
+        let interpolation_factor = self
+            .motion_smoothing_scheduler
+            .as_mut()
+            .map(|scheduler| scheduler.interpolation_factor(!hardware_buffer.is_null()))
+            .unwrap_or(1.0);
+
+        let comfort_vignette_radius = self
+            .comfort_vignette
+            .as_mut()
+            .map(|vignette| {
+                let time = self.start_time.elapsed();
+
+                // Repeated frames carry the same pose and would be measured as no motion
+                if !hardware_buffer.is_null() {
+                    let head_pose = Pose {
+                        orientation: view_inputs[0]
+                            .pose
+                            .orientation
+                            .slerp(view_inputs[1].pose.orientation, 0.5),
+                        position: (view_inputs[0].pose.position + view_inputs[1].pose.position)
+                            / 2.0,
+                    };
+                    vignette.report_head_pose(head_pose, time);
+                }
+
+                vignette.radius(time)
+            })
+            .unwrap_or(1.0);
+
+        #[cfg(target_os = "android")]
+        unsafe {
+            let eye_inputs = [0, 1].map(|eye| super::opengl::FfiViewInput {
+                position: poses[eye].position.to_array(),
+                orientation: poses[eye].orientation.to_array(),
+                fovLeft: view_inputs[eye].fov.left,
+                fovRight: view_inputs[eye].fov.right,
+                fovUp: view_inputs[eye].fov.up,
+                fovDown: view_inputs[eye].fov.down,
+                swapchainIndex: view_inputs[eye].swapchain_index as _,
+            });
+
+            super::opengl::renderStreamNative(
+                hardware_buffer,
+                eye_inputs.as_ptr(),
+                test_pattern_frame_index,
+                interpolation_factor,
+                comfort_vignette_radius,
+            );
+        }
+    }
+
+    // Drawn into the swapchain images of both eyes, after render(). The poses should be the ones
+    // the frame is displayed with, so the reticle stays in place after reprojection.
+    #[allow(unused_variables)]
+    pub fn draw_reticle(&self, view_inputs: [RenderViewInput; 2], reticle: &ReticleParams) {
+        let centers = reticle.view_tangents([view_inputs[0].pose, view_inputs[1].pose]);
+
+        #[cfg(target_os = "android")]
+        unsafe {
+            let eye_inputs = [0, 1].map(|eye| super::opengl::FfiViewInput {
+                position: view_inputs[eye].pose.position.to_array(),
+                orientation: view_inputs[eye].pose.orientation.to_array(),
+                fovLeft: view_inputs[eye].fov.left,
+                fovRight: view_inputs[eye].fov.right,
+                fovUp: view_inputs[eye].fov.up,
+                fovDown: view_inputs[eye].fov.down,
+                swapchainIndex: view_inputs[eye].swapchain_index as _,
+            });
+
+            super::opengl::renderReticleNative(
+                eye_inputs.as_ptr(),
+                super::opengl::FfiReticle {
+                    shape: reticle.shape as _,
+                    centers: centers.map(|c| c.unwrap_or_default().to_array()),
+                    visible: centers.map(|c| c.is_some().into()),
+                    radius: reticle.radius,
+                    halfThickness: reticle.half_thickness,
+                    color: reticle.color,
+                },
+            );
+        }
+    }
+
+    // Drawn into the swapchain images of both eyes, after render() and the reticle. The value is
+    // given by stamp_value()
+    #[allow(unused_variables)]
+    pub fn draw_latency_stamp(
+        &self,
+        view_inputs: [RenderViewInput; 2],
+        stamp: &LatencyStamp,
+        image_height: u32,
+        value: u64,
+    ) {
+        #[cfg(target_os = "android")]
+        unsafe {
+            let eye_inputs = [0, 1].map(|eye| super::opengl::FfiViewInput {
+                position: view_inputs[eye].pose.position.to_array(),
+                orientation: view_inputs[eye].pose.orientation.to_array(),
+                fovLeft: view_inputs[eye].fov.left,
+                fovRight: view_inputs[eye].fov.right,
+                fovUp: view_inputs[eye].fov.up,
+                fovDown: view_inputs[eye].fov.down,
+                swapchainIndex: view_inputs[eye].swapchain_index as _,
+            });
+
+            super::opengl::renderLatencyStampNative(
+                eye_inputs.as_ptr(),
+                super::opengl::FfiLatencyStamp {
+                    origin: stamp.origin.to_array(),
+                    cellSize: stamp.cell_size,
+                    imageHeight: image_height,
+                    value: [value as u32, (value >> 32) as u32],
+                },
+            );
         }
     }
 }
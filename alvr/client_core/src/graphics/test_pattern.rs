@@ -0,0 +1,137 @@
+// Frame index of the test pattern rendered by the shader in place of the decoded stream. It
+// advances once per rendered frame.
+pub struct TestPatternSource {
+    frame_index: u32,
+}
+
+impl TestPatternSource {
+    pub fn new() -> Self {
+        Self { frame_index: 0 }
+    }
+
+    // Returns the index of the frame to render
+    pub fn next_frame(&mut self) -> u32 {
+        let index = self.frame_index;
+        self.frame_index = self.frame_index.wrapping_add(1);
+
+        index
+    }
+}
+
+impl Default for TestPatternSource {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graphics::gl_test::{self, TestGl, UniformBlock};
+    use alvr_session::TestPattern;
+
+    const TEST_PATTERN_PASS_CPP: &str = include_str!("../../cpp/test_pattern_pass.cpp");
+
+    // Per eye, a multiple of the cells of the patterns
+    const EYE_WIDTH: usize = 240;
+
+    // One row of the side by side output of the shader, as white or black per channel
+    fn render_row(gl: &TestGl, pattern: TestPattern, frame_index: u32) -> Vec<[bool; 3]> {
+        // Same define as TestPatternPass
+        let shader = [
+            gl_test::glsl(TEST_PATTERN_PASS_CPP, "TEST_PATTERN_FRAGMENT_SHADER_HEADER"),
+            format!("#define PATTERN {}", pattern as u32),
+            gl_test::glsl(TEST_PATTERN_PASS_CPP, "TEST_PATTERN_FRAGMENT_SHADER"),
+        ]
+        .join("\n");
+
+        let output = gl.output((EYE_WIDTH * 2) as _, 1, glow::RGBA8);
+        gl.render(
+            &gl_test::quad_vertex_shader(),
+            &shader,
+            &[],
+            UniformBlock::default().uint(frame_index),
+            &output,
+        );
+
+        gl.read_rgba8(&output)
+            .into_iter()
+            .map(|pixel| [0, 1, 2].map(|c| pixel[c] > 127))
+            .collect()
+    }
+
+    // Reads back the counter from the center of each of the 16 bit cells, in both eyes
+    fn read_frame_counter(row: &[[bool; 3]]) -> u32 {
+        let cell_width = EYE_WIDTH / 16;
+        let eye_values = [0, EYE_WIDTH].map(|eye_start| {
+            (0..16).fold(0, |value, cell| {
+                let pixel = row[eye_start + cell * cell_width + cell_width / 2];
+                (value << 1) | pixel[0] as u32
+            })
+        });
+        assert_eq!(eye_values[0], eye_values[1]);
+
+        eye_values[0]
+    }
+
+    #[test]
+    #[ignore = "needs a GLES 3 driver"]
+    fn test_frame_counter_increments() {
+        let gl = TestGl::new();
+
+        let mut source = TestPatternSource::new();
+
+        let mut last_value = None;
+        for _ in 0..300 {
+            let value = read_frame_counter(&render_row(
+                &gl,
+                TestPattern::FrameCounter,
+                source.next_frame(),
+            ));
+
+            if let Some(last_value) = last_value {
+                assert_eq!(value, last_value + 1);
+            }
+            last_value = Some(value);
+        }
+        assert_eq!(last_value, Some(299));
+    }
+
+    #[test]
+    #[ignore = "needs a GLES 3 driver"]
+    fn test_color_bars_and_scroll_bar() {
+        let gl = TestGl::new();
+
+        // White, yellow, cyan, green, magenta, red, blue, black, in both eyes
+        let row = render_row(&gl, TestPattern::ColorBars, 0);
+        let expected = [
+            [true, true, true],
+            [true, true, false],
+            [false, true, true],
+            [false, true, false],
+            [true, false, true],
+            [true, false, false],
+            [false, false, true],
+            [false, false, false],
+        ];
+        let bar_width = EYE_WIDTH / expected.len();
+        for (bar, color) in expected.into_iter().enumerate() {
+            for eye_start in [0, EYE_WIDTH] {
+                assert_eq!(row[eye_start + bar * bar_width + bar_width / 2], color);
+            }
+        }
+
+        // The bar is 1/16 of the eye wide, it moves right by 1/120 each frame
+        let bar_at = |frame_index| {
+            let row = render_row(&gl, TestPattern::ScrollBar, frame_index);
+            let start = row.iter().position(|pixel| pixel[0]).unwrap();
+            let end = start + row[start..].iter().take_while(|pixel| pixel[0]).count();
+
+            (start, end)
+        };
+        assert_eq!(bar_at(10), (20, 35));
+        assert_eq!(bar_at(11), (22, 37));
+        // Back to the start after a period
+        assert_eq!(bar_at(130), (20, 35));
+    }
+}
@@ -0,0 +1,177 @@
+// The vignette correction is configured by the stream renderer, these are the tests of its shader
+#[cfg(test)]
+mod tests {
+    use crate::graphics::{
+        gl_test::{self, TestGl, UniformBlock},
+        lens_centers,
+    };
+    use alvr_common::glam::{UVec2, Vec2};
+    use alvr_session::{settings_schema::Switch, LensCenterOffsetConfig, VignetteCorrectionConfig};
+
+    const VIGNETTE_CORRECTION_PASS_CPP: &str =
+        include_str!("../../cpp/vignette_correction_pass.cpp");
+
+    // Corrects a side by side image of a single gray level, like the stream texture. Returns the
+    // RGBA pixels, bottom row first
+    fn render_corrected(
+        gl: &TestGl,
+        config: &VignetteCorrectionConfig,
+        eye_size: usize,
+        gray: f32,
+    ) -> Vec<[f32; 4]> {
+        let lens_centers = lens_centers(config.lens_center_offset.as_option()).unwrap();
+
+        // Same defines as VignetteCorrectionPass
+        let shader = [
+            gl_test::glsl(
+                VIGNETTE_CORRECTION_PASS_CPP,
+                "VIGNETTE_CORRECTION_FRAGMENT_SHADER_HEADER",
+            ),
+            format!("#define LEFT_STRENGTH ({:?})", config.left_strength),
+            format!("#define RIGHT_STRENGTH ({:?})", config.right_strength),
+            format!("#define FALLOFF_EXPONENT ({:?})", config.falloff_exponent),
+            format!(
+                "#define LEFT_LENS_CENTER vec2({:?}, {:?})",
+                lens_centers[0].x, lens_centers[0].y
+            ),
+            format!(
+                "#define RIGHT_LENS_CENTER vec2({:?}, {:?})",
+                lens_centers[1].x, lens_centers[1].y
+            ),
+            gl_test::glsl(
+                VIGNETTE_CORRECTION_PASS_CPP,
+                "VIGNETTE_CORRECTION_FRAGMENT_SHADER",
+            ),
+        ]
+        .join("\n");
+
+        let input = gl.texture_rgba16f(
+            (eye_size * 2) as _,
+            eye_size as _,
+            &vec![[gray, gray, gray, 1.0]; eye_size * 2 * eye_size],
+        );
+        let output = gl.output((eye_size * 2) as _, eye_size as _, glow::RGBA16F);
+        gl.render(
+            &gl_test::quad_vertex_shader(),
+            &shader,
+            &[input],
+            UniformBlock::default(),
+            &output,
+        );
+
+        gl.read_rgba_f32(&output)
+    }
+
+    #[test]
+    #[ignore = "needs a GLES 3 driver"]
+    fn test_vignette_correction_golden_image() {
+        const EYE_SIZE: usize = 3;
+
+        let gl = TestGl::new();
+
+        let config = VignetteCorrectionConfig {
+            left_strength: 0.5,
+            right_strength: 0.0,
+            falloff_exponent: 2.0,
+            lens_center_offset: Switch::Disabled,
+        };
+        let image = render_corrected(&gl, &config, EYE_SIZE, 0.5);
+
+        #[rustfmt::skip]
+        let golden = [
+            0.6111, 0.5556, 0.6111, 0.5, 0.5, 0.5,
+            0.5556, 0.5,    0.5556, 0.5, 0.5, 0.5,
+            0.6111, 0.5556, 0.6111, 0.5, 0.5, 0.5,
+        ];
+
+        for (pixel, expected) in image.iter().zip(golden) {
+            for channel in &pixel[..3] {
+                assert!((channel - expected).abs() < 2e-3, "{channel} != {expected}");
+            }
+        }
+    }
+
+    #[test]
+    #[ignore = "needs a GLES 3 driver"]
+    fn test_vignette_correction_does_not_overbrighten() {
+        const EYE_SIZE: usize = 3;
+
+        let gl = TestGl::new();
+
+        let config = VignetteCorrectionConfig {
+            left_strength: 1.0,
+            right_strength: 0.2,
+            falloff_exponent: 1.0,
+            lens_center_offset: Switch::Disabled,
+        };
+
+        // The gain is the output over the input
+        let image = render_corrected(&gl, &config, EYE_SIZE, 0.5);
+        let gain =
+            |eye: usize, x: usize, y: usize| image[y * EYE_SIZE * 2 + eye * EYE_SIZE + x][0] / 0.5;
+
+        for eye in 0..2 {
+            assert!((gain(eye, 1, 1) - 1.0).abs() < 1e-3);
+        }
+
+        // Edges are brighter than the center, the corners the brightest. The center of the corner
+        // pixel is at 2/3 of the distance to the corner of the image
+        let (center, edge, corner) = (gain(1, 1, 1), gain(1, 2, 1), gain(1, 2, 2));
+        assert!(center < edge && edge < corner);
+        assert!((corner - (1.0 + 0.2 * 2.0 / 3.0)).abs() < 2e-3);
+
+        let image = render_corrected(&gl, &config, EYE_SIZE, 1.0);
+        assert!(image.iter().all(|pixel| pixel[..3] == [1.0; 3]));
+    }
+
+    #[test]
+    #[ignore = "needs a GLES 3 driver"]
+    fn test_offset_lens_center_shifts_the_correction_origin() {
+        const EYE_SIZE: usize = 40;
+
+        let gl = TestGl::new();
+
+        // Mirrored lenses, closer to the nose than the image centers. They are at pixel centers
+        let offset = LensCenterOffsetConfig {
+            left_x: 0.1125,
+            left_y: -0.0625,
+            right_x: -0.1125,
+            right_y: -0.0625,
+        };
+        let lens_centers = lens_centers(Some(&offset)).unwrap();
+        let config = VignetteCorrectionConfig {
+            left_strength: 0.5,
+            right_strength: 0.5,
+            // Linear, the minimum is sharp enough for the precision of the shader
+            falloff_exponent: 1.0,
+            lens_center_offset: Switch::Enabled(offset),
+        };
+
+        // The gain is the output over the input
+        let pixels = render_corrected(&gl, &config, EYE_SIZE, 0.5);
+
+        for eye in 0..2 {
+            let gain = |x: usize, y: usize| pixels[y * EYE_SIZE * 2 + eye * EYE_SIZE + x][0] / 0.5;
+            let pixel_of = |uv: Vec2| (uv * EYE_SIZE as f32 - 0.5).round().as_uvec2();
+
+            // The least corrected pixel is the one of the lens center, not the image center
+            let (origin, min_gain) = (0..EYE_SIZE)
+                .flat_map(|y| (0..EYE_SIZE).map(move |x| (x, y)))
+                .map(|(x, y)| (UVec2::new(x as _, y as _), gain(x, y)))
+                .min_by(|(_, a), (_, b)| a.total_cmp(b))
+                .unwrap();
+            assert_eq!(origin, pixel_of(lens_centers[eye]));
+            assert!((min_gain - 1.0).abs() < 1e-2);
+            assert!(gain(EYE_SIZE / 2, EYE_SIZE / 2) > min_gain + 0.01);
+
+            // The strongest correction is at the farthest corner, on the temple side
+            let (far_x, near_x) = if eye == 0 {
+                (0, EYE_SIZE - 1)
+            } else {
+                (EYE_SIZE - 1, 0)
+            };
+            assert!((gain(far_x, EYE_SIZE - 1) - 1.5).abs() < 0.05);
+            assert!(gain(near_x, 0) < gain(far_x, EYE_SIZE - 1) - 0.1);
+        }
+    }
+}
@@ -0,0 +1,244 @@
+use alvr_common::Pose;
+use alvr_session::MonoVirtualScreenConfig;
+
+// Below this value the screen is treated as flat
+const MIN_CURVATURE: f32 = 0.001;
+
+// Head locked screen placed along -Z in front of the head (the midpoint between the eyes)
+#[derive(Clone, Copy)]
+pub struct VirtualScreen {
+    pub distance: f32,
+    pub width: f32,
+    pub height: f32,
+    pub curvature: f32,
+}
+
+impl VirtualScreen {
+    pub fn new(config: &MonoVirtualScreenConfig, source_aspect_ratio: f32) -> Self {
+        Self {
+            distance: config.distance_m,
+            width: config.width_m,
+            height: config.width_m / source_aspect_ratio,
+            curvature: config.curvature,
+        }
+    }
+
+    pub fn is_curved(&self) -> bool {
+        self.curvature > MIN_CURVATURE
+    }
+
+    // Radius of the cylinder, whose axis is vertical and has the screen center at distance
+    pub fn radius(&self) -> f32 {
+        self.distance / self.curvature
+    }
+}
+
+// Convert eye poses into poses relative to the head, which is placed in between the eyes. This
+// keeps the screen in front of the user while preserving the per-eye parallax.
+pub fn eye_poses_in_head_space(eye_poses: [Pose; 2]) -> [Pose; 2] {
+    let head_orientation = eye_poses[0]
+        .orientation
+        .slerp(eye_poses[1].orientation, 0.5);
+    let head_position = (eye_poses[0].position + eye_poses[1].position) / 2.0;
+
+    let inverse_head_orientation = head_orientation.inverse();
+
+    eye_poses.map(|pose| Pose {
+        orientation: inverse_head_orientation * pose.orientation,
+        position: inverse_head_orientation * (pose.position - head_position),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graphics::gl_test::{self, TestGl, UniformBlock};
+    use alvr_common::glam::{Mat4, Quat, Vec2, Vec3};
+
+    const VIRTUAL_SCREEN_PASS_CPP: &str = include_str!("../../cpp/virtual_screen_pass.cpp");
+
+    const IPD: f32 = 0.064;
+    // Per eye, of the source
+    const SOURCE_SIZE: usize = 64;
+
+    fn eye_poses(head_orientation: Quat) -> [Pose; 2] {
+        let head_position = Vec3::new(0.3, 1.6, -0.2);
+
+        [-IPD / 2.0, IPD / 2.0].map(|x| Pose {
+            orientation: head_orientation,
+            position: head_position + head_orientation * Vec3::new(x, 0.0, 0.0),
+        })
+    }
+
+    // Tangents of the direction of a point given in head space, as seen from the eye
+    fn tangent(eye_pose: Pose, point: Vec3) -> Vec2 {
+        let local = eye_pose.orientation.inverse() * (point - eye_pose.position);
+
+        Vec2::new(local.x, local.y) / -local.z
+    }
+
+    // Screen UV hit by the ray of the eye with the tangents, or None if it misses the screen. The
+    // shader renders a single pixel, at the center of a FOV around the ray, from a source that
+    // encodes its own UV coordinates
+    fn shader_screen_uv(
+        gl: &TestGl,
+        screen: &VirtualScreen,
+        eye_pose: Pose,
+        tangent: Vec2,
+    ) -> Option<Vec2> {
+        // Same defines as VirtualScreenPass, with the curvature of the stream renderer
+        let mut defines = vec![
+            format!("#define DISTANCE ({:?})", screen.distance),
+            format!("#define WIDTH ({:?})", screen.width),
+            format!("#define HEIGHT ({:?})", screen.height),
+        ];
+        if screen.is_curved() {
+            defines.push(format!("#define RADIUS ({:?})", screen.radius()));
+        }
+        let shader = [
+            gl_test::glsl(
+                VIRTUAL_SCREEN_PASS_CPP,
+                "VIRTUAL_SCREEN_FRAGMENT_SHADER_HEADER",
+            ),
+            defines.join("\n"),
+            gl_test::glsl(VIRTUAL_SCREEN_PASS_CPP, "VIRTUAL_SCREEN_FRAGMENT_SHADER"),
+        ]
+        .join("\n");
+
+        // The source UV of the left half goes from 0 to 1, the right half continues it so that
+        // the filtering stays linear up to the edge
+        let source = (0..SOURCE_SIZE)
+            .flat_map(|y| {
+                (0..SOURCE_SIZE * 2).map(move |x| {
+                    [
+                        (x as f32 + 0.5) / SOURCE_SIZE as f32,
+                        (y as f32 + 0.5) / SOURCE_SIZE as f32,
+                        1.0,
+                        1.0,
+                    ]
+                })
+            })
+            .collect::<Vec<_>>();
+        let input = gl.texture_rgba32f((SOURCE_SIZE * 2) as _, SOURCE_SIZE as _, &source);
+
+        let output = gl.output(1, 1, glow::RGBA32F);
+        let extent = Vec2::splat(0.01);
+        gl.render(
+            &gl_test::quad_vertex_shader(),
+            &shader,
+            &[input],
+            UniformBlock::default()
+                .mat4(Mat4::from_quat(eye_pose.orientation).to_cols_array_2d())
+                .vec4(eye_pose.position.extend(0.0).to_array())
+                .vec4([
+                    tangent.x - extent.x,
+                    tangent.y - extent.y,
+                    tangent.x + extent.x,
+                    tangent.y + extent.y,
+                ]),
+            &output,
+        );
+
+        // Black outside of the screen
+        let [u, v, inside, _] = gl.read_rgba_f32(&output)[0];
+        (inside > 0.5).then_some(Vec2::new(u, v))
+    }
+
+    // The filtering of the source limits the precision of the UV read back
+    fn assert_uv_eq(uv: Option<Vec2>, expected: Vec2) {
+        let uv = uv.unwrap();
+        assert!((uv - expected).length() < 1e-3, "{uv} != {expected}");
+    }
+
+    #[test]
+    #[ignore = "needs a GLES 3 driver"]
+    fn test_flat_screen_per_eye_parallax() {
+        let gl = TestGl::new();
+
+        let screen = VirtualScreen {
+            distance: 2.0,
+            width: 2.0,
+            height: 1.0,
+            curvature: 0.0,
+        };
+
+        for head_orientation in [Quat::IDENTITY, Quat::from_rotation_y(0.5)] {
+            let [left_pose, right_pose] = eye_poses_in_head_space(eye_poses(head_orientation));
+
+            let center = Vec3::new(0.0, 0.0, -screen.distance);
+            let left_tangent = tangent(left_pose, center);
+            let right_tangent = tangent(right_pose, center);
+
+            // The screen center is shifted towards the nose in each eye
+            let expected_shift = IPD / 2.0 / screen.distance;
+            assert!((left_tangent.x - expected_shift).abs() < 1e-4);
+            assert!((right_tangent.x + expected_shift).abs() < 1e-4);
+
+            assert_uv_eq(
+                shader_screen_uv(&gl, &screen, left_pose, left_tangent),
+                Vec2::splat(0.5),
+            );
+            assert_uv_eq(
+                shader_screen_uv(&gl, &screen, right_pose, right_tangent),
+                Vec2::splat(0.5),
+            );
+
+            // Near the top left corner
+            let point = Vec3::new(-0.4 * screen.width, 0.4 * screen.height, -screen.distance);
+            for pose in [left_pose, right_pose] {
+                assert_uv_eq(
+                    shader_screen_uv(&gl, &screen, pose, tangent(pose, point)),
+                    Vec2::splat(0.1),
+                );
+            }
+
+            // Looking straight ahead from an eye hits the screen off center
+            let left_uv = shader_screen_uv(&gl, &screen, left_pose, Vec2::ZERO).unwrap();
+            assert!((left_uv.x - (0.5 - IPD / 2.0 / screen.width)).abs() < 1e-3);
+        }
+    }
+
+    #[test]
+    #[ignore = "needs a GLES 3 driver"]
+    fn test_curved_screen_per_eye_parallax() {
+        let gl = TestGl::new();
+
+        let screen = VirtualScreen {
+            distance: 2.0,
+            width: 2.0,
+            height: 1.0,
+            curvature: 1.0,
+        };
+
+        let [left_pose, right_pose] = eye_poses_in_head_space(eye_poses(Quat::IDENTITY));
+
+        let center = Vec3::new(0.0, 0.0, -screen.distance);
+        assert_uv_eq(
+            shader_screen_uv(&gl, &screen, left_pose, tangent(left_pose, center)),
+            Vec2::splat(0.5),
+        );
+        assert_uv_eq(
+            shader_screen_uv(&gl, &screen, right_pose, tangent(right_pose, center)),
+            Vec2::splat(0.5),
+        );
+
+        // The screen wraps around the user: with curvature 1 the cylinder is centered on the head,
+        // so the right edge is at an angle of width / (2 * radius)
+        let angle = 0.8 * screen.width / (2.0 * screen.radius());
+        let point = Vec3::new(
+            screen.radius() * angle.sin(),
+            0.0,
+            -screen.radius() * angle.cos(),
+        );
+        for pose in [left_pose, right_pose] {
+            assert_uv_eq(
+                shader_screen_uv(&gl, &screen, pose, tangent(pose, point)),
+                Vec2::new(0.9, 0.5),
+            );
+        }
+
+        // Outside of the screen
+        let outside = Vec3::new(0.0, screen.height, -screen.distance);
+        assert!(shader_screen_uv(&gl, &screen, left_pose, tangent(left_pose, outside)).is_none());
+    }
+}
@@ -8,11 +8,15 @@
 mod c_api;
 mod connection;
 mod decoder;
+mod foveation;
+mod frame_references;
+mod frame_user_data;
 mod logging_backend;
 mod platform;
 mod sockets;
 mod statistics;
 mod storage;
+mod video_slices;
 
 #[cfg(target_os = "android")]
 mod audio;
@@ -29,7 +33,7 @@ use alvr_packets::{
     BatteryInfo, ButtonEntry, ClientControlPacket, FaceData, NegotiatedStreamingConfig,
     ReservedClientControlPacket, Tracking, ViewParams, ViewsConfig,
 };
-use alvr_session::{CodecType, Settings};
+use alvr_session::{CodecType, FoveatedEncodingConfig, Settings};
 use connection::ConnectionContext;
 use serde::{Deserialize, Serialize};
 use std::{
@@ -75,13 +79,22 @@ pub enum ClientCoreEvent {
         timestamp: Duration,
         view_params: [ViewParams; 2],
         nal: Vec<u8>,
+        user_data: Vec<u8>,
     },
+    // Clamped calibration transforms relative to each eye, updated at runtime
+    EyeCalibration([Pose; 2]),
+    // No frames are received while paused. The stream resumes with an IDR
+    StreamPaused(bool),
 }
 
 pub struct DecodedFrame {
     pub timestamp: Duration,
     pub view_params: [ViewParams; 2],
     pub buffer_ptr: *mut std::ffi::c_void,
+    // The frame has been encoded with this foveated encoding, which can change during the stream
+    pub foveated_encoding: Option<FoveatedEncodingConfig>,
+    // Attached to the frame by the server, empty if not set
+    pub user_data: Vec<u8>,
 }
 
 // Note: this struct may change without breaking network protocol changes
@@ -94,6 +107,10 @@ pub struct ClientCapabilities {
     pub encoder_high_profile: bool,
     pub encoder_10_bits: bool,
     pub encoder_av1: bool,
+    // The renderer can be restarted when the foveated encoding changes
+    pub runtime_foveation: bool,
+    // The stream swapchain preserves the precision of 10 bit frames
+    pub display_10_bits: bool,
 }
 
 pub struct ClientCoreContext {
@@ -181,6 +198,24 @@ impl ClientCoreContext {
         }
     }
 
+    // The pause is effective once the server notifies it with a StreamPaused event
+    pub fn set_stream_paused(&self, paused: bool) {
+        if let Some(sender) = &mut *self.connection_context.control_sender.lock() {
+            sender
+                .send(&ClientControlPacket::RequestStreamPause(paused))
+                .ok();
+        }
+    }
+
+    // Used to pause the stream while the headset is removed, if enabled in the settings
+    pub fn send_headset_presence(&self, present: bool) {
+        if let Some(sender) = &mut *self.connection_context.control_sender.lock() {
+            sender
+                .send(&ClientControlPacket::HeadsetPresence(present))
+                .ok();
+        }
+    }
+
     pub fn send_active_interaction_profile(&self, device_id: u64, profile_id: u64) {
         if let Some(sender) = &mut *self.connection_context.control_sender.lock() {
             sender
@@ -314,6 +349,8 @@ impl ClientCoreContext {
 
         if let Some(stats) = &mut *self.connection_context.statistics_manager.lock() {
             stats.report_compositor_start(frame_timestamp);
+            stats.report_decoder_parallelism(frame_timestamp, decoder_source.parallelism());
+            stats.report_decoder_dropped_frames(frame_timestamp, decoder_source.dropped_frames());
         }
 
         let mut view_params = *self.connection_context.last_good_view_params.read();
@@ -324,10 +361,25 @@ impl ClientCoreContext {
             }
         }
 
+        let foveated_encoding = self
+            .connection_context
+            .foveation_sync
+            .lock()
+            .as_ref()
+            .and_then(|sync| sync.frame_config(frame_timestamp));
+
+        let user_data = self
+            .connection_context
+            .frame_user_data
+            .lock()
+            .frame_user_data(frame_timestamp);
+
         Some(DecodedFrame {
             timestamp: frame_timestamp,
             view_params,
             buffer_ptr,
+            foveated_encoding,
+            user_data,
         })
     }
 
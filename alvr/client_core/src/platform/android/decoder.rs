@@ -25,7 +25,7 @@ use std::{
     time::Duration,
 };
 
-use crate::decoder::DecoderConfig;
+use crate::decoder::{DecodedFrameQueue, DecoderConfig};
 
 struct FakeThreadSafe<T>(T);
 unsafe impl<T> Send for FakeThreadSafe<T> {}
@@ -98,11 +98,17 @@ pub struct VideoDecoderSource {
 unsafe impl Send for VideoDecoderSource {}
 
 impl VideoDecoderSource {
-    // The application MUST finish using the returned buffer before calling this function again
-    pub fn dequeue_frame(&mut self) -> Result<Option<(Duration, *mut c_void)>> {
+    pub fn check_error(&mut self) -> Result<()> {
         if let Some(error) = self.error.lock().take() {
-            return Err(anyhow!(error));
+            Err(anyhow!(error))
+        } else {
+            Ok(())
         }
+    }
+
+    // The application MUST finish using the returned buffer before calling this function again
+    pub fn dequeue_frame(&mut self) -> Result<Option<(Duration, *mut c_void)>> {
+        self.check_error()?;
 
         let mut image_queue_lock = self.image_queue.lock();
 
@@ -121,10 +127,30 @@ impl VideoDecoderSource {
             image_queue_lock.pop_front();
         }
 
-        if let Some(queued_image) = image_queue_lock.front_mut() {
+        Ok(Self::use_front(&mut image_queue_lock))
+    }
+
+    // Like dequeue_frame(), without dropping frames to limit the buffering. Used when the output
+    // order is decided externally
+    pub fn dequeue_front(&mut self) -> Result<Option<(Duration, *mut c_void)>> {
+        self.check_error()?;
+
+        let mut image_queue_lock = self.image_queue.lock();
+
+        if let Some(queued_image) = image_queue_lock.front() {
+            if queued_image.in_use {
+                image_queue_lock.pop_front();
+            }
+        }
+
+        Ok(Self::use_front(&mut image_queue_lock))
+    }
+
+    fn use_front(image_queue: &mut VecDeque<QueuedImage>) -> Option<(Duration, *mut c_void)> {
+        if let Some(queued_image) = image_queue.front_mut() {
             queued_image.in_use = true;
 
-            Ok(Some((
+            Some((
                 queued_image.timestamp,
                 queued_image
                     .image
@@ -132,12 +158,33 @@ impl VideoDecoderSource {
                     .unwrap()
                     .as_ptr()
                     .cast(),
-            )))
+            ))
         } else {
             // TODO: add back when implementing proper phase sync
             //warn!("Video frame queue underflow!");
-            Ok(None)
+            None
+        }
+    }
+}
+
+impl DecodedFrameQueue for VideoDecoderSource {
+    // The image in use is released, the application must have finished using it
+    fn front_timestamp(&mut self) -> Option<Duration> {
+        let mut image_queue_lock = self.image_queue.lock();
+
+        if let Some(queued_image) = image_queue_lock.front() {
+            if queued_image.in_use {
+                image_queue_lock.pop_front();
+            }
         }
+
+        image_queue_lock
+            .front()
+            .map(|queued_image| queued_image.timestamp)
+    }
+
+    fn discard_front(&mut self) {
+        self.image_queue.lock().pop_front();
     }
 }
 
@@ -17,6 +17,7 @@ pub struct StatisticsManager {
     prev_vsync: Instant,
     total_pipeline_latency_average: SlidingWindowAverage<Duration>,
     steamvr_pipeline_latency: Duration,
+    video_packets_lost: u32,
 }
 
 impl StatisticsManager {
@@ -36,6 +37,7 @@ impl StatisticsManager {
             steamvr_pipeline_latency: Duration::from_secs_f32(
                 steamvr_pipeline_frames * nominal_server_frame_interval.as_secs_f32(),
             ),
+            video_packets_lost: 0,
         }
     }
 
@@ -94,6 +96,30 @@ impl StatisticsManager {
         }
     }
 
+    pub fn report_decoder_parallelism(&mut self, target_timestamp: Duration, parallelism: usize) {
+        if let Some(frame) = self
+            .history_buffer
+            .iter_mut()
+            .find(|frame| frame.client_stats.target_timestamp == target_timestamp)
+        {
+            frame.client_stats.decoder_parallelism = parallelism as u32;
+        }
+    }
+
+    pub fn report_decoder_dropped_frames(&mut self, target_timestamp: Duration, count: usize) {
+        if let Some(frame) = self
+            .history_buffer
+            .iter_mut()
+            .find(|frame| frame.client_stats.target_timestamp == target_timestamp)
+        {
+            frame.client_stats.decoder_dropped_frames = count as u32;
+        }
+    }
+
+    pub fn report_video_packet_loss(&mut self) {
+        self.video_packets_lost += 1;
+    }
+
     // vsync_queue is the latency between this call and the vsync. it cannot be measured by ALVR and
     // should be reported by the VR runtime
     pub fn report_submit(&mut self, target_timestamp: Duration, vsync_queue: Duration) {
@@ -125,7 +151,10 @@ impl StatisticsManager {
         self.history_buffer
             .iter()
             .find(|frame| frame.client_stats.target_timestamp == target_timestamp)
-            .map(|frame| frame.client_stats.clone())
+            .map(|frame| ClientStatistics {
+                video_packets_lost: self.video_packets_lost,
+                ..frame.client_stats.clone()
+            })
     }
 
     // latency used for head prediction
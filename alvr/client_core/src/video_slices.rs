@@ -0,0 +1,125 @@
+use alvr_packets::VideoPacketHeader;
+
+pub struct SlicedFrame {
+    pub header: VideoPacketHeader,
+    // The received slices in order
+    pub nal: Vec<u8>,
+    pub missing_slices: usize,
+}
+
+struct PendingFrame {
+    header: VideoPacketHeader,
+    slices: Vec<Option<Vec<u8>>>,
+}
+
+impl PendingFrame {
+    fn into_frame(self) -> SlicedFrame {
+        SlicedFrame {
+            header: VideoPacketHeader {
+                slice_index: 0,
+                ..self.header
+            },
+            missing_slices: self.slices.iter().filter(|slice| slice.is_none()).count(),
+            nal: self.slices.into_iter().flatten().flatten().collect(),
+        }
+    }
+}
+
+// Joins the slices of each frame, which are received in separate packets. A frame is returned as
+// soon as all its slices are received. Otherwise it is returned with the slices that arrived when
+// a slice of a newer frame is received, since the socket discards the older packets
+#[derive(Default)]
+pub struct SliceAssembler {
+    pending: Option<PendingFrame>,
+}
+
+impl SliceAssembler {
+    pub fn push_slice(&mut self, header: VideoPacketHeader, slice: &[u8]) -> Vec<SlicedFrame> {
+        let slices_count = u32::max(header.slices_count, 1) as usize;
+        let slice_index = header.slice_index as usize;
+        if slice_index >= slices_count {
+            return vec![];
+        }
+
+        let mut frames = vec![];
+        if let Some(pending) = self.pending.take() {
+            if pending.header.timestamp == header.timestamp {
+                self.pending = Some(pending);
+            } else {
+                frames.push(pending.into_frame());
+            }
+        }
+
+        let pending = self.pending.get_or_insert_with(|| PendingFrame {
+            header: header.clone(),
+            slices: vec![None; slices_count],
+        });
+        if let Some(entry) = pending.slices.get_mut(slice_index) {
+            *entry = Some(slice.to_vec());
+        }
+
+        if pending.slices.iter().all(Option::is_some) {
+            frames.extend(self.pending.take().map(PendingFrame::into_frame));
+        }
+
+        frames
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn header(frame_index: u32, slice_index: u32) -> VideoPacketHeader {
+        VideoPacketHeader {
+            timestamp: Duration::from_millis(frame_index as u64 * 14),
+            is_idr: frame_index == 0,
+            frame_index,
+            foveation_epoch: 0,
+            user_data: vec![],
+            slice_index,
+            slices_count: 4,
+        }
+    }
+
+    fn slice(frame_index: u32, slice_index: u32) -> Vec<u8> {
+        vec![0, 0, 1, frame_index as u8, slice_index as u8]
+    }
+
+    #[test]
+    fn test_frame_with_lost_slice_decodes_the_other_slices() {
+        let mut assembler = SliceAssembler::default();
+
+        // The packets of slice 2 of the first frame are lost
+        for slice_index in [0, 1, 3] {
+            assert!(assembler
+                .push_slice(header(0, slice_index), &slice(0, slice_index))
+                .is_empty());
+        }
+
+        let mut frames = vec![];
+        for slice_index in 0..4 {
+            frames.extend(assembler.push_slice(header(1, slice_index), &slice(1, slice_index)));
+        }
+
+        assert_eq!(frames.len(), 2);
+
+        let partial = &frames[0];
+        assert_eq!(partial.header.frame_index, 0);
+        assert!(partial.header.is_idr);
+        assert_eq!(partial.missing_slices, 1);
+        assert_eq!(
+            partial.nal,
+            [slice(0, 0), slice(0, 1), slice(0, 3)].concat()
+        );
+
+        let complete = &frames[1];
+        assert_eq!(complete.header.frame_index, 1);
+        assert_eq!(complete.missing_slices, 0);
+        assert_eq!(
+            complete.nal,
+            (0..4).flat_map(|index| slice(1, index)).collect::<Vec<_>>()
+        );
+    }
+}
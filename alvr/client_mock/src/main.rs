@@ -1,8 +1,9 @@
 use alvr_client_core::{ClientCapabilities, ClientCoreContext, ClientCoreEvent};
 use alvr_common::{
     glam::{Quat, UVec2, Vec3},
+    info,
     parking_lot::RwLock,
-    Fov, Pose, RelaxedAtomic,
+    Fov, Pose, RelaxedAtomic, RngSource,
 };
 use alvr_packets::{FaceData, ViewParams};
 use alvr_session::CodecType;
@@ -163,6 +164,10 @@ fn tracking_thread(
 
     let mut position_offset = Vec3::ZERO;
 
+    let rng_source = RngSource::from_env_or(None);
+    info!("Random seed of the mock tracking: {}", rng_source.seed());
+    let mut rng = rng_source.stream("random_position");
+
     let mut loop_deadline = Instant::now();
     let mut random_position_deadline = Instant::now();
     while streaming.value() {
@@ -175,7 +180,7 @@ fn tracking_thread(
             random_position_deadline =
                 Instant::now() + Duration::from_millis(input_lock.random_position_interval_ms);
 
-            position_offset = (Vec3::new(rand::random(), rand::random(), rand::random())
+            position_offset = (Vec3::new(rng.next_f32(), rng.next_f32(), rng.next_f32())
                 - Vec3::ONE / 0.5)
                 * input_lock.random_position_offset_magnitude;
         }
@@ -244,6 +249,8 @@ fn client_thread(
         encoder_high_profile: false,
         encoder_10_bits: false,
         encoder_av1: false,
+        runtime_foveation: false,
+        display_10_bits: false,
     };
     let client_core_context = Arc::new(ClientCoreContext::new(capabilities));
 
@@ -289,7 +296,9 @@ fn client_thread(
                         thread.join().ok();
                     }
                 }
-                ClientCoreEvent::Haptics { .. } => (),
+                ClientCoreEvent::Haptics { .. }
+                | ClientCoreEvent::EyeCalibration(_)
+                | ClientCoreEvent::StreamPaused(_) => (),
                 ClientCoreEvent::DecoderConfig { codec, .. } => {
                     window_output.decoder_codec = Some(codec)
                 }
@@ -1,6 +1,139 @@
 use alvr_client_core::graphics::{self, GraphicsContext};
 use alvr_common::glam::UVec2;
 use openxr as xr;
+use std::{
+    fmt::{self, Display, Formatter},
+    ptr,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CompositorError {
+    // The swapchain image did not become available in time. The image stays acquired and waiting
+    // can be retried later.
+    AcquireTimeout,
+    EmptySwapchain {
+        width: u32,
+        height: u32,
+    },
+    SwapchainTooLarge {
+        width: u32,
+        height: u32,
+        max_width: u32,
+        max_height: u32,
+    },
+    Runtime(xr::sys::Result),
+}
+
+impl Display for CompositorError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            CompositorError::AcquireTimeout => write!(f, "Swapchain image acquire timed out"),
+            CompositorError::EmptySwapchain { width, height } => {
+                write!(f, "Swapchain size {width}x{height} has a zero dimension")
+            }
+            CompositorError::SwapchainTooLarge {
+                width,
+                height,
+                max_width,
+                max_height,
+            } => write!(
+                f,
+                "Swapchain size {width}x{height} exceeds the runtime limit of {max_width}x{max_height}"
+            ),
+            CompositorError::Runtime(res) => write!(f, "Swapchain operation failed: {res}"),
+        }
+    }
+}
+
+impl std::error::Error for CompositorError {}
+
+pub trait SwapchainImages {
+    fn acquire_image(&mut self) -> Result<u32, CompositorError>;
+    fn wait_image(&mut self, timeout: xr::Duration) -> Result<(), CompositorError>;
+    fn release_image(&mut self) -> Result<(), CompositorError>;
+}
+
+impl SwapchainImages for xr::Swapchain<xr::OpenGlEs> {
+    fn acquire_image(&mut self) -> Result<u32, CompositorError> {
+        xr::Swapchain::acquire_image(self).map_err(CompositorError::Runtime)
+    }
+
+    // The safe wrapper reports XR_TIMEOUT_EXPIRED as a success, call the runtime directly
+    fn wait_image(&mut self, timeout: xr::Duration) -> Result<(), CompositorError> {
+        let info = xr::sys::SwapchainImageWaitInfo {
+            ty: xr::sys::SwapchainImageWaitInfo::TYPE,
+            next: ptr::null(),
+            timeout,
+        };
+        let res = unsafe { (self.instance().fp().wait_swapchain_image)(self.as_raw(), &info) };
+
+        if res == xr::sys::Result::TIMEOUT_EXPIRED {
+            Err(CompositorError::AcquireTimeout)
+        } else if res.into_raw() < 0 {
+            Err(CompositorError::Runtime(res))
+        } else {
+            Ok(())
+        }
+    }
+
+    fn release_image(&mut self) -> Result<(), CompositorError> {
+        xr::Swapchain::release_image(self).map_err(CompositorError::Runtime)
+    }
+}
+
+// Keeps track of the images acquired from a swapchain. If waiting for an image times out, the image
+// stays acquired and the next call retries the wait on the same image instead of acquiring a new
+// one (which would fail since OpenXR allows only one image waited at a time).
+#[derive(Default)]
+pub struct SwapchainAcquirer {
+    pending_index: Option<u32>,
+}
+
+impl SwapchainAcquirer {
+    pub fn acquire_and_wait(
+        &mut self,
+        swapchain: &mut impl SwapchainImages,
+        timeout: xr::Duration,
+    ) -> Result<u32, CompositorError> {
+        let index = match self.pending_index {
+            Some(index) => index,
+            None => {
+                let index = swapchain.acquire_image()?;
+                self.pending_index = Some(index);
+
+                index
+            }
+        };
+
+        swapchain.wait_image(timeout)?;
+        self.pending_index = None;
+
+        Ok(index)
+    }
+}
+
+// Acquire and wait for both eye swapchain images. On failure, the images already waited for are
+// released without rendering, so the caller can drop the frame and try again at the next one.
+pub fn acquire_view_images(
+    acquirers: &mut [SwapchainAcquirer; 2],
+    swapchains: &mut [impl SwapchainImages; 2],
+    timeout: xr::Duration,
+) -> Result<[u32; 2], CompositorError> {
+    let [left_acquirer, right_acquirer] = acquirers;
+    let [left_swapchain, right_swapchain] = swapchains;
+
+    let left_index = left_acquirer.acquire_and_wait(left_swapchain, timeout)?;
+    let right_index = match right_acquirer.acquire_and_wait(right_swapchain, timeout) {
+        Ok(index) => index,
+        Err(e) => {
+            left_swapchain.release_image()?;
+
+            return Err(e);
+        }
+    };
+
+    Ok([left_index, right_index])
+}
 
 #[allow(unused)]
 pub fn session_create_info(ctx: &GraphicsContext) -> xr::opengles::SessionCreateInfo {
@@ -16,16 +149,61 @@ pub fn session_create_info(ctx: &GraphicsContext) -> xr::opengles::SessionCreate
     unimplemented!()
 }
 
-pub fn create_swapchain(
-    session: &xr::Session<xr::OpenGlEs>,
+// Limit of the swapchain image size of the runtime. No limit if the runtime doesn't report it
+pub fn max_swapchain_resolution(instance: &xr::Instance, system: xr::SystemId) -> UVec2 {
+    instance
+        .system_properties(system)
+        .map(|props| {
+            UVec2::new(
+                props.graphics_properties.max_swapchain_image_width,
+                props.graphics_properties.max_swapchain_image_height,
+            )
+        })
+        .unwrap_or(UVec2::MAX)
+}
+
+// Checked before creating the swapchain, since runtimes report an invalid size with a generic error
+pub fn validate_swapchain_resolution(
     resolution: UVec2,
-    foveation: Option<&xr::FoveationProfileFB>,
+    max_resolution: UVec2,
+) -> Result<(), CompositorError> {
+    if resolution.x == 0 || resolution.y == 0 {
+        Err(CompositorError::EmptySwapchain {
+            width: resolution.x,
+            height: resolution.y,
+        })
+    } else if resolution.x > max_resolution.x || resolution.y > max_resolution.y {
+        Err(CompositorError::SwapchainTooLarge {
+            width: resolution.x,
+            height: resolution.y,
+            max_width: max_resolution.x,
+            max_height: max_resolution.y,
+        })
+    } else {
+        Ok(())
+    }
+}
+
+pub fn swapchain_format(
+    session: &xr::Session<xr::OpenGlEs>,
     enable_hdr: bool,
-) -> xr::Swapchain<xr::OpenGlEs> {
-    let format = graphics::choose_swapchain_format(
+    bit_depth: u8,
+) -> u32 {
+    graphics::choose_swapchain_format(
         session.enumerate_swapchain_formats().ok().as_deref(),
         enable_hdr,
-    );
+        bit_depth,
+    )
+}
+
+pub fn create_swapchain(
+    session: &xr::Session<xr::OpenGlEs>,
+    resolution: UVec2,
+    max_resolution: UVec2,
+    foveation: Option<&xr::FoveationProfileFB>,
+    format: u32,
+) -> Result<xr::Swapchain<xr::OpenGlEs>, CompositorError> {
+    validate_swapchain_resolution(resolution, max_resolution)?;
 
     let swapchain_info = xr::SwapchainCreateInfo {
         create_flags: xr::SwapchainCreateFlags::EMPTY,
@@ -45,13 +223,17 @@ pub fn create_swapchain(
                 &swapchain_info,
                 xr::SwapchainCreateFoveationFlagsFB::SCALED_BIN,
             )
-            .unwrap();
-
-        swapchain.update_foveation(foveation).unwrap();
+            .map_err(CompositorError::Runtime)?;
 
         swapchain
+            .update_foveation(foveation)
+            .map_err(CompositorError::Runtime)?;
+
+        Ok(swapchain)
     } else {
-        session.create_swapchain(&swapchain_info).unwrap()
+        session
+            .create_swapchain(&swapchain_info)
+            .map_err(CompositorError::Runtime)
     }
 }
 
@@ -78,3 +260,129 @@ impl<'a> CompositionLayerBuilder<'a> {
             .views(&self.layers)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::VecDeque;
+
+    #[derive(Default)]
+    struct MockSwapchain {
+        next_index: u32,
+        acquired: Vec<u32>,
+        waited: Option<u32>,
+        wait_results: VecDeque<Result<(), CompositorError>>,
+    }
+
+    impl SwapchainImages for MockSwapchain {
+        fn acquire_image(&mut self) -> Result<u32, CompositorError> {
+            let index = self.next_index;
+            self.next_index = (self.next_index + 1) % 3;
+            self.acquired.push(index);
+
+            Ok(index)
+        }
+
+        fn wait_image(&mut self, _: xr::Duration) -> Result<(), CompositorError> {
+            assert!(self.waited.is_none());
+            self.wait_results.pop_front().unwrap_or(Ok(()))?;
+            self.waited = Some(self.acquired.remove(0));
+
+            Ok(())
+        }
+
+        fn release_image(&mut self) -> Result<(), CompositorError> {
+            self.waited.take().unwrap();
+
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_acquire_timeout_drops_frame_and_recovers() {
+        let timeout = xr::Duration::from_nanos(1_000_000);
+        let mut acquirers = [SwapchainAcquirer::default(), SwapchainAcquirer::default()];
+        let mut swapchains = [MockSwapchain::default(), MockSwapchain::default()];
+        swapchains[1]
+            .wait_results
+            .push_back(Err(CompositorError::AcquireTimeout));
+
+        // First frame: the right eye times out and the frame is dropped
+        assert_eq!(
+            acquire_view_images(&mut acquirers, &mut swapchains, timeout),
+            Err(CompositorError::AcquireTimeout)
+        );
+        assert!(swapchains[0].waited.is_none());
+        assert_eq!(swapchains[1].acquired, [0]);
+
+        // Next frames: the right eye retries the wait on the same image, then the pipeline proceeds
+        // normally
+        for expected in [[1, 0], [2, 1], [0, 2]] {
+            let indices = acquire_view_images(&mut acquirers, &mut swapchains, timeout).unwrap();
+            assert_eq!(indices, expected);
+
+            for swapchain in &mut swapchains {
+                swapchain.release_image().unwrap();
+                assert!(swapchain.acquired.is_empty());
+            }
+        }
+    }
+
+    #[test]
+    fn test_runtime_error_is_distinct_from_timeout() {
+        let mut acquirer = SwapchainAcquirer::default();
+        let mut swapchain = MockSwapchain::default();
+        swapchain
+            .wait_results
+            .push_back(Err(CompositorError::Runtime(
+                xr::sys::Result::ERROR_SESSION_LOST,
+            )));
+
+        let res = acquirer.acquire_and_wait(&mut swapchain, xr::Duration::INFINITE);
+        assert_eq!(
+            res,
+            Err(CompositorError::Runtime(
+                xr::sys::Result::ERROR_SESSION_LOST
+            ))
+        );
+        assert_ne!(res, Err(CompositorError::AcquireTimeout));
+    }
+
+    #[test]
+    fn test_invalid_swapchain_resolution_is_rejected() {
+        let max = UVec2::new(4096, 4096);
+
+        assert_eq!(
+            validate_swapchain_resolution(UVec2::new(0, 1824), max),
+            Err(CompositorError::EmptySwapchain {
+                width: 0,
+                height: 1824
+            })
+        );
+        assert_eq!(
+            validate_swapchain_resolution(UVec2::new(1920, 0), max),
+            Err(CompositorError::EmptySwapchain {
+                width: 1920,
+                height: 0
+            })
+        );
+
+        let error = validate_swapchain_resolution(UVec2::new(1920, 8192), max).unwrap_err();
+        assert_eq!(
+            error,
+            CompositorError::SwapchainTooLarge {
+                width: 1920,
+                height: 8192,
+                max_width: 4096,
+                max_height: 4096
+            }
+        );
+        assert_eq!(
+            error.to_string(),
+            "Swapchain size 1920x8192 exceeds the runtime limit of 4096x4096"
+        );
+
+        assert!(validate_swapchain_resolution(max, max).is_ok());
+        assert!(validate_swapchain_resolution(UVec2::new(1920, 1824), UVec2::MAX).is_ok());
+    }
+}
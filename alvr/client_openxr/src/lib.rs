@@ -4,14 +4,14 @@ mod interaction;
 mod lobby;
 mod stream;
 
-use crate::stream::StreamConfig;
+use crate::{graphics::CompositorError, stream::StreamConfig};
 use alvr_client_core::{
     graphics::GraphicsContext, ClientCapabilities, ClientCoreContext, ClientCoreEvent, Platform,
 };
 use alvr_common::{
     error,
     glam::{Quat, UVec2, Vec3},
-    info, Fov, Pose, HAND_LEFT_ID,
+    info, warn, Fov, Pose, HAND_LEFT_ID,
 };
 use lobby::Lobby;
 use openxr as xr;
@@ -26,6 +26,8 @@ use stream::StreamContext;
 use xr::ColorSpaceFB;
 
 const DECODER_MAX_TIMEOUT_MULTIPLIER: f32 = 0.8;
+// Give up waiting for a swapchain image after this many frame intervals and drop the frame
+const SWAPCHAIN_WAIT_TIMEOUT_FRAMES: i64 = 2;
 
 fn from_xr_vec3(v: xr::Vector3f) -> Vec3 {
     Vec3::new(v.x, v.y, v.z)
@@ -238,6 +240,10 @@ pub fn entry_point() {
             encoder_high_profile: platform != Platform::Unknown,
             encoder_10_bits: platform != Platform::Unknown,
             encoder_av1: platform == Platform::Quest3,
+            runtime_foveation: true,
+            display_10_bits: alvr_client_core::graphics::supports_10_bit_swapchain(
+                xr_session.enumerate_swapchain_formats().ok().as_deref(),
+            ),
         };
         let core_context = Arc::new(ClientCoreContext::new(capabilities));
 
@@ -260,6 +266,7 @@ pub fn entry_point() {
         );
         let mut session_running = false;
         let mut stream_context = None::<StreamContext>;
+        let mut stream_paused = false;
 
         let mut event_storage = xr::EventDataBuffer::new();
         'render_loop: loop {
@@ -286,6 +293,10 @@ pub fn entry_point() {
 
                             xr_session.end().unwrap();
                         }
+                        // The session is idle when the headset is removed, detected by the
+                        // proximity sensor
+                        xr::SessionState::IDLE => core_context.send_headset_presence(false),
+                        xr::SessionState::FOCUSED => core_context.send_headset_presence(true),
                         xr::SessionState::EXITING => break 'render_loop,
                         xr::SessionState::LOSS_PENDING => break 'render_loop,
                         _ => (),
@@ -353,20 +364,39 @@ pub fn entry_point() {
                             continue;
                         }
 
-                        stream_context = Some(StreamContext::new(
+                        stream_context = match StreamContext::new(
                             Arc::clone(&core_context),
                             xr_context.clone(),
                             Rc::clone(&graphics_context),
                             Arc::clone(&interaction_context),
                             platform,
                             &new_config,
-                        ));
+                        ) {
+                            Ok(context) => Some(context),
+                            Err(e) => {
+                                error!("Failed to create the stream swapchains: {e}");
+                                lobby.update_hud_message(&format!(
+                                    "Failed to start the stream: {e}"
+                                ));
+
+                                None
+                            }
+                        };
 
                         stream_config = Some(new_config);
+                        stream_paused = false;
                     }
                     ClientCoreEvent::StreamingStopped => {
                         stream_context = None;
                     }
+                    ClientCoreEvent::StreamPaused(paused) => {
+                        stream_paused = paused;
+                    }
+                    ClientCoreEvent::EyeCalibration(poses) => {
+                        if let Some(context) = &mut stream_context {
+                            context.set_eye_calibration(poses);
+                        }
+                    }
                     ClientCoreEvent::Haptics {
                         device_id,
                         duration,
@@ -422,8 +452,14 @@ pub fn entry_point() {
                 continue;
             }
 
+            let acquire_timeout = xr::Duration::from_nanos(
+                frame_state.predicted_display_period.as_nanos() * SWAPCHAIN_WAIT_TIMEOUT_FRAMES,
+            );
+
             // todo: allow rendering lobby and stream layers at the same time and add cross fade
-            let (layer, display_time) = if let Some(context) = &mut stream_context {
+            // The lobby is shown while the stream is paused
+            let active_stream_context = stream_context.as_mut().filter(|_| !stream_paused);
+            let (layer, display_time) = if let Some(context) = active_stream_context {
                 let frame_poll_deadline = Instant::now()
                     + Duration::from_secs_f32(
                         frame_interval.as_secs_f32() * DECODER_MAX_TIMEOUT_MULTIPLIER,
@@ -439,15 +475,47 @@ pub fn entry_point() {
                     .map(|r| r.timestamp)
                     .unwrap_or(vsync_time);
 
-                let layer = context.render(frame_result, vsync_time);
+                let layer = context.render(frame_result, vsync_time, acquire_timeout);
 
                 (layer, timestamp)
             } else {
-                let layer = lobby.render(frame_state.predicted_display_time);
+                let layer = lobby.render(frame_state.predicted_display_time, acquire_timeout);
 
                 (layer, vsync_time)
             };
 
+            let layer = match layer {
+                Ok(layer) => layer,
+                Err(CompositorError::Runtime(e)) => {
+                    // Runtime failures like XR_ERROR_SESSION_LOST are not recoverable by retrying
+                    // at the next frame. Recreate the session instead
+                    error!("{e}, recreating session");
+
+                    break 'render_loop;
+                }
+                Err(e) => {
+                    // Treat the frame as dropped. A timed out image wait is retried at the next
+                    // frame
+                    if e == CompositorError::AcquireTimeout {
+                        warn!("{e}, dropping frame");
+                    } else {
+                        error!("{e}, dropping frame");
+                    }
+
+                    if let Err(e) = xr_frame_stream.end(
+                        frame_state.predicted_display_time,
+                        xr::EnvironmentBlendMode::OPAQUE,
+                        &[],
+                    ) {
+                        error!("End frame failed! {e}, recreating session");
+
+                        break 'render_loop;
+                    }
+
+                    continue;
+                }
+            };
+
             let res = xr_frame_stream.end(
                 to_xr_time(display_time),
                 xr::EnvironmentBlendMode::OPAQUE,
@@ -458,13 +526,15 @@ pub fn entry_point() {
                 let time = to_xr_time(display_time);
                 error!("End frame failed! {e}, timestamp: {display_time:?}, time: {time:?}");
 
-                xr_frame_stream
-                    .end(
-                        frame_state.predicted_display_time,
-                        xr::EnvironmentBlendMode::OPAQUE,
-                        &[],
-                    )
-                    .unwrap();
+                if let Err(e) = xr_frame_stream.end(
+                    frame_state.predicted_display_time,
+                    xr::EnvironmentBlendMode::OPAQUE,
+                    &[],
+                ) {
+                    error!("End frame failed! {e}, recreating session");
+
+                    break 'render_loop;
+                }
             }
         }
     }
@@ -1,5 +1,5 @@
 use crate::{
-    graphics::{self, CompositionLayerBuilder},
+    graphics::{self, CompositionLayerBuilder, CompositorError, SwapchainAcquirer},
     interaction, XrContext,
 };
 use alvr_client_core::graphics::{GraphicsContext, LobbyRenderer, RenderViewInput};
@@ -12,6 +12,7 @@ pub struct Lobby {
     xr_session: xr::Session<xr::OpenGlEs>,
     reference_space: xr::Space,
     swapchains: [xr::Swapchain<xr::OpenGlEs>; 2],
+    swapchain_acquirers: [SwapchainAcquirer; 2],
     view_resolution: UVec2,
     reference_space_type: xr::ReferenceSpaceType,
     renderer: LobbyRenderer,
@@ -35,9 +36,26 @@ impl Lobby {
         let reference_space =
             interaction::get_reference_space(&xr_ctx.session, reference_space_type);
 
+        // The recommended resolution is always supported by the runtime
+        let max_resolution = graphics::max_swapchain_resolution(&xr_ctx.instance, xr_ctx.system);
+        let format = graphics::swapchain_format(&xr_ctx.session, false, 8);
         let swapchains = [
-            graphics::create_swapchain(&xr_ctx.session, view_resolution, None, false),
-            graphics::create_swapchain(&xr_ctx.session, view_resolution, None, false),
+            graphics::create_swapchain(
+                &xr_ctx.session,
+                view_resolution,
+                max_resolution,
+                None,
+                format,
+            )
+            .unwrap(),
+            graphics::create_swapchain(
+                &xr_ctx.session,
+                view_resolution,
+                max_resolution,
+                None,
+                format,
+            )
+            .unwrap(),
         ];
 
         let renderer = LobbyRenderer::new(
@@ -65,6 +83,7 @@ impl Lobby {
             xr_session: xr_ctx.session.clone(),
             reference_space,
             swapchains,
+            swapchain_acquirers: Default::default(),
             view_resolution,
             reference_space_type,
             renderer,
@@ -80,7 +99,11 @@ impl Lobby {
         self.renderer.update_hud_message(message);
     }
 
-    pub fn render(&mut self, predicted_display_time: xr::Time) -> CompositionLayerBuilder {
+    pub fn render(
+        &mut self,
+        predicted_display_time: xr::Time,
+        acquire_timeout: xr::Duration,
+    ) -> Result<CompositionLayerBuilder, CompositorError> {
         let (flags, maybe_views) = self
             .xr_session
             .locate_views(
@@ -96,15 +119,11 @@ impl Lobby {
             vec![crate::default_view(), crate::default_view()]
         };
 
-        let left_swapchain_idx = self.swapchains[0].acquire_image().unwrap();
-        let right_swapchain_idx = self.swapchains[1].acquire_image().unwrap();
-
-        self.swapchains[0]
-            .wait_image(xr::Duration::INFINITE)
-            .unwrap();
-        self.swapchains[1]
-            .wait_image(xr::Duration::INFINITE)
-            .unwrap();
+        let [left_swapchain_idx, right_swapchain_idx] = graphics::acquire_view_images(
+            &mut self.swapchain_acquirers,
+            &mut self.swapchains,
+            acquire_timeout,
+        )?;
 
         self.renderer.render([
             RenderViewInput {
@@ -130,7 +149,7 @@ impl Lobby {
             },
         };
 
-        CompositionLayerBuilder::new(
+        Ok(CompositionLayerBuilder::new(
             &self.reference_space,
             [
                 xr::CompositionLayerProjectionView::new()
@@ -152,6 +171,6 @@ impl Lobby {
                             .image_rect(rect),
                     ),
             ],
-        )
+        ))
     }
 }
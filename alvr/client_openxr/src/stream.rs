@@ -1,22 +1,27 @@
 use crate::{
     from_xr_pose,
-    graphics::{self, CompositionLayerBuilder},
+    graphics::{self, CompositionLayerBuilder, CompositorError, SwapchainAcquirer},
     interaction::{self, InteractionContext},
     to_xr_fov, to_xr_pose, XrContext,
 };
 use alvr_client_core::{
-    graphics::{GraphicsContext, StreamRenderer},
+    graphics::{
+        self as core_graphics, GraphicsContext, LatencyStamp, RenderViewInput, ReticleParams,
+        StreamPassesConfig, StreamRenderer,
+    },
     ClientCoreContext, DecodedFrame, Platform,
 };
 use alvr_common::{
     error,
     glam::{UVec2, Vec2, Vec3},
-    RelaxedAtomic, HAND_LEFT_ID, HAND_RIGHT_ID,
+    info, Pose, RelaxedAtomic, RngSource, HAND_LEFT_ID, HAND_RIGHT_ID,
 };
 use alvr_packets::{FaceData, NegotiatedStreamingConfig, ViewParams};
 use alvr_session::{
-    BodyTrackingSourcesConfig, ClientsideFoveationConfig, ClientsideFoveationMode, EncoderConfig,
-    FaceTrackingSourcesConfig, FoveatedEncodingConfig, Settings,
+    BodyTrackingSourcesConfig, ClientsideFoveationConfig, ClientsideFoveationMode, ColorLutConfig,
+    ComfortVignetteConfig, DitheringConfig, EncoderConfig, EyeCalibrationConfig,
+    FaceTrackingSourcesConfig, FoveatedEncodingConfig, LatencyStampConfig, MonoVirtualScreenConfig,
+    ReticleConfig, Settings, TestPattern, VignetteCorrectionConfig,
 };
 use openxr as xr;
 use std::{
@@ -32,10 +37,23 @@ const MAX_PREDICTION: Duration = Duration::from_millis(70);
 #[derive(PartialEq)]
 pub struct StreamConfig {
     pub view_resolution: UVec2,
+    pub upscaled_view_resolution: UVec2,
     pub refresh_rate_hint: f32,
     pub foveated_encoding_config: Option<FoveatedEncodingConfig>,
     pub clientside_foveation_config: Option<ClientsideFoveationConfig>,
     pub encoder_config: EncoderConfig,
+    pub bit_depth: u8,
+    pub motion_smoothing: bool,
+    pub test_pattern: Option<TestPattern>,
+    pub vignette_correction_config: Option<VignetteCorrectionConfig>,
+    pub color_lut_config: Option<ColorLutConfig>,
+    pub comfort_vignette_config: Option<ComfortVignetteConfig>,
+    pub dithering_config: Option<DitheringConfig>,
+    pub rng_seed: Option<u64>,
+    pub reticle_config: Option<ReticleConfig>,
+    pub latency_stamp_config: Option<LatencyStampConfig>,
+    pub mono_virtual_screen_config: Option<MonoVirtualScreenConfig>,
+    pub eye_calibration_config: Option<EyeCalibrationConfig>,
     pub face_sources_config: Option<FaceTrackingSourcesConfig>,
     pub body_sources_config: Option<BodyTrackingSourcesConfig>,
 }
@@ -44,6 +62,7 @@ impl StreamConfig {
     pub fn new(settings: &Settings, negotiated_config: NegotiatedStreamingConfig) -> StreamConfig {
         StreamConfig {
             view_resolution: negotiated_config.view_resolution,
+            upscaled_view_resolution: negotiated_config.upscaled_view_resolution,
             refresh_rate_hint: negotiated_config.refresh_rate_hint,
             foveated_encoding_config: negotiated_config
                 .enable_foveated_encoding
@@ -51,6 +70,18 @@ impl StreamConfig {
                 .flatten(),
             clientside_foveation_config: settings.video.clientside_foveation.as_option().cloned(),
             encoder_config: settings.video.encoder_config.clone(),
+            bit_depth: negotiated_config.bit_depth,
+            motion_smoothing: settings.video.motion_smoothing,
+            test_pattern: settings.video.test_pattern.as_option().copied(),
+            vignette_correction_config: settings.video.vignette_correction.as_option().cloned(),
+            color_lut_config: settings.video.color_lut.as_option().cloned(),
+            comfort_vignette_config: settings.video.comfort_vignette.as_option().cloned(),
+            dithering_config: settings.video.dithering.as_option().cloned(),
+            rng_seed: settings.extra.rng_seed.as_option().copied(),
+            reticle_config: settings.video.reticle.as_option().cloned(),
+            latency_stamp_config: settings.video.latency_stamp.as_option().cloned(),
+            mono_virtual_screen_config: settings.video.mono_virtual_screen.as_option().cloned(),
+            eye_calibration_config: settings.headset.eye_calibration.as_option().cloned(),
             face_sources_config: settings
                 .headset
                 .face_tracking
@@ -71,9 +102,15 @@ pub struct StreamContext {
     interaction_context: Arc<InteractionContext>,
     reference_space: Arc<xr::Space>,
     swapchains: [xr::Swapchain<xr::OpenGlEs>; 2],
-    view_resolution: UVec2,
+    swapchain_acquirers: [SwapchainAcquirer; 2],
+    swapchain_resolution: UVec2,
     refresh_rate: f32,
     last_good_view_params: [ViewParams; 2],
+    last_good_timestamp: Duration,
+    eye_calibration: [Pose; 2],
+    foveated_encoding: Option<FoveatedEncodingConfig>,
+    reticle: Option<ReticleParams>,
+    latency_stamp: Option<LatencyStamp>,
     input_thread: Option<JoinHandle<()>>,
     input_thread_running: Arc<RelaxedAtomic>,
     renderer: StreamRenderer,
@@ -87,12 +124,24 @@ impl StreamContext {
         interaction_ctx: Arc<InteractionContext>,
         platform: Platform,
         config: &StreamConfig,
-    ) -> StreamContext {
+    ) -> Result<StreamContext, CompositorError> {
         if xr_ctx.instance.exts().fb_display_refresh_rate.is_some() {
-            xr_ctx
-                .session
-                .request_display_refresh_rate(config.refresh_rate_hint)
-                .unwrap();
+            // Older servers don't validate the refresh rate, and the display cannot switch to an
+            // unsupported one. If the runtime fails, the stream keeps the current refresh rate
+            match xr_ctx.session.enumerate_display_refresh_rates() {
+                Ok(supported_refresh_rates) => match alvr_packets::validate_refresh_rate(
+                    config.refresh_rate_hint,
+                    &supported_refresh_rates,
+                ) {
+                    Ok(refresh_rate) => {
+                        if let Err(e) = xr_ctx.session.request_display_refresh_rate(refresh_rate) {
+                            error!("Failed to request the refresh rate {refresh_rate} Hz: {e}");
+                        }
+                    }
+                    Err(e) => error!("{e}"),
+                },
+                Err(e) => error!("Failed to enumerate the display refresh rates: {e}"),
+            }
         }
         // todo: check which permissions are needed for htc
         #[cfg(target_os = "android")]
@@ -159,24 +208,43 @@ impl StreamContext {
             None
         };
 
+        let max_resolution = graphics::max_swapchain_resolution(&xr_ctx.instance, xr_ctx.system);
+        let format = graphics::swapchain_format(
+            &xr_ctx.session,
+            config.encoder_config.enable_hdr,
+            config.bit_depth,
+        );
         let swapchains = [
             graphics::create_swapchain(
                 &xr_ctx.session,
-                config.view_resolution,
+                config.upscaled_view_resolution,
+                max_resolution,
                 foveation_profile.as_ref(),
-                config.encoder_config.enable_hdr,
-            ),
+                format,
+            )?,
             graphics::create_swapchain(
                 &xr_ctx.session,
-                config.view_resolution,
+                config.upscaled_view_resolution,
+                max_resolution,
                 foveation_profile.as_ref(),
-                config.encoder_config.enable_hdr,
-            ),
+                format,
+            )?,
         ];
 
+        // Higher precision swapchains do not quantize to 8 bit
+        let dithering_config = config
+            .dithering_config
+            .clone()
+            .filter(|_| core_graphics::is_8_bit_swapchain_format(format));
+        let rng_source = RngSource::from_env_or(config.rng_seed);
+        if dithering_config.is_some() {
+            info!("Random seed of the dithering: {}", rng_source.seed());
+        }
+
         let renderer = StreamRenderer::new(
             gfx_ctx,
             config.view_resolution,
+            config.upscaled_view_resolution,
             [
                 swapchains[0]
                     .enumerate_images()
@@ -199,6 +267,16 @@ impl StreamContext {
                     && config.encoder_config.enable_hdr),
             !config.encoder_config.enable_hdr,
             config.encoder_config.encoding_gamma,
+            config.motion_smoothing,
+            StreamPassesConfig {
+                test_pattern: config.test_pattern,
+                vignette_correction: config.vignette_correction_config.clone(),
+                color_lut: config.color_lut_config.clone(),
+                comfort_vignette: config.comfort_vignette_config.clone(),
+                dithering: dithering_config,
+                mono_virtual_screen: config.mono_virtual_screen_config.clone(),
+            },
+            rng_source,
         );
 
         core_ctx.send_playspace(
@@ -244,19 +322,33 @@ impl StreamContext {
             }
         });
 
-        StreamContext {
+        Ok(StreamContext {
             core_context: core_ctx,
             xr_context: xr_ctx,
             interaction_context: interaction_ctx,
             reference_space,
             swapchains,
-            view_resolution: config.view_resolution,
+            swapchain_acquirers: Default::default(),
+            swapchain_resolution: config.upscaled_view_resolution,
             refresh_rate: config.refresh_rate_hint,
             last_good_view_params: [ViewParams::default(); 2],
+            last_good_timestamp: Duration::ZERO,
+            eye_calibration: core_graphics::eye_calibration_poses(
+                config.eye_calibration_config.as_ref(),
+            ),
+            foveated_encoding: config.foveated_encoding_config.clone(),
+            reticle: config.reticle_config.as_ref().map(ReticleParams::new),
+            latency_stamp: config.latency_stamp_config.as_ref().map(|stamp_config| {
+                LatencyStamp::new(stamp_config, config.upscaled_view_resolution)
+            }),
             input_thread: Some(input_thread),
             input_thread_running,
             renderer,
-        }
+        })
+    }
+
+    pub fn set_eye_calibration(&mut self, poses: [Pose; 2]) {
+        self.eye_calibration = poses;
     }
 
     pub fn update_reference_space(&mut self) {
@@ -305,7 +397,8 @@ impl StreamContext {
         &mut self,
         decoded_frame: Option<DecodedFrame>,
         vsync_time: Duration,
-    ) -> CompositionLayerBuilder {
+        acquire_timeout: xr::Duration,
+    ) -> Result<CompositionLayerBuilder, CompositorError> {
         let timestamp;
         let view_params;
         let buffer_ptr;
@@ -315,24 +408,64 @@ impl StreamContext {
             buffer_ptr = frame.buffer_ptr;
 
             self.last_good_view_params = frame.view_params;
+            self.last_good_timestamp = frame.timestamp;
+
+            if frame.foveated_encoding != self.foveated_encoding {
+                self.renderer
+                    .set_foveated_encoding(frame.foveated_encoding.clone());
+                self.foveated_encoding = frame.foveated_encoding;
+            }
         } else {
             timestamp = vsync_time;
             view_params = self.last_good_view_params;
             buffer_ptr = std::ptr::null_mut();
         }
 
-        let left_swapchain_idx = self.swapchains[0].acquire_image().unwrap();
-        let right_swapchain_idx = self.swapchains[1].acquire_image().unwrap();
+        let [left_swapchain_idx, right_swapchain_idx] = graphics::acquire_view_images(
+            &mut self.swapchain_acquirers,
+            &mut self.swapchains,
+            acquire_timeout,
+        )?;
+
+        let view_inputs = [
+            RenderViewInput {
+                pose: view_params[0].pose,
+                fov: view_params[0].fov,
+                swapchain_index: left_swapchain_idx,
+            },
+            RenderViewInput {
+                pose: view_params[1].pose,
+                fov: view_params[1].fov,
+                swapchain_index: right_swapchain_idx,
+            },
+        ];
+        self.renderer.render(buffer_ptr, view_inputs);
+
+        let layer_poses = core_graphics::apply_eye_calibration(
+            [view_params[0].pose, view_params[1].pose],
+            self.eye_calibration,
+        );
 
-        self.swapchains[0]
-            .wait_image(xr::Duration::INFINITE)
-            .unwrap();
-        self.swapchains[1]
-            .wait_image(xr::Duration::INFINITE)
-            .unwrap();
+        // Placed with the poses used to display the frame
+        if let Some(reticle) = &self.reticle {
+            self.renderer.draw_reticle(
+                [0, 1].map(|eye| RenderViewInput {
+                    pose: layer_poses[eye],
+                    ..view_inputs[eye]
+                }),
+                reticle,
+            );
+        }
 
-        self.renderer
-            .render(buffer_ptr, [left_swapchain_idx, right_swapchain_idx]);
+        // Without a new frame the last one is drawn again, together with its timestamp
+        if let Some(stamp) = &self.latency_stamp {
+            self.renderer.draw_latency_stamp(
+                view_inputs,
+                stamp,
+                self.swapchain_resolution.y,
+                core_graphics::stamp_value(self.last_good_timestamp),
+            );
+        }
 
         self.swapchains[0].release_image().unwrap();
         self.swapchains[1].release_image().unwrap();
@@ -347,16 +480,16 @@ impl StreamContext {
         let rect = xr::Rect2Di {
             offset: xr::Offset2Di { x: 0, y: 0 },
             extent: xr::Extent2Di {
-                width: self.view_resolution.x as _,
-                height: self.view_resolution.y as _,
+                width: self.swapchain_resolution.x as _,
+                height: self.swapchain_resolution.y as _,
             },
         };
 
-        CompositionLayerBuilder::new(
+        Ok(CompositionLayerBuilder::new(
             &self.reference_space,
             [
                 xr::CompositionLayerProjectionView::new()
-                    .pose(to_xr_pose(view_params[0].pose))
+                    .pose(to_xr_pose(layer_poses[0]))
                     .fov(to_xr_fov(view_params[0].fov))
                     .sub_image(
                         xr::SwapchainSubImage::new()
@@ -365,7 +498,7 @@ impl StreamContext {
                             .image_rect(rect),
                     ),
                 xr::CompositionLayerProjectionView::new()
-                    .pose(to_xr_pose(view_params[1].pose))
+                    .pose(to_xr_pose(layer_poses[1]))
                     .fov(to_xr_fov(view_params[1].fov))
                     .sub_image(
                         xr::SwapchainSubImage::new()
@@ -374,7 +507,7 @@ impl StreamContext {
                             .image_rect(rect),
                     ),
             ],
-        )
+        ))
     }
 }
 
@@ -0,0 +1,218 @@
+use anyhow::{bail, Result};
+use serde::{Deserialize, Serialize};
+use std::mem;
+
+// Phase of the connection to a client. Unlike ConnectionState, which is stored in the client list,
+// this follows the attempts and the failures
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub enum ConnectionPhase {
+    Disconnected,
+    // Waiting for the client to answer on one of its IPs
+    Discovering,
+    // Negotiating the stream
+    Handshaking,
+    Streaming,
+    // Waiting for the next attempt after a failure, or renegotiating the stream in place
+    Reconnecting,
+    Error(String),
+}
+
+impl ConnectionPhase {
+    // Disconnected can be reached from any other phase, for example when the client is removed
+    pub fn can_transition_to(&self, next: &ConnectionPhase) -> bool {
+        use ConnectionPhase::*;
+
+        match (self, next) {
+            (Disconnected, Disconnected) => false,
+            (_, Disconnected) => true,
+            (Disconnected, Discovering)
+            | (Discovering, Handshaking | Reconnecting)
+            | (Handshaking, Streaming | Error(_))
+            | (Streaming, Reconnecting | Error(_))
+            | (Reconnecting, Discovering | Streaming)
+            | (Error(_), Reconnecting) => true,
+            _ => false,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct ConnectionTransition {
+    pub from: ConnectionPhase,
+    pub to: ConnectionPhase,
+}
+
+// Validates the transitions between the phases of a connection and notifies them to the observers,
+// in order
+pub struct ConnectionStateMachine {
+    phase: ConnectionPhase,
+    observers: Vec<Box<dyn FnMut(&ConnectionTransition) + Send>>,
+}
+
+impl ConnectionStateMachine {
+    pub fn new() -> Self {
+        Self {
+            phase: ConnectionPhase::Disconnected,
+            observers: vec![],
+        }
+    }
+
+    pub fn phase(&self) -> &ConnectionPhase {
+        &self.phase
+    }
+
+    pub fn add_observer(&mut self, observer: impl FnMut(&ConnectionTransition) + Send + 'static) {
+        self.observers.push(Box::new(observer));
+    }
+
+    // An invalid transition keeps the current phase and is not notified
+    pub fn transition(&mut self, to: ConnectionPhase) -> Result<()> {
+        if !self.phase.can_transition_to(&to) {
+            bail!(
+                "Invalid connection transition from {:?} to {to:?}",
+                self.phase
+            );
+        }
+
+        let transition = ConnectionTransition {
+            from: mem::replace(&mut self.phase, to.clone()),
+            to,
+        };
+        for observer in &mut self.observers {
+            observer(&transition);
+        }
+
+        Ok(())
+    }
+}
+
+impl Default for ConnectionStateMachine {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use parking_lot::Mutex;
+    use std::sync::Arc;
+    use ConnectionPhase::*;
+
+    fn observed_machine() -> (ConnectionStateMachine, Arc<Mutex<Vec<ConnectionPhase>>>) {
+        let phases = Arc::new(Mutex::new(vec![]));
+
+        let mut machine = ConnectionStateMachine::new();
+        machine.add_observer({
+            let phases = Arc::clone(&phases);
+            move |transition| phases.lock().push(transition.to.clone())
+        });
+
+        (machine, phases)
+    }
+
+    #[test]
+    fn test_normal_connect_sequence() {
+        let (mut machine, phases) = observed_machine();
+
+        // The first attempt times out, the client answers the second one
+        for phase in [
+            Discovering,
+            Reconnecting,
+            Discovering,
+            Handshaking,
+            Streaming,
+            Disconnected,
+        ] {
+            machine.transition(phase).unwrap();
+        }
+
+        assert_eq!(
+            *phases.lock(),
+            [
+                Discovering,
+                Reconnecting,
+                Discovering,
+                Handshaking,
+                Streaming,
+                Disconnected
+            ]
+        );
+        assert_eq!(*machine.phase(), Disconnected);
+    }
+
+    #[test]
+    fn test_error_then_reconnect_sequence() {
+        let (mut machine, phases) = observed_machine();
+
+        for phase in [Discovering, Handshaking, Streaming] {
+            machine.transition(phase).unwrap();
+        }
+
+        // Encoder change in place
+        machine.transition(Reconnecting).unwrap();
+        machine.transition(Streaming).unwrap();
+
+        machine.transition(Error("Connection lost".into())).unwrap();
+
+        // The stream cannot resume without a new handshake
+        assert!(machine.transition(Streaming).is_err());
+        assert!(machine.transition(Handshaking).is_err());
+        assert_eq!(*machine.phase(), Error("Connection lost".into()));
+
+        for phase in [Reconnecting, Discovering, Handshaking, Streaming] {
+            machine.transition(phase).unwrap();
+        }
+
+        assert_eq!(
+            *phases.lock(),
+            [
+                Discovering,
+                Handshaking,
+                Streaming,
+                Reconnecting,
+                Streaming,
+                Error("Connection lost".into()),
+                Reconnecting,
+                Discovering,
+                Handshaking,
+                Streaming,
+            ]
+        );
+
+        machine.transition(Disconnected).unwrap();
+        assert!(machine.transition(Disconnected).is_err());
+    }
+
+    // The server starts an attempt or a fast reconnect only if the machine accepts the transition
+    #[test]
+    fn test_transitions_gate_attempts_and_fast_reconnects() {
+        let (mut machine, phases) = observed_machine();
+
+        machine.transition(Discovering).unwrap();
+        machine.transition(Handshaking).unwrap();
+
+        // A due retry while the client is handshaking or streaming is skipped
+        assert!(machine.transition(Discovering).is_err());
+        machine.transition(Streaming).unwrap();
+        assert!(machine.transition(Discovering).is_err());
+
+        // A second fast reconnect waits for the first one to complete
+        machine.transition(Reconnecting).unwrap();
+        assert!(machine.transition(Reconnecting).is_err());
+        machine.transition(Streaming).unwrap();
+        machine.transition(Reconnecting).unwrap();
+
+        assert_eq!(
+            *phases.lock(),
+            [
+                Discovering,
+                Handshaking,
+                Streaming,
+                Reconnecting,
+                Streaming,
+                Reconnecting
+            ]
+        );
+    }
+}
@@ -1,8 +1,10 @@
 mod average;
+mod connection_phase;
 mod connection_result;
 mod inputs;
 mod logging;
 mod primitives;
+mod rng;
 mod version;
 
 use once_cell::sync::Lazy;
@@ -18,11 +20,13 @@ pub use semver;
 pub use settings_schema;
 
 pub use average::*;
+pub use connection_phase::*;
 pub use connection_result::*;
 pub use inputs::*;
 pub use log::{debug, error, info, warn};
 pub use logging::*;
 pub use primitives::*;
+pub use rng::*;
 pub use version::*;
 
 pub const ALVR_NAME: &str = "ALVR";
@@ -0,0 +1,121 @@
+use std::{
+    env,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+// Overrides the seed of the settings, to reproduce a run without editing the session
+pub const RNG_SEED_ENV_VAR: &str = "ALVR_RNG_SEED";
+
+// Deterministic pseudo-random generator (SplitMix64). Not meant for identifiers that must be
+// unique across runs, like the client hostname
+#[derive(Clone, Debug)]
+pub struct Rng {
+    state: u64,
+}
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        Self { state: seed }
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+
+        let mut value = self.state;
+        value = (value ^ (value >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        value = (value ^ (value >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+
+        value ^ (value >> 31)
+    }
+
+    // Uniform in [0, 1)
+    pub fn next_f32(&mut self) -> f32 {
+        (self.next_u64() >> 40) as f32 / (1_u64 << 24) as f32
+    }
+
+    // Uniform in [min, max)
+    pub fn range_f32(&mut self, min: f32, max: f32) -> f32 {
+        min + (max - min) * self.next_f32()
+    }
+
+    // Returns true with the given probability
+    pub fn chance(&mut self, probability: f32) -> bool {
+        self.next_f32() < probability
+    }
+}
+
+// Source of the random generators of a session. Each component takes its own named stream, so
+// the sequence of a component does not depend on how the other components and threads consume
+// theirs, and a given seed reproduces a run exactly.
+#[derive(Clone, Copy, Debug)]
+pub struct RngSource {
+    seed: u64,
+}
+
+impl RngSource {
+    pub fn new(seed: u64) -> Self {
+        Self { seed }
+    }
+
+    // The environment variable takes precedence over the configured seed. Without either, the seed
+    // is random. The seed is returned by seed(), to be logged
+    pub fn from_env_or(config_seed: Option<u64>) -> Self {
+        let env_seed = env::var(RNG_SEED_ENV_VAR)
+            .ok()
+            .and_then(|value| value.trim().parse().ok());
+
+        let seed = env_seed.or(config_seed).unwrap_or_else(|| {
+            let nanos = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|duration| duration.as_nanos() as u64)
+                .unwrap_or_default();
+
+            Rng::new(nanos ^ ((std::process::id() as u64) << 32)).next_u64()
+        });
+
+        Self { seed }
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    pub fn stream(&self, name: &str) -> Rng {
+        // FNV-1a
+        let name_hash = name.bytes().fold(0xcbf2_9ce4_8422_2325_u64, |hash, byte| {
+            (hash ^ byte as u64).wrapping_mul(0x100_0000_01b3)
+        });
+
+        Rng::new(Rng::new(self.seed ^ name_hash).next_u64())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_streams_are_reproducible_and_independent() {
+        let source = RngSource::new(1234);
+
+        let sequence = |mut rng: Rng| (0..100).map(|_| rng.next_u64()).collect::<Vec<_>>();
+        assert_eq!(
+            sequence(source.stream("loss")),
+            sequence(source.stream("loss"))
+        );
+        assert_ne!(
+            sequence(source.stream("loss")),
+            sequence(source.stream("dither"))
+        );
+        assert_ne!(
+            sequence(source.stream("loss")),
+            sequence(RngSource::new(1235).stream("loss"))
+        );
+
+        let mut rng = source.stream("values");
+        let values = (0..10_000).map(|_| rng.next_f32()).collect::<Vec<_>>();
+        assert!(values.iter().all(|value| (0.0..1.0).contains(value)));
+        let mean = values.iter().sum::<f32>() / values.len() as f32;
+        assert!((mean - 0.5).abs() < 0.02);
+    }
+}
@@ -4,7 +4,7 @@ use eframe::egui::Ui;
 pub fn debug_tab_ui(ui: &mut Ui) -> Option<ServerRequest> {
     let mut request = None;
 
-    ui.columns(4, |ui| {
+    ui.columns(7, |ui| {
         if ui[0].button("Capture frame").clicked() {
             request = Some(ServerRequest::CaptureFrame);
         }
@@ -20,6 +20,24 @@ pub fn debug_tab_ui(ui: &mut Ui) -> Option<ServerRequest> {
         if ui[3].button("Stop recording").clicked() {
             request = Some(ServerRequest::StopRecording);
         }
+
+        if ui[4].button("Recenter").clicked() {
+            request = Some(ServerRequest::Recenter);
+        }
+
+        if ui[5]
+            .button("Fast reconnect")
+            .on_hover_text(
+                "Applies codec changes by restarting the encoder. Not supported on Linux",
+            )
+            .clicked()
+        {
+            request = Some(ServerRequest::FastReconnect);
+        }
+
+        if ui[6].button("Benchmark").clicked() {
+            request = Some(ServerRequest::StartBenchmark);
+        }
     });
 
     request
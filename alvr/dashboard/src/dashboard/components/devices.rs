@@ -1,5 +1,6 @@
 use crate::dashboard::ServerRequest;
-use alvr_common::ConnectionState;
+use alvr_common::{ConnectionPhase, ConnectionState};
+use alvr_events::ConnectionTransitionEvent;
 use alvr_gui_common::theme::{self, log_colors};
 use alvr_packets::ClientListAction;
 use alvr_session::{ClientConnectionConfig, SessionConfig};
@@ -8,6 +9,7 @@ use eframe::{
     emath::{Align, Align2},
     epaint::Color32,
 };
+use std::collections::HashMap;
 
 struct EditPopupState {
     new_devices: bool,
@@ -19,6 +21,8 @@ pub struct DevicesTab {
     new_devices: Option<Vec<(String, ClientConnectionConfig)>>,
     trusted_devices: Option<Vec<(String, ClientConnectionConfig)>>,
     edit_popup_state: Option<EditPopupState>,
+    // Shown instead of the connection state while retrying
+    connection_phases: HashMap<String, ConnectionPhase>,
 }
 
 impl DevicesTab {
@@ -27,9 +31,14 @@ impl DevicesTab {
             new_devices: None,
             trusted_devices: None,
             edit_popup_state: None,
+            connection_phases: HashMap::new(),
         }
     }
 
+    pub fn update_connection_phase(&mut self, event: ConnectionTransitionEvent) {
+        self.connection_phases.insert(event.hostname, event.to);
+    }
+
     pub fn update_client_list(&mut self, session: &SessionConfig) {
         let (trusted_clients, untrusted_clients) =
             session
@@ -84,9 +93,12 @@ impl DevicesTab {
             ui.add_space(10.0);
 
             if let Some(clients) = &mut self.trusted_devices {
-                if let Some(request) =
-                    trusted_clients_section(ui, clients, &mut self.edit_popup_state)
-                {
+                if let Some(request) = trusted_clients_section(
+                    ui,
+                    clients,
+                    &self.connection_phases,
+                    &mut self.edit_popup_state,
+                ) {
                     requests.push(request);
                 }
             }
@@ -191,6 +203,7 @@ fn new_clients_section(
 fn trusted_clients_section(
     ui: &mut Ui,
     clients: &mut [(String, ClientConnectionConfig)],
+    connection_phases: &HashMap<String, ConnectionPhase>,
     edit_popup_state: &mut Option<EditPopupState>,
 ) -> Option<ServerRequest> {
     let mut request = None;
@@ -217,25 +230,40 @@ fn trusted_clients_section(
                                     ui.horizontal(|ui| {
                                         ui.with_layout(
                                             Layout::right_to_left(Align::Center),
-                                            |ui| match data.connection_state {
-                                                ConnectionState::Disconnected => {
-                                                    ui.colored_label(Color32::GRAY, "Disconnected")
-                                                }
-                                                ConnectionState::Connecting => ui.colored_label(
-                                                    log_colors::WARNING_LIGHT,
-                                                    "Connecting",
-                                                ),
-                                                ConnectionState::Connected => {
-                                                    ui.colored_label(theme::OK_GREEN, "Connected")
-                                                }
-                                                ConnectionState::Streaming => {
-                                                    ui.colored_label(theme::OK_GREEN, "Streaming")
-                                                }
-                                                ConnectionState::Disconnecting { .. } => ui
+                                            |ui| match connection_phases.get(hostname.as_str()) {
+                                                Some(ConnectionPhase::Reconnecting) => ui
                                                     .colored_label(
                                                         log_colors::WARNING_LIGHT,
-                                                        "Disconnecting",
+                                                        "Reconnecting",
+                                                    ),
+                                                Some(ConnectionPhase::Error(e)) => ui
+                                                    .colored_label(log_colors::ERROR_LIGHT, "Error")
+                                                    .on_hover_text(e),
+                                                _ => match data.connection_state {
+                                                    ConnectionState::Disconnected => ui
+                                                        .colored_label(
+                                                            Color32::GRAY,
+                                                            "Disconnected",
+                                                        ),
+                                                    ConnectionState::Connecting => ui
+                                                        .colored_label(
+                                                            log_colors::WARNING_LIGHT,
+                                                            "Connecting",
+                                                        ),
+                                                    ConnectionState::Connected => ui.colored_label(
+                                                        theme::OK_GREEN,
+                                                        "Connected",
+                                                    ),
+                                                    ConnectionState::Streaming => ui.colored_label(
+                                                        theme::OK_GREEN,
+                                                        "Streaming",
                                                     ),
+                                                    ConnectionState::Disconnecting { .. } => ui
+                                                        .colored_label(
+                                                            log_colors::WARNING_LIGHT,
+                                                            "Disconnecting",
+                                                        ),
+                                                },
                                             },
                                         );
                                     });
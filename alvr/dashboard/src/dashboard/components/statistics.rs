@@ -270,6 +270,7 @@ impl StatisticsTab {
                 let mut decoder_latency_limiter = Vec::with_capacity(GRAPH_HISTORY_SIZE);
                 let mut network_latency_limiter = Vec::with_capacity(GRAPH_HISTORY_SIZE);
                 let mut encoder_latency_limiter = Vec::with_capacity(GRAPH_HISTORY_SIZE);
+                let mut congestion_controller = Vec::with_capacity(GRAPH_HISTORY_SIZE);
                 let mut manual_max = Vec::with_capacity(GRAPH_HISTORY_SIZE);
                 let mut manual_min = Vec::with_capacity(GRAPH_HISTORY_SIZE);
                 let mut requested = Vec::with_capacity(GRAPH_HISTORY_SIZE);
@@ -289,6 +290,9 @@ impl StatisticsTab {
                     if let Some(value) = nom_br.encoder_latency_limiter_bps {
                         encoder_latency_limiter.push(to_screen_trans * pos2(i as f32, value / 1e6))
                     }
+                    if let Some(value) = nom_br.congestion_controller_bps {
+                        congestion_controller.push(to_screen_trans * pos2(i as f32, value / 1e6))
+                    }
                     if let Some(value) = nom_br.manual_max_bps {
                         manual_max.push(to_screen_trans * pos2(i as f32, value / 1e6))
                     }
@@ -305,6 +309,7 @@ impl StatisticsTab {
                 draw_lines(painter, encoder_latency_limiter, graph_colors::TRANSCODE);
                 draw_lines(painter, network_latency_limiter, graph_colors::NETWORK);
                 draw_lines(painter, decoder_latency_limiter, graph_colors::TRANSCODE);
+                draw_lines(painter, congestion_controller, graph_colors::NETWORK);
                 draw_lines(painter, manual_max, graph_colors::RENDER);
                 draw_lines(painter, manual_min, graph_colors::RENDER);
                 draw_lines(painter, requested, theme::OK_GREEN);
@@ -348,6 +353,12 @@ impl StatisticsTab {
                     n.decoder_latency_limiter_bps,
                     graph_colors::TRANSCODE,
                 );
+                maybe_label(
+                    ui,
+                    "Congestion controller",
+                    n.congestion_controller_bps,
+                    graph_colors::NETWORK,
+                );
                 maybe_label(ui, "Manual max", n.manual_max_bps, graph_colors::RENDER);
                 maybe_label(ui, "Manual min", n.manual_min_bps, graph_colors::RENDER);
                 maybe_label(ui, "Requested", Some(n.requested_bps), theme::OK_GREEN);
@@ -395,6 +406,21 @@ impl StatisticsTab {
                 statistics.packets_lost_total, statistics.packets_lost_per_sec
             ));
 
+            ui[0].label("Packet pacing interval:");
+            ui[1].label(
+                &if let Some(interval_us) = statistics.packet_pacing_interval_us {
+                    format!("{interval_us} µs")
+                } else {
+                    "Disabled".into()
+                },
+            );
+
+            ui[0].label("Decoder parallelism:");
+            ui[1].label(&format!("{} sessions", statistics.decoder_parallelism));
+
+            ui[0].label("Decoder dropped frames:");
+            ui[1].label(&format!("{} frames", statistics.decoder_dropped_frames));
+
             ui[0].label("Client FPS:");
             ui[1].label(&format!("{} FPS", statistics.client_fps));
 
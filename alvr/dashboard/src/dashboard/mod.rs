@@ -179,6 +179,9 @@ impl eframe::App for Dashboard {
                 }
                 EventType::ServerRequestsSelfRestart => self.restart_steamvr(&mut requests),
                 EventType::AudioDevices(list) => self.settings_tab.update_audio_devices(list),
+                EventType::ConnectionTransition(event) => {
+                    self.connections_tab.update_connection_phase(event)
+                }
                 #[cfg(not(target_arch = "wasm32"))]
                 EventType::DriversList(list) => self.installation_tab.update_drivers(list),
                 _ => (),
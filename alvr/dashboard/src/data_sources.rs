@@ -181,8 +181,11 @@ impl DataSources {
                                 }
                                 ServerRequest::CaptureFrame
                                 | ServerRequest::InsertIdr
+                                | ServerRequest::Recenter
+                                | ServerRequest::FastReconnect
                                 | ServerRequest::StartRecording
-                                | ServerRequest::StopRecording => {
+                                | ServerRequest::StopRecording
+                                | ServerRequest::StartBenchmark => {
                                     warn!("Cannot perform action, streamer (SteamVR) is not connected.")
                                 }
                                 ServerRequest::RestartSteamvr | ServerRequest::ShutdownSteamvr => {
@@ -1,4 +1,4 @@
-use alvr_common::{info, DeviceMotion, LogEntry, Pose};
+use alvr_common::{info, ConnectionPhase, DeviceMotion, LogEntry, Pose};
 use alvr_packets::{AudioDevicesList, ButtonValue};
 use alvr_session::SessionConfig;
 use serde::{Deserialize, Serialize};
@@ -20,6 +20,9 @@ pub struct StatisticsSummary {
     pub server_fps: u32,
     pub battery_hmd: u32,
     pub hmd_plugged: bool,
+    pub packet_pacing_interval_us: Option<u32>,
+    pub decoder_parallelism: u32,
+    pub decoder_dropped_frames: u32,
 }
 
 // Bitrate statistics minus the empirical output value
@@ -29,6 +32,7 @@ pub struct NominalBitrateStats {
     pub decoder_latency_limiter_bps: Option<f32>,
     pub network_latency_limiter_bps: Option<f32>,
     pub encoder_latency_limiter_bps: Option<f32>,
+    pub congestion_controller_bps: Option<f32>,
     pub manual_max_bps: Option<f32>,
     pub manual_min_bps: Option<f32>,
     pub requested_bps: f32,
@@ -75,6 +79,13 @@ pub struct HapticsEvent {
     pub amplitude: f32,
 }
 
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ConnectionTransitionEvent {
+    pub hostname: String,
+    pub from: ConnectionPhase,
+    pub to: ConnectionPhase,
+}
+
 #[derive(Serialize, Deserialize, Clone, Debug)]
 #[serde(tag = "id", content = "data")]
 pub enum EventType {
@@ -87,6 +98,7 @@ pub enum EventType {
     Haptics(HapticsEvent),
     AudioDevices(AudioDevicesList),
     DriversList(Vec<PathBuf>),
+    ConnectionTransition(ConnectionTransitionEvent),
     ServerRequestsSelfRestart,
 }
 
@@ -1,9 +1,12 @@
 use alvr_common::{
-    anyhow::Result,
+    anyhow::{anyhow, Result},
     glam::{UVec2, Vec2},
     ConnectionState, DeviceMotion, Fov, LogEntry, LogSeverity, Pose, ToAny,
 };
-use alvr_session::{CodecType, SessionConfig, Settings};
+use alvr_session::{
+    CodecType, EyeCalibrationConfig, FoveatedEncodingConfig, ReferenceStructure, SessionConfig,
+    Settings,
+};
 use serde::{Deserialize, Serialize};
 use serde_json as json;
 use std::{
@@ -20,6 +23,9 @@ pub const AUDIO: u16 = 2;
 pub const VIDEO: u16 = 3;
 pub const STATISTICS: u16 = 4;
 
+// Bound for the user data attached to each video frame, which is sent with every video packet
+pub const MAX_FRAME_USER_DATA_SIZE: usize = 256;
+
 // todo: use simple string
 #[derive(Serialize, Deserialize, Clone)]
 pub struct VideoStreamingCapabilitiesLegacy {
@@ -38,6 +44,79 @@ pub struct VideoStreamingCapabilities {
     pub encoder_high_profile: bool,
     pub encoder_10_bits: bool,
     pub encoder_av1: bool,
+    // The client can follow foveated encoding changes during the stream
+    pub supports_runtime_foveation: bool,
+    // Limited by both the decoder and the display swapchain formats
+    pub max_bit_depth: u8,
+    pub supports_separate_audio_socket: bool,
+}
+
+// Encode resolutions are multiples of 32, like the resolutions computed from the settings
+const ENCODE_RESOLUTION_ALIGNMENT: u32 = 32;
+
+// Scales the view resolution down to fit within the maximum encode resolution, keeping the aspect
+// ratio. The client upscales the decoded frames back to the view resolution
+pub fn cap_encode_resolution(view_resolution: UVec2, max_resolution: UVec2) -> UVec2 {
+    if view_resolution.cmple(max_resolution).all() {
+        return view_resolution;
+    }
+
+    // The scale is the ratio of the most constrained axis, in integers to avoid rounding errors
+    let (numerator, denominator) = if max_resolution.x as u64 * view_resolution.y as u64
+        <= max_resolution.y as u64 * view_resolution.x as u64
+    {
+        (max_resolution.x as u64, view_resolution.x as u64)
+    } else {
+        (max_resolution.y as u64, view_resolution.y as u64)
+    };
+    let scale = |value: u32| {
+        let scaled = (value as u64 * numerator / denominator) as u32;
+
+        u32::max(
+            scaled / ENCODE_RESOLUTION_ALIGNMENT * ENCODE_RESOLUTION_ALIGNMENT,
+            ENCODE_RESOLUTION_ALIGNMENT,
+        )
+    };
+
+    UVec2::new(scale(view_resolution.x), scale(view_resolution.y))
+}
+
+// Bit depth of the video stream, 8 or 10. It's the minimum of what is requested and what is
+// supported by each side
+pub fn negotiate_bit_depth(requested: u8, server_max: u8, client_max: u8) -> u8 {
+    if requested.min(server_max).min(client_max) >= 10 {
+        10
+    } else {
+        8
+    }
+}
+
+// The refresh rates enumerated by the runtimes are not always round numbers, like 72.00001 or 119.99
+const REFRESH_RATE_TOLERANCE: f32 = 0.5;
+
+// Returns the supported refresh rate matching the requested one, or an error if the display does
+// not support it
+pub fn validate_refresh_rate(requested: f32, supported: &[f32]) -> Result<f32> {
+    supported
+        .iter()
+        .copied()
+        .find(|rate| (rate - requested).abs() <= REFRESH_RATE_TOLERANCE)
+        .ok_or_else(|| {
+            anyhow!("Refresh rate {requested}Hz not supported. Supported rates: {supported:?}")
+        })
+}
+
+// An unsupported refresh rate is clamped to the nearest supported one. It's an error only if the
+// client reported no valid refresh rate
+pub fn negotiate_refresh_rate(requested: f32, supported: &[f32]) -> Result<f32> {
+    validate_refresh_rate(requested, supported).or_else(|_| {
+        supported
+            .iter()
+            .copied()
+            .filter(|rate| rate.is_finite() && *rate > 0.0)
+            .min_by(|a, b| (a - requested).abs().total_cmp(&(b - requested).abs()))
+            .ok_or_else(|| anyhow!("The client did not report any supported refresh rate"))
+    })
 }
 
 // Nasty workaround to make the packet extensible, pushing the limits of protocol compatibility
@@ -82,6 +161,8 @@ pub fn decode_video_streaming_capabilities(
     let caps_json =
         json::from_str::<json::Value>(&String::from_utf8(json_bytes)?).unwrap_or(json::Value::Null);
 
+    let encoder_10_bits = caps_json["encoder_10_bits"].as_bool().unwrap_or(true);
+
     Ok(VideoStreamingCapabilities {
         default_view_resolution: legacy.default_view_resolution,
         supported_refresh_rates,
@@ -90,8 +171,19 @@ pub fn decode_video_streaming_capabilities(
             .as_bool()
             .unwrap_or(true),
         encoder_high_profile: caps_json["encoder_high_profile"].as_bool().unwrap_or(true),
-        encoder_10_bits: caps_json["encoder_10_bits"].as_bool().unwrap_or(true),
+        encoder_10_bits,
         encoder_av1: caps_json["encoder_av1"].as_bool().unwrap_or(true),
+        supports_runtime_foveation: caps_json["supports_runtime_foveation"]
+            .as_bool()
+            .unwrap_or(false),
+        // Older clients display 10 bit streams whenever they can decode them
+        max_bit_depth: caps_json["max_bit_depth"]
+            .as_u64()
+            .map(|depth| depth as u8)
+            .unwrap_or(if encoder_10_bits { 10 } else { 8 }),
+        supports_separate_audio_socket: caps_json["supports_separate_audio_socket"]
+            .as_bool()
+            .unwrap_or(false),
     })
 }
 
@@ -109,10 +201,22 @@ pub enum ClientConnectionResult {
 // Note: not a network packet
 #[derive(Serialize, Deserialize, Clone)]
 pub struct NegotiatedStreamingConfig {
+    // Resolution of the encoded views
     pub view_resolution: UVec2,
+    // Resolution of the views rendered by the client, larger than the encoded one if the encode
+    // resolution is capped
+    pub upscaled_view_resolution: UVec2,
     pub refresh_rate_hint: f32,
     pub game_audio_sample_rate: u32,
+    // Initial layout of the game audio, each audio packet carries the current one
+    pub game_audio_channels_count: u16,
     pub enable_foveated_encoding: bool,
+    // Fragment size used by both peers to shard and reconstruct stream packets
+    pub packet_size: usize,
+    pub bit_depth: u8,
+    pub reference_structure: ReferenceStructure,
+    // Port of the socket dedicated to audio, None if audio shares the stream socket
+    pub audio_port: Option<u16>,
 }
 
 #[derive(Serialize, Deserialize)]
@@ -141,20 +245,43 @@ pub fn decode_stream_config(
     let negotiated_json = json::from_str::<json::Value>(&packet.negotiated)?;
 
     let view_resolution = json::from_value(negotiated_json["view_resolution"].clone())?;
+    let upscaled_view_resolution =
+        json::from_value(negotiated_json["upscaled_view_resolution"].clone())
+            .unwrap_or(view_resolution);
     let refresh_rate_hint = json::from_value(negotiated_json["refresh_rate_hint"].clone())?;
     let game_audio_sample_rate =
         json::from_value(negotiated_json["game_audio_sample_rate"].clone())?;
+    let game_audio_channels_count =
+        json::from_value(negotiated_json["game_audio_channels_count"].clone()).unwrap_or(2);
     let enable_foveated_encoding =
         json::from_value(negotiated_json["enable_foveated_encoding"].clone())
             .unwrap_or_else(|_| settings.video.foveated_encoding.enabled());
+    let packet_size = json::from_value(negotiated_json["packet_size"].clone())
+        .unwrap_or(settings.connection.packet_size as _);
+    let bit_depth = json::from_value(negotiated_json["bit_depth"].clone()).unwrap_or(
+        if settings.video.encoder_config.use_10bit {
+            10
+        } else {
+            8
+        },
+    );
+    let reference_structure = json::from_value(negotiated_json["reference_structure"].clone())
+        .unwrap_or(ReferenceStructure::POnly);
+    let audio_port = json::from_value(negotiated_json["audio_port"].clone()).unwrap_or(None);
 
     Ok((
         settings,
         NegotiatedStreamingConfig {
             view_resolution,
+            upscaled_view_resolution,
             refresh_rate_hint,
             game_audio_sample_rate,
+            game_audio_channels_count,
             enable_foveated_encoding,
+            packet_size,
+            bit_depth,
+            reference_structure,
+            audio_port,
         },
     ))
 }
@@ -172,6 +299,13 @@ pub enum ServerControlPacket {
     Restarting,
     KeepAlive,
     ServerPredictionAverage(Duration), // todo: remove
+    EyeCalibration(Option<EyeCalibrationConfig>),
+    // Foveated encoding used by video frames tagged with the epoch
+    FoveatedEncoding {
+        epoch: u32,
+        config: Option<FoveatedEncodingConfig>,
+    },
+    StreamPaused(bool), // No frames are sent while paused
     Reserved(String),
     ReservedBuffer(Vec<u8>),
 }
@@ -229,6 +363,8 @@ pub enum ClientControlPacket {
     Buttons(Vec<ButtonEntry>),
     ActiveInteractionProfile { device_id: u64, profile_id: u64 },
     Log { level: LogSeverity, message: String },
+    RequestStreamPause(bool),
+    HeadsetPresence(bool), // The stream can be paused while the headset is removed
     Reserved(String),
     ReservedBuffer(Vec<u8>),
 }
@@ -241,10 +377,20 @@ pub struct FaceData {
     pub htc_lip_expression: Option<Vec<f32>>, // issue: Serialize does not support [f32; 37]
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, Clone)]
 pub struct VideoPacketHeader {
     pub timestamp: Duration,
     pub is_idr: bool,
+    // Count of the encoded frames since the last IDR, which has index 0. Together with the
+    // reference structure it gives the references of the frame
+    pub frame_index: u32,
+    // Each slice of the frame is sent in its own packet, with the same header otherwise
+    pub slice_index: u32,
+    pub slices_count: u32,
+    // Identifies the foveated encoding of the frame. The epoch 0 corresponds to the negotiated one
+    pub foveation_epoch: u32,
+    // Set by the server application, delivered together with the decoded frame
+    pub user_data: Vec<u8>,
 }
 
 // Note: face_data does not respect target_timestamp.
@@ -331,6 +477,9 @@ pub struct ClientStatistics {
     pub rendering: Duration,
     pub vsync_queue: Duration,
     pub total_pipeline_latency: Duration,
+    pub decoder_parallelism: u32, // decoder sessions used when the frame was dequeued
+    pub decoder_dropped_frames: u32, // dropped by the decode queue since the decoder creation
+    pub video_packets_lost: u32,  // lost by the network since the stream start
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
@@ -358,8 +507,13 @@ pub enum ServerRequest {
     GetAudioDevices,
     CaptureFrame,
     InsertIdr,
+    Recenter,
+    // Apply the changed stream settings without SteamVR restart and new handshake, if possible
+    FastReconnect,
     StartRecording,
     StopRecording,
+    // Sweep the stream parameters of the benchmark settings and log the measurements
+    StartBenchmark,
     FirewallRules(FirewallRulesAction),
     RegisterAlvrDriver,
     UnregisterDriver(PathBuf),
@@ -375,3 +529,104 @@ pub struct ViewParams {
     pub pose: Pose,
     pub fov: Fov,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn negotiated_config() -> NegotiatedStreamingConfig {
+        NegotiatedStreamingConfig {
+            view_resolution: UVec2::new(1920, 1824),
+            upscaled_view_resolution: UVec2::new(1920, 1824),
+            refresh_rate_hint: 90.0,
+            game_audio_sample_rate: 48000,
+            game_audio_channels_count: 2,
+            enable_foveated_encoding: false,
+            packet_size: 1400,
+            bit_depth: 8,
+            reference_structure: ReferenceStructure::POnly,
+            audio_port: None,
+        }
+    }
+
+    #[test]
+    fn test_negotiated_bit_depth_is_minimum_of_capabilities() {
+        for requested in [8, 10] {
+            for server_max in [8, 10] {
+                for client_max in [8, 10] {
+                    assert_eq!(
+                        negotiate_bit_depth(requested, server_max, client_max),
+                        requested.min(server_max).min(client_max)
+                    );
+                }
+            }
+        }
+
+        // Unsupported depths fall back to 8 bits
+        assert_eq!(negotiate_bit_depth(10, 12, 9), 8);
+        assert_eq!(negotiate_bit_depth(12, 12, 12), 10);
+    }
+
+    #[test]
+    fn test_encode_resolution_is_capped_and_upscaled_on_receive() {
+        let max_resolution = UVec2::new(2048, 2048);
+
+        // A panel resolution above the cap is scaled down keeping the aspect ratio
+        let view_resolution = UVec2::new(3840, 3552);
+        let encode_resolution = cap_encode_resolution(view_resolution, max_resolution);
+        assert_eq!(encode_resolution, UVec2::new(2048, 1888));
+        assert!(encode_resolution.cmple(max_resolution).all());
+        assert!(encode_resolution % 32 == UVec2::ZERO);
+
+        // Resolutions within the cap are not changed
+        assert_eq!(
+            cap_encode_resolution(UVec2::new(1920, 1824), max_resolution),
+            UVec2::new(1920, 1824)
+        );
+        assert_eq!(
+            cap_encode_resolution(view_resolution, UVec2::new(16, 16)),
+            UVec2::new(32, 32)
+        );
+
+        // The client decodes at the encode resolution and renders at the view resolution
+        let session = SessionConfig::default();
+        let mut config = negotiated_config();
+        config.view_resolution = encode_resolution;
+        config.upscaled_view_resolution = view_resolution;
+        let packet = encode_stream_config(&session, &config).unwrap();
+        let (_, negotiated) = decode_stream_config(&packet).unwrap();
+        assert_eq!(negotiated.view_resolution, encode_resolution);
+        assert_eq!(negotiated.upscaled_view_resolution, view_resolution);
+
+        // Older servers don't cap the encode resolution
+        let mut negotiated_json = json::from_str::<json::Value>(&packet.negotiated).unwrap();
+        negotiated_json
+            .as_object_mut()
+            .unwrap()
+            .remove("upscaled_view_resolution");
+        let packet = StreamConfigPacket {
+            negotiated: negotiated_json.to_string(),
+            ..packet
+        };
+        let (_, negotiated) = decode_stream_config(&packet).unwrap();
+        assert_eq!(negotiated.upscaled_view_resolution, encode_resolution);
+    }
+
+    #[test]
+    fn test_refresh_rate_is_validated_against_the_display() {
+        let supported = [72.00001, 80.0, 90.0, 119.99];
+
+        assert_eq!(validate_refresh_rate(90.0, &supported).unwrap(), 90.0);
+        assert_eq!(validate_refresh_rate(72.0, &supported).unwrap(), 72.00001);
+        assert_eq!(validate_refresh_rate(120.0, &supported).unwrap(), 119.99);
+        assert!(validate_refresh_rate(60.0, &supported).is_err());
+        assert!(validate_refresh_rate(85.0, &supported).is_err());
+
+        // The negotiation clamps to the nearest supported rate
+        assert_eq!(negotiate_refresh_rate(60.0, &supported).unwrap(), 72.00001);
+        assert_eq!(negotiate_refresh_rate(144.0, &supported).unwrap(), 119.99);
+        assert_eq!(negotiate_refresh_rate(88.0, &supported).unwrap(), 90.0);
+        assert!(negotiate_refresh_rate(90.0, &[]).is_err());
+        assert!(negotiate_refresh_rate(90.0, &[f32::NAN, 0.0]).is_err());
+    }
+}
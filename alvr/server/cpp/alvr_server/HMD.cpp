@@ -70,6 +70,7 @@ Hmd::Hmd()
 Hmd::~Hmd() {
     //ShutdownRuntime();
 
+    std::lock_guard<std::mutex> lock(m_encoderMutex);
     if (m_encoder) {
         Debug("Hmd::~Hmd(): Stopping encoder...\n");
         m_encoder->Stop();
@@ -242,31 +243,77 @@ void Hmd::StartStreaming() {
 
     // Spin up a separate thread to handle the overlapped encoding/transmit step.
     if (IsHMD()) {
-#ifdef _WIN32
-        m_encoder = std::make_shared<CEncoder>();
-        try {
-            m_encoder->Initialize(m_D3DRender);
-        } catch (Exception e) {
-            Error("Your GPU does not meet the requirements for video encoding. %s %s\n%s %s\n",
-                  "If you get this error after changing some settings, you can revert them by",
-                  "deleting the file \"session.json\" in the installation folder.",
-                  "Failed to initialize CEncoder:",
-                  e.what());
+        std::lock_guard<std::mutex> lock(m_encoderMutex);
+        if (m_encoderStopped) {
+            return;
         }
-        m_encoder->Start();
 
-        m_directModeComponent->SetEncoder(m_encoder);
+        CreateEncoder();
+    }
+
+    m_streamComponentsInitialized = true;
+}
+
+void Hmd::CreateEncoder() {
+#ifdef _WIN32
+    m_encoder = std::make_shared<CEncoder>();
+    try {
+        m_encoder->Initialize(m_D3DRender);
+    } catch (Exception e) {
+        Error("Your GPU does not meet the requirements for video encoding. %s %s\n%s %s\n",
+              "If you get this error after changing some settings, you can revert them by",
+              "deleting the file \"session.json\" in the installation folder.",
+              "Failed to initialize CEncoder:",
+              e.what());
+    }
+    m_encoder->Start();
+
+    m_directModeComponent->SetEncoder(m_encoder);
 
 #elif __APPLE__
-        m_encoder = std::make_shared<CEncoder>();
+    m_encoder = std::make_shared<CEncoder>();
 #else
-        m_encoder = std::make_shared<CEncoder>(m_poseHistory);
-        m_encoder->Start();
+    m_encoder = std::make_shared<CEncoder>(m_poseHistory);
+    m_encoder->Start();
 #endif
-        m_encoder->OnStreamStart();
+    m_encoder->OnStreamStart();
+}
+
+void Hmd::RestartEncoder() {
+    if (!m_streamComponentsInitialized || !IsHMD()) {
+        return;
     }
 
-    m_streamComponentsInitialized = true;
+#ifdef _WIN32
+    std::lock_guard<std::mutex> lock(m_encoderMutex);
+    if (m_encoderStopped) {
+        return;
+    }
+
+    // Stop feeding frames to the old encoder before shutting it down
+    m_directModeComponent->SetEncoder(nullptr);
+    m_encoder->Stop();
+    m_encoder.reset();
+
+    CreateEncoder();
+#else
+    // The encoder is bound to the connection with the Vulkan layer, which cannot be reestablished
+    Warn("Restarting the encoder during the stream is not supported on this platform\n");
+#endif
+}
+
+void Hmd::StopEncoder() {
+    std::lock_guard<std::mutex> lock(m_encoderMutex);
+    m_encoderStopped = true;
+    if (!m_encoder) {
+        return;
+    }
+
+#ifdef _WIN32
+    m_directModeComponent->SetEncoder(nullptr);
+#endif
+    m_encoder->Stop();
+    m_encoder.reset();
 }
 
 void Hmd::StopStreaming() { vr::VRDriverInput()->UpdateBooleanComponent(m_proximity, false, 0.0); }
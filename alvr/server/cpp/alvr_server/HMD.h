@@ -4,6 +4,7 @@
 #include "TrackedDevice.h"
 #include "openvr_driver.h"
 #include <memory>
+#include <mutex>
 #ifdef _WIN32
 #include "platform/win32/OvrDirectModeComponent.h"
 #endif
@@ -37,6 +38,11 @@ class Hmd : public TrackedDevice, public vr::ITrackedDeviceServerDriver, vr::IVR
 
     void StopStreaming();
 
+    // Recreate the encoder to apply settings that change the encoded frame size
+    void RestartEncoder();
+    // When the driver shuts down. The stream cannot be resumed
+    void StopEncoder();
+
     void SetViewsConfig(FfiViewsConfig config);
 
     vr::ETrackedDeviceClass GetDeviceClass() const { return m_deviceClass; }
@@ -61,10 +67,16 @@ class Hmd : public TrackedDevice, public vr::ITrackedDeviceServerDriver, vr::IVR
     std::shared_ptr<PoseHistory> m_poseHistory;
 
   private:
+    void CreateEncoder();
+
     FfiViewsConfig views_config;
 
     bool m_baseComponentsInitialized;
     bool m_streamComponentsInitialized;
+
+    // Held while the encoder is replaced or stopped, which can happen from the shutdown thread
+    std::mutex m_encoderMutex;
+    bool m_encoderStopped = false;
     vr::ETrackedDeviceClass m_deviceClass;
 
     vr::HmdMatrix34_t m_eyeToHeadLeft;
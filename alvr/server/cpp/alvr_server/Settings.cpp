@@ -39,6 +39,8 @@ void Settings::Load() {
         m_refreshRate = (int)config.get("refresh_rate").get<int64_t>();
         m_renderWidth = config.get("eye_resolution_width").get<int64_t>() * 2;
         m_renderHeight = config.get("eye_resolution_height").get<int64_t>();
+        m_encodeWidth = m_renderWidth;
+        m_encodeHeight = m_renderHeight;
         m_recommendedTargetWidth = config.get("target_eye_resolution_width").get<int64_t>() * 2;
         m_recommendedTargetHeight = config.get("target_eye_resolution_height").get<int64_t>();
         m_nAdapterIndex = (int32_t)config.get("adapter_index").get<int64_t>();
@@ -65,6 +67,13 @@ void Settings::Load() {
         m_fillerData = config.get("filler_data").get<bool>();
         m_entropyCoding = (uint32_t)config.get("entropy_coding").get<int64_t>();
         m_use10bitEncoder = config.get("use_10bit_encoder").get<bool>();
+        m_temporalLayers = (uint32_t)config.get("temporal_layers").get<int64_t>();
+        m_slicesCount = (uint32_t)config.get("slices_count").get<int64_t>();
+        m_qpDeltaMap.clear();
+        for (auto &delta : config.get("qp_delta_map").get<picojson::array>()) {
+            m_qpDeltaMap.push_back((int8_t)delta.get<int64_t>());
+        }
+        m_qpDeltaMapColumns = (uint32_t)config.get("qp_delta_map_columns").get<int64_t>();
         m_useFullRangeEncoding = config.get("use_full_range_encoding").get<bool>();
         m_encodingGamma = config.get("encoding_gamma").get<double>();
         m_enableHdr = config.get("enable_hdr").get<bool>();
@@ -79,6 +88,7 @@ void Settings::Load() {
         m_amdEncoderQualityPreset =
             (uint32_t)config.get("amd_encoder_quality_preset").get<int64_t>();
         m_amdBitrateCorruptionFix = (bool)config.get("amd_bitrate_corruption_fix").get<bool>();
+        m_encoderQualityFeedback = config.get("encoder_quality_feedback").get<bool>();
         m_nvencQualityPreset = (uint32_t)config.get("nvenc_quality_preset").get<int64_t>();
         m_force_sw_encoding = config.get("force_sw_encoding").get<bool>();
         m_swThreadCount = (int32_t)config.get("sw_thread_count").get<int64_t>();
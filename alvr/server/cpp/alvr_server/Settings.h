@@ -1,7 +1,9 @@
 #pragma once
 
 #include "ALVR-common/packet_types.h"
+#include <atomic>
 #include <string>
+#include <vector>
 
 class Settings {
     static Settings m_Instance;
@@ -19,6 +21,9 @@ class Settings {
     int m_refreshRate;
     uint32_t m_renderWidth;
     uint32_t m_renderHeight;
+    // Size of the frames of the encoder. The compositor downscales the render size to it
+    uint32_t m_encodeWidth;
+    uint32_t m_encodeHeight;
     int32_t m_recommendedTargetWidth;
     int32_t m_recommendedTargetHeight;
     int32_t m_nAdapterIndex;
@@ -42,6 +47,11 @@ class Settings {
     int m_codec;
     int m_h264Profile;
     bool m_use10bitEncoder;
+    uint32_t m_temporalLayers;
+    uint32_t m_slicesCount;
+    // Per encoder block, empty if disabled
+    std::vector<int8_t> m_qpDeltaMap;
+    uint32_t m_qpDeltaMapColumns;
     bool m_useFullRangeEncoding;
     double m_encodingGamma;
     bool m_enableHdr;
@@ -55,6 +65,8 @@ class Settings {
     uint32_t m_preProcTor;
     uint32_t m_amdEncoderQualityPreset;
     bool m_amdBitrateCorruptionFix;
+    // Report the PSNR and the SSIM of every frame
+    bool m_encoderQualityFeedback;
     uint32_t m_nvencQualityPreset;
     uint32_t m_rateControlMode;
     bool m_fillerData;
@@ -62,6 +74,9 @@ class Settings {
     bool m_force_sw_encoding;
     uint32_t m_swThreadCount;
 
+    // Set by the benchmark during the stream, read by the compositor at every frame
+    std::atomic<bool> m_benchmarkTestPattern = false;
+
     uint32_t m_nvencTuningPreset;
     uint32_t m_nvencMultiPass;
     uint32_t m_nvencAdaptiveQuantizationMode;
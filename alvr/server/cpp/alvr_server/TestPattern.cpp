@@ -0,0 +1,72 @@
+#include "TestPattern.h"
+
+namespace {
+
+// Color bars at 75%: white, yellow, cyan, green, magenta, red, blue, black
+const uint8_t BARS[8][3] = {
+    {191, 191, 191},
+    {191, 191, 0},
+    {0, 191, 191},
+    {0, 191, 0},
+    {191, 0, 191},
+    {191, 0, 0},
+    {0, 0, 191},
+    {0, 0, 0},
+};
+
+const uint32_t CHECKER_SIZES[3] = {32, 8, 2};
+
+uint32_t Pack(uint8_t r, uint8_t g, uint8_t b, bool bgra) {
+    if (bgra) {
+        return b | (g << 8) | (r << 16) | (0xFFu << 24);
+    } else {
+        return r | (g << 8) | (b << 16) | (0xFFu << 24);
+    }
+}
+
+// Deterministic, so that the noise is the same at every connection
+uint8_t Noise(uint32_t x, uint32_t y) {
+    uint32_t h = x * 0x9E3779B1u ^ y * 0x85EBCA77u;
+    h ^= h >> 15;
+    h *= 0x2C1B3C6Du;
+    h ^= h >> 12;
+
+    return 64 + (h & 0x7F);
+}
+
+} // namespace
+
+std::vector<uint32_t>
+TestPattern::Generate(uint32_t width, uint32_t height, uint32_t rowLength, bool bgra) {
+    std::vector<uint32_t> pixels(rowLength * height);
+
+    for (uint32_t y = 0; y < height; y++) {
+        uint32_t band = y * 4 / height;
+
+        for (uint32_t column = 0; column < rowLength; column++) {
+            uint32_t x = column % width;
+
+            uint32_t pixel;
+            if (band == 0) {
+                auto bar = BARS[x * 8 / width];
+                pixel = Pack(bar[0], bar[1], bar[2], bgra);
+            } else if (band == 1) {
+                // Up and down, without a step at the period boundary
+                uint32_t ramp = x * 510 / width;
+                uint8_t value = ramp < 256 ? ramp : 510 - ramp;
+                pixel = Pack(value, value, value, bgra);
+            } else if (band == 2) {
+                uint32_t size = CHECKER_SIZES[x * 3 / width];
+                uint8_t value = ((x / size + y / size) % 2) ? 235 : 16;
+                pixel = Pack(value, value, value, bgra);
+            } else {
+                uint8_t value = Noise(x, y);
+                pixel = Pack(value, value, value, bgra);
+            }
+
+            pixels[y * rowLength + column] = pixel;
+        }
+    }
+
+    return pixels;
+}
@@ -0,0 +1,17 @@
+#pragma once
+
+#include <stdint.h>
+#include <vector>
+
+// Content streamed by the benchmark instead of the game, so that every point encodes the same
+// frames. The pattern is periodic horizontally and scrolls by a fixed step at every frame, for the
+// encoder to have motion to predict.
+namespace TestPattern {
+
+const uint32_t SCROLL_STEP = 4;
+
+// 8-bit sRGB pixels, row by row. Each row is rowLength pixels long, repeating the period of width
+// pixels, so a window of width pixels at any offset is a scrolled frame
+std::vector<uint32_t> Generate(uint32_t width, uint32_t height, uint32_t rowLength, bool bgra);
+
+} // namespace TestPattern
@@ -197,6 +197,13 @@ class DriverProvider : public vr::IServerTrackedDeviceProvider {
         return vr::VRInitError_None;
     }
     virtual void Cleanup() override {
+        // SteamVR can unload the driver without reporting that it is exiting. The encoder must be
+        // stopped while the HMD still exists
+        if (!shutdown_called) {
+            shutdown_called = true;
+            ShutdownRuntime();
+        }
+
         this->left_controller.reset();
         this->right_controller.reset();
         this->hmd.reset();
@@ -294,11 +301,13 @@ void (*ShutdownRuntime)();
 unsigned long long (*PathStringToHash)(const char *path);
 void (*ReportPresent)(unsigned long long timestamp_ns, unsigned long long offset_ns);
 void (*ReportComposed)(unsigned long long timestamp_ns, unsigned long long offset_ns);
+void (*ReportEncodeQuality)(double psnrDb, double ssim);
 FfiDynamicEncoderParams (*GetDynamicEncoderParams)();
 unsigned long long (*GetSerialNumber)(unsigned long long deviceID, char *outString);
 void (*SetOpenvrProps)(unsigned long long deviceID);
 void (*RegisterButtons)(unsigned long long deviceID);
 void (*WaitForVSync)();
+bool (*CheckVulkanDriver)(FfiVulkanDriverInfo info);
 
 void CppInit() {
     HookCrashHandler();
@@ -335,6 +344,12 @@ void DeinitializeStreaming() {
     }
 }
 
+void StopEncoder() {
+    if (g_driver_provider.hmd) {
+        g_driver_provider.hmd->StopEncoder();
+    }
+}
+
 void SendVSync() { vr::VRServerDriverHost()->VsyncEvent(0.0); }
 
 void RequestIDR() {
@@ -343,6 +358,18 @@ void RequestIDR() {
     }
 }
 
+void SetStreamPaused(bool paused) {
+    if (g_driver_provider.hmd && g_driver_provider.hmd->m_encoder) {
+        g_driver_provider.hmd->m_encoder->SetPaused(paused);
+    }
+}
+
+void SetEncodeFrameDivider(unsigned int divider) {
+    if (g_driver_provider.hmd && g_driver_provider.hmd->m_encoder) {
+        g_driver_provider.hmd->m_encoder->SetFrameDivider(divider);
+    }
+}
+
 void SetTracking(unsigned long long targetTimestampNs,
                  float controllerPoseTimeOffsetS,
                  const FfiDeviceMotion *deviceMotions,
@@ -419,6 +446,39 @@ void SetViewsConfig(FfiViewsConfig config) {
     }
 }
 
+void SetFoveatedEncoding(FfiFoveatedEncoding config) {
+    auto &settings = Settings::Instance();
+    settings.m_enableFoveatedEncoding = config.enable;
+    settings.m_foveationCenterSizeX = config.centerSizeX;
+    settings.m_foveationCenterSizeY = config.centerSizeY;
+    settings.m_foveationCenterShiftX = config.centerShiftX;
+    settings.m_foveationCenterShiftY = config.centerShiftY;
+    settings.m_foveationEdgeRatioX = config.edgeRatioX;
+    settings.m_foveationEdgeRatioY = config.edgeRatioY;
+
+    if (g_driver_provider.hmd) {
+        g_driver_provider.hmd->RestartEncoder();
+    }
+}
+
+void SetVideoCodec(int codec, unsigned int h264Profile) {
+    auto &settings = Settings::Instance();
+    settings.m_codec = codec;
+    settings.m_h264Profile = h264Profile;
+
+    if (g_driver_provider.hmd) {
+        g_driver_provider.hmd->RestartEncoder();
+    }
+}
+
+void SetEncodeResolution(unsigned int eyeWidth, unsigned int eyeHeight) {
+    auto &settings = Settings::Instance();
+    settings.m_encodeWidth = eyeWidth * 2;
+    settings.m_encodeHeight = eyeHeight;
+}
+
+void SetBenchmarkTestPattern(bool enabled) { Settings::Instance().m_benchmarkTestPattern = enabled; }
+
 void SetBattery(unsigned long long deviceID, float gauge_value, bool is_plugged) {
     auto device_it = g_driver_provider.tracked_devices.find(deviceID);
 
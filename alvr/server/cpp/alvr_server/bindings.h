@@ -65,6 +65,16 @@ struct FfiViewsConfig {
     float ipd_m;
 };
 
+struct FfiFoveatedEncoding {
+    unsigned int enable;
+    float centerSizeX;
+    float centerSizeY;
+    float centerShiftX;
+    float centerShiftY;
+    float edgeRatioX;
+    float edgeRatioY;
+};
+
 enum FfiButtonType {
     BUTTON_TYPE_BINARY,
     BUTTON_TYPE_SCALAR,
@@ -84,6 +94,17 @@ struct FfiDynamicEncoderParams {
     float framerate;
 };
 
+// Fields of VkPhysicalDeviceProperties and VkPhysicalDeviceDriverProperties
+struct FfiVulkanDriverInfo {
+    const char *deviceName;
+    const char *driverName;
+    const char *driverInfo;
+    unsigned int driverId;
+    unsigned int driverVersion;
+    unsigned int apiVersion;
+    unsigned char conformanceVersion[4];
+};
+
 extern "C" const unsigned char *FRAME_RENDER_VS_CSO_PTR;
 extern "C" unsigned int FRAME_RENDER_VS_CSO_LEN;
 extern "C" const unsigned char *FRAME_RENDER_PS_CSO_PTR;
@@ -128,18 +149,24 @@ extern "C" void (*ShutdownRuntime)();
 extern "C" unsigned long long (*PathStringToHash)(const char *path);
 extern "C" void (*ReportPresent)(unsigned long long timestamp_ns, unsigned long long offset_ns);
 extern "C" void (*ReportComposed)(unsigned long long timestamp_ns, unsigned long long offset_ns);
+// Negative if not measured by the encoder
+extern "C" void (*ReportEncodeQuality)(double psnrDb, double ssim);
 extern "C" FfiDynamicEncoderParams (*GetDynamicEncoderParams)();
 extern "C" unsigned long long (*GetSerialNumber)(unsigned long long deviceID, char *outString);
 extern "C" void (*SetOpenvrProps)(unsigned long long deviceID);
 extern "C" void (*RegisterButtons)(unsigned long long deviceID);
 extern "C" void (*WaitForVSync)();
+extern "C" bool (*CheckVulkanDriver)(FfiVulkanDriverInfo info);
 
 extern "C" void CppInit();
 extern "C" void *CppOpenvrEntryPoint(const char *pInterfaceName, int *pReturnCode);
 extern "C" void InitializeStreaming();
 extern "C" void DeinitializeStreaming();
+extern "C" void StopEncoder();
 extern "C" void SendVSync();
 extern "C" void RequestIDR();
+extern "C" void SetStreamPaused(bool paused);
+extern "C" void SetEncodeFrameDivider(unsigned int divider);
 extern "C" void SetTracking(unsigned long long targetTimestampNs,
                             float controllerPoseTimeOffsetS,
                             const FfiDeviceMotion *deviceMotions,
@@ -156,6 +183,11 @@ extern "C" void ShutdownSteamvr();
 extern "C" void SetOpenvrProperty(unsigned long long deviceID, FfiOpenvrProperty prop);
 extern "C" void RegisterButton(unsigned long long buttonID);
 extern "C" void SetViewsConfig(FfiViewsConfig config);
+extern "C" void SetFoveatedEncoding(FfiFoveatedEncoding config);
+extern "C" void SetVideoCodec(int codec, unsigned int h264Profile);
+// Applied at the next encoder restart
+extern "C" void SetEncodeResolution(unsigned int eyeWidth, unsigned int eyeHeight);
+extern "C" void SetBenchmarkTestPattern(bool enabled);
 extern "C" void SetBattery(unsigned long long deviceID, float gauge_value, bool is_plugged);
 extern "C" void SetButton(unsigned long long buttonID, FfiButtonValue value);
 
@@ -101,7 +101,7 @@ void av_logfn(void*, int level, const char* data, va_list va)
 
 } // namespace
 
-void CEncoder::GetFds(int client, int (*received_fds)[6]) {
+void CEncoder::GetFds(int client, int *received_fds, size_t count) {
     struct msghdr msg;
     struct cmsghdr *cmsg;
     union {
@@ -129,7 +129,11 @@ void CEncoder::GetFds(int client, int (*received_fds)[6]) {
 
     for (cmsg = CMSG_FIRSTHDR(&msg); cmsg != NULL; cmsg = CMSG_NXTHDR(&msg, cmsg)) {
         if (cmsg->cmsg_level == SOL_SOCKET && cmsg->cmsg_type == SCM_RIGHTS) {
-            memcpy(received_fds, CMSG_DATA(cmsg), sizeof(*received_fds));
+            size_t received_count = (cmsg->cmsg_len - CMSG_LEN(0)) / sizeof(int);
+            if (received_count != count) {
+                throw MakeException("expected %zu fds, received %zu", count, received_count);
+            }
+            memcpy(received_fds, CMSG_DATA(cmsg), count * sizeof(int));
             break;
         }
     }
@@ -195,7 +199,12 @@ void CEncoder::Run() {
     Info("CEncoder client connected, pid %d, cmdline %s\n", (int)init.source_pid, ifbuf2);
 
     try {
-        GetFds(client.fd, &m_fds);
+        uint32_t image_sets = init.layout == input_layout::separate_eyes ? 2 : 1;
+        size_t fds_count = 2 * init.num_images * image_sets;
+        if (fds_count > MAX_INPUT_FDS) {
+            throw MakeException("too many input images: %u", init.num_images);
+        }
+        GetFds(client.fd, m_fds, fds_count);
 
         m_connected = true;
 
@@ -203,7 +212,13 @@ void CEncoder::Run() {
 
       av_log_set_callback(av_logfn);
 
-      alvr::VkContext vk_ctx(init.device_uuid.data(), {});
+      // The compositor images and semaphores are imported from the Vulkan layer as fds
+      alvr::VkContext vk_ctx(init.device_uuid.data(), {
+          VK_KHR_EXTERNAL_MEMORY_FD_EXTENSION_NAME,
+          VK_KHR_EXTERNAL_SEMAPHORE_FD_EXTENSION_NAME,
+          VK_EXT_EXTERNAL_MEMORY_DMA_BUF_EXTENSION_NAME,
+          VK_EXT_IMAGE_DRM_FORMAT_MODIFIER_EXTENSION_NAME,
+      });
 
       FrameRender render(vk_ctx, init, m_fds);
       auto output = render.CreateOutput();
@@ -213,12 +228,23 @@ void CEncoder::Run() {
       auto encode_pipeline = alvr::EncodePipeline::Create(&render, vk_ctx, frame, vk_frame_ctx, render.GetEncodingWidth(), render.GetEncodingHeight());
 
       bool valid_timestamps = true;
+      bool test_pattern_warned = false;
 
       fprintf(stderr, "CEncoder starting to read present packets");
       present_packet frame_info;
       while (not m_exiting) {
         read_latest(client, (char *)&frame_info, sizeof(frame_info), m_exiting);
 
+        // Keep consuming the presented images, without rendering or encoding them
+        if (m_paused) {
+          continue;
+        }
+
+        // With the idle power saving, only one of every divider frames is encoded
+        if (m_frameCount++ % m_frameDivider != 0) {
+          continue;
+        }
+
         encode_pipeline->SetParams(GetDynamicEncoderParams());
 
         auto pose = m_poseHistory->GetBestPoseMatch((const vr::HmdMatrix34_t&)frame_info.pose);
@@ -233,7 +259,19 @@ void CEncoder::Run() {
           render.CaptureOutputFrame(Settings::Instance().m_captureFrameDir + "/alvr_frame_output.ppm");
         }
 
-        render.Render(frame_info.image, frame_info.semaphore_value);
+        if (!render.SetTestPattern(Settings::Instance().m_benchmarkTestPattern) && !test_pattern_warned) {
+          Warn("The benchmark test pattern is not supported with the image format of the game");
+          test_pattern_warned = true;
+        }
+
+        if (init.layout == input_layout::separate_eyes) {
+            render.RenderEyes(frame_info.image,
+                              frame_info.semaphore_value,
+                              frame_info.right_image,
+                              frame_info.right_semaphore_value);
+        } else {
+            render.Render(frame_info.image, frame_info.semaphore_value);
+        }
 
         if (!valid_timestamps) {
           ReportPresent(pose->targetTimestampNs, 0);
@@ -276,6 +314,10 @@ void CEncoder::Run() {
           ReportComposed(pose->targetTimestampNs, composed_offset);
         }
 
+        if (packet.psnr >= 0.0 || packet.ssim >= 0.0) {
+          ReportEncodeQuality(packet.psnr, packet.ssim);
+        }
+
         ParseFrameNals(encode_pipeline->GetCodec(), packet.data, packet.size, packet.pts, packet.isIDR);
       }
     }
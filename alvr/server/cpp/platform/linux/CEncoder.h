@@ -1,6 +1,7 @@
 #pragma once
 
 #include "alvr_server/IDRScheduler.h"
+#include "protocol.h"
 #include "shared/threadtools.h"
 #include <atomic>
 #include <memory>
@@ -20,17 +21,22 @@ class CEncoder : public CThread {
     void OnStreamStart();
     void OnPacketLoss();
     void InsertIDR();
+    void SetPaused(bool paused) { m_paused = paused; }
+    void SetFrameDivider(unsigned int divider) { m_frameDivider = divider > 0 ? divider : 1; }
     bool IsConnected() { return m_connected; }
     void CaptureFrame();
 
   private:
-    void GetFds(int client, int (*fds)[6]);
+    void GetFds(int client, int *fds, size_t count);
     std::shared_ptr<PoseHistory> m_poseHistory;
     std::atomic_bool m_exiting{false};
     IDRScheduler m_scheduler;
     pollfd m_socket;
     std::string m_socketPath;
-    int m_fds[6];
+    int m_fds[MAX_INPUT_FDS];
     bool m_connected = false;
     std::atomic_bool m_captureFrame = false;
+    std::atomic_bool m_paused = false;
+    std::atomic_uint m_frameDivider = 1;
+    uint64_t m_frameCount = 0;
 };
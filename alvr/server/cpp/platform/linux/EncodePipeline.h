@@ -21,6 +21,9 @@ struct FramePacket {
   int size;
   uint64_t pts;
   bool isIDR;
+  // Negative if not measured by the encoder
+  double psnr = -1.0;
+  double ssim = -1.0;
 };
 
 class EncodePipeline
@@ -46,9 +46,12 @@ alvr::EncodePipelineSW::EncodePipelineSW(Renderer *render, uint32_t width, uint3
   param.b_cabac = settings.m_entropyCoding == ALVR_CABAC;
   param.b_sliced_threads = true;
   param.i_threads = settings.m_swThreadCount;
+  param.i_slice_count = settings.m_slicesCount;
   param.i_width = width;
   param.i_height = height;
   param.rc.i_rc_method = X264_RC_ABR;
+  param.analyse.b_psnr = settings.m_encoderQualityFeedback;
+  param.analyse.b_ssim = settings.m_encoderQualityFeedback;
 
   switch (settings.m_h264Profile) {
   case ALVR_H264_PROFILE_BASELINE:
@@ -117,6 +120,12 @@ bool alvr::EncodePipelineSW::GetEncoded(FramePacket &packet)
   packet.size = nal_size;
   packet.data = nal[0].p_payload;
   packet.pts = pts;
+  if (param.analyse.b_psnr) {
+    packet.psnr = picture_out.prop.f_psnr[0];
+  }
+  if (param.analyse.b_ssim) {
+    packet.ssim = picture_out.prop.f_ssim;
+  }
   return packet.size > 0;
 }
 
@@ -0,0 +1,20 @@
+#include "EyeComposition.h"
+
+extern "C" void ComposeEyes(uint32_t imagesPerEye,
+                            VkExtent3D eyeSize,
+                            uint32_t leftImage,
+                            uint32_t rightImage,
+                            EyeCopy copies[2])
+{
+    uint32_t images[2] = {leftImage, imagesPerEye + rightImage};
+    for (uint32_t eye = 0; eye < 2; ++eye) {
+        VkImageCopy region = {};
+        region.srcSubresource.aspectMask = VK_IMAGE_ASPECT_COLOR_BIT;
+        region.srcSubresource.layerCount = 1;
+        region.dstSubresource = region.srcSubresource;
+        region.dstOffset.x = eye * eyeSize.width;
+        region.extent = eyeSize;
+
+        copies[eye] = {images[eye], region};
+    }
+}
@@ -0,0 +1,18 @@
+#pragma once
+
+#include <vulkan/vulkan.h>
+
+// Copy of the image of one eye into the frame that is encoded
+struct EyeCopy {
+    // Index in the received images
+    uint32_t image;
+    VkImageCopy region;
+};
+
+// With separate eye images, the image sets of the two eyes are received one after the other, left
+// eye first. The eye images of a present are copied side by side, left eye first
+extern "C" void ComposeEyes(uint32_t imagesPerEye,
+                            VkExtent3D eyeSize,
+                            uint32_t leftImage,
+                            uint32_t rightImage,
+                            EyeCopy copies[2]);
@@ -12,14 +12,26 @@ FrameRender::FrameRender(alvr::VkContext &ctx, init_packet &init, int fds[])
     m_quadShaderSize = QUAD_SHADER_COMP_SPV_LEN;
     m_quadShaderCode = reinterpret_cast<const uint32_t*>(QUAD_SHADER_COMP_SPV_PTR);
 
-    Startup(init.image_create_info.extent.width, init.image_create_info.extent.height, init.image_create_info.format);
+    uint32_t imageSets = 1;
+    uint32_t inputWidth = init.image_create_info.extent.width;
+    if (init.layout == input_layout::separate_eyes) {
+        // The eye images are composed side by side
+        imageSets = 2;
+        inputWidth *= 2;
+    }
+
+    Startup(inputWidth, init.image_create_info.extent.height, init.image_create_info.format);
 
-    for (size_t i = 0; i < 3; ++i) {
+    for (size_t i = 0; i < init.num_images * imageSets; ++i) {
         AddImage(init.image_create_info, init.mem_index, fds[2 * i], fds[2 * i + 1]);
     }
 
-    m_width = Settings::Instance().m_renderWidth;
-    m_height = Settings::Instance().m_renderHeight;
+    if (init.layout == input_layout::separate_eyes) {
+        SetSeparateEyes(init.num_images, init.image_create_info.extent);
+    }
+
+    m_width = Settings::Instance().m_encodeWidth;
+    m_height = Settings::Instance().m_encodeHeight;
 
     Info("FrameRender: Input size %ux%u", m_width, m_height);
 
@@ -177,9 +189,18 @@ void FrameRender::setupCustomShaders(const std::string &stage)
             }
             Info("FrameRender: Adding [%s] shader %s", stage.c_str(), entry.path().filename().c_str());
             RenderPipeline *pipeline = new RenderPipeline(this);
-            pipeline->SetShader(entry.path().c_str());
-            m_pipelines.push_back(pipeline);
-            AddPipeline(pipeline);
+            try {
+                pipeline->SetShader(entry.path().c_str());
+                AddPipeline(pipeline);
+                m_pipelines.push_back(pipeline);
+            } catch (const std::exception &e) {
+                // Skip the broken shader, the built-in passes are used instead
+                Error("FrameRender: Failed to load [%s] shader %s: %s",
+                      stage.c_str(),
+                      entry.path().filename().c_str(),
+                      e.what());
+                delete pipeline;
+            }
         }
     } catch (...) { }
 }
@@ -7,6 +7,8 @@
 #include <cstring>
 #include <algorithm>
 
+#include "alvr_server/TestPattern.h"
+
 #ifndef DRM_FORMAT_INVALID
 #define DRM_FORMAT_INVALID 0
 #define fourcc_code(a, b, c, d) ((uint32_t)(a) | ((uint32_t)(b) << 8) | \
@@ -99,6 +101,20 @@ Renderer::~Renderer()
         vkFreeMemory(m_dev, image.memory, nullptr);
     }
 
+    if (m_eyesImage.image != VK_NULL_HANDLE) {
+        vkDestroyImageView(m_dev, m_eyesImage.view, nullptr);
+        vkDestroyImage(m_dev, m_eyesImage.image, nullptr);
+        vkFreeMemory(m_dev, m_eyesImage.memory, nullptr);
+    }
+
+    if (m_testPatternBuffer != VK_NULL_HANDLE) {
+        vkDestroyBuffer(m_dev, m_testPatternBuffer, nullptr);
+        vkFreeMemory(m_dev, m_testPatternMemory, nullptr);
+        vkDestroyImageView(m_dev, m_testPatternImage.view, nullptr);
+        vkDestroyImage(m_dev, m_testPatternImage.image, nullptr);
+        vkFreeMemory(m_dev, m_testPatternImage.memory, nullptr);
+    }
+
     vkDestroyImageView(m_dev, m_output.view, nullptr);
     vkDestroyImage(m_dev, m_output.image, nullptr);
     vkFreeMemory(m_dev, m_output.memory, nullptr);
@@ -548,15 +564,43 @@ void Renderer::ImportOutput(const DrmImage &drm)
 
 void Renderer::Render(uint32_t index, uint64_t waitValue)
 {
+    render({{index, waitValue}});
+}
+
+void Renderer::SetSeparateEyes(uint32_t imagesPerEye, VkExtent3D eyeSize)
+{
+    m_imagesPerEye = imagesPerEye;
+    m_eyeSize = eyeSize;
+    m_eyesImage = createStagingImage(m_imageSize.width, m_imageSize.height);
+}
+
+void Renderer::RenderEyes(uint32_t leftIndex, uint64_t leftWaitValue, uint32_t rightIndex, uint64_t rightWaitValue)
+{
+    ComposeEyes(m_imagesPerEye, m_eyeSize, leftIndex, rightIndex, m_eyeCopies);
+    render({{m_eyeCopies[0].image, leftWaitValue}, {m_eyeCopies[1].image, rightWaitValue}});
+}
+
+void Renderer::render(const std::vector<std::pair<uint32_t, uint64_t>> &inputs)
+{
+    bool separateEyes = inputs.size() == 2;
+
     if (!m_inputImageCapture.empty()) {
-        VkSemaphoreWaitInfo waitInfo = {};
-        waitInfo.sType = VK_STRUCTURE_TYPE_SEMAPHORE_WAIT_INFO;
-        waitInfo.semaphoreCount = 1;
-        waitInfo.pSemaphores = &m_images[index].semaphore;
-        waitInfo.pValues = &waitValue;
-        VK_CHECK(vkWaitSemaphores(m_dev, &waitInfo, UINT64_MAX));
-
-        dumpImage(m_images[index].image, m_images[index].view, m_images[index].layout, m_imageSize.width, m_imageSize.height, m_inputImageCapture);
+        for (auto &[index, waitValue] : inputs) {
+            VkSemaphoreWaitInfo waitInfo = {};
+            waitInfo.sType = VK_STRUCTURE_TYPE_SEMAPHORE_WAIT_INFO;
+            waitInfo.semaphoreCount = 1;
+            waitInfo.pSemaphores = &m_images[index].semaphore;
+            waitInfo.pValues = &waitValue;
+            VK_CHECK(vkWaitSemaphores(m_dev, &waitInfo, UINT64_MAX));
+        }
+
+        // Only the left eye is captured with separate eye images
+        auto &img = m_images[inputs[0].first];
+        if (separateEyes) {
+            dumpImage(img.image, img.view, img.layout, m_eyeSize.width, m_eyeSize.height, m_inputImageCapture);
+        } else {
+            dumpImage(img.image, img.view, img.layout, m_imageSize.width, m_imageSize.height, m_inputImageCapture);
+        }
         m_inputImageCapture.clear();
     }
 
@@ -567,6 +611,12 @@ void Renderer::Render(uint32_t index, uint64_t waitValue)
     vkCmdResetQueryPool(m_commandBuffer, m_queryPool, 0, 2);
     vkCmdWriteTimestamp(m_commandBuffer, VK_PIPELINE_STAGE_TOP_OF_PIPE_BIT, m_queryPool, 0);
 
+    if (m_testPattern) {
+        recordTestPattern();
+    } else if (separateEyes) {
+        recordEyesComposition();
+    }
+
     for (size_t i = 0; i < m_pipelines.size(); ++i) {
         VkRect2D rect = {};
         VkImage in = VK_NULL_HANDLE;
@@ -575,8 +625,16 @@ void Renderer::Render(uint32_t index, uint64_t waitValue)
         VkImage out = VK_NULL_HANDLE;
         VkImageView outView = VK_NULL_HANDLE;
         VkImageLayout *outLayout = nullptr;
-        if (i == 0) {
-            auto &img = m_images[index];
+        if (i == 0 && m_testPattern) {
+            in = m_testPatternImage.image;
+            inView = m_testPatternImage.view;
+            inLayout = &m_testPatternImage.layout;
+        } else if (i == 0 && separateEyes) {
+            in = m_eyesImage.image;
+            inView = m_eyesImage.view;
+            inLayout = &m_eyesImage.layout;
+        } else if (i == 0) {
+            auto &img = m_images[inputs[0].first];
             in = img.image;
             inView = img.view;
             inLayout = &img.layout;
@@ -633,19 +691,26 @@ void Renderer::Render(uint32_t index, uint64_t waitValue)
 
     VK_CHECK(vkEndCommandBuffer(m_commandBuffer));
 
+    std::vector<VkSemaphore> waitSemaphores;
+    std::vector<uint64_t> waitValues;
+    std::vector<VkPipelineStageFlags> waitStages;
+    for (auto &[index, waitValue] : inputs) {
+        waitSemaphores.push_back(m_images[index].semaphore);
+        waitValues.push_back(waitValue);
+        waitStages.push_back(separateEyes ? VK_PIPELINE_STAGE_TRANSFER_BIT : VK_PIPELINE_STAGE_COMPUTE_SHADER_BIT);
+    }
+
     VkTimelineSemaphoreSubmitInfo timelineInfo = {};
     timelineInfo.sType = VK_STRUCTURE_TYPE_TIMELINE_SEMAPHORE_SUBMIT_INFO;
-    timelineInfo.waitSemaphoreValueCount = 1;
-    timelineInfo.pWaitSemaphoreValues = &waitValue;
-
-    VkPipelineStageFlags waitStage = VK_PIPELINE_STAGE_COMPUTE_SHADER_BIT;
+    timelineInfo.waitSemaphoreValueCount = waitValues.size();
+    timelineInfo.pWaitSemaphoreValues = waitValues.data();
 
     VkSubmitInfo submitInfo = {};
     submitInfo.sType = VK_STRUCTURE_TYPE_SUBMIT_INFO;
     submitInfo.pNext = &timelineInfo;
-    submitInfo.waitSemaphoreCount = 1;
-    submitInfo.pWaitSemaphores = &m_images[index].semaphore;
-    submitInfo.pWaitDstStageMask = &waitStage;
+    submitInfo.waitSemaphoreCount = waitSemaphores.size();
+    submitInfo.pWaitSemaphores = waitSemaphores.data();
+    submitInfo.pWaitDstStageMask = waitStages.data();
     submitInfo.signalSemaphoreCount = 1;
     submitInfo.pSignalSemaphores = &m_output.semaphore;
     submitInfo.commandBufferCount = 1;
@@ -653,6 +718,144 @@ void Renderer::Render(uint32_t index, uint64_t waitValue)
     VK_CHECK(vkQueueSubmit(m_queue, 1, &submitInfo, nullptr));
 }
 
+void Renderer::recordEyesComposition()
+{
+    InputImage *eyes[2] = {&m_images[m_eyeCopies[0].image], &m_images[m_eyeCopies[1].image]};
+
+    VkImageMemoryBarrier imageBarrier = {};
+    imageBarrier.sType = VK_STRUCTURE_TYPE_IMAGE_MEMORY_BARRIER;
+    imageBarrier.subresourceRange.aspectMask = VK_IMAGE_ASPECT_COLOR_BIT;
+    imageBarrier.subresourceRange.layerCount = 1;
+    imageBarrier.subresourceRange.levelCount = 1;
+    std::vector<VkImageMemoryBarrier> imageBarriers;
+    for (InputImage *eye : eyes) {
+        if (eye->layout != VK_IMAGE_LAYOUT_TRANSFER_SRC_OPTIMAL) {
+            imageBarrier.image = eye->image;
+            imageBarrier.oldLayout = eye->layout;
+            eye->layout = VK_IMAGE_LAYOUT_TRANSFER_SRC_OPTIMAL;
+            imageBarrier.newLayout = eye->layout;
+            imageBarrier.srcAccessMask = 0;
+            imageBarrier.dstAccessMask = VK_ACCESS_TRANSFER_READ_BIT;
+            imageBarriers.push_back(imageBarrier);
+        }
+    }
+    // The previous content is fully overwritten
+    imageBarrier.image = m_eyesImage.image;
+    imageBarrier.oldLayout = VK_IMAGE_LAYOUT_UNDEFINED;
+    imageBarrier.newLayout = VK_IMAGE_LAYOUT_TRANSFER_DST_OPTIMAL;
+    imageBarrier.srcAccessMask = VK_ACCESS_SHADER_READ_BIT;
+    imageBarrier.dstAccessMask = VK_ACCESS_TRANSFER_WRITE_BIT;
+    imageBarriers.push_back(imageBarrier);
+    vkCmdPipelineBarrier(m_commandBuffer, VK_PIPELINE_STAGE_COMPUTE_SHADER_BIT, VK_PIPELINE_STAGE_TRANSFER_BIT, 0, 0, nullptr, 0, nullptr, imageBarriers.size(), imageBarriers.data());
+
+    for (uint32_t eye = 0; eye < 2; ++eye) {
+        vkCmdCopyImage(m_commandBuffer, eyes[eye]->image, VK_IMAGE_LAYOUT_TRANSFER_SRC_OPTIMAL, m_eyesImage.image, VK_IMAGE_LAYOUT_TRANSFER_DST_OPTIMAL, 1, &m_eyeCopies[eye].region);
+    }
+
+    imageBarrier.oldLayout = VK_IMAGE_LAYOUT_TRANSFER_DST_OPTIMAL;
+    imageBarrier.newLayout = VK_IMAGE_LAYOUT_SHADER_READ_ONLY_OPTIMAL;
+    imageBarrier.srcAccessMask = VK_ACCESS_TRANSFER_WRITE_BIT;
+    imageBarrier.dstAccessMask = VK_ACCESS_SHADER_READ_BIT;
+    vkCmdPipelineBarrier(m_commandBuffer, VK_PIPELINE_STAGE_TRANSFER_BIT, VK_PIPELINE_STAGE_COMPUTE_SHADER_BIT, 0, 0, nullptr, 0, nullptr, 1, &imageBarrier);
+    m_eyesImage.layout = VK_IMAGE_LAYOUT_SHADER_READ_ONLY_OPTIMAL;
+}
+
+bool Renderer::SetTestPattern(bool enabled)
+{
+    m_testPattern = false;
+    if (!enabled) {
+        return true;
+    }
+
+    bool bgra;
+    switch (m_format) {
+    case VK_FORMAT_R8G8B8A8_UNORM:
+    case VK_FORMAT_R8G8B8A8_SRGB:
+        bgra = false;
+        break;
+    case VK_FORMAT_B8G8R8A8_UNORM:
+    case VK_FORMAT_B8G8R8A8_SRGB:
+        bgra = true;
+        break;
+    default:
+        return false;
+    }
+
+    if (m_testPatternBuffer == VK_NULL_HANDLE) {
+        createTestPattern(bgra);
+    }
+    m_testPattern = true;
+
+    return true;
+}
+
+void Renderer::createTestPattern(bool bgra)
+{
+    // Two periods per row, so that the window of each frame is contiguous
+    uint32_t eyeWidth = m_imageSize.width / 2;
+    auto pixels = TestPattern::Generate(eyeWidth, m_imageSize.height, 2 * eyeWidth, bgra);
+    VkDeviceSize size = pixels.size() * sizeof(uint32_t);
+
+    VkBufferCreateInfo bufferInfo = {};
+    bufferInfo.sType = VK_STRUCTURE_TYPE_BUFFER_CREATE_INFO;
+    bufferInfo.size = size;
+    bufferInfo.usage = VK_BUFFER_USAGE_TRANSFER_SRC_BIT;
+    bufferInfo.sharingMode = VK_SHARING_MODE_EXCLUSIVE;
+    VK_CHECK(vkCreateBuffer(m_dev, &bufferInfo, nullptr, &m_testPatternBuffer));
+
+    VkMemoryRequirements memoryReqs;
+    vkGetBufferMemoryRequirements(m_dev, m_testPatternBuffer, &memoryReqs);
+    VkMemoryAllocateInfo memoryAllocInfo = {};
+    memoryAllocInfo.sType = VK_STRUCTURE_TYPE_MEMORY_ALLOCATE_INFO;
+    memoryAllocInfo.allocationSize = memoryReqs.size;
+    memoryAllocInfo.memoryTypeIndex = memoryTypeIndex(VK_MEMORY_PROPERTY_HOST_VISIBLE_BIT | VK_MEMORY_PROPERTY_HOST_COHERENT_BIT, memoryReqs.memoryTypeBits);
+    VK_CHECK(vkAllocateMemory(m_dev, &memoryAllocInfo, nullptr, &m_testPatternMemory));
+    VK_CHECK(vkBindBufferMemory(m_dev, m_testPatternBuffer, m_testPatternMemory, 0));
+
+    void *data;
+    VK_CHECK(vkMapMemory(m_dev, m_testPatternMemory, 0, size, 0, &data));
+    memcpy(data, pixels.data(), size);
+    vkUnmapMemory(m_dev, m_testPatternMemory);
+
+    m_testPatternImage = createStagingImage(m_imageSize.width, m_imageSize.height);
+}
+
+void Renderer::recordTestPattern()
+{
+    uint32_t eyeWidth = m_imageSize.width / 2;
+
+    VkImageMemoryBarrier imageBarrier = {};
+    imageBarrier.sType = VK_STRUCTURE_TYPE_IMAGE_MEMORY_BARRIER;
+    imageBarrier.image = m_testPatternImage.image;
+    imageBarrier.subresourceRange.aspectMask = VK_IMAGE_ASPECT_COLOR_BIT;
+    imageBarrier.subresourceRange.layerCount = 1;
+    imageBarrier.subresourceRange.levelCount = 1;
+    imageBarrier.oldLayout = VK_IMAGE_LAYOUT_UNDEFINED;
+    imageBarrier.newLayout = VK_IMAGE_LAYOUT_TRANSFER_DST_OPTIMAL;
+    imageBarrier.srcAccessMask = VK_ACCESS_SHADER_READ_BIT;
+    imageBarrier.dstAccessMask = VK_ACCESS_TRANSFER_WRITE_BIT;
+    vkCmdPipelineBarrier(m_commandBuffer, VK_PIPELINE_STAGE_COMPUTE_SHADER_BIT, VK_PIPELINE_STAGE_TRANSFER_BIT, 0, 0, nullptr, 0, nullptr, 1, &imageBarrier);
+
+    VkBufferImageCopy regions[2] = {};
+    for (uint32_t eye = 0; eye < 2; ++eye) {
+        regions[eye].bufferOffset = m_testPatternOffset * sizeof(uint32_t);
+        regions[eye].bufferRowLength = 2 * eyeWidth;
+        regions[eye].imageSubresource.aspectMask = VK_IMAGE_ASPECT_COLOR_BIT;
+        regions[eye].imageSubresource.layerCount = 1;
+        regions[eye].imageOffset.x = eye * eyeWidth;
+        regions[eye].imageExtent = {eyeWidth, m_imageSize.height, 1};
+    }
+    vkCmdCopyBufferToImage(m_commandBuffer, m_testPatternBuffer, m_testPatternImage.image, VK_IMAGE_LAYOUT_TRANSFER_DST_OPTIMAL, 2, regions);
+    m_testPatternOffset = (m_testPatternOffset + TestPattern::SCROLL_STEP) % eyeWidth;
+
+    imageBarrier.oldLayout = VK_IMAGE_LAYOUT_TRANSFER_DST_OPTIMAL;
+    imageBarrier.newLayout = VK_IMAGE_LAYOUT_SHADER_READ_ONLY_OPTIMAL;
+    imageBarrier.srcAccessMask = VK_ACCESS_TRANSFER_WRITE_BIT;
+    imageBarrier.dstAccessMask = VK_ACCESS_SHADER_READ_BIT;
+    vkCmdPipelineBarrier(m_commandBuffer, VK_PIPELINE_STAGE_TRANSFER_BIT, VK_PIPELINE_STAGE_COMPUTE_SHADER_BIT, 0, 0, nullptr, 0, nullptr, 1, &imageBarrier);
+    m_testPatternImage.layout = VK_IMAGE_LAYOUT_SHADER_READ_ONLY_OPTIMAL;
+}
+
 void Renderer::Sync()
 {
     VkPipelineStageFlags waitStage = VK_PIPELINE_STAGE_BOTTOM_OF_PIPE_BIT;
@@ -774,6 +977,11 @@ void Renderer::commandBufferSubmit()
 }
 
 void Renderer::addStagingImage(uint32_t width, uint32_t height)
+{
+    m_stagingImages.push_back(createStagingImage(width, height));
+}
+
+Renderer::StagingImage Renderer::createStagingImage(uint32_t width, uint32_t height)
 {
     VkImageCreateInfo imageInfo = {};
     imageInfo = {};
@@ -787,7 +995,7 @@ void Renderer::addStagingImage(uint32_t width, uint32_t height)
     imageInfo.arrayLayers = 1;
     imageInfo.samples = VK_SAMPLE_COUNT_1_BIT;
     imageInfo.tiling = VK_IMAGE_TILING_OPTIMAL;
-    imageInfo.usage = VK_IMAGE_USAGE_STORAGE_BIT | VK_IMAGE_USAGE_SAMPLED_BIT;
+    imageInfo.usage = VK_IMAGE_USAGE_STORAGE_BIT | VK_IMAGE_USAGE_SAMPLED_BIT | VK_IMAGE_USAGE_TRANSFER_DST_BIT;
     imageInfo.sharingMode = VK_SHARING_MODE_EXCLUSIVE;
     imageInfo.initialLayout = VK_IMAGE_LAYOUT_UNDEFINED;
     VkImage image;
@@ -821,7 +1029,7 @@ void Renderer::addStagingImage(uint32_t width, uint32_t height)
     VkImageView view;
     VK_CHECK(vkCreateImageView(m_dev, &viewInfo, nullptr, &view));
 
-    m_stagingImages.push_back({image, VK_IMAGE_LAYOUT_UNDEFINED, memory, view});
+    return {image, VK_IMAGE_LAYOUT_UNDEFINED, memory, view};
 }
 
 void Renderer::dumpImage(VkImage image, VkImageView imageView, VkImageLayout imageLayout, uint32_t width, uint32_t height, const std::string &filename)
@@ -1038,10 +1246,12 @@ void RenderPipeline::SetShader(const char *filename)
 {
     std::ifstream is(filename, std::ios::binary | std::ios::in | std::ios::ate);
     if (!is.is_open()) {
-        std::cerr << "Failed to open shader file: " << filename << std::endl;
-        return;
+        throw std::runtime_error(std::string("Failed to open shader file: ") + filename);
     }
     size_t size = is.tellg();
+    if (size == 0 || size % sizeof(uint32_t) != 0) {
+        throw std::runtime_error("Invalid SPIR-V size: " + std::to_string(size) + " bytes");
+    }
     is.seekg(0, std::ios::beg);
     std::vector<char> data(size);
     is.read(data.data(), size);
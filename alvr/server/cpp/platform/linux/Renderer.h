@@ -6,6 +6,8 @@
 #include <iostream>
 #include <vulkan/vulkan.h>
 
+#include "EyeComposition.h"
+
 #define VK_CHECK(f) \
 { \
     VkResult res = (f); \
@@ -68,12 +70,19 @@ public:
 
     void Render(uint32_t index, uint64_t waitValue);
 
+    // The first imagesPerEye images are the left eye ones, followed by the right eye ones. They are
+    // composed side by side before the pipelines
+    void SetSeparateEyes(uint32_t imagesPerEye, VkExtent3D eyeSize);
+    void RenderEyes(uint32_t leftIndex, uint64_t leftWaitValue, uint32_t rightIndex, uint64_t rightWaitValue);
+
     void Sync();
 
     Output &GetOutput();
     Timestamps GetTimestamps();
 
     void CaptureInputFrame(const std::string &filename);
+    // Replaces the inputs with the benchmark pattern. Returns false if the format is not supported
+    bool SetTestPattern(bool enabled);
     void CaptureOutputFrame(const std::string &filename);
 
     static std::string result_to_str(VkResult result);
@@ -97,6 +106,11 @@ public:
     void commandBufferBegin();
     void commandBufferSubmit();
     void addStagingImage(uint32_t width, uint32_t height);
+    StagingImage createStagingImage(uint32_t width, uint32_t height);
+    void render(const std::vector<std::pair<uint32_t, uint64_t>> &inputs);
+    void recordEyesComposition();
+    void createTestPattern(bool bgra);
+    void recordTestPattern();
     void dumpImage(VkImage image, VkImageView imageView, VkImageLayout imageLayout, uint32_t width, uint32_t height, const std::string &filename);
     uint32_t memoryTypeIndex(VkMemoryPropertyFlags properties, uint32_t typeBits) const;
 
@@ -115,6 +129,16 @@ public:
     Output m_output;
     std::vector<InputImage> m_images;
     std::vector<StagingImage> m_stagingImages;
+    uint32_t m_imagesPerEye = 0;
+    VkExtent3D m_eyeSize = {0, 0, 0};
+    StagingImage m_eyesImage;
+    EyeCopy m_eyeCopies[2] = {};
+    // The pattern is uploaded once, each frame copies a scrolled window of it for both eyes
+    bool m_testPattern = false;
+    VkBuffer m_testPatternBuffer = VK_NULL_HANDLE;
+    VkDeviceMemory m_testPatternMemory = VK_NULL_HANDLE;
+    StagingImage m_testPatternImage;
+    uint32_t m_testPatternOffset = 0;
     std::vector<RenderPipeline*> m_pipelines;
 
     VkInstance m_inst = VK_NULL_HANDLE;
@@ -55,7 +55,14 @@ alvr::VkContext::VkContext(const uint8_t *deviceUUID, const std::vector<const ch
       VK_EXT_PHYSICAL_DEVICE_DRM_EXTENSION_NAME,
       VK_EXT_CALIBRATED_TIMESTAMPS_EXTENSION_NAME,
   };
-  device_extensions.insert(device_extensions.end(), requiredDeviceExtensions.begin(), requiredDeviceExtensions.end());
+  for (const char *name : requiredDeviceExtensions) {
+    bool listed = std::any_of(device_extensions.begin(), device_extensions.end(), [name](const char *e) {
+      return strcmp(e, name) == 0;
+    });
+    if (!listed) {
+      device_extensions.push_back(name);
+    }
+  }
 
   uint32_t instanceExtensionCount = 0;
   vkEnumerateInstanceExtensionProperties(nullptr, &instanceExtensionCount, nullptr);
@@ -114,8 +121,12 @@ alvr::VkContext::VkContext(const uint8_t *deviceUUID, const std::vector<const ch
     throw std::runtime_error("Failed to find vulkan device.");
   }
 
+  VkPhysicalDeviceDriverProperties driverProps = {};
+  driverProps.sType = VK_STRUCTURE_TYPE_PHYSICAL_DEVICE_DRIVER_PROPERTIES;
+
   VkPhysicalDeviceDrmPropertiesEXT drmProps = {};
   drmProps.sType = VK_STRUCTURE_TYPE_PHYSICAL_DEVICE_DRM_PROPERTIES_EXT;
+  drmProps.pNext = &driverProps;
 
   VkPhysicalDeviceProperties2 deviceProps = {};
   deviceProps.sType = VK_STRUCTURE_TYPE_PHYSICAL_DEVICE_PROPERTIES_2;
@@ -127,17 +138,47 @@ alvr::VkContext::VkContext(const uint8_t *deviceUUID, const std::vector<const ch
   nvidia = deviceProps.properties.vendorID == 0x10de;
   Info("Using Vulkan device %s", deviceProps.properties.deviceName);
 
+  // The versions are logged for bug reports, the features used below declare their minimum
+  // versions on the Rust side. Fail here instead of with an obscure error later
+  FfiVulkanDriverInfo driverInfo = {};
+  driverInfo.deviceName = deviceProps.properties.deviceName;
+  driverInfo.driverName = driverProps.driverName;
+  driverInfo.driverInfo = driverProps.driverInfo;
+  driverInfo.driverId = driverProps.driverID;
+  driverInfo.driverVersion = deviceProps.properties.driverVersion;
+  driverInfo.apiVersion = deviceProps.properties.apiVersion;
+  driverInfo.conformanceVersion[0] = driverProps.conformanceVersion.major;
+  driverInfo.conformanceVersion[1] = driverProps.conformanceVersion.minor;
+  driverInfo.conformanceVersion[2] = driverProps.conformanceVersion.subminor;
+  driverInfo.conformanceVersion[3] = driverProps.conformanceVersion.patch;
+  if (!CheckVulkanDriver(driverInfo)) {
+    throw std::runtime_error(std::string("Vulkan driver of ") + deviceProps.properties.deviceName + " does not meet the requirements, see the log for details");
+  }
+
   uint32_t deviceExtensionCount = 0;
   VK_CHECK(vkEnumerateDeviceExtensionProperties(physicalDevice, nullptr, &deviceExtensionCount, nullptr));
   std::vector<VkExtensionProperties> deviceExts(deviceExtensionCount);
   VK_CHECK(vkEnumerateDeviceExtensionProperties(physicalDevice, nullptr, &deviceExtensionCount, deviceExts.data()));
+  // The context is headless, swapchain and presentation extensions are never needed. The
+  // extensions above are enabled only if available, the ones requested by the caller are mandatory
+  std::string missingExtensions;
   for (const char *name : device_extensions) {
     auto it = std::find_if(deviceExts.begin(), deviceExts.end(), [name](VkExtensionProperties e) {
       return strcmp(e.extensionName, name) == 0;
     });
     if (it != deviceExts.end()) {
       deviceExtensions.push_back(name);
+      continue;
     }
+    bool required = std::any_of(requiredDeviceExtensions.begin(), requiredDeviceExtensions.end(), [name](const char *e) {
+      return strcmp(e, name) == 0;
+    });
+    if (required) {
+      missingExtensions += (missingExtensions.empty() ? "" : ", ") + std::string(name);
+    }
+  }
+  if (!missingExtensions.empty()) {
+    throw std::runtime_error(std::string("Vulkan device ") + deviceProps.properties.deviceName + " is missing required extensions: " + missingExtensions);
   }
 
   float queuePriority = 1.0;
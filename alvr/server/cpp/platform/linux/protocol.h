@@ -8,14 +8,30 @@
 #include <mutex>
 #include <vulkan/vulkan.h>
 
+// Both eyes are either rendered side by side in each image, or in a separate image set per eye. In the
+// latter case the image sets are sent one after the other, left eye first, and are composed side by
+// side on ingest
+enum class input_layout : uint32_t {
+    combined,
+    separate_eyes,
+};
+
+// An image and a semaphore for each of the 3 images of the two eyes
+constexpr size_t MAX_INPUT_FDS = 2 * 3 * 2;
+
 struct present_packet {
     uint32_t image;
     uint32_t frame;
     uint64_t semaphore_value;
     float pose[3][4];
+    // Only used with separate eye images
+    uint32_t right_image;
+    uint64_t right_semaphore_value;
 };
 
 struct init_packet {
+    input_layout layout;
+    // Per image set
     uint32_t num_images;
     std::array<uint8_t, VK_UUID_SIZE> device_uuid;
     VkImageCreateInfo image_create_info;
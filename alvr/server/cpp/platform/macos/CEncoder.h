@@ -13,4 +13,5 @@ class CEncoder : public CThread {
     void OnStreamStart() {}
     void OnPacketLoss() {}
     void InsertIDR() {}
+    void SetPaused(bool) {}
 };
@@ -97,7 +97,10 @@
 				if (m_bExiting)
 					break;
 
-				if (m_FrameRender->GetTexture())
+				// With the idle power saving, only one of every divider frames is encoded
+				bool encodeFrame = m_frameCount++ % m_frameDivider == 0;
+
+				if (m_FrameRender->GetTexture() && !m_paused && encodeFrame)
 				{
 					m_videoEncoder->Transmit(m_FrameRender->GetTexture().Get(), m_presentationTime, m_targetTimestampNs, m_scheduler.CheckIDRInsertion());
 				}
@@ -137,5 +140,13 @@
 			m_scheduler.InsertIDR();
 		}
 
+		void CEncoder::SetPaused(bool paused) {
+			m_paused = paused;
+		}
+
+		void CEncoder::SetFrameDivider(unsigned int divider) {
+			m_frameDivider = divider > 0 ? divider : 1;
+		}
+
 		void CEncoder::CaptureFrame() {
 		}
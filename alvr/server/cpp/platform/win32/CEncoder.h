@@ -18,6 +18,7 @@
 	#include "VideoEncoderSW.h"
 #endif
 #include "alvr_server/IDRScheduler.h"
+#include <atomic>
 
 
 	using Microsoft::WRL::ComPtr;
@@ -52,12 +53,19 @@
 
 		void InsertIDR();
 
+		void SetPaused(bool paused);
+
+		void SetFrameDivider(unsigned int divider);
+
 		void CaptureFrame();
 
 	private:
 		CThreadEvent m_newFrameReady, m_encodeFinished;
 		std::shared_ptr<VideoEncoder> m_videoEncoder;
 		bool m_bExiting;
+		std::atomic_bool m_paused = false;
+		std::atomic_uint m_frameDivider = 1;
+		uint64_t m_frameCount = 0;
 		uint64_t m_presentationTime;
 		uint64_t m_targetTimestampNs;
 
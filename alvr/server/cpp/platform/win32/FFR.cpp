@@ -27,8 +27,8 @@ namespace {
 	};
 
 	FoveationVars CalculateFoveationVars() {
-		float targetEyeWidth = (float)Settings::Instance().m_renderWidth / 2;
-		float targetEyeHeight = (float)Settings::Instance().m_renderHeight;
+		float targetEyeWidth = (float)Settings::Instance().m_encodeWidth / 2;
+		float targetEyeHeight = (float)Settings::Instance().m_encodeHeight;
 
 		float centerSizeX = (float)Settings::Instance().m_foveationCenterSizeX;
 		float centerSizeY = (float)Settings::Instance().m_foveationCenterSizeY;
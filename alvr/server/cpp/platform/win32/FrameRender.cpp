@@ -2,6 +2,7 @@
 #include "alvr_server/Utils.h"
 #include "alvr_server/Logger.h"
 #include "alvr_server/Settings.h"
+#include "alvr_server/TestPattern.h"
 #include "alvr_server/bindings.h"
 
 extern uint64_t g_DriverTestMode;
@@ -33,8 +34,8 @@ bool FrameRender::Startup()
 
 	D3D11_TEXTURE2D_DESC compositionTextureDesc;
 	ZeroMemory(&compositionTextureDesc, sizeof(compositionTextureDesc));
-	compositionTextureDesc.Width = Settings::Instance().m_renderWidth;
-	compositionTextureDesc.Height = Settings::Instance().m_renderHeight;
+	compositionTextureDesc.Width = Settings::Instance().m_encodeWidth;
+	compositionTextureDesc.Height = Settings::Instance().m_encodeHeight;
 	compositionTextureDesc.Format = Settings::Instance().m_enableHdr ? DXGI_FORMAT_R16G16B16A16_FLOAT : DXGI_FORMAT_R8G8B8A8_UNORM_SRGB;
 	compositionTextureDesc.MipLevels = 1;
 	compositionTextureDesc.ArraySize = 1;
@@ -92,8 +93,8 @@ bool FrameRender::Startup()
 	m_pD3DRender->GetContext()->OMSetRenderTargets(1, m_pRenderTargetView.GetAddressOf(), m_pDepthStencilView.Get());
 
 	D3D11_VIEWPORT viewport;
-	viewport.Width = (float)Settings::Instance().m_renderWidth;
-	viewport.Height = (float)Settings::Instance().m_renderHeight;
+	viewport.Width = (float)Settings::Instance().m_encodeWidth;
+	viewport.Height = (float)Settings::Instance().m_encodeHeight;
 	viewport.MinDepth = 0.0f;
 	viewport.MaxDepth = 1.0f;
 	viewport.TopLeftX = 0;
@@ -290,7 +291,7 @@ bool FrameRender::Startup()
 		std::vector<uint8_t> colorCorrectionShaderCSO(COLOR_CORRECTION_CSO_PTR, COLOR_CORRECTION_CSO_PTR + COLOR_CORRECTION_CSO_LEN);
 
 		ComPtr<ID3D11Texture2D> colorCorrectedTexture = CreateTexture(m_pD3DRender->GetDevice(),
-			Settings::Instance().m_renderWidth, Settings::Instance().m_renderHeight,
+			Settings::Instance().m_encodeWidth, Settings::Instance().m_encodeHeight,
 			Settings::Instance().m_enableHdr ? DXGI_FORMAT_R16G16B16A16_FLOAT : DXGI_FORMAT_R8G8B8A8_UNORM_SRGB);
 
 		struct ColorCorrection {
@@ -303,7 +304,7 @@ bool FrameRender::Startup()
 			float sharpening;
 			float _align;
 		};
-		ColorCorrection colorCorrectionStruct = { (float)Settings::Instance().m_renderWidth, (float)Settings::Instance().m_renderHeight,
+		ColorCorrection colorCorrectionStruct = { (float)Settings::Instance().m_encodeWidth, (float)Settings::Instance().m_encodeHeight,
 												  Settings::Instance().m_brightness, Settings::Instance().m_contrast + 1.f,
 												  Settings::Instance().m_saturation + 1.f, Settings::Instance().m_gamma,
 												  Settings::Instance().m_sharpening };
@@ -420,8 +421,8 @@ bool FrameRender::RenderFrame(ID3D11Texture2D *pTexture[][2], vr::VRTextureBound
 
 	// Set viewport
 	D3D11_VIEWPORT viewport;
-	viewport.Width = (float)Settings::Instance().m_renderWidth;
-	viewport.Height = (float)Settings::Instance().m_renderHeight;
+	viewport.Width = (float)Settings::Instance().m_encodeWidth;
+	viewport.Height = (float)Settings::Instance().m_encodeHeight;
 	viewport.MinDepth = 0.0f;
 	viewport.MaxDepth = 1.0f;
 	viewport.TopLeftX = 0;
@@ -431,6 +432,13 @@ bool FrameRender::RenderFrame(ID3D11Texture2D *pTexture[][2], vr::VRTextureBound
 	// Clear the back buffer
 	m_pD3DRender->GetContext()->ClearRenderTargetView(m_pRenderTargetView.Get(), DirectX::Colors::MidnightBlue);
 
+	// The benchmark replaces the layers of the game with its pattern
+	bool testPattern = Settings::Instance().m_benchmarkTestPattern && CreateTestPatternTexture();
+	if (testPattern) {
+		layerCount = 1;
+		recentering = false;
+	}
+
 	// Overlay recentering texture on top of all layers.
 	int recenterLayer = -1;
 	if (recentering) {
@@ -442,7 +450,20 @@ bool FrameRender::RenderFrame(ID3D11Texture2D *pTexture[][2], vr::VRTextureBound
 		ID3D11Texture2D *textures[2];
 		vr::VRTextureBounds_t bound[2];
 
-		if (i == recenterLayer) {
+		if (testPattern) {
+			textures[0] = m_testPatternTexture.Get();
+			textures[1] = m_testPatternTexture.Get();
+
+			// The sampler wraps around the period of the pattern
+			uint32_t eyeWidth = Settings::Instance().m_encodeWidth / 2;
+			float shift = (float)m_testPatternOffset / eyeWidth;
+			m_testPatternOffset = (m_testPatternOffset + TestPattern::SCROLL_STEP) % eyeWidth;
+			bound[0].uMin = bound[1].uMin = shift;
+			bound[0].uMax = bound[1].uMax = shift + 1.0f;
+			bound[0].vMin = bound[1].vMin = 0.0f;
+			bound[0].vMax = bound[1].vMax = 1.0f;
+		}
+		else if (i == recenterLayer) {
 			textures[0] = (ID3D11Texture2D *)m_recenterTexture.Get();
 			textures[1] = (ID3D11Texture2D *)m_recenterTexture.Get();
 			bound[0].uMin = bound[0].vMin = bound[1].uMin = bound[1].vMin = 0.0f;
@@ -601,6 +622,39 @@ bool FrameRender::RenderFrame(ID3D11Texture2D *pTexture[][2], vr::VRTextureBound
 	return true;
 }
 
+bool FrameRender::CreateTestPatternTexture()
+{
+	if (m_testPatternTexture) {
+		return true;
+	}
+
+	uint32_t width = Settings::Instance().m_encodeWidth / 2;
+	uint32_t height = Settings::Instance().m_encodeHeight;
+	auto pixels = TestPattern::Generate(width, height, width, false);
+
+	D3D11_TEXTURE2D_DESC desc = {};
+	desc.Width = width;
+	desc.Height = height;
+	desc.MipLevels = 1;
+	desc.ArraySize = 1;
+	desc.Format = DXGI_FORMAT_R8G8B8A8_UNORM_SRGB;
+	desc.SampleDesc.Count = 1;
+	desc.Usage = D3D11_USAGE_IMMUTABLE;
+	desc.BindFlags = D3D11_BIND_SHADER_RESOURCE;
+
+	D3D11_SUBRESOURCE_DATA data = {};
+	data.pSysMem = pixels.data();
+	data.SysMemPitch = width * sizeof(uint32_t);
+
+	HRESULT hr = m_pD3DRender->GetDevice()->CreateTexture2D(&desc, &data, &m_testPatternTexture);
+	if (FAILED(hr)) {
+		Error("Failed to create the test pattern texture %p %ls\n", hr, GetErrorStr(hr).c_str());
+		return false;
+	}
+
+	return true;
+}
+
 ComPtr<ID3D11Texture2D> FrameRender::GetTexture()
 {
 	return m_pStagingTexture;
@@ -611,8 +665,8 @@ void FrameRender::GetEncodingResolution(uint32_t *width, uint32_t *height) {
 		m_ffr->GetOptimizedResolution(width, height);
 	}
 	else {
-		*width = Settings::Instance().m_renderWidth;
-		*height = Settings::Instance().m_renderHeight;
+		*width = Settings::Instance().m_encodeWidth;
+		*height = Settings::Instance().m_encodeHeight;
 	}
 	
 }
\ No newline at end of file
@@ -78,6 +78,11 @@ private:
 	ComPtr<ID3D11Resource> m_messageBGTexture;
 	ComPtr<ID3D11ShaderResourceView> m_messageBGResourceView;
 
+	// Created at the first frame of the benchmark, with the size of an eye
+	ComPtr<ID3D11Texture2D> m_testPatternTexture;
+	uint32_t m_testPatternOffset = 0;
+	bool CreateTestPatternTexture();
+
 	struct SimpleVertex
 	{
 		DirectX::XMFLOAT3 Pos;
@@ -8,6 +8,9 @@ OvrDirectModeComponent::OvrDirectModeComponent(std::shared_ptr<CD3DRender> pD3DR
 }
 
 void OvrDirectModeComponent::SetEncoder(std::shared_ptr<CEncoder> pEncoder) {
+	// The encoder can be replaced during the stream
+	std::lock_guard<std::mutex> lock(m_presentMutex);
+
 	m_pEncoder = pEncoder;
 }
 
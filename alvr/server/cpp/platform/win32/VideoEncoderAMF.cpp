@@ -264,6 +264,21 @@ amf::AMFComponentPtr VideoEncoderAMF::MakeEncoder(
 
 		amfEncoder->SetProperty(AMF_VIDEO_ENCODER_MAX_NUM_REFRAMES, 0);
 
+		// Hierarchical P, with the top layer frames not referenced. The base layer is not counted in
+		// the enhancement layers
+		if (Settings::Instance().m_temporalLayers > 1) {
+			amf_int64 temporalLayers = Settings::Instance().m_temporalLayers;
+			amf_int64 maxTemporalLayers = 0;
+			if (caps == nullptr || caps->GetProperty(AMF_VIDEO_ENCODER_CAP_MAX_TEMPORAL_LAYERS, &maxTemporalLayers) != AMF_OK
+				|| maxTemporalLayers < temporalLayers) {
+				throw MakeException("This GPU does not support hierarchical P with %d temporal layers. Use P-only instead.", (int)temporalLayers);
+			}
+			amfEncoder->SetProperty(AMF_VIDEO_ENCODER_MAX_NUM_TEMPORAL_LAYERS, temporalLayers);
+			amfEncoder->SetProperty(AMF_VIDEO_ENCODER_NUM_TEMPORAL_ENHANCMENT_LAYERS, temporalLayers - 1);
+		}
+
+		amfEncoder->SetProperty(AMF_VIDEO_ENCODER_SLICES_PER_FRAME, (amf_int64)Settings::Instance().m_slicesCount);
+
 		if (m_hasQueryTimeout) {
 			amfEncoder->SetProperty(AMF_VIDEO_ENCODER_QUERY_TIMEOUT, 1000); // 1s timeout
 		}
@@ -363,6 +378,8 @@ amf::AMFComponentPtr VideoEncoderAMF::MakeEncoder(
 
 		amfEncoder->SetProperty(AMF_VIDEO_ENCODER_HEVC_MAX_NUM_REFRAMES, 0);
 
+		amfEncoder->SetProperty(AMF_VIDEO_ENCODER_HEVC_SLICES_PER_FRAME, (amf_int64)Settings::Instance().m_slicesCount);
+
 		if (m_hasQueryTimeout) {
 			amfEncoder->SetProperty(AMF_VIDEO_ENCODER_HEVC_QUERY_TIMEOUT, 1000); // 1s timeout
 		}
@@ -495,6 +512,9 @@ amf::AMFComponentPtr VideoEncoderAMF::MakePreprocessor(
 void VideoEncoderAMF::Initialize()
 {
 	Debug("Initializing VideoEncoderAMF.\n");
+	if (!Settings::Instance().m_qpDeltaMap.empty()) {
+		Warn("The QP delta map is not supported by AMF. Ignoring it.");
+	}
 	AMF_THROW_IF(g_AMFFactory.Init());
 
 	AMF_THROW_IF(g_AMFFactory.GetFactory()->CreateContext(&m_amfContext));
@@ -631,12 +651,35 @@ void VideoEncoderAMF::Receive(AMFDataPtr data)
 		isIdr = type == AMF_VIDEO_ENCODER_HEVC_OUTPUT_DATA_TYPE_IDR;
 	}
 
+	if (Settings::Instance().m_encoderQualityFeedback) {
+		double psnr = -1.0;
+		double ssim = -1.0;
+		switch (m_codec) {
+		case ALVR_CODEC_H264:
+			data->GetProperty(AMF_VIDEO_ENCODER_STATISTIC_PSNR_Y, &psnr);
+			data->GetProperty(AMF_VIDEO_ENCODER_STATISTIC_SSIM_Y, &ssim);
+			break;
+		case ALVR_CODEC_HEVC:
+			data->GetProperty(AMF_VIDEO_ENCODER_HEVC_STATISTIC_PSNR_Y, &psnr);
+			data->GetProperty(AMF_VIDEO_ENCODER_HEVC_STATISTIC_SSIM_Y, &ssim);
+			break;
+		case ALVR_CODEC_AV1:
+			data->GetProperty(AMF_VIDEO_ENCODER_AV1_STATISTIC_PSNR_Y, &psnr);
+			data->GetProperty(AMF_VIDEO_ENCODER_AV1_STATISTIC_SSIM_Y, &ssim);
+			break;
+		}
+		ReportEncodeQuality(psnr, ssim);
+	}
+
 	ParseFrameNals(m_codec, reinterpret_cast<uint8_t *>(p), length, targetTimestampNs, isIdr);
 }
 
 void VideoEncoderAMF::ApplyFrameProperties(const amf::AMFSurfacePtr &surface, bool insertIDR) {
+	bool qualityFeedback = Settings::Instance().m_encoderQualityFeedback;
 	switch (m_codec) {
 	case ALVR_CODEC_H264:
+		surface->SetProperty(AMF_VIDEO_ENCODER_PSNR_FEEDBACK, qualityFeedback);
+		surface->SetProperty(AMF_VIDEO_ENCODER_SSIM_FEEDBACK, qualityFeedback);
 		// FIXME: This option doesn't work in drivers 22.3.1 - 22.5.1, but works in 22.10.3
 		surface->SetProperty(AMF_VIDEO_ENCODER_INSERT_AUD, false);
 		if (insertIDR) {
@@ -647,6 +690,8 @@ void VideoEncoderAMF::ApplyFrameProperties(const amf::AMFSurfacePtr &surface, bo
 		}
 		break;
 	case ALVR_CODEC_HEVC:
+		surface->SetProperty(AMF_VIDEO_ENCODER_HEVC_PSNR_FEEDBACK, qualityFeedback);
+		surface->SetProperty(AMF_VIDEO_ENCODER_HEVC_SSIM_FEEDBACK, qualityFeedback);
 		// FIXME: This option works with 22.10.3, but may not work with older drivers
 		surface->SetProperty(AMF_VIDEO_ENCODER_HEVC_INSERT_AUD, false);
 		if (insertIDR) {
@@ -659,6 +704,8 @@ void VideoEncoderAMF::ApplyFrameProperties(const amf::AMFSurfacePtr &surface, bo
 		}
 		break;
 	case ALVR_CODEC_AV1:
+		surface->SetProperty(AMF_VIDEO_ENCODER_AV1_PSNR_FEEDBACK, qualityFeedback);
+		surface->SetProperty(AMF_VIDEO_ENCODER_AV1_SSIM_FEEDBACK, qualityFeedback);
 		if (insertIDR) {
 			Debug("Inserting IDR frame for AV1.\n");
 			surface->SetProperty(AMF_VIDEO_ENCODER_AV1_FORCE_INSERT_SEQUENCE_HEADER, true);
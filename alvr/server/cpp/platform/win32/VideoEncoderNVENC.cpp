@@ -41,6 +41,31 @@ void VideoEncoderNVENC::Initialize()
 		throw MakeException("NvEnc NvEncoderD3D11 failed. Code=%d %hs\n", e.getErrorCode(), e.what());
 	}
 
+	// The client tracks the frame references assuming the negotiated structure, the encoder must not
+	// fall back to P-only
+	uint32_t temporalLayers = Settings::Instance().m_temporalLayers;
+	if (temporalLayers > 1 && (m_codec != ALVR_CODEC_H264 ||
+		!m_NvNecoder->GetCapabilityValue(NV_ENC_CODEC_H264_GUID, NV_ENC_CAPS_SUPPORT_TEMPORAL_SVC) ||
+		m_NvNecoder->GetCapabilityValue(NV_ENC_CODEC_H264_GUID, NV_ENC_CAPS_NUM_MAX_TEMPORAL_LAYERS) < (int)temporalLayers)) {
+		throw MakeException("This GPU does not support hierarchical P with %d temporal layers. Use P-only instead.", temporalLayers);
+	}
+
+	// The map is computed for the frame size negotiated with the client. It is not applied after a
+	// change of the foveated encoding during the stream
+	m_qpDeltaMap.clear();
+	const auto &qpDeltaMap = Settings::Instance().m_qpDeltaMap;
+	if (!qpDeltaMap.empty()) {
+		// Macroblocks for h264, CTUs for HEVC and superblocks for AV1
+		uint32_t blockSize = m_codec == ALVR_CODEC_H264 ? 16 : (m_codec == ALVR_CODEC_HEVC ? 32 : 64);
+		uint32_t columns = (m_renderWidth + blockSize - 1) / blockSize;
+		uint32_t rows = (m_renderHeight + blockSize - 1) / blockSize;
+		if (Settings::Instance().m_qpDeltaMapColumns == columns && qpDeltaMap.size() == columns * rows) {
+			m_qpDeltaMap = qpDeltaMap;
+		} else {
+			Warn("The QP delta map does not match the encoder blocks of the frame. Ignoring it.");
+		}
+	}
+
 	NV_ENC_INITIALIZE_PARAMS initializeParams = { NV_ENC_INITIALIZE_PARAMS_VER };
 	NV_ENC_CONFIG encodeConfig = { NV_ENC_CONFIG_VER };
 	initializeParams.encodeConfig = &encodeConfig;
@@ -109,6 +134,10 @@ void VideoEncoderNVENC::Transmit(ID3D11Texture2D *pTexture, uint64_t presentatio
 		Debug("Inserting IDR frame.\n");
 		picParams.encodePicFlags = NV_ENC_PIC_FLAG_FORCEIDR;
 	}
+	if (!m_qpDeltaMap.empty()) {
+		picParams.qpDeltaMap = m_qpDeltaMap.data();
+		picParams.qpDeltaMapSize = (uint32_t)m_qpDeltaMap.size();
+	}
 	m_NvNecoder->EncodeFrame(vPacket, &picParams);
 
 	for (std::vector<uint8_t> &packet : vPacket)
@@ -220,6 +249,25 @@ void VideoEncoderNVENC::FillEncodeConfig(NV_ENC_INITIALIZE_PARAMS &initializePar
 		config.maxNumRefFrames = maxNumRefFrames;
 		config.idrPeriod = gopLength;
 
+		// Hierarchical P. The client tracks the references assuming the dyadic layer pattern that
+		// restarts at each IDR, where the frames of the top layer are not referenced
+		if (Settings::Instance().m_temporalLayers > 1) {
+			uint32_t temporalLayers = Settings::Instance().m_temporalLayers;
+			config.enableTemporalSVC = 1;
+			config.disableSVCPrefixNalu = 1;
+			config.numTemporalLayers = temporalLayers;
+			config.maxTemporalLayers = temporalLayers;
+			// Minimum DPB size documented by NVENC for hierarchical coding
+			if (maxNumRefFrames != 0 && maxNumRefFrames < (temporalLayers - 2) * 2) {
+				config.maxNumRefFrames = (temporalLayers - 2) * 2;
+			}
+		}
+
+		if (Settings::Instance().m_slicesCount > 1) {
+			config.sliceMode = 3;
+			config.sliceModeData = Settings::Instance().m_slicesCount;
+		}
+
 		if (Settings::Instance().m_fillerData) {
 			config.enableFillerDataInsertion = Settings::Instance().m_rateControlMode == ALVR_CBR;
 		}
@@ -238,6 +286,7 @@ void VideoEncoderNVENC::FillEncodeConfig(NV_ENC_INITIALIZE_PARAMS &initializePar
 			config.h264VUIParameters.transferCharacteristics = NV_ENC_VUI_TRANSFER_CHARACTERISTIC_SRGB;
 			config.h264VUIParameters.colourMatrix = NV_ENC_VUI_MATRIX_COEFFS_BT709;
 		}
+		break;
 	}
 	case ALVR_CODEC_HEVC:
 	{
@@ -255,6 +304,11 @@ void VideoEncoderNVENC::FillEncodeConfig(NV_ENC_INITIALIZE_PARAMS &initializePar
 		config.maxNumRefFramesInDPB = maxNumRefFrames;
 		config.idrPeriod = gopLength;
 
+		if (Settings::Instance().m_slicesCount > 1) {
+			config.sliceMode = 3;
+			config.sliceModeData = Settings::Instance().m_slicesCount;
+		}
+
 		if (Settings::Instance().m_use10bitEncoder) {
 			encodeConfig.encodeCodecConfig.hevcConfig.pixelBitDepthMinus8 = 2;
 		}
@@ -277,6 +331,7 @@ void VideoEncoderNVENC::FillEncodeConfig(NV_ENC_INITIALIZE_PARAMS &initializePar
 			config.hevcVUIParameters.transferCharacteristics = NV_ENC_VUI_TRANSFER_CHARACTERISTIC_SRGB;
 			config.hevcVUIParameters.colourMatrix = NV_ENC_VUI_MATRIX_COEFFS_BT709;
 		}
+		break;
 	}
 	case ALVR_CODEC_AV1:
 	{
@@ -314,6 +369,10 @@ void VideoEncoderNVENC::FillEncodeConfig(NV_ENC_INITIALIZE_PARAMS &initializePar
 	encodeConfig.rcParams.vbvInitialDelay = maxFrameSize * 1.1;
 	encodeConfig.rcParams.maxBitRate = static_cast<uint32_t>(bitrate_bps);
 	encodeConfig.rcParams.averageBitRate = static_cast<uint32_t>(bitrate_bps);
+	// The deltas are applied on top of the QP chosen by the rate control
+	if (!m_qpDeltaMap.empty()) {
+		encodeConfig.rcParams.qpMapMode = NV_ENC_QP_MAP_DELTA;
+	}
 	if (Settings::Instance().m_nvencAdaptiveQuantizationMode == SpatialAQ) {
 		encodeConfig.rcParams.enableAQ = 1;
 	} else if (Settings::Instance().m_nvencAdaptiveQuantizationMode == TemporalAQ) {
@@ -36,4 +36,5 @@ private:
 	int m_renderWidth;
 	int m_renderHeight;
 	int m_bitrateInMBits;
+	std::vector<int8_t> m_qpDeltaMap;
 };
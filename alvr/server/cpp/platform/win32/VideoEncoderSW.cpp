@@ -10,6 +10,8 @@
 #include <string>
 #include <array>
 #include <algorithm>
+#include <cmath>
+#include <cstring>
 
 VideoEncoderSW::VideoEncoderSW(std::shared_ptr<CD3DRender> d3dRender
 	, int width, int height)
@@ -35,12 +37,37 @@ void VideoEncoderSW::LibVALog(void* v, int level, const char* data, va_list va)
 	vprintf(sstream.str().c_str(), va);
 }
 
+void VideoEncoderSW::ReportLumaPsnr(AVPacket *packet) {
+	// The quality, the picture type, the error count, two reserved bytes, then the sum of the
+	// squared errors of each plane
+	size_t size = 0;
+	const uint8_t *stats = av_packet_get_side_data(packet, AV_PKT_DATA_QUALITY_STATS, &size);
+	if (stats == nullptr || size < 16 || stats[5] == 0) {
+		return;
+	}
+	uint64_t lumaError;
+	memcpy(&lumaError, stats + 8, sizeof(lumaError));
+
+	// Capped for the frames without any error
+	double psnr = 100.0;
+	if (lumaError > 0) {
+		double pixels = (double)m_renderWidth * m_renderHeight;
+		psnr = std::min(10.0 * log10(255.0 * 255.0 * pixels / lumaError), psnr);
+	}
+	ReportEncodeQuality(psnr, -1.0);
+}
+
 void VideoEncoderSW::Initialize() {
 	int err;
 	Debug("Initializing VideoEncoderSW.\n");
 
 	const auto& settings = Settings::Instance();
 
+	// The client would track the frame references assuming hierarchical P
+	if (settings.m_temporalLayers > 1) {
+		throw MakeException("Hierarchical P is not supported by the software encoder");
+	}
+
 	// Query codec
 	AVCodecID codecId = ToFFMPEGCodec(m_codec);
 	if(!codecId) throw MakeException("Invalid requested codec %d", m_codec);
@@ -111,6 +138,11 @@ void VideoEncoderSW::Initialize() {
 	}
 	m_codecContext->rc_max_rate = m_codecContext->bit_rate;
 	m_codecContext->thread_count = settings.m_swThreadCount;
+	m_codecContext->slices = settings.m_slicesCount;
+	if (settings.m_encoderQualityFeedback) {
+		// Only the PSNR is exported by libx264
+		m_codecContext->flags |= AV_CODEC_FLAG_PSNR;
+	}
 
 	if((err = avcodec_open2(m_codecContext, codec, &opt))) throw MakeException("Cannot open video encoder codec: %d", err);
 
@@ -226,6 +258,9 @@ void VideoEncoderSW::Transmit(ID3D11Texture2D *pTexture, uint64_t presentationTi
 		}
 		// Send encoded frame to client
 		bool isIdr = (packet->flags & AV_PKT_FLAG_KEY) != 0;
+		if (Settings::Instance().m_encoderQualityFeedback) {
+			ReportLumaPsnr(packet);
+		}
 		ParseFrameNals(m_codec, packet->data, packet->size, packet->pts, isIdr);
 		//Debug("Sent encoded packet to client");
 		av_packet_free(&packet);
@@ -36,6 +36,8 @@ public:
 	HRESULT SetupStagingTexture(ID3D11Texture2D *pTexture);
 	HRESULT CopyTexture(ID3D11Texture2D *pTexture);
 private:
+	void ReportLumaPsnr(AVPacket *packet);
+
     std::shared_ptr<CD3DRender> m_d3dRender;
 
 	AVCodecContext *m_codecContext;
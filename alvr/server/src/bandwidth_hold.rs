@@ -0,0 +1,234 @@
+use alvr_session::{settings_schema::Switch, BitrateMode, FreezeAndHoldConfig};
+use std::time::{Duration, Instant};
+
+// The hold applies only to the adaptive bitrate with a minimum bitrate, which is the floor. Returns
+// the floor and the config
+pub fn freeze_and_hold_config(mode: &BitrateMode) -> Option<(f32, &FreezeAndHoldConfig)> {
+    if let BitrateMode::Adaptive {
+        min_bitrate_mbps: Switch::Enabled(min_bitrate_mbps),
+        freeze_and_hold: Switch::Enabled(config),
+        ..
+    } = mode
+    {
+        Some((*min_bitrate_mbps as f32 * 1e6, config))
+    } else {
+        None
+    }
+}
+
+// Freeze and hold of the stream when the network cannot sustain even the minimum bitrate. Instead
+// of sending frames that would arrive late or corrupted, only IDR frames are sent while holding,
+// as periodic probes of the bandwidth, and the headset reprojects the last complete frame in
+// between. The hold is released after enough probes arrive with a throughput above the recovery
+// threshold, and the stream resumes from an IDR.
+pub struct BandwidthHold {
+    holding: bool,
+    below_floor_since: Option<Instant>,
+    recovered_probes: u32,
+    last_probe: Option<Instant>,
+    waiting_for_idr: bool,
+}
+
+impl BandwidthHold {
+    pub fn new() -> Self {
+        Self {
+            holding: false,
+            below_floor_since: None,
+            recovered_probes: 0,
+            last_probe: None,
+            waiting_for_idr: false,
+        }
+    }
+
+    pub fn is_holding(&self) -> bool {
+        self.holding
+    }
+
+    // Reports the throughput measured for a frame received by the client. Returns true if the
+    // hold state changed
+    pub fn report_throughput(
+        &mut self,
+        config: &FreezeAndHoldConfig,
+        floor_bps: f32,
+        throughput_bps: f32,
+        now: Instant,
+    ) -> bool {
+        if !self.holding {
+            if throughput_bps >= floor_bps {
+                self.below_floor_since = None;
+
+                return false;
+            }
+
+            let below_floor_since = *self.below_floor_since.get_or_insert(now);
+            if now.saturating_duration_since(below_floor_since)
+                >= Duration::from_millis(config.trigger_time_ms)
+            {
+                self.holding = true;
+                self.recovered_probes = 0;
+                self.last_probe = Some(now);
+
+                return true;
+            }
+        } else if throughput_bps >= floor_bps * config.recovery_threshold_multiplier {
+            self.recovered_probes += 1;
+
+            if self.recovered_probes >= config.recovery_probes {
+                return self.release();
+            }
+        } else {
+            self.recovered_probes = 0;
+        }
+
+        false
+    }
+
+    // Returns true if the hold state changed
+    pub fn release(&mut self) -> bool {
+        let was_holding = self.holding;
+        if was_holding {
+            self.waiting_for_idr = true;
+        }
+        self.holding = false;
+        self.below_floor_since = None;
+
+        was_holding
+    }
+
+    // Returns true if an IDR must be requested to probe the bandwidth
+    pub fn probe_due(&mut self, config: &FreezeAndHoldConfig, now: Instant) -> bool {
+        if !self.holding {
+            return false;
+        }
+
+        let due = self
+            .last_probe
+            .map(|last| {
+                now.saturating_duration_since(last)
+                    >= Duration::from_millis(config.probe_interval_ms)
+            })
+            .unwrap_or(true);
+        if due {
+            self.last_probe = Some(now);
+        }
+
+        due
+    }
+
+    // Returns false if the encoded frame must not be sent
+    pub fn frame_allowed(&mut self, is_idr: bool) -> bool {
+        if (self.holding || self.waiting_for_idr) && !is_idr {
+            return false;
+        }
+        self.waiting_for_idr = false;
+
+        true
+    }
+}
+
+impl Default for BandwidthHold {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FRAME_INTERVAL: Duration = Duration::from_micros(13_889);
+
+    fn config() -> FreezeAndHoldConfig {
+        FreezeAndHoldConfig {
+            trigger_time_ms: 500,
+            probe_interval_ms: 1000,
+            recovery_threshold_multiplier: 1.5,
+            recovery_probes: 2,
+        }
+    }
+
+    // Streams at 72fps for the duration, with the throughput measured for each frame sent. The
+    // encoder produces an IDR only when a probe is requested. Returns the frames sent, as is_idr,
+    // and the new hold states at each change
+    fn stream(
+        hold: &mut BandwidthHold,
+        time: &mut Instant,
+        throughput_mbps: f32,
+        duration_ms: u64,
+    ) -> (Vec<bool>, Vec<bool>) {
+        let mut sent_frames = vec![];
+        let mut changes = vec![];
+        let mut idr_requested = false;
+        for _ in 0..duration_ms * 1000 / FRAME_INTERVAL.as_micros() as u64 {
+            *time += FRAME_INTERVAL;
+
+            idr_requested |= hold.probe_due(&config(), *time);
+            let is_idr = std::mem::take(&mut idr_requested);
+
+            if hold.frame_allowed(is_idr) {
+                sent_frames.push(is_idr);
+
+                if hold.report_throughput(&config(), 5e6, throughput_mbps * 1e6, *time) {
+                    changes.push(hold.is_holding());
+
+                    // The IDR requested on resume
+                    idr_requested |= !hold.is_holding();
+                }
+            }
+        }
+
+        (sent_frames, changes)
+    }
+
+    #[test]
+    fn test_severe_drop_holds_and_recovery_resumes_from_idr() {
+        let mut time = Instant::now();
+        let mut hold = BandwidthHold::new();
+
+        // A drop that the network can still sustain at the floor bitrate does not hold
+        let (sent, changes) = stream(&mut hold, &mut time, 30.0, 1000);
+        assert!(sent.iter().all(|is_idr| !is_idr));
+        assert!(changes.is_empty());
+        let (_, changes) = stream(&mut hold, &mut time, 6.0, 2000);
+        assert!(changes.is_empty());
+
+        // A short dip below the floor is tolerated by the watchdog
+        assert!(stream(&mut hold, &mut time, 1.0, 400).1.is_empty());
+        assert!(stream(&mut hold, &mut time, 6.0, 100).1.is_empty());
+
+        let (_, changes) = stream(&mut hold, &mut time, 1.0, 600);
+        assert_eq!(changes, [true]);
+
+        // While holding only the probe IDRs are sent, one per second
+        let (sent, changes) = stream(&mut hold, &mut time, 1.0, 3000);
+        assert_eq!(sent, [true, true, true]);
+        assert!(changes.is_empty());
+
+        // Bandwidth above the floor but below the recovery threshold keeps the hold
+        let (sent, changes) = stream(&mut hold, &mut time, 6.0, 2000);
+        assert_eq!(sent.len(), 2);
+        assert!(changes.is_empty());
+
+        // The second probe above the threshold resumes the stream, starting from an IDR
+        let (sent, changes) = stream(&mut hold, &mut time, 30.0, 4000);
+        assert_eq!(changes, [false]);
+        assert_eq!(&sent[..4], [true, true, true, false]);
+        assert!(sent[3..].iter().all(|is_idr| !is_idr));
+        assert!(sent.len() > 100);
+    }
+
+    #[test]
+    fn test_release_waits_for_idr() {
+        let mut time = Instant::now();
+        let mut hold = BandwidthHold::new();
+
+        stream(&mut hold, &mut time, 1.0, 600);
+        assert!(hold.is_holding());
+
+        assert!(hold.release());
+        assert!(!hold.release());
+        assert!(!hold.frame_allowed(false));
+        assert!(hold.frame_allowed(true));
+        assert!(hold.frame_allowed(false));
+    }
+}
@@ -0,0 +1,317 @@
+use alvr_session::{BenchmarkConfig, CodecType};
+use std::{
+    fmt::Write,
+    mem,
+    time::{Duration, Instant},
+};
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct BenchmarkPoint {
+    pub codec: CodecType,
+    pub bitrate_mbps: u64,
+}
+
+// Statistics sampled during a segment. The counters and the quality sums are cumulative, the
+// latencies are the current averages
+#[derive(Clone, Copy, Default)]
+pub struct BenchmarkSample {
+    pub video_frames: usize,
+    pub video_bytes: usize,
+    pub packets_lost: usize,
+    pub total_pipeline_latency: Duration,
+    pub network_latency: Duration,
+    // Of the frames measured by the encoder
+    pub psnr_db_sum: f64,
+    pub psnr_frames: usize,
+    pub ssim_sum: f64,
+    pub ssim_frames: usize,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct SegmentResult {
+    pub total_pipeline_latency: Duration,
+    pub network_latency: Duration,
+    pub bandwidth_bps: f32,
+    pub packets_lost: usize,
+    // Averages of the luma, None if the encoder doesn't measure them
+    pub psnr_db: Option<f32>,
+    pub ssim: Option<f32>,
+}
+
+pub struct BenchmarkResult {
+    pub point: BenchmarkPoint,
+    // The reason if the point was skipped
+    pub outcome: Result<SegmentResult, String>,
+}
+
+pub enum BenchmarkAction {
+    Wait,
+    // Stream with the parameters of the point, then call report_applied() or report_skipped()
+    Apply(BenchmarkPoint),
+    Finished(Vec<BenchmarkResult>),
+}
+
+struct Segment {
+    measure_start: Instant,
+    end: Instant,
+    first_sample: Option<(Instant, BenchmarkSample)>,
+    latency_sums: (Duration, Duration),
+    latency_samples: u32,
+}
+
+enum State {
+    NextPoint,
+    Applying,
+    Measuring(Segment),
+    Done,
+}
+
+// Streams a short segment for each point of the parameter grid and measures it with the
+// statistics of the stream and the quality reported by the encoder. The first seconds of each
+// segment are discarded, to let the encoder and the averages settle.
+pub struct BenchmarkSweep {
+    points: Vec<BenchmarkPoint>,
+    warmup: Duration,
+    segment_duration: Duration,
+    results: Vec<BenchmarkResult>,
+    state: State,
+}
+
+impl BenchmarkSweep {
+    pub fn new(config: &BenchmarkConfig) -> Self {
+        // The bitrate is the cheapest parameter to change
+        let mut points = vec![];
+        for &codec in &config.codecs {
+            for &bitrate_mbps in &config.bitrates_mbps {
+                points.push(BenchmarkPoint {
+                    codec,
+                    bitrate_mbps,
+                });
+            }
+        }
+
+        Self {
+            points,
+            warmup: Duration::from_secs_f32(config.warmup_s),
+            segment_duration: Duration::from_secs_f32(config.segment_duration_s),
+            results: vec![],
+            state: State::NextPoint,
+        }
+    }
+
+    pub fn update(&mut self, now: Instant, sample: BenchmarkSample) -> BenchmarkAction {
+        loop {
+            match &mut self.state {
+                State::NextPoint => {
+                    if let Some(point) = self.points.get(self.results.len()) {
+                        self.state = State::Applying;
+
+                        return BenchmarkAction::Apply(*point);
+                    } else {
+                        self.state = State::Done;
+
+                        return BenchmarkAction::Finished(mem::take(&mut self.results));
+                    }
+                }
+                State::Applying | State::Done => return BenchmarkAction::Wait,
+                State::Measuring(segment) => {
+                    if now < segment.measure_start {
+                        return BenchmarkAction::Wait;
+                    }
+
+                    let (first_instant, first_sample) =
+                        *segment.first_sample.get_or_insert((now, sample));
+                    segment.latency_sums.0 += sample.total_pipeline_latency;
+                    segment.latency_sums.1 += sample.network_latency;
+                    segment.latency_samples += 1;
+
+                    if now < segment.end {
+                        return BenchmarkAction::Wait;
+                    }
+
+                    let frames = sample.video_frames - first_sample.video_frames;
+                    let bits = ((sample.video_bytes - first_sample.video_bytes) * 8) as f32;
+                    let average = |sum: f64, first_sum: f64, count: usize, first_count: usize| {
+                        (count > first_count)
+                            .then(|| ((sum - first_sum) / (count - first_count) as f64) as f32)
+                    };
+                    let outcome = if frames == 0 || now == first_instant {
+                        Err("No frame was encoded".into())
+                    } else {
+                        Ok(SegmentResult {
+                            total_pipeline_latency: segment.latency_sums.0
+                                / segment.latency_samples,
+                            network_latency: segment.latency_sums.1 / segment.latency_samples,
+                            bandwidth_bps: bits / (now - first_instant).as_secs_f32(),
+                            packets_lost: sample.packets_lost - first_sample.packets_lost,
+                            psnr_db: average(
+                                sample.psnr_db_sum,
+                                first_sample.psnr_db_sum,
+                                sample.psnr_frames,
+                                first_sample.psnr_frames,
+                            ),
+                            ssim: average(
+                                sample.ssim_sum,
+                                first_sample.ssim_sum,
+                                sample.ssim_frames,
+                                first_sample.ssim_frames,
+                            ),
+                        })
+                    };
+
+                    self.results.push(BenchmarkResult {
+                        point: self.points[self.results.len()],
+                        outcome,
+                    });
+                    self.state = State::NextPoint;
+                }
+            }
+        }
+    }
+
+    // The stream uses the parameters of the current point from now
+    pub fn report_applied(&mut self, now: Instant) {
+        let measure_start = now + self.warmup;
+        self.state = State::Measuring(Segment {
+            measure_start,
+            end: measure_start + self.segment_duration,
+            first_sample: None,
+            latency_sums: (Duration::ZERO, Duration::ZERO),
+            latency_samples: 0,
+        });
+    }
+
+    pub fn report_skipped(&mut self, reason: &str) {
+        self.results.push(BenchmarkResult {
+            point: self.points[self.results.len()],
+            outcome: Err(reason.to_owned()),
+        });
+        self.state = State::NextPoint;
+    }
+}
+
+pub struct BenchmarkRun {
+    pub sweep: BenchmarkSweep,
+    // The stream parameters override the settings, which are left unchanged
+    pub point: Option<BenchmarkPoint>,
+}
+
+pub fn results_table(results: &[BenchmarkResult]) -> String {
+    let mut table = format!(
+        "{:<6} {:>9} {:>10} {:>10} {:>11} {:>6} {:>9} {:>6}",
+        "Codec", "Bitrate", "Latency", "Network", "Bandwidth", "Lost", "PSNR", "SSIM"
+    );
+
+    for BenchmarkResult { point, outcome } in results {
+        let bitrate = format!("{} Mbps", point.bitrate_mbps);
+        write!(table, "\n{:<6} {bitrate:>9}", format!("{:?}", point.codec)).ok();
+
+        match outcome {
+            Ok(result) => {
+                let psnr = result
+                    .psnr_db
+                    .map(|psnr| format!("{psnr:.2} dB"))
+                    .unwrap_or_else(|| "n/a".into());
+                let ssim = result
+                    .ssim
+                    .map(|ssim| format!("{ssim:.4}"))
+                    .unwrap_or_else(|| "n/a".into());
+                write!(
+                    table,
+                    " {:>10} {:>10} {:>11} {:>6} {psnr:>9} {ssim:>6}",
+                    format!(
+                        "{:.1} ms",
+                        result.total_pipeline_latency.as_secs_f32() * 1000.0
+                    ),
+                    format!("{:.1} ms", result.network_latency.as_secs_f32() * 1000.0),
+                    format!("{:.1} Mbps", result.bandwidth_bps / 1e6),
+                    result.packets_lost,
+                )
+                .ok();
+            }
+            Err(reason) => {
+                write!(table, " Skipped: {reason}").ok();
+            }
+        }
+    }
+
+    table
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FPS: f32 = 72.0;
+
+    #[test]
+    fn test_two_point_sweep_produces_complete_results() {
+        let config = BenchmarkConfig {
+            bitrates_mbps: vec![30, 60],
+            codecs: vec![CodecType::Hevc],
+            test_pattern: true,
+            measure_encoding_quality: true,
+            warmup_s: 1.0,
+            segment_duration_s: 5.0,
+        };
+        let mut sweep = BenchmarkSweep::new(&config);
+
+        let tick = Duration::from_millis(500);
+        let mut now = Instant::now();
+        let mut sample = BenchmarkSample {
+            total_pipeline_latency: Duration::from_millis(50),
+            network_latency: Duration::from_millis(8),
+            ..Default::default()
+        };
+        let mut bitrate_mbps = 0;
+        let mut applied = vec![];
+
+        let results = loop {
+            match sweep.update(now, sample) {
+                BenchmarkAction::Wait => (),
+                BenchmarkAction::Apply(point) => {
+                    bitrate_mbps = point.bitrate_mbps;
+                    applied.push(point);
+                    sweep.report_applied(now);
+                }
+                BenchmarkAction::Finished(results) => break results,
+            }
+
+            // The encoder hits the target bitrate, with a lost packet per second. It measures the
+            // PSNR but not the SSIM
+            now += tick;
+            let frames = (FPS * tick.as_secs_f32()) as usize;
+            sample.video_frames += frames;
+            sample.video_bytes += (bitrate_mbps as f32 * 1e6 / 8.0 * tick.as_secs_f32()) as usize;
+            sample.psnr_db_sum += (30.0 + bitrate_mbps as f64 / 10.0) * frames as f64;
+            sample.psnr_frames += frames;
+            if sample.video_frames % 72 == 0 {
+                sample.packets_lost += 1;
+            }
+        };
+
+        assert_eq!(applied.len(), 2);
+        assert_eq!(results.len(), 2);
+        for (result, point) in results.iter().zip(&applied) {
+            assert_eq!(result.point, *point);
+
+            let segment = result.outcome.as_ref().unwrap();
+            assert_eq!(segment.total_pipeline_latency, Duration::from_millis(50));
+            assert_eq!(segment.network_latency, Duration::from_millis(8));
+            assert!((segment.bandwidth_bps / (point.bitrate_mbps as f32 * 1e6) - 1.0).abs() < 0.01);
+            assert_eq!(segment.packets_lost, 5);
+
+            let psnr_db = 30.0 + point.bitrate_mbps as f32 / 10.0;
+            assert!((segment.psnr_db.unwrap() - psnr_db).abs() < 0.01);
+            assert_eq!(segment.ssim, None);
+        }
+
+        let table = results_table(&results);
+        let rows = table.lines().collect::<Vec<_>>();
+        assert_eq!(rows.len(), 3);
+        assert!(rows[1].starts_with("Hevc     30 Mbps"));
+        assert!(rows[1].ends_with("33.00 dB    n/a"));
+        assert!(rows[2].contains("60.0 Mbps"));
+        assert!(!table.contains("Skipped"));
+    }
+}
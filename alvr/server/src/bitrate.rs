@@ -1,3 +1,4 @@
+use crate::congestion_control::DelayBasedEstimator;
 use alvr_common::SlidingWindowAverage;
 use alvr_events::NominalBitrateStats;
 use alvr_session::{
@@ -25,11 +26,16 @@ pub struct BitrateManager {
     network_latency_average: SlidingWindowAverage<Duration>,
     bitrate_average: SlidingWindowAverage<f32>,
     decoder_latency_overstep_count: usize,
+    delay_based_estimator: DelayBasedEstimator,
     last_frame_instant: Instant,
     last_update_instant: Instant,
     dynamic_max_bitrate: f32,
     previous_config: Option<BitrateConfig>,
     update_needed: bool,
+    last_target: Option<(f32, Duration)>,
+    // Adaptive bitrate before the manual bounds and the low power profile
+    bandwidth_estimate: Option<f32>,
+    low_power: bool,
 }
 
 impl BitrateManager {
@@ -51,11 +57,15 @@ impl BitrateManager {
             ),
             bitrate_average: SlidingWindowAverage::new(30_000_000.0, max_history_size),
             decoder_latency_overstep_count: 0,
+            delay_based_estimator: DelayBasedEstimator::new(),
             last_frame_instant: Instant::now(),
             last_update_instant: Instant::now(),
             dynamic_max_bitrate: f32::MAX,
             previous_config: None,
+            last_target: None,
+            bandwidth_estimate: None,
             update_needed: true,
+            low_power: false,
         }
     }
 
@@ -96,24 +106,42 @@ impl BitrateManager {
     }
 
     // decoder_latency is used to learn a suitable maximum bitrate bound to avoid decoder runaway
-    // latency
+    // latency. Returns the throughput measured for the frame
     pub fn report_frame_latencies(
         &mut self,
         config: &BitrateMode,
         timestamp: Duration,
         network_latency: Duration,
         decoder_latency: Duration,
-    ) {
+    ) -> Option<f32> {
         if network_latency.is_zero() {
-            return;
+            return None;
         }
 
         self.network_latency_average.submit_sample(network_latency);
 
+        let mut throughput_bps = None;
         while let Some(&(timestamp_, size_bits)) = self.packet_sizes_bits_history.front() {
             if timestamp_ == timestamp {
-                self.bitrate_average
-                    .submit_sample(size_bits as f32 / network_latency.as_secs_f32());
+                let throughput = size_bits as f32 / network_latency.as_secs_f32();
+                self.bitrate_average.submit_sample(throughput);
+                throughput_bps = Some(throughput);
+
+                if let BitrateMode::Adaptive {
+                    congestion_controller: Switch::Enabled(config),
+                    ..
+                } = &config
+                {
+                    // React to congestion without waiting for the next periodic update
+                    if self.delay_based_estimator.report_frame(
+                        config,
+                        timestamp,
+                        network_latency,
+                        size_bits,
+                    ) {
+                        self.update_needed = true;
+                    }
+                }
 
                 self.packet_sizes_bits_history.pop_front();
 
@@ -144,6 +172,30 @@ impl BitrateManager {
                 self.decoder_latency_overstep_count = 0;
             }
         }
+
+        throughput_bps
+    }
+
+    pub fn report_packet_loss(&mut self) {
+        self.delay_based_estimator.report_packet_loss();
+    }
+
+    // Applies the low power profile of the idle power saving
+    pub fn set_low_power(&mut self, low_power: bool) {
+        if low_power != self.low_power {
+            self.low_power = low_power;
+            self.update_needed = true;
+        }
+    }
+
+    // Bitrate and frame interval last requested to the encoder
+    pub fn last_target(&self) -> Option<(f32, Duration)> {
+        self.last_target
+    }
+
+    // Bandwidth available to the video, as estimated by the adaptive bitrate
+    pub fn bandwidth_estimate(&self) -> Option<f32> {
+        self.bandwidth_estimate
     }
 
     pub fn get_encoder_params(
@@ -172,7 +224,7 @@ impl BitrateManager {
 
         let mut stats = NominalBitrateStats::default();
 
-        let bitrate_bps = match &config.mode {
+        let mut bitrate_bps = match &config.mode {
             BitrateMode::ConstantMbps(bitrate_mbps) => *bitrate_mbps as f32 * 1e6,
             BitrateMode::Adaptive {
                 saturation_multiplier,
@@ -180,6 +232,7 @@ impl BitrateManager {
                 min_bitrate_mbps,
                 max_network_latency_ms,
                 encoder_latency_limiter,
+                congestion_controller,
                 ..
             } => {
                 let initial_bitrate_average_bps = self.bitrate_average.get_average();
@@ -212,6 +265,16 @@ impl BitrateManager {
                     }
                 }
 
+                if congestion_controller.enabled() {
+                    if let Some(max) = self.delay_based_estimator.estimate_bps() {
+                        bitrate_bps = f32::min(bitrate_bps, max);
+
+                        stats.congestion_controller_bps = Some(max);
+                    }
+                }
+
+                self.bandwidth_estimate = Some(bitrate_bps);
+
                 if let Switch::Enabled(max) = max_bitrate_mbps {
                     let max = *max as f32 * 1e6;
                     bitrate_bps = f32::min(bitrate_bps, max);
@@ -229,14 +292,21 @@ impl BitrateManager {
             }
         };
 
-        stats.requested_bps = bitrate_bps;
-
-        let frame_interval = if config.adapt_to_framerate.enabled() {
+        let mut frame_interval = if config.adapt_to_framerate.enabled() {
             self.frame_interval_average.get_average()
         } else {
             self.nominal_frame_interval
         };
 
+        if let (true, Switch::Enabled(idle_config)) = (self.low_power, &config.idle_power_saving) {
+            bitrate_bps = f32::min(bitrate_bps, idle_config.low_power_bitrate_mbps as f32 * 1e6);
+            frame_interval *= u32::max(idle_config.low_power_frame_divider, 1);
+        }
+
+        stats.requested_bps = bitrate_bps;
+
+        self.last_target = Some((bitrate_bps, frame_interval));
+
         Some((
             DynamicEncoderParams {
                 bitrate_bps: bitrate_bps as u64,
@@ -1,6 +1,8 @@
 #![allow(dead_code, unused_variables)]
 
-use crate::{logging_backend, ServerCoreContext, ServerCoreEvent, SERVER_DATA_MANAGER};
+use crate::{
+    frame_user_data, logging_backend, ServerCoreContext, ServerCoreEvent, SERVER_DATA_MANAGER,
+};
 use alvr_common::{
     log,
     once_cell::sync::Lazy,
@@ -124,6 +126,7 @@ pub enum AlvrEvent {
     RequestIDR,
     RestartPending,
     ShutdownPending,
+    StreamPaused(bool),
 }
 
 #[repr(C)]
@@ -314,6 +317,13 @@ pub unsafe extern "C" fn alvr_poll_event(out_event: *mut AlvrEvent) -> bool {
                     *out_event = AlvrEvent::ButtonsUpdated;
                 }
                 ServerCoreEvent::RequestIDR => *out_event = AlvrEvent::RequestIDR,
+                ServerCoreEvent::FoveatedEncoding { .. } => {} // not sent to C API servers
+                ServerCoreEvent::VideoCodec { .. } => {}       // not sent to C API servers
+                ServerCoreEvent::BenchmarkTestPattern(_) => {} // not sent to C API servers
+                ServerCoreEvent::StreamPaused(paused) => {
+                    *out_event = AlvrEvent::StreamPaused(paused);
+                }
+                ServerCoreEvent::EncodeFrameDivider(_) => {} // not sent to C API servers
                 ServerCoreEvent::GameRenderLatencyFeedback(_) => {} // implementation not needed
                 ServerCoreEvent::RestartPending => {
                     *out_event = AlvrEvent::RestartPending;
@@ -480,6 +490,29 @@ pub extern "C" fn alvr_send_video_nal(
     }
 }
 
+// Stop sending video while keeping the connection alive. The stream resumes with an IDR
+#[no_mangle]
+pub extern "C" fn alvr_set_stream_paused(paused: bool) {
+    if let Some(context) = &*SERVER_CORE_CONTEXT.read() {
+        context.set_stream_paused(paused);
+    }
+}
+
+// Must be called before alvr_send_video_nal() for the same frame
+#[no_mangle]
+pub unsafe extern "C" fn alvr_set_frame_user_data(
+    timestamp_ns: u64,
+    buffer_ptr: *const u8,
+    len: u32,
+) {
+    if let Some(context) = &*SERVER_CORE_CONTEXT.read() {
+        context.set_frame_user_data(
+            Duration::from_nanos(timestamp_ns),
+            frame_user_data::user_data_from_raw(buffer_ptr, len),
+        );
+    }
+}
+
 // Returns true if updated
 #[no_mangle]
 pub unsafe extern "C" fn alvr_get_dynamic_encoder_params(
@@ -509,6 +542,17 @@ pub extern "C" fn alvr_report_composed(timestamp_ns: u64, offset_ns: u64) {
     }
 }
 
+/// Negative values if not measured by the encoder
+#[no_mangle]
+pub extern "C" fn alvr_report_encode_quality(psnr_db: f64, ssim: f64) {
+    if let Some(context) = &*SERVER_CORE_CONTEXT.read() {
+        context.report_encode_quality(
+            (psnr_db >= 0.0).then_some(psnr_db),
+            (ssim >= 0.0).then_some(ssim),
+        );
+    }
+}
+
 #[no_mangle]
 pub extern "C" fn alvr_report_present(timestamp_ns: u64, offset_ns: u64) {
     if let Some(context) = &*SERVER_CORE_CONTEXT.read() {
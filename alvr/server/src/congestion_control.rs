@@ -0,0 +1,360 @@
+use alvr_session::CongestionController;
+use std::{collections::VecDeque, time::Duration};
+
+// Delay based bandwidth estimation, modeled after Google Congestion Control (GCC). The slope of
+// the queueing delay is estimated from the inter-frame delay variation. When the delay keeps
+// growing the link is overused and the estimate is reduced before any packet is lost.
+
+const TRENDLINE_WINDOW_SIZE: usize = 20;
+const TRENDLINE_SMOOTHING: f64 = 0.9;
+const TRENDLINE_THRESHOLD_GAIN: f64 = 4.0;
+const MAX_DELTAS_COUNT: usize = 60;
+
+// Adaptive threshold parameters (ms)
+const INITIAL_THRESHOLD: f64 = 12.5;
+const MIN_THRESHOLD: f64 = 6.0;
+const MAX_THRESHOLD: f64 = 600.0;
+const MAX_THRESHOLD_STEP: f64 = 15.0;
+const THRESHOLD_UP_GAIN: f64 = 0.0087;
+const THRESHOLD_DOWN_GAIN: f64 = 0.039;
+const OVERUSE_TIME_THRESHOLD: Duration = Duration::from_millis(10);
+
+const MIN_DECREASE_INTERVAL: Duration = Duration::from_millis(200);
+const MAX_RECEIVED_RATE_MULTIPLIER: f32 = 1.5;
+const RECEIVED_RATE_WINDOW: Duration = Duration::from_millis(500);
+
+const LOSS_HISTORY_SIZE: usize = 60;
+const LOSS_FRACTION_THRESHOLD: f32 = 0.1;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum BandwidthUsage {
+    Normal,
+    Overusing,
+    Underusing,
+}
+
+pub struct DelayBasedEstimator {
+    last_frame: Option<(Duration, Duration)>,
+    first_arrival: Option<Duration>,
+    accumulated_delay_ms: f64,
+    smoothed_delay_ms: f64,
+    deltas_count: usize,
+    trendline_samples: VecDeque<(f64, f64)>,
+    previous_trend: f64,
+    threshold: f64,
+    last_threshold_update: Option<Duration>,
+    overuse_start: Option<Duration>,
+    usage: BandwidthUsage,
+    received_bits_history: VecDeque<(Duration, usize)>,
+    estimate_bps: Option<f32>,
+    last_estimate_update: Option<Duration>,
+    last_decrease: Option<Duration>,
+    loss_history: VecDeque<bool>,
+}
+
+impl DelayBasedEstimator {
+    pub fn new() -> Self {
+        Self {
+            last_frame: None,
+            first_arrival: None,
+            accumulated_delay_ms: 0.0,
+            smoothed_delay_ms: 0.0,
+            deltas_count: 0,
+            trendline_samples: VecDeque::new(),
+            previous_trend: 0.0,
+            threshold: INITIAL_THRESHOLD,
+            last_threshold_update: None,
+            overuse_start: None,
+            usage: BandwidthUsage::Normal,
+            received_bits_history: VecDeque::new(),
+            estimate_bps: None,
+            last_estimate_update: None,
+            last_decrease: None,
+            loss_history: VecDeque::new(),
+        }
+    }
+
+    pub fn usage(&self) -> BandwidthUsage {
+        self.usage
+    }
+
+    // Combined delay based and loss based estimate. None until enough frames are received
+    pub fn estimate_bps(&self) -> Option<f32> {
+        let loss_fraction = self.loss_history.iter().filter(|lost| **lost).count() as f32
+            / usize::max(self.loss_history.len(), 1) as f32;
+
+        // Loss is only taken into account when the delay signal missed the congestion
+        let loss_multiplier = if loss_fraction > LOSS_FRACTION_THRESHOLD {
+            1.0 - 0.5 * loss_fraction
+        } else {
+            1.0
+        };
+
+        self.estimate_bps.map(|bps| bps * loss_multiplier)
+    }
+
+    // A frame sent at send_timestamp finished being received after network_latency.
+    // Returns true if the link started being overused with this frame
+    pub fn report_frame(
+        &mut self,
+        config: &CongestionController,
+        send_timestamp: Duration,
+        network_latency: Duration,
+        size_bits: usize,
+    ) -> bool {
+        let arrival = send_timestamp + network_latency;
+
+        self.loss_history.push_back(false);
+        if self.loss_history.len() > LOSS_HISTORY_SIZE {
+            self.loss_history.pop_front();
+        }
+
+        self.received_bits_history.push_back((arrival, size_bits));
+        while let Some(&(oldest_arrival, _)) = self.received_bits_history.front() {
+            if oldest_arrival + RECEIVED_RATE_WINDOW < arrival {
+                self.received_bits_history.pop_front();
+            } else {
+                break;
+            }
+        }
+
+        let previous_usage = self.usage;
+
+        if let Some((last_send, last_arrival)) = self.last_frame {
+            // Frames can be reordered by the latency sampling, skip them
+            if send_timestamp <= last_send || arrival < last_arrival {
+                return false;
+            }
+
+            let delay_variation_ms = (arrival - last_arrival).as_secs_f64() * 1000.0
+                - (send_timestamp - last_send).as_secs_f64() * 1000.0;
+
+            let trend = self.update_trendline(arrival, delay_variation_ms);
+            self.detect_usage(arrival, trend);
+            self.update_estimate(config, arrival);
+        }
+        self.last_frame = Some((send_timestamp, arrival));
+
+        previous_usage != BandwidthUsage::Overusing && self.usage == BandwidthUsage::Overusing
+    }
+
+    pub fn report_packet_loss(&mut self) {
+        if let Some(lost) = self.loss_history.back_mut() {
+            *lost = true;
+        }
+    }
+
+    fn received_rate_bps(&self) -> Option<f32> {
+        let first = self.received_bits_history.front()?.0;
+        let last = self.received_bits_history.back()?.0;
+        if last <= first {
+            return None;
+        }
+
+        // The bits of the first frame arrived before the start of the window
+        let bits = self
+            .received_bits_history
+            .iter()
+            .skip(1)
+            .map(|(_, bits)| *bits)
+            .sum::<usize>();
+
+        Some(bits as f32 / (last - first).as_secs_f32())
+    }
+
+    // Returns the modified trend, the slope of the queueing delay scaled to be compared with the
+    // threshold
+    fn update_trendline(&mut self, arrival: Duration, delay_variation_ms: f64) -> f64 {
+        let first_arrival = *self.first_arrival.get_or_insert(arrival);
+
+        self.deltas_count = usize::min(self.deltas_count + 1, MAX_DELTAS_COUNT);
+        self.accumulated_delay_ms += delay_variation_ms;
+        self.smoothed_delay_ms = TRENDLINE_SMOOTHING * self.smoothed_delay_ms
+            + (1.0 - TRENDLINE_SMOOTHING) * self.accumulated_delay_ms;
+
+        self.trendline_samples.push_back((
+            (arrival - first_arrival).as_secs_f64() * 1000.0,
+            self.smoothed_delay_ms,
+        ));
+        if self.trendline_samples.len() > TRENDLINE_WINDOW_SIZE {
+            self.trendline_samples.pop_front();
+        }
+
+        if self.trendline_samples.len() < TRENDLINE_WINDOW_SIZE {
+            return 0.0;
+        }
+
+        // Least squares slope
+        let count = self.trendline_samples.len() as f64;
+        let mean_x = self.trendline_samples.iter().map(|(x, _)| x).sum::<f64>() / count;
+        let mean_y = self.trendline_samples.iter().map(|(_, y)| y).sum::<f64>() / count;
+        let (numerator, denominator) =
+            self.trendline_samples
+                .iter()
+                .fold((0.0, 0.0), |(numerator, denominator), (x, y)| {
+                    (
+                        numerator + (x - mean_x) * (y - mean_y),
+                        denominator + (x - mean_x) * (x - mean_x),
+                    )
+                });
+        let slope = if denominator > 0.0 {
+            numerator / denominator
+        } else {
+            0.0
+        };
+
+        slope * self.deltas_count as f64 * TRENDLINE_THRESHOLD_GAIN
+    }
+
+    fn detect_usage(&mut self, arrival: Duration, trend: f64) {
+        if trend > self.threshold {
+            let overuse_start = *self.overuse_start.get_or_insert(arrival);
+
+            if arrival - overuse_start >= OVERUSE_TIME_THRESHOLD && trend >= self.previous_trend {
+                self.usage = BandwidthUsage::Overusing;
+            }
+        } else if trend < -self.threshold {
+            self.overuse_start = None;
+            self.usage = BandwidthUsage::Underusing;
+        } else {
+            self.overuse_start = None;
+            self.usage = BandwidthUsage::Normal;
+        }
+        self.previous_trend = trend;
+
+        // The threshold follows the trend, so that the detector is not starved by concurrent TCP
+        // flows, but it reacts slowly when the trend goes above the threshold
+        let elapsed_ms = self
+            .last_threshold_update
+            .map(|last| (arrival - last).as_secs_f64() * 1000.0)
+            .unwrap_or(0.0);
+        self.last_threshold_update = Some(arrival);
+
+        if trend.abs() - self.threshold <= MAX_THRESHOLD_STEP {
+            let gain = if trend.abs() < self.threshold {
+                THRESHOLD_DOWN_GAIN
+            } else {
+                THRESHOLD_UP_GAIN
+            };
+            self.threshold += f64::min(elapsed_ms, 100.0) * gain * (trend.abs() - self.threshold);
+            self.threshold = self.threshold.clamp(MIN_THRESHOLD, MAX_THRESHOLD);
+        }
+    }
+
+    fn update_estimate(&mut self, config: &CongestionController, arrival: Duration) {
+        let Some(received_rate_bps) = self.received_rate_bps() else {
+            return;
+        };
+
+        let elapsed = self
+            .last_estimate_update
+            .map(|last| arrival.saturating_sub(last))
+            .unwrap_or_default();
+        self.last_estimate_update = Some(arrival);
+
+        let estimate_bps = *self.estimate_bps.get_or_insert(received_rate_bps);
+
+        let estimate_bps = match self.usage {
+            BandwidthUsage::Overusing => {
+                if self
+                    .last_decrease
+                    .map(|last| arrival >= last + MIN_DECREASE_INTERVAL)
+                    .unwrap_or(true)
+                {
+                    self.last_decrease = Some(arrival);
+
+                    f32::min(estimate_bps, received_rate_bps) * config.overuse_decrease_multiplier
+                } else {
+                    estimate_bps
+                }
+            }
+            // Let the queues drain
+            BandwidthUsage::Underusing => estimate_bps,
+            BandwidthUsage::Normal => f32::min(
+                estimate_bps
+                    * config
+                        .increase_multiplier_per_second
+                        .powf(elapsed.as_secs_f32()),
+                received_rate_bps * MAX_RECEIVED_RATE_MULTIPLIER,
+            ),
+        };
+
+        self.estimate_bps = Some(estimate_bps);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FRAME_INTERVAL: Duration = Duration::from_micros(11_111);
+    const BITRATE_BPS: f32 = 30e6;
+
+    fn config() -> CongestionController {
+        CongestionController {
+            overuse_decrease_multiplier: 0.85,
+            increase_multiplier_per_second: 1.08,
+        }
+    }
+
+    fn frame_bits() -> usize {
+        (BITRATE_BPS * FRAME_INTERVAL.as_secs_f32()) as usize
+    }
+
+    #[test]
+    fn test_stable_delay_keeps_estimate() {
+        let mut estimator = DelayBasedEstimator::new();
+
+        for i in 0..360 {
+            estimator.report_frame(
+                &config(),
+                FRAME_INTERVAL * i,
+                Duration::from_millis(5),
+                frame_bits(),
+            );
+            assert_ne!(estimator.usage(), BandwidthUsage::Overusing);
+        }
+
+        // The estimate can probe above the current rate but it is bounded by what is received
+        let estimate = estimator.estimate_bps().unwrap();
+        assert!(estimate >= BITRATE_BPS * 0.99);
+        assert!(estimate <= BITRATE_BPS * MAX_RECEIVED_RATE_MULTIPLIER * 1.01);
+    }
+
+    #[test]
+    fn test_growing_delay_reduces_estimate_without_loss() {
+        let mut estimator = DelayBasedEstimator::new();
+
+        let mut timestamp = Duration::ZERO;
+        for _ in 0..180 {
+            estimator.report_frame(&config(), timestamp, Duration::from_millis(5), frame_bits());
+            timestamp += FRAME_INTERVAL;
+        }
+        let stable_estimate = estimator.estimate_bps().unwrap();
+
+        // The queue grows by 1ms each frame, no packet is lost
+        let mut latency = Duration::from_millis(5);
+        let mut overuse_latency = None;
+        for _ in 0..60 {
+            latency += Duration::from_millis(1);
+            if estimator.report_frame(&config(), timestamp, latency, frame_bits()) {
+                overuse_latency.get_or_insert(latency);
+            }
+            timestamp += FRAME_INTERVAL;
+        }
+
+        // The overuse is detected early, while the queueing delay is still small
+        assert!(overuse_latency.unwrap() < Duration::from_millis(30));
+        assert!(estimator.estimate_bps().unwrap() < stable_estimate * 0.85);
+        assert!(estimator.estimate_bps().unwrap() < BITRATE_BPS);
+
+        // Packet loss reduces the estimate further
+        let delay_estimate = estimator.estimate_bps().unwrap();
+        for _ in 0..20 {
+            estimator.report_frame(&config(), timestamp, latency, frame_bits());
+            estimator.report_packet_loss();
+            timestamp += FRAME_INTERVAL;
+        }
+        assert!(estimator.estimate_bps().unwrap() < delay_estimate);
+    }
+}
@@ -0,0 +1,202 @@
+use alvr_common::settings_schema::Switch;
+use alvr_session::ConnectRetryConfig;
+use std::{
+    collections::{HashMap, HashSet},
+    net::IpAddr,
+    time::{Duration, Instant},
+};
+
+#[derive(PartialEq, Eq, Debug)]
+pub enum RetryState {
+    Retrying { attempt: u32, delay: Duration },
+    // No more attempts until the client is found with a new IP
+    Failed { attempts: u32 },
+}
+
+pub struct ConnectAttempt {
+    pub hostname: String,
+    pub ips: Vec<IpAddr>,
+    pub attempt: u32,
+}
+
+struct Target {
+    ips: HashSet<IpAddr>,
+    failed_attempts: u32,
+    next_attempt: Instant,
+}
+
+// Schedules the connection attempts to each client. The targets are fed by the manual IPs and by
+// the discovery beacons of the clients. After a failed attempt the next one is delayed with an
+// exponential backoff, a new IP restarts the attempts immediately.
+#[derive(Default)]
+pub struct ConnectRetry {
+    targets: HashMap<String, Target>,
+}
+
+impl ConnectRetry {
+    pub fn add_target(&mut self, hostname: &str, ip: IpAddr, now: Instant) {
+        let target = self
+            .targets
+            .entry(hostname.to_owned())
+            .or_insert_with(|| Target {
+                ips: HashSet::new(),
+                failed_attempts: 0,
+                next_attempt: now,
+            });
+
+        if target.ips.insert(ip) {
+            target.failed_attempts = 0;
+            target.next_attempt = now;
+        }
+    }
+
+    pub fn retain_targets(&mut self, mut filter: impl FnMut(&str) -> bool) {
+        self.targets.retain(|hostname, _| filter(hostname));
+    }
+
+    pub fn due_attempts(&self, config: &ConnectRetryConfig, now: Instant) -> Vec<ConnectAttempt> {
+        self.targets
+            .iter()
+            .filter(|(_, target)| !is_failed(config, target) && target.next_attempt <= now)
+            .map(|(hostname, target)| ConnectAttempt {
+                hostname: hostname.clone(),
+                ips: target.ips.iter().copied().collect(),
+                attempt: target.failed_attempts + 1,
+            })
+            .collect()
+    }
+
+    pub fn report_failure(
+        &mut self,
+        config: &ConnectRetryConfig,
+        hostname: &str,
+        now: Instant,
+    ) -> Option<RetryState> {
+        let target = self.targets.get_mut(hostname)?;
+        target.failed_attempts += 1;
+
+        if is_failed(config, target) {
+            return Some(RetryState::Failed {
+                attempts: target.failed_attempts,
+            });
+        }
+
+        let delay = Duration::min(
+            Duration::from_millis(config.initial_backoff_ms)
+                .saturating_mul(2_u32.saturating_pow(target.failed_attempts - 1)),
+            Duration::from_millis(config.max_backoff_ms),
+        );
+        target.next_attempt = now + delay;
+
+        Some(RetryState::Retrying {
+            attempt: target.failed_attempts + 1,
+            delay,
+        })
+    }
+
+    // The next disconnection is retried immediately
+    pub fn report_success(&mut self, hostname: &str, now: Instant) {
+        if let Some(target) = self.targets.get_mut(hostname) {
+            target.failed_attempts = 0;
+            target.next_attempt = now;
+        }
+    }
+}
+
+fn is_failed(config: &ConnectRetryConfig, target: &Target) -> bool {
+    matches!(config.max_attempts, Switch::Enabled(max) if target.failed_attempts >= max)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::Ipv4Addr;
+
+    const CLIENT: &str = "1234.client.local.";
+    const IP: IpAddr = IpAddr::V4(Ipv4Addr::new(192, 168, 1, 10));
+
+    fn config(max_attempts: Switch<u32>) -> ConnectRetryConfig {
+        ConnectRetryConfig {
+            initial_backoff_ms: 500,
+            max_backoff_ms: 4000,
+            max_attempts,
+        }
+    }
+
+    #[test]
+    fn test_retries_with_increasing_backoff_until_connected() {
+        let config = config(Switch::Disabled);
+        let mut retry = ConnectRetry::default();
+        let mut now = Instant::now();
+
+        retry.add_target(CLIENT, IP, now);
+
+        let mut delays = vec![];
+        for attempt in 1..=6 {
+            let attempts = retry.due_attempts(&config, now);
+            assert_eq!(attempts.len(), 1);
+            assert_eq!(attempts[0].hostname, CLIENT);
+            assert_eq!(attempts[0].ips, [IP]);
+            assert_eq!(attempts[0].attempt, attempt);
+
+            // The client is not reachable yet
+            let Some(RetryState::Retrying {
+                attempt: next,
+                delay,
+            }) = retry.report_failure(&config, CLIENT, now)
+            else {
+                panic!("Unexpected state");
+            };
+            assert_eq!(next, attempt + 1);
+            delays.push(delay.as_millis());
+
+            // No attempts before the backoff elapses
+            assert!(retry
+                .due_attempts(&config, now + delay - Duration::from_millis(1))
+                .is_empty());
+            now += delay;
+        }
+        assert_eq!(delays, [500, 1000, 2000, 4000, 4000, 4000]);
+
+        // The client appears and the connection succeeds
+        assert_eq!(retry.due_attempts(&config, now)[0].attempt, 7);
+        retry.report_success(CLIENT, now);
+        assert_eq!(retry.due_attempts(&config, now)[0].attempt, 1);
+    }
+
+    #[test]
+    fn test_gives_up_until_new_ip() {
+        let config = config(Switch::Enabled(3));
+        let mut retry = ConnectRetry::default();
+        let mut now = Instant::now();
+
+        retry.add_target(CLIENT, IP, now);
+        for _ in 0..2 {
+            assert!(matches!(
+                retry.report_failure(&config, CLIENT, now),
+                Some(RetryState::Retrying { .. })
+            ));
+            now += Duration::from_secs(10);
+        }
+        assert_eq!(
+            retry.report_failure(&config, CLIENT, now),
+            Some(RetryState::Failed { attempts: 3 })
+        );
+        assert!(retry
+            .due_attempts(&config, now + Duration::from_secs(60))
+            .is_empty());
+
+        // Beacons from the same IP don't restart the attempts
+        retry.add_target(CLIENT, IP, now);
+        assert!(retry.due_attempts(&config, now).is_empty());
+
+        let new_ip = IpAddr::V4(Ipv4Addr::new(192, 168, 1, 11));
+        retry.add_target(CLIENT, new_ip, now);
+        let attempts = retry.due_attempts(&config, now);
+        assert_eq!(attempts[0].attempt, 1);
+        assert_eq!(attempts[0].ips.len(), 2);
+
+        retry.retain_targets(|hostname| hostname != CLIENT);
+        assert!(retry.due_attempts(&config, now).is_empty());
+    }
+}
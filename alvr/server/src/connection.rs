@@ -1,43 +1,57 @@
 use crate::{
+    bandwidth_hold::{self, BandwidthHold},
+    benchmark::{self, BenchmarkAction, BenchmarkPoint},
     bitrate::BitrateManager,
     body_tracking::BodyTrackingSink,
+    connect_retry::{ConnectRetry, RetryState},
     face_tracking::FaceTrackingSink,
+    fast_reconnect::{self, ReconnectKind},
+    foveation::{self, FoveationEpochs},
     hand_gestures::{trigger_hand_gesture_actions, HandGestureManager, HAND_GESTURE_BUTTON_SET},
+    idle::IdleDetector,
     input_mapping::ButtonMappingManager,
+    joint_resolution::{self, JointResolutionController},
+    qp_map::{self, QpDeltaBlocks},
     sockets::WelcomeSocket,
     statistics::StatisticsManager,
-    tracking::{self, TrackingManager},
-    ConnectionContext, ServerCoreEvent, ViewsConfig, SERVER_DATA_MANAGER,
+    stream_pause::StreamPause,
+    stream_rng,
+    tracking::{self, CoordinateTransform, TrackingManager},
+    update_bandwidth_hold, update_idle_power_saving, update_stream_pause, ConnectionContext,
+    ServerCoreEvent, ViewsConfig, SERVER_DATA_MANAGER,
 };
 use alvr_audio::AudioDevice;
 use alvr_common::{
-    con_bail, debug, error,
+    anyhow, con_bail, debug, error,
     glam::{Quat, UVec2, Vec2, Vec3},
     info,
     parking_lot::{Condvar, Mutex, RwLock},
     settings_schema::Switch,
-    warn, AnyhowToCon, ConResult, ConnectionError, ConnectionState, LifecycleState, Pose,
-    BUTTON_INFO, CONTROLLER_PROFILE_INFO, DEVICE_ID_TO_PATH, HAND_LEFT_ID, HAND_RIGHT_ID, HEAD_ID,
-    QUEST_CONTROLLER_PROFILE_PATH,
+    warn, AnyhowToCon, ConResult, ConnectionError, ConnectionPhase, ConnectionState,
+    ConnectionStateMachine, LifecycleState, Pose, RngSource, BUTTON_INFO, CONTROLLER_PROFILE_INFO,
+    DEVICE_ID_TO_PATH, HAND_LEFT_ID, HAND_RIGHT_ID, HEAD_ID, QUEST_CONTROLLER_PROFILE_PATH,
 };
-use alvr_events::{ButtonEvent, EventType, TrackingEvent};
+use alvr_events::{ButtonEvent, ConnectionTransitionEvent, EventType, TrackingEvent};
 use alvr_packets::{
     BatteryInfo, ClientConnectionResult, ClientControlPacket, ClientListAction, ClientStatistics,
     NegotiatedStreamingConfig, ReservedClientControlPacket, ServerControlPacket, Tracking,
-    VideoPacketHeader, AUDIO, HAPTICS, STATISTICS, TRACKING, VIDEO,
+    VideoPacketHeader, VideoStreamingCapabilities, AUDIO, HAPTICS, STATISTICS, TRACKING, VIDEO,
 };
 use alvr_session::{
-    BodyTrackingConfig, BodyTrackingSinkConfig, CodecType, ControllersEmulationMode, FrameSize,
-    H264Profile, OpenvrConfig, SessionConfig,
+    BodyTrackingConfig, BodyTrackingSinkConfig, CodecType, ControllersEmulationMode,
+    FoveatedEncodingConfig, FrameSize, GameAudioMode, H264Profile, OpenvrConfig,
+    ReferenceStructure, SessionConfig, Settings, SocketProtocol, VideoLossRecovery,
 };
 use alvr_sockets::{
-    PeerType, ProtoControlSocket, StreamSocketBuilder, KEEPALIVE_INTERVAL, KEEPALIVE_TIMEOUT,
+    ControlSocketSender, NetworkSimulation, PacingConfig, PeerType, ProtoControlSocket,
+    StreamSocketBuilder, KEEPALIVE_INTERVAL, KEEPALIVE_TIMEOUT,
 };
 use std::{
     collections::HashMap,
     net::IpAddr,
+    ops::Range,
     process::Command,
-    sync::{mpsc::RecvTimeoutError, Arc},
+    sync::{atomic::Ordering, mpsc::RecvTimeoutError, Arc},
     thread,
     time::{Duration, Instant},
 };
@@ -51,12 +65,28 @@ const MAX_UNREAD_PACKETS: usize = 10; // Applies per stream
 pub struct VideoPacket {
     pub header: VideoPacketHeader,
     pub payload: Vec<u8>,
+    // Ranges of the payload sent in separate packets
+    pub slices: Vec<Range<usize>>,
 }
 
 fn align32(value: f32) -> u32 {
     ((value / 32.).floor() * 32.) as u32
 }
 
+// Mirrors the foveation fields set by contruct_openvr_config()
+fn set_openvr_foveated_encoding(
+    openvr_config: &mut OpenvrConfig,
+    config: Option<&FoveatedEncodingConfig>,
+) {
+    openvr_config.enable_foveated_encoding = config.is_some();
+    openvr_config.foveation_center_size_x = config.map(|c| c.center_size_x).unwrap_or(0.0);
+    openvr_config.foveation_center_size_y = config.map(|c| c.center_size_y).unwrap_or(0.0);
+    openvr_config.foveation_center_shift_x = config.map(|c| c.center_shift_x).unwrap_or(0.0);
+    openvr_config.foveation_center_shift_y = config.map(|c| c.center_shift_y).unwrap_or(0.0);
+    openvr_config.foveation_edge_ratio_x = config.map(|c| c.edge_ratio_x).unwrap_or(0.0);
+    openvr_config.foveation_edge_ratio_y = config.map(|c| c.edge_ratio_y).unwrap_or(0.0);
+}
+
 fn is_streaming(client_hostname: &str) -> bool {
     SERVER_DATA_MANAGER
         .read()
@@ -66,6 +96,32 @@ fn is_streaming(client_hostname: &str) -> bool {
         .unwrap_or(false)
 }
 
+// Falls back to stereo if the layout of the device cannot be streamed
+fn game_audio_channels_count(mode: GameAudioMode, device: &AudioDevice) -> u16 {
+    match device
+        .input_channels_count()
+        .and_then(|count| alvr_audio::game_audio_channels_count(mode, count))
+    {
+        Ok(count) => count,
+        Err(e) => {
+            warn!("Multichannel game audio is not available, using stereo: {e}");
+
+            2
+        }
+    }
+}
+
+// Read again at each packet, the mode is a real-time setting
+fn game_audio_mode() -> Option<GameAudioMode> {
+    SERVER_DATA_MANAGER
+        .read()
+        .settings()
+        .audio
+        .game_audio
+        .as_option()
+        .map(|config| config.mode)
+}
+
 pub fn contruct_openvr_config(session: &SessionConfig) -> OpenvrConfig {
     let old_config = session.openvr_config.clone();
     let settings = session.to_settings();
@@ -159,6 +215,11 @@ pub fn contruct_openvr_config(session: &SessionConfig) -> OpenvrConfig {
         filler_data: settings.video.encoder_config.filler_data,
         entropy_coding: settings.video.encoder_config.entropy_coding as u32,
         use_10bit_encoder: settings.video.encoder_config.use_10bit,
+        temporal_layers: temporal_layers(settings.video.encoder_config.reference_structure),
+        slices_count: settings.video.encoder_config.slices_count,
+        // Depends on the negotiated codec and resolution
+        qp_delta_map: vec![],
+        qp_delta_map_columns: 0,
         use_full_range_encoding: settings.video.encoder_config.use_full_range,
         encoding_gamma: settings.video.encoder_config.encoding_gamma,
         enable_hdr: settings.video.encoder_config.enable_hdr,
@@ -216,11 +277,439 @@ pub fn contruct_openvr_config(session: &SessionConfig) -> OpenvrConfig {
         nvenc_enable_weighted_prediction: nvenc_overrides.enable_weighted_prediction,
         capture_frame_dir: settings.extra.capture.capture_frame_dir,
         amd_bitrate_corruption_fix: settings.video.bitrate.image_corruption_fix,
+        encoder_quality_feedback: settings.extra.benchmark.measure_encoding_quality,
         _controller_profile,
         ..old_config
     }
 }
 
+fn get_view_res(config: FrameSize, default_res: UVec2) -> UVec2 {
+    let res = match config {
+        FrameSize::Scale(scale) => default_res.as_vec2() * scale,
+        FrameSize::Absolute { width, height } => {
+            let width = width as f32;
+            Vec2::new(
+                width,
+                height.map(|h| h as f32).unwrap_or_else(|| {
+                    let default_res = default_res.as_vec2();
+                    width * default_res.y / default_res.x
+                }),
+            )
+        }
+    };
+
+    UVec2::new(align32(res.x), align32(res.y))
+}
+
+#[derive(Clone, Copy)]
+struct StreamParams {
+    view_resolution: UVec2,
+    upscaled_view_resolution: UVec2,
+    target_view_resolution: UVec2,
+    fps: f32,
+    enable_foveated_encoding: bool,
+    h264_profile: H264Profile,
+    codec: CodecType,
+    bit_depth: u8,
+    reference_structure: ReferenceStructure,
+    slices_count: u32,
+    enable_qp_delta_map: bool,
+}
+
+fn temporal_layers(reference_structure: ReferenceStructure) -> u32 {
+    match reference_structure {
+        ReferenceStructure::POnly => 1,
+        ReferenceStructure::HierarchicalP { temporal_layers } => temporal_layers as _,
+    }
+}
+
+// The QP delta map is laid over the eyes as they are encoded, compressed by the foveated encoding
+fn qp_delta_blocks(
+    settings: &Settings,
+    params: &StreamParams,
+) -> Option<anyhow::Result<QpDeltaBlocks>> {
+    let config = settings.video.encoder_config.qp_delta_map.as_option()?;
+
+    let eye_size = match settings.video.foveated_encoding.as_option() {
+        Some(foveation_config) if params.enable_foveated_encoding => {
+            match foveation::foveation_vars(foveation_config, params.view_resolution) {
+                Ok(vars) => vars.optimized_eye_size,
+                Err(e) => return Some(Err(e)),
+            }
+        }
+        _ => params.view_resolution,
+    };
+
+    Some(qp_map::qp_delta_blocks(config, params.codec, eye_size))
+}
+
+// Falls back to the best parameters supported by both the client and the server
+fn negotiate_stream_params(
+    settings: &Settings,
+    streaming_caps: &VideoStreamingCapabilities,
+) -> StreamParams {
+    let upscaled_view_resolution = get_view_res(
+        settings.video.transcoding_view_resolution,
+        streaming_caps.default_view_resolution,
+    );
+
+    // The compositor downscales the frames to the encode resolution, the client upscales them back
+    let view_resolution = match settings.video.max_encode_view_resolution.as_option() {
+        Some(config) => alvr_packets::cap_encode_resolution(
+            upscaled_view_resolution,
+            UVec2::new(config.width, config.height),
+        ),
+        None => upscaled_view_resolution,
+    };
+    if view_resolution != upscaled_view_resolution {
+        info!("Encode resolution capped from {upscaled_view_resolution} to {view_resolution}");
+    }
+
+    let target_view_resolution = get_view_res(
+        settings.video.emulated_headset_view_resolution,
+        streaming_caps.default_view_resolution,
+    );
+
+    let preferred_fps = settings.video.preferred_fps;
+    let supported_refresh_rates = &streaming_caps.supported_refresh_rates;
+    let fps = match alvr_packets::negotiate_refresh_rate(preferred_fps, supported_refresh_rates) {
+        Ok(fps) => {
+            if let Err(e) =
+                alvr_packets::validate_refresh_rate(preferred_fps, supported_refresh_rates)
+            {
+                warn!("{e}. Using {fps}Hz");
+            }
+
+            fps
+        }
+        Err(e) => {
+            warn!("{e}. Using {preferred_fps}Hz");
+
+            preferred_fps
+        }
+    };
+
+    let enable_foveated_encoding =
+        if let Switch::Enabled(config) = &settings.video.foveated_encoding {
+            let enable = streaming_caps.supports_foveated_encoding || config.force_enable;
+
+            if !enable {
+                warn!("Foveated encoding is not supported by the client.");
+            }
+
+            enable
+                && match foveation::foveation_vars(config, view_resolution) {
+                    Ok(vars) => {
+                        debug!(
+                            "Foveated encoding eye size: {}, eye size ratio: {}",
+                            vars.optimized_eye_size, vars.eye_size_ratio
+                        );
+
+                        true
+                    }
+                    Err(e) => {
+                        warn!("Invalid foveated encoding, disabling it: {e}");
+
+                        false
+                    }
+                }
+        } else {
+            false
+        };
+
+    let encoder_profile = if settings.video.encoder_config.h264_profile == H264Profile::High {
+        let profile = if streaming_caps.encoder_high_profile {
+            H264Profile::High
+        } else {
+            H264Profile::Main
+        };
+
+        if profile != H264Profile::High {
+            warn!("High profile encoding is not supported by the client.");
+        }
+
+        profile
+    } else {
+        settings.video.encoder_config.h264_profile
+    };
+
+    let codec = if settings.video.preferred_codec == CodecType::AV1 {
+        let codec = if streaming_caps.encoder_av1 {
+            CodecType::AV1
+        } else {
+            CodecType::Hevc
+        };
+
+        if codec != CodecType::AV1 {
+            warn!("AV1 encoding is not supported by the client.");
+        }
+
+        codec
+    } else {
+        settings.video.preferred_codec
+    };
+
+    let requested_bit_depth = if settings.video.encoder_config.use_10bit {
+        10
+    } else {
+        8
+    };
+    // The encoders support 10 bits only with HEVC and AV1
+    let server_max_bit_depth = if codec == CodecType::H264 { 8 } else { 10 };
+    let bit_depth = alvr_packets::negotiate_bit_depth(
+        requested_bit_depth,
+        server_max_bit_depth,
+        streaming_caps.max_bit_depth,
+    );
+    if bit_depth < requested_bit_depth {
+        if server_max_bit_depth < requested_bit_depth {
+            warn!("10 bits encoding is not supported with {codec:?}.");
+        } else {
+            warn!("10 bits encoding is not supported by the client.");
+        }
+    }
+
+    // Hierarchical P is configured only by the NVENC and AMF encoders, and NVENC supports it only
+    // with h264. The client must know the actual structure to track the frame references, so the
+    // encoders fail to initialize if the GPU does not support the temporal layers
+    let hierarchical_p_supported = cfg!(windows)
+        && codec == CodecType::H264
+        && !settings
+            .video
+            .encoder_config
+            .software
+            .force_software_encoding;
+    let reference_structure = match settings.video.encoder_config.reference_structure {
+        ReferenceStructure::HierarchicalP { .. } if !hierarchical_p_supported => {
+            warn!("Hierarchical P is supported only with h264 on Windows hardware encoders.");
+
+            ReferenceStructure::POnly
+        }
+        reference_structure => reference_structure,
+    };
+
+    // AV1 frames are split in tiles, without independent slice units
+    let slices_count = settings.video.encoder_config.slices_count.clamp(1, 16);
+    let slices_count = if codec == CodecType::AV1 && slices_count > 1 {
+        warn!("Slices are not supported with AV1.");
+
+        1
+    } else {
+        slices_count
+    };
+
+    let mut params = StreamParams {
+        view_resolution,
+        upscaled_view_resolution,
+        target_view_resolution,
+        fps,
+        enable_foveated_encoding,
+        h264_profile: encoder_profile,
+        codec,
+        bit_depth,
+        reference_structure,
+        slices_count,
+        enable_qp_delta_map: false,
+    };
+
+    // Only NVENC applies the map. The AMF encoder ignores it with a warning
+    if settings.video.encoder_config.qp_delta_map.enabled() {
+        if !cfg!(windows)
+            || settings
+                .video
+                .encoder_config
+                .software
+                .force_software_encoding
+        {
+            warn!("The QP delta map is supported only by NVENC on Windows.");
+        } else if let Some(Err(e)) = qp_delta_blocks(settings, &params) {
+            warn!("Invalid QP delta map, disabling it: {e}");
+        } else {
+            params.enable_qp_delta_map = true;
+        }
+    }
+
+    params
+}
+
+// The OpenVR config depends on the settings and on the negotiation with the client
+fn stream_openvr_config(session: &SessionConfig, params: &StreamParams) -> OpenvrConfig {
+    let mut config = contruct_openvr_config(session);
+    config.eye_resolution_width = params.view_resolution.x;
+    config.eye_resolution_height = params.view_resolution.y;
+    config.target_eye_resolution_width = params.target_view_resolution.x;
+    config.target_eye_resolution_height = params.target_view_resolution.y;
+    config.refresh_rate = params.fps as _;
+    config.enable_foveated_encoding = params.enable_foveated_encoding;
+    config.h264_profile = params.h264_profile as _;
+    config.use_10bit_encoder = params.bit_depth == 10;
+    config.temporal_layers = temporal_layers(params.reference_structure);
+    config.slices_count = params.slices_count;
+    config.codec = params.codec as _;
+    if params.enable_qp_delta_map {
+        if let Some(Ok(blocks)) = qp_delta_blocks(&session.to_settings(), params) {
+            config.qp_delta_map = blocks.deltas;
+            config.qp_delta_map_columns = blocks.columns;
+        }
+    }
+
+    config
+}
+
+// Restarts the encoder with the codec of the stream and the frames downscaled to the encode
+// resolution. The client keeps upscaling them to the negotiated view resolution
+fn restart_encoder(ctx: &ConnectionContext, params: &StreamParams, encode_resolution: UVec2) {
+    *ctx.encode_resolution.lock() =
+        (encode_resolution != params.view_resolution).then_some(encode_resolution);
+
+    let mut events_queue = ctx.events_queue.lock();
+    events_queue.push_back(ServerCoreEvent::EncodeResolution(encode_resolution));
+    events_queue.push_back(ServerCoreEvent::VideoCodec {
+        codec: params.codec,
+        h264_profile: params.h264_profile,
+    });
+}
+
+// Renegotiates the stream with the current settings. Encoder changes are applied in place, keeping
+// the connection and the pairing, in a fraction of the time of a full connection. So is the
+// resolution scale of the joint resolution. Other changes fall back to a SteamVR restart and a new
+// handshake. Returns true if the encoder is being restarted
+fn apply_fast_reconnect(
+    ctx: &ConnectionContext,
+    streaming_caps: &VideoStreamingCapabilities,
+    control_sender: &Mutex<ControlSocketSender<ServerControlPacket>>,
+) -> bool {
+    let mut data_manager = SERVER_DATA_MANAGER.write();
+
+    let params = negotiate_stream_params(data_manager.settings(), streaming_caps);
+    let new_openvr_config = stream_openvr_config(data_manager.session(), &params);
+
+    let maybe_scale = ctx.joint_resolution_scale.lock().filter(|_| {
+        joint_resolution::joint_resolution_config(&data_manager.settings().video.bitrate.mode)
+            .is_some()
+    });
+    let encode_resolution = maybe_scale
+        .map(|scale| {
+            joint_resolution::encode_resolution(
+                params.view_resolution,
+                params.upscaled_view_resolution,
+                scale,
+            )
+        })
+        .unwrap_or(params.view_resolution);
+    let current_encode_resolution = ctx
+        .encode_resolution
+        .lock()
+        .unwrap_or(params.view_resolution);
+
+    match fast_reconnect::reconnect(
+        &mut data_manager.session_mut().openvr_config,
+        &new_openvr_config,
+    ) {
+        ReconnectKind::Unchanged if encode_resolution == current_encode_resolution => {
+            info!("Fast reconnect: no stream parameter changed");
+
+            false
+        }
+        ReconnectKind::Unchanged | ReconnectKind::Fast => {
+            info!(
+                "Fast reconnect: restarting the encoder with {:?} at {encode_resolution}",
+                params.codec
+            );
+            restart_encoder(ctx, &params, encode_resolution);
+
+            true
+        }
+        ReconnectKind::Full => {
+            info!("Fast reconnect: the changed settings require a SteamVR restart");
+            data_manager.session_mut().openvr_config = new_openvr_config;
+
+            control_sender
+                .lock()
+                .send(&ServerControlPacket::Restarting)
+                .ok();
+            crate::notify_restart_driver();
+
+            false
+        }
+    }
+}
+
+// Streams with the codec of a benchmark point, which overrides the settings without changing
+// them. The bitrate is overridden by stream_bitrate_config(). Returns true if the encoder was
+// restarted, or the reason if the point cannot be applied during the stream
+fn apply_benchmark_point(
+    ctx: &ConnectionContext,
+    streaming_caps: &VideoStreamingCapabilities,
+    encoder_restart: bool,
+    point: BenchmarkPoint,
+) -> Result<bool, String> {
+    let mut data_manager = SERVER_DATA_MANAGER.write();
+
+    let mut settings = data_manager.settings().clone();
+    settings.video.preferred_codec = point.codec;
+    // The benchmark streams without the joint resolution
+    let params = negotiate_stream_params(&settings, streaming_caps);
+    if params.codec != point.codec {
+        return Err("The codec is not supported".into());
+    }
+    let new_openvr_config = stream_openvr_config(data_manager.session(), &params);
+
+    let downscaled = ctx.encode_resolution.lock().is_some();
+    let mut current_openvr_config = data_manager.session().openvr_config.clone();
+    match fast_reconnect::reconnect(&mut current_openvr_config, &new_openvr_config) {
+        ReconnectKind::Unchanged if !downscaled => Ok(false),
+        ReconnectKind::Unchanged | ReconnectKind::Fast if encoder_restart => {
+            data_manager.session_mut().openvr_config = current_openvr_config;
+            restart_encoder(ctx, &params, params.view_resolution);
+
+            Ok(true)
+        }
+        ReconnectKind::Unchanged | ReconnectKind::Fast => {
+            Err("The encoder cannot be restarted during the stream".into())
+        }
+        ReconnectKind::Full => Err("Requires a SteamVR restart".into()),
+    }
+}
+
+// Moves the connection to the client to the next phase and reports the transition to the dashboard.
+// The state machine rejects the actions that are not allowed in the current phase, like starting an
+// attempt while the client is streaming
+fn transition_connection_phase(
+    ctx: &ConnectionContext,
+    hostname: &str,
+    phase: ConnectionPhase,
+) -> anyhow::Result<()> {
+    let mut phases = ctx.connection_phases.lock();
+    let machine = phases.entry(hostname.to_owned()).or_insert_with(|| {
+        let mut machine = ConnectionStateMachine::new();
+        machine.add_observer({
+            let hostname = hostname.to_owned();
+            move |transition| {
+                alvr_events::send_event(EventType::ConnectionTransition(
+                    ConnectionTransitionEvent {
+                        hostname: hostname.clone(),
+                        from: transition.from.clone(),
+                        to: transition.to.clone(),
+                    },
+                ))
+            }
+        });
+
+        machine
+    });
+
+    machine.transition(phase)
+}
+
+// For the transitions that always follow from the previous phase. If rejected, it's a bug of the
+// connection logic, it is logged and ignored
+fn set_connection_phase(ctx: &ConnectionContext, hostname: &str, phase: ConnectionPhase) {
+    if let Err(e) = transition_connection_phase(ctx, hostname, phase) {
+        warn!("{hostname}: {e}");
+    }
+}
+
 // Alternate connection trials with manual IPs and clients discovered on the local network
 pub fn handshake_loop(ctx: Arc<ConnectionContext>, lifecycle_state: Arc<RwLock<LifecycleState>>) {
     let mut welcome_socket = match WelcomeSocket::new() {
@@ -231,103 +720,112 @@ pub fn handshake_loop(ctx: Arc<ConnectionContext>, lifecycle_state: Arc<RwLock<L
         }
     };
 
+    let mut connect_retry = ConnectRetry::default();
+
     while *lifecycle_state.read() != LifecycleState::ShuttingDown {
-        let available_manual_client_ips = {
-            let mut manual_client_ips = HashMap::new();
-            for (hostname, connection_info) in SERVER_DATA_MANAGER
-                .read()
-                .client_list()
-                .iter()
-                .filter(|(_, info)| info.connection_state == ConnectionState::Disconnected)
-            {
-                for ip in &connection_info.manual_ips {
-                    manual_client_ips.insert(*ip, hostname.clone());
+        let now = Instant::now();
+        let config = SERVER_DATA_MANAGER.read().settings().connection.clone();
+
+        for (hostname, connection_info) in SERVER_DATA_MANAGER.read().client_list() {
+            for ip in &connection_info.manual_ips {
+                connect_retry.add_target(hostname, *ip, now);
+            }
+        }
+
+        if let Switch::Enabled(discovery_config) = &config.client_discovery {
+            match welcome_socket.recv_all() {
+                Ok(clients) => {
+                    for (client_hostname, client_ip) in clients {
+                        let mut data_manager = SERVER_DATA_MANAGER.write();
+
+                        data_manager.update_client_list(
+                            client_hostname.clone(),
+                            ClientListAction::AddIfMissing {
+                                trusted: false,
+                                manual_ips: vec![],
+                            },
+                        );
+
+                        if discovery_config.auto_trust_clients {
+                            data_manager.update_client_list(
+                                client_hostname.clone(),
+                                ClientListAction::Trust,
+                            );
+                        }
+
+                        connect_retry.add_target(&client_hostname, client_ip, now);
+                    }
                 }
+                Err(e) => warn!("mDNS listening error: {e:?}"),
             }
-            manual_client_ips
-        };
+        }
 
-        if !available_manual_client_ips.is_empty()
-            && try_connect(
-                Arc::clone(&ctx),
-                Arc::clone(&lifecycle_state),
-                available_manual_client_ips,
-            )
-            .is_ok()
         {
-            thread::sleep(RETRY_CONNECT_MIN_INTERVAL);
-            continue;
+            let data_manager = SERVER_DATA_MANAGER.read();
+            connect_retry
+                .retain_targets(|hostname| data_manager.client_list().contains_key(hostname));
         }
 
-        let discovery_config = SERVER_DATA_MANAGER
-            .read()
-            .settings()
-            .connection
-            .client_discovery
-            .clone();
-        if let Switch::Enabled(config) = discovery_config {
-            let clients = match welcome_socket.recv_all() {
-                Ok(clients) => clients,
-                Err(e) => {
-                    warn!("mDNS listening error: {e:?}");
+        for attempt in connect_retry.due_attempts(&config.connect_retry, now) {
+            let hostname = attempt.hostname;
 
-                    thread::sleep(RETRY_CONNECT_MIN_INTERVAL);
-                    continue;
-                }
-            };
-
-            if clients.is_empty() {
-                thread::sleep(RETRY_CONNECT_MIN_INTERVAL);
+            // do not attempt connection if the client is already connected
+            if !SERVER_DATA_MANAGER
+                .read()
+                .client_list()
+                .get(&hostname)
+                .map(|c| c.trusted && c.connection_state == ConnectionState::Disconnected)
+                .unwrap_or(false)
+            {
+                continue;
+            }
+            // The connection thread might not have updated the client list yet. The attempt stays
+            // due until the state machine accepts it
+            if transition_connection_phase(&ctx, &hostname, ConnectionPhase::Discovering).is_err() {
                 continue;
             }
 
-            for (client_hostname, client_ip) in clients {
-                let trusted = {
-                    let mut data_manager = SERVER_DATA_MANAGER.write();
-
-                    data_manager.update_client_list(
-                        client_hostname.clone(),
-                        ClientListAction::AddIfMissing {
-                            trusted: false,
-                            manual_ips: vec![],
-                        },
-                    );
+            debug!("Connecting to {hostname} (attempt {})", attempt.attempt);
 
-                    if config.auto_trust_clients {
-                        data_manager
-                            .update_client_list(client_hostname.clone(), ClientListAction::Trust);
-                    }
+            let client_ips = attempt
+                .ips
+                .into_iter()
+                .map(|ip| (ip, hostname.clone()))
+                .collect();
+            match try_connect(
+                Arc::clone(&ctx),
+                Arc::clone(&lifecycle_state),
+                client_ips,
+                Duration::from_millis(config.connect_timeout_ms),
+            ) {
+                Ok(()) => connect_retry.report_success(&hostname, Instant::now()),
+                Err(e) => {
+                    let phase = match connect_retry.report_failure(
+                        &config.connect_retry,
+                        &hostname,
+                        Instant::now(),
+                    ) {
+                        Some(RetryState::Retrying { attempt, delay }) => {
+                            info!(
+                                "Could not connect to {hostname}: {e}. Attempt {attempt} in {:.1}s",
+                                delay.as_secs_f32()
+                            );
 
-                    data_manager
-                        .client_list()
-                        .get(&client_hostname)
-                        .map(|c| c.trusted)
-                        .unwrap_or(false)
-                };
+                            ConnectionPhase::Reconnecting
+                        }
+                        Some(RetryState::Failed { attempts }) => {
+                            warn!("Failed to connect to {hostname} after {attempts} attempts");
 
-                // do not attempt connection if the client is already connected
-                if trusted
-                    && SERVER_DATA_MANAGER
-                        .read()
-                        .client_list()
-                        .get(&client_hostname)
-                        .map(|c| c.connection_state == ConnectionState::Disconnected)
-                        .unwrap_or(false)
-                {
-                    if let Err(e) = try_connect(
-                        Arc::clone(&ctx),
-                        Arc::clone(&lifecycle_state),
-                        [(client_ip, client_hostname.clone())].into_iter().collect(),
-                    ) {
-                        error!("Could not initiate connection for {client_hostname}: {e}");
-                    }
+                            ConnectionPhase::Disconnected
+                        }
+                        None => ConnectionPhase::Disconnected,
+                    };
+                    set_connection_phase(&ctx, &hostname, phase);
                 }
-
-                thread::sleep(RETRY_CONNECT_MIN_INTERVAL);
             }
-        } else {
-            thread::sleep(RETRY_CONNECT_MIN_INTERVAL);
         }
+
+        thread::sleep(RETRY_CONNECT_MIN_INTERVAL);
     }
 
     // At this point, LIFECYCLE_STATE == ShuttingDown, so all threads are already terminating
@@ -340,9 +838,10 @@ fn try_connect(
     ctx: Arc<ConnectionContext>,
     lifecycle_state: Arc<RwLock<LifecycleState>>,
     mut client_ips: HashMap<IpAddr, String>,
+    timeout: Duration,
 ) -> ConResult {
     let (proto_socket, client_ip) = ProtoControlSocket::connect_to(
-        Duration::from_secs(1),
+        timeout,
         PeerType::AnyClient(client_ips.keys().cloned().collect()),
     )?;
 
@@ -350,26 +849,44 @@ fn try_connect(
         con_bail!("unreachable");
     };
 
+    set_connection_phase(&ctx, &client_hostname, ConnectionPhase::Handshaking);
+
     ctx.connection_threads.lock().push(thread::spawn({
         let ctx = Arc::clone(&ctx);
         move || {
-            if let Err(e) = connection_pipeline(
+            // After an error the handshake loop connects again right away
+            let phase = match connection_pipeline(
                 Arc::clone(&ctx),
                 lifecycle_state,
                 proto_socket,
                 client_hostname.clone(),
                 client_ip,
             ) {
-                error!("Handshake error for {client_hostname}: {e}");
-            }
+                Ok(()) => ConnectionPhase::Disconnected,
+                Err(e) => {
+                    error!("Handshake error for {client_hostname}: {e}");
+                    set_connection_phase(
+                        &ctx,
+                        &client_hostname,
+                        ConnectionPhase::Error(e.to_string()),
+                    );
+
+                    ConnectionPhase::Reconnecting
+                }
+            };
 
             let mut clients_to_be_removed = ctx.clients_to_be_removed.lock();
 
             let action = if clients_to_be_removed.contains(&client_hostname) {
                 clients_to_be_removed.remove(&client_hostname);
 
+                set_connection_phase(&ctx, &client_hostname, ConnectionPhase::Disconnected);
+                ctx.connection_phases.lock().remove(&client_hostname);
+
                 ClientListAction::RemoveEntry
             } else {
+                set_connection_phase(&ctx, &client_hostname, phase);
+
                 ClientListAction::SetConnectionState(ConnectionState::Disconnected)
             };
             SERVER_DATA_MANAGER
@@ -449,112 +966,18 @@ fn connection_pipeline(
 
     let settings = server_data_lock.settings().clone();
 
-    fn get_view_res(config: FrameSize, default_res: UVec2) -> UVec2 {
-        let res = match config {
-            FrameSize::Scale(scale) => default_res.as_vec2() * scale,
-            FrameSize::Absolute { width, height } => {
-                let width = width as f32;
-                Vec2::new(
-                    width,
-                    height.map(|h| h as f32).unwrap_or_else(|| {
-                        let default_res = default_res.as_vec2();
-                        width * default_res.y / default_res.x
-                    }),
-                )
-            }
-        };
-
-        UVec2::new(align32(res.x), align32(res.y))
-    }
-
-    let stream_view_resolution = get_view_res(
-        settings.video.transcoding_view_resolution,
-        streaming_caps.default_view_resolution,
-    );
-
-    let target_view_resolution = get_view_res(
-        settings.video.emulated_headset_view_resolution,
-        streaming_caps.default_view_resolution,
-    );
-
-    let fps = {
-        let mut best_match = 0_f32;
-        let mut min_diff = f32::MAX;
-        for rate in &streaming_caps.supported_refresh_rates {
-            let diff = (*rate - settings.video.preferred_fps).abs();
-            if diff < min_diff {
-                best_match = *rate;
-                min_diff = diff;
-            }
-        }
-        best_match
-    };
-
-    if !streaming_caps
-        .supported_refresh_rates
-        .contains(&settings.video.preferred_fps)
-    {
-        warn!("Chosen refresh rate not supported. Using {fps}Hz");
-    }
-
-    let enable_foveated_encoding = if let Switch::Enabled(config) = settings.video.foveated_encoding
-    {
-        let enable = streaming_caps.supports_foveated_encoding || config.force_enable;
-
-        if !enable {
-            warn!("Foveated encoding is not supported by the client.");
-        }
-
-        enable
-    } else {
-        false
-    };
-
-    let encoder_profile = if settings.video.encoder_config.h264_profile == H264Profile::High {
-        let profile = if streaming_caps.encoder_high_profile {
-            H264Profile::High
-        } else {
-            H264Profile::Main
-        };
-
-        if profile != H264Profile::High {
-            warn!("High profile encoding is not supported by the client.");
-        }
-
-        profile
-    } else {
-        settings.video.encoder_config.h264_profile
-    };
-
-    let enable_10_bits_encoding = if settings.video.encoder_config.use_10bit {
-        let enable = streaming_caps.encoder_10_bits;
-
-        if !enable {
-            warn!("10 bits encoding is not supported by the client.");
-        }
-
-        enable
-    } else {
-        false
-    };
-
-    let codec = if settings.video.preferred_codec == CodecType::AV1 {
-        let codec = if streaming_caps.encoder_av1 {
-            CodecType::AV1
-        } else {
-            CodecType::Hevc
-        };
-
-        if codec != CodecType::AV1 {
-            warn!("AV1 encoding is not supported by the client.");
-        }
-
-        codec
-    } else {
-        settings.video.preferred_codec
-    };
+    let stream_params = negotiate_stream_params(&settings, &streaming_caps);
+    let StreamParams {
+        view_resolution: stream_view_resolution,
+        upscaled_view_resolution,
+        fps,
+        enable_foveated_encoding,
+        bit_depth,
+        reference_structure,
+        ..
+    } = stream_params;
 
-    let game_audio_sample_rate =
+    let (game_audio_sample_rate, game_audio_channels_count) =
         if let Switch::Enabled(game_audio_config) = &settings.audio.game_audio {
             let game_audio_device = AudioDevice::new_output(
                 Some(settings.audio.linux_backend),
@@ -576,18 +999,63 @@ fn connection_pipeline(
                 }
             }
 
-            game_audio_device.input_sample_rate().to_con()?
+            (
+                game_audio_device.input_sample_rate().to_con()?,
+                game_audio_channels_count(game_audio_config.mode, &game_audio_device),
+            )
         } else {
-            0
+            (0, 0)
         };
 
+    let packet_size = if settings.connection.path_mtu_probing
+        && matches!(settings.connection.stream_protocol, SocketProtocol::Udp)
+    {
+        match alvr_sockets::probe_max_packet_size(client_ip, settings.connection.packet_size as _) {
+            Ok(size) => {
+                if size < settings.connection.packet_size as usize {
+                    info!("Path MTU probing: reducing packet size to {size}B");
+                }
+
+                size
+            }
+            Err(e) => {
+                warn!("Path MTU probing failed: {e}");
+
+                settings.connection.packet_size as _
+            }
+        }
+    } else {
+        settings.connection.packet_size as _
+    };
+
+    let audio_port = match settings.connection.separate_audio_port {
+        Switch::Enabled(_) if !streaming_caps.supports_separate_audio_socket => {
+            warn!("The client does not support a separate audio socket.");
+
+            None
+        }
+        Switch::Enabled(port) if port == settings.connection.stream_port => {
+            warn!("The separate audio port must be different from the stream port.");
+
+            None
+        }
+        Switch::Enabled(port) => Some(port),
+        Switch::Disabled => None,
+    };
+
     let stream_config_packet = alvr_packets::encode_stream_config(
         server_data_lock.session(),
         &NegotiatedStreamingConfig {
             view_resolution: stream_view_resolution,
+            upscaled_view_resolution,
             refresh_rate_hint: fps,
             game_audio_sample_rate,
+            game_audio_channels_count,
             enable_foveated_encoding,
+            packet_size,
+            bit_depth,
+            reference_structure,
+            audio_port,
         },
     )
     .to_con()?;
@@ -596,16 +1064,7 @@ fn connection_pipeline(
     let (mut control_sender, mut control_receiver) =
         proto_socket.split(STREAMING_RECV_TIMEOUT).to_con()?;
 
-    let mut new_openvr_config = contruct_openvr_config(server_data_lock.session());
-    new_openvr_config.eye_resolution_width = stream_view_resolution.x;
-    new_openvr_config.eye_resolution_height = stream_view_resolution.y;
-    new_openvr_config.target_eye_resolution_width = target_view_resolution.x;
-    new_openvr_config.target_eye_resolution_height = target_view_resolution.y;
-    new_openvr_config.refresh_rate = fps as _;
-    new_openvr_config.enable_foveated_encoding = enable_foveated_encoding;
-    new_openvr_config.h264_profile = encoder_profile as _;
-    new_openvr_config.use_10bit_encoder = enable_10_bits_encoding;
-    new_openvr_config.codec = codec as _;
+    let new_openvr_config = stream_openvr_config(server_data_lock.session(), &stream_params);
 
     if server_data_lock.session().openvr_config != new_openvr_config {
         server_data_lock.session_mut().openvr_config = new_openvr_config;
@@ -634,6 +1093,49 @@ fn connection_pipeline(
     ));
 
     *ctx.bitrate_manager.lock() = BitrateManager::new(settings.video.bitrate.history_size, fps);
+    // The clients that support changing the foveated encoding during the stream also rebuild their
+    // decoder when the encoder is restarted
+    let encoder_restart =
+        ctx.encoder_restart_supported.value() && streaming_caps.supports_runtime_foveation;
+
+    let joint_resolution_config =
+        joint_resolution::joint_resolution_config(&settings.video.bitrate.mode);
+    if joint_resolution_config.is_some() && !encoder_restart {
+        warn!("The joint resolution is disabled: it restarts the encoder during the stream, which is not supported on Linux or by the client");
+    }
+    *ctx.joint_resolution.lock() =
+        joint_resolution_config
+            .filter(|_| encoder_restart)
+            .map(|config| {
+                JointResolutionController::new(
+                    joint_resolution::operating_points(config, upscaled_view_resolution, fps),
+                    ctx.joint_resolution_scale.lock().unwrap_or(1.0),
+                )
+            });
+    *ctx.foveation_epochs.lock() = FoveationEpochs::new();
+    *ctx.bandwidth_hold.lock() = BandwidthHold::new();
+    let rng_source = RngSource::from_env_or(settings.extra.rng_seed.as_option().copied());
+    info!("Random seed of the stream: {}", rng_source.seed());
+    *ctx.rng_source.lock() = rng_source;
+    ctx.frame_user_data.lock().clear();
+    // The settings have just been negotiated
+    ctx.fast_reconnect_requested.set(false);
+    update_stream_pause(&ctx, |stream_pause| {
+        let was_paused = stream_pause.is_paused();
+        *stream_pause = StreamPause::new(settings.connection.pause_stream_on_headset_removal);
+
+        was_paused
+    });
+    update_idle_power_saving(
+        &ctx,
+        settings.video.bitrate.idle_power_saving.as_option(),
+        |idle_detector| {
+            let was_idle = idle_detector.is_idle();
+            *idle_detector = IdleDetector::new(Instant::now());
+
+            was_idle
+        },
+    );
 
     let mut stream_socket = StreamSocketBuilder::connect_to_client(
         HANDSHAKE_ACTION_TIMEOUT,
@@ -643,12 +1145,57 @@ fn connection_pipeline(
         settings.connection.dscp,
         settings.connection.server_send_buffer_bytes,
         settings.connection.server_recv_buffer_bytes,
-        settings.connection.packet_size as _,
+        packet_size,
     )?;
+    // With a dedicated socket, audio is never queued behind the video shards, in the OS buffers of
+    // either side
+    let mut audio_socket = if let Some(port) = audio_port {
+        Some(StreamSocketBuilder::connect_to_client(
+            HANDSHAKE_ACTION_TIMEOUT,
+            client_ip,
+            port,
+            settings.connection.stream_protocol,
+            settings.connection.dscp,
+            settings.connection.server_send_buffer_bytes,
+            settings.connection.server_recv_buffer_bytes,
+            packet_size,
+        )?)
+    } else {
+        None
+    };
+    // Both sockets go through the same link, so the audio bytes delay the paced video shards
+    if let Some(socket) = &mut audio_socket {
+        socket.share_pacing_budget(&stream_socket);
+    }
+    if let Switch::Enabled(config) = &settings.extra.network_simulation {
+        warn!(
+            "Simulating a network with {}% of loss and up to {} ms of jitter",
+            config.packet_loss_percent, config.max_jitter_ms
+        );
+        let simulation = NetworkSimulation {
+            loss_probability: config.packet_loss_percent / 100.0,
+            max_jitter: Duration::from_millis(config.max_jitter_ms),
+        };
+        stream_socket.simulate_network(simulation, stream_rng(&ctx, "network_simulation"));
+        if let Some(socket) = &audio_socket {
+            socket.simulate_network(simulation, stream_rng(&ctx, "audio_network_simulation"));
+        }
+    }
 
-    let mut video_sender = stream_socket.request_stream(VIDEO);
-    let game_audio_sender = stream_socket.request_stream(AUDIO);
-    let mut microphone_receiver = stream_socket.subscribe_to_stream(AUDIO, MAX_UNREAD_PACKETS);
+    let mut video_sender = if let VideoLossRecovery::Nack { max_age_ms, .. } =
+        settings.connection.video_loss_recovery
+    {
+        stream_socket.request_stream_with_retransmission(VIDEO, Duration::from_millis(max_age_ms))
+    } else {
+        stream_socket.request_stream(VIDEO)
+    };
+    let (game_audio_sender, mut microphone_receiver) = {
+        let socket = audio_socket.as_mut().unwrap_or(&mut stream_socket);
+        (
+            socket.request_stream(AUDIO),
+            socket.subscribe_to_stream(AUDIO, MAX_UNREAD_PACKETS),
+        )
+    };
     let mut tracking_receiver =
         stream_socket.subscribe_to_stream::<Tracking>(TRACKING, MAX_UNREAD_PACKETS);
     let haptics_sender = stream_socket.request_stream(HAPTICS);
@@ -661,28 +1208,75 @@ fn connection_pipeline(
     *ctx.haptics_sender.lock() = Some(haptics_sender);
 
     let video_send_thread = thread::spawn({
+        let ctx = Arc::clone(&ctx);
         let client_hostname = client_hostname.clone();
+        let packet_pacing = settings.connection.packet_pacing;
+        let video_loss_recovery = settings.connection.video_loss_recovery;
         move || {
             while is_streaming(&client_hostname) {
-                let VideoPacket { header, payload } =
-                    match video_channel_receiver.recv_timeout(STREAMING_RECV_TIMEOUT) {
-                        Ok(packet) => packet,
-                        Err(RecvTimeoutError::Timeout) => continue,
-                        Err(RecvTimeoutError::Disconnected) => return,
+                let VideoPacket {
+                    header,
+                    payload,
+                    slices,
+                } = match video_channel_receiver.recv_timeout(STREAMING_RECV_TIMEOUT) {
+                    Ok(packet) => packet,
+                    Err(RecvTimeoutError::Timeout) => continue,
+                    Err(RecvTimeoutError::Disconnected) => return,
+                };
+
+                if packet_pacing {
+                    let pacing = ctx.bitrate_manager.lock().last_target().map(
+                        |(bitrate_bps, frame_interval)| PacingConfig {
+                            bitrate_bps,
+                            frame_interval,
+                        },
+                    );
+                    video_sender.set_pacing(pacing);
+                }
+
+                if let VideoLossRecovery::Nack {
+                    max_age_rtts,
+                    max_age_ms,
+                } = video_loss_recovery
+                {
+                    // The round trip time is estimated as twice the network latency
+                    let rtt = ctx
+                        .statistics_manager
+                        .lock()
+                        .as_ref()
+                        .map(|stats| stats.network_latency_average() * 2)
+                        .unwrap_or_default();
+                    let max_age = Duration::from_millis(max_age_ms);
+
+                    video_sender.set_retransmission_max_age(if rtt.is_zero() {
+                        max_age
+                    } else {
+                        Duration::min(rtt.mul_f32(max_age_rtts), max_age)
+                    });
+                }
+
+                // Each slice starts a new packet, so a lost shard affects only its slice
+                for (slice_index, range) in slices.into_iter().enumerate() {
+                    let header = VideoPacketHeader {
+                        slice_index: slice_index as u32,
+                        ..header.clone()
                     };
+                    let mut buffer = video_sender.get_buffer(&header).unwrap();
+                    // todo: make encoder write to socket buffers directly to avoid copy
+                    buffer
+                        .get_range_mut(0, range.len())
+                        .copy_from_slice(&payload[range]);
+                    video_sender.send(buffer).ok();
+                }
 
-                let mut buffer = video_sender.get_buffer(&header).unwrap();
-                // todo: make encoder write to socket buffers directly to avoid copy
-                buffer
-                    .get_range_mut(0, payload.len())
-                    .copy_from_slice(&payload);
-                video_sender.send(buffer).ok();
+                if let Some(stats) = &mut *ctx.statistics_manager.lock() {
+                    stats.report_packet_pacing_interval(video_sender.pacing_interval());
+                }
             }
         }
     });
 
     let game_audio_thread = if let Switch::Enabled(config) = settings.audio.game_audio {
-        #[cfg(windows)]
         let ctx = Arc::clone(&ctx);
 
         let client_hostname = client_hostname.clone();
@@ -712,15 +1306,29 @@ fn connection_pipeline(
                     continue;
                 };
 
+                // The recording restarts with the new layout when the mode changes. Packets are
+                // tagged with their layout, so the client switches at the same packet
+                let mode = game_audio_mode().unwrap_or(config.mode);
+                let channels_count = game_audio_channels_count(mode, &device);
+
                 if let Err(e) = alvr_audio::record_audio_blocking(
                     Arc::new({
                         let client_hostname = client_hostname.clone();
-                        move || is_streaming(&client_hostname)
+                        move || {
+                            is_streaming(&client_hostname)
+                                && game_audio_mode().unwrap_or(mode) == mode
+                        }
                     }),
                     game_audio_sender.clone(),
                     &device,
-                    2,
+                    channels_count,
                     config.mute_when_streaming,
+                    {
+                        let ctx = Arc::clone(&ctx);
+                        move |samples| {
+                            crate::write_recording(&ctx, |recording| recording.write_audio(samples))
+                        }
+                    },
                 ) {
                     error!("Audio record error: {e:?}");
                 }
@@ -814,16 +1422,38 @@ fn connection_pipeline(
                         BodyTrackingSink::new(config.sink, settings.connection.osc_local_port).ok()
                     });
 
+            let mut input_coordinate_convention = None;
+            let mut coordinate_transform = None;
+
             while is_streaming(&client_hostname) {
                 let data = match tracking_receiver.recv(STREAMING_RECV_TIMEOUT) {
                     Ok(tracking) => tracking,
                     Err(ConnectionError::TryAgain(_)) => continue,
                     Err(ConnectionError::Other(_)) => return,
                 };
-                let Ok(tracking) = data.get_header() else {
+                let Ok(mut tracking) = data.get_header() else {
                     return;
                 };
 
+                let convention = SERVER_DATA_MANAGER
+                    .read()
+                    .settings()
+                    .headset
+                    .input_coordinate_convention
+                    .as_option()
+                    .copied();
+                if convention != input_coordinate_convention {
+                    input_coordinate_convention = convention;
+                    coordinate_transform = convention.and_then(|convention| {
+                        CoordinateTransform::new(convention)
+                            .map_err(|e| warn!("Ignoring the input coordinate convention: {e}"))
+                            .ok()
+                    });
+                }
+                if let Some(transform) = &coordinate_transform {
+                    transform.transform_tracking(&mut tracking);
+                }
+
                 let controllers_config = {
                     let data_lock = SERVER_DATA_MANAGER.read();
                     data_lock
@@ -841,6 +1471,18 @@ fn connection_pipeline(
                     let data_manager_lock = SERVER_DATA_MANAGER.read();
                     let headset_config = &data_manager_lock.settings().headset;
 
+                    if ctx.recenter_requested.value() {
+                        ctx.recenter_requested.set(false);
+
+                        if !headset_config.tracking_ref_only {
+                            info!("Recentering playspace");
+                            tracking_manager_lock.recenter(
+                                headset_config.position_recentering_mode,
+                                headset_config.rotation_recentering_mode,
+                            );
+                        }
+                    }
+
                     motions = tracking_manager_lock.transform_motions(
                         headset_config,
                         &tracking.device_motions,
@@ -858,6 +1500,31 @@ fn connection_pipeline(
                     ];
                 };
 
+                {
+                    let idle_config = SERVER_DATA_MANAGER
+                        .read()
+                        .settings()
+                        .video
+                        .bitrate
+                        .idle_power_saving
+                        .as_option()
+                        .cloned();
+                    let now = Instant::now();
+                    update_idle_power_saving(&ctx, idle_config.as_ref(), |idle_detector| {
+                        if let Some(config) = &idle_config {
+                            // The speeds do not depend on the recentering
+                            idle_detector.report_motions(
+                                config,
+                                tracking.device_motions.iter().map(|(_, motion)| motion),
+                                now,
+                            )
+                        } else {
+                            // Disabling the power saving restores the active profile
+                            idle_detector.report_input(now)
+                        }
+                    });
+                }
+
                 // Note: using the raw unrecentered head
                 let local_eye_gazes = tracking
                     .device_motions
@@ -977,6 +1644,7 @@ fn connection_pipeline(
         let ctx = Arc::clone(&ctx);
         let client_hostname = client_hostname.clone();
         move || {
+            let mut last_video_packets_lost = 0;
             while is_streaming(&client_hostname) {
                 let data = match statics_receiver.recv(STREAMING_RECV_TIMEOUT) {
                     Ok(stats) => stats,
@@ -987,7 +1655,20 @@ fn connection_pipeline(
                     return;
                 };
 
+                // The client counts the lost packets since the stream start
+                let video_packets_lost = client_stats
+                    .video_packets_lost
+                    .saturating_sub(last_video_packets_lost);
+                last_video_packets_lost = client_stats.video_packets_lost;
+
                 if let Some(stats) = &mut *ctx.statistics_manager.lock() {
+                    for _ in 0..video_packets_lost {
+                        stats.report_packet_loss();
+                    }
+                    if video_packets_lost > 0 {
+                        ctx.bitrate_manager.lock().report_packet_loss();
+                    }
+
                     let timestamp = client_stats.target_timestamp;
                     let decoder_latency = client_stats.video_decode;
                     let (network_latency, game_latency) = stats.report_statistics(client_stats);
@@ -996,13 +1677,34 @@ fn connection_pipeline(
                         .lock()
                         .push_back(ServerCoreEvent::GameRenderLatencyFeedback(game_latency));
 
-                    let server_data_lock = SERVER_DATA_MANAGER.read();
-                    ctx.bitrate_manager.lock().report_frame_latencies(
-                        &server_data_lock.settings().video.bitrate.mode,
+                    // The session lock is released before locking the bandwidth hold
+                    let bitrate_config =
+                        crate::stream_bitrate_config(&ctx, SERVER_DATA_MANAGER.read().settings())
+                            .into_owned();
+                    let bitrate_mode = &bitrate_config.mode;
+                    let throughput_bps = ctx.bitrate_manager.lock().report_frame_latencies(
+                        bitrate_mode,
                         timestamp,
                         network_latency,
                         decoder_latency,
                     );
+
+                    let hold_config = bandwidth_hold::freeze_and_hold_config(bitrate_mode);
+                    update_bandwidth_hold(&ctx, |bandwidth_hold| {
+                        if let Some((floor_bps, config)) = hold_config {
+                            throughput_bps.is_some_and(|throughput_bps| {
+                                bandwidth_hold.report_throughput(
+                                    config,
+                                    floor_bps,
+                                    throughput_bps,
+                                    Instant::now(),
+                                )
+                            })
+                        } else {
+                            // Disabled while holding
+                            bandwidth_hold.release()
+                        }
+                    });
                 }
             }
         }
@@ -1014,6 +1716,17 @@ fn connection_pipeline(
         let control_sender = Arc::clone(&control_sender);
         let disconnect_notif = Arc::clone(&disconnect_notif);
         let client_hostname = client_hostname.clone();
+        let mut eye_calibration = settings.headset.eye_calibration.as_option().cloned();
+        let ctx = Arc::clone(&ctx);
+        let client_supports_foveation = streaming_caps.supports_foveated_encoding;
+        let mut foveated_encoding = enable_foveated_encoding
+            .then(|| settings.video.foveated_encoding.as_option().cloned())
+            .flatten();
+        let mut rejected_foveated_encoding = None;
+        let mut stream_paused = false;
+        let streaming_caps = streaming_caps.clone();
+        // Config buffer of the previous encoder, while waiting for the restarted one
+        let mut replaced_decoder_config = None;
         move || {
             while is_streaming(&client_hostname) {
                 if let Err(e) = control_sender.lock().send(&ServerControlPacket::KeepAlive) {
@@ -1024,6 +1737,211 @@ fn connection_pipeline(
                     return;
                 }
 
+                // The client reads the initial calibration from the settings
+                let new_eye_calibration = SERVER_DATA_MANAGER
+                    .read()
+                    .settings()
+                    .headset
+                    .eye_calibration
+                    .as_option()
+                    .cloned();
+                if new_eye_calibration != eye_calibration {
+                    control_sender
+                        .lock()
+                        .send(&ServerControlPacket::EyeCalibration(
+                            new_eye_calibration.clone(),
+                        ))
+                        .ok();
+                    eye_calibration = new_eye_calibration;
+                }
+
+                if encoder_restart {
+                    let new_foveated_encoding = SERVER_DATA_MANAGER
+                        .read()
+                        .settings()
+                        .video
+                        .foveated_encoding
+                        .as_option()
+                        .filter(|config| client_supports_foveation || config.force_enable)
+                        .cloned();
+                    if new_foveated_encoding != foveated_encoding
+                        && new_foveated_encoding != rejected_foveated_encoding
+                    {
+                        if let Some(Err(e)) = new_foveated_encoding
+                            .as_ref()
+                            .map(|config| foveation::foveation_vars(config, stream_view_resolution))
+                        {
+                            warn!("Invalid foveated encoding, keeping the current one: {e}");
+                            rejected_foveated_encoding = new_foveated_encoding;
+                        } else {
+                            let epoch = ctx.foveation_epochs.lock().request_change();
+
+                            // Avoid a SteamVR restart on the next connection
+                            set_openvr_foveated_encoding(
+                                &mut SERVER_DATA_MANAGER.write().session_mut().openvr_config,
+                                new_foveated_encoding.as_ref(),
+                            );
+
+                            control_sender
+                                .lock()
+                                .send(&ServerControlPacket::FoveatedEncoding {
+                                    epoch,
+                                    config: new_foveated_encoding.clone(),
+                                })
+                                .ok();
+                            ctx.events_queue
+                                .lock()
+                                .push_back(ServerCoreEvent::FoveatedEncoding {
+                                    epoch,
+                                    config: new_foveated_encoding.clone(),
+                                });
+
+                            foveated_encoding = new_foveated_encoding;
+                        }
+                    }
+                }
+
+                // The pause can be requested by both the server and the client
+                let new_stream_paused = ctx.stream_pause.lock().is_paused();
+                if new_stream_paused != stream_paused {
+                    control_sender
+                        .lock()
+                        .send(&ServerControlPacket::StreamPaused(new_stream_paused))
+                        .ok();
+                    stream_paused = new_stream_paused;
+                }
+
+                let mut encoder_restarted = false;
+                let benchmark_sample = ctx
+                    .statistics_manager
+                    .lock()
+                    .as_ref()
+                    .map(|stats| stats.benchmark_sample())
+                    .unwrap_or_default();
+                let benchmark_action = ctx
+                    .benchmark
+                    .lock()
+                    .as_mut()
+                    .map(|run| run.sweep.update(Instant::now(), benchmark_sample));
+                match benchmark_action {
+                    Some(BenchmarkAction::Apply(point)) => {
+                        info!("Benchmark: streaming with {point:?}");
+                        let outcome =
+                            apply_benchmark_point(&ctx, &streaming_caps, encoder_restart, point);
+                        encoder_restarted = matches!(outcome, Ok(true));
+
+                        if let Some(run) = &mut *ctx.benchmark.lock() {
+                            match outcome {
+                                Ok(_) => {
+                                    run.point = Some(point);
+                                    run.sweep.report_applied(Instant::now());
+                                }
+                                Err(reason) => {
+                                    warn!("Benchmark: skipping {point:?}. {reason}");
+                                    run.sweep.report_skipped(&reason);
+                                }
+                            }
+                        }
+                    }
+                    Some(BenchmarkAction::Finished(results)) => {
+                        info!("Benchmark results:\n{}", benchmark::results_table(&results));
+
+                        *ctx.benchmark.lock() = None;
+                        ctx.events_queue
+                            .lock()
+                            .push_back(ServerCoreEvent::BenchmarkTestPattern(false));
+                        // Back to the stream parameters of the settings
+                        ctx.fast_reconnect_requested.set(true);
+                    }
+                    _ => (),
+                }
+
+                // The resolution is chosen again only once the stream has settled
+                if ctx.benchmark.lock().is_none() && replaced_decoder_config.is_none() {
+                    let maybe_config = joint_resolution::joint_resolution_config(
+                        &SERVER_DATA_MANAGER.read().settings().video.bitrate.mode,
+                    )
+                    .cloned();
+                    let maybe_bandwidth = ctx.bitrate_manager.lock().bandwidth_estimate();
+                    let mut maybe_point = None;
+                    if let (Some(config), Some(bandwidth_bps), Some(controller)) = (
+                        maybe_config,
+                        maybe_bandwidth,
+                        &mut *ctx.joint_resolution.lock(),
+                    ) {
+                        maybe_point = controller.update(&config, bandwidth_bps, Instant::now());
+                    }
+                    if let Some(point) = maybe_point {
+                        let bandwidth_mbps = maybe_bandwidth.unwrap_or_default() / 1e6;
+                        *ctx.joint_resolution_scale.lock() = Some(point.resolution_scale);
+
+                        // Only the encoder is restarted, the stream keeps the negotiated resolution
+                        info!(
+                            "Switching to the resolution scale {} for the bandwidth of {bandwidth_mbps:.1} Mbps",
+                            point.resolution_scale,
+                        );
+                        ctx.fast_reconnect_requested.set(true);
+                    }
+                }
+
+                // A benchmark point can replace the encoder during a renegotiation
+                if encoder_restarted {
+                    transition_connection_phase(
+                        &ctx,
+                        &client_hostname,
+                        ConnectionPhase::Reconnecting,
+                    )
+                    .ok();
+                }
+
+                // Only a streaming connection can be renegotiated. Otherwise the request waits for
+                // the renegotiation in progress to complete
+                if ctx.fast_reconnect_requested.value()
+                    && transition_connection_phase(
+                        &ctx,
+                        &client_hostname,
+                        ConnectionPhase::Reconnecting,
+                    )
+                    .is_ok()
+                {
+                    ctx.fast_reconnect_requested.set(false);
+
+                    if !encoder_restart {
+                        warn!("The encoder cannot be restarted during the stream on Linux or with this client, restart SteamVR to apply the settings");
+                    } else if apply_fast_reconnect(&ctx, &streaming_caps, &control_sender) {
+                        encoder_restarted = true;
+                    }
+
+                    if !encoder_restarted {
+                        set_connection_phase(&ctx, &client_hostname, ConnectionPhase::Streaming);
+                    }
+                }
+
+                // The stream is renegotiated until the restarted encoder sends its config
+                if encoder_restarted {
+                    replaced_decoder_config = Some(
+                        ctx.decoder_config
+                            .lock()
+                            .as_ref()
+                            .map(|c| c.config_buffer.clone()),
+                    );
+                }
+
+                // The client rebuilds its decoder when it receives the new config
+                if let Some(replaced_config) = &replaced_decoder_config {
+                    let maybe_config = ctx.decoder_config.lock().clone();
+                    if let Some(config) =
+                        maybe_config.filter(|c| replaced_config.as_ref() != Some(&c.config_buffer))
+                    {
+                        control_sender
+                            .lock()
+                            .send(&ServerControlPacket::DecoderConfig(config))
+                            .ok();
+                        replaced_decoder_config = None;
+                        set_connection_phase(&ctx, &client_hostname, ConnectionPhase::Streaming);
+                    }
+                }
+
                 thread::sleep(KEEPALIVE_INTERVAL);
             }
         }
@@ -1110,12 +2028,10 @@ fn connection_pipeline(
                     }
                     ClientControlPacket::VideoErrorReport => {
                         // legacy endpoint. todo: remove
+                        // The client requests an IDR only when the lost frames are referenced
                         if let Some(stats) = &mut *ctx.statistics_manager.lock() {
                             stats.report_packet_loss();
                         }
-                        ctx.events_queue
-                            .lock()
-                            .push_back(ServerCoreEvent::RequestIDR)
                     }
                     ClientControlPacket::ViewsConfig(config) => {
                         ctx.events_queue
@@ -1154,6 +2070,16 @@ fn connection_pipeline(
                     ClientControlPacket::Buttons(entries) => {
                         {
                             let data_manager_lock = SERVER_DATA_MANAGER.read();
+                            let idle_config = data_manager_lock
+                                .settings()
+                                .video
+                                .bitrate
+                                .idle_power_saving
+                                .as_option();
+                            update_idle_power_saving(&ctx, idle_config, |idle_detector| {
+                                idle_detector.report_input(Instant::now())
+                            });
+
                             if data_manager_lock
                                 .settings()
                                 .extra
@@ -1208,6 +2134,16 @@ fn connection_pipeline(
                                 None
                             };
                     }
+                    ClientControlPacket::RequestStreamPause(paused) => {
+                        update_stream_pause(&ctx, |stream_pause| {
+                            stream_pause.request_pause(paused)
+                        });
+                    }
+                    ClientControlPacket::HeadsetPresence(present) => {
+                        update_stream_pause(&ctx, |stream_pause| {
+                            stream_pause.report_headset_presence(present)
+                        });
+                    }
                     ClientControlPacket::Log { level, message } => {
                         info!("Client {client_hostname}: [{level:?}] {message}")
                     }
@@ -1275,6 +2211,30 @@ fn connection_pipeline(
         }
     });
 
+    let audio_receive_thread = if let Some(mut audio_socket) = audio_socket {
+        thread::spawn({
+            let disconnect_notif = Arc::clone(&disconnect_notif);
+            let client_hostname = client_hostname.clone();
+            move || {
+                while is_streaming(&client_hostname) {
+                    match audio_socket.recv() {
+                        Ok(()) => (),
+                        Err(ConnectionError::TryAgain(_)) => continue,
+                        Err(e) => {
+                            info!("Client disconnected. Cause: {e}");
+
+                            disconnect_notif.notify_one();
+
+                            return;
+                        }
+                    }
+                }
+            }
+        })
+    } else {
+        thread::spawn(|| ())
+    };
+
     let lifecycle_check_thread = thread::spawn({
         let disconnect_notif = Arc::clone(&disconnect_notif);
         let client_hostname = client_hostname.clone();
@@ -1308,14 +2268,18 @@ fn connection_pipeline(
         }
     }
 
+    ctx.game_audio_sample_rate
+        .store(game_audio_sample_rate, Ordering::Relaxed);
+
     if settings.extra.capture.startup_video_recording {
-        crate::create_recording_file(&ctx, server_data_lock.settings());
+        crate::create_recording_file(&ctx, &server_data_lock);
     }
 
     server_data_lock.update_client_list(
         client_hostname.clone(),
         ClientListAction::SetConnectionState(ConnectionState::Streaming),
     );
+    set_connection_phase(&ctx, &client_hostname, ConnectionPhase::Streaming);
 
     ctx.events_queue
         .lock()
@@ -1329,6 +2293,13 @@ fn connection_pipeline(
 
     *ctx.video_recording_file.lock() = None;
 
+    if ctx.benchmark.lock().take().is_some() {
+        warn!("Benchmark interrupted by the disconnection");
+        ctx.events_queue
+            .lock()
+            .push_back(ServerCoreEvent::BenchmarkTestPattern(false));
+    }
+
     server_data_lock.update_client_list(
         client_hostname.clone(),
         ClientListAction::SetConnectionState(ConnectionState::Disconnecting),
@@ -1360,6 +2331,7 @@ fn connection_pipeline(
     statistics_thread.join().ok();
     control_receive_thread.join().ok();
     stream_receive_thread.join().ok();
+    audio_receive_thread.join().ok();
     keepalive_thread.join().ok();
     lifecycle_check_thread.join().ok();
 
@@ -0,0 +1,179 @@
+use alvr_common::anyhow::{bail, Result};
+use std::fmt::{self, Display, Formatter};
+
+// Values of VkDriverId
+pub const DRIVER_ID_NVIDIA_PROPRIETARY: u32 = 4;
+pub const DRIVER_ID_INTEL_PROPRIETARY_WINDOWS: u32 = 5;
+
+#[derive(PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Debug)]
+pub struct DriverVersion(pub [u32; 4]);
+
+impl DriverVersion {
+    // The packing of VkPhysicalDeviceProperties::driverVersion is vendor specific
+    pub fn decode(driver_id: u32, version: u32) -> Self {
+        match driver_id {
+            DRIVER_ID_NVIDIA_PROPRIETARY => Self([
+                version >> 22,
+                (version >> 14) & 0xff,
+                (version >> 6) & 0xff,
+                version & 0x3f,
+            ]),
+            DRIVER_ID_INTEL_PROPRIETARY_WINDOWS => Self([version >> 14, version & 0x3fff, 0, 0]),
+            _ => Self([version >> 22, (version >> 12) & 0x3ff, version & 0xfff, 0]),
+        }
+    }
+}
+
+impl Display for DriverVersion {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        let [major, minor, patch, build] = self.0;
+        if build != 0 {
+            write!(f, "{major}.{minor}.{patch}.{build}")
+        } else {
+            write!(f, "{major}.{minor}.{patch}")
+        }
+    }
+}
+
+// Versions probed when creating the Vulkan context
+pub struct DriverInfo {
+    pub device_name: String,
+    pub driver_name: String,
+    pub driver_info: String,
+    pub driver_id: u32,
+    pub driver_version: DriverVersion,
+    // Vulkan API version supported by the device, as (major, minor)
+    pub api_version: (u32, u32),
+    pub conformance_version: [u8; 4],
+}
+
+impl Display for DriverInfo {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        let [c_major, c_minor, c_subminor, c_patch] = self.conformance_version;
+        write!(
+            f,
+            "{}, driver {} {} ({}), Vulkan {}.{}, conformance {c_major}.{c_minor}.{c_subminor}.{c_patch}",
+            self.device_name,
+            self.driver_name,
+            self.driver_version,
+            self.driver_info,
+            self.api_version.0,
+            self.api_version.1,
+        )
+    }
+}
+
+pub struct FeatureRequirement {
+    pub feature: &'static str,
+    pub min_api_version: (u32, u32),
+    // Only drivers listed here have a minimum version
+    pub min_driver_versions: &'static [(u32, DriverVersion)],
+}
+
+// Features used by the Vulkan context of the Linux encoder
+pub const VULKAN_REQUIREMENTS: &[FeatureRequirement] = &[
+    FeatureRequirement {
+        feature: "Timeline semaphores",
+        min_api_version: (1, 2),
+        min_driver_versions: &[],
+    },
+    FeatureRequirement {
+        feature: "DRM format modifiers",
+        min_api_version: (1, 2),
+        min_driver_versions: &[(DRIVER_ID_NVIDIA_PROPRIETARY, DriverVersion([515, 0, 0, 0]))],
+    },
+];
+
+// Fails with the first unmet requirement
+pub fn check_requirements(info: &DriverInfo, requirements: &[FeatureRequirement]) -> Result<()> {
+    for requirement in requirements {
+        if info.api_version < requirement.min_api_version {
+            let (major, minor) = requirement.min_api_version;
+            bail!(
+                "{} requires Vulkan {major}.{minor}, but {} supports only Vulkan {}.{}. Please update the graphics driver",
+                requirement.feature,
+                info.device_name,
+                info.api_version.0,
+                info.api_version.1,
+            );
+        }
+
+        if let Some((_, min_version)) = requirement
+            .min_driver_versions
+            .iter()
+            .find(|(driver_id, _)| *driver_id == info.driver_id)
+        {
+            if info.driver_version < *min_version {
+                bail!(
+                    "{} requires {} {min_version} or newer, found {}. Please update the graphics driver",
+                    requirement.feature,
+                    info.driver_name,
+                    info.driver_version,
+                );
+            }
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DRIVER_ID_MESA_RADV: u32 = 3;
+
+    fn nvidia_driver(version: u32) -> DriverInfo {
+        DriverInfo {
+            device_name: "NVIDIA GeForce RTX 3070".into(),
+            driver_name: "NVIDIA".into(),
+            driver_info: "510.108.03".into(),
+            driver_id: DRIVER_ID_NVIDIA_PROPRIETARY,
+            driver_version: DriverVersion::decode(DRIVER_ID_NVIDIA_PROPRIETARY, version),
+            api_version: (1, 3),
+            conformance_version: [1, 3, 1, 1],
+        }
+    }
+
+    #[test]
+    fn test_driver_version_decoding() {
+        // 510.108.03 as reported by the NVIDIA driver
+        let nvidia = (510 << 22) | (108 << 14) | (3 << 6);
+        assert_eq!(
+            DriverVersion::decode(DRIVER_ID_NVIDIA_PROPRIETARY, nvidia).to_string(),
+            "510.108.3"
+        );
+
+        // Mesa 23.1.4, packed with VK_MAKE_VERSION
+        let mesa = (23 << 22) | (1 << 12) | 4;
+        assert_eq!(
+            DriverVersion::decode(DRIVER_ID_MESA_RADV, mesa),
+            DriverVersion([23, 1, 4, 0])
+        );
+    }
+
+    #[test]
+    fn test_unavailable_version_is_rejected() {
+        let old_driver = nvidia_driver((510 << 22) | (108 << 14) | (3 << 6));
+        let error = check_requirements(&old_driver, VULKAN_REQUIREMENTS)
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("DRM format modifiers"));
+        assert!(error.contains("515.0.0"));
+        assert!(error.contains("510.108.3"));
+
+        let new_driver = nvidia_driver(535 << 22);
+        assert!(check_requirements(&new_driver, VULKAN_REQUIREMENTS).is_ok());
+
+        // Other drivers are checked only against the API version
+        let mesa = DriverInfo {
+            driver_id: DRIVER_ID_MESA_RADV,
+            api_version: (1, 1),
+            ..nvidia_driver(0)
+        };
+        let error = check_requirements(&mesa, VULKAN_REQUIREMENTS)
+            .unwrap_err()
+            .to_string();
+        assert!(error.starts_with("Timeline semaphores requires Vulkan 1.2"));
+    }
+}
@@ -0,0 +1,85 @@
+use alvr_session::OpenvrConfig;
+
+#[derive(PartialEq, Eq, Debug)]
+pub enum ReconnectKind {
+    Unchanged,
+    // The encoder is restarted and the client rebuilds its decoder from the new decoder config. The
+    // connection and the pairing are kept
+    Fast,
+    // The change requires a SteamVR restart and a new handshake
+    Full,
+}
+
+// Parameters that the encoder reads when it is (re)created
+fn with_encoder_params(config: &OpenvrConfig, params: &OpenvrConfig) -> OpenvrConfig {
+    OpenvrConfig {
+        codec: params.codec,
+        h264_profile: params.h264_profile,
+        // The block size depends on the codec
+        qp_delta_map: params.qp_delta_map.clone(),
+        qp_delta_map_columns: params.qp_delta_map_columns,
+        ..config.clone()
+    }
+}
+
+// Decides how new stream parameters can be applied to an established connection. On a fast
+// reconnect the current config is updated with the new parameters
+pub fn reconnect(current: &mut OpenvrConfig, new: &OpenvrConfig) -> ReconnectKind {
+    if current == new {
+        ReconnectKind::Unchanged
+    } else if with_encoder_params(current, new) == *new {
+        *current = new.clone();
+
+        ReconnectKind::Fast
+    } else {
+        ReconnectKind::Full
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alvr_session::CodecType;
+
+    const HEVC: u8 = CodecType::Hevc as _;
+    const AV1: u8 = CodecType::AV1 as _;
+
+    fn established_config() -> OpenvrConfig {
+        OpenvrConfig {
+            eye_resolution_width: 1856,
+            eye_resolution_height: 1920,
+            refresh_rate: 90,
+            codec: HEVC,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_codec_change_reconnects_fast() {
+        let mut current = established_config();
+
+        let new = OpenvrConfig {
+            codec: AV1,
+            ..established_config()
+        };
+        // A fast reconnect keeps the control connection, the client is not paired again
+        assert_eq!(reconnect(&mut current, &new), ReconnectKind::Fast);
+        assert_eq!(current.codec, AV1);
+
+        assert_eq!(reconnect(&mut current, &new), ReconnectKind::Unchanged);
+    }
+
+    #[test]
+    fn test_other_changes_require_full_reconnect() {
+        let mut current = established_config();
+
+        let new = OpenvrConfig {
+            codec: AV1,
+            refresh_rate: 120,
+            ..established_config()
+        };
+        assert_eq!(reconnect(&mut current, &new), ReconnectKind::Full);
+        // Applied by the new connection
+        assert_eq!(current.codec, HEVC);
+    }
+}
@@ -0,0 +1,235 @@
+use alvr_common::{
+    anyhow::{bail, Result},
+    glam::{UVec2, Vec2},
+};
+use alvr_session::FoveatedEncodingConfig;
+
+// Keeps track of the foveated encoding used by the video frames. A change is requested with a new
+// epoch, which becomes effective once the encoder has been restarted with the new parameters.
+// Frames are dropped while a change is in progress, and the first frame of each epoch is an IDR,
+// so the client never decodes a frame with a mismatched foveation.
+pub struct FoveationEpochs {
+    current_epoch: u32,
+    requested_epoch: Option<u32>,
+    waiting_for_idr: bool,
+}
+
+impl FoveationEpochs {
+    pub fn new() -> Self {
+        Self {
+            current_epoch: 0,
+            requested_epoch: None,
+            waiting_for_idr: false,
+        }
+    }
+
+    // Returns the epoch to be used for the new foveated encoding
+    pub fn request_change(&mut self) -> u32 {
+        let epoch = self.requested_epoch.unwrap_or(self.current_epoch) + 1;
+        self.requested_epoch = Some(epoch);
+
+        epoch
+    }
+
+    // Called after the encoder has been restarted with the foveated encoding of the epoch
+    pub fn report_applied(&mut self, epoch: u32) {
+        self.current_epoch = epoch;
+        if self.requested_epoch == Some(epoch) {
+            self.requested_epoch = None;
+        }
+        self.waiting_for_idr = true;
+    }
+
+    // Returns the epoch to tag an encoded frame with, or None if the frame must be dropped
+    pub fn frame_epoch(&mut self, is_idr: bool) -> Option<u32> {
+        if self.requested_epoch.is_some() || (self.waiting_for_idr && !is_idr) {
+            return None;
+        }
+        self.waiting_for_idr = false;
+
+        Some(self.current_epoch)
+    }
+}
+
+impl Default for FoveationEpochs {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// Parameters of the foveated encoding, in the eye UV space. The right eye is mirrored
+// horizontally, so the same parameters put the center at the same distance from the nose.
+#[derive(Debug)]
+pub struct FoveationVars {
+    // Size of each eye in the stream frame
+    pub optimized_eye_size: UVec2,
+    // Part of the eye slot used by the compressed eye, the rest is padding up to the aligned size
+    pub eye_size_ratio: Vec2,
+    pub center_size: Vec2,
+    pub center_shift: Vec2,
+    pub edge_ratio: Vec2,
+}
+
+impl FoveationVars {
+    // Region of the eye which is not downscaled, as (min, max)
+    pub fn foveal_region(&self) -> (Vec2, Vec2) {
+        let c0 = (Vec2::ONE - self.center_size) / 2.0;
+
+        (
+            c0 * (self.center_shift + Vec2::ONE),
+            c0 * (self.center_shift - Vec2::ONE) + Vec2::ONE,
+        )
+    }
+
+    // Position of the foveal region in the eye slot of the stream frame
+    pub fn compressed_foveal_region(&self) -> (Vec2, Vec2) {
+        let (min, max) = self.foveal_region();
+        let c2 = (self.edge_ratio - Vec2::ONE) * self.center_size + Vec2::ONE;
+
+        (
+            min / c2 * self.eye_size_ratio,
+            (Vec2::ONE - (Vec2::ONE - max) / c2) * self.eye_size_ratio,
+        )
+    }
+}
+
+// Same alignment as CalculateFoveationVars() on the server and on the client, which compress and
+// expand the eyes
+pub fn foveation_vars(
+    config: &FoveatedEncodingConfig,
+    view_resolution: UVec2,
+) -> Result<FoveationVars> {
+    let center_size = Vec2::new(config.center_size_x, config.center_size_y);
+    let center_shift = Vec2::new(config.center_shift_x, config.center_shift_y);
+    let edge_ratio = Vec2::new(config.edge_ratio_x, config.edge_ratio_y);
+
+    if center_size.cmple(Vec2::ZERO).any() || center_size.cmpge(Vec2::ONE).any() {
+        bail!("The center region size must be between 0 and 1");
+    }
+    if center_shift.abs().cmpgt(Vec2::ONE).any() {
+        bail!("The center shift must be between -1 and 1");
+    }
+    if edge_ratio.cmplt(Vec2::ONE).any() {
+        bail!("The edge ratio must be at least 1");
+    }
+
+    let target_eye_size = view_resolution.as_vec2();
+    let edge_size = target_eye_size - center_size * target_eye_size;
+
+    let center_size_aligned =
+        Vec2::ONE - (edge_size / (edge_ratio * 2.0)).ceil() * (edge_ratio * 2.0) / target_eye_size;
+    let edge_size_aligned = target_eye_size - center_size_aligned * target_eye_size;
+    let center_shift_aligned = (center_shift * edge_size_aligned / (edge_ratio * 2.0)).ceil()
+        * (edge_ratio * 2.0)
+        / edge_size_aligned;
+
+    let foveation_scale = center_size_aligned + (Vec2::ONE - center_size_aligned) / edge_ratio;
+    let eye_size = foveation_scale * target_eye_size;
+
+    // Round the frame dimensions to a number of pixels multiple of 32 for the encoder
+    let optimized_eye_size = (eye_size / 32.0).ceil().as_uvec2() * 32;
+
+    let vars = FoveationVars {
+        optimized_eye_size,
+        eye_size_ratio: eye_size / optimized_eye_size.as_vec2(),
+        center_size: center_size_aligned,
+        center_shift: center_shift_aligned,
+        edge_ratio,
+    };
+
+    let (min, max) = vars.foveal_region();
+    if !(vars.center_size.is_finite() && vars.center_shift.is_finite())
+        || min.cmplt(Vec2::ZERO).any()
+        || max.cmpgt(Vec2::ONE).any()
+    {
+        bail!("The center region does not fit in the eye");
+    }
+
+    Ok(vars)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_frames_are_dropped_until_change_is_applied() {
+        let mut epochs = FoveationEpochs::new();
+        assert_eq!(epochs.frame_epoch(false), Some(0));
+
+        let epoch = epochs.request_change();
+        assert_eq!(epoch, 1);
+        // Frames encoded while the encoder is being restarted could use either foveation
+        assert_eq!(epochs.frame_epoch(false), None);
+        assert_eq!(epochs.frame_epoch(true), None);
+
+        epochs.report_applied(epoch);
+        // The transition frame is an IDR
+        assert_eq!(epochs.frame_epoch(false), None);
+        assert_eq!(epochs.frame_epoch(true), Some(1));
+        assert_eq!(epochs.frame_epoch(false), Some(1));
+
+        // A change requested before the previous one is applied is not skipped
+        let first = epochs.request_change();
+        let second = epochs.request_change();
+        epochs.report_applied(first);
+        assert_eq!(epochs.frame_epoch(true), None);
+        epochs.report_applied(second);
+        assert_eq!(epochs.frame_epoch(true), Some(second));
+    }
+
+    fn foveation_config() -> FoveatedEncodingConfig {
+        FoveatedEncodingConfig {
+            force_enable: false,
+            center_size_x: 0.45,
+            center_size_y: 0.4,
+            center_shift_x: 0.4,
+            center_shift_y: 0.1,
+            edge_ratio_x: 4.0,
+            edge_ratio_y: 5.0,
+        }
+    }
+
+    #[test]
+    fn test_foveal_region_keeps_full_resolution() {
+        let view_resolution = UVec2::new(1832, 1920);
+        let config = foveation_config();
+        let vars = foveation_vars(&config, view_resolution).unwrap();
+
+        assert_eq!(vars.optimized_eye_size % 32, UVec2::ZERO);
+        assert!(vars.optimized_eye_size.cmplt(view_resolution).all());
+        assert!(vars.eye_size_ratio.cmple(Vec2::ONE).all());
+
+        let center_size = Vec2::new(config.center_size_x, config.center_size_y);
+        let center_shift = Vec2::new(config.center_shift_x, config.center_shift_y);
+        let (min, max) = vars.foveal_region();
+        let expected_center = Vec2::splat(0.5) + center_shift * (Vec2::ONE - center_size) / 2.0;
+        assert!(((min + max) / 2.0).abs_diff_eq(expected_center, 0.01));
+        assert!((max - min).abs_diff_eq(center_size, 0.01));
+
+        let (compressed_min, compressed_max) = vars.compressed_foveal_region();
+        let full_size = (max - min) * view_resolution.as_vec2();
+        let compressed_size = (compressed_max - compressed_min) * vars.optimized_eye_size.as_vec2();
+        assert!(full_size.abs_diff_eq(compressed_size, 1.0));
+        assert!(compressed_min.cmpge(Vec2::ZERO).all());
+        assert!(compressed_max.cmple(vars.eye_size_ratio).all());
+    }
+
+    #[test]
+    fn test_invalid_foveation_is_rejected() {
+        let view_resolution = UVec2::new(1832, 1920);
+        assert!(foveation_vars(&foveation_config(), view_resolution).is_ok());
+
+        let mut config = foveation_config();
+        config.center_shift_x = 1.5;
+        assert!(foveation_vars(&config, view_resolution).is_err());
+
+        let mut config = foveation_config();
+        config.edge_ratio_x = 0.5;
+        assert!(foveation_vars(&config, view_resolution).is_err());
+
+        let mut config = foveation_config();
+        config.center_size_x = 1.0;
+        assert!(foveation_vars(&config, view_resolution).is_err());
+    }
+}
@@ -0,0 +1,84 @@
+use std::{collections::VecDeque, time::Duration};
+
+// In case the frames are never encoded
+const MAX_PENDING_FRAMES: usize = 64;
+
+// User data set by the server application, waiting for the frame with the same target timestamp
+// to be encoded
+#[derive(Default)]
+pub struct FrameUserDataQueue {
+    frames: VecDeque<(Duration, Vec<u8>)>,
+}
+
+impl FrameUserDataQueue {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push(&mut self, target_timestamp: Duration, user_data: Vec<u8>) {
+        self.frames.push_back((target_timestamp, user_data));
+        while self.frames.len() > MAX_PENDING_FRAMES {
+            self.frames.pop_front();
+        }
+    }
+
+    // Empty for frames without user data
+    pub fn take(&mut self, target_timestamp: Duration) -> Vec<u8> {
+        // Data of older frames belongs to frames skipped by the encoder
+        while self
+            .frames
+            .front()
+            .is_some_and(|(timestamp, _)| *timestamp < target_timestamp)
+        {
+            self.frames.pop_front();
+        }
+
+        if self
+            .frames
+            .front()
+            .is_some_and(|(timestamp, _)| *timestamp == target_timestamp)
+        {
+            self.frames.pop_front().unwrap().1
+        } else {
+            vec![]
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.frames.clear();
+    }
+}
+
+// The buffer comes from the server application through the C API and can be null when empty
+pub unsafe fn user_data_from_raw(buffer_ptr: *const u8, len: u32) -> Vec<u8> {
+    if buffer_ptr.is_null() || len == 0 {
+        vec![]
+    } else {
+        std::slice::from_raw_parts(buffer_ptr, len as usize).to_vec()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::ptr;
+
+    #[test]
+    fn test_user_data_is_taken_by_its_frame() {
+        let mut queue = FrameUserDataQueue::new();
+        for i in 0..4_u8 {
+            queue.push(Duration::from_millis(i as _), vec![i]);
+        }
+
+        // Frame 1 is skipped by the encoder, and frame 4 has no user data
+        assert_eq!(queue.take(Duration::from_millis(0)), [0]);
+        assert_eq!(queue.take(Duration::from_millis(2)), [2]);
+        assert_eq!(queue.take(Duration::from_millis(3)), [3]);
+        assert!(queue.take(Duration::from_millis(4)).is_empty());
+
+        let buffer = [1, 2, 3];
+        assert_eq!(unsafe { user_data_from_raw(buffer.as_ptr(), 3) }, buffer);
+        assert!(unsafe { user_data_from_raw(buffer.as_ptr(), 0) }.is_empty());
+        assert!(unsafe { user_data_from_raw(ptr::null(), 3) }.is_empty());
+    }
+}
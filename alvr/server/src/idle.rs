@@ -0,0 +1,133 @@
+use alvr_common::DeviceMotion;
+use alvr_session::IdlePowerSavingConfig;
+use std::time::{Duration, Instant};
+
+// Detects when the user is idle, to switch the stream to the low power profile. The user becomes
+// idle only after the devices stay still for the whole idle time, so short pauses in active apps
+// never reach it. While idle, the thresholds are raised by the wake multiplier, so the tracking
+// noise of a headset left on a table does not restore the active profile.
+pub struct IdleDetector {
+    last_activity: Instant,
+    idle: bool,
+}
+
+impl IdleDetector {
+    pub fn new(now: Instant) -> Self {
+        Self {
+            last_activity: now,
+            idle: false,
+        }
+    }
+
+    pub fn is_idle(&self) -> bool {
+        self.idle
+    }
+
+    // Returns true if the idle state changed
+    pub fn report_motions<'a>(
+        &mut self,
+        config: &IdlePowerSavingConfig,
+        motions: impl IntoIterator<Item = &'a DeviceMotion>,
+        now: Instant,
+    ) -> bool {
+        let multiplier = if self.idle {
+            config.wake_threshold_multiplier
+        } else {
+            1.0
+        };
+        let angular_threshold = config.angular_threshold_deg_per_s.to_radians() * multiplier;
+        let linear_threshold = config.linear_threshold_m_per_s * multiplier;
+
+        if motions.into_iter().any(|motion| {
+            motion.angular_velocity.length() > angular_threshold
+                || motion.linear_velocity.length() > linear_threshold
+        }) {
+            self.last_activity = now;
+        }
+
+        let was_idle = self.idle;
+        self.idle = now.saturating_duration_since(self.last_activity)
+            >= Duration::from_secs_f32(config.idle_time_s);
+
+        self.idle != was_idle
+    }
+
+    // Returns true if the idle state changed
+    pub fn report_input(&mut self, now: Instant) -> bool {
+        self.last_activity = now;
+
+        let was_idle = self.idle;
+        self.idle = false;
+
+        was_idle
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alvr_common::{glam::Vec3, Pose};
+
+    fn config() -> IdlePowerSavingConfig {
+        IdlePowerSavingConfig {
+            idle_time_s: 30.0,
+            angular_threshold_deg_per_s: 5.0,
+            linear_threshold_m_per_s: 0.03,
+            wake_threshold_multiplier: 2.0,
+            low_power_bitrate_mbps: 10,
+            low_power_frame_divider: 2,
+        }
+    }
+
+    fn motion(angular_deg_per_s: f32) -> DeviceMotion {
+        DeviceMotion {
+            pose: Pose::default(),
+            linear_velocity: Vec3::ZERO,
+            angular_velocity: Vec3::new(0.0, angular_deg_per_s.to_radians(), 0.0),
+        }
+    }
+
+    // Reports the motion at 10Hz for the duration, returns the new states at each change
+    fn hold(
+        detector: &mut IdleDetector,
+        time: &mut Instant,
+        motion: DeviceMotion,
+        duration_s: u64,
+    ) -> Vec<bool> {
+        let mut changes = vec![];
+        for _ in 0..duration_s * 10 {
+            *time += Duration::from_millis(100);
+            if detector.report_motions(&config(), [&motion], *time) {
+                changes.push(detector.is_idle());
+            }
+        }
+
+        changes
+    }
+
+    #[test]
+    fn test_sustained_idle_engages_low_power_and_activity_restores_it() {
+        let mut time = Instant::now();
+        let mut detector = IdleDetector::new(time);
+
+        // Stillness shorter than the idle time, then a head turn, as when reading a menu
+        assert!(hold(&mut detector, &mut time, motion(1.0), 25).is_empty());
+        assert!(hold(&mut detector, &mut time, motion(40.0), 1).is_empty());
+        assert!(hold(&mut detector, &mut time, motion(2.0), 25).is_empty());
+        assert!(!detector.is_idle());
+
+        assert_eq!(hold(&mut detector, &mut time, motion(2.0), 10), [true]);
+
+        // Slow drift above the idle threshold but below the wake threshold keeps the low power
+        assert!(hold(&mut detector, &mut time, motion(8.0), 10).is_empty());
+        assert!(detector.is_idle());
+
+        assert_eq!(hold(&mut detector, &mut time, motion(30.0), 1), [false]);
+
+        // Idle again, then restored by a button press
+        assert_eq!(hold(&mut detector, &mut time, motion(0.0), 31), [true]);
+        assert!(detector.report_input(time));
+        assert!(!detector.is_idle());
+        assert!(!detector.report_input(time));
+    }
+}
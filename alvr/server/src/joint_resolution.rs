@@ -0,0 +1,285 @@
+use alvr_common::glam::UVec2;
+use alvr_session::{settings_schema::Switch, BitrateMode, JointResolutionConfig};
+use std::time::{Duration, Instant};
+
+// The joint selection applies only to the adaptive bitrate, which provides the bandwidth estimate
+pub fn joint_resolution_config(mode: &BitrateMode) -> Option<&JointResolutionConfig> {
+    if let BitrateMode::Adaptive {
+        joint_resolution: Switch::Enabled(config),
+        ..
+    } = mode
+    {
+        Some(config)
+    } else {
+        None
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct OperatingPoint {
+    pub resolution_scale: f32,
+    // Below this the frames are encoded with less than the minimum bits per pixel
+    pub min_bitrate_bps: f32,
+}
+
+// Rate-distortion curve of the stream, from the lowest resolution. The quality at a resolution grows
+// with the bitrate, until the resolution itself is the limit. Below the minimum bits per pixel the
+// next lower resolution looks better.
+pub fn operating_points(
+    config: &JointResolutionConfig,
+    view_resolution: UVec2,
+    fps: f32,
+) -> Vec<OperatingPoint> {
+    let mut scales = config
+        .resolution_scales
+        .iter()
+        .copied()
+        .filter(|scale| *scale > 0.0 && *scale <= 1.0)
+        .collect::<Vec<_>>();
+    scales.sort_by(f32::total_cmp);
+    scales.dedup();
+    if scales.is_empty() {
+        scales.push(1.0);
+    }
+
+    let pixels_per_second = 2.0 * view_resolution.x as f32 * view_resolution.y as f32 * fps;
+
+    scales
+        .into_iter()
+        .map(|scale| OperatingPoint {
+            resolution_scale: scale,
+            min_bitrate_bps: config.min_bits_per_pixel * pixels_per_second * scale * scale,
+        })
+        .collect()
+}
+
+// Encode resolution of a scale, within the one negotiated with the client. The compositor downscales
+// the frames to it and the client upscales them back, so a switch restarts only the encoder
+pub fn encode_resolution(
+    view_resolution: UVec2,
+    upscaled_view_resolution: UVec2,
+    resolution_scale: f32,
+) -> UVec2 {
+    let max_resolution = (upscaled_view_resolution.as_vec2() * resolution_scale).as_uvec2();
+
+    alvr_packets::cap_encode_resolution(view_resolution, max_resolution)
+}
+
+// Chooses the operating point for the bandwidth estimated by the adaptive bitrate, which keeps
+// setting the bitrate within the point. Resolution switches are expensive: a higher point is chosen
+// only with a margin above its minimum bitrate and after the bandwidth sustained it for a while, a
+// lower one when the current point cannot be sustained. This keeps the choice stable when the
+// bandwidth hovers around a threshold.
+pub struct JointResolutionController {
+    points: Vec<OperatingPoint>,
+    current: usize,
+    // Point supported by the bandwidth, and since when
+    candidate: Option<(usize, Instant)>,
+}
+
+impl JointResolutionController {
+    // Starts from the point closest to the resolution scale of the stream
+    pub fn new(points: Vec<OperatingPoint>, resolution_scale: f32) -> Self {
+        let current = (0..points.len())
+            .min_by(|&a, &b| {
+                let distance =
+                    |index: usize| (points[index].resolution_scale - resolution_scale).abs();
+                distance(a).total_cmp(&distance(b))
+            })
+            .unwrap_or(0);
+
+        Self {
+            points,
+            current,
+            candidate: None,
+        }
+    }
+
+    pub fn operating_point(&self) -> OperatingPoint {
+        self.points[self.current]
+    }
+
+    // Returns the new operating point when switching
+    pub fn update(
+        &mut self,
+        config: &JointResolutionConfig,
+        bandwidth_bps: f32,
+        now: Instant,
+    ) -> Option<OperatingPoint> {
+        if bandwidth_bps.is_nan() || bandwidth_bps <= 0.0 {
+            return None;
+        }
+
+        let sustains = |index: usize, margin: f32| {
+            bandwidth_bps >= self.points[index].min_bitrate_bps * (1.0 + margin)
+        };
+
+        let (target, hold_s) = if self.current > 0 && !sustains(self.current, 0.0) {
+            // The highest lower point that can be sustained, or the lowest one
+            let target = (0..self.current)
+                .rev()
+                .find(|&index| sustains(index, 0.0))
+                .unwrap_or(0);
+
+            (target, config.downgrade_hold_s)
+        } else {
+            let target = (self.current + 1..self.points.len())
+                .rev()
+                .find(|&index| sustains(index, config.switch_margin))
+                .unwrap_or(self.current);
+
+            (target, config.upgrade_hold_s)
+        };
+
+        if target == self.current {
+            self.candidate = None;
+
+            return None;
+        }
+
+        // The hold restarts when the direction changes
+        let since = match self.candidate {
+            Some((candidate, since)) if (candidate > self.current) == (target > self.current) => {
+                since
+            }
+            _ => now,
+        };
+        if now < since + Duration::from_secs_f32(hold_s) {
+            self.candidate = Some((target, since));
+
+            return None;
+        }
+
+        self.current = target;
+        self.candidate = None;
+
+        Some(self.points[target])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const STEP: Duration = Duration::from_secs(1);
+
+    fn config() -> JointResolutionConfig {
+        JointResolutionConfig {
+            resolution_scales: vec![1.0, 0.5, 0.75],
+            min_bits_per_pixel: 0.08,
+            switch_margin: 0.25,
+            upgrade_hold_s: 30.0,
+            downgrade_hold_s: 3.0,
+        }
+    }
+
+    fn new_controller(resolution_scale: f32) -> JointResolutionController {
+        let points = operating_points(&config(), UVec2::new(1856, 1920), 72.0);
+
+        JointResolutionController::new(points, resolution_scale)
+    }
+
+    // Feeds one estimate per second, returns the switches as (seconds, resolution scale)
+    fn run(
+        controller: &mut JointResolutionController,
+        start: Instant,
+        bandwidths_mbps: impl IntoIterator<Item = f32>,
+    ) -> Vec<(u32, f32)> {
+        bandwidths_mbps
+            .into_iter()
+            .enumerate()
+            .filter_map(|(second, bandwidth_mbps)| {
+                let now = start + STEP * second as u32;
+                controller
+                    .update(&config(), bandwidth_mbps * 1e6, now)
+                    .map(|point| (second as u32, point.resolution_scale))
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_bandwidth_levels_select_stable_operating_points() {
+        let points = operating_points(&config(), UVec2::new(1856, 1920), 72.0);
+        let min_bitrates_mbps = points
+            .iter()
+            .map(|point| (point.min_bitrate_bps / 1e6).round())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            points
+                .iter()
+                .map(|p| p.resolution_scale)
+                .collect::<Vec<_>>(),
+            [0.5, 0.75, 1.0]
+        );
+        assert_eq!(min_bitrates_mbps, [10.0, 23.0, 41.0]);
+
+        let mut controller = new_controller(1.0);
+        let mut start = Instant::now();
+        let mut level = |controller: &mut JointResolutionController, mbps: f32, seconds: usize| {
+            let switches = run(controller, start, vec![mbps; seconds]);
+            start += STEP * seconds as u32;
+
+            switches
+        };
+
+        // Plenty of bandwidth keeps the full resolution
+        assert!(level(&mut controller, 100.0, 60).is_empty());
+
+        // Drops are followed quickly, one point at a time when possible
+        assert_eq!(level(&mut controller, 30.0, 60), [(3, 0.75)]);
+        assert_eq!(level(&mut controller, 15.0, 60), [(3, 0.5)]);
+        assert_eq!(level(&mut controller, 5.0, 60), []);
+
+        // Recoveries wait for the bandwidth to hold, and for the margin above the next point. 45Mbps
+        // is above the minimum bitrate of the full resolution, but not by the margin
+        assert_eq!(level(&mut controller, 45.0, 60), [(30, 0.75)]);
+        assert_eq!(level(&mut controller, 200.0, 60), [(30, 1.0)]);
+        assert_eq!(controller.operating_point().resolution_scale, 1.0);
+
+        // A short drop does not switch
+        let mut switches = level(&mut controller, 20.0, 2);
+        switches.extend(level(&mut controller, 100.0, 60));
+        assert!(switches.is_empty());
+    }
+
+    #[test]
+    fn test_encode_resolution_stays_within_the_negotiated_one() {
+        let upscaled = UVec2::new(1856, 1920);
+
+        assert_eq!(encode_resolution(upscaled, upscaled, 1.0), upscaled);
+        assert_eq!(
+            encode_resolution(upscaled, upscaled, 0.5),
+            UVec2::new(928, 960)
+        );
+
+        // Capped by the maximum encode resolution
+        let capped = UVec2::new(1216, 1280);
+        assert_eq!(encode_resolution(capped, upscaled, 0.75), capped);
+        assert_eq!(
+            encode_resolution(capped, upscaled, 0.5),
+            UVec2::new(896, 960)
+        );
+    }
+
+    #[test]
+    fn test_bandwidth_around_threshold_does_not_oscillate() {
+        // Hovering around the minimum bitrate of the full resolution (41Mbps), in short cycles
+        let mut controller = new_controller(1.0);
+        let noisy = (0..600).map(|second| if second % 4 < 2 { 38.0 } else { 44.0 });
+        assert!(run(&mut controller, Instant::now(), noisy).is_empty());
+
+        // In long cycles the resolution is lowered once, the peaks do not reach the margin to go back
+        let slow = (0..600).map(|second| {
+            let phase = second as f32 / 60.0 * std::f32::consts::TAU;
+            42.0 + 8.0 * phase.sin()
+        });
+        let switches = run(&mut controller, Instant::now(), slow);
+        assert_eq!(switches.len(), 1);
+        assert_eq!(switches[0].1, 0.75);
+
+        // Starting from a lower point, a bandwidth between the thresholds is stable too
+        let mut controller = new_controller(0.75);
+        assert!(run(&mut controller, Instant::now(), vec![35.0; 600]).is_empty());
+        assert_eq!(controller.operating_point().resolution_scale, 0.75);
+    }
+}
@@ -1,17 +1,32 @@
+mod bandwidth_hold;
+mod benchmark;
 mod bitrate;
 mod body_tracking;
 mod c_api;
+mod congestion_control;
+mod connect_retry;
 mod connection;
+mod driver_requirements;
 mod face_tracking;
+mod fast_reconnect;
+mod foveation;
+mod frame_user_data;
 mod graphics;
 mod hand_gestures;
 mod haptics;
+mod idle;
 mod input_mapping;
+mod joint_resolution;
 mod logging_backend;
 mod openvr;
+mod qp_map;
+mod recording;
 mod sockets;
 mod statistics;
+mod stream_pause;
+mod teardown;
 mod tracking;
+mod video_slices;
 mod web_server;
 
 #[allow(
@@ -29,37 +44,50 @@ use bindings::*;
 
 use crate::connection::VideoPacket;
 use alvr_common::{
+    anyhow::Result,
     error,
-    glam::Vec2,
+    glam::{UVec2, Vec2},
     once_cell::sync::Lazy,
     parking_lot::{Mutex, RwLock},
     settings_schema::Switch,
-    warn, ConnectionState, Fov, LifecycleState, Pose, RelaxedAtomic, DEVICE_ID_TO_PATH,
+    warn, ConnectionState, ConnectionStateMachine, Fov, LifecycleState, Pose, RelaxedAtomic, Rng,
+    RngSource, DEVICE_ID_TO_PATH,
 };
 use alvr_events::{EventType, HapticsEvent};
 use alvr_filesystem::{self as afs, Layout};
 use alvr_packets::{
     BatteryInfo, ButtonEntry, ClientListAction, DecoderInitializationConfig, Haptics, Tracking,
-    VideoPacketHeader,
+    VideoPacketHeader, MAX_FRAME_USER_DATA_SIZE,
 };
 use alvr_server_io::ServerDataManager;
-use alvr_session::{CodecType, OpenvrProperty, Settings};
+use alvr_session::{
+    BitrateConfig, BitrateMode, CodecType, FoveatedEncodingConfig, H264Profile,
+    IdlePowerSavingConfig, OpenvrProperty, RecordingFormat, Settings,
+};
+use bandwidth_hold::BandwidthHold;
+use benchmark::BenchmarkRun;
 use bitrate::{BitrateManager, DynamicEncoderParams};
+use foveation::FoveationEpochs;
+use frame_user_data::FrameUserDataQueue;
+use idle::IdleDetector;
+use joint_resolution::JointResolutionController;
+use recording::{AudioTrackInfo, VideoRecording};
 use statistics::StatisticsManager;
 use std::{
-    collections::{HashSet, VecDeque},
+    borrow::Cow,
+    collections::{HashMap, HashSet, VecDeque},
     env,
     ffi::CString,
     fs::File,
-    io::Write,
     sync::{
-        atomic::{AtomicBool, Ordering},
+        atomic::{AtomicBool, AtomicU32, Ordering},
         mpsc::{SyncSender, TrySendError},
         Arc,
     },
     thread::{self, JoinHandle},
     time::{Duration, Instant},
 };
+use stream_pause::StreamPause;
 use sysinfo::{ProcessRefreshKind, RefreshKind};
 use tokio::{runtime::Runtime, sync::broadcast};
 
@@ -95,6 +123,25 @@ pub enum ServerCoreEvent {
     },
     Buttons(Vec<ButtonEntry>), // Note: this is after mapping
     RequestIDR,
+    // Restart the encoder with the new foveated encoding, then call
+    // report_foveated_encoding_applied()
+    FoveatedEncoding {
+        epoch: u32,
+        config: Option<FoveatedEncodingConfig>,
+    },
+    // Stop or restart encoding, while keeping the connection alive
+    StreamPaused(bool),
+    // Encode only one of every divider frames presented by the game, for the idle power saving
+    EncodeFrameDivider(u32),
+    // Size of the frames of each eye, applied by the next encoder restart
+    EncodeResolution(UVec2),
+    // Restart the encoder with the new codec, as part of a fast reconnect
+    VideoCodec {
+        codec: CodecType,
+        h264_profile: H264Profile,
+    },
+    // Stream the benchmark pattern instead of the game. The encoder is not restarted
+    BenchmarkTestPattern(bool),
     GameRenderLatencyFeedback(Duration), // only used for SteamVR
     ShutdownPending,
     RestartPending,
@@ -106,19 +153,162 @@ pub struct ConnectionContext {
     bitrate_manager: Mutex<BitrateManager>,
     decoder_config: Mutex<Option<DecoderInitializationConfig>>,
     video_mirror_sender: Mutex<Option<broadcast::Sender<Vec<u8>>>>,
-    video_recording_file: Mutex<Option<File>>,
+    video_recording_file: Mutex<Option<VideoRecording>>,
+    // Zero if game audio is disabled
+    game_audio_sample_rate: AtomicU32,
     connection_threads: Mutex<Vec<JoinHandle<()>>>,
     clients_to_be_removed: Mutex<HashSet<String>>,
     video_channel_sender: Mutex<Option<SyncSender<VideoPacket>>>,
     haptics_sender: Mutex<Option<StreamSender<Haptics>>>,
+    foveation_epochs: Mutex<FoveationEpochs>,
+    // User data waiting for the frame with the same target timestamp to be encoded
+    frame_user_data: Mutex<FrameUserDataQueue>,
+    stream_pause: Mutex<StreamPause>,
+    // Fed by the statistics of the client, with the adaptive bitrate
+    bandwidth_hold: Mutex<BandwidthHold>,
+    // Fed by the tracking and the input of the client
+    idle_detector: Mutex<IdleDetector>,
+    // The encoder can be restarted during the stream. Needed to change the foveated encoding, for
+    // the fast reconnects, the codecs of the benchmark and the joint resolution switches
+    encoder_restart_supported: RelaxedAtomic,
+    // Handled by the keepalive thread, which renegotiates the stream with the current settings
+    fast_reconnect_requested: RelaxedAtomic,
+    // Handled by the tracking thread, using the current head pose
+    recenter_requested: RelaxedAtomic,
+    // Stepped by the keepalive thread
+    benchmark: Mutex<Option<BenchmarkRun>>,
+    // Chosen at each connection
+    rng_source: Mutex<RngSource>,
+    // Created at each connection if enabled, stepped by the keepalive thread
+    joint_resolution: Mutex<Option<JointResolutionController>>,
+    // Chosen by the joint resolution controller. Like the encoder, it is kept across the
+    // connections until SteamVR restarts
+    joint_resolution_scale: Mutex<Option<f32>>,
+    // Encode resolution of the restarted encoder, if different from the negotiated one
+    encode_resolution: Mutex<Option<UVec2>>,
+    // Driven by the handshake loop and by the connection of each client
+    connection_phases: Mutex<HashMap<String, ConnectionStateMachine>>,
+}
+
+// Random generator of a component of the stream. The same seed gives the same sequence
+pub fn stream_rng(connection_context: &ConnectionContext, name: &str) -> Rng {
+    connection_context.rng_source.lock().stream(name)
+}
+
+// The benchmark streams with the constant bitrate of its current point, without changing the
+// settings
+pub fn stream_bitrate_config<'a>(
+    connection_context: &ConnectionContext,
+    settings: &'a Settings,
+) -> Cow<'a, BitrateConfig> {
+    let maybe_point = connection_context
+        .benchmark
+        .lock()
+        .as_ref()
+        .and_then(|run| run.point);
+    if let Some(point) = maybe_point {
+        Cow::Owned(BitrateConfig {
+            mode: BitrateMode::ConstantMbps(point.bitrate_mbps),
+            ..settings.video.bitrate.clone()
+        })
+    } else {
+        Cow::Borrowed(&settings.video.bitrate)
+    }
+}
+
+// Applies a change of the pause state to the encoder. update() returns true if the state changed
+pub fn update_stream_pause(
+    connection_context: &ConnectionContext,
+    update: impl FnOnce(&mut StreamPause) -> bool,
+) {
+    let mut stream_pause_lock = connection_context.stream_pause.lock();
+    if !update(&mut stream_pause_lock) {
+        return;
+    }
+    let paused = stream_pause_lock.is_paused();
+
+    let mut events_queue_lock = connection_context.events_queue.lock();
+    events_queue_lock.push_back(ServerCoreEvent::StreamPaused(paused));
+    if paused {
+        info!("Stream paused");
+    } else {
+        events_queue_lock.push_back(ServerCoreEvent::RequestIDR);
+        info!("Stream resumed");
+    }
+}
+
+// Applies a change of the freeze and hold state. The encoder keeps running, the frames are dropped
+// while holding. update() returns true if the state changed
+pub fn update_bandwidth_hold(
+    connection_context: &ConnectionContext,
+    update: impl FnOnce(&mut BandwidthHold) -> bool,
+) {
+    let mut bandwidth_hold_lock = connection_context.bandwidth_hold.lock();
+    if !update(&mut bandwidth_hold_lock) {
+        return;
+    }
+
+    if bandwidth_hold_lock.is_holding() {
+        warn!("Bandwidth below the minimum bitrate, holding the last frame");
+    } else {
+        connection_context
+            .events_queue
+            .lock()
+            .push_back(ServerCoreEvent::RequestIDR);
+        info!("Bandwidth recovered, resuming the stream");
+    }
 }
 
-pub fn create_recording_file(connection_context: &ConnectionContext, settings: &Settings) {
-    let codec = settings.video.preferred_codec;
-    let ext = match codec {
-        CodecType::H264 => "h264",
-        CodecType::Hevc => "h265",
-        CodecType::AV1 => "av1",
+// Switches between the active and the low power profiles when the idle state changes. Without a
+// config the low power profile is never applied
+pub fn update_idle_power_saving(
+    connection_context: &ConnectionContext,
+    config: Option<&IdlePowerSavingConfig>,
+    update: impl FnOnce(&mut IdleDetector) -> bool,
+) {
+    let mut idle_detector_lock = connection_context.idle_detector.lock();
+    if !update(&mut idle_detector_lock) {
+        return;
+    }
+    let low_power = idle_detector_lock.is_idle() && config.is_some();
+
+    connection_context
+        .bitrate_manager
+        .lock()
+        .set_low_power(low_power);
+
+    let frame_divider = match config {
+        Some(config) if low_power => u32::max(config.low_power_frame_divider, 1),
+        _ => 1,
+    };
+    connection_context
+        .events_queue
+        .lock()
+        .push_back(ServerCoreEvent::EncodeFrameDivider(frame_divider));
+
+    if low_power {
+        info!("User idle, switching to the low power profile");
+    } else {
+        info!("Activity detected, restoring the active profile");
+    }
+}
+
+pub fn create_recording_file(
+    connection_context: &ConnectionContext,
+    data_manager: &ServerDataManager,
+) {
+    let settings = data_manager.settings();
+    let decoder_config = connection_context.decoder_config.lock().clone();
+    let codec = decoder_config
+        .as_ref()
+        .map(|config| config.codec)
+        .unwrap_or(settings.video.preferred_codec);
+    let format = settings.extra.capture.recording_format;
+    let ext = match (format, codec) {
+        (RecordingFormat::Matroska, _) => "mkv",
+        (RecordingFormat::RawVideo, CodecType::H264) => "h264",
+        (RecordingFormat::RawVideo, CodecType::Hevc) => "h265",
+        (RecordingFormat::RawVideo, CodecType::AV1) => "av1",
     };
 
     let path = FILESYSTEM_LAYOUT.log_dir.join(format!(
@@ -127,12 +317,33 @@ pub fn create_recording_file(connection_context: &ConnectionContext, settings: &
     ));
 
     match File::create(path) {
-        Ok(mut file) => {
-            if let Some(config) = &*connection_context.decoder_config.lock() {
-                file.write_all(&config.config_buffer).ok();
+        Ok(file) => {
+            let mut recording = if format == RecordingFormat::Matroska {
+                // The size, the chroma format and the bit depth are read from the parameter sets
+                let sample_rate = connection_context
+                    .game_audio_sample_rate
+                    .load(Ordering::Relaxed);
+
+                VideoRecording::new_matroska(
+                    file,
+                    codec,
+                    (sample_rate != 0).then_some(AudioTrackInfo {
+                        sample_rate,
+                        channels: 2,
+                    }),
+                )
+            } else {
+                VideoRecording::Raw(file)
+            };
+
+            if let Some(config) = decoder_config {
+                if let Err(e) = recording.write_config_nals(&config.config_buffer) {
+                    error!("Failed to record video on disk: {e}");
+                    return;
+                }
             }
 
-            *connection_context.video_recording_file.lock() = Some(file);
+            *connection_context.video_recording_file.lock() = Some(recording);
 
             unsafe { RequestIDR() };
         }
@@ -142,6 +353,20 @@ pub fn create_recording_file(connection_context: &ConnectionContext, settings: &
     }
 }
 
+// Stops the recording if the write failed
+pub fn write_recording(
+    connection_context: &ConnectionContext,
+    write: impl FnOnce(&mut VideoRecording) -> Result<()>,
+) {
+    let mut recording_lock = connection_context.video_recording_file.lock();
+    if let Some(recording) = &mut *recording_lock {
+        if let Err(e) = write(recording) {
+            error!("Recording stopped: {e}");
+            *recording_lock = None;
+        }
+    }
+}
+
 pub fn notify_restart_driver() {
     let mut system = sysinfo::System::new_with_specifics(
         RefreshKind::new().with_processes(ProcessRefreshKind::everything()),
@@ -188,10 +413,25 @@ impl ServerCoreContext {
             decoder_config: Mutex::new(None),
             video_mirror_sender: Mutex::new(None),
             video_recording_file: Mutex::new(None),
+            game_audio_sample_rate: AtomicU32::new(0),
             connection_threads: Mutex::new(Vec::new()),
             clients_to_be_removed: Mutex::new(HashSet::new()),
             video_channel_sender: Mutex::new(None),
             haptics_sender: Mutex::new(None),
+            foveation_epochs: Mutex::new(FoveationEpochs::new()),
+            frame_user_data: Mutex::new(FrameUserDataQueue::new()),
+            stream_pause: Mutex::new(StreamPause::new(false)),
+            bandwidth_hold: Mutex::new(BandwidthHold::new()),
+            idle_detector: Mutex::new(IdleDetector::new(Instant::now())),
+            encoder_restart_supported: RelaxedAtomic::new(false),
+            fast_reconnect_requested: RelaxedAtomic::new(false),
+            recenter_requested: RelaxedAtomic::new(false),
+            benchmark: Mutex::new(None),
+            rng_source: Mutex::new(RngSource::new(0)),
+            joint_resolution: Mutex::new(None),
+            joint_resolution_scale: Mutex::new(None),
+            encode_resolution: Mutex::new(None),
+            connection_phases: Mutex::new(HashMap::new()),
         });
 
         let webserver_runtime = Runtime::new().unwrap();
@@ -247,6 +487,11 @@ impl ServerCoreContext {
         }));
     }
 
+    // The connection loops stop, the context is dropped afterwards
+    fn set_shutting_down(&self) {
+        *self.lifecycle_state.write() = LifecycleState::ShuttingDown;
+    }
+
     fn poll_event(&self) -> Option<ServerCoreEvent> {
         self.connection_context.events_queue.lock().pop_front()
     }
@@ -290,9 +535,9 @@ impl ServerCoreContext {
             sender.send(config_buffer.clone()).ok();
         }
 
-        if let Some(file) = &mut *self.connection_context.video_recording_file.lock() {
-            file.write_all(&config_buffer).ok();
-        }
+        write_recording(&self.connection_context, |recording| {
+            recording.write_config_nals(&config_buffer)
+        });
 
         *self.connection_context.decoder_config.lock() = Some(DecoderInitializationConfig {
             codec,
@@ -300,14 +545,96 @@ impl ServerCoreContext {
         });
     }
 
+    // Attach user data to the frame, to be retrieved on the client together with the decoded
+    // frame. Must be called before the frame is encoded
+    fn set_frame_user_data(&self, target_timestamp: Duration, user_data: Vec<u8>) {
+        if user_data.len() > MAX_FRAME_USER_DATA_SIZE {
+            warn!(
+                "Discarding frame user data. Reason: {} bytes, the maximum is {MAX_FRAME_USER_DATA_SIZE}",
+                user_data.len()
+            );
+            return;
+        }
+
+        self.connection_context
+            .frame_user_data
+            .lock()
+            .push(target_timestamp, user_data);
+    }
+
+    fn set_stream_paused(&self, paused: bool) {
+        update_stream_pause(&self.connection_context, |stream_pause| {
+            stream_pause.request_pause(paused)
+        });
+    }
+
     fn send_video_nal(&self, target_timestamp: Duration, nal_buffer: Vec<u8>, is_idr: bool) {
         // start in the corrupts state, the client didn't receive the initial IDR yet.
         static STREAM_CORRUPTED: AtomicBool = AtomicBool::new(true);
         static LAST_IDR_INSTANT: Lazy<Mutex<Instant>> = Lazy::new(|| Mutex::new(Instant::now()));
+        static FRAME_INDEX: AtomicU32 = AtomicU32::new(0);
 
         if let Some(sender) = &*self.connection_context.video_channel_sender.lock() {
             let buffer_size = nal_buffer.len();
 
+            // Counts also the frames dropped below, the client sees them as lost
+            let frame_index = if is_idr {
+                0
+            } else {
+                FRAME_INDEX.load(Ordering::SeqCst).wrapping_add(1)
+            };
+            FRAME_INDEX.store(frame_index, Ordering::SeqCst);
+
+            let user_data = self
+                .connection_context
+                .frame_user_data
+                .lock()
+                .take(target_timestamp);
+
+            if !self
+                .connection_context
+                .stream_pause
+                .lock()
+                .frame_allowed(is_idr)
+            {
+                return;
+            }
+
+            {
+                // Read before locking the hold, the statistics thread locks them in this order
+                let bitrate_config = stream_bitrate_config(
+                    &self.connection_context,
+                    SERVER_DATA_MANAGER.read().settings(),
+                )
+                .into_owned();
+                let mut bandwidth_hold_lock = self.connection_context.bandwidth_hold.lock();
+
+                if let Some((_, config)) =
+                    bandwidth_hold::freeze_and_hold_config(&bitrate_config.mode)
+                {
+                    if bandwidth_hold_lock.probe_due(config, Instant::now()) {
+                        self.connection_context
+                            .events_queue
+                            .lock()
+                            .push_back(ServerCoreEvent::RequestIDR);
+                    }
+                }
+
+                if !bandwidth_hold_lock.frame_allowed(is_idr) {
+                    return;
+                }
+            }
+
+            let Some(foveation_epoch) = self
+                .connection_context
+                .foveation_epochs
+                .lock()
+                .frame_epoch(is_idr)
+            else {
+                warn!("Dropping video packet. Reason: Foveated encoding is changing");
+                return;
+            };
+
             if is_idr {
                 STREAM_CORRUPTED.store(false, Ordering::SeqCst);
             }
@@ -330,7 +657,7 @@ impl ServerCoreContext {
                     if is_idr {
                         create_recording_file(
                             &self.connection_context,
-                            SERVER_DATA_MANAGER.read().settings(),
+                            &SERVER_DATA_MANAGER.read(),
                         );
                         *LAST_IDR_INSTANT.lock() = Instant::now();
                     }
@@ -348,17 +675,41 @@ impl ServerCoreContext {
                     sender.send(nal_buffer.clone()).ok();
                 }
 
-                if let Some(file) = &mut *self.connection_context.video_recording_file.lock() {
-                    file.write_all(&nal_buffer).ok();
-                }
+                write_recording(&self.connection_context, |recording| {
+                    recording.write_video(&nal_buffer, is_idr)
+                });
+
+                let slices_count = SERVER_DATA_MANAGER
+                    .read()
+                    .session()
+                    .openvr_config
+                    .slices_count;
+                let codec = self
+                    .connection_context
+                    .decoder_config
+                    .lock()
+                    .as_ref()
+                    .map(|config| config.codec);
+                let slices = match codec {
+                    Some(codec) if slices_count > 1 => {
+                        video_slices::slice_ranges(codec, &nal_buffer)
+                    }
+                    _ => vec![0..nal_buffer.len()],
+                };
 
                 if matches!(
                     sender.try_send(VideoPacket {
                         header: VideoPacketHeader {
                             timestamp: target_timestamp,
-                            is_idr
+                            is_idr,
+                            frame_index,
+                            foveation_epoch,
+                            user_data,
+                            slice_index: 0,
+                            slices_count: slices.len() as u32,
                         },
                         payload: nal_buffer,
+                        slices,
                     }),
                     Err(TrySendError::Full(_))
                 ) {
@@ -384,13 +735,29 @@ impl ServerCoreContext {
         }
     }
 
+    fn report_foveated_encoding_applied(&self, epoch: u32) {
+        self.connection_context
+            .foveation_epochs
+            .lock()
+            .report_applied(epoch);
+
+        // The transition frame
+        self.connection_context
+            .events_queue
+            .lock()
+            .push_back(ServerCoreEvent::RequestIDR);
+    }
+
     fn get_dynamic_encoder_params(&self) -> Option<DynamicEncoderParams> {
         let pair = {
             let server_data_lock = SERVER_DATA_MANAGER.read();
             self.connection_context
                 .bitrate_manager
                 .lock()
-                .get_encoder_params(&server_data_lock.settings().video.bitrate)
+                .get_encoder_params(&stream_bitrate_config(
+                    &self.connection_context,
+                    server_data_lock.settings(),
+                ))
         };
 
         if let Some((params, stats)) = pair {
@@ -410,6 +777,12 @@ impl ServerCoreContext {
         }
     }
 
+    fn report_encode_quality(&self, psnr_db: Option<f64>, ssim: Option<f64>) {
+        if let Some(stats) = &mut *self.connection_context.statistics_manager.lock() {
+            stats.report_encode_quality(psnr_db, ssim);
+        }
+    }
+
     fn report_present(&self, target_timestamp: Duration, offset: Duration) {
         if let Some(stats) = &mut *self.connection_context.statistics_manager.lock() {
             stats.report_frame_present(target_timestamp, offset);
@@ -2,22 +2,63 @@ mod props;
 mod tracking;
 
 use crate::{
-    input_mapping, logging_backend, FfiButtonValue, FfiDynamicEncoderParams, FfiFov,
-    FfiViewsConfig, ServerCoreContext, ServerCoreEvent, SERVER_DATA_MANAGER,
+    driver_requirements::{self, DriverInfo, DriverVersion},
+    input_mapping, logging_backend,
+    teardown::Teardown,
+    FfiButtonValue, FfiDynamicEncoderParams, FfiFov, FfiFoveatedEncoding, FfiViewsConfig,
+    FfiVulkanDriverInfo, ServerCoreContext, ServerCoreEvent, SERVER_DATA_MANAGER,
+};
+use alvr_common::{
+    error, info, once_cell::sync::Lazy, parking_lot::RwLock, warn, HAND_LEFT_ID, HAND_RIGHT_ID,
 };
-use alvr_common::{once_cell::sync::Lazy, parking_lot::RwLock, warn, HAND_LEFT_ID, HAND_RIGHT_ID};
 use alvr_packets::{ButtonValue, Haptics};
 use alvr_session::CodecType;
 use std::{
-    ffi::{c_char, c_void},
+    ffi::{c_char, c_void, CStr},
     ptr, thread,
     time::{Duration, Instant},
 };
 
+const TEARDOWN_STAGE_TIMEOUT: Duration = Duration::from_secs(3);
+const VIDEO_DRAIN_TIME: Duration = Duration::from_millis(100);
+
 static SERVER_CORE_CONTEXT: Lazy<RwLock<Option<ServerCoreContext>>> = Lazy::new(|| {
     logging_backend::init_logging();
 
-    RwLock::new(Some(ServerCoreContext::new()))
+    let context = ServerCoreContext::new();
+    // On Linux the encoder is bound to the connection with the Vulkan layer, which cannot be
+    // reestablished
+    context
+        .connection_context
+        .encoder_restart_supported
+        .set(cfg!(windows));
+
+    // SteamVR can be killed without shutting down the driver, which leaves the GPU resources of the
+    // encoder and the sockets to the OS. The process is not exited here, SteamVR owns it
+    if let Err(e) = ctrlc::set_handler(|| {
+        info!("Termination requested, shutting down");
+        if let Some(context) = &*SERVER_CORE_CONTEXT.read() {
+            context.set_shutting_down();
+        }
+        DRIVER_TEARDOWN.run();
+    }) {
+        warn!("Failed to set the termination handler: {e}");
+    }
+
+    RwLock::new(Some(context))
+});
+
+// Run on the termination signals, when SteamVR exits or when it unloads the driver
+static DRIVER_TEARDOWN: Lazy<Teardown> = Lazy::new(|| {
+    Teardown::new(TEARDOWN_STAGE_TIMEOUT)
+        // Completes the frame being encoded and releases the encoder and its GPU resources
+        .stage("encoder", || unsafe { crate::StopEncoder() })
+        // Gives time to the video send thread to send the frames left in the queue
+        .stage("video queue", || thread::sleep(VIDEO_DRAIN_TIME))
+        // Disconnects the clients, joins the connection threads and closes the sockets
+        .stage("server context", || {
+            SERVER_CORE_CONTEXT.write().take();
+        })
 });
 
 extern "C" fn driver_ready_idle(set_default_chap: bool) {
@@ -194,6 +235,37 @@ extern "C" fn driver_ready_idle(set_default_chap: bool) {
                     }
                 }
                 ServerCoreEvent::RequestIDR => unsafe { crate::RequestIDR() },
+                ServerCoreEvent::FoveatedEncoding { epoch, config } => {
+                    unsafe {
+                        crate::SetFoveatedEncoding(FfiFoveatedEncoding {
+                            enable: config.is_some().into(),
+                            centerSizeX: config.as_ref().map(|c| c.center_size_x).unwrap_or(0.0),
+                            centerSizeY: config.as_ref().map(|c| c.center_size_y).unwrap_or(0.0),
+                            centerShiftX: config.as_ref().map(|c| c.center_shift_x).unwrap_or(0.0),
+                            centerShiftY: config.as_ref().map(|c| c.center_shift_y).unwrap_or(0.0),
+                            edgeRatioX: config.as_ref().map(|c| c.edge_ratio_x).unwrap_or(0.0),
+                            edgeRatioY: config.as_ref().map(|c| c.edge_ratio_y).unwrap_or(0.0),
+                        })
+                    };
+
+                    if let Some(context) = &*SERVER_CORE_CONTEXT.read() {
+                        context.report_foveated_encoding_applied(epoch);
+                    }
+                }
+                ServerCoreEvent::StreamPaused(paused) => unsafe { crate::SetStreamPaused(paused) },
+                ServerCoreEvent::EncodeFrameDivider(divider) => unsafe {
+                    crate::SetEncodeFrameDivider(divider)
+                },
+                ServerCoreEvent::EncodeResolution(resolution) => unsafe {
+                    crate::SetEncodeResolution(resolution.x, resolution.y)
+                },
+                ServerCoreEvent::VideoCodec {
+                    codec,
+                    h264_profile,
+                } => unsafe { crate::SetVideoCodec(codec as _, h264_profile as _) },
+                ServerCoreEvent::BenchmarkTestPattern(enabled) => unsafe {
+                    crate::SetBenchmarkTestPattern(enabled)
+                },
                 ServerCoreEvent::GameRenderLatencyFeedback(game_latency) => {
                     if cfg!(target_os = "linux") && game_latency.as_secs_f32() > 0.25 {
                         let now = Instant::now();
@@ -288,6 +360,15 @@ extern "C" fn report_composed(timestamp_ns: u64, offset_ns: u64) {
     }
 }
 
+extern "C" fn report_encode_quality(psnr_db: f64, ssim: f64) {
+    if let Some(context) = &*SERVER_CORE_CONTEXT.read() {
+        context.report_encode_quality(
+            (psnr_db >= 0.0).then_some(psnr_db),
+            (ssim >= 0.0).then_some(ssim),
+        );
+    }
+}
+
 extern "C" fn report_present(timestamp_ns: u64, offset_ns: u64) {
     if let Some(context) = &*SERVER_CORE_CONTEXT.read() {
         context.report_present(
@@ -318,8 +399,40 @@ extern "C" fn wait_for_vsync() {
     }
 }
 
+extern "C" fn check_vulkan_driver(info: FfiVulkanDriverInfo) -> bool {
+    let to_string = |ptr| {
+        unsafe { CStr::from_ptr(ptr) }
+            .to_string_lossy()
+            .into_owned()
+    };
+
+    let info = DriverInfo {
+        device_name: to_string(info.deviceName),
+        driver_name: to_string(info.driverName),
+        driver_info: to_string(info.driverInfo),
+        driver_id: info.driverId,
+        driver_version: DriverVersion::decode(info.driverId, info.driverVersion),
+        api_version: (
+            (info.apiVersion >> 22) & 0x7f,
+            (info.apiVersion >> 12) & 0x3ff,
+        ),
+        conformance_version: info.conformanceVersion,
+    };
+    info!("Vulkan device: {info}");
+
+    match driver_requirements::check_requirements(&info, driver_requirements::VULKAN_REQUIREMENTS) {
+        Ok(()) => true,
+        Err(e) => {
+            error!("Unsupported Vulkan driver: {e}");
+
+            false
+        }
+    }
+}
+
 pub extern "C" fn shutdown_driver() {
-    SERVER_CORE_CONTEXT.write().take();
+    info!("Shutting down the driver");
+    DRIVER_TEARDOWN.run();
 }
 
 /// This is the SteamVR/OpenVR entry point
@@ -341,8 +454,10 @@ pub unsafe extern "C" fn HmdDriverFactory(
     crate::VideoSend = Some(send_video);
     crate::GetDynamicEncoderParams = Some(get_dynamic_encoder_params);
     crate::ReportComposed = Some(report_composed);
+    crate::ReportEncodeQuality = Some(report_encode_quality);
     crate::ReportPresent = Some(report_present);
     crate::WaitForVSync = Some(wait_for_vsync);
+    crate::CheckVulkanDriver = Some(check_vulkan_driver);
     crate::ShutdownRuntime = Some(shutdown_driver);
 
     crate::CppOpenvrEntryPoint(interface_name, return_code)
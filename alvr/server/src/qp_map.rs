@@ -0,0 +1,140 @@
+use alvr_common::{
+    anyhow::{bail, Result},
+    glam::UVec2,
+};
+use alvr_session::{CodecType, QpDeltaMapConfig};
+
+// Maximum QP of h264 and HEVC. AV1 uses a larger scale internally but NVENC takes the same deltas
+const MAX_QP_DELTA: i32 = 51;
+
+// Size of the regions that the encoder assigns a QP to: macroblocks for h264, CTUs for HEVC (NVENC
+// uses 32x32 CTUs) and superblocks for AV1
+pub fn qp_block_size(codec: CodecType) -> u32 {
+    match codec {
+        CodecType::H264 => 16,
+        CodecType::Hevc => 32,
+        CodecType::AV1 => 64,
+    }
+}
+
+// QP deltas of each encoder block of the stream frame, in raster order
+#[derive(Debug)]
+pub struct QpDeltaBlocks {
+    pub columns: u32,
+    pub deltas: Vec<i8>,
+}
+
+// The map is a grid of tiles over each eye, applied to both eyes in the stream frame. A negative
+// delta lowers the QP, so the tile gets a higher quality and a larger share of the bitrate. With
+// foveated encoding the map refers to the compressed eye, where the center tiles contain the foveal
+// region. Each block takes the delta of the tile containing its center.
+pub fn qp_delta_blocks(
+    config: &QpDeltaMapConfig,
+    codec: CodecType,
+    eye_size: UVec2,
+) -> Result<QpDeltaBlocks> {
+    let block_size = qp_block_size(codec);
+    let eye_blocks = (eye_size + block_size - 1) / block_size;
+
+    if config.columns == 0 || config.rows == 0 {
+        bail!("The QP delta map must have at least one tile");
+    }
+    if config.deltas.len() != (config.columns * config.rows) as usize {
+        bail!(
+            "The QP delta map has {} deltas instead of {}x{}",
+            config.deltas.len(),
+            config.columns,
+            config.rows
+        );
+    }
+    // Finer tiles would not be applied, since each block has a single QP
+    if config.columns > eye_blocks.x || config.rows > eye_blocks.y {
+        bail!(
+            "The QP delta map must have at most {}x{} tiles, the encoder blocks of each eye",
+            eye_blocks.x,
+            eye_blocks.y
+        );
+    }
+    if config.deltas.iter().any(|delta| delta.abs() > MAX_QP_DELTA) {
+        bail!("The QP deltas must be between -{MAX_QP_DELTA} and {MAX_QP_DELTA}");
+    }
+
+    let frame_size = UVec2::new(eye_size.x * 2, eye_size.y);
+    let frame_blocks = (frame_size + block_size - 1) / block_size;
+
+    let mut deltas = Vec::with_capacity((frame_blocks.x * frame_blocks.y) as usize);
+    for y in 0..frame_blocks.y {
+        let center_y = u32::min(y * block_size + block_size / 2, eye_size.y - 1);
+        let row = center_y * config.rows / eye_size.y;
+
+        for x in 0..frame_blocks.x {
+            let center_x = u32::min(x * block_size + block_size / 2, frame_size.x - 1);
+            let column = (center_x % eye_size.x) * config.columns / eye_size.x;
+
+            deltas.push(config.deltas[(row * config.columns + column) as usize] as i8);
+        }
+    }
+
+    Ok(QpDeltaBlocks {
+        columns: frame_blocks.x,
+        deltas,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_center_tile_lowers_the_qp_of_its_blocks() {
+        // Higher quality in the center of each eye, lower at the borders
+        let config = QpDeltaMapConfig {
+            columns: 3,
+            rows: 3,
+            deltas: vec![2, 2, 2, 2, -6, 2, 2, 2, 2],
+        };
+        let eye_size = UVec2::new(1920, 1440);
+
+        let blocks = qp_delta_blocks(&config, CodecType::Hevc, eye_size).unwrap();
+        assert_eq!(blocks.columns, 120);
+        assert_eq!(blocks.deltas.len(), 120 * 45);
+
+        let delta = |x: u32, y: u32| blocks.deltas[(y / 32 * blocks.columns + x / 32) as usize];
+        for eye_offset in [0, eye_size.x] {
+            // The center tile spans 640..1280 horizontally and 480..960 vertically
+            assert_eq!(delta(eye_offset + 960, 720), -6);
+            assert_eq!(delta(eye_offset + 650, 490), -6);
+            assert_eq!(delta(eye_offset + 1270, 950), -6);
+            assert_eq!(delta(eye_offset + 600, 720), 2);
+            assert_eq!(delta(eye_offset + 960, 1000), 2);
+            assert_eq!(delta(eye_offset + 10, 10), 2);
+        }
+
+        let lower_qp_blocks = blocks.deltas.iter().filter(|delta| **delta < 0).count();
+        assert_eq!(lower_qp_blocks, 2 * 20 * 15);
+    }
+
+    #[test]
+    fn test_map_dimensions_are_validated() {
+        let eye_size = UVec2::new(1920, 1440);
+        let map = |columns, rows, deltas_count| QpDeltaMapConfig {
+            columns,
+            rows,
+            deltas: vec![-2; deltas_count],
+        };
+
+        assert!(qp_delta_blocks(&map(4, 4, 15), CodecType::H264, eye_size).is_err());
+        assert!(qp_delta_blocks(&map(0, 4, 0), CodecType::H264, eye_size).is_err());
+
+        // 120x90 macroblocks per eye, but only 30x23 superblocks
+        assert!(qp_delta_blocks(&map(120, 90, 120 * 90), CodecType::H264, eye_size).is_ok());
+        assert!(qp_delta_blocks(&map(120, 90, 120 * 90), CodecType::AV1, eye_size).is_err());
+
+        let out_of_range = QpDeltaMapConfig {
+            columns: 1,
+            rows: 1,
+            deltas: vec![-60],
+        };
+        assert!(qp_delta_blocks(&out_of_range, CodecType::H264, eye_size).is_err());
+    }
+}
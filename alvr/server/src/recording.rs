@@ -0,0 +1,1374 @@
+use alvr_common::{
+    anyhow::{anyhow, bail, Result},
+    error,
+};
+use alvr_session::CodecType;
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    sync::mpsc::{self, SyncSender, TrySendError},
+    thread,
+    time::{Duration, Instant},
+};
+
+// Block timestamps are 16 bit offsets from the cluster timestamp, in milliseconds
+const MAX_CLUSTER_DURATION: Duration = Duration::from_secs(30);
+
+const EBML: u32 = 0x1A45DFA3;
+const EBML_VERSION: u32 = 0x4286;
+const EBML_READ_VERSION: u32 = 0x42F7;
+const EBML_MAX_ID_LENGTH: u32 = 0x42F2;
+const EBML_MAX_SIZE_LENGTH: u32 = 0x42F3;
+const DOC_TYPE: u32 = 0x4282;
+const DOC_TYPE_VERSION: u32 = 0x4287;
+const DOC_TYPE_READ_VERSION: u32 = 0x4285;
+const SEGMENT: u32 = 0x18538067;
+const INFO: u32 = 0x1549A966;
+const TIMESTAMP_SCALE: u32 = 0x2AD7B1;
+const MUXING_APP: u32 = 0x4D80;
+const WRITING_APP: u32 = 0x5741;
+const TRACKS: u32 = 0x1654AE6B;
+const TRACK_ENTRY: u32 = 0xAE;
+const TRACK_NUMBER: u32 = 0xD7;
+const TRACK_UID: u32 = 0x73C5;
+const TRACK_TYPE: u32 = 0x83;
+const CODEC_ID: u32 = 0x86;
+const CODEC_PRIVATE: u32 = 0x63A2;
+const VIDEO: u32 = 0xE0;
+const PIXEL_WIDTH: u32 = 0xB0;
+const PIXEL_HEIGHT: u32 = 0xBA;
+const AUDIO: u32 = 0xE1;
+const SAMPLING_FREQUENCY: u32 = 0xB5;
+const CHANNELS: u32 = 0x9F;
+const BIT_DEPTH: u32 = 0x6264;
+const CLUSTER: u32 = 0x1F43B675;
+const CLUSTER_TIMESTAMP: u32 = 0xE7;
+const SIMPLE_BLOCK: u32 = 0xA3;
+
+const VIDEO_TRACK: u8 = 1;
+const AUDIO_TRACK: u8 = 2;
+
+// Segment and clusters are written with unknown size, so the file never needs to be rewritten and
+// stays readable up to the last complete block if the server crashes
+const UNKNOWN_SIZE: [u8; 8] = [0x01, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF];
+
+fn write_id(buffer: &mut Vec<u8>, id: u32) {
+    buffer.extend(id.to_be_bytes().into_iter().skip_while(|b| *b == 0));
+}
+
+fn write_size(buffer: &mut Vec<u8>, size: u64) {
+    // A size with all value bits set is reserved for unknown sizes
+    let length = (1..8).find(|n| size < (1 << (7 * n)) - 1).unwrap_or(8);
+    let marked_size = size | (1 << (7 * length));
+
+    buffer.extend(&marked_size.to_be_bytes()[8 - length as usize..]);
+}
+
+fn element(buffer: &mut Vec<u8>, id: u32, payload: &[u8]) {
+    write_id(buffer, id);
+    write_size(buffer, payload.len() as u64);
+    buffer.extend(payload);
+}
+
+fn uint_element(buffer: &mut Vec<u8>, id: u32, value: u64) {
+    let bytes = value.to_be_bytes();
+    let skip = (value.leading_zeros() / 8).min(7) as usize;
+
+    element(buffer, id, &bytes[skip..]);
+}
+
+fn master_element(buffer: &mut Vec<u8>, id: u32, build: impl FnOnce(&mut Vec<u8>)) {
+    let mut payload = vec![];
+    build(&mut payload);
+
+    element(buffer, id, &payload);
+}
+
+fn annex_b_nals(data: &[u8]) -> Vec<&[u8]> {
+    let mut starts = vec![];
+    let mut i = 0;
+    while i + 3 <= data.len() {
+        if data[i..i + 3] == [0, 0, 1] {
+            starts.push(i + 3);
+            i += 3;
+        } else {
+            i += 1;
+        }
+    }
+
+    starts
+        .iter()
+        .enumerate()
+        .map(|(index, start)| {
+            let end = starts.get(index + 1).map(|s| s - 3).unwrap_or(data.len());
+            let mut nal = &data[*start..end];
+            // Zeros of the next 4 byte start code
+            while let [rest @ .., 0] = nal {
+                nal = rest;
+            }
+
+            nal
+        })
+        .filter(|nal| !nal.is_empty())
+        .collect()
+}
+
+fn nal_type(codec: CodecType, nal: &[u8]) -> u8 {
+    match codec {
+        CodecType::Hevc => (nal[0] >> 1) & 0x3F,
+        _ => nal[0] & 0x1F,
+    }
+}
+
+// Removes the emulation prevention bytes
+fn nal_to_rbsp(nal: &[u8]) -> Vec<u8> {
+    let mut rbsp = Vec::with_capacity(nal.len());
+    for byte in nal {
+        if *byte == 3 && rbsp.ends_with(&[0, 0]) {
+            continue;
+        }
+        rbsp.push(*byte);
+    }
+
+    rbsp
+}
+
+struct Obu<'a> {
+    obu_type: u8,
+    data: &'a [u8],
+    payload: &'a [u8],
+}
+
+fn av1_obus(data: &[u8]) -> Result<Vec<Obu<'_>>> {
+    let mut obus = vec![];
+    let mut pos = 0;
+    while pos < data.len() {
+        let header = data[pos];
+        let obu_type = (header >> 3) & 0xF;
+        let mut payload_start = pos + 1 + ((header >> 2) & 1) as usize;
+
+        let end = if (header >> 1) & 1 == 1 {
+            let mut size = 0;
+            for i in 0..8 {
+                let Some(byte) = data.get(payload_start) else {
+                    bail!("Truncated OBU");
+                };
+                payload_start += 1;
+                size |= ((byte & 0x7F) as usize) << (7 * i);
+                if byte & 0x80 == 0 {
+                    break;
+                }
+            }
+            payload_start + size
+        } else {
+            data.len()
+        };
+        if end > data.len() {
+            bail!("Truncated OBU");
+        }
+
+        obus.push(Obu {
+            obu_type,
+            data: &data[pos..end],
+            payload: &data[payload_start..end],
+        });
+        pos = end;
+    }
+
+    Ok(obus)
+}
+
+struct BitReader<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl BitReader<'_> {
+    fn read(&mut self, bits: usize) -> Result<u32> {
+        let mut value = 0;
+        for _ in 0..bits {
+            let Some(byte) = self.data.get(self.position / 8) else {
+                bail!("Truncated bitstream");
+            };
+            value = (value << 1) | ((byte >> (7 - self.position % 8)) & 1) as u32;
+            self.position += 1;
+        }
+
+        Ok(value)
+    }
+
+    fn skip(&mut self, bits: usize) -> Result<()> {
+        for _ in 0..bits {
+            self.read(1)?;
+        }
+
+        Ok(())
+    }
+
+    // Exp-Golomb code, ue(v) for HEVC and uvlc() for AV1
+    fn read_ue(&mut self) -> Result<u32> {
+        let mut leading_zeros = 0;
+        while self.read(1)? == 0 {
+            leading_zeros += 1;
+            if leading_zeros == 32 {
+                bail!("Invalid Exp-Golomb code");
+            }
+        }
+
+        Ok(self.read(leading_zeros)? + ((1 << leading_zeros) - 1))
+    }
+
+    // se(v)
+    fn read_se(&mut self) -> Result<i32> {
+        let code = self.read_ue()? as i64;
+
+        Ok(if code % 2 == 1 {
+            (code + 1) / 2
+        } else {
+            -code / 2
+        } as i32)
+    }
+}
+
+// Size of the pictures, without the padding of the macroblocks. It depends on the foveated encoding
+// and on the joint resolution, so the track is sized from the SPS
+fn h264_sps_size(sps_rbsp: &[u8]) -> Result<(u32, u32)> {
+    let mut reader = BitReader {
+        data: sps_rbsp,
+        position: 0,
+    };
+
+    // NAL header
+    reader.skip(8)?;
+    let profile_idc = reader.read(8)?;
+    // Constraint flags, level
+    reader.skip(16)?;
+    let _sps_id = reader.read_ue()?;
+
+    let mut chroma_array_type = 1;
+    if [100, 110, 122, 244, 44, 83, 86, 118, 128, 138, 139, 134, 135].contains(&profile_idc) {
+        let chroma_format_idc = reader.read_ue()?;
+        if chroma_format_idc > 3 {
+            bail!("Invalid H264 chroma format {chroma_format_idc}");
+        }
+        chroma_array_type = chroma_format_idc;
+        if chroma_format_idc == 3 && reader.read(1)? == 1 {
+            // Separate colour planes
+            chroma_array_type = 0;
+        }
+        // Bit depths, transform bypass
+        reader.read_ue()?;
+        reader.read_ue()?;
+        reader.skip(1)?;
+        if reader.read(1)? == 1 {
+            // scaling_list()
+            for index in 0..if chroma_format_idc == 3 { 12 } else { 8 } {
+                if reader.read(1)? == 1 {
+                    let mut next_scale = 8;
+                    for _ in 0..if index < 6 { 16 } else { 64 } {
+                        if next_scale != 0 {
+                            next_scale = (next_scale + reader.read_se()? + 256) % 256;
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    let _log2_max_frame_num_minus4 = reader.read_ue()?;
+    match reader.read_ue()? {
+        0 => {
+            reader.read_ue()?;
+        }
+        1 => {
+            reader.skip(1)?;
+            reader.read_se()?;
+            reader.read_se()?;
+            for _ in 0..reader.read_ue()? {
+                reader.read_se()?;
+            }
+        }
+        _ => (),
+    }
+    let _max_num_ref_frames = reader.read_ue()?;
+    let _gaps_in_frame_num_allowed = reader.read(1)?;
+    let width_in_mbs = reader.read_ue()? + 1;
+    let height_in_map_units = reader.read_ue()? + 1;
+    let frame_mbs_only = reader.read(1)?;
+    if frame_mbs_only == 0 {
+        let _mb_adaptive_frame_field = reader.read(1)?;
+    }
+    let _direct_8x8_inference = reader.read(1)?;
+
+    let width = width_in_mbs * 16;
+    let height = (2 - frame_mbs_only) * height_in_map_units * 16;
+    if reader.read(1)? == 0 {
+        return Ok((width, height));
+    }
+
+    let (crop_unit_x, crop_unit_y) = match chroma_array_type {
+        1 => (2, 2 * (2 - frame_mbs_only)),
+        2 => (2, 2 - frame_mbs_only),
+        _ => (1, 2 - frame_mbs_only),
+    };
+    let (left, right) = (reader.read_ue()?, reader.read_ue()?);
+    let (top, bottom) = (reader.read_ue()?, reader.read_ue()?);
+
+    let crop_width = crop_unit_x * (left + right);
+    let crop_height = crop_unit_y * (top + bottom);
+    if crop_width >= width || crop_height >= height {
+        bail!("Invalid H264 cropping");
+    }
+
+    Ok((width - crop_width, height - crop_height))
+}
+
+// Size and format of the coded pictures, read from the parameter sets since the encoders can fall
+// back to another chroma subsampling or bit depth than the requested ones
+#[derive(Debug, PartialEq)]
+struct PictureFormat {
+    width: u32,
+    height: u32,
+    // 0: monochrome, 1: 4:2:0, 2: 4:2:2, 3: 4:4:4
+    chroma_format_idc: u8,
+    bit_depth_luma: u8,
+    bit_depth_chroma: u8,
+}
+
+fn hevc_sps_format(sps_rbsp: &[u8]) -> Result<PictureFormat> {
+    let mut reader = BitReader {
+        data: sps_rbsp,
+        position: 0,
+    };
+
+    // NAL header, VPS id
+    reader.skip(16 + 4)?;
+    let max_sub_layers_minus1 = reader.read(3)? as usize;
+    let _temporal_id_nesting = reader.read(1)?;
+
+    // profile_tier_level()
+    reader.skip(88 + 8)?;
+    let mut sub_layers = vec![];
+    for _ in 0..max_sub_layers_minus1 {
+        sub_layers.push((reader.read(1)? == 1, reader.read(1)? == 1));
+    }
+    if max_sub_layers_minus1 > 0 {
+        reader.skip(2 * (8 - max_sub_layers_minus1))?;
+    }
+    for (profile_present, level_present) in sub_layers {
+        reader.skip(if profile_present { 88 } else { 0 } + if level_present { 8 } else { 0 })?;
+    }
+
+    let _sps_id = reader.read_ue()?;
+    let chroma_format_idc = reader.read_ue()?;
+    if chroma_format_idc > 3 {
+        bail!("Invalid HEVC chroma format {chroma_format_idc}");
+    }
+    let separate_colour_plane = chroma_format_idc == 3 && reader.read(1)? == 1;
+    let mut width = reader.read_ue()?;
+    let mut height = reader.read_ue()?;
+    if reader.read(1)? == 1 {
+        // Conformance window, the encoders pad the pictures to the size of the coding blocks
+        let (sub_width, sub_height) = match chroma_format_idc {
+            _ if separate_colour_plane => (1, 1),
+            1 => (2, 2),
+            2 => (2, 1),
+            _ => (1, 1),
+        };
+        let (left, right) = (reader.read_ue()?, reader.read_ue()?);
+        let (top, bottom) = (reader.read_ue()?, reader.read_ue()?);
+        if sub_width * (left + right) >= width || sub_height * (top + bottom) >= height {
+            bail!("Invalid HEVC conformance window");
+        }
+        width -= sub_width * (left + right);
+        height -= sub_height * (top + bottom);
+    }
+    let bit_depth_luma = reader.read_ue()? + 8;
+    let bit_depth_chroma = reader.read_ue()? + 8;
+    if bit_depth_luma > 16 || bit_depth_chroma > 16 {
+        bail!("Invalid HEVC bit depth");
+    }
+
+    Ok(PictureFormat {
+        width,
+        height,
+        chroma_format_idc: chroma_format_idc as u8,
+        bit_depth_luma: bit_depth_luma as u8,
+        bit_depth_chroma: bit_depth_chroma as u8,
+    })
+}
+
+struct Av1SequenceHeader {
+    // The frame headers can signal smaller frames, the encoders don't use them
+    max_frame_width: u32,
+    max_frame_height: u32,
+    profile: u8,
+    // Of the first operating point
+    level: u8,
+    tier: u8,
+    bit_depth: u8,
+    monochrome: bool,
+    subsampling_x: u8,
+    subsampling_y: u8,
+    chroma_sample_position: u8,
+}
+
+// Reads the sequence header OBU up to the color config
+fn av1_sequence_header(payload: &[u8]) -> Result<Av1SequenceHeader> {
+    let mut reader = BitReader {
+        data: payload,
+        position: 0,
+    };
+
+    let profile = reader.read(3)? as u8;
+    let _still_picture = reader.read(1)?;
+    let reduced_still_picture_header = reader.read(1)? == 1;
+
+    let (level, tier) = if reduced_still_picture_header {
+        (reader.read(5)? as u8, 0)
+    } else {
+        if reader.read(1)? == 1 {
+            // timing_info()
+            reader.skip(64)?;
+            if reader.read(1)? == 1 {
+                reader.read_ue()?;
+            }
+        }
+        let buffer_delay_length = if reader.read(1)? == 1 {
+            // decoder_model_info()
+            let buffer_delay_length = reader.read(5)? as usize + 1;
+            reader.skip(32 + 5 + 5)?;
+
+            Some(buffer_delay_length)
+        } else {
+            None
+        };
+        let initial_display_delay_present = reader.read(1)? == 1;
+
+        let mut first_point = None;
+        for _ in 0..=reader.read(5)? {
+            let _idc = reader.read(12)?;
+            let level = reader.read(5)? as u8;
+            let tier = if level > 7 { reader.read(1)? as u8 } else { 0 };
+            if let Some(length) = buffer_delay_length {
+                if reader.read(1)? == 1 {
+                    // operating_parameters_info(), with the low delay flag
+                    reader.skip(2 * length + 1)?;
+                }
+            }
+            if initial_display_delay_present && reader.read(1)? == 1 {
+                reader.skip(4)?;
+            }
+            first_point.get_or_insert((level, tier));
+        }
+
+        first_point.unwrap_or((31, 0))
+    };
+
+    let width_bits = reader.read(4)? as usize + 1;
+    let height_bits = reader.read(4)? as usize + 1;
+    let max_frame_width = reader.read(width_bits)? + 1;
+    let max_frame_height = reader.read(height_bits)? + 1;
+    if !reduced_still_picture_header && reader.read(1)? == 1 {
+        // Frame id lengths
+        reader.skip(4 + 3)?;
+    }
+
+    // Superblock size, filter intra, intra edge filter
+    reader.skip(3)?;
+    if !reduced_still_picture_header {
+        // Interintra compound, masked compound, warped motion, dual filter
+        reader.skip(4)?;
+        let enable_order_hint = reader.read(1)? == 1;
+        if enable_order_hint {
+            // Jnt comp, ref frame mvs
+            reader.skip(2)?;
+        }
+        let force_screen_content_tools = if reader.read(1)? == 1 {
+            2
+        } else {
+            reader.read(1)?
+        };
+        if force_screen_content_tools > 0 && reader.read(1)? == 0 {
+            let _force_integer_mv = reader.read(1)?;
+        }
+        if enable_order_hint {
+            reader.skip(3)?;
+        }
+    }
+    // Superres, cdef, restoration
+    reader.skip(3)?;
+
+    // color_config()
+    let high_bitdepth = reader.read(1)? == 1;
+    let bit_depth = if profile == 2 && high_bitdepth && reader.read(1)? == 1 {
+        12
+    } else if high_bitdepth {
+        10
+    } else {
+        8
+    };
+    let monochrome = profile != 1 && reader.read(1)? == 1;
+    let (primaries, transfer, matrix) = if reader.read(1)? == 1 {
+        (reader.read(8)?, reader.read(8)?, reader.read(8)?)
+    } else {
+        (2, 2, 2)
+    };
+
+    let (subsampling_x, subsampling_y) = if monochrome {
+        (1, 1)
+    } else if primaries == 1 && transfer == 13 && matrix == 0 {
+        // sRGB
+        (0, 0)
+    } else {
+        let _color_range = reader.read(1)?;
+        match profile {
+            0 => (1, 1),
+            1 => (0, 0),
+            _ if bit_depth == 12 => {
+                let x = reader.read(1)? as u8;
+                let y = if x == 1 { reader.read(1)? as u8 } else { 0 };
+
+                (x, y)
+            }
+            _ => (1, 0),
+        }
+    };
+    let chroma_sample_position = if !monochrome && subsampling_x == 1 && subsampling_y == 1 {
+        reader.read(2)? as u8
+    } else {
+        0
+    };
+
+    Ok(Av1SequenceHeader {
+        max_frame_width,
+        max_frame_height,
+        profile,
+        level,
+        tier,
+        bit_depth,
+        monochrome,
+        subsampling_x,
+        subsampling_y,
+        chroma_sample_position,
+    })
+}
+
+// Interleaved 16 bit PCM
+pub struct AudioTrackInfo {
+    pub sample_rate: u32,
+    pub channels: u16,
+}
+
+// Matroska muxer for the encoded stream. The header can be written only once the parameter sets
+// are known, so the recording starts at the first IDR after the config NALs.
+pub struct MatroskaWriter<W: Write> {
+    writer: W,
+    codec: CodecType,
+    audio: Option<AudioTrackInfo>,
+    config_buffer: Option<Vec<u8>>,
+    header_written: bool,
+    cluster_start: Option<Duration>,
+}
+
+impl<W: Write> MatroskaWriter<W> {
+    pub fn new(writer: W, codec: CodecType, audio: Option<AudioTrackInfo>) -> Self {
+        Self {
+            writer,
+            codec,
+            audio,
+            config_buffer: None,
+            header_written: false,
+            cluster_start: None,
+        }
+    }
+
+    // Parameter sets sent after the header are kept in the frames, the decoders read them in-band
+    pub fn set_config_nals(&mut self, config_buffer: Vec<u8>) {
+        if !self.header_written {
+            self.config_buffer = Some(config_buffer);
+        }
+    }
+
+    // Returns the CodecPrivate element and the size of the pictures
+    fn codec_private(&self, config_buffer: &[u8]) -> Result<(Vec<u8>, (u32, u32))> {
+        let codec = self.codec;
+        if codec == CodecType::AV1 {
+            return self.av1_config(config_buffer);
+        }
+
+        let nals = annex_b_nals(config_buffer);
+        let nals_of_type = |ty| -> Vec<&[u8]> {
+            nals.iter()
+                .copied()
+                .filter(|n| nal_type(codec, n) == ty)
+                .collect()
+        };
+        let write_nals = |buffer: &mut Vec<u8>, nals: &[&[u8]]| {
+            for nal in nals {
+                buffer.extend((nal.len() as u16).to_be_bytes());
+                buffer.extend(*nal);
+            }
+        };
+
+        let mut private = vec![];
+        let size = if codec == CodecType::H264 {
+            let (sps, pps) = (nals_of_type(7), nals_of_type(8));
+            if sps.is_empty() || sps[0].len() < 4 || pps.is_empty() {
+                bail!("Missing H264 parameter sets");
+            }
+            let size = h264_sps_size(&nal_to_rbsp(sps[0]))?;
+
+            // AVCDecoderConfigurationRecord, with 4 bytes NAL lengths
+            private.extend([1, sps[0][1], sps[0][2], sps[0][3], 0xFF]);
+            private.push(0xE0 | sps.len() as u8);
+            write_nals(&mut private, &sps);
+            private.push(pps.len() as u8);
+            write_nals(&mut private, &pps);
+
+            size
+        } else {
+            let (vps, sps, pps) = (nals_of_type(32), nals_of_type(33), nals_of_type(34));
+            let sps_rbsp = sps.first().map(|s| nal_to_rbsp(s)).unwrap_or_default();
+            if vps.is_empty() || sps_rbsp.len() < 15 || pps.is_empty() {
+                bail!("Missing HEVC parameter sets");
+            }
+            let format = hevc_sps_format(&sps_rbsp)?;
+
+            // HEVCDecoderConfigurationRecord. The profile, tier and level are copied from the SPS
+            private.push(1);
+            private.extend(&sps_rbsp[3..15]);
+            private.extend([0xF0, 0x00, 0xFC, 0xFC | format.chroma_format_idc]);
+            private.push(0xF8 | (format.bit_depth_luma - 8));
+            private.push(0xF8 | (format.bit_depth_chroma - 8));
+            private.extend([0, 0, 0x0F]);
+            private.push(3);
+            for (ty, nals) in [(32, vps), (33, sps), (34, pps)] {
+                private.push(0x80 | ty);
+                private.extend((nals.len() as u16).to_be_bytes());
+                write_nals(&mut private, &nals);
+            }
+
+            (format.width, format.height)
+        };
+
+        Ok((private, size))
+    }
+
+    fn av1_config(&self, config_buffer: &[u8]) -> Result<(Vec<u8>, (u32, u32))> {
+        let obus = av1_obus(config_buffer)?;
+        let Some(sequence_header) = obus.iter().find(|obu| obu.obu_type == 1) else {
+            bail!("Missing AV1 sequence header");
+        };
+
+        let header = av1_sequence_header(sequence_header.payload)?;
+
+        // AV1CodecConfigurationRecord
+        let mut private = vec![
+            0x81,
+            header.profile << 5 | header.level,
+            header.tier << 7
+                | ((header.bit_depth > 8) as u8) << 6
+                | ((header.bit_depth == 12) as u8) << 5
+                | (header.monochrome as u8) << 4
+                | header.subsampling_x << 3
+                | header.subsampling_y << 2
+                | header.chroma_sample_position,
+            0,
+        ];
+        private.extend(sequence_header.data);
+
+        Ok((private, (header.max_frame_width, header.max_frame_height)))
+    }
+
+    fn write_header(&mut self, config_buffer: &[u8]) -> Result<()> {
+        let (codec_private, (width, height)) = self.codec_private(config_buffer)?;
+
+        let mut buffer = vec![];
+        master_element(&mut buffer, EBML, |b| {
+            uint_element(b, EBML_VERSION, 1);
+            uint_element(b, EBML_READ_VERSION, 1);
+            uint_element(b, EBML_MAX_ID_LENGTH, 4);
+            uint_element(b, EBML_MAX_SIZE_LENGTH, 8);
+            element(b, DOC_TYPE, b"matroska");
+            uint_element(b, DOC_TYPE_VERSION, 4);
+            uint_element(b, DOC_TYPE_READ_VERSION, 2);
+        });
+
+        write_id(&mut buffer, SEGMENT);
+        buffer.extend(UNKNOWN_SIZE);
+
+        master_element(&mut buffer, INFO, |b| {
+            // Timestamps in milliseconds
+            uint_element(b, TIMESTAMP_SCALE, 1_000_000);
+            element(b, MUXING_APP, b"ALVR");
+            element(b, WRITING_APP, b"ALVR");
+        });
+
+        master_element(&mut buffer, TRACKS, |b| {
+            master_element(b, TRACK_ENTRY, |b| {
+                uint_element(b, TRACK_NUMBER, VIDEO_TRACK as _);
+                uint_element(b, TRACK_UID, VIDEO_TRACK as _);
+                uint_element(b, TRACK_TYPE, 1);
+                let codec_id: &[u8] = match self.codec {
+                    CodecType::H264 => b"V_MPEG4/ISO/AVC",
+                    CodecType::Hevc => b"V_MPEGH/ISO/HEVC",
+                    CodecType::AV1 => b"V_AV1",
+                };
+                element(b, CODEC_ID, codec_id);
+                element(b, CODEC_PRIVATE, &codec_private);
+                master_element(b, VIDEO, |b| {
+                    uint_element(b, PIXEL_WIDTH, width as _);
+                    uint_element(b, PIXEL_HEIGHT, height as _);
+                });
+            });
+
+            if let Some(audio) = &self.audio {
+                master_element(b, TRACK_ENTRY, |b| {
+                    uint_element(b, TRACK_NUMBER, AUDIO_TRACK as _);
+                    uint_element(b, TRACK_UID, AUDIO_TRACK as _);
+                    uint_element(b, TRACK_TYPE, 2);
+                    element(b, CODEC_ID, b"A_PCM/INT/LIT");
+                    master_element(b, AUDIO, |b| {
+                        element(
+                            b,
+                            SAMPLING_FREQUENCY,
+                            &(audio.sample_rate as f64).to_be_bytes(),
+                        );
+                        uint_element(b, CHANNELS, audio.channels as _);
+                        uint_element(b, BIT_DEPTH, 16);
+                    });
+                });
+            }
+        });
+
+        self.writer.write_all(&buffer)?;
+        self.header_written = true;
+
+        Ok(())
+    }
+
+    fn start_cluster(&mut self, timestamp: Duration) -> Result<()> {
+        // The previous cluster is complete
+        self.writer.flush()?;
+
+        let mut buffer = vec![];
+        write_id(&mut buffer, CLUSTER);
+        buffer.extend(UNKNOWN_SIZE);
+        uint_element(&mut buffer, CLUSTER_TIMESTAMP, timestamp.as_millis() as _);
+        self.writer.write_all(&buffer)?;
+
+        self.cluster_start = Some(timestamp);
+
+        Ok(())
+    }
+
+    fn write_block(
+        &mut self,
+        track: u8,
+        timestamp: Duration,
+        keyframe: bool,
+        frame: &[u8],
+    ) -> Result<()> {
+        let Some(cluster_start) = self.cluster_start else {
+            return Ok(());
+        };
+        let Ok(offset) =
+            i16::try_from(timestamp.as_millis() as i64 - cluster_start.as_millis() as i64)
+        else {
+            // Late audio from before the start of the cluster
+            return Ok(());
+        };
+
+        let mut payload = vec![0x80 | track];
+        payload.extend(offset.to_be_bytes());
+        payload.push(if keyframe { 0x80 } else { 0 });
+        payload.extend(frame);
+
+        let mut buffer = vec![];
+        element(&mut buffer, SIMPLE_BLOCK, &payload);
+        self.writer.write_all(&buffer)?;
+
+        Ok(())
+    }
+
+    pub fn write_video(&mut self, timestamp: Duration, data: &[u8], is_idr: bool) -> Result<()> {
+        if !self.header_written {
+            match self.config_buffer.take() {
+                Some(config_buffer) if is_idr => self.write_header(&config_buffer)?,
+                config_buffer => {
+                    self.config_buffer = config_buffer;
+                    return Ok(());
+                }
+            }
+        }
+
+        let cluster_expired = self
+            .cluster_start
+            .map(|start| timestamp.saturating_sub(start) >= MAX_CLUSTER_DURATION)
+            .unwrap_or(true);
+        if is_idr || cluster_expired {
+            self.start_cluster(timestamp)?;
+        }
+
+        let frame = if self.codec == CodecType::AV1 {
+            // Temporal delimiters must not be stored in the container
+            av1_obus(data)?
+                .into_iter()
+                .filter(|obu| obu.obu_type != 2)
+                .flat_map(|obu| obu.data.iter().copied())
+                .collect::<Vec<_>>()
+        } else {
+            annex_b_nals(data)
+                .into_iter()
+                .flat_map(|nal| {
+                    (nal.len() as u32)
+                        .to_be_bytes()
+                        .into_iter()
+                        .chain(nal.iter().copied())
+                })
+                .collect()
+        };
+
+        self.write_block(VIDEO_TRACK, timestamp, is_idr, &frame)
+    }
+
+    pub fn write_audio(&mut self, timestamp: Duration, data: &[u8]) -> Result<()> {
+        if self.audio.is_none() {
+            return Ok(());
+        }
+
+        // Long GOPs
+        if self
+            .cluster_start
+            .map(|start| timestamp.saturating_sub(start) >= MAX_CLUSTER_DURATION)
+            .unwrap_or(false)
+        {
+            self.start_cluster(timestamp)?;
+        }
+
+        self.write_block(AUDIO_TRACK, timestamp, true, data)
+    }
+}
+
+pub enum RecordingPacket {
+    Config(Vec<u8>),
+    Video {
+        timestamp: Duration,
+        data: Vec<u8>,
+        is_idr: bool,
+    },
+    Audio {
+        timestamp: Duration,
+        data: Vec<u8>,
+    },
+}
+
+// Packets held while the writer thread waits for the disk. Audio is dropped when the queue is full,
+// video blocks the encoder thread
+const MAX_QUEUED_PACKETS: usize = 256;
+
+// Recording of the stream, written to disk while streaming without re-encoding. Writes that fail
+// return an error and the recording must be stopped
+pub enum VideoRecording {
+    // Video elementary stream, as sent to the client
+    Raw(File),
+    // Muxed on its own thread, since the audio is recorded from the realtime callback
+    Matroska {
+        sender: SyncSender<RecordingPacket>,
+        start: Instant,
+    },
+}
+
+impl VideoRecording {
+    pub fn new_matroska(file: File, codec: CodecType, audio: Option<AudioTrackInfo>) -> Self {
+        let (sender, receiver) = mpsc::sync_channel(MAX_QUEUED_PACKETS);
+
+        thread::spawn(move || {
+            let mut writer = MatroskaWriter::new(BufWriter::new(file), codec, audio);
+            for packet in receiver {
+                let res = match packet {
+                    RecordingPacket::Config(config_buffer) => {
+                        writer.set_config_nals(config_buffer);
+                        Ok(())
+                    }
+                    RecordingPacket::Video {
+                        timestamp,
+                        data,
+                        is_idr,
+                    } => writer.write_video(timestamp, &data, is_idr),
+                    RecordingPacket::Audio { timestamp, data } => {
+                        writer.write_audio(timestamp, &data)
+                    }
+                };
+
+                // The receiver is dropped, the next write of the stream stops the recording
+                if let Err(e) = res {
+                    error!("Failed to write the recording: {e}");
+                    return;
+                }
+            }
+        });
+
+        VideoRecording::Matroska {
+            sender,
+            start: Instant::now(),
+        }
+    }
+
+    fn send(sender: &SyncSender<RecordingPacket>, packet: RecordingPacket) -> Result<()> {
+        match sender.try_send(packet) {
+            Ok(()) => Ok(()),
+            // The audio callback must not wait for the disk
+            Err(TrySendError::Full(RecordingPacket::Audio { .. })) => Ok(()),
+            Err(TrySendError::Full(packet)) => sender
+                .send(packet)
+                .map_err(|_| anyhow!("Recording writer stopped")),
+            Err(TrySendError::Disconnected(_)) => bail!("Recording writer stopped"),
+        }
+    }
+
+    pub fn write_config_nals(&mut self, config_buffer: &[u8]) -> Result<()> {
+        match self {
+            VideoRecording::Raw(file) => file.write_all(config_buffer)?,
+            VideoRecording::Matroska { sender, .. } => {
+                Self::send(sender, RecordingPacket::Config(config_buffer.to_vec()))?
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn write_video(&mut self, nal_buffer: &[u8], is_idr: bool) -> Result<()> {
+        match self {
+            VideoRecording::Raw(file) => file.write_all(nal_buffer)?,
+            VideoRecording::Matroska { sender, start } => Self::send(
+                sender,
+                RecordingPacket::Video {
+                    timestamp: start.elapsed(),
+                    data: nal_buffer.to_vec(),
+                    is_idr,
+                },
+            )?,
+        }
+
+        Ok(())
+    }
+
+    pub fn write_audio(&mut self, data: &[u8]) -> Result<()> {
+        if let VideoRecording::Matroska { sender, start } = self {
+            Self::send(
+                sender,
+                RecordingPacket::Audio {
+                    timestamp: start.elapsed(),
+                    data: data.to_vec(),
+                },
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PPS: &[u8] = &[0x68, 0xEE, 0x3C, 0x80];
+
+    struct Block {
+        track: u8,
+        timestamp_ms: i64,
+        keyframe: bool,
+        frame: Vec<u8>,
+    }
+
+    struct Track {
+        number: u64,
+        codec_id: Vec<u8>,
+        codec_private: Vec<u8>,
+        size: (u64, u64),
+    }
+
+    #[derive(Default)]
+    struct ParsedFile {
+        doc_type: Vec<u8>,
+        tracks: Vec<Track>,
+        blocks: Vec<Block>,
+        // All bytes have been consumed by complete elements
+        complete: bool,
+    }
+
+    fn read_vint(data: &[u8], pos: &mut usize, keep_marker: bool) -> Option<Option<u64>> {
+        let first = *data.get(*pos)?;
+        let length = first.leading_zeros() as usize + 1;
+        let bytes = data.get(*pos..*pos + length)?;
+        *pos += length;
+
+        let mut value = if keep_marker {
+            first as u64
+        } else {
+            first as u64 & (0xFF >> length)
+        };
+        for byte in &bytes[1..] {
+            value = (value << 8) | *byte as u64;
+        }
+
+        let unknown = !keep_marker && value == (1 << (7 * length)) - 1;
+        Some((!unknown).then_some(value))
+    }
+
+    // (id, payload), None for unknown sizes
+    fn read_element<'a>(data: &'a [u8], pos: &mut usize) -> Option<(u32, Option<&'a [u8]>)> {
+        let id = read_vint(data, pos, true)?? as u32;
+        let Some(size) = read_vint(data, pos, false)? else {
+            return Some((id, None));
+        };
+        let payload = data.get(*pos..*pos + size as usize)?;
+        *pos += size as usize;
+
+        Some((id, Some(payload)))
+    }
+
+    fn children(data: &[u8]) -> Vec<(u32, &[u8])> {
+        let mut pos = 0;
+        let mut children = vec![];
+        while pos < data.len() {
+            let (id, payload) = read_element(data, &mut pos).unwrap();
+            children.push((id, payload.unwrap()));
+        }
+
+        children
+    }
+
+    fn uint(data: &[u8]) -> u64 {
+        data.iter()
+            .fold(0, |value, byte| (value << 8) | *byte as u64)
+    }
+
+    fn parse(data: &[u8]) -> ParsedFile {
+        let mut file = ParsedFile::default();
+
+        let mut pos = 0;
+        let (id, header) = read_element(data, &mut pos).unwrap();
+        assert_eq!(id, EBML);
+        for (id, payload) in children(header.unwrap()) {
+            if id == DOC_TYPE {
+                file.doc_type = payload.to_vec();
+            }
+        }
+
+        let (id, size) = read_element(data, &mut pos).unwrap();
+        assert!(id == SEGMENT && size.is_none());
+
+        // Children of the unknown size clusters follow them at the same level
+        let mut cluster_timestamp = None;
+        while pos < data.len() {
+            let Some((id, payload)) = read_element(data, &mut pos) else {
+                return file;
+            };
+            match (id, payload) {
+                (CLUSTER, None) => cluster_timestamp = None,
+                (CLUSTER_TIMESTAMP, Some(payload)) => cluster_timestamp = Some(uint(payload)),
+                (INFO, Some(_)) => (),
+                (TRACKS, Some(payload)) => {
+                    for (id, entry) in children(payload) {
+                        assert_eq!(id, TRACK_ENTRY);
+                        let mut track = Track {
+                            number: 0,
+                            codec_id: vec![],
+                            codec_private: vec![],
+                            size: (0, 0),
+                        };
+                        for (id, value) in children(entry) {
+                            match id {
+                                TRACK_NUMBER => track.number = uint(value),
+                                CODEC_ID => track.codec_id = value.to_vec(),
+                                CODEC_PRIVATE => track.codec_private = value.to_vec(),
+                                VIDEO => {
+                                    for (id, value) in children(value) {
+                                        match id {
+                                            PIXEL_WIDTH => track.size.0 = uint(value),
+                                            PIXEL_HEIGHT => track.size.1 = uint(value),
+                                            _ => (),
+                                        }
+                                    }
+                                }
+                                _ => (),
+                            }
+                        }
+                        file.tracks.push(track);
+                    }
+                }
+                (SIMPLE_BLOCK, Some(payload)) => {
+                    let mut block_pos = 0;
+                    let track = read_vint(payload, &mut block_pos, false).unwrap().unwrap();
+                    let offset = i16::from_be_bytes([payload[block_pos], payload[block_pos + 1]]);
+                    file.blocks.push(Block {
+                        track: track as u8,
+                        timestamp_ms: cluster_timestamp.unwrap() as i64 + offset as i64,
+                        keyframe: payload[block_pos + 2] & 0x80 != 0,
+                        frame: payload[block_pos + 3..].to_vec(),
+                    })
+                }
+                _ => panic!("Unexpected element {id:X}"),
+            }
+        }
+        file.complete = true;
+
+        file
+    }
+
+    // High profile, 3712x1936 cropped to 3712x1920
+    fn sps() -> Vec<u8> {
+        bitstream(&[
+            (8, 0x67),
+            (8, 100),
+            (8, 0),
+            (8, 31),
+            (0, 0),
+            (0, 1),
+            (0, 0),
+            (0, 0),
+            (1, 0),
+            (1, 0),
+            (0, 0),
+            (0, 0),
+            (0, 0),
+            (0, 1),
+            (1, 0),
+            (0, 231),
+            (0, 120),
+            (1, 1),
+            (1, 1),
+            // Cropping
+            (1, 1),
+            (0, 0),
+            (0, 0),
+            (0, 0),
+            (0, 8),
+            (1, 0),
+            (1, 1),
+        ])
+    }
+
+    // 2 seconds at 90 fps with an IDR every 30 frames and 10 ms audio packets
+    fn record_stream() -> (Vec<u8>, Vec<Vec<u8>>) {
+        let mut config_buffer = vec![0, 0, 0, 1];
+        config_buffer.extend(sps());
+        config_buffer.extend([0, 0, 0, 1]);
+        config_buffer.extend(PPS);
+
+        let mut writer = MatroskaWriter::new(
+            vec![],
+            CodecType::H264,
+            Some(AudioTrackInfo {
+                sample_rate: 48000,
+                channels: 2,
+            }),
+        );
+
+        // Frames before the config NALs cannot be decoded from the file
+        writer
+            .write_video(Duration::ZERO, &[0, 0, 0, 1, 0x65, 1], true)
+            .unwrap();
+        writer.set_config_nals(config_buffer);
+
+        let mut frames = vec![];
+        let mut audio_time = Duration::ZERO;
+        for i in 0..180_u32 {
+            let timestamp = Duration::from_micros(i as u64 * 11_111);
+            let is_idr = i % 30 == 0;
+
+            // Ends with the RBSP stop bit
+            let nal = [
+                if is_idr { 0x65 } else { 0x41 },
+                0x88,
+                0,
+                0,
+                3,
+                i as u8,
+                0x80,
+            ];
+            let mut data = vec![0, 0, 0, 1];
+            data.extend(nal);
+            writer.write_video(timestamp, &data, is_idr).unwrap();
+            frames.push(nal.to_vec());
+
+            while audio_time <= timestamp {
+                writer.write_audio(audio_time, &[0; 1920]).unwrap();
+                audio_time += Duration::from_millis(10);
+            }
+        }
+
+        (writer.writer, frames)
+    }
+
+    #[test]
+    fn test_recording_is_valid_matroska() {
+        let (data, frames) = record_stream();
+        let file = parse(&data);
+
+        assert!(file.complete);
+        assert_eq!(file.doc_type, b"matroska");
+
+        assert_eq!(file.tracks.len(), 2);
+        assert_eq!(file.tracks[0].number, VIDEO_TRACK as u64);
+        assert_eq!(file.tracks[0].codec_id, b"V_MPEG4/ISO/AVC");
+        assert_eq!(file.tracks[0].size, (3712, 1920));
+        let sps = sps();
+        let mut avcc = vec![1, 0x64, 0x00, 0x1F, 0xFF, 0xE1, 0, sps.len() as u8];
+        avcc.extend(&sps);
+        avcc.extend([1, 0, PPS.len() as u8]);
+        avcc.extend(PPS);
+        assert_eq!(file.tracks[0].codec_private, avcc);
+        assert_eq!(file.tracks[1].codec_id, b"A_PCM/INT/LIT");
+
+        let video_blocks = file
+            .blocks
+            .iter()
+            .filter(|b| b.track == VIDEO_TRACK)
+            .collect::<Vec<_>>();
+        assert_eq!(video_blocks.len(), frames.len());
+        for (index, (block, nal)) in video_blocks.iter().zip(&frames).enumerate() {
+            assert_eq!(block.keyframe, index % 30 == 0);
+            assert_eq!(block.timestamp_ms, index as i64 * 11_111 / 1000);
+            // Length prefixed NALs
+            assert_eq!(block.frame[..4], (nal.len() as u32).to_be_bytes());
+            assert_eq!(block.frame[4..], nal[..]);
+        }
+
+        let audio_blocks = file.blocks.iter().filter(|b| b.track == AUDIO_TRACK);
+        assert_eq!(audio_blocks.count(), 199);
+    }
+
+    // (bits, value) fields, zero bits for an Exp-Golomb code
+    fn bitstream(fields: &[(usize, u32)]) -> Vec<u8> {
+        let mut bits = vec![];
+        for &(length, value) in fields {
+            if length == 0 {
+                let length = 32 - (value + 1).leading_zeros() as usize;
+                bits.extend(vec![0; length - 1]);
+                bits.extend((0..length).rev().map(|i| ((value + 1) >> i) & 1));
+            } else {
+                bits.extend((0..length).rev().map(|i| (value as u64 >> i) as u32 & 1));
+            }
+        }
+
+        bits.chunks(8)
+            .map(|byte| {
+                byte.iter()
+                    .enumerate()
+                    .fold(0, |acc, (i, bit)| acc | (*bit as u8) << (7 - i))
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_codec_private_uses_the_coded_format() {
+        // HEVC Range Extensions, 4:4:4 10 bit
+        let sps_rbsp = bitstream(&[
+            (16, 0x4201),
+            (4, 0),
+            (3, 0),
+            (1, 1),
+            // Profile, compatibility flags, constraint flags and level
+            (8, 4),
+            (32, 0x0800_0000),
+            (16, 0x9000),
+            (32, 0),
+            (8, 120),
+            (0, 0),
+            (0, 3),
+            (1, 0),
+            (0, 3712),
+            (0, 1928),
+            // Conformance window
+            (1, 1),
+            (0, 0),
+            (0, 0),
+            (0, 0),
+            (0, 8),
+            (0, 2),
+            (0, 2),
+            (1, 1),
+        ]);
+        assert_eq!(
+            hevc_sps_format(&sps_rbsp).unwrap(),
+            PictureFormat {
+                width: 3712,
+                height: 1920,
+                chroma_format_idc: 3,
+                bit_depth_luma: 10,
+                bit_depth_chroma: 10,
+            }
+        );
+
+        let mut config_buffer = vec![];
+        for nal in [&[0x40, 0x01, 0x0C][..], &sps_rbsp, &[0x44, 0x01, 0xC0]] {
+            config_buffer.extend([0, 0, 0, 1]);
+            config_buffer.extend(nal);
+        }
+        let writer = MatroskaWriter::new(vec![], CodecType::Hevc, None);
+        let (hvcc, size) = writer.codec_private(&config_buffer).unwrap();
+        assert_eq!(hvcc[16..19], [0xFF, 0xFA, 0xFA]);
+        assert_eq!(size, (3712, 1920));
+
+        // AV1 High profile, 4:4:4 8 bit with two operating points
+        let header = bitstream(&[
+            (3, 1),
+            (1, 0),
+            (1, 0),
+            (1, 0),
+            (1, 0),
+            (1, 0),
+            (5, 1),
+            (12, 0x101),
+            (5, 12),
+            (1, 1),
+            (12, 0x301),
+            (5, 8),
+            (1, 0),
+            // Frame size
+            (4, 11),
+            (4, 10),
+            (12, 3711),
+            (11, 1919),
+            (1, 0),
+            // Coding tools, with order hints and screen content tools
+            (3, 0),
+            (4, 0),
+            (1, 1),
+            (2, 0),
+            (1, 1),
+            (1, 1),
+            (3, 6),
+            (3, 0),
+            // Color config, full range
+            (1, 0),
+            (1, 0),
+            (1, 1),
+            (1, 1),
+        ]);
+
+        let mut config_buffer = vec![0x0A, header.len() as u8];
+        config_buffer.extend(&header);
+        let writer = MatroskaWriter::new(vec![], CodecType::AV1, None);
+        let (av1c, size) = writer.codec_private(&config_buffer).unwrap();
+        assert_eq!(av1c[..4], [0x81, 1 << 5 | 12, 0x80, 0]);
+        assert_eq!(av1c[4..], config_buffer[..]);
+        assert_eq!(size, (3712, 1920));
+    }
+
+    #[test]
+    fn test_interrupted_recording_is_readable() {
+        let (data, _) = record_stream();
+
+        // The server crashed while writing a block
+        let file = parse(&data[..data.len() * 2 / 3]);
+
+        assert!(!file.complete);
+        assert_eq!(file.tracks.len(), 2);
+        assert!(file.blocks.len() > 100);
+    }
+}
@@ -1,3 +1,4 @@
+use crate::benchmark::BenchmarkSample;
 use alvr_common::{SlidingWindowAverage, HEAD_ID};
 use alvr_events::{EventType, GraphStatistics, NominalBitrateStats, StatisticsSummary};
 use alvr_packets::ClientStatistics;
@@ -51,12 +52,18 @@ pub struct StatisticsManager {
     video_bytes_partial_sum: usize,
     packets_lost_total: usize,
     packets_lost_partial_sum: usize,
+    psnr_db_sum: f64,
+    psnr_frames: usize,
+    ssim_sum: f64,
+    ssim_frames: usize,
     battery_gauges: HashMap<u64, BatteryData>,
     steamvr_pipeline_latency: Duration,
     total_pipeline_latency_average: SlidingWindowAverage<Duration>,
+    network_latency_average: SlidingWindowAverage<Duration>,
     last_vsync_time: Instant,
     frame_interval: Duration,
     last_nominal_bitrate_stats: NominalBitrateStats,
+    packet_pacing_interval: Option<Duration>,
 }
 
 impl StatisticsManager {
@@ -78,6 +85,10 @@ impl StatisticsManager {
             video_bytes_partial_sum: 0,
             packets_lost_total: 0,
             packets_lost_partial_sum: 0,
+            psnr_db_sum: 0.0,
+            psnr_frames: 0,
+            ssim_sum: 0.0,
+            ssim_frames: 0,
             battery_gauges: HashMap::new(),
             steamvr_pipeline_latency: Duration::from_secs_f32(
                 steamvr_pipeline_frames * nominal_server_frame_interval.as_secs_f32(),
@@ -86,9 +97,11 @@ impl StatisticsManager {
                 Duration::ZERO,
                 max_history_size,
             ),
+            network_latency_average: SlidingWindowAverage::new(Duration::ZERO, max_history_size),
             last_vsync_time: Instant::now(),
             frame_interval: nominal_server_frame_interval,
             last_nominal_bitrate_stats: NominalBitrateStats::default(),
+            packet_pacing_interval: None,
         }
     }
 
@@ -180,6 +193,10 @@ impl StatisticsManager {
         self.last_nominal_bitrate_stats = stats;
     }
 
+    pub fn report_packet_pacing_interval(&mut self, interval: Option<Duration>) {
+        self.packet_pacing_interval = interval;
+    }
+
     // Called every frame. Some statistics are reported once every frame
     // Returns (network latency, game time latency)
     pub fn report_statistics(&mut self, client_stats: ClientStatistics) -> (Duration, Duration) {
@@ -217,6 +234,7 @@ impl StatisticsManager {
                     + client_stats.rendering
                     + client_stats.vsync_queue,
             );
+            self.network_latency_average.submit_sample(network_latency);
 
             let client_fps = 1.0
                 / client_stats
@@ -264,6 +282,11 @@ impl StatisticsManager {
                         .cloned()
                         .unwrap_or_default()
                         .is_plugged,
+                    packet_pacing_interval_us: self
+                        .packet_pacing_interval
+                        .map(|interval| interval.as_micros() as u32),
+                    decoder_parallelism: client_stats.decoder_parallelism,
+                    decoder_dropped_frames: client_stats.decoder_dropped_frames,
                 }));
 
                 self.video_packets_partial_sum = 0;
@@ -307,6 +330,37 @@ impl StatisticsManager {
         self.total_pipeline_latency_average.get_average()
     }
 
+    // Zero until the first statistics are received
+    pub fn network_latency_average(&self) -> Duration {
+        self.network_latency_average.get_average()
+    }
+
+    // Either can be missing, depending on the encoder
+    pub fn report_encode_quality(&mut self, psnr_db: Option<f64>, ssim: Option<f64>) {
+        if let Some(psnr_db) = psnr_db {
+            self.psnr_db_sum += psnr_db;
+            self.psnr_frames += 1;
+        }
+        if let Some(ssim) = ssim {
+            self.ssim_sum += ssim;
+            self.ssim_frames += 1;
+        }
+    }
+
+    pub fn benchmark_sample(&self) -> BenchmarkSample {
+        BenchmarkSample {
+            video_frames: self.video_packets_total,
+            video_bytes: self.video_bytes_total,
+            packets_lost: self.packets_lost_total,
+            total_pipeline_latency: self.total_pipeline_latency_average.get_average(),
+            network_latency: self.network_latency_average.get_average(),
+            psnr_db_sum: self.psnr_db_sum,
+            psnr_frames: self.psnr_frames,
+            ssim_sum: self.ssim_sum,
+            ssim_frames: self.ssim_frames,
+        }
+    }
+
     pub fn tracker_pose_time_offset(&self) -> Duration {
         // This is the opposite of the client's StatisticsManager::tracker_prediction_offset().
         self.steamvr_pipeline_latency
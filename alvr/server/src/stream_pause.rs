@@ -0,0 +1,110 @@
+// The stream can be paused by the application (on both the server and the client side) and when
+// the headset is removed. While paused the connection stays alive but no frames are encoded or
+// sent. Encoded frames still in flight are discarded, and the stream resumes from an IDR.
+pub struct StreamPause {
+    requested: bool,
+    headset_removed: bool,
+    pause_on_headset_removal: bool,
+    waiting_for_idr: bool,
+}
+
+impl StreamPause {
+    pub fn new(pause_on_headset_removal: bool) -> Self {
+        Self {
+            requested: false,
+            headset_removed: false,
+            pause_on_headset_removal,
+            waiting_for_idr: false,
+        }
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.requested || (self.pause_on_headset_removal && self.headset_removed)
+    }
+
+    // Returns true if the paused state changed
+    pub fn request_pause(&mut self, paused: bool) -> bool {
+        let was_paused = self.is_paused();
+        self.requested = paused;
+
+        self.update(was_paused)
+    }
+
+    // Returns true if the paused state changed
+    pub fn report_headset_presence(&mut self, present: bool) -> bool {
+        let was_paused = self.is_paused();
+        self.headset_removed = !present;
+
+        self.update(was_paused)
+    }
+
+    fn update(&mut self, was_paused: bool) -> bool {
+        let paused = self.is_paused();
+        if was_paused && !paused {
+            self.waiting_for_idr = true;
+        }
+
+        paused != was_paused
+    }
+
+    // Returns false if the encoded frame must not be sent
+    pub fn frame_allowed(&mut self, is_idr: bool) -> bool {
+        if self.is_paused() || (self.waiting_for_idr && !is_idr) {
+            return false;
+        }
+        self.waiting_for_idr = false;
+
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Frames produced by the encoder, as is_idr. Returns the frames that are sent
+    fn send_frames(pause: &mut StreamPause, frames: &[bool]) -> Vec<bool> {
+        frames
+            .iter()
+            .copied()
+            .filter(|is_idr| pause.frame_allowed(*is_idr))
+            .collect()
+    }
+
+    #[test]
+    fn test_pause_stops_frames_and_resume_starts_from_idr() {
+        let mut pause = StreamPause::new(true);
+        assert_eq!(send_frames(&mut pause, &[true, false]), [true, false]);
+
+        assert!(pause.request_pause(true));
+        assert!(!pause.request_pause(true));
+        // Frames encoded before the encoder observed the pause
+        assert!(send_frames(&mut pause, &[false, true, false]).is_empty());
+
+        assert!(pause.request_pause(false));
+        // The first frame sent after resuming is the requested IDR
+        assert_eq!(
+            send_frames(&mut pause, &[false, true, false]),
+            [true, false]
+        );
+    }
+
+    #[test]
+    fn test_headset_removal_pause() {
+        let mut pause = StreamPause::new(true);
+
+        assert!(pause.report_headset_presence(false));
+        assert!(send_frames(&mut pause, &[true]).is_empty());
+
+        // Explicitly requested pauses have to be resumed explicitly
+        assert!(!pause.request_pause(true));
+        assert!(!pause.report_headset_presence(true));
+        assert!(pause.is_paused());
+        assert!(pause.request_pause(false));
+        assert_eq!(send_frames(&mut pause, &[false, true]), [true]);
+
+        let mut pause = StreamPause::new(false);
+        assert!(!pause.report_headset_presence(false));
+        assert_eq!(send_frames(&mut pause, &[false]), [false]);
+    }
+}
@@ -0,0 +1,90 @@
+use alvr_common::{parking_lot::Mutex, warn};
+use std::{sync::mpsc, thread, time::Duration};
+
+type Stage = (&'static str, Box<dyn FnOnce() + Send>);
+
+// Ordered shutdown steps, run at most once. Each stage runs in its own thread and is abandoned if
+// it does not complete within the timeout, so a hung stage cannot block the shutdown. The following
+// stages are run anyway.
+pub struct Teardown {
+    stages: Mutex<Option<Vec<Stage>>>,
+    stage_timeout: Duration,
+}
+
+impl Teardown {
+    pub fn new(stage_timeout: Duration) -> Self {
+        Self {
+            stages: Mutex::new(Some(vec![])),
+            stage_timeout,
+        }
+    }
+
+    pub fn stage(self, name: &'static str, run: impl FnOnce() + Send + 'static) -> Self {
+        if let Some(stages) = &mut *self.stages.lock() {
+            stages.push((name, Box::new(run)));
+        }
+
+        self
+    }
+
+    // Returns the stages that did not complete, because they timed out or panicked. Calls after the
+    // first one do nothing
+    pub fn run(&self) -> Vec<&'static str> {
+        let Some(stages) = self.stages.lock().take() else {
+            return vec![];
+        };
+
+        let mut failed_stages = vec![];
+        for (name, run) in stages {
+            let (sender, receiver) = mpsc::channel();
+            let spawn_result = thread::Builder::new()
+                .name(format!("teardown {name}"))
+                .spawn(move || {
+                    run();
+                    sender.send(()).ok();
+                });
+
+            if spawn_result.is_err() || receiver.recv_timeout(self.stage_timeout).is_err() {
+                warn!("Shutdown stage \"{name}\" did not complete");
+                failed_stages.push(name);
+            }
+        }
+
+        failed_stages
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{sync::Arc, time::Instant};
+
+    #[test]
+    fn test_teardown_releases_resources_once_despite_hung_stage() {
+        let released = Arc::new(Mutex::new(vec![]));
+        let (_hang_sender, hang_receiver) = mpsc::channel::<()>();
+
+        let teardown = Teardown::new(Duration::from_millis(100))
+            .stage("encoder", {
+                let released = Arc::clone(&released);
+                move || released.lock().push("encoder")
+            })
+            .stage("hung", move || {
+                hang_receiver.recv().ok();
+            })
+            .stage("panicking", || panic!())
+            .stage("sockets", {
+                let released = Arc::clone(&released);
+                move || released.lock().push("sockets")
+            });
+
+        let begin = Instant::now();
+        assert_eq!(teardown.run(), ["hung", "panicking"]);
+        assert!(begin.elapsed() < Duration::from_secs(2));
+        assert_eq!(*released.lock(), ["encoder", "sockets"]);
+
+        // A second shutdown does not release the resources again
+        assert!(teardown.run().is_empty());
+        assert_eq!(released.lock().len(), 2);
+    }
+}
@@ -1,11 +1,14 @@
 use alvr_common::{
-    glam::{EulerRot, Quat, Vec3},
+    anyhow::{bail, Result},
+    glam::{EulerRot, Mat3, Quat, Vec3},
     DeviceMotion, Pose, BODY_CHEST_ID, BODY_HIPS_ID, BODY_LEFT_ELBOW_ID, BODY_LEFT_FOOT_ID,
     BODY_LEFT_KNEE_ID, BODY_RIGHT_ELBOW_ID, BODY_RIGHT_FOOT_ID, BODY_RIGHT_KNEE_ID, HAND_LEFT_ID,
     HAND_RIGHT_ID, HEAD_ID,
 };
+use alvr_packets::Tracking;
 use alvr_session::{
-    settings_schema::Switch, HeadsetConfig, PositionRecenteringMode, RotationRecenteringMode,
+    settings_schema::Switch, CoordinateConvention, HeadsetConfig, PositionRecenteringMode,
+    RotationRecenteringMode,
 };
 use std::{collections::HashMap, f32::consts::PI};
 
@@ -44,6 +47,69 @@ pub fn get_hand_skeleton_offsets(config: &HeadsetConfig) -> (Pose, Pose) {
     (left_offset, right_offset)
 }
 
+// Converts the poses of a source with another coordinate convention to the SteamVR one: X right,
+// Y up, -Z forward, right-handed. The columns of the basis are the source axes in the SteamVR space.
+// A mirrored basis changes the handedness, the converted orientations are still rotations.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct CoordinateTransform {
+    basis: Mat3,
+}
+
+impl CoordinateTransform {
+    pub fn new(convention: CoordinateConvention) -> Result<Self> {
+        let basis = match convention {
+            // X right, Y forward, Z up
+            CoordinateConvention::ZUpRightHanded => Mat3::from_cols(Vec3::X, Vec3::NEG_Z, Vec3::Y),
+            // X right, Y up, Z forward
+            CoordinateConvention::YUpLeftHanded => Mat3::from_cols(Vec3::X, Vec3::Y, Vec3::NEG_Z),
+            CoordinateConvention::Custom {
+                x_axis,
+                y_axis,
+                z_axis,
+            } => Mat3::from_cols(x_axis.into(), y_axis.into(), z_axis.into()),
+        };
+
+        if !(basis.transpose() * basis).abs_diff_eq(Mat3::IDENTITY, 1e-3) {
+            bail!(
+                "The coordinate basis {:?} is not orthonormal",
+                basis.to_cols_array_2d()
+            );
+        }
+
+        Ok(Self { basis })
+    }
+
+    pub fn transform_pose(&self, pose: Pose) -> Pose {
+        let rotation = self.basis * Mat3::from_quat(pose.orientation) * self.basis.transpose();
+
+        Pose {
+            orientation: Quat::from_mat3(&rotation).normalize(),
+            position: self.basis * pose.position,
+        }
+    }
+
+    pub fn transform_motion(&self, motion: DeviceMotion) -> DeviceMotion {
+        DeviceMotion {
+            pose: self.transform_pose(motion.pose),
+            linear_velocity: self.basis * motion.linear_velocity,
+            // Rotation axes are flipped by a mirrored basis
+            angular_velocity: self.basis.determinant() * (self.basis * motion.angular_velocity),
+        }
+    }
+
+    pub fn transform_tracking(&self, tracking: &mut Tracking) {
+        for (_, motion) in &mut tracking.device_motions {
+            *motion = self.transform_motion(*motion);
+        }
+        for pose in tracking.hand_skeletons.iter_mut().flatten().flatten() {
+            *pose = self.transform_pose(*pose);
+        }
+        for pose in tracking.face_data.eye_gazes.iter_mut().flatten() {
+            *pose = self.transform_pose(*pose);
+        }
+    }
+}
+
 // todo: Move this struct to Settings and use it for every tracked device
 #[derive(Default)]
 struct MotionConfig {
@@ -249,3 +315,135 @@ pub fn to_local_eyes(
         raw_global_eyes[1].map(|e| raw_global_head.inverse() * e),
     ]
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f32::consts::FRAC_PI_2;
+
+    fn forward(orientation: Quat) -> Vec3 {
+        orientation * -Vec3::Z
+    }
+
+    fn head_pose() -> Pose {
+        Pose {
+            orientation: Quat::from_euler(EulerRot::YXZ, 1.2, -0.3, 0.0),
+            position: Vec3::new(0.5, 1.7, -1.0),
+        }
+    }
+
+    #[test]
+    fn test_yaw_recentering() {
+        let mut manager = TrackingManager::new();
+        manager.last_head_pose = head_pose();
+        manager.recenter(
+            PositionRecenteringMode::LocalFloor,
+            RotationRecenteringMode::Yaw,
+        );
+
+        let head = manager.recenter_pose(head_pose());
+
+        // The captured forward direction becomes -Z, while the pitch is kept
+        let direction = forward(head.orientation);
+        assert!(direction.x.abs() < 1e-5);
+        assert!(direction.z < 0.0);
+        assert!((direction.y - f32::sin(-0.3)).abs() < 1e-5);
+
+        // The origin is moved to the floor below the head
+        assert!(head.position.distance(Vec3::new(0.0, 1.7, 0.0)) < 1e-5);
+
+        // A controller held at the right of the head stays at the right
+        let controller = Pose {
+            orientation: head_pose().orientation,
+            position: head_pose().position + head_pose().orientation * Vec3::new(0.3, 0.0, 0.0),
+        };
+        let controller = manager.recenter_pose(controller);
+        assert!(controller.position.x > 0.29);
+        assert!(controller.position.z.abs() < 1e-5);
+    }
+
+    #[test]
+    fn test_z_up_poses_to_y_up() {
+        let transform = CoordinateTransform::new(CoordinateConvention::ZUpRightHanded).unwrap();
+
+        // A controller 1.5m above the floor, 2m forward and 1m right, turned left by 90°
+        let motion = transform.transform_motion(DeviceMotion {
+            pose: Pose {
+                orientation: Quat::from_rotation_z(FRAC_PI_2),
+                position: Vec3::new(1.0, 2.0, 1.5),
+            },
+            linear_velocity: Vec3::new(0.0, 1.0, 0.0),
+            angular_velocity: Vec3::new(0.0, 0.0, 1.0),
+        });
+
+        assert!(motion
+            .pose
+            .position
+            .abs_diff_eq(Vec3::new(1.0, 1.5, -2.0), 1e-5));
+        assert!(
+            motion
+                .pose
+                .orientation
+                .angle_between(Quat::from_rotation_y(FRAC_PI_2))
+                < 1e-3
+        );
+        assert!(forward(motion.pose.orientation).abs_diff_eq(-Vec3::X, 1e-5));
+        assert!(motion.linear_velocity.abs_diff_eq(-Vec3::Z, 1e-5));
+        assert!(motion.angular_velocity.abs_diff_eq(Vec3::Y, 1e-5));
+
+        // The same motion from a Y-up left-handed source: a left turn is a negative rotation
+        let transform = CoordinateTransform::new(CoordinateConvention::YUpLeftHanded).unwrap();
+        let motion = transform.transform_motion(DeviceMotion {
+            pose: Pose {
+                orientation: Quat::from_rotation_y(-FRAC_PI_2),
+                position: Vec3::new(1.0, 1.5, 2.0),
+            },
+            linear_velocity: Vec3::new(0.0, 0.0, 1.0),
+            angular_velocity: Vec3::new(0.0, -1.0, 0.0),
+        });
+        assert!(motion
+            .pose
+            .position
+            .abs_diff_eq(Vec3::new(1.0, 1.5, -2.0), 1e-5));
+        assert!(forward(motion.pose.orientation).abs_diff_eq(-Vec3::X, 1e-5));
+        assert!(motion.angular_velocity.abs_diff_eq(Vec3::Y, 1e-5));
+    }
+
+    #[test]
+    fn test_non_orthonormal_basis_is_rejected() {
+        let custom = |z_axis| CoordinateConvention::Custom {
+            x_axis: [1.0, 0.0, 0.0],
+            y_axis: [0.0, 0.0, -1.0],
+            z_axis,
+        };
+
+        assert_eq!(
+            CoordinateTransform::new(custom([0.0, 1.0, 0.0])).unwrap(),
+            CoordinateTransform::new(CoordinateConvention::ZUpRightHanded).unwrap()
+        );
+        // Scaled and skewed axes
+        assert!(CoordinateTransform::new(custom([0.0, 2.0, 0.0])).is_err());
+        assert!(CoordinateTransform::new(custom([1.0, 1.0, 0.0])).is_err());
+    }
+
+    #[test]
+    fn test_full_recentering() {
+        let mut manager = TrackingManager::new();
+        manager.last_head_pose = head_pose();
+        manager.recenter(
+            PositionRecenteringMode::Disabled,
+            RotationRecenteringMode::Tilted,
+        );
+
+        let head = manager.recenter_pose(head_pose());
+        assert!(forward(head.orientation).distance(-Vec3::Z) < 1e-5);
+
+        // A controller pointing right of the head keeps pointing right
+        let controller = Pose {
+            orientation: head_pose().orientation * Quat::from_rotation_y(-0.5),
+            position: head_pose().position,
+        };
+        let direction = forward(manager.recenter_pose(controller).orientation);
+        assert!(direction.x > 0.0 && direction.y.abs() < 1e-5);
+    }
+}
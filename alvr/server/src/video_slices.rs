@@ -0,0 +1,103 @@
+use alvr_session::CodecType;
+use std::ops::Range;
+
+fn is_slice_nal(codec: CodecType, nal_header: u8) -> bool {
+    match codec {
+        CodecType::H264 => (1..=5).contains(&(nal_header & 0x1F)),
+        // Types 0 to 31 are the VCL NAL units
+        CodecType::Hevc => (nal_header >> 1) & 0x3F < 32,
+        CodecType::AV1 => false,
+    }
+}
+
+// Splits an Annex B frame in ranges containing one slice each, to send each slice in its own
+// packet. Each range starts with a start code. The parameter sets and the SEI are kept together
+// with the following slice, the other NAL units after the last slice (filler data) with the last
+// one. A frame without slices is not split.
+pub fn slice_ranges(codec: CodecType, frame: &[u8]) -> Vec<Range<usize>> {
+    let mut slice_starts = vec![0];
+    let mut slice_found = false;
+    // Start of the NAL units that precede the next slice
+    let mut next_slice_start = None;
+
+    let mut i = 0;
+    while i + 3 < frame.len() {
+        if frame[i..i + 3] != [0, 0, 1] {
+            i += 1;
+            continue;
+        }
+
+        // The leading zero of a 4 byte start code
+        let nal_start = if i > 0 && frame[i - 1] == 0 { i - 1 } else { i };
+        if is_slice_nal(codec, frame[i + 3]) {
+            if slice_found {
+                slice_starts.push(next_slice_start.unwrap_or(nal_start));
+            }
+            slice_found = true;
+            next_slice_start = None;
+        } else if slice_found && next_slice_start.is_none() {
+            next_slice_start = Some(nal_start);
+        }
+
+        i += 3;
+    }
+
+    slice_starts
+        .iter()
+        .enumerate()
+        .map(|(index, start)| *start..slice_starts.get(index + 1).copied().unwrap_or(frame.len()))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn nal(start_code: &[u8], header: u8, payload_size: usize) -> Vec<u8> {
+        [start_code, &[header], &vec![0xAA; payload_size]].concat()
+    }
+
+    #[test]
+    fn test_slices_are_split_at_nal_boundaries() {
+        const SPS: u8 = 0x67;
+        const PPS: u8 = 0x68;
+        const IDR_SLICE: u8 = 0x65;
+        const FILLER: u8 = 0x0C;
+
+        let slices = [
+            [
+                nal(&[0, 0, 0, 1], SPS, 10),
+                nal(&[0, 0, 0, 1], PPS, 4),
+                nal(&[0, 0, 0, 1], IDR_SLICE, 300),
+            ]
+            .concat(),
+            nal(&[0, 0, 1], IDR_SLICE, 200),
+            [
+                nal(&[0, 0, 0, 1], IDR_SLICE, 250),
+                nal(&[0, 0, 1], FILLER, 50),
+            ]
+            .concat(),
+        ];
+        let frame = slices.concat();
+
+        let ranges = slice_ranges(CodecType::H264, &frame);
+        assert_eq!(ranges.len(), 3);
+        for (range, slice) in ranges.iter().zip(&slices) {
+            assert_eq!(&frame[range.clone()], slice.as_slice());
+        }
+
+        // HEVC slices of a P frame, separated by an SEI
+        let hevc_frame = [
+            nal(&[0, 0, 0, 1], 0x02, 100),
+            nal(&[0, 0, 0, 1], 0x4E, 8),
+            nal(&[0, 0, 0, 1], 0x02, 100),
+        ]
+        .concat();
+        assert_eq!(
+            slice_ranges(CodecType::Hevc, &hevc_frame),
+            [0..105, 105..hevc_frame.len()]
+        );
+
+        assert_eq!(slice_ranges(CodecType::AV1, &frame), [0..frame.len()]);
+    }
+}
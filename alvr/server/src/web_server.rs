@@ -1,6 +1,7 @@
 use crate::{
-    logging_backend::LOGGING_EVENTS_SENDER, ConnectionContext, ServerCoreEvent, FILESYSTEM_LAYOUT,
-    SERVER_DATA_MANAGER,
+    benchmark::{BenchmarkRun, BenchmarkSweep},
+    logging_backend::LOGGING_EVENTS_SENDER,
+    ConnectionContext, ServerCoreEvent, FILESYSTEM_LAYOUT, SERVER_DATA_MANAGER,
 };
 use alvr_common::{
     anyhow::{self, Result},
@@ -140,13 +141,50 @@ async fn http_api(
                     }
                     ServerRequest::CaptureFrame => unsafe { crate::CaptureFrame() },
                     ServerRequest::InsertIdr => unsafe { crate::RequestIDR() },
+                    ServerRequest::Recenter => connection_context.recenter_requested.set(true),
+                    ServerRequest::FastReconnect => {
+                        connection_context.fast_reconnect_requested.set(true)
+                    }
                     ServerRequest::StartRecording => crate::create_recording_file(
                         connection_context,
-                        SERVER_DATA_MANAGER.read().settings(),
+                        &SERVER_DATA_MANAGER.read(),
                     ),
                     ServerRequest::StopRecording => {
                         *connection_context.video_recording_file.lock() = None
                     }
+                    ServerRequest::StartBenchmark => {
+                        let data_manager = SERVER_DATA_MANAGER.read();
+                        let mut benchmark_lock = connection_context.benchmark.lock();
+                        let config = &data_manager.settings().extra.benchmark;
+                        // The codec points would be reported without being streamed
+                        let streamed_codec = data_manager.session().openvr_config.codec;
+                        let changes_codec = config
+                            .codecs
+                            .iter()
+                            .any(|&codec| codec as u8 != streamed_codec);
+                        if benchmark_lock.is_some() {
+                            warn!("A benchmark is already running");
+                        } else if changes_codec
+                            && !connection_context.encoder_restart_supported.value()
+                        {
+                            warn!("The benchmark cannot start: changing the codec restarts the encoder, which is not supported on Linux. Keep only the streamed codec in the benchmark settings");
+                        } else {
+                            info!("Starting the benchmark");
+                            if !config.measure_encoding_quality {
+                                warn!("The encoding quality is not measured by the benchmark");
+                            }
+                            if config.test_pattern {
+                                connection_context
+                                    .events_queue
+                                    .lock()
+                                    .push_back(ServerCoreEvent::BenchmarkTestPattern(true));
+                            }
+                            *benchmark_lock = Some(BenchmarkRun {
+                                sweep: BenchmarkSweep::new(config),
+                                point: None,
+                            });
+                        }
+                    }
                     ServerRequest::FirewallRules(action) => {
                         if alvr_server_io::firewall_rules(action).is_ok() {
                             info!("Setting firewall rules succeeded!");
@@ -49,6 +49,12 @@ pub struct OpenvrConfig {
     pub h264_profile: u32,
     pub refresh_rate: u32,
     pub use_10bit_encoder: bool,
+    // 1 for the P-only reference structure
+    pub temporal_layers: u32,
+    pub slices_count: u32,
+    // Per encoder block, in raster order. Empty if disabled
+    pub qp_delta_map: Vec<i8>,
+    pub qp_delta_map_columns: u32,
     pub use_full_range_encoding: bool,
     pub encoding_gamma: f32,
     pub enable_hdr: bool,
@@ -105,6 +111,8 @@ pub struct OpenvrConfig {
     pub nvenc_enable_weighted_prediction: bool,
     pub capture_frame_dir: String,
     pub amd_bitrate_corruption_fix: bool,
+    // Per frame PSNR and SSIM, for the benchmark
+    pub encoder_quality_feedback: bool,
 
     // these settings are not used on the C++ side, but we need them to correctly trigger a SteamVR
     // restart
@@ -21,6 +21,14 @@ pub enum FrameSize {
     },
 }
 
+#[derive(SettingsSchema, Serialize, Deserialize, Clone, PartialEq)]
+pub struct MaxEncodeResolutionConfig {
+    #[schema(gui(slider(min = 32, max = 0x1000, step = 32)))]
+    pub width: u32,
+    #[schema(gui(slider(min = 32, max = 0x1000, step = 32)))]
+    pub height: u32,
+}
+
 #[repr(u32)]
 #[derive(SettingsSchema, Serialize, Deserialize, Clone, PartialEq)]
 pub enum EncoderQualityPresetAmd {
@@ -87,6 +95,18 @@ pub enum EntropyCoding {
     Cabac = 0,
 }
 
+#[derive(SettingsSchema, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[schema(gui = "button_group")]
+pub enum ReferenceStructure {
+    #[schema(strings(display_name = "P-only"))]
+    POnly,
+    #[schema(strings(display_name = "Hierarchical P"))]
+    HierarchicalP {
+        #[schema(gui(slider(min = 2, max = 4)))]
+        temporal_layers: u8,
+    },
+}
+
 /// Except for preset, the value of these fields is not applied if == -1 (flag)
 #[derive(SettingsSchema, Serialize, Deserialize, Clone, PartialEq)]
 #[schema(collapsible)]
@@ -194,6 +214,18 @@ pub struct SoftwareEncodingConfig {
     pub thread_count: u32,
 }
 
+#[derive(SettingsSchema, Serialize, Deserialize, Clone, PartialEq)]
+pub struct QpDeltaMapConfig {
+    pub columns: u32,
+
+    pub rows: u32,
+
+    #[schema(strings(
+        help = "One QP delta per tile, row by row from the top left. Negative values increase the quality of the tile"
+    ))]
+    pub deltas: Vec<i32>,
+}
+
 #[derive(SettingsSchema, Serialize, Deserialize, Clone, PartialEq)]
 #[schema(collapsible)]
 pub struct EncoderConfig {
@@ -220,6 +252,27 @@ CABAC produces better compression but it's significantly slower and may lead to
     #[schema(flag = "steamvr-restart")]
     pub entropy_coding: EntropyCoding,
 
+    #[schema(strings(
+        help = r#"P-only: each frame references the previous one. A lost frame breaks the stream until the next IDR, but the recovery is the fastest.
+Hierarchical P: frames are split in temporal layers, the frames of the top layer are not referenced. Improves compression, and the loss of a frame of the upper layers is recovered without an IDR. Only supported with h264 on Windows hardware encoders"#
+    ))]
+    #[schema(flag = "steamvr-restart")]
+    pub reference_structure: ReferenceStructure,
+
+    #[schema(strings(
+        help = "Splits each frame in slices that are decoded independently. A lost slice corrupts only its region of the frame, and the decoder can work on the slices in parallel, at the cost of a slightly lower compression. Not supported with AV1"
+    ))]
+    #[schema(flag = "steamvr-restart")]
+    #[schema(gui(slider(min = 1, max = 16)))]
+    pub slices_count: u32,
+
+    #[schema(strings(
+        display_name = "QP delta map",
+        help = "Grid of tiles over each eye that shifts the quality of the encoding, for example toward a HUD at the center. Applied on top of foveated encoding, to the compressed eye. Supported only by NVENC on Windows"
+    ))]
+    #[schema(flag = "steamvr-restart")]
+    pub qp_delta_map: Switch<QpDeltaMapConfig>,
+
     #[schema(strings(
         display_name = "10 bit encoding",
         help = "Sets the encoder to use 10 bits per channel instead of 8. Does not work on Linux with Nvidia"
@@ -318,6 +371,92 @@ pub struct DecoderLatencyLimiter {
     pub latency_overstep_multiplier: f32,
 }
 
+#[derive(SettingsSchema, Serialize, Deserialize, Clone, PartialEq)]
+#[schema(collapsible)]
+pub struct CongestionController {
+    #[schema(strings(
+        help = "Controls how much the bitrate is reduced when the network queueing delay starts growing"
+    ))]
+    #[schema(flag = "real-time")]
+    #[schema(gui(slider(min = 0.5, max = 1.0, step = 0.01)))]
+    pub overuse_decrease_multiplier: f32,
+
+    #[schema(strings(help = "Bitrate growth per second while the network is not congested"))]
+    #[schema(flag = "real-time")]
+    #[schema(gui(slider(min = 1.0, max = 1.5, step = 0.01)))]
+    pub increase_multiplier_per_second: f32,
+}
+
+#[derive(SettingsSchema, Serialize, Deserialize, Clone, PartialEq)]
+#[schema(collapsible)]
+pub struct FreezeAndHoldConfig {
+    #[schema(strings(
+        help = "Time the throughput must stay below the minimum bitrate before the stream is held"
+    ))]
+    #[schema(flag = "real-time")]
+    #[schema(gui(slider(min = 100, max = 5000, step = 100)), suffix = "ms")]
+    pub trigger_time_ms: u64,
+
+    #[schema(strings(
+        help = "While holding, an IDR frame is sent with this interval to probe the bandwidth"
+    ))]
+    #[schema(flag = "real-time")]
+    #[schema(gui(slider(min = 200, max = 5000, step = 100)), suffix = "ms")]
+    pub probe_interval_ms: u64,
+
+    #[schema(strings(
+        help = "The stream resumes when the throughput of the probes reaches the minimum bitrate multiplied by this factor"
+    ))]
+    #[schema(flag = "real-time")]
+    #[schema(gui(slider(min = 1.0, max = 3.0, step = 0.1)))]
+    pub recovery_threshold_multiplier: f32,
+
+    #[schema(strings(
+        help = "Consecutive probes above the threshold needed to resume the stream"
+    ))]
+    #[schema(flag = "real-time")]
+    #[schema(gui(slider(min = 1, max = 10)))]
+    pub recovery_probes: u32,
+}
+
+#[derive(SettingsSchema, Serialize, Deserialize, Clone, PartialEq)]
+#[schema(collapsible)]
+pub struct JointResolutionConfig {
+    #[schema(strings(
+        help = "Encode resolution scales of the operating points, relative to the transcoding resolution"
+    ))]
+    #[schema(flag = "real-time")]
+    pub resolution_scales: Vec<f32>,
+
+    #[schema(strings(
+        help = "Encoded bits per pixel below which a lower resolution looks better than a starved higher one"
+    ))]
+    #[schema(flag = "real-time")]
+    #[schema(gui(slider(min = 0.01, max = 0.5, step = 0.01)))]
+    pub min_bits_per_pixel: f32,
+
+    #[schema(strings(
+        help = "A higher resolution is chosen only if the bandwidth exceeds its minimum bitrate by this fraction"
+    ))]
+    #[schema(flag = "real-time")]
+    #[schema(gui(slider(min = 0.0, max = 1.0, step = 0.05)))]
+    pub switch_margin: f32,
+
+    #[schema(strings(
+        help = "Time the bandwidth must support a higher resolution before switching to it"
+    ))]
+    #[schema(flag = "real-time")]
+    #[schema(gui(slider(min = 5.0, max = 120.0, step = 1.0)), suffix = "s")]
+    pub upgrade_hold_s: f32,
+
+    #[schema(strings(
+        help = "Time the bandwidth must stay below the minimum bitrate of the current resolution before switching to a lower one"
+    ))]
+    #[schema(flag = "real-time")]
+    #[schema(gui(slider(min = 1.0, max = 30.0, step = 0.5)), suffix = "s")]
+    pub downgrade_hold_s: f32,
+}
+
 #[derive(SettingsSchema, Serialize, Deserialize, Clone, PartialEq)]
 #[schema(gui = "button_group")]
 pub enum BitrateMode {
@@ -343,6 +482,12 @@ pub enum BitrateMode {
         #[schema(gui(slider(min = 1, max = 100, logarithmic)), suffix = "Mbps")]
         min_bitrate_mbps: Switch<u64>,
 
+        #[schema(strings(
+            help = "When the network cannot sustain even the minimum bitrate, stop streaming and let the headset reproject the last frame instead of showing corrupted frames. The stream resumes from an IDR frame when the bandwidth recovers. Requires the minimum bitrate"
+        ))]
+        #[schema(flag = "real-time")]
+        freeze_and_hold: Switch<FreezeAndHoldConfig>,
+
         #[schema(strings(display_name = "Maximum network latency"))]
         #[schema(flag = "real-time")]
         #[schema(gui(slider(min = 1, max = 50)), suffix = "ms")]
@@ -356,6 +501,18 @@ pub enum BitrateMode {
         ))]
         #[schema(flag = "real-time")]
         decoder_latency_limiter: Switch<DecoderLatencyLimiter>,
+
+        #[schema(strings(
+            help = "Estimate the available bandwidth from the growth of the network delay, to reduce the bitrate before packets start getting lost"
+        ))]
+        #[schema(flag = "real-time")]
+        congestion_controller: Switch<CongestionController>,
+
+        #[schema(strings(
+            help = "Choose the encode resolution together with the bitrate, from the estimated bandwidth. Each resolution switch restarts the encoder, so this is only supported on Windows"
+        ))]
+        #[schema(flag = "real-time")]
+        joint_resolution: Switch<JointResolutionConfig>,
     },
 }
 
@@ -370,6 +527,45 @@ pub struct BitrateAdaptiveFramerateConfig {
     pub framerate_reset_threshold_multiplier: f32,
 }
 
+#[derive(SettingsSchema, Serialize, Deserialize, Clone, PartialEq)]
+pub struct IdlePowerSavingConfig {
+    #[schema(strings(
+        help = "Time without motion of the headset and the controllers and without input before switching to the low power profile"
+    ))]
+    #[schema(flag = "real-time")]
+    #[schema(gui(slider(min = 5.0, max = 600.0, step = 5.0)), suffix = "s")]
+    pub idle_time_s: f32,
+
+    #[schema(strings(help = "Rotation speed below which a device is considered still"))]
+    #[schema(flag = "real-time")]
+    #[schema(gui(slider(min = 0.5, max = 30.0, step = 0.5)), suffix = "°/s")]
+    pub angular_threshold_deg_per_s: f32,
+
+    #[schema(strings(help = "Movement speed below which a device is considered still"))]
+    #[schema(flag = "real-time")]
+    #[schema(gui(slider(min = 0.005, max = 0.2, step = 0.005)), suffix = "m/s")]
+    pub linear_threshold_m_per_s: f32,
+
+    #[schema(strings(
+        help = "While idle, the motion must exceed the thresholds multiplied by this factor to restore the active profile, so the tracking noise cannot toggle the profiles. Any input restores it immediately"
+    ))]
+    #[schema(flag = "real-time")]
+    #[schema(gui(slider(min = 1.0, max = 5.0, step = 0.1)))]
+    pub wake_threshold_multiplier: f32,
+
+    #[schema(strings(help = "Maximum bitrate of the low power profile"))]
+    #[schema(flag = "real-time")]
+    #[schema(suffix = "Mbps")]
+    pub low_power_bitrate_mbps: u64,
+
+    #[schema(strings(
+        help = "The low power profile encodes one frame every this many. The headset reprojects the last frame in between"
+    ))]
+    #[schema(flag = "real-time")]
+    #[schema(gui(slider(min = 1, max = 6)))]
+    pub low_power_frame_divider: u32,
+}
+
 #[derive(SettingsSchema, Serialize, Deserialize, Clone, PartialEq)]
 #[schema(collapsible)]
 pub struct BitrateConfig {
@@ -382,6 +578,12 @@ pub struct BitrateConfig {
     #[schema(flag = "real-time")]
     pub adapt_to_framerate: Switch<BitrateAdaptiveFramerateConfig>,
 
+    #[schema(strings(
+        help = "Reduce the bitrate and the framerate while the user is idle, to save power and bandwidth"
+    ))]
+    #[schema(flag = "real-time")]
+    pub idle_power_saving: Switch<IdlePowerSavingConfig>,
+
     #[schema(strings(help = "Controls the smoothness during calculations"))]
     pub history_size: usize,
 
@@ -416,6 +618,221 @@ pub struct ClientsideFoveationConfig {
     pub vertical_offset_deg: f32,
 }
 
+#[derive(SettingsSchema, Serialize, Deserialize, Clone, PartialEq)]
+pub struct MonoVirtualScreenConfig {
+    #[schema(gui(slider(min = 0.5, max = 10.0, step = 0.1)), suffix = "m")]
+    pub distance_m: f32,
+
+    #[schema(gui(slider(min = 0.5, max = 10.0, step = 0.1)), suffix = "m")]
+    pub width_m: f32,
+
+    #[schema(strings(help = "0: flat screen, 1: cylindrical screen centered on the user"))]
+    #[schema(gui(slider(min = 0.0, max = 1.0, step = 0.01)))]
+    pub curvature: f32,
+}
+
+#[derive(SettingsSchema, Serialize, Deserialize, Clone, PartialEq)]
+pub struct LensCenterOffsetConfig {
+    #[schema(strings(
+        help = "Horizontal offset of the left lens center from the center of the eye image, as a fraction of its width. Positive values move it to the right"
+    ))]
+    #[schema(gui(slider(min = -0.25, max = 0.25, step = 0.005)))]
+    pub left_x: f32,
+
+    #[schema(strings(
+        help = "Vertical offset of the left lens center from the center of the eye image, as a fraction of its height, along the texture V axis"
+    ))]
+    #[schema(gui(slider(min = -0.25, max = 0.25, step = 0.005)))]
+    pub left_y: f32,
+
+    #[schema(strings(
+        help = "Horizontal offset of the right lens center from the center of the eye image, as a fraction of its width. Positive values move it to the right"
+    ))]
+    #[schema(gui(slider(min = -0.25, max = 0.25, step = 0.005)))]
+    pub right_x: f32,
+
+    #[schema(strings(
+        help = "Vertical offset of the right lens center from the center of the eye image, as a fraction of its height, along the texture V axis"
+    ))]
+    #[schema(gui(slider(min = -0.25, max = 0.25, step = 0.005)))]
+    pub right_y: f32,
+}
+
+#[derive(SettingsSchema, Serialize, Deserialize, Clone, PartialEq)]
+pub struct VignetteCorrectionConfig {
+    #[schema(strings(help = "Brightness gain added at the corners of the left eye image"))]
+    #[schema(gui(slider(min = 0.0, max = 1.0, step = 0.01)))]
+    pub left_strength: f32,
+
+    #[schema(strings(help = "Brightness gain added at the corners of the right eye image"))]
+    #[schema(gui(slider(min = 0.0, max = 1.0, step = 0.01)))]
+    pub right_strength: f32,
+
+    #[schema(strings(help = "Higher values restrict the correction to the edges of the image"))]
+    #[schema(gui(slider(min = 1.0, max = 4.0, step = 0.1)))]
+    pub falloff_exponent: f32,
+
+    #[schema(strings(
+        help = "Optical center of each lens, if it is not at the center of the eye image. The correction is centered on it"
+    ))]
+    pub lens_center_offset: Switch<LensCenterOffsetConfig>,
+}
+
+#[derive(SettingsSchema, Serialize, Deserialize, Clone, PartialEq)]
+pub struct ColorLutConfig {
+    #[schema(strings(help = "Path of the .cube file on the headset, readable by the client app"))]
+    pub file_path: String,
+
+    #[schema(strings(help = "Blend between the original colors (0) and the graded colors (1)"))]
+    #[schema(gui(slider(min = 0.0, max = 1.0, step = 0.01)))]
+    pub intensity: f32,
+}
+
+#[repr(u8)]
+#[derive(SettingsSchema, Serialize, Deserialize, Copy, Clone, PartialEq, Debug)]
+#[schema(gui = "button_group")]
+pub enum DitheringMode {
+    #[schema(strings(help = "Regular 8x8 Bayer pattern, which can be noticeable as a fine grid"))]
+    Ordered = 0,
+    #[schema(strings(
+        help = "Pattern without low frequencies, which is less visible. Generated from the random seed"
+    ))]
+    BlueNoise = 1,
+}
+
+#[derive(SettingsSchema, Serialize, Deserialize, Clone, PartialEq)]
+pub struct DitheringConfig {
+    pub mode: DitheringMode,
+
+    #[schema(strings(
+        help = "Amplitude of the dithering noise, in 8 bit steps. 1 is enough to remove the banding"
+    ))]
+    #[schema(gui(slider(min = 0.0, max = 2.0, step = 0.05)))]
+    pub strength: f32,
+}
+
+#[derive(SettingsSchema, Serialize, Deserialize, Clone, PartialEq)]
+pub struct ComfortVignetteConfig {
+    #[schema(strings(help = "Fraction of the view darkened at full intensity"))]
+    #[schema(gui(slider(min = 0.0, max = 0.8, step = 0.01)))]
+    pub strength: f32,
+
+    #[schema(strings(help = "Head rotation speed at which the vignette starts to appear"))]
+    #[schema(gui(slider(min = 0.0, max = 360.0, step = 5.0)), suffix = "°/s")]
+    pub angular_threshold_deg_per_s: f32,
+
+    #[schema(strings(help = "Head rotation speed at which the vignette reaches full intensity"))]
+    #[schema(gui(slider(min = 0.0, max = 720.0, step = 5.0)), suffix = "°/s")]
+    pub angular_full_deg_per_s: f32,
+
+    #[schema(strings(help = "Head movement speed at which the vignette starts to appear"))]
+    #[schema(gui(slider(min = 0.0, max = 5.0, step = 0.1)), suffix = "m/s")]
+    pub linear_threshold_m_per_s: f32,
+
+    #[schema(strings(help = "Head movement speed at which the vignette reaches full intensity"))]
+    #[schema(gui(slider(min = 0.0, max = 10.0, step = 0.1)), suffix = "m/s")]
+    pub linear_full_m_per_s: f32,
+
+    #[schema(strings(help = "Higher values make the vignette fade in and out more slowly"))]
+    #[schema(gui(slider(min = 0.05, max = 1.0, step = 0.05)), suffix = "s")]
+    pub transition_time_s: f32,
+}
+
+#[repr(u8)]
+#[derive(SettingsSchema, Serialize, Deserialize, Copy, Clone, PartialEq, Debug)]
+#[schema(gui = "button_group")]
+pub enum ReticleShape {
+    Crosshair = 0,
+    Dot = 1,
+}
+
+#[derive(SettingsSchema, Serialize, Deserialize, Copy, Clone, PartialEq, Debug)]
+pub enum ReticlePosition {
+    #[schema(strings(
+        help = "Direction relative to the head, far away. Positive angles are to the right and up"
+    ))]
+    Screen {
+        #[schema(gui(slider(min = -30.0, max = 30.0, step = 0.5)), suffix = "°")]
+        horizontal_deg: f32,
+        #[schema(gui(slider(min = -30.0, max = 30.0, step = 0.5)), suffix = "°")]
+        vertical_deg: f32,
+    },
+    #[schema(strings(help = "Point fixed in the play space"))]
+    World {
+        #[schema(suffix = "m")]
+        x: f32,
+        #[schema(suffix = "m")]
+        y: f32,
+        #[schema(suffix = "m")]
+        z: f32,
+    },
+}
+
+#[derive(SettingsSchema, Serialize, Deserialize, Clone, PartialEq)]
+pub struct ReticleConfig {
+    pub shape: ReticleShape,
+
+    pub position: ReticlePosition,
+
+    #[schema(strings(help = "Angular diameter of the reticle"))]
+    #[schema(gui(slider(min = 0.1, max = 10.0, step = 0.1)), suffix = "°")]
+    pub size_deg: f32,
+
+    #[schema(strings(help = "Width of the crosshair lines"))]
+    #[schema(gui(slider(min = 0.01, max = 1.0, step = 0.01)), suffix = "°")]
+    pub thickness_deg: f32,
+
+    #[schema(gui(slider(min = 0.0, max = 1.0, step = 0.01)))]
+    pub red: f32,
+
+    #[schema(gui(slider(min = 0.0, max = 1.0, step = 0.01)))]
+    pub green: f32,
+
+    #[schema(gui(slider(min = 0.0, max = 1.0, step = 0.01)))]
+    pub blue: f32,
+
+    #[schema(gui(slider(min = 0.0, max = 1.0, step = 0.01)))]
+    pub opacity: f32,
+}
+
+#[derive(SettingsSchema, Serialize, Deserialize, Copy, Clone, PartialEq, Debug)]
+#[schema(gui = "button_group")]
+pub enum StampCorner {
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    BottomRight,
+}
+
+#[derive(SettingsSchema, Serialize, Deserialize, Clone, PartialEq)]
+pub struct LatencyStampConfig {
+    pub corner: StampCorner,
+
+    #[schema(strings(
+        help = "Distance from the corner, as a fraction of the eye image. The corners of the image are outside of the lens view"
+    ))]
+    #[schema(gui(slider(min = 0.0, max = 0.4, step = 0.01)))]
+    pub margin: f32,
+
+    #[schema(strings(
+        help = "Side of each bit of the stamp. Increase it if the camera cannot read the stamp"
+    ))]
+    #[schema(gui(slider(min = 2, max = 32)), suffix = "px")]
+    pub cell_size_px: u32,
+}
+
+#[repr(u8)]
+#[derive(SettingsSchema, Serialize, Deserialize, Copy, Clone, PartialEq, Debug)]
+#[schema(gui = "button_group")]
+pub enum TestPattern {
+    ColorBars = 0,
+    #[schema(strings(help = "Vertical bar moving horizontally, useful to measure latency"))]
+    ScrollBar = 1,
+    Checkerboard = 2,
+    #[schema(strings(help = "Frame index encoded in binary, most significant bit on the left"))]
+    FrameCounter = 3,
+}
+
 #[derive(SettingsSchema, Serialize, Deserialize, Clone, PartialEq)]
 pub struct FoveatedEncodingConfig {
     #[schema(strings(help = "Force enable on smartphone clients"))]
@@ -423,32 +840,32 @@ pub struct FoveatedEncodingConfig {
 
     #[schema(strings(display_name = "Center region width"))]
     #[schema(gui(slider(min = 0.0, max = 1.0, step = 0.01)))]
-    #[schema(flag = "steamvr-restart")]
+    #[schema(flag = "real-time")]
     pub center_size_x: f32,
 
     #[schema(strings(display_name = "Center region height"))]
     #[schema(gui(slider(min = 0.0, max = 1.0, step = 0.01)))]
-    #[schema(flag = "steamvr-restart")]
+    #[schema(flag = "real-time")]
     pub center_size_y: f32,
 
     #[schema(strings(display_name = "Center shift X"))]
     #[schema(gui(slider(min = -1.0, max = 1.0, step = 0.01)))]
-    #[schema(flag = "steamvr-restart")]
+    #[schema(flag = "real-time")]
     pub center_shift_x: f32,
 
     #[schema(strings(display_name = "Center shift Y"))]
     #[schema(gui(slider(min = -1.0, max = 1.0, step = 0.01)))]
-    #[schema(flag = "steamvr-restart")]
+    #[schema(flag = "real-time")]
     pub center_shift_y: f32,
 
     #[schema(strings(display_name = "Horizontal edge ratio"))]
     #[schema(gui(slider(min = 1.0, max = 10.0, step = 1.0)))]
-    #[schema(flag = "steamvr-restart")]
+    #[schema(flag = "real-time")]
     pub edge_ratio_x: f32,
 
     #[schema(strings(display_name = "Vertical edge ratio"))]
     #[schema(gui(slider(min = 1.0, max = 10.0, step = 1.0)))]
-    #[schema(flag = "steamvr-restart")]
+    #[schema(flag = "real-time")]
     pub edge_ratio_y: f32,
 }
 
@@ -511,7 +928,10 @@ pub struct VideoConfig {
     #[schema(flag = "steamvr-restart")]
     pub preferred_codec: CodecType,
 
-    #[schema(flag = "steamvr-restart")]
+    #[schema(strings(
+        help = "Changes are applied during the stream on Windows with supported clients. Otherwise they are applied on the next connection"
+    ))]
+    #[schema(flag = "real-time")]
     pub foveated_encoding: Switch<FoveatedEncodingConfig>,
 
     #[schema(flag = "steamvr-restart")]
@@ -542,6 +962,20 @@ pub struct VideoConfig {
     ))]
     pub force_software_decoder: bool,
 
+    #[schema(strings(
+        display_name = "Parallel decoder sessions",
+        help = r#"Number of hardware decoder sessions. Each GOP (from an IDR to the next one) is decoded by the next session, so frames are decoded in parallel only with frequent IDRs.
+Frames are not dropped to limit the buffering when using multiple sessions. Applied on the next connection."#
+    ))]
+    #[schema(gui(slider(min = 1, max = 4)))]
+    pub parallel_decoder_sessions: u32,
+
+    #[schema(strings(
+        display_name = "Max decode queue frames",
+        help = "Frames submitted to the decoder and not decoded yet. When the queue is full, frames are dropped until the next IDR, which is requested from the server."
+    ))]
+    pub max_decode_queue_frames: Switch<u32>,
+
     pub mediacodec_extra_options: Vec<(String, MediacodecDataType)>,
 
     #[schema(strings(
@@ -550,6 +984,12 @@ pub struct VideoConfig {
     #[schema(flag = "steamvr-restart")]
     pub transcoding_view_resolution: FrameSize,
 
+    #[schema(strings(
+        help = "Cap the resolution used for encoding and decoding, keeping the aspect ratio. Relative to a single eye view. The frames are downscaled before encoding and upscaled by the headset to the transcoding resolution after decoding"
+    ))]
+    #[schema(flag = "steamvr-restart")]
+    pub max_encode_view_resolution: Switch<MaxEncodeResolutionConfig>,
+
     #[schema(strings(
         help = "This is the resolution that SteamVR will use as default for the game rendering. Relative to a single eye view."
     ))]
@@ -566,6 +1006,51 @@ pub struct VideoConfig {
     pub adapter_index: u32,
 
     pub clientside_foveation: Switch<ClientsideFoveationConfig>,
+
+    #[schema(strings(
+        help = "Show the left view of the stream on a virtual screen in front of the user, in both eyes. Use this for flat 2D content"
+    ))]
+    pub mono_virtual_screen: Switch<MonoVirtualScreenConfig>,
+
+    #[schema(strings(
+        help = "Brighten the edges of each eye image to compensate the lens falloff. Applied after color correction"
+    ))]
+    pub vignette_correction: Switch<VignetteCorrectionConfig>,
+
+    #[schema(strings(
+        help = "Apply a 3D LUT (.cube file) for color grading, as the final color transform"
+    ))]
+    pub color_lut: Switch<ColorLutConfig>,
+
+    #[schema(strings(
+        help = "Darken the periphery of the view during fast head motion, to reduce motion sickness"
+    ))]
+    pub comfort_vignette: Switch<ComfortVignetteConfig>,
+
+    #[schema(strings(
+        help = "Add noise before the stream is quantized to 8 bit for the headset, to break up the banding of gradients. The intermediate images use a higher precision while enabled. Not applied with HDR or 10 bit"
+    ))]
+    pub dithering: Switch<DitheringConfig>,
+
+    #[schema(strings(
+        help = "Draw a crosshair or a dot on top of the stream in both eyes, for aiming calibration"
+    ))]
+    pub reticle: Switch<ReticleConfig>,
+
+    #[schema(strings(
+        help = "Measurement mode: draw the timestamp of each frame as a binary stamp in a corner of both eyes, to measure the latency with an external camera"
+    ))]
+    pub latency_stamp: Switch<LatencyStampConfig>,
+
+    #[schema(strings(
+        help = "Replace the stream with a test pattern generated on the headset, for diagnostics"
+    ))]
+    pub test_pattern: Switch<TestPattern>,
+
+    #[schema(strings(
+        help = "Synthesize intermediate frames when the stream framerate is lower than the headset refresh rate. Adds up to half a frame of latency"
+    ))]
+    pub motion_smoothing: bool,
 }
 
 #[derive(SettingsSchema, Serialize, Deserialize, Clone, Copy)]
@@ -598,11 +1083,26 @@ pub struct AudioBufferingConfig {
     pub batch_ms: u64,
 }
 
+#[derive(SettingsSchema, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum GameAudioMode {
+    #[schema(strings(display_name = "Pre-spatialized stereo"))]
+    PreSpatializedStereo,
+    #[schema(strings(display_name = "Multichannel passthrough"))]
+    MultichannelPassthrough,
+}
+
 #[derive(SettingsSchema, Serialize, Deserialize, Clone)]
 #[schema(collapsible)]
 pub struct GameAudioConfig {
     pub device: Option<CustomAudioDeviceConfig>,
 
+    #[schema(strings(
+        help = r#"Pre-spatialized stereo: the audio is mixed down to stereo on the PC, use this for binaural audio.
+Multichannel passthrough: all channels of the audio device are streamed and mixed by the headset."#
+    ))]
+    #[schema(flag = "real-time")]
+    pub mode: GameAudioMode,
+
     #[schema(strings(display_name = "Mute desktop audio when streaming"))]
     pub mute_when_streaming: bool,
 
@@ -961,6 +1461,64 @@ pub enum RotationRecenteringMode {
     Tilted,
 }
 
+#[derive(SettingsSchema, Serialize, Deserialize, Clone, PartialEq)]
+pub struct EyeCalibration {
+    #[schema(flag = "real-time")]
+    #[schema(gui(slider(min = -2.0, max = 2.0, step = 0.01)), suffix = "°")]
+    pub yaw_deg: f32,
+    #[schema(flag = "real-time")]
+    #[schema(gui(slider(min = -2.0, max = 2.0, step = 0.01)), suffix = "°")]
+    pub pitch_deg: f32,
+    #[schema(flag = "real-time")]
+    #[schema(gui(slider(min = -2.0, max = 2.0, step = 0.01)), suffix = "°")]
+    pub roll_deg: f32,
+
+    #[schema(strings(display_name = "Offset X"))]
+    #[schema(flag = "real-time")]
+    #[schema(gui(slider(min = -5.0, max = 5.0, step = 0.1)), suffix = "mm")]
+    pub offset_x_mm: f32,
+    #[schema(strings(display_name = "Offset Y"))]
+    #[schema(flag = "real-time")]
+    #[schema(gui(slider(min = -5.0, max = 5.0, step = 0.1)), suffix = "mm")]
+    pub offset_y_mm: f32,
+    #[schema(strings(display_name = "Offset Z"))]
+    #[schema(flag = "real-time")]
+    #[schema(gui(slider(min = -5.0, max = 5.0, step = 0.1)), suffix = "mm")]
+    pub offset_z_mm: f32,
+}
+
+#[derive(SettingsSchema, Serialize, Deserialize, Clone, PartialEq)]
+#[schema(collapsible)]
+pub struct EyeCalibrationConfig {
+    #[schema(flag = "real-time")]
+    pub left_eye: EyeCalibration,
+    #[schema(flag = "real-time")]
+    pub right_eye: EyeCalibration,
+}
+
+#[derive(SettingsSchema, Serialize, Deserialize, Clone, Copy, PartialEq)]
+pub enum CoordinateConvention {
+    #[schema(strings(display_name = "Z-up right-handed"))]
+    ZUpRightHanded,
+    #[schema(strings(display_name = "Y-up left-handed"))]
+    YUpLeftHanded,
+    #[schema(collapsible)]
+    Custom {
+        #[schema(strings(
+            help = "Direction of the X axis of the source in the Y-up right-handed space"
+        ))]
+        x_axis: [f32; 3],
+        #[schema(strings(
+            help = "Direction of the Y axis of the source in the Y-up right-handed space"
+        ))]
+        y_axis: [f32; 3],
+        #[schema(strings(
+            help = "Direction of the Z axis of the source in the Y-up right-handed space"
+        ))]
+        z_axis: [f32; 3],
+    },
+}
+
 #[derive(SettingsSchema, Serialize, Deserialize, Clone)]
 pub struct HeadsetConfig {
     #[schema(strings(
@@ -979,6 +1537,15 @@ Tilted: the world gets tilted when long pressing the oculus button. This is usef
     #[schema(flag = "real-time")]
     pub rotation_recentering_mode: RotationRecenteringMode,
 
+    #[schema(strings(
+        help = r#"Converts the poses reported by the client from another coordinate convention, before recentering and the controller offsets. SteamVR uses Y up, -Z forward, right-handed.
+Z-up right-handed: X right, Y forward, Z up.
+Y-up left-handed: X right, Y up, Z forward.
+Custom: the axes must be orthonormal. A mirrored basis changes the handedness"#
+    ))]
+    #[schema(flag = "real-time")]
+    pub input_coordinate_convention: Switch<CoordinateConvention>,
+
     #[schema(flag = "steamvr-restart")]
     pub controllers: Switch<ControllersConfig>,
 
@@ -998,6 +1565,11 @@ Tilted: the world gets tilted when long pressing the oculus button. This is usef
 
     #[schema(flag = "steamvr-restart")]
     pub body_tracking: Switch<BodyTrackingConfig>,
+    #[schema(strings(
+        help = "Small per-eye rotation and offset applied to the projection of each eye, to correct misaligned displays"
+    ))]
+    #[schema(flag = "real-time")]
+    pub eye_calibration: Switch<EyeCalibrationConfig>,
 }
 
 #[derive(SettingsSchema, Serialize, Deserialize, Clone)]
@@ -1017,6 +1589,23 @@ pub struct DiscoveryConfig {
     pub auto_trust_clients: bool,
 }
 
+#[derive(SettingsSchema, Serialize, Deserialize, Clone)]
+pub struct ConnectRetryConfig {
+    #[schema(strings(
+        help = "Delay before retrying after the first failed connection attempt to a client. It doubles after each failed attempt"
+    ))]
+    #[schema(gui(slider(min = 100, max = 5000, step = 100)), suffix = "ms")]
+    pub initial_backoff_ms: u64,
+
+    #[schema(gui(slider(min = 1000, max = 60000, step = 1000)), suffix = "ms")]
+    pub max_backoff_ms: u64,
+
+    #[schema(strings(
+        help = "Stop trying to connect to a client after this many failed attempts, until it is found with a different IP"
+    ))]
+    pub max_attempts: Switch<u32>,
+}
+
 #[derive(SettingsSchema, Serialize, Deserialize, Clone)]
 pub enum SocketBufferSize {
     Default,
@@ -1024,6 +1613,29 @@ pub enum SocketBufferSize {
     Custom(#[schema(suffix = "B")] u32),
 }
 
+#[derive(SettingsSchema, Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[schema(gui = "button_group")]
+pub enum VideoLossRecovery {
+    Disabled,
+
+    #[schema(strings(display_name = "NACK"))]
+    #[schema(collapsible)]
+    Nack {
+        #[schema(strings(
+            help = "Lost fragments are not retransmitted after this many round trip times, since they would arrive too late to be useful"
+        ))]
+        #[schema(gui(slider(min = 1.0, max = 10.0, step = 0.5)))]
+        max_age_rtts: f32,
+
+        #[schema(strings(
+            display_name = "Maximum retransmission age",
+            help = "Limit to the age of retransmitted fragments. The client waits at most this long for a retransmission before skipping the frame"
+        ))]
+        #[schema(gui(slider(min = 5, max = 100)), suffix = "ms")]
+        max_age_ms: u64,
+    },
+}
+
 #[derive(SettingsSchema, Serialize, Deserialize, Clone)]
 pub struct ConnectionConfig {
     #[schema(strings(
@@ -1034,6 +1646,12 @@ TCP: Slower than UDP, but more stable. Pick this if you experience video or audi
 
     pub client_discovery: Switch<DiscoveryConfig>,
 
+    #[schema(strings(help = "Timeout of each connection attempt to a client"))]
+    #[schema(gui(slider(min = 100, max = 10000, step = 100)), suffix = "ms")]
+    pub connect_timeout_ms: u64,
+
+    pub connect_retry: ConnectRetryConfig,
+
     #[schema(strings(
         help = "This script will be ran when the headset connects. Env var ACTION will be set to `connect`."
     ))]
@@ -1051,10 +1669,38 @@ For now works only on Windows+Nvidia"#
     ))]
     pub avoid_video_glitching: bool,
 
+    #[schema(strings(
+        help = "Maximum size of each fragment of a stream packet. Lower this if the network path has a small MTU (VPNs, some Wi-Fi setups)"
+    ))]
     #[schema(gui(slider(min = 1024, max = 65507, logarithmic)), suffix = "B")]
     pub packet_size: i32,
 
+    #[schema(strings(
+        help = "Probe the largest packet size that can reach the client without IP fragmentation, and use it if smaller than the packet size. Works only with UDP"
+    ))]
+    pub path_mtu_probing: bool,
+
+    #[schema(strings(
+        help = "Spread the fragments of each video frame over the frame interval according to the target bitrate, instead of sending them in a single burst. Can reduce packet loss on routers with small buffers"
+    ))]
+    pub packet_pacing: bool,
+
+    #[schema(strings(help = r#"Recovery of the video fragments lost by the network.
+NACK: the client requests the retransmission of the lost fragments. Cheap on low latency networks, since only the lost fragments are sent again. Works only with UDP"#))]
+    pub video_loss_recovery: VideoLossRecovery,
+
+    #[schema(strings(
+        help = "Stop encoding and sending video while the headset is removed, keeping the connection alive. Requires a headset with a proximity sensor"
+    ))]
+    pub pause_stream_on_headset_removal: bool,
+
     pub stream_port: u16,
+
+    #[schema(strings(
+        help = "Send audio through a separate socket on this port, so it is not queued behind the video fragments. The port must be open in the firewall of both devices. Older clients use the stream port"
+    ))]
+    pub separate_audio_port: Switch<u16>,
+
     pub web_server_port: u16,
     pub osc_local_port: u16,
 
@@ -1177,17 +1823,72 @@ pub struct RollingVideoFilesConfig {
     pub duration_s: u64,
 }
 
+#[derive(SettingsSchema, Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[schema(gui = "button_group")]
+pub enum RecordingFormat {
+    #[schema(strings(display_name = "Matroska (.mkv)"))]
+    Matroska,
+    #[schema(strings(display_name = "Raw video"))]
+    RawVideo,
+}
+
 #[derive(SettingsSchema, Serialize, Deserialize, Clone)]
 pub struct CaptureConfig {
     #[schema(strings(display_name = "Start video recording at client connection"))]
     pub startup_video_recording: bool,
 
+    #[schema(strings(
+        help = "Matroska files contain the video and the game audio, and stay playable if the recording is interrupted. Raw video files contain only the video stream"
+    ))]
+    pub recording_format: RecordingFormat,
+
     pub rolling_video_files: Switch<RollingVideoFilesConfig>,
 
     #[schema(flag = "steamvr-restart")]
     pub capture_frame_dir: String,
 }
 
+#[derive(SettingsSchema, Serialize, Deserialize, Clone)]
+pub struct BenchmarkConfig {
+    #[schema(strings(help = "Constant bitrates of the sweep, in Mbps"))]
+    pub bitrates_mbps: Vec<u64>,
+
+    pub codecs: Vec<CodecType>,
+
+    #[schema(strings(
+        help = "Streams a scrolling pattern instead of the game, so that every point encodes the same content"
+    ))]
+    pub test_pattern: bool,
+
+    #[schema(strings(
+        help = "The encoder computes the PSNR and the SSIM of every frame. Supported by the AMD and the software encoders"
+    ))]
+    #[schema(flag = "steamvr-restart")]
+    pub measure_encoding_quality: bool,
+
+    #[schema(strings(
+        help = "Time for the encoder and the statistics to settle before measuring"
+    ))]
+    #[schema(gui(slider(min = 0.0, max = 10.0, step = 0.5)), suffix = "s")]
+    pub warmup_s: f32,
+
+    #[schema(gui(slider(min = 1.0, max = 60.0, step = 1.0)), suffix = "s")]
+    pub segment_duration_s: f32,
+}
+
+#[derive(SettingsSchema, Serialize, Deserialize, Clone)]
+pub struct NetworkSimulationConfig {
+    #[schema(strings(help = "Probability that each datagram sent by the server is dropped"))]
+    #[schema(gui(slider(min = 0.0, max = 20.0, step = 0.5)), suffix = "%")]
+    pub packet_loss_percent: f32,
+
+    #[schema(strings(
+        help = "Each packet sent by the server is delayed by a random time up to this value. The packets sent after it are delayed too"
+    ))]
+    #[schema(gui(slider(min = 0, max = 50)), suffix = "ms")]
+    pub max_jitter_ms: u64,
+}
+
 #[derive(SettingsSchema, Serialize, Deserialize, Clone)]
 pub struct Patches {
     #[schema(strings(
@@ -1206,6 +1907,19 @@ pub struct Patches {
 pub struct ExtraConfig {
     pub steamvr_launcher: SteamvrLauncher,
     pub capture: CaptureConfig,
+    #[schema(strings(
+        help = "Parameter grid of the benchmark started from the debug tab. Each point streams for a segment, the results are logged as a table. Changing the codec restarts the encoder, which is only supported on Windows"
+    ))]
+    pub benchmark: BenchmarkConfig,
+    #[schema(strings(
+        display_name = "Random seed",
+        help = "Seed of the random generators of the stream, to reproduce a run exactly. Otherwise a new seed is chosen at each connection. The seed in use is printed in the log. The ALVR_RNG_SEED environment variable takes precedence"
+    ))]
+    pub rng_seed: Switch<u64>,
+    #[schema(strings(
+        help = "Degrades the stream sent by the server, to test the loss recovery. Reproducible with the random seed"
+    ))]
+    pub network_simulation: Switch<NetworkSimulationConfig>,
     pub logging: LoggingConfig,
     pub patches: Patches,
     pub open_setup_wizard: bool,
@@ -1251,6 +1965,13 @@ pub fn session_settings_default() -> SettingsDefault {
         video: VideoConfigDefault {
             adapter_index: 0,
             transcoding_view_resolution: view_resolution.clone(),
+            max_encode_view_resolution: SwitchDefault {
+                enabled: false,
+                content: MaxEncodeResolutionConfigDefault {
+                    width: 2048,
+                    height: 2048,
+                },
+            },
             emulated_headset_view_resolution: view_resolution,
             preferred_fps: 72.,
             max_buffering_frames: 2.0,
@@ -1271,6 +1992,16 @@ pub fn session_settings_default() -> SettingsDefault {
                             enabled: false,
                             content: 5,
                         },
+                        freeze_and_hold: SwitchDefault {
+                            enabled: false,
+                            content: FreezeAndHoldConfigDefault {
+                                gui_collapsed: true,
+                                trigger_time_ms: 500,
+                                probe_interval_ms: 1000,
+                                recovery_threshold_multiplier: 1.5,
+                                recovery_probes: 2,
+                            },
+                        },
                         max_network_latency_ms: SwitchDefault {
                             enabled: false,
                             content: 8,
@@ -1290,6 +2021,29 @@ pub fn session_settings_default() -> SettingsDefault {
                                 latency_overstep_multiplier: 0.99,
                             },
                         },
+                        congestion_controller: SwitchDefault {
+                            enabled: false,
+                            content: CongestionControllerDefault {
+                                gui_collapsed: false,
+                                overuse_decrease_multiplier: 0.85,
+                                increase_multiplier_per_second: 1.08,
+                            },
+                        },
+                        joint_resolution: SwitchDefault {
+                            enabled: false,
+                            content: JointResolutionConfigDefault {
+                                gui_collapsed: true,
+                                resolution_scales: VectorDefault {
+                                    gui_collapsed: true,
+                                    element: 1.0,
+                                    content: vec![0.5, 0.75, 1.0],
+                                },
+                                min_bits_per_pixel: 0.08,
+                                switch_margin: 0.25,
+                                upgrade_hold_s: 30.0,
+                                downgrade_hold_s: 3.0,
+                            },
+                        },
                     },
                     variant: BitrateModeDefaultVariant::ConstantMbps,
                 },
@@ -1299,6 +2053,17 @@ pub fn session_settings_default() -> SettingsDefault {
                         framerate_reset_threshold_multiplier: 2.0,
                     },
                 },
+                idle_power_saving: SwitchDefault {
+                    enabled: false,
+                    content: IdlePowerSavingConfigDefault {
+                        idle_time_s: 60.0,
+                        angular_threshold_deg_per_s: 5.0,
+                        linear_threshold_m_per_s: 0.03,
+                        wake_threshold_multiplier: 2.0,
+                        low_power_bitrate_mbps: 10,
+                        low_power_frame_divider: 2,
+                    },
+                },
                 history_size: 256,
                 image_corruption_fix: false,
             },
@@ -1317,6 +2082,23 @@ pub fn session_settings_default() -> SettingsDefault {
                 entropy_coding: EntropyCodingDefault {
                     variant: EntropyCodingDefaultVariant::Cavlc,
                 },
+                reference_structure: ReferenceStructureDefault {
+                    variant: ReferenceStructureDefaultVariant::POnly,
+                    HierarchicalP: ReferenceStructureHierarchicalPDefault { temporal_layers: 2 },
+                },
+                slices_count: 1,
+                qp_delta_map: SwitchDefault {
+                    enabled: false,
+                    content: QpDeltaMapConfigDefault {
+                        columns: 3,
+                        rows: 3,
+                        deltas: VectorDefault {
+                            gui_collapsed: true,
+                            element: 0,
+                            content: vec![2, 2, 2, 2, -4, 2, 2, 2, 2],
+                        },
+                    },
+                },
                 use_10bit: false,
                 use_full_range: true,
                 encoding_gamma: 1.0,
@@ -1427,7 +2209,107 @@ pub fn session_settings_default() -> SettingsDefault {
                     vertical_offset_deg: 0.0,
                 },
             },
+            mono_virtual_screen: SwitchDefault {
+                enabled: false,
+                content: MonoVirtualScreenConfigDefault {
+                    distance_m: 2.0,
+                    width_m: 2.4,
+                    curvature: 0.0,
+                },
+            },
+            vignette_correction: SwitchDefault {
+                enabled: false,
+                content: VignetteCorrectionConfigDefault {
+                    left_strength: 0.3,
+                    right_strength: 0.3,
+                    falloff_exponent: 2.0,
+                    lens_center_offset: SwitchDefault {
+                        enabled: false,
+                        content: LensCenterOffsetConfigDefault {
+                            left_x: 0.0,
+                            left_y: 0.0,
+                            right_x: 0.0,
+                            right_y: 0.0,
+                        },
+                    },
+                },
+            },
+            color_lut: SwitchDefault {
+                enabled: false,
+                content: ColorLutConfigDefault {
+                    file_path: "".into(),
+                    intensity: 1.0,
+                },
+            },
+            comfort_vignette: SwitchDefault {
+                enabled: false,
+                content: ComfortVignetteConfigDefault {
+                    strength: 0.5,
+                    angular_threshold_deg_per_s: 60.0,
+                    angular_full_deg_per_s: 180.0,
+                    linear_threshold_m_per_s: 1.0,
+                    linear_full_m_per_s: 3.0,
+                    transition_time_s: 0.2,
+                },
+            },
+            dithering: SwitchDefault {
+                enabled: false,
+                content: DitheringConfigDefault {
+                    mode: DitheringModeDefault {
+                        variant: DitheringModeDefaultVariant::BlueNoise,
+                    },
+                    strength: 1.0,
+                },
+            },
+            reticle: SwitchDefault {
+                enabled: false,
+                content: ReticleConfigDefault {
+                    shape: ReticleShapeDefault {
+                        variant: ReticleShapeDefaultVariant::Crosshair,
+                    },
+                    position: ReticlePositionDefault {
+                        variant: ReticlePositionDefaultVariant::Screen,
+                        Screen: ReticlePositionScreenDefault {
+                            horizontal_deg: 0.0,
+                            vertical_deg: 0.0,
+                        },
+                        World: ReticlePositionWorldDefault {
+                            x: 0.0,
+                            y: 1.5,
+                            z: -2.0,
+                        },
+                    },
+                    size_deg: 2.0,
+                    thickness_deg: 0.1,
+                    red: 0.0,
+                    green: 1.0,
+                    blue: 0.0,
+                    opacity: 0.8,
+                },
+            },
+            latency_stamp: SwitchDefault {
+                enabled: false,
+                content: LatencyStampConfigDefault {
+                    corner: StampCornerDefault {
+                        variant: StampCornerDefaultVariant::TopLeft,
+                    },
+                    margin: 0.25,
+                    cell_size_px: 8,
+                },
+            },
+            test_pattern: SwitchDefault {
+                enabled: false,
+                content: TestPatternDefault {
+                    variant: TestPatternDefaultVariant::ColorBars,
+                },
+            },
+            motion_smoothing: false,
             force_software_decoder: false,
+            parallel_decoder_sessions: 1,
+            max_decode_queue_frames: SwitchDefault {
+                enabled: false,
+                content: 4,
+            },
             color_correction: SwitchDefault {
                 enabled: true,
                 content: ColorCorrectionConfigDefault {
@@ -1451,6 +2333,9 @@ pub fn session_settings_default() -> SettingsDefault {
                         set: false,
                         content: default_custom_audio_device.clone(),
                     },
+                    mode: GameAudioModeDefault {
+                        variant: GameAudioModeDefaultVariant::PreSpatializedStereo,
+                    },
                     mute_when_streaming: true,
                     buffering: AudioBufferingConfigDefault {
                         gui_collapsed: true,
@@ -1525,6 +2410,28 @@ pub fn session_settings_default() -> SettingsDefault {
                     tracked: true,
                 },
             },
+            eye_calibration: SwitchDefault {
+                enabled: false,
+                content: EyeCalibrationConfigDefault {
+                    gui_collapsed: true,
+                    left_eye: EyeCalibrationDefault {
+                        yaw_deg: 0.0,
+                        pitch_deg: 0.0,
+                        roll_deg: 0.0,
+                        offset_x_mm: 0.0,
+                        offset_y_mm: 0.0,
+                        offset_z_mm: 0.0,
+                    },
+                    right_eye: EyeCalibrationDefault {
+                        yaw_deg: 0.0,
+                        pitch_deg: 0.0,
+                        roll_deg: 0.0,
+                        offset_x_mm: 0.0,
+                        offset_y_mm: 0.0,
+                        offset_z_mm: 0.0,
+                    },
+                },
+            },
             controllers: SwitchDefault {
                 enabled: true,
                 content: ControllersConfigDefault {
@@ -1642,6 +2549,27 @@ pub fn session_settings_default() -> SettingsDefault {
             rotation_recentering_mode: RotationRecenteringModeDefault {
                 variant: RotationRecenteringModeDefaultVariant::Yaw,
             },
+            input_coordinate_convention: SwitchDefault {
+                enabled: false,
+                content: CoordinateConventionDefault {
+                    variant: CoordinateConventionDefaultVariant::ZUpRightHanded,
+                    Custom: CoordinateConventionCustomDefault {
+                        gui_collapsed: true,
+                        x_axis: ArrayDefault {
+                            gui_collapsed: false,
+                            content: [1.0, 0.0, 0.0],
+                        },
+                        y_axis: ArrayDefault {
+                            gui_collapsed: false,
+                            content: [0.0, 1.0, 0.0],
+                        },
+                        z_axis: ArrayDefault {
+                            gui_collapsed: false,
+                            content: [0.0, 0.0, 1.0],
+                        },
+                    },
+                },
+            },
         },
         connection: ConnectionConfigDefault {
             stream_protocol: SocketProtocolDefault {
@@ -1653,8 +2581,21 @@ pub fn session_settings_default() -> SettingsDefault {
                     auto_trust_clients: cfg!(debug_assertions),
                 },
             },
+            connect_timeout_ms: 1000,
+            connect_retry: ConnectRetryConfigDefault {
+                initial_backoff_ms: 500,
+                max_backoff_ms: 8000,
+                max_attempts: SwitchDefault {
+                    enabled: false,
+                    content: 10,
+                },
+            },
             web_server_port: 8082,
             stream_port: 9944,
+            separate_audio_port: SwitchDefault {
+                enabled: false,
+                content: 9945,
+            },
             osc_local_port: 9942,
             dscp: OptionalDefault {
                 set: false,
@@ -1679,6 +2620,17 @@ pub fn session_settings_default() -> SettingsDefault {
             on_connect_script: "".into(),
             on_disconnect_script: "".into(),
             packet_size: 1400,
+            path_mtu_probing: false,
+            packet_pacing: false,
+            video_loss_recovery: VideoLossRecoveryDefault {
+                Nack: VideoLossRecoveryNackDefault {
+                    gui_collapsed: true,
+                    max_age_rtts: 3.0,
+                    max_age_ms: 30,
+                },
+                variant: VideoLossRecoveryDefaultVariant::Disabled,
+            },
+            pause_stream_on_headset_removal: true,
             statistics_history_size: 256,
         },
         extra: ExtraConfigDefault {
@@ -1717,6 +2669,9 @@ pub fn session_settings_default() -> SettingsDefault {
             },
             capture: CaptureConfigDefault {
                 startup_video_recording: false,
+                recording_format: RecordingFormatDefault {
+                    variant: RecordingFormatDefaultVariant::Matroska,
+                },
                 rolling_video_files: SwitchDefault {
                     enabled: false,
                     content: RollingVideoFilesConfigDefault { duration_s: 5 },
@@ -1727,6 +2682,42 @@ pub fn session_settings_default() -> SettingsDefault {
                     "".into()
                 },
             },
+            benchmark: BenchmarkConfigDefault {
+                bitrates_mbps: VectorDefault {
+                    gui_collapsed: true,
+                    element: 30,
+                    content: vec![30, 60, 100],
+                },
+                codecs: VectorDefault {
+                    gui_collapsed: true,
+                    element: CodecTypeDefault {
+                        variant: CodecTypeDefaultVariant::H264,
+                    },
+                    content: vec![
+                        CodecTypeDefault {
+                            variant: CodecTypeDefaultVariant::H264,
+                        },
+                        CodecTypeDefault {
+                            variant: CodecTypeDefaultVariant::Hevc,
+                        },
+                    ],
+                },
+                test_pattern: true,
+                measure_encoding_quality: false,
+                warmup_s: 3.0,
+                segment_duration_s: 10.0,
+            },
+            rng_seed: SwitchDefault {
+                enabled: false,
+                content: 0,
+            },
+            network_simulation: SwitchDefault {
+                enabled: false,
+                content: NetworkSimulationConfigDefault {
+                    packet_loss_percent: 2.0,
+                    max_jitter_ms: 5,
+                },
+            },
             patches: PatchesDefault {
                 linux_async_compute: false,
                 linux_async_reprojection: false,
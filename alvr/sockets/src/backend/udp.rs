@@ -6,11 +6,17 @@ use alvr_session::{DscpTos, SocketBufferSize};
 use socket2::{MaybeUninitSlice, Socket};
 use std::{
     ffi::c_int,
-    mem,
-    net::{IpAddr, UdpSocket},
+    io, mem,
+    net::{IpAddr, Ipv6Addr, UdpSocket},
+    thread,
     time::Duration,
 };
 
+// Probe datagrams are sent to the discard port, so they are never interpreted by the peer
+const PROBE_PORT: u16 = 9;
+// Time given to routers along the path to report a smaller MTU through ICMP
+const PROBE_ICMP_WAIT: Duration = Duration::from_millis(200);
+
 // Create tokio socket, convert to socket2, apply settings, convert back to tokio. This is done to
 // let tokio set all the internal parameters it needs from the start.
 pub fn bind(
@@ -40,6 +46,145 @@ pub fn connect(
     Ok((socket.try_clone()?, socket.try_clone()?.into()))
 }
 
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn set_dont_fragment(socket: &UdpSocket, ipv6: bool) -> io::Result<()> {
+    use std::os::fd::AsRawFd;
+
+    let (level, name, value) = if ipv6 {
+        (
+            libc::IPPROTO_IPV6,
+            libc::IPV6_MTU_DISCOVER,
+            libc::IPV6_PMTUDISC_DO,
+        )
+    } else {
+        (
+            libc::IPPROTO_IP,
+            libc::IP_MTU_DISCOVER,
+            libc::IP_PMTUDISC_DO,
+        )
+    };
+
+    let res = unsafe {
+        libc::setsockopt(
+            socket.as_raw_fd(),
+            level,
+            name,
+            &value as *const c_int as _,
+            mem::size_of::<c_int>() as _,
+        )
+    };
+
+    if res == 0 {
+        Ok(())
+    } else {
+        Err(io::Error::last_os_error())
+    }
+}
+
+#[cfg(windows)]
+fn set_dont_fragment(socket: &UdpSocket, ipv6: bool) -> io::Result<()> {
+    use std::os::windows::io::AsRawSocket;
+    use windows_sys::Win32::Networking::WinSock;
+
+    let (level, name) = if ipv6 {
+        (WinSock::IPPROTO_IPV6, WinSock::IPV6_DONTFRAG)
+    } else {
+        (WinSock::IPPROTO_IP, WinSock::IP_DONTFRAGMENT)
+    };
+    let value: u32 = 1;
+
+    let res = unsafe {
+        WinSock::setsockopt(
+            socket.as_raw_socket() as _,
+            level,
+            name,
+            &value as *const u32 as _,
+            mem::size_of::<u32>() as _,
+        )
+    };
+
+    if res == 0 {
+        Ok(())
+    } else {
+        Err(io::Error::last_os_error())
+    }
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "android", windows)))]
+fn set_dont_fragment(_: &UdpSocket, _: bool) -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "Path MTU probing is not supported on this platform",
+    ))
+}
+
+fn is_message_too_long(error: &io::Error) -> bool {
+    #[cfg(windows)]
+    const EMSGSIZE: i32 = windows_sys::Win32::Networking::WinSock::WSAEMSGSIZE;
+    #[cfg(not(windows))]
+    const EMSGSIZE: i32 = libc::EMSGSIZE;
+
+    error.raw_os_error() == Some(EMSGSIZE)
+}
+
+fn datagram_fits(socket: &UdpSocket, datagram: &[u8]) -> io::Result<bool> {
+    loop {
+        match socket.send(datagram) {
+            Ok(_) => return Ok(true),
+            Err(e) if is_message_too_long(&e) => return Ok(false),
+            // Consequence of an ICMP port unreachable caused by a previous probe. The error is
+            // reported only once, so the send can be retried
+            Err(e) if e.kind() == io::ErrorKind::ConnectionRefused => (),
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+// Find the largest datagram payload in the range [min_payload_size, max_payload_size] that can be
+// sent to the peer without IP fragmentation. Datagrams are sent with the "don't fragment" flag, so
+// the OS rejects sizes bigger than the MTU of the outgoing interface or the path MTU learned from
+// ICMP "fragmentation needed" messages. The search is repeated after a short wait to account for
+// the ICMP feedback caused by the first round.
+pub fn probe_max_payload_size(
+    peer_ip: IpAddr,
+    min_payload_size: usize,
+    max_payload_size: usize,
+) -> Result<usize> {
+    let local_ip = if peer_ip.is_ipv6() {
+        IpAddr::V6(Ipv6Addr::UNSPECIFIED)
+    } else {
+        LOCAL_IP
+    };
+    let socket = UdpSocket::bind((local_ip, 0))?;
+    socket.connect((peer_ip, PROBE_PORT))?;
+    set_dont_fragment(&socket, peer_ip.is_ipv6())?;
+
+    let datagram = vec![0; max_payload_size];
+
+    let mut low = min_payload_size;
+    let mut high = max_payload_size;
+    for round in 0..2 {
+        if round > 0 {
+            thread::sleep(PROBE_ICMP_WAIT);
+
+            // The path MTU can only have shrunk
+            high = low;
+            low = min_payload_size;
+        }
+
+        while low < high {
+            let size = (low + high + 1) / 2;
+            if datagram_fits(&socket, &datagram[..size])? {
+                low = size;
+            } else {
+                high = size - 1;
+            }
+        }
+    }
+
+    Ok(low)
+}
+
 impl SocketWriter for UdpSocket {
     fn send(&mut self, buffer: &[u8]) -> Result<()> {
         UdpSocket::send(self, buffer)?;
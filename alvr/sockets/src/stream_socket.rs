@@ -18,18 +18,25 @@
 
 use crate::backend::{tcp, udp, SocketReader, SocketWriter};
 use alvr_common::{
-    anyhow::Result, debug, parking_lot::Mutex, AnyhowToCon, ConResult, HandleTryAgain, ToCon,
+    anyhow::{bail, Result},
+    debug,
+    parking_lot::Mutex,
+    AnyhowToCon, ConResult, HandleTryAgain, Rng, ToCon,
 };
 use alvr_session::{DscpTos, SocketBufferSize, SocketProtocol};
 use serde::{de::DeserializeOwned, Serialize};
 use std::{
     cmp::Ordering,
-    collections::{HashMap, HashSet},
+    collections::{HashMap, HashSet, VecDeque},
     marker::PhantomData,
     mem,
     net::{IpAddr, TcpListener, UdpSocket},
-    sync::{mpsc, Arc},
-    time::Duration,
+    sync::{
+        atomic::{AtomicUsize, Ordering as AtomicOrdering},
+        mpsc, Arc,
+    },
+    thread,
+    time::{Duration, Instant},
 };
 
 const SHARD_PREFIX_SIZE: usize = mem::size_of::<u32>() // packet length - field itself (4 bytes)
@@ -38,6 +45,85 @@ const SHARD_PREFIX_SIZE: usize = mem::size_of::<u32>() // packet length - field
     + mem::size_of::<u32>() // shards count
     + mem::size_of::<u32>(); // shards index
 
+// Number of bytes added to the configured packet size to obtain the size of the datagrams on wire
+const PACKET_SIZE_COMPAT_OFFSET: usize = 4;
+
+// Smallest UDP payload that is guaranteed to be deliverable without fragmentation (IPv4 minimum
+// reassembly buffer size minus the maximum IP header and the UDP header)
+const MIN_PROBED_PAYLOAD_SIZE: usize = 508;
+
+// A paced packet is always fully sent within this fraction of the frame interval, to leave some
+// margin before the next frame is ready
+const PACING_FRAME_BUDGET_FRACTION: f32 = 0.8;
+
+// Shards are sent in small bursts when the next one is due in less than this time. The OS cannot
+// sleep accurately for shorter durations
+const MIN_PACING_SLEEP: Duration = Duration::from_micros(200);
+
+// Reserved stream used by the receiving side to request the retransmission of lost shards
+const NACK_STREAM_ID: u16 = u16::MAX;
+
+// Keeps each NACK packet within a single shard of the smallest packet size
+const MAX_NACKED_SHARDS_PER_PACKET: usize = 64;
+
+const MAX_QUEUED_NACKS: usize = 32;
+
+/// Find the largest packet size (as accepted by `StreamSocketBuilder`) that can be sent to the peer
+/// without being fragmented by the IP layer. The result is capped to `max_packet_size`.
+/// Note: both peers must use the returned size, since the reassembler infers the position of each
+/// shard from the packet size.
+pub fn probe_max_packet_size(peer_ip: IpAddr, max_packet_size: usize) -> Result<usize> {
+    let payload_size = udp::probe_max_payload_size(
+        peer_ip,
+        MIN_PROBED_PAYLOAD_SIZE,
+        max_packet_size + PACKET_SIZE_COMPAT_OFFSET,
+    )?;
+
+    Ok(payload_size - PACKET_SIZE_COMPAT_OFFSET)
+}
+
+#[derive(Clone, Copy)]
+pub struct NetworkSimulation {
+    pub loss_probability: f32,
+    pub max_jitter: Duration,
+}
+
+// Drops datagrams and delays the first shard of each packet, which delays the following datagrams
+// too. The random sequence depends only on the sent datagrams
+struct SimulatedLink {
+    inner: Box<dyn SocketWriter>,
+    simulation: NetworkSimulation,
+    rng: Rng,
+}
+
+impl SocketWriter for SimulatedLink {
+    fn send(&mut self, buffer: &[u8]) -> Result<()> {
+        // The shard index is the last field of the prefix
+        let is_first_shard = buffer
+            .get(SHARD_PREFIX_SIZE - mem::size_of::<u32>()..SHARD_PREFIX_SIZE)
+            .is_some_and(|shard_index| shard_index == [0; 4]);
+        let jitter_fraction = self.rng.next_f32();
+        if is_first_shard && !self.simulation.max_jitter.is_zero() {
+            thread::sleep(self.simulation.max_jitter.mul_f32(jitter_fraction));
+        }
+
+        if self.rng.chance(self.simulation.loss_probability) {
+            return Ok(());
+        }
+
+        self.inner.send(buffer)
+    }
+}
+
+// Placeholder while the writer is being replaced
+struct ClosedWriter;
+
+impl SocketWriter for ClosedWriter {
+    fn send(&mut self, _: &[u8]) -> Result<()> {
+        bail!("Socket closed")
+    }
+}
+
 /// Memory buffer that contains a hidden prefix
 #[derive(Default)]
 pub struct Buffer<H = ()> {
@@ -84,6 +170,94 @@ impl<H> Buffer<H> {
     }
 }
 
+// Request for the retransmission of some shards of a packet. An empty list of shard indices is
+// never sent
+#[derive(Serialize, Deserialize)]
+struct Nack {
+    stream_id: u16,
+    packet_index: u32,
+    shard_indices: Vec<u32>,
+}
+
+struct SentPacket {
+    index: u32,
+    buffer: Vec<u8>,
+    size: usize, // contains prefix
+    shards_count: usize,
+    // The last bytes of each shard, before they were overwritten by the prefix of the next shard
+    overwritten_tails: Vec<[u8; SHARD_PREFIX_SIZE]>,
+    send_instant: Instant,
+}
+
+// Recently sent packets of a stream, kept to serve NACKs. Packets older than max_age are dropped:
+// their retransmission would arrive too late to be useful
+struct RetransmissionBuffer {
+    max_age: Duration,
+    packets: VecDeque<SentPacket>,
+}
+
+impl RetransmissionBuffer {
+    fn retransmit(
+        &mut self,
+        nack: &Nack,
+        max_packet_size: usize,
+        socket: &Mutex<Box<dyn SocketWriter>>,
+    ) -> Result<()> {
+        let max_age = self.max_age;
+        let Some(packet) = self
+            .packets
+            .iter_mut()
+            .find(|p| p.index == nack.packet_index && p.send_instant.elapsed() <= max_age)
+        else {
+            return Ok(());
+        };
+
+        let max_shard_data_size = max_packet_size - SHARD_PREFIX_SIZE;
+        for idx in nack.shard_indices.iter().map(|idx| *idx as usize) {
+            if idx >= packet.shards_count {
+                continue;
+            }
+
+            let packet_start_position = idx * max_shard_data_size;
+            let packet_length = usize::min(max_packet_size, packet.size - packet_start_position);
+
+            if let Some(tail) = packet.overwritten_tails.get(idx) {
+                packet.buffer[packet_start_position + max_shard_data_size..][..SHARD_PREFIX_SIZE]
+                    .copy_from_slice(tail);
+            }
+
+            let shard = &mut packet.buffer[packet_start_position..][..packet_length];
+            write_shard_prefix(
+                shard,
+                nack.stream_id,
+                packet.index,
+                packet.shards_count,
+                idx,
+            );
+            socket.lock().send(shard)?;
+        }
+
+        Ok(())
+    }
+}
+
+fn write_shard_prefix(
+    shard: &mut [u8],
+    stream_id: u16,
+    packet_index: u32,
+    shards_count: usize,
+    shard_index: usize,
+) {
+    // todo: switch to little endian
+    // todo: do not remove sizeof<u32> for packet length
+    let shard_length = (shard.len() - mem::size_of::<u32>()) as u32;
+    shard[0..4].copy_from_slice(&shard_length.to_be_bytes());
+    shard[4..6].copy_from_slice(&stream_id.to_be_bytes());
+    shard[6..10].copy_from_slice(&packet_index.to_be_bytes());
+    shard[10..14].copy_from_slice(&(shards_count as u32).to_be_bytes());
+    shard[14..18].copy_from_slice(&(shard_index as u32).to_be_bytes());
+}
+
 #[derive(Clone)]
 pub struct StreamSender<H> {
     inner: Arc<Mutex<Box<dyn SocketWriter>>>,
@@ -92,10 +266,53 @@ pub struct StreamSender<H> {
     // if the packet index overflows the worst that happens is a false positive packet loss
     next_packet_index: u32,
     used_buffers: Vec<Vec<u8>>,
+    pacing: Option<PacingConfig>,
+    last_pacing_interval: Option<Duration>,
+    // Bytes sent without pacing on the sockets sharing the budget, not yet accounted by a paced
+    // sender
+    unpaced_bytes: Arc<AtomicUsize>,
+    retransmission: Option<Arc<Mutex<RetransmissionBuffer>>>,
     _phantom: PhantomData<H>,
 }
 
+#[derive(Clone, Copy)]
+pub struct PacingConfig {
+    pub bitrate_bps: f32,
+    pub frame_interval: Duration,
+}
+
 impl<H> StreamSender<H> {
+    /// Spread the shards of each packet over time at the rate of the target bitrate, instead of
+    /// sending them back-to-back. Pass None to disable pacing.
+    pub fn set_pacing(&mut self, config: Option<PacingConfig>) {
+        self.pacing = config;
+    }
+
+    /// Interval between the shards of the last sent packet, None if it was not paced
+    pub fn pacing_interval(&self) -> Option<Duration> {
+        self.last_pacing_interval
+    }
+
+    /// Maximum age of the shards that can be retransmitted. This should be a few round trip times.
+    /// No-op if the stream was not requested with retransmission.
+    pub fn set_retransmission_max_age(&mut self, max_age: Duration) {
+        if let Some(retransmission) = &self.retransmission {
+            retransmission.lock().max_age = max_age;
+        }
+    }
+
+    fn get_pacing_interval(&self, shards_count: usize) -> Option<Duration> {
+        let config = self.pacing?;
+
+        let shard_interval = Duration::from_secs_f32(
+            self.max_packet_size as f32 * 8.0 / config.bitrate_bps.max(1.0),
+        );
+        let max_interval = config.frame_interval.mul_f32(PACING_FRAME_BUDGET_FRACTION)
+            / shards_count.max(1) as u32;
+
+        Some(Duration::min(shard_interval, max_interval))
+    }
+
     /// Shard and send a buffer with zero copies and zero allocations.
     /// The prefix of each shard is written over the previously sent shard to avoid reallocations.
     pub fn send(&mut self, mut buffer: Buffer<H>) -> Result<()> {
@@ -104,33 +321,92 @@ impl<H> StreamSender<H> {
         let data_size = actual_buffer_size - SHARD_PREFIX_SIZE;
         let shards_count = (data_size as f32 / max_shard_data_size as f32).ceil() as usize;
 
+        let pacing_interval = self.get_pacing_interval(shards_count);
+        self.last_pacing_interval = pacing_interval;
+        let start_instant = Instant::now();
+
+        // Time taken by the unpaced bytes of the other streams, which delays the next shards. It
+        // is capped to the frame budget so an idle paced stream doesn't accumulate a backlog.
+        let mut shared_delay = Duration::ZERO;
+
+        let mut overwritten_tails = vec![];
+
         for idx in 0..shards_count {
+            // Deadlines are relative to the first shard, so oversleeping doesn't accumulate
+            if let (Some(interval), Some(config)) = (pacing_interval, self.pacing) {
+                let unpaced_bytes = self.unpaced_bytes.swap(0, AtomicOrdering::Relaxed);
+                shared_delay = Duration::min(
+                    shared_delay
+                        + Duration::from_secs_f32(
+                            unpaced_bytes as f32 * 8.0 / config.bitrate_bps.max(1.0),
+                        ),
+                    config.frame_interval.mul_f32(PACING_FRAME_BUDGET_FRACTION),
+                );
+
+                let deadline = start_instant + interval * idx as u32 + shared_delay;
+                let now = Instant::now();
+                if deadline > now + MIN_PACING_SLEEP {
+                    thread::sleep(deadline - now);
+                }
+            }
+
             // this overlaps with the previous shard, this is intended behavior and allows to
             // reduce allocations
             let packet_start_position = idx * max_shard_data_size;
             let sub_buffer = &mut buffer.inner[packet_start_position..];
 
+            // A retransmitted shard needs the bytes that are about to be overwritten
+            if self.retransmission.is_some() && idx > 0 {
+                overwritten_tails.push(sub_buffer[..SHARD_PREFIX_SIZE].try_into().unwrap());
+            }
+
             // NB: true shard length (account for last shard that is smaller)
             let packet_length = usize::min(
                 self.max_packet_size,
                 actual_buffer_size - packet_start_position,
             );
 
-            // todo: switch to little endian
-            // todo: do not remove sizeof<u32> for packet length
-            sub_buffer[0..4]
-                .copy_from_slice(&((packet_length - mem::size_of::<u32>()) as u32).to_be_bytes());
-            sub_buffer[4..6].copy_from_slice(&self.stream_id.to_be_bytes());
-            sub_buffer[6..10].copy_from_slice(&self.next_packet_index.to_be_bytes());
-            sub_buffer[10..14].copy_from_slice(&(shards_count as u32).to_be_bytes());
-            sub_buffer[14..18].copy_from_slice(&(idx as u32).to_be_bytes());
+            let shard = &mut sub_buffer[..packet_length];
+            write_shard_prefix(
+                shard,
+                self.stream_id,
+                self.next_packet_index,
+                shards_count,
+                idx,
+            );
+
+            self.inner.lock().send(shard)?;
 
-            self.inner.lock().send(&sub_buffer[..packet_length])?;
+            if pacing_interval.is_none() {
+                self.unpaced_bytes
+                    .fetch_add(packet_length, AtomicOrdering::Relaxed);
+            }
         }
 
-        self.next_packet_index += 1;
+        if let Some(retransmission) = &self.retransmission {
+            let mut retransmission = retransmission.lock();
+
+            while let Some(packet) = retransmission.packets.front() {
+                if packet.send_instant.elapsed() <= retransmission.max_age {
+                    break;
+                }
+                let packet = retransmission.packets.pop_front().unwrap();
+                self.used_buffers.push(packet.buffer);
+            }
+
+            retransmission.packets.push_back(SentPacket {
+                index: self.next_packet_index,
+                buffer: buffer.inner,
+                size: actual_buffer_size,
+                shards_count,
+                overwritten_tails,
+                send_instant: start_instant,
+            });
+        } else {
+            self.used_buffers.push(buffer.inner);
+        }
 
-        self.used_buffers.push(buffer.inner);
+        self.next_packet_index += 1;
 
         Ok(())
     }
@@ -315,15 +591,11 @@ impl StreamSocketBuilder {
                 }
             };
 
-        Ok(StreamSocket {
-            // +4 is a workaround to retain compatibilty with old protocol
-            // todo: remove +4
-            max_packet_size: max_packet_size + 4,
-            send_socket: Arc::new(Mutex::new(send_socket)),
+        Ok(StreamSocket::new(
+            max_packet_size,
+            send_socket,
             receive_socket,
-            shard_recv_state: None,
-            stream_recv_components: HashMap::new(),
-        })
+        ))
     }
 
     #[allow(clippy::too_many_arguments)]
@@ -360,15 +632,11 @@ impl StreamSocketBuilder {
                 }
             };
 
-        Ok(StreamSocket {
-            // +4 is a workaround to retain compatibilty with old protocol
-            // todo: remove +4
-            max_packet_size: max_packet_size + 4,
-            send_socket: Arc::new(Mutex::new(send_socket)),
+        Ok(StreamSocket::new(
+            max_packet_size,
+            send_socket,
             receive_socket,
-            shard_recv_state: None,
-            stream_recv_components: HashMap::new(),
-        })
+        ))
     }
 }
 
@@ -387,6 +655,30 @@ struct InProgressPacket {
     buffer: Vec<u8>,
     buffer_length: usize,
     received_shard_indices: HashSet<usize>,
+    shards_count: usize,
+    // Shards are sent in order, the shards skipped before this index have been lost
+    next_shard_index: usize,
+    first_nack_instant: Option<Instant>,
+}
+
+impl InProgressPacket {
+    // Returns the indices of the shards lost before the given one
+    fn report_shard(&mut self, shard_index: usize) -> Vec<u32> {
+        let lost_shards = (self.next_shard_index..shard_index)
+            .filter(|idx| !self.received_shard_indices.contains(idx))
+            .map(|idx| idx as u32)
+            .collect();
+        self.next_shard_index = usize::max(self.next_shard_index, shard_index + 1);
+
+        lost_shards
+    }
+}
+
+// Completed packets are delivered in order. A packet is held while an older packet is waiting for
+// its lost shards to be retransmitted, until they arrive or the wait times out
+struct NackState {
+    max_wait: Duration,
+    held_packets: Vec<ReconstructedPacket>,
 }
 
 struct StreamRecvComponents {
@@ -395,6 +687,53 @@ struct StreamRecvComponents {
     packet_queue: mpsc::Sender<ReconstructedPacket>,
     in_progress_packets: HashMap<u32, InProgressPacket>,
     discarded_shards_sink: InProgressPacket,
+    last_completed_packet_index: Option<u32>,
+    nack_state: Option<NackState>,
+}
+
+impl StreamRecvComponents {
+    fn complete_packet(&mut self, packet: ReconstructedPacket) {
+        let index = packet.index;
+        self.packet_queue.send(packet).ok();
+        self.last_completed_packet_index = Some(index);
+
+        // Keep only shards with later packet index (using wrapping logic)
+        while let Some((idx, _)) = self
+            .in_progress_packets
+            .iter()
+            .find(|(idx, _)| wrapping_cmp(**idx, index) == Ordering::Less)
+        {
+            let idx = *idx; // fix borrow rule
+            let packet = self.in_progress_packets.remove(&idx).unwrap();
+
+            // Recycle buffer
+            self.used_buffer_sender.send(packet.buffer).ok();
+        }
+    }
+
+    fn release_held_packets(&mut self) {
+        loop {
+            let Some(nack_state) = &mut self.nack_state else {
+                return;
+            };
+            let Some(index) = nack_state.held_packets.first().map(|p| p.index) else {
+                return;
+            };
+
+            let max_wait = nack_state.max_wait;
+            if self.in_progress_packets.iter().any(|(idx, packet)| {
+                wrapping_cmp(*idx, index) == Ordering::Less
+                    && packet
+                        .first_nack_instant
+                        .is_some_and(|instant| instant.elapsed() < max_wait)
+            }) {
+                return;
+            }
+
+            let packet = nack_state.held_packets.remove(0);
+            self.complete_packet(packet);
+        }
+    }
 }
 
 // Note: used buffers don't *have* to be split by stream ID, but doing so improves memory usage
@@ -405,9 +744,40 @@ pub struct StreamSocket {
     receive_socket: Box<dyn SocketReader>,
     shard_recv_state: Option<RecvState>,
     stream_recv_components: HashMap<u16, StreamRecvComponents>,
+    retransmission_buffers: HashMap<u16, Arc<Mutex<RetransmissionBuffer>>>,
+    nack_sender: Option<StreamSender<Nack>>,
+    nack_receiver: Option<StreamReceiver<Nack>>,
+    unpaced_bytes: Arc<AtomicUsize>,
 }
 
 impl StreamSocket {
+    fn new(
+        max_packet_size: usize,
+        send_socket: Box<dyn SocketWriter>,
+        receive_socket: Box<dyn SocketReader>,
+    ) -> Self {
+        Self {
+            // +4 is a workaround to retain compatibilty with old protocol
+            // todo: remove +4
+            max_packet_size: max_packet_size + PACKET_SIZE_COMPAT_OFFSET,
+            send_socket: Arc::new(Mutex::new(send_socket)),
+            receive_socket,
+            shard_recv_state: None,
+            stream_recv_components: HashMap::new(),
+            retransmission_buffers: HashMap::new(),
+            nack_sender: None,
+            nack_receiver: None,
+            unpaced_bytes: Arc::new(AtomicUsize::new(0)),
+        }
+    }
+
+    /// Use the pacing budget of another socket which shares the same link: the bytes sent without
+    /// pacing on either socket delay the paced streams of both. Only the streams requested after
+    /// this call are affected.
+    pub fn share_pacing_budget(&mut self, other: &StreamSocket) {
+        self.unpaced_bytes = Arc::clone(&other.unpaced_bytes);
+    }
+
     pub fn request_stream<T>(&self, stream_id: u16) -> StreamSender<T> {
         StreamSender {
             inner: Arc::clone(&self.send_socket),
@@ -415,10 +785,50 @@ impl StreamSocket {
             max_packet_size: self.max_packet_size,
             next_packet_index: 0,
             used_buffers: vec![],
+            pacing: None,
+            last_pacing_interval: None,
+            unpaced_bytes: Arc::clone(&self.unpaced_bytes),
+            retransmission: None,
             _phantom: PhantomData,
         }
     }
 
+    // Sent packets are kept for max_age to serve retransmission requests of the peer. The peer
+    // must subscribe with subscribe_to_stream_with_nacks(), it is not possible with TCP
+    pub fn request_stream_with_retransmission<T>(
+        &mut self,
+        stream_id: u16,
+        max_age: Duration,
+    ) -> StreamSender<T> {
+        if self.nack_receiver.is_none() {
+            self.nack_receiver = Some(self.subscribe_to_stream(NACK_STREAM_ID, MAX_QUEUED_NACKS));
+        }
+
+        let retransmission = Arc::new(Mutex::new(RetransmissionBuffer {
+            max_age,
+            packets: VecDeque::new(),
+        }));
+        self.retransmission_buffers
+            .insert(stream_id, Arc::clone(&retransmission));
+
+        StreamSender {
+            retransmission: Some(retransmission),
+            ..self.request_stream(stream_id)
+        }
+    }
+
+    /// Degrade the sent datagrams, for all the streams of the socket. The rng determines the
+    /// dropped and delayed datagrams.
+    pub fn simulate_network(&self, simulation: NetworkSimulation, rng: Rng) {
+        let mut send_socket = self.send_socket.lock();
+        let inner = mem::replace(&mut *send_socket, Box::new(ClosedWriter));
+        *send_socket = Box::new(SimulatedLink {
+            inner,
+            simulation,
+            rng,
+        });
+    }
+
     // max_concurrent_buffers: number of buffers allocated by this call which will be reused to
     // receive packets for this stream ID. If packets are not read fast enough, the shards received
     // for this particular stream will be discarded
@@ -445,7 +855,12 @@ impl StreamSocket {
                     buffer: vec![],
                     buffer_length: 0,
                     received_shard_indices: HashSet::new(),
+                    shards_count: 0,
+                    next_shard_index: 0,
+                    first_nack_instant: None,
                 },
+                last_completed_packet_index: None,
+                nack_state: None,
             },
         );
 
@@ -457,7 +872,56 @@ impl StreamSocket {
         }
     }
 
+    // Lost shards are requested to the peer, which must have requested the stream with
+    // request_stream_with_retransmission(). A completed packet is held for at most max_wait
+    // while the retransmission of an older packet is pending
+    pub fn subscribe_to_stream_with_nacks<T>(
+        &mut self,
+        stream_id: u16,
+        max_concurrent_buffers: usize,
+        max_wait: Duration,
+    ) -> StreamReceiver<T> {
+        if self.nack_sender.is_none() {
+            self.nack_sender = Some(self.request_stream(NACK_STREAM_ID));
+        }
+
+        let receiver = self.subscribe_to_stream(stream_id, max_concurrent_buffers);
+        self.stream_recv_components
+            .get_mut(&stream_id)
+            .unwrap()
+            .nack_state = Some(NackState {
+            max_wait,
+            held_packets: vec![],
+        });
+
+        receiver
+    }
+
+    fn serve_nacks(&mut self) -> Result<()> {
+        let Some(receiver) = &mut self.nack_receiver else {
+            return Ok(());
+        };
+
+        while let Ok(data) = receiver.recv(Duration::ZERO) {
+            let Ok(nack) = data.get_header() else {
+                continue;
+            };
+            if let Some(buffer) = self.retransmission_buffers.get(&nack.stream_id) {
+                buffer
+                    .lock()
+                    .retransmit(&nack, self.max_packet_size, &self.send_socket)?;
+            }
+        }
+
+        Ok(())
+    }
+
     pub fn recv(&mut self) -> ConResult {
+        // Held packets are released also when no shards are received
+        for components in self.stream_recv_components.values_mut() {
+            components.release_held_packets();
+        }
+
         let shard_recv_state_mut = if let Some(state) = &mut self.shard_recv_state {
             state
         } else {
@@ -499,7 +963,21 @@ impl StreamSocket {
             return alvr_common::try_again();
         };
 
+        let stream_id = shard_recv_state_mut.stream_id;
+        let packet_index = shard_recv_state_mut.packet_index;
+        let mut nacks = vec![];
+
         let in_progress_packet = if shard_recv_state_mut.should_discard {
+            &mut components.discarded_shards_sink
+        } else if components
+            .last_completed_packet_index
+            .is_some_and(|idx| wrapping_cmp(packet_index, idx) != Ordering::Greater)
+        {
+            // Late shard (for example a duplicated retransmission) of an already completed or
+            // skipped packet
+            shard_recv_state_mut.should_discard = true;
+            shard_recv_state_mut.shard_index = 0;
+
             &mut components.discarded_shards_sink
         } else if let Some(packet) = components
             .in_progress_packets
@@ -513,6 +991,17 @@ impl StreamSocket {
             let idx = *components.in_progress_packets.iter().next()?.0;
             Some(components.in_progress_packets.remove(&idx).unwrap().buffer)
         }) {
+            // The first shard of a new packet means that the missing last shards of the previous
+            // packets have been lost
+            if components.nack_state.is_some() {
+                for (idx, packet) in &mut components.in_progress_packets {
+                    if wrapping_cmp(*idx, packet_index) == Ordering::Less {
+                        let shard_indices = packet.report_shard(packet.shards_count);
+                        nacks.push((*idx, shard_indices));
+                    }
+                }
+            }
+
             // NB: Can't use entry pattern because we want to allow bailing out on the line above
             components.in_progress_packets.insert(
                 shard_recv_state_mut.packet_index,
@@ -523,6 +1012,9 @@ impl StreamSocket {
                     received_shard_indices: HashSet::with_capacity(
                         shard_recv_state_mut.shards_count,
                     ),
+                    shards_count: shard_recv_state_mut.shards_count,
+                    next_shard_index: 0,
+                    first_nack_instant: None,
                 },
             );
             components
@@ -585,6 +1077,12 @@ impl StreamSocket {
         }
 
         if !shard_recv_state_mut.should_discard {
+            if components.nack_state.is_some() {
+                let shard_indices =
+                    in_progress_packet.report_shard(shard_recv_state_mut.shard_index);
+                nacks.push((packet_index, shard_indices));
+            }
+
             in_progress_packet
                 .received_shard_indices
                 .insert(shard_recv_state_mut.shard_index);
@@ -592,35 +1090,530 @@ impl StreamSocket {
 
         // Check if packet is complete and send
         if in_progress_packet.received_shard_indices.len() == shard_recv_state_mut.shards_count {
-            let size = in_progress_packet.buffer_length;
-            components
-                .packet_queue
-                .send(ReconstructedPacket {
-                    index: shard_recv_state_mut.packet_index,
-                    buffer: components
-                        .in_progress_packets
-                        .remove(&shard_recv_state_mut.packet_index)
-                        .unwrap()
-                        .buffer,
-                    size,
-                })
-                .ok();
-
-            // Keep only shards with later packet index (using wrapping logic)
-            while let Some((idx, _)) = components.in_progress_packets.iter().find(|(idx, _)| {
-                wrapping_cmp(**idx, shard_recv_state_mut.packet_index) == Ordering::Less
-            }) {
-                let idx = *idx; // fix borrow rule
-                let packet = components.in_progress_packets.remove(&idx).unwrap();
+            let packet = ReconstructedPacket {
+                index: packet_index,
+                size: in_progress_packet.buffer_length,
+                buffer: components
+                    .in_progress_packets
+                    .remove(&packet_index)
+                    .unwrap()
+                    .buffer,
+            };
+
+            if let Some(nack_state) = &mut components.nack_state {
+                nack_state.held_packets.push(packet);
+                nack_state
+                    .held_packets
+                    .sort_by(|a, b| wrapping_cmp(a.index, b.index));
 
-                // Recycle buffer
-                components.used_buffer_sender.send(packet.buffer).ok();
+                components.release_held_packets();
+            } else {
+                components.complete_packet(packet);
             }
         }
 
         // Mark current shard as read and allow for a new shard to be read
         self.shard_recv_state = None;
 
+        if let Some(nack_sender) = &mut self.nack_sender {
+            for (packet_index, shard_indices) in nacks {
+                if shard_indices.is_empty() {
+                    continue;
+                }
+
+                if let Some(packet) = components.in_progress_packets.get_mut(&packet_index) {
+                    packet.first_nack_instant.get_or_insert_with(Instant::now);
+                }
+
+                for chunk in shard_indices.chunks(MAX_NACKED_SHARDS_PER_PACKET) {
+                    nack_sender
+                        .send_header(&Nack {
+                            stream_id,
+                            packet_index,
+                            shard_indices: chunk.to_vec(),
+                        })
+                        .to_con()?;
+                }
+            }
+        }
+
+        if stream_id == NACK_STREAM_ID {
+            self.serve_nacks().to_con()?;
+        }
+
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alvr_common::RngSource;
+    use std::net::Ipv4Addr;
+
+    struct ChannelWriter {
+        sender: mpsc::Sender<Vec<u8>>,
+        sent_sizes: Arc<Mutex<Vec<usize>>>,
+        sent_instants: Arc<Mutex<Vec<Instant>>>,
+        // Indices of the sent datagrams that never reach the receiver
+        lost_datagrams: HashSet<usize>,
+    }
+
+    impl SocketWriter for ChannelWriter {
+        fn send(&mut self, buffer: &[u8]) -> Result<()> {
+            let mut sent_sizes = self.sent_sizes.lock();
+            if !self.lost_datagrams.contains(&sent_sizes.len()) {
+                self.sender.send(buffer.to_vec())?;
+            }
+            sent_sizes.push(buffer.len());
+            self.sent_instants.lock().push(Instant::now());
+
+            Ok(())
+        }
+    }
+
+    // Behaves like a datagram socket: each recv() consumes a whole datagram
+    struct ChannelReader {
+        receiver: mpsc::Receiver<Vec<u8>>,
+        peeked_datagram: Mutex<Option<Vec<u8>>>,
+    }
+
+    impl SocketReader for ChannelReader {
+        fn recv(&mut self, buffer: &mut [u8]) -> ConResult<usize> {
+            let datagram = if let Some(datagram) = self.peeked_datagram.get_mut().take() {
+                datagram
+            } else {
+                self.receiver.try_recv().handle_try_again()?
+            };
+
+            let size = usize::min(buffer.len(), datagram.len());
+            buffer[..size].copy_from_slice(&datagram[..size]);
+
+            Ok(size)
+        }
+
+        fn peek(&self, buffer: &mut [u8]) -> ConResult<usize> {
+            let mut peeked_datagram = self.peeked_datagram.lock();
+            if peeked_datagram.is_none() {
+                *peeked_datagram = Some(self.receiver.try_recv().handle_try_again()?);
+            }
+            let datagram = peeked_datagram.as_ref().unwrap();
+
+            let size = usize::min(buffer.len(), datagram.len());
+            buffer[..size].copy_from_slice(&datagram[..size]);
+
+            // Like MSG_TRUNC, return the real size of the datagram
+            Ok(datagram.len())
+        }
+    }
+
+    #[allow(clippy::type_complexity)]
+    fn loopback_socket(
+        max_packet_size: usize,
+    ) -> (
+        StreamSocket,
+        Arc<Mutex<Vec<usize>>>,
+        Arc<Mutex<Vec<Instant>>>,
+    ) {
+        lossy_loopback_socket(max_packet_size, &[])
+    }
+
+    #[allow(clippy::type_complexity)]
+    fn lossy_loopback_socket(
+        max_packet_size: usize,
+        lost_datagrams: &[usize],
+    ) -> (
+        StreamSocket,
+        Arc<Mutex<Vec<usize>>>,
+        Arc<Mutex<Vec<Instant>>>,
+    ) {
+        let (sender, receiver) = mpsc::channel();
+        let sent_sizes = Arc::new(Mutex::new(vec![]));
+        let sent_instants = Arc::new(Mutex::new(vec![]));
+
+        let socket = StreamSocket::new(
+            max_packet_size,
+            Box::new(ChannelWriter {
+                sender,
+                sent_sizes: Arc::clone(&sent_sizes),
+                sent_instants: Arc::clone(&sent_instants),
+                lost_datagrams: lost_datagrams.iter().copied().collect(),
+            }),
+            Box::new(ChannelReader {
+                receiver,
+                peeked_datagram: Mutex::new(None),
+            }),
+        );
+
+        (socket, sent_sizes, sent_instants)
+    }
+
+    #[test]
+    fn test_shard_and_reconstruct_with_packet_sizes() {
+        let payload = (0..100_000).map(|i| (i % 251) as u8).collect::<Vec<_>>();
+
+        for packet_size in [MIN_PROBED_PAYLOAD_SIZE, 1024, 1200, 1400, 8996] {
+            let (mut socket, sent_sizes, _) = loopback_socket(packet_size);
+            let mut sender = socket.request_stream::<u32>(0);
+            let mut receiver = socket.subscribe_to_stream::<u32>(0, 1);
+
+            let mut buffer = sender.get_buffer(&42).unwrap();
+            buffer
+                .get_range_mut(0, payload.len())
+                .copy_from_slice(&payload);
+            sender.send(buffer).unwrap();
+
+            let sent_sizes = sent_sizes.lock().clone();
+            assert!(sent_sizes.len() > 1);
+            assert!(sent_sizes
+                .iter()
+                .all(|size| *size <= packet_size + PACKET_SIZE_COMPAT_OFFSET));
+
+            for _ in 0..sent_sizes.len() {
+                assert!(socket.recv().is_ok());
+            }
+
+            let Ok(data) = receiver.recv(Duration::from_millis(100)) else {
+                panic!("Packet not reconstructed with packet size {packet_size}");
+            };
+            assert!(!data.had_packet_loss());
+
+            let (header, received_payload) = data.get().unwrap();
+            assert_eq!(header, 42);
+            assert_eq!(received_payload, payload.as_slice());
+        }
+    }
+
+    #[test]
+    fn test_pacing_spreads_shards() {
+        let payload = vec![0; 100_000];
+        let frame_interval = Duration::from_secs_f32(1.0 / 60.0);
+
+        let (socket, _, sent_instants) = loopback_socket(1400);
+        let mut sender = socket.request_stream::<u32>(0);
+
+        let send_payload = |sender: &mut StreamSender<u32>| {
+            let mut buffer = sender.get_buffer(&0).unwrap();
+            buffer
+                .get_range_mut(0, payload.len())
+                .copy_from_slice(&payload);
+            sender.send(buffer).unwrap();
+        };
+
+        // Without pacing the shards are sent back-to-back
+        send_payload(&mut sender);
+        assert_eq!(sender.pacing_interval(), None);
+
+        sender.set_pacing(Some(PacingConfig {
+            bitrate_bps: 100e6,
+            frame_interval,
+        }));
+        sent_instants.lock().clear();
+        let start_instant = Instant::now();
+        send_payload(&mut sender);
+
+        let interval = sender.pacing_interval().unwrap();
+        let expected_interval =
+            Duration::from_secs_f32((1400 + PACKET_SIZE_COMPAT_OFFSET) as f32 * 8.0 / 100e6);
+        assert!((interval.as_secs_f32() - expected_interval.as_secs_f32()).abs() < 1e-6);
+
+        // No shard is sent earlier than its slot, except when grouped to avoid tiny sleeps
+        let sent_instants = sent_instants.lock().clone();
+        assert!(sent_instants.len() > 50);
+        for (idx, instant) in sent_instants.iter().enumerate() {
+            let elapsed = instant.duration_since(start_instant);
+            assert!(elapsed + MIN_PACING_SLEEP >= interval * idx as u32);
+        }
+
+        // A low bitrate doesn't push the packet into the next frame budget
+        sender.set_pacing(Some(PacingConfig {
+            bitrate_bps: 1e6,
+            frame_interval,
+        }));
+        send_payload(&mut sender);
+        let shards_count = sent_instants.len() as u32;
+        assert!(
+            sender.pacing_interval().unwrap() * shards_count
+                <= frame_interval.mul_f32(PACING_FRAME_BUDGET_FRACTION)
+        );
+    }
+
+    // Receives until there is nothing left to read, including NACKs and retransmitted shards
+    fn recv_all(socket: &mut StreamSocket) {
+        while socket.recv().is_ok() {}
+    }
+
+    fn send_payload(sender: &mut StreamSender<u32>, header: u32, payload: &[u8]) {
+        let mut buffer = sender.get_buffer(&header).unwrap();
+        buffer
+            .get_range_mut(0, payload.len())
+            .copy_from_slice(payload);
+        sender.send(buffer).unwrap();
+    }
+
+    // Sends 20 packets of 4 shards through a link that loses each datagram with a 5% probability.
+    // Returns the header of each packet received, and whether a loss was detected before it
+    fn simulated_loss_pattern(rng_source: RngSource) -> Vec<(u32, bool)> {
+        let (mut socket, _, _) = lossy_loopback_socket(1400, &[]);
+        socket.simulate_network(
+            NetworkSimulation {
+                loss_probability: 0.05,
+                max_jitter: Duration::from_millis(1),
+            },
+            rng_source.stream("network_simulation"),
+        );
+        let mut sender = socket.request_stream::<u32>(0);
+        let mut receiver = socket.subscribe_to_stream::<u32>(0, 21);
+
+        for index in 0..20 {
+            send_payload(&mut sender, index, &[0; 5000]);
+        }
+        recv_all(&mut socket);
+
+        let mut received = vec![];
+        while let Ok(data) = receiver.recv(Duration::ZERO) {
+            received.push((data.get_header().unwrap(), data.had_packet_loss()));
+        }
+
+        received
+    }
+
+    #[test]
+    fn test_same_seed_reproduces_simulated_loss() {
+        let pattern = simulated_loss_pattern(RngSource::new(42));
+        assert!(pattern.len() < 20);
+        assert!(pattern.iter().any(|(_, had_packet_loss)| *had_packet_loss));
+
+        assert_eq!(simulated_loss_pattern(RngSource::new(42)), pattern);
+        assert_ne!(simulated_loss_pattern(RngSource::new(43)), pattern);
+    }
+
+    #[test]
+    fn test_lost_shards_are_retransmitted() {
+        let payload = (0..20_000).map(|i| (i % 251) as u8).collect::<Vec<_>>();
+
+        // The first packet is sent as 15 shards and loses one in the middle and the last one
+        let (mut socket, sent_sizes, _) = lossy_loopback_socket(1400, &[3, 7, 14]);
+        let mut sender = socket.request_stream_with_retransmission(0, Duration::from_secs(1));
+        let mut receiver =
+            socket.subscribe_to_stream_with_nacks::<u32>(0, 4, Duration::from_secs(1));
+
+        send_payload(&mut sender, 0, &payload);
+        assert_eq!(sent_sizes.lock().len(), 15);
+        // The loss of the last shard is detected with the next packet
+        send_payload(&mut sender, 1, &payload[..1000]);
+        recv_all(&mut socket);
+
+        // 2 packets, 3 NACKs and the 3 retransmitted shards
+        assert_eq!(sent_sizes.lock().len(), 15 + 1 + 3 + 3);
+
+        // The next packet was completed first but is delivered in order
+        for index in 0..2 {
+            let data = receiver.recv(Duration::from_millis(100)).unwrap();
+            assert!(!data.had_packet_loss());
+
+            let (header, received_payload) = data.get().unwrap();
+            assert_eq!(header, index);
+            let expected_size = if index == 0 { payload.len() } else { 1000 };
+            assert_eq!(received_payload, &payload[..expected_size]);
+        }
+    }
+
+    #[test]
+    fn test_old_shards_are_not_retransmitted() {
+        let payload = vec![0; 5000];
+        let max_wait = Duration::from_millis(20);
+
+        // Each packet is sent as 4 shards, the second packet loses one
+        let (mut socket, sent_sizes, _) = lossy_loopback_socket(1400, &[5]);
+        // The retransmission window is shorter than the round trip
+        let mut sender = socket.request_stream_with_retransmission(0, Duration::ZERO);
+        let mut receiver = socket.subscribe_to_stream_with_nacks::<u32>(0, 4, max_wait);
+
+        for index in 0..3 {
+            send_payload(&mut sender, index, &payload);
+        }
+        recv_all(&mut socket);
+        assert_eq!(sent_sizes.lock().len(), 3 * 4 + 1);
+        assert_eq!(
+            receiver.recv(Duration::ZERO).unwrap().get_header().unwrap(),
+            0
+        );
+
+        // The last packet is held while waiting for the retransmission
+        assert!(receiver.recv(Duration::ZERO).is_err());
+
+        thread::sleep(max_wait);
+        recv_all(&mut socket);
+        let data = receiver.recv(Duration::from_millis(100)).unwrap();
+        assert!(data.had_packet_loss());
+        assert_eq!(data.get_header().unwrap(), 2);
+    }
+
+    const TEST_TIMEOUT: Duration = Duration::from_millis(100);
+
+    // Connects the stream and audio sockets on localhost in the same order as the connection
+    // setup, with the audio socket sharing the pacing budget of the stream socket like on the
+    // server. Returns the server and client sockets.
+    fn connect_stream_and_audio_sockets() -> ([StreamSocket; 2], [StreamSocket; 2]) {
+        let listen = || {
+            StreamSocketBuilder::listen_for_server(
+                TEST_TIMEOUT,
+                0,
+                SocketProtocol::Tcp,
+                None,
+                SocketBufferSize::Custom(1_000_000),
+                SocketBufferSize::Custom(1_000_000),
+            )
+            .unwrap()
+        };
+        let port = |builder: &StreamSocketBuilder| match builder {
+            StreamSocketBuilder::Tcp(listener) => listener.local_addr().unwrap().port(),
+            StreamSocketBuilder::Udp(socket) => socket.local_addr().unwrap().port(),
+        };
+        let localhost = IpAddr::V4(Ipv4Addr::LOCALHOST);
+
+        let stream_builder = listen();
+        let audio_builder = listen();
+        let ports = [port(&stream_builder), port(&audio_builder)];
+
+        let [server_stream, mut server_audio] = ports.map(|port| {
+            StreamSocketBuilder::connect_to_client(
+                TEST_TIMEOUT,
+                localhost,
+                port,
+                SocketProtocol::Tcp,
+                None,
+                SocketBufferSize::Custom(1_000_000),
+                SocketBufferSize::Custom(1_000_000),
+                1400,
+            )
+            .unwrap()
+        });
+        server_audio.share_pacing_budget(&server_stream);
+
+        let client_stream = stream_builder
+            .accept_from_server(localhost, ports[0], 1400, TEST_TIMEOUT)
+            .unwrap();
+        let client_audio = audio_builder
+            .accept_from_server(localhost, ports[1], 1400, TEST_TIMEOUT)
+            .unwrap();
+
+        ([server_stream, server_audio], [client_stream, client_audio])
+    }
+
+    // Number of shards read from the socket before the audio packet is available to the receiver
+    fn recv_calls_until_audio(
+        socket: &mut StreamSocket,
+        receiver: &mut StreamReceiver<u32>,
+    ) -> usize {
+        let mut calls = 0;
+        let deadline = Instant::now() + Duration::from_secs(1);
+        while Instant::now() < deadline {
+            if let Ok(data) = receiver.recv(Duration::ZERO) {
+                assert_eq!(data.get().unwrap().1, [1; 960]);
+
+                return calls;
+            }
+            if socket.recv().is_ok() {
+                calls += 1;
+            }
+        }
+
+        panic!("Audio packet not received");
+    }
+
+    #[test]
+    fn test_separate_audio_socket_is_not_delayed_by_video_bursts() {
+        // A video frame of 40 shards, sent right before an audio packet
+        let video_payload = vec![0; 55_000];
+        let audio_payload = vec![1; 960];
+
+        let ([server_stream, server_audio], [mut client_stream, mut client_audio]) =
+            connect_stream_and_audio_sockets();
+
+        // On a shared socket, the audio shard waits for the whole burst
+        let mut video_sender = server_stream.request_stream::<u32>(0);
+        let mut audio_sender = server_stream.request_stream::<u32>(1);
+        let mut video_receiver = client_stream.subscribe_to_stream::<u32>(0, 4);
+        let mut audio_receiver = client_stream.subscribe_to_stream::<u32>(1, 4);
+        send_payload(&mut video_sender, 0, &video_payload);
+        send_payload(&mut audio_sender, 0, &audio_payload);
+        assert_eq!(
+            recv_calls_until_audio(&mut client_stream, &mut audio_receiver),
+            41
+        );
+        assert!(video_receiver.recv(Duration::ZERO).is_ok());
+
+        // With separate sockets, the audio packet is available after its own shard, while the
+        // bursts are still queued on the stream socket
+        let mut audio_sender = server_audio.request_stream::<u32>(1);
+        let mut audio_receiver = client_audio.subscribe_to_stream::<u32>(1, 4);
+        for index in 1..4 {
+            send_payload(&mut video_sender, index, &video_payload);
+            send_payload(&mut audio_sender, index, &audio_payload);
+            assert_eq!(
+                recv_calls_until_audio(&mut client_audio, &mut audio_receiver),
+                1
+            );
+        }
+
+        // The video flows on its own socket
+        let deadline = Instant::now() + Duration::from_secs(1);
+        for index in 1..4 {
+            let data = loop {
+                client_stream.recv().ok();
+                if let Ok(data) = video_receiver.recv(Duration::ZERO) {
+                    break data;
+                }
+                assert!(Instant::now() < deadline, "Video packet not received");
+            };
+            assert!(!data.had_packet_loss());
+
+            let (header, payload) = data.get().unwrap();
+            assert_eq!(header, index);
+            assert_eq!(payload, video_payload.as_slice());
+        }
+    }
+
+    #[test]
+    fn test_audio_on_separate_socket_delays_the_paced_video() {
+        // 1 ms between the video shards
+        let pacing = PacingConfig {
+            bitrate_bps: 1404.0 * 8.0 * 1000.0,
+            frame_interval: Duration::from_secs(1),
+        };
+        let video_payload = vec![0; 27_000];
+        let audio_payload = vec![1; 960];
+
+        let ([server_stream, server_audio], _client_sockets) = connect_stream_and_audio_sockets();
+        let mut video_sender = server_stream.request_stream::<u32>(0);
+        let mut audio_sender = server_audio.request_stream::<u32>(1);
+        video_sender.set_pacing(Some(pacing));
+
+        let instant = Instant::now();
+        send_payload(&mut video_sender, 0, &video_payload);
+        let video_duration = instant.elapsed();
+        let interval = video_sender.pacing_interval().unwrap();
+        assert!(interval > Duration::from_micros(999) && interval < Duration::from_micros(1001));
+        assert!(video_duration >= interval * 19);
+
+        // The audio is sent right away, its bytes delay the next video shards instead. 30 shards
+        // of 980 bytes take 21 ms at the video bitrate
+        let instant = Instant::now();
+        for index in 0..30 {
+            send_payload(&mut audio_sender, index, &audio_payload);
+        }
+        assert!(instant.elapsed() < Duration::from_millis(10));
+        assert_eq!(audio_sender.pacing_interval(), None);
+
+        let instant = Instant::now();
+        send_payload(&mut video_sender, 1, &video_payload);
+        assert!(instant.elapsed() >= interval * 19 + Duration::from_millis(20));
+
+        // The budget was consumed by the previous packet
+        let instant = Instant::now();
+        send_payload(&mut video_sender, 2, &video_payload);
+        assert!(instant.elapsed() < video_duration + Duration::from_millis(20));
+    }
+}
@@ -22,10 +22,14 @@ fn main() {
             .is_some()
     });
 
+    // The composition of separate eye images is shared with the server
+    let eye_composition_path = server_cpp_dir.join("platform/linux/EyeComposition");
+
     let mut build = cc::Build::new();
     build
         .cpp(true)
         .files(source_files_paths)
+        .file(eye_composition_path.with_extension("cpp"))
         .flag("-std=c++17")
         .flag_if_supported("-Wno-unused-parameter")
         .define("VK_USE_PLATFORM_XLIB_XRANDR_EXT", None)
@@ -39,6 +43,7 @@ fn main() {
     bindgen::builder()
         .clang_arg("-xc++")
         .header("layer/layer.h")
+        .header(eye_composition_path.with_extension("h").to_string_lossy())
         .derive_default(true)
         .generate()
         .expect("layer bindings")
@@ -55,6 +60,12 @@ fn main() {
     for path in cpp_paths {
         println!("cargo:rerun-if-changed={}", path.to_string_lossy());
     }
+    for ext in ["h", "cpp"] {
+        println!(
+            "cargo:rerun-if-changed={}",
+            eye_composition_path.with_extension(ext).to_string_lossy()
+        );
+    }
 }
 
 #[cfg(not(target_os = "linux"))]
@@ -111,7 +111,7 @@ VKAPI_ATTR VkResult VKAPI_CALL wsi_layer_vkGetDisplayPlaneSupportedDisplaysKHR(
 }
 
 VKAPI_ATTR VkResult VKAPI_CALL wsi_layer_vkCreateDisplayPlaneSurfaceKHR(
-    VkInstance vkinstance, const VkDisplaySurfaceCreateInfoKHR * /*pCreateInfo*/,
+    VkInstance vkinstance, const VkDisplaySurfaceCreateInfoKHR *pCreateInfo,
     const VkAllocationCallbacks *pAllocator, VkSurfaceKHR *pSurface) {
     auto &instance = layer::instance_private_data::get(vkinstance);
     VkHeadlessSurfaceCreateInfoEXT createInfo = {};
@@ -120,7 +120,15 @@ VKAPI_ATTR VkResult VKAPI_CALL wsi_layer_vkCreateDisplayPlaneSurfaceKHR(
         instance.disp.CreateHeadlessSurfaceEXT(vkinstance, &createInfo, pAllocator, pSurface);
     if (*pSurface == NULL)
         std::abort();
-    instance.add_surface(*pSurface);
+
+    // Compositors that render each eye to its own swapchain create a surface of the size of one
+    // eye for each of them. Other surfaces have the size of the display
+    VkExtent2D extent = {Settings::Instance().m_renderWidth, Settings::Instance().m_renderHeight};
+    if (pCreateInfo->imageExtent.width == extent.width / 2 &&
+        pCreateInfo->imageExtent.height == extent.height) {
+        extent = pCreateInfo->imageExtent;
+    }
+    instance.add_surface(*pSurface, extent);
     return res;
 }
 
@@ -126,9 +126,14 @@ void instance_private_data::destroy(VkInstance inst) {
     g_instance_data.erase(get_key(inst));
 }
 
-void instance_private_data::add_surface(VkSurfaceKHR surface) {
+void instance_private_data::add_surface(VkSurfaceKHR surface, VkExtent2D image_extent) {
     scoped_mutex lock(g_data_lock);
-    surfaces.insert(surface);
+    surfaces[surface] = image_extent;
+}
+
+VkExtent2D instance_private_data::get_surface_extent(VkSurfaceKHR surface) {
+    scoped_mutex lock(g_data_lock);
+    return surfaces.at(surface);
 }
 
 device_private_data::device_private_data(instance_private_data &inst_data,
@@ -34,6 +34,7 @@
 #include <cassert>
 #include <memory>
 #include <mutex>
+#include <unordered_map>
 #include <unordered_set>
 
 using scoped_mutex = std::lock_guard<std::mutex>;
@@ -183,7 +184,15 @@ class instance_private_data {
      */
     bool does_layer_support_surface(VkSurfaceKHR surface);
 
-    void add_surface(VkSurfaceKHR);
+    /**
+     * @brief Register a surface handled by the layer, with the size of its images.
+     */
+    void add_surface(VkSurfaceKHR surface, VkExtent2D image_extent);
+
+    /**
+     * @brief Get the size of the images of a surface handled by the layer.
+     */
+    VkExtent2D get_surface_extent(VkSurfaceKHR surface);
 
     static void destroy(VkInstance inst);
 
@@ -199,7 +208,7 @@ class instance_private_data {
     const PFN_vkSetInstanceLoaderData SetInstanceLoaderData;
     const util::wsi_platform_set enabled_layer_platforms;
 
-    std::unordered_set<VkSurfaceKHR> surfaces;
+    std::unordered_map<VkSurfaceKHR, VkExtent2D> surfaces;
 };
 
 class device_private_data {
@@ -28,3 +28,71 @@ pub unsafe extern "C" fn ALVR_Negotiate(nli: *mut VkNegotiateLayerInterface) ->
 
     bindings::wsi_layer_Negotiate(nli)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::bindings::*;
+
+    const IMAGES_PER_EYE: u32 = 3;
+    const EYE_SIZE: VkExtent3D = VkExtent3D {
+        width: 4,
+        height: 2,
+        depth: 1,
+    };
+
+    // Each pixel is tagged with the eye, the image and its position
+    fn eye_image(eye: u32, image: u32) -> Vec<u32> {
+        (0..EYE_SIZE.width * EYE_SIZE.height)
+            .map(|pixel| eye << 16 | image << 8 | pixel)
+            .collect()
+    }
+
+    // As done by vkCmdCopyImage, for 2D images
+    fn copy_image(
+        src: &[u32],
+        src_width: u32,
+        dst: &mut [u32],
+        dst_width: u32,
+        copy: &VkImageCopy,
+    ) {
+        for y in 0..copy.extent.height as i32 {
+            for x in 0..copy.extent.width as i32 {
+                let src_index = (copy.srcOffset.y + y) * src_width as i32 + copy.srcOffset.x + x;
+                let dst_index = (copy.dstOffset.y + y) * dst_width as i32 + copy.dstOffset.x + x;
+                dst[dst_index as usize] = src[src_index as usize];
+            }
+        }
+    }
+
+    #[test]
+    fn test_eye_images_are_composed_side_by_side() {
+        // The image sets of the two eyes, in the order they are received by the server
+        let images = (0..2)
+            .flat_map(|eye| (0..IMAGES_PER_EYE).map(move |image| eye_image(eye, image)))
+            .collect::<Vec<_>>();
+
+        // The eyes present different images
+        let mut copies = [EyeCopy::default(); 2];
+        unsafe { ComposeEyes(IMAGES_PER_EYE, EYE_SIZE, 2, 0, copies.as_mut_ptr()) };
+
+        let frame_width = 2 * EYE_SIZE.width;
+        let mut frame = vec![0; (frame_width * EYE_SIZE.height) as usize];
+        for copy in &copies {
+            copy_image(
+                &images[copy.image as usize],
+                EYE_SIZE.width,
+                &mut frame,
+                frame_width,
+                &copy.region,
+            );
+        }
+
+        let left = eye_image(0, 2);
+        let right = eye_image(1, 0);
+        let width = EYE_SIZE.width as usize;
+        for (y, row) in frame.chunks(2 * width).enumerate() {
+            assert_eq!(row[..width], left[y * width..(y + 1) * width]);
+            assert_eq!(row[width..], right[y * width..(y + 1) * width]);
+        }
+    }
+}
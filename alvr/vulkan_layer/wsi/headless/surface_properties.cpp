@@ -33,7 +33,6 @@
 #include <vulkan/vulkan.h>
 
 #include <layer/private_data.hpp>
-#include "layer/settings.h"
 
 #include "surface_properties.hpp"
 
@@ -50,16 +49,15 @@ surface_properties &surface_properties::get_instance() {
 VkResult
 surface_properties::get_surface_capabilities(VkPhysicalDevice physical_device, VkSurfaceKHR surface,
                                              VkSurfaceCapabilitiesKHR *surface_capabilities) {
-    UNUSED(surface);
     /* Image count limits */
     surface_capabilities->minImageCount = 1;
     /* There is no maximum theoretically speaking */
     surface_capabilities->maxImageCount = UINT32_MAX;
 
-    /* Surface extents */
+    /* Surface extents, of the display or of one eye */
     surface_capabilities->currentExtent = surface_capabilities->maxImageExtent =
-        surface_capabilities->minImageExtent = {Settings::Instance().m_renderWidth,
-                                                Settings::Instance().m_renderHeight};
+        surface_capabilities->minImageExtent =
+            layer::instance_private_data::get(physical_device).get_surface_extent(surface);
     /* Ask the device for max */
     VkPhysicalDeviceProperties dev_props;
     layer::instance_private_data::get(physical_device)
@@ -40,9 +40,11 @@
 #include <unistd.h>
 #include <vulkan/vulkan.h>
 #include <sys/mman.h>
+#include <mutex>
 
 #include <util/timed_semaphore.hpp>
 
+#include "layer/settings.h"
 #include "util/logger.h"
 #include "platform/linux/protocol.h"
 #include "swapchain.hpp"
@@ -56,15 +58,57 @@ struct image_data {
     VkDeviceMemory memory;
 };
 
+namespace {
+// Some compositors render each eye to its own swapchain, created on a surface of the size of one
+// eye. These swapchains are paired, the first one created being the left eye. The left eye
+// swapchain connects with the images of both eyes, and a present is sent once both eyes have
+// submitted an image.
+struct eye_pair {
+    std::mutex mutex;
+    swapchain *eyes[2] = {nullptr, nullptr};
+    bool submitted[2] = {false, false};
+    uint32_t images[2] = {0, 0};
+};
+eye_pair g_eye_pair;
+} // namespace
+
 swapchain::swapchain(layer::device_private_data &dev_data, const VkAllocationCallbacks *pAllocator)
     : wsi::swapchain_base(dev_data, pAllocator), m_display(*dev_data.display) {}
 
 swapchain::~swapchain() {
+    if (m_eye != -1) {
+        std::lock_guard<std::mutex> lock(g_eye_pair.mutex);
+        g_eye_pair.eyes[m_eye] = nullptr;
+        g_eye_pair.submitted[m_eye] = false;
+    }
+
     /* Call the base's teardown */
     close(m_socket);
     teardown();
 }
 
+VkResult swapchain::init_platform(VkDevice device,
+                                  const VkSwapchainCreateInfoKHR *pSwapchainCreateInfo) {
+    VkExtent2D surface_extent =
+        m_device_data.instance_data.get_surface_extent(pSwapchainCreateInfo->surface);
+    if (surface_extent.width == Settings::Instance().m_renderWidth) {
+        return VK_SUCCESS;
+    }
+
+    std::lock_guard<std::mutex> lock(g_eye_pair.mutex);
+    for (int eye = 0; eye < 2; ++eye) {
+        if (g_eye_pair.eyes[eye] == nullptr) {
+            g_eye_pair.eyes[eye] = this;
+            g_eye_pair.submitted[eye] = false;
+            m_eye = eye;
+            return VK_SUCCESS;
+        }
+    }
+
+    Error("Only one swapchain per eye is supported\n");
+    return VK_ERROR_INITIALIZATION_FAILED;
+}
+
 VkResult swapchain::create_image(const VkImageCreateInfo &image_create,
                                  wsi::swapchain_image &image) {
     VkResult res = VK_SUCCESS;
@@ -197,27 +241,25 @@ VkResult swapchain::create_image(const VkImageCreateInfo &image_create,
     return res;
 }
 
-int swapchain::send_fds() {
+int swapchain::send_fds(const std::vector<int> &fds) {
     // This function does the arcane magic for sending
     // file descriptors over unix domain sockets
     // Stolen from https://gist.github.com/kokjo/75cec0f466fc34fa2922
     //
-    // There will always be 6 fds (for the 3 images and sempahores created in the swapchain) so we can avoid
-    // dynamic length. Initially, I tried to send the length in the normal data field (msg.msg_iov /
-    // data) but for some reason it was emptied on arrival, no matter what I did.
+    // The number of fds (for the images and sempahores created in the swapchains) is known by the
+    // receiver from the init packet. Initially, I tried to send the length in the normal data field
+    // (msg.msg_iov / data) but for some reason it was emptied on arrival, no matter what I did.
     //
     struct msghdr msg;
     struct iovec iov[1];
     struct cmsghdr *cmsg = NULL;
-    assert(m_fds.size() == 6);
-    int fds[6];
-    char ctrl_buf[CMSG_SPACE(sizeof(fds))];
+    assert(fds.size() <= MAX_INPUT_FDS);
+    size_t fds_size = fds.size() * sizeof(int);
+    char ctrl_buf[CMSG_SPACE(MAX_INPUT_FDS * sizeof(int))];
     char data[1];
 
-    std::copy(m_fds.begin(), m_fds.end(), fds);
-
     memset(&msg, 0, sizeof(struct msghdr));
-    memset(ctrl_buf, 0, CMSG_SPACE(sizeof(fds)));
+    memset(ctrl_buf, 0, sizeof(ctrl_buf));
 
     iov[0].iov_base = data;
     iov[0].iov_len = sizeof(data);
@@ -226,19 +268,19 @@ int swapchain::send_fds() {
     msg.msg_namelen = 0;
     msg.msg_iov = iov;
     msg.msg_iovlen = 1;
-    msg.msg_controllen = CMSG_SPACE(sizeof(fds));
+    msg.msg_controllen = CMSG_SPACE(fds_size);
     msg.msg_control = ctrl_buf;
 
     cmsg = CMSG_FIRSTHDR(&msg);
     cmsg->cmsg_level = SOL_SOCKET;
     cmsg->cmsg_type = SCM_RIGHTS;
-    cmsg->cmsg_len = CMSG_LEN(sizeof(fds));
+    cmsg->cmsg_len = CMSG_LEN(fds_size);
 
-    memcpy(CMSG_DATA(cmsg), fds, sizeof(fds));
+    memcpy(CMSG_DATA(cmsg), fds.data(), fds_size);
 
     int ret = sendmsg(m_socket, &msg, 0);
 
-    for (auto fd: m_fds)
+    for (auto fd: fds)
       close(fd);
 
     return ret;
@@ -246,6 +288,28 @@ int swapchain::send_fds() {
 
 bool swapchain::try_connect() {
     Debug("swapchain::try_connect\n");
+
+    input_layout layout = input_layout::combined;
+    std::vector<int> fds = m_fds;
+    swapchain *right_eye = nullptr;
+    if (m_eye != -1) {
+        // Called for the left eye, with the eye pair lock held
+        right_eye = g_eye_pair.eyes[1];
+        if (right_eye == nullptr) {
+            return false;
+        }
+        if (right_eye->m_create_info.extent.width != m_create_info.extent.width ||
+            right_eye->m_create_info.extent.height != m_create_info.extent.height ||
+            right_eye->m_create_info.format != m_create_info.format ||
+            right_eye->m_mem_index != m_mem_index ||
+            right_eye->m_swapchain_images.size() != m_swapchain_images.size()) {
+            Error("The swapchains of the two eyes don't match\n");
+            return false;
+        }
+
+        layout = input_layout::separate_eyes;
+        fds.insert(fds.end(), right_eye->m_fds.begin(), right_eye->m_fds.end());
+    }
     m_socketPath = getenv("XDG_RUNTIME_DIR");
     m_socketPath += "/alvr-ipc";
 
@@ -277,7 +341,8 @@ bool swapchain::try_connect() {
     m_device_data.instance_data.disp.GetPhysicalDeviceProperties2(m_device_data.physical_device,
                                                                   &props);
 
-    init_packet init{.num_images = uint32_t(m_swapchain_images.size()),
+    init_packet init{.layout = layout,
+      .num_images = uint32_t(m_swapchain_images.size()),
       .device_uuid = {},
       .image_create_info = m_create_info,
       .mem_index = m_mem_index,
@@ -289,32 +354,76 @@ bool swapchain::try_connect() {
         exit(1);
     }
 
-    ret = send_fds();
+    ret = send_fds(fds);
     if (ret == -1) {
         perror("sendmsg");
         exit(1);
     }
+    m_fds.clear();
+    if (right_eye != nullptr) {
+        right_eye->m_fds.clear();
+    }
     Debug("swapchain sent fds\n");
 
     return true;
 }
 
+void swapchain::send_present(const present_packet &packet) {
+    int ret = write(m_socket, &packet, sizeof(packet));
+    if (ret == -1) {
+        //FIXME: try to reconnect?
+    }
+}
+
 void swapchain::submit_image(uint32_t pending_index) {
+    if (m_eye != -1) {
+        submit_eye_image(pending_index);
+        return;
+    }
+
     const auto & pose = m_swapchain_images[pending_index].pose.mDeviceToAbsoluteTracking.m;
     if (!m_connected) {
         m_connected = try_connect();
     }
     if (m_connected) {
-        int ret;
-        present_packet packet;
+        present_packet packet = {};
         packet.image = pending_index;
         packet.frame = m_display.m_vsync_count;
         packet.semaphore_value = m_swapchain_images[pending_index].semaphore_value;
         memcpy(&packet.pose, pose, sizeof(packet.pose));
-        ret = write(m_socket, &packet, sizeof(packet));
-        if (ret == -1) {
-            //FIXME: try to reconnect?
-        }
+        send_present(packet);
+    }
+}
+
+void swapchain::submit_eye_image(uint32_t pending_index) {
+    std::lock_guard<std::mutex> lock(g_eye_pair.mutex);
+    g_eye_pair.submitted[m_eye] = true;
+    g_eye_pair.images[m_eye] = pending_index;
+
+    swapchain *left_eye = g_eye_pair.eyes[0];
+    swapchain *right_eye = g_eye_pair.eyes[1];
+    if (left_eye == nullptr || right_eye == nullptr || !g_eye_pair.submitted[0] ||
+        !g_eye_pair.submitted[1]) {
+        return;
+    }
+    g_eye_pair.submitted[0] = false;
+    g_eye_pair.submitted[1] = false;
+
+    if (!left_eye->m_connected) {
+        left_eye->m_connected = left_eye->try_connect();
+    }
+    if (left_eye->m_connected) {
+        auto &left_image = left_eye->m_swapchain_images[g_eye_pair.images[0]];
+        auto &right_image = right_eye->m_swapchain_images[g_eye_pair.images[1]];
+
+        present_packet packet = {};
+        packet.image = g_eye_pair.images[0];
+        packet.frame = m_display.m_vsync_count;
+        packet.semaphore_value = left_image.semaphore_value;
+        memcpy(&packet.pose, left_image.pose.mDeviceToAbsoluteTracking.m, sizeof(packet.pose));
+        packet.right_image = g_eye_pair.images[1];
+        packet.right_semaphore_value = right_image.semaphore_value;
+        left_eye->send_present(packet);
     }
 }
 
@@ -58,9 +58,7 @@ class swapchain : public wsi::swapchain_base {
     /**
      * @brief Platform specific init
      */
-    VkResult init_platform(VkDevice device, const VkSwapchainCreateInfoKHR *pSwapchainCreateInfo) {
-        return VK_SUCCESS;
-    };
+    VkResult init_platform(VkDevice device, const VkSwapchainCreateInfoKHR *pSwapchainCreateInfo);
 
     /**
      * @brief Creates a new swapchain image.
@@ -94,7 +92,9 @@ class swapchain : public wsi::swapchain_base {
 
   private:
     bool try_connect();
-    int send_fds();
+    int send_fds(const std::vector<int> &fds);
+    void send_present(const present_packet &packet);
+    void submit_eye_image(uint32_t pending_index);
     int m_socket = -1;
     std::string m_socketPath;
     bool m_connected = false;
@@ -103,6 +103,8 @@ class swapchain : public wsi::swapchain_base {
     size_t m_mem_index;
     display &m_display;
     uint32_t in_flight_index = UINT32_MAX;
+    // Index of the eye for swapchains of the size of one eye, -1 for both eyes
+    int m_eye = -1;
 };
 
 } /* namespace headless */